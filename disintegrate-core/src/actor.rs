@@ -0,0 +1,178 @@
+//! The actor on behalf of whom the events are appended.
+//!
+//! The actor is taken from the execution context: the event stores stamp the events appended within a
+//! future wrapped by `WithActor::with_actor` with its actor, so the business logic does not carry it.
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "std")]
+thread_local! {
+    static CURRENT_ACTOR: RefCell<Option<Actor>> = const { RefCell::new(None) };
+}
+
+/// The kind of an actor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActorKind {
+    /// A human user.
+    User,
+    /// A service acting on its own behalf, such as a scheduled job.
+    ServiceAccount,
+    /// A client authenticated by an API key, identified by the ID of the key.
+    ApiKey,
+}
+
+impl ActorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ActorKind::User => "user",
+            ActorKind::ServiceAccount => "service_account",
+            ActorKind::ApiKey => "api_key",
+        }
+    }
+}
+
+/// Who appended an event.
+///
+/// It is displayed, and parsed back, as `kind:id`, e.g. `user:alice` or `api_key:k-42`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Actor {
+    kind: ActorKind,
+    id: String,
+}
+
+impl Actor {
+    /// Creates an actor of the given kind.
+    pub fn new(kind: ActorKind, id: impl Into<String>) -> Self {
+        Self {
+            kind,
+            id: id.into(),
+        }
+    }
+
+    /// Creates a user actor.
+    pub fn user(id: impl Into<String>) -> Self {
+        Self::new(ActorKind::User, id)
+    }
+
+    /// Creates a service account actor.
+    pub fn service_account(id: impl Into<String>) -> Self {
+        Self::new(ActorKind::ServiceAccount, id)
+    }
+
+    /// Creates an actor authenticated by the API key with the given ID.
+    pub fn api_key(id: impl Into<String>) -> Self {
+        Self::new(ActorKind::ApiKey, id)
+    }
+
+    /// Returns the kind of the actor.
+    pub fn kind(&self) -> ActorKind {
+        self.kind
+    }
+
+    /// Returns the ID of the actor.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the actor of the current execution context, if any.
+    #[cfg(feature = "std")]
+    pub fn current() -> Option<Actor> {
+        CURRENT_ACTOR.with(|actor| actor.borrow().clone())
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.as_str(), self.id)
+    }
+}
+
+/// The error returned when an actor cannot be parsed.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid actor {0}, expected kind:id")]
+pub struct ParseActorError(String);
+
+impl FromStr for Actor {
+    type Err = ParseActorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, id) = s
+            .split_once(':')
+            .ok_or_else(|| ParseActorError(s.to_string()))?;
+        let kind = match kind {
+            "user" => ActorKind::User,
+            "service_account" => ActorKind::ServiceAccount,
+            "api_key" => ActorKind::ApiKey,
+            _ => return Err(ParseActorError(s.to_string())),
+        };
+        Ok(Self::new(kind, id))
+    }
+}
+
+/// Runs a future with an actor in its execution context.
+#[cfg(feature = "std")]
+pub trait WithActor: Future + Sized {
+    /// Sets the actor of the execution context while the future is polled.
+    fn with_actor(self, actor: Actor) -> ActorScoped<Self> {
+        ActorScoped {
+            future: Box::pin(self),
+            actor: Some(actor),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Future> WithActor for F {}
+
+/// A future with an actor in its execution context, see `WithActor::with_actor`.
+#[cfg(feature = "std")]
+pub struct ActorScoped<F> {
+    future: Pin<Box<F>>,
+    actor: Option<Actor>,
+}
+
+#[cfg(feature = "std")]
+impl<F: Future> Future for ActorScoped<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let previous = CURRENT_ACTOR.with(|current| current.replace(this.actor.take()));
+        let poll = this.future.as_mut().poll(cx);
+        this.actor = CURRENT_ACTOR.with(|current| current.replace(previous));
+        poll
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn it_sets_the_actor_of_the_execution_context() {
+        let actor = Actor::api_key("k-42");
+
+        let current = async { Actor::current() }.with_actor(actor.clone()).await;
+
+        assert_eq!(current, Some(actor));
+        assert_eq!(Actor::current(), None);
+    }
+
+    #[test]
+    fn it_parses_the_displayed_actor() {
+        let actor = Actor::service_account("billing:nightly");
+
+        assert_eq!(actor.to_string(), "service_account:billing:nightly");
+        assert_eq!(actor.to_string().parse(), Ok(actor));
+        assert!("alice".parse::<Actor>().is_err());
+    }
+}
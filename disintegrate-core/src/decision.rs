@@ -0,0 +1,49 @@
+//! A Decision serves as a building block for developing the business logic of an application.
+use alloc::vec::Vec;
+
+use crate::{Event, EventId, StreamQuery};
+
+/// Represents a business decision taken from a state built upon the occurred events.
+pub trait Decision: Send + Sync {
+    type Event: Event + Clone + Send + Sync;
+    type StateQuery: Clone + Send + Sync;
+    type Error: Send + Sync;
+
+    /// Returns the state query to compute the decision state from the events in the event store.
+    ///
+    /// If there are no events that match the specified query, the default values of the state query is utilized to make the decision.
+    fn state_query(&self) -> Self::StateQuery;
+
+    /// Returns the stream query used to validate the decision.
+    ///
+    /// If the validation query is `None`, the state query will be used for validation.
+    /// This means that the decision will only be confirmed if new events that would make the state query outdated are not found.
+    /// However, if a `validation_query` is provided, it will be used to confirm the decision.
+    /// This allows narrowing down the set of events that could invalidate the decision.
+    ///
+    /// For example, in a banking system, deposit events should not invalidate withdrawals if the account balance is already sufficient.
+    /// In this case, the state query needs to include both withdraw and deposit events to compute the available balance,
+    /// but only withdraw events should invalidate the decision.
+    /// In other words, once we have confirmed that the account has a sufficient amount,
+    /// only a withdraw event can reduce the balance below the requested amount and invalidate the decision.
+    fn validation_query<ID: EventId>(&self) -> Option<StreamQuery<ID, Self::Event>> {
+        None
+    }
+
+    /// Evaluates the decision based on the mutated state, ensuring that all business rules
+    /// are verified against the current state. This method generates a series of events
+    /// that capture the changes made by the decision, allowing the results to be
+    /// persisted in the event store.
+    ///
+    /// # Parameters
+    ///
+    /// - `state`: A reference to the current state of the system, obtained through
+    ///   the implementation of the `StateQuery` trait.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success of the process. If successful, it contains
+    /// a vector of events representing the changes made. In case of an error, it
+    /// contains details about the encountered issue.
+    fn process(&self, state: &Self::StateQuery) -> Result<Vec<Self::Event>, Self::Error>;
+}
@@ -7,7 +7,7 @@
 //! Creating a `DomainIdentifierSet` with two domain identifiers:
 //!
 //! ```
-//! use disintegrate::{DomainIdentifier, DomainIdentifierSet, Identifier, domain_identifiers, IntoIdentifierValue};
+//! use disintegrate_core::{DomainIdentifier, DomainIdentifierSet, Identifier, domain_identifiers, IntoIdentifierValue};
 //!
 //! // Create domain identifiers
 //! let identifier1 = Identifier::new("id1").unwrap();
@@ -36,7 +36,8 @@
 //! }
 //! ```
 use crate::{Identifier, IdentifierValue};
-use std::{collections::BTreeMap, ops::Deref};
+use alloc::collections::BTreeMap;
+use core::ops::Deref;
 
 /// Represents a key-value pair of domain identifiers.
 ///
@@ -83,8 +84,11 @@ macro_rules! domain_identifiers{
     };
     {$($key:ident: $value:expr),*} => {{
         #[allow(unused_mut)]
-        let mut domain_identifiers = std::collections::BTreeMap::<$crate::Identifier, $crate::IdentifierValue>::new();
-        $(domain_identifiers.insert($crate::ident!(#$key), $crate::IntoIdentifierValue::into_identifier_value($value.clone()));)*
-        $crate::DomainIdentifierSet::new(domain_identifiers)
+        let mut domain_identifiers = $crate::DomainIdentifierSet::default();
+        $(domain_identifiers.insert($crate::DomainIdentifier {
+            key: $crate::ident!(#$key),
+            value: $crate::IntoIdentifierValue::into_identifier_value($value.clone()),
+        });)*
+        domain_identifiers
     }};
 }
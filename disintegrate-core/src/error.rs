@@ -0,0 +1,171 @@
+//! Classification of the errors, so callers can react to them without matching their messages.
+//!
+//! Every error of the library has an `ErrorKind`, returned by the `Classify` trait. The errors of the event
+//! stores are type-erased by the state stores, so they are wrapped in a `ClassifiedError` that keeps their
+//! kind, which can be retrieved from a boxed error with `error_kind`.
+use alloc::boxed::Box;
+use core::error::Error as StdError;
+use core::fmt;
+
+use crate::BoxDynError;
+
+/// The kind of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// A concurrent change invalidated the operation, e.g. a decision made on a stale state.
+    Conflict,
+    /// A temporary failure, such as a timeout or a dropped connection.
+    Transient,
+    /// The stored data is malformed or was tampered with.
+    Corruption,
+    /// The operation was rejected, e.g. by the domain or because of an invalid configuration.
+    Validation,
+    /// A failure of the storage or of the network that is not expected to resolve by itself.
+    Io,
+}
+
+impl ErrorKind {
+    /// Returns `true` if the failed operation can be retried as is.
+    ///
+    /// The conflicts are retryable as well: a decision retried on the current state may succeed.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorKind::Conflict | ErrorKind::Transient)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::Transient => "transient",
+            ErrorKind::Corruption => "corruption",
+            ErrorKind::Validation => "validation",
+            ErrorKind::Io => "io",
+        };
+        f.write_str(kind)
+    }
+}
+
+/// Classifies an error by its `ErrorKind`.
+pub trait Classify {
+    /// Returns the kind of the error.
+    fn kind(&self) -> ErrorKind;
+
+    /// Returns `true` if the failed operation can be retried as is.
+    fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+/// An error that keeps its `ErrorKind` once boxed.
+///
+/// It displays as the wrapped error.
+#[derive(Debug)]
+pub struct ClassifiedError {
+    kind: ErrorKind,
+    source: BoxDynError,
+}
+
+impl ClassifiedError {
+    /// Creates a new `ClassifiedError` of the given kind.
+    pub fn new(kind: ErrorKind, source: impl Into<BoxDynError>) -> Self {
+        Self {
+            kind,
+            source: source.into(),
+        }
+    }
+
+    /// Boxes the error along with its kind.
+    pub fn boxed<E>(err: E) -> BoxDynError
+    where
+        E: StdError + Classify + Send + Sync + 'static,
+    {
+        Box::new(Self::new(err.kind(), err))
+    }
+
+    /// Returns the wrapped error.
+    pub fn into_inner(self) -> BoxDynError {
+        self.source
+    }
+}
+
+impl fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl StdError for ClassifiedError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.source()
+    }
+}
+
+impl Classify for ClassifiedError {
+    fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+/// Returns the kind of a type-erased error, looking for a classified error along its sources.
+///
+/// Returns `None` if the error was not classified.
+pub fn error_kind(err: &(dyn StdError + 'static)) -> Option<ErrorKind> {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(err) = err.downcast_ref::<ClassifiedError>() {
+            return Some(err.kind());
+        }
+        #[cfg(feature = "std")]
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return Some(Classify::kind(err));
+        }
+        current = err.source();
+    }
+    None
+}
+
+#[cfg(feature = "std")]
+impl Classify for std::io::Error {
+    fn kind(&self) -> ErrorKind {
+        use std::io::ErrorKind as IoErrorKind;
+        match std::io::Error::kind(self) {
+            IoErrorKind::TimedOut
+            | IoErrorKind::Interrupted
+            | IoErrorKind::WouldBlock
+            | IoErrorKind::ConnectionRefused
+            | IoErrorKind::ConnectionReset
+            | IoErrorKind::ConnectionAborted
+            | IoErrorKind::BrokenPipe => ErrorKind::Transient,
+            IoErrorKind::InvalidData | IoErrorKind::UnexpectedEof => ErrorKind::Corruption,
+            IoErrorKind::InvalidInput | IoErrorKind::PermissionDenied => ErrorKind::Validation,
+            _ => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Classify for disintegrate_serde::Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Corruption
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_kind_of_the_boxed_errors() {
+        let err = ClassifiedError::boxed(std::io::Error::from(std::io::ErrorKind::TimedOut));
+
+        assert_eq!(error_kind(err.as_ref()), Some(ErrorKind::Transient));
+        assert_eq!(err.to_string(), "timed out");
+        assert_eq!(
+            error_kind(&std::fmt::Error as &(dyn StdError + 'static)),
+            None
+        );
+        assert!(ErrorKind::Conflict.is_retryable());
+        assert!(!ErrorKind::Corruption.is_retryable());
+    }
+}
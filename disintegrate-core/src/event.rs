@@ -0,0 +1,370 @@
+//! Event represents an occurrence or action of interest within the system.
+//!
+//! This module defines the Event trait, which provides methods for retrieving domain identifiers associated
+//! with the event and getting the event's name.
+//!
+//! The PersistedEvent struct wraps an event and contains an ID assigned by the event store. It represents
+//! an event that has been persisted in the event store.
+use crate::{
+    domain_identifier::DomainIdentifierSet, Actor, Classify, ErrorKind, Identifier, IdentifierType,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+/// Represents the ID of an event.
+pub trait EventId:
+    Default + Copy + Clone + PartialEq + Eq + Ord + PartialOrd + Send + Sync + 'static
+{
+}
+
+impl<Id> EventId for Id where
+    Id: Default + Copy + Clone + PartialEq + Eq + Ord + PartialOrd + Send + Sync + 'static
+{
+}
+
+/// Represents the schema of an event.
+///
+/// The event info contains the name of the event and the domain identifiers associated with it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EventInfo {
+    /// The name of the event.
+    pub name: &'static str,
+    /// The domain identifiers associated with the event.
+    pub domain_identifiers: &'static [&'static Identifier],
+}
+
+impl EventInfo {
+    /// Returns true if the event has the given domain identifier.
+    pub fn has_domain_identifier(&self, ident: &Identifier) -> bool {
+        self.domain_identifiers.contains(&ident)
+    }
+}
+
+/// Represents the domain identifier and its type.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DomainIdentifierInfo {
+    /// The domain identifier.
+    pub ident: Identifier,
+    /// The type of the domain identifier.
+    pub type_info: IdentifierType,
+}
+
+/// Describes an event type at runtime: its name, its domain identifiers and the fingerprint of its payload.
+///
+/// The descriptors of an event are returned by `Event::descriptors`, for tooling that inspects or validates
+/// the events of an application.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EventDescriptor {
+    /// The name of the event.
+    pub name: &'static str,
+    /// The domain identifiers of the event, with their types.
+    pub domain_identifiers: Vec<DomainIdentifierInfo>,
+    /// The fingerprint of the shape of the payload, or 0 when it is unknown.
+    pub fingerprint: u64,
+}
+
+/// Represents the schema of all supported events.
+///
+/// The schema contains the names of all supported events,
+/// the domain identifiers associated with them, and the domain identifiers' types.
+#[derive(Debug, Clone)]
+pub struct EventSchema {
+    pub events: &'static [&'static str],
+    pub events_info: &'static [&'static EventInfo],
+    pub domain_identifiers: &'static [&'static DomainIdentifierInfo],
+}
+
+impl EventSchema {
+    pub fn event_info(&self, name: &str) -> Option<&EventInfo> {
+        self.events_info
+            .iter()
+            .find(|info| info.name == name)
+            .copied()
+    }
+}
+
+/// Represents an event in the event store.
+///
+/// An event is an occurrence or action of interest within the system. It can be persisted and retrieved from
+/// the event store. The `Event` trait provides methods for retrieving domain identifiers associated with the event
+/// and getting the event's name. The constant `SCHEMA` holds the name and the domain identifiers of all supported events.
+pub trait Event {
+    /// Returns the schema of all supported events.
+    const SCHEMA: EventSchema;
+    /// Retrieves the domain identifiers associated with the event.
+    fn domain_identifiers(&self) -> DomainIdentifierSet;
+    /// Retrieves the name of the event.
+    fn name(&self) -> &'static str;
+    /// Retrieves the payload fields of the event that stream queries can filter on.
+    ///
+    /// The `Event` derive returns the fields marked with the `#[filter]` attribute.
+    fn payload_fields(&self) -> DomainIdentifierSet {
+        DomainIdentifierSet::default()
+    }
+    /// The fingerprints of the shapes of the payloads, in the order of `SCHEMA.events_info`.
+    ///
+    /// The `Event` derive computes them from the fields of the events, so that a change of the payload
+    /// changes its fingerprint.
+    const FINGERPRINTS: &'static [u64] = &[];
+
+    /// Returns the payload fields of the event named `event_type` that hold personal data.
+    ///
+    /// The `Event` derive returns the fields marked with the `#[pii]` attribute. The fields are masked by
+    /// a `Redactor` wherever the payloads leave the event store.
+    fn pii_fields(_event_type: &str) -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Returns the descriptors of all supported events.
+    fn descriptors() -> Vec<EventDescriptor>
+    where
+        Self: Sized,
+    {
+        Self::SCHEMA
+            .events_info
+            .iter()
+            .enumerate()
+            .map(|(index, info)| EventDescriptor {
+                name: info.name,
+                domain_identifiers: info
+                    .domain_identifiers
+                    .iter()
+                    .filter_map(|ident| {
+                        Self::SCHEMA
+                            .domain_identifiers
+                            .iter()
+                            .find(|identifier| identifier.ident == **ident)
+                            .map(|identifier| **identifier)
+                    })
+                    .collect(),
+                fingerprint: Self::FINGERPRINTS.get(index).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Returns the descriptor of the event with the given name.
+    fn descriptor(name: &str) -> Option<EventDescriptor>
+    where
+        Self: Sized,
+    {
+        Self::descriptors()
+            .into_iter()
+            .find(|descriptor| descriptor.name == name)
+    }
+}
+
+/// The error returned when an event of a union does not belong to the event type it is projected to.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("the event `{0}` does not belong to the projected event type")]
+pub struct ProjectionError(pub &'static str);
+
+impl Classify for ProjectionError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Validation
+    }
+}
+
+/// Composes several event types into a single one.
+///
+/// Each variant wraps an event type, usually the events of a bounded context. The macro implements `Event`
+/// for the union, whose schema is the union of the schemas of its members, `From` for every member, and
+/// `TryFrom` to project an event of the union back down to its member. The members can then be used as the
+/// events of state queries and decisions on an event store of the union. The attributes are applied to the
+/// enum, and the names of the events must be unique across the members.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// disintegrate::event_union! {
+///     #[derive(Debug, Clone, Serialize, Deserialize)]
+///     pub enum ShopEvent {
+///         Cart(CartEvent),
+///         Coupon(CouponEvent),
+///     }
+/// }
+///
+/// let event: ShopEvent = CartEvent::ItemAdded { cart_id, item_id }.into();
+/// let cart_event = CartEvent::try_from(event)?;
+/// ```
+#[macro_export]
+macro_rules! event_union {
+    (@concat $item:ty; $acc:expr; $field:ident;) => {
+        $acc
+    };
+    (@concat $item:ty; $acc:expr; $field:ident; $head:ty $(, $tail:ty)*) => {
+        $crate::event_union!(
+            @concat $item;
+            $crate::const_slices_concat!($item, $acc, <$head as $crate::Event>::SCHEMA.$field);
+            $field;
+            $($tail),*
+        )
+    };
+    (@fingerprints $acc:expr;) => {
+        $acc
+    };
+    (@fingerprints $acc:expr; $head:ty $(, $tail:ty)*) => {
+        $crate::event_union!(
+            @fingerprints
+            $crate::const_slices_concat!(u64, $acc, <$head as $crate::Event>::FINGERPRINTS);
+            $($tail),*
+        )
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident($ty:ty)),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant($ty),)+
+        }
+
+        #[automatically_derived]
+        impl $crate::Event for $name {
+            const SCHEMA: $crate::EventSchema = $crate::EventSchema {
+                events: $crate::event_union!(@concat &str; &[]; events; $($ty),+),
+                events_info: $crate::event_union!(@concat &$crate::EventInfo; &[]; events_info; $($ty),+),
+                domain_identifiers: $crate::const_slice_unique!(
+                    &$crate::DomainIdentifierInfo,
+                    $crate::event_union!(@concat &$crate::DomainIdentifierInfo; &[]; domain_identifiers; $($ty),+),
+                    const fn compare(a: &$crate::DomainIdentifierInfo, b: &$crate::DomainIdentifierInfo) -> i8 {
+                        let result = $crate::utils::compare(a.ident.into_inner(), b.ident.into_inner());
+                        if result == 0 && (a.type_info as isize) != (b.type_info as isize) {
+                            panic!("Domain identifiers must have a consistent type in all its definitions");
+                        }
+                        result
+                    }
+                ),
+            };
+            const FINGERPRINTS: &'static [u64] = {
+                const FINGERPRINTS: &[u64] = $crate::event_union!(@fingerprints &[]; $($ty),+);
+                // The fingerprints are only aligned with the events when all the members have them.
+                if FINGERPRINTS.len() == <$name as $crate::Event>::SCHEMA.events_info.len() {
+                    FINGERPRINTS
+                } else {
+                    &[]
+                }
+            };
+
+            fn pii_fields(event_type: &str) -> &'static [&'static str] {
+                $(
+                    if <$ty as $crate::Event>::SCHEMA.events.contains(&event_type) {
+                        return <$ty as $crate::Event>::pii_fields(event_type);
+                    }
+                )+
+                &[]
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant(event) => $crate::Event::name(event),)+
+                }
+            }
+
+            fn domain_identifiers(&self) -> $crate::DomainIdentifierSet {
+                match self {
+                    $(Self::$variant(event) => $crate::Event::domain_identifiers(event),)+
+                }
+            }
+
+            fn payload_fields(&self) -> $crate::DomainIdentifierSet {
+                match self {
+                    $(Self::$variant(event) => $crate::Event::payload_fields(event),)+
+                }
+            }
+        }
+
+        $(
+            impl From<$ty> for $name {
+                fn from(event: $ty) -> Self {
+                    Self::$variant(event)
+                }
+            }
+
+            impl TryFrom<$name> for $ty {
+                type Error = $crate::ProjectionError;
+
+                fn try_from(event: $name) -> Result<Self, Self::Error> {
+                    match event {
+                        $name::$variant(event) => Ok(event),
+                        #[allow(unreachable_patterns)]
+                        event => Err($crate::ProjectionError($crate::Event::name(&event))),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+/// Wrapper for a persisted event.
+///
+/// It contains an ID assigned by the event store and the event itself.
+///
+/// The event stores stamp the appended events with the actor of the execution context, see `Actor`.
+#[derive(Debug)]
+pub struct PersistedEvent<ID: EventId, E: Event> {
+    pub(crate) id: ID,
+    pub(crate) event: E,
+    pub(crate) actor: Option<Arc<Actor>>,
+}
+
+impl<ID: EventId, E: Event> PersistedEvent<ID, E> {
+    /// Creates a new `PersistedEvent` instance with the given ID and event.
+    pub fn new(id: ID, event: E) -> Self {
+        Self {
+            id,
+            event,
+            actor: None,
+        }
+    }
+
+    /// Sets the actor that appended the event.
+    pub fn with_actor(mut self, actor: Option<Actor>) -> Self {
+        self.actor = actor.map(Arc::new);
+        self
+    }
+
+    /// Returns the actor that appended the event, if known.
+    pub fn actor(&self) -> Option<&Actor> {
+        self.actor.as_deref()
+    }
+
+    /// Returns the inner event.
+    pub fn into_inner(self) -> E {
+        self.event
+    }
+
+    /// Retrieves the ID assigned by the event store for this persisted event.
+    pub fn id(&self) -> ID {
+        self.id
+    }
+}
+
+impl<ID: EventId, E: Event + Clone> Clone for PersistedEvent<ID, E> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            event: self.event.clone(),
+            actor: self.actor.clone(),
+        }
+    }
+}
+
+impl<ID: EventId, E: Event + PartialEq> PartialEq for PersistedEvent<ID, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.event == other.event && self.actor == other.actor
+    }
+}
+
+impl<ID: EventId, E: Event> Deref for PersistedEvent<ID, E> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.event
+    }
+}
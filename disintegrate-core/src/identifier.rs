@@ -9,7 +9,7 @@
 //! Creating a new identifier:
 //!
 //! ```
-//! use disintegrate::Identifier;
+//! use disintegrate_core::Identifier;
 //!
 //! let identifier = Identifier::new("my_identifier").unwrap();
 //! println!("Identifier: {}", identifier);
@@ -18,7 +18,7 @@
 //! Using the `ident!` macro to create identifiers in a safe manner:
 //!
 //! ```
-//! use disintegrate::ident;
+//! use disintegrate_core::ident;
 //!
 //! let identifier = ident!(#my_identifier);
 //! println!("Identifier: {}", identifier);
@@ -27,7 +27,7 @@
 //! Handling identifier validation errors:
 //!
 //! ```
-//! use disintegrate::Identifier;
+//! use disintegrate_core::Identifier;
 //!
 //! let identifier = Identifier::new("invalid identifier");
 //! match identifier {
@@ -36,12 +36,12 @@
 //! }
 //! ```
 //!
-use lazy_static::lazy_static;
-use regex::Regex;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Deref;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
-use std::fmt::{self, Display, Formatter};
-use std::ops::Deref;
 use uuid::Uuid;
 
 /// Represents a valid identifier.
@@ -62,7 +62,7 @@ impl Identifier {
     /// # Examples
     ///
     /// ```
-    /// use disintegrate::Identifier;
+    /// use disintegrate_core::Identifier;
     ///
     /// let identifier = Identifier::new("my_identifier").unwrap();
     /// println!("Identifier: {}", identifier);
@@ -104,16 +104,17 @@ impl Identifier {
     /// # Examples
     ///
     /// ```
-    /// use disintegrate::Identifier;
+    /// use disintegrate_core::Identifier;
     ///
     /// assert_eq!(Identifier::is_valid_identifier("my_identifier"), true);
     /// assert_eq!(Identifier::is_valid_identifier("123"), false);
     /// ```
     pub fn is_valid_identifier(s: &str) -> bool {
-        lazy_static! {
-            static ref RE: Regex = Regex::new("^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
-        }
-        RE.is_match(s)
+        let mut chars = s.chars();
+        chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
     }
     /// The inner string value of the identifier.
     ///
@@ -134,11 +135,26 @@ impl Identifier {
 /// # Example
 ///
 /// ```
-/// use disintegrate::ident;
+/// use disintegrate_core::ident;
 ///
 /// let identifier = ident!(#my_identifier);
 /// ```
 ///
+/// Prefixing the identifier with an event type checks at compile time that the events declare it as a
+/// domain identifier, which is useful to build the filters of a stream query:
+///
+/// ```compile_fail
+/// # use disintegrate_core::{ident, DomainIdentifierSet, Event, EventSchema};
+/// # struct CartEvent;
+/// # impl Event for CartEvent {
+/// #     const SCHEMA: EventSchema = EventSchema { events: &[], events_info: &[], domain_identifiers: &[] };
+/// #     fn name(&self) -> &'static str { "" }
+/// #     fn domain_identifiers(&self) -> DomainIdentifierSet { DomainIdentifierSet::default() }
+/// # }
+/// // `CartEvent` declares no domain identifier, so the build fails.
+/// let identifier = ident!(CartEvent, #cart_id);
+/// ```
+///
 /// # Safety
 /// The `ident` macro is marked as unsafe because it bypasses the runtime validation of identifiers.
 /// It should only be used in controlled scenarios where it is guaranteed that the identifier passed
@@ -151,6 +167,10 @@ macro_rules! ident {
         unsafe { ident = $crate::Identifier::unsafe_new(stringify!($id)) }
         ident
     }};
+    ($event_ty:ty, #$id:ident) => {{
+        $crate::assert_domain_identifiers!($event_ty; $id);
+        $crate::ident!(#$id)
+    }};
 }
 
 /// The `Display` trait implementation enables formatting an `Identifier` for display purposes.
@@ -160,7 +180,7 @@ macro_rules! ident {
 ///
 /// ```
 /// use std::fmt::Display;
-/// use disintegrate::Identifier;
+/// use disintegrate_core::Identifier;
 ///
 /// let identifier = Identifier::new("my_identifier").unwrap();
 /// println!("Identifier: {}", identifier);
@@ -204,7 +224,7 @@ impl TryFrom<&'static str> for Identifier {
 /// # Examples
 ///
 /// ```
-/// use disintegrate::Identifier;
+/// use disintegrate_core::Identifier;
 ///
 /// let identifier = Identifier::new("my_identifier").unwrap();
 /// assert_eq!(*identifier, "my_identifier");
@@ -233,14 +253,16 @@ macro_rules! impl_identifier_type {
            $($type,)+
         }
 
-        #[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+        #[derive(Debug, Eq, PartialEq, PartialOrd, Clone, Deserialize, Serialize)]
         #[allow(non_camel_case_types)]
         /// Represents the value of an identifier, allowing different types.
+        ///
+        /// Values of the same type are ordered as the underlying type.
         pub enum IdentifierValue{
            $($type($type),)+
         }
 
-        impl std::fmt::Display for IdentifierValue{
+        impl fmt::Display for IdentifierValue{
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 match self{
                     $(Self::$type(value) => write!(f, "{}", value),)+
@@ -273,6 +295,35 @@ macro_rules! impl_identifier_type {
 
 impl_identifier_type! {String, i64, Uuid}
 
+/// Implements `IntoIdentifierValue` for integer types stored as `i64` identifier values.
+macro_rules! impl_identifier_integer {
+    ($convert:path; $($type:ty),+) => {
+        $(impl IntoIdentifierValue for $type {
+            const TYPE: IdentifierType = IdentifierType::i64;
+            fn into_identifier_value(self) -> IdentifierValue {
+                IdentifierValue::i64($convert(self))
+            }
+        })+
+
+        $(impl IntoIdentifierValue for &$type {
+            const TYPE: IdentifierType = IdentifierType::i64;
+            fn into_identifier_value(self) -> IdentifierValue {
+                (*self).into_identifier_value()
+            }
+        })+
+    };
+}
+
+/// Converts an unsigned integer into an `i64`, panicking when the value does not fit.
+fn checked_i64<T: TryInto<i64>>(value: T) -> i64 {
+    value
+        .try_into()
+        .unwrap_or_else(|_| panic!("the identifier value does not fit into an i64"))
+}
+
+impl_identifier_integer! {i64::from; i8, i16, i32, u8, u16, u32}
+impl_identifier_integer! {checked_i64; u64, usize}
+
 /// Represents a value that can be used as an identifier value.
 ///
 /// The `IntoIdentifierValue` trait allows converting values into `IdentifierValue` instances,
@@ -360,6 +411,20 @@ mod tests {
         assert_eq!(identifier_value, IdentifierValue::i64(42));
     }
 
+    #[test]
+    fn it_converts_narrower_integers_into_identifier_value() {
+        assert_eq!(7i32.into_identifier_value(), IdentifierValue::i64(7));
+        assert_eq!((&7u16).into_identifier_value(), IdentifierValue::i64(7));
+        assert_eq!(7u64.into_identifier_value(), IdentifierValue::i64(7));
+        assert_eq!(<u32 as IntoIdentifierValue>::TYPE, IdentifierType::i64);
+    }
+
+    #[test]
+    #[should_panic(expected = "the identifier value does not fit into an i64")]
+    fn it_rejects_unsigned_integers_out_of_the_i64_range() {
+        u64::MAX.into_identifier_value();
+    }
+
     #[test]
     fn it_converts_uuid_into_identifier_value() {
         let uuid_value = uuid::Uuid::new_v4();
@@ -0,0 +1,78 @@
+//! The domain abstractions of Disintegrate: the events, the state queries and the decisions.
+//!
+//! The crate is `no_std` and only requires an allocator, so the decisions and the folding of their states
+//! can run on embedded devices, while the backend replays the same code on top of the event stores of the
+//! `disintegrate` crate, which re-exports all the items of this crate. The `std` feature adds the integrations
+//! with the standard library, such as the actor of the execution context.
+//!
+//! The derive macros generate paths to the `disintegrate` crate, so the crate is renamed when it is used
+//! directly:
+//!
+//! ```toml
+//! [dependencies]
+//! disintegrate = { package = "disintegrate-core", version = "2.0.0", features = ["macros"] }
+//! ```
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod actor;
+mod decision;
+mod domain_identifier;
+mod error;
+mod event;
+mod identifier;
+mod state;
+mod stream_query;
+pub mod utils;
+
+#[doc(inline)]
+pub use crate::actor::{Actor, ActorKind, ParseActorError};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use crate::actor::{ActorScoped, WithActor};
+#[doc(inline)]
+pub use crate::decision::Decision;
+#[doc(inline)]
+pub use crate::domain_identifier::{DomainIdentifier, DomainIdentifierSet};
+#[doc(inline)]
+pub use crate::error::{error_kind, ClassifiedError, Classify, ErrorKind};
+#[doc(inline)]
+pub use crate::event::{
+    DomainIdentifierInfo, Event, EventDescriptor, EventId, EventInfo, EventSchema, PersistedEvent,
+    ProjectionError,
+};
+#[doc(inline)]
+pub use crate::identifier::{Identifier, IdentifierType, IdentifierValue, IntoIdentifierValue};
+#[doc(hidden)]
+pub use crate::state::EventsSubset;
+#[doc(inline)]
+pub use crate::state::{
+    HydrationWindow, IntoState, IntoStatePart, StateMutate, StatePart, StateQuery,
+};
+#[doc(inline)]
+pub use crate::stream_query::{query, IdentifierRange, StreamFilter, StreamQuery};
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! all_the_tuples {
+    ($name:ident) => {
+        $name!([], T1);
+        $name!([T1], T2);
+        $name!([T1, T2], T3);
+        $name!([T1, T2, T3], T4);
+        $name!([T1, T2, T3, T4], T5);
+        $name!([T1, T2, T3, T4, T5], T6);
+        $name!([T1, T2, T3, T4, T5, T6], T7);
+        $name!([T1, T2, T3, T4, T5, T6, T7], T8);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8], T9);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9], T10);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10], T11);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11], T12);
+    };
+}
+
+pub type BoxDynError = alloc::boxed::Box<dyn core::error::Error + 'static + Send + Sync>;
+
+#[cfg(feature = "macros")]
+pub use disintegrate_macros::{Event, IntoIdentifierValue, StateQuery};
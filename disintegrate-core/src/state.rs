@@ -0,0 +1,262 @@
+//! A State contains the initial conditions that a `Decision` uses to make the changes.
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::time::Duration;
+
+use paste::paste;
+use serde::{Deserialize, Serialize};
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+use std::time::Instant;
+#[cfg(all(feature = "std", target_arch = "wasm32", target_os = "unknown"))]
+use web_time::Instant;
+
+use crate::{all_the_tuples, Event, EventId, PersistedEvent, StreamQuery};
+
+/// A mutable state that can be changed by events from the event store.
+pub trait StateMutate: StateQuery {
+    /// Mutates the state object based on the provided event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to be applied to mutate the state.
+    fn mutate(&mut self, event: Self::Event);
+}
+
+/// The suffix of the event stream folded to build a windowed state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HydrationWindow {
+    /// Only the last N events matching the query are folded.
+    LastEvents(u64),
+    /// Only the events appended within the given duration are folded.
+    Within(Duration),
+}
+
+/// Represents a state query used to retrieve events from the event store to build a state.
+///
+/// The query method returns a `StreamQuery` to be used for querying the event store.
+pub trait StateQuery: Clone + Send + Sync {
+    /// the unique name of the state query.
+    const NAME: &'static str;
+    /// The fingerprint of the shape of the state query. Snapshots taken with a different fingerprint are
+    /// ignored and rebuilt.
+    ///
+    /// The `StateQuery` derive computes it from the fields of the struct. Manual implementations should
+    /// change it whenever the shape of the state changes.
+    const FINGERPRINT: u64 = 0;
+    /// The suffix of the stream needed to build the state, or `None` to fold the whole stream.
+    ///
+    /// A windowed state query is meant for decisions whose invariants only depend on recent history.
+    /// Its state is always hydrated from the beginning of the window, so it is never snapshotted.
+    const WINDOW: Option<HydrationWindow> = None;
+    /// The type of events queried by this state query.
+    type Event: Event + Clone + Send + Sync;
+
+    /// Returns the stream query used to retrieve relevant events for building the state.
+    fn query<ID: EventId>(&self) -> StreamQuery<ID, Self::Event>;
+}
+
+impl<ID, S, E: Event + Clone> From<&S> for StreamQuery<ID, E>
+where
+    S: StateQuery<Event = E>,
+    ID: EventId,
+{
+    fn from(state: &S) -> Self {
+        state.query()
+    }
+}
+
+/// Fails the build when the events of `S` are not all events of `E`.
+///
+/// A state query is hydrated from the events of the event store, the ones of the decision. The conversions
+/// between the two event types compile as soon as `From` is implemented, but an event of the state query
+/// that is not among the events of `E` is never found in the event store. The check is an associated
+/// constant, so it is evaluated for every pair of types that is actually hydrated.
+///
+/// ```compile_fail
+/// # use disintegrate_core::{DomainIdentifierSet, Event, EventSchema};
+/// # #[derive(Clone)]
+/// # struct CartEvent;
+/// # impl Event for CartEvent {
+/// #     const SCHEMA: EventSchema = EventSchema { events: &["ItemAdded"], events_info: &[], domain_identifiers: &[] };
+/// #     fn name(&self) -> &'static str { "ItemAdded" }
+/// #     fn domain_identifiers(&self) -> DomainIdentifierSet { DomainIdentifierSet::default() }
+/// # }
+/// # #[derive(Clone)]
+/// # struct CouponEvent;
+/// # impl Event for CouponEvent {
+/// #     const SCHEMA: EventSchema = EventSchema { events: &["CouponApplied"], events_info: &[], domain_identifiers: &[] };
+/// #     fn name(&self) -> &'static str { "CouponApplied" }
+/// #     fn domain_identifiers(&self) -> DomainIdentifierSet { DomainIdentifierSet::default() }
+/// # }
+/// # impl From<CouponEvent> for CartEvent { fn from(_: CouponEvent) -> Self { CartEvent } }
+/// # #[derive(Clone)]
+/// # struct Coupon;
+/// # impl disintegrate_core::StateQuery for Coupon {
+/// #     const NAME: &'static str = "Coupon";
+/// #     type Event = CouponEvent;
+/// #     fn query<ID: disintegrate_core::EventId>(&self) -> disintegrate_core::StreamQuery<ID, CouponEvent> { disintegrate_core::query!(CouponEvent) }
+/// # }
+/// let coupon = disintegrate_core::StatePart::new(0i64, Coupon);
+/// let event = disintegrate_core::PersistedEvent::new(1i64, CartEvent);
+/// // `CouponApplied` is not an event of `CartEvent`, so the build fails.
+/// coupon.matches_event(&event);
+/// ```
+#[doc(hidden)]
+pub struct EventsSubset<S, E>(PhantomData<(S, E)>);
+
+impl<S: Event, E: Event> EventsSubset<S, E> {
+    pub const ASSERT: () = assert!(
+        crate::utils::include(E::SCHEMA.events, S::SCHEMA.events),
+        "the events of the state query are not all events of the event type of the decision"
+    );
+}
+
+/// A structure representing a sub-state in a multi-state object. It encapsulates
+/// the version, applied events count, and the payload of a sub-state.
+///
+/// # Type Parameters
+///
+/// - `S`: The type implementing the `StateMutate` trait, representing the sub-state.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StatePart<ID: EventId, S: StateQuery> {
+    /// The version of the sub-state.
+    version: ID,
+    /// The count of events applied to the sub-state.
+    applied_events: u64,
+    /// The payload of the sub-state.
+    inner: S,
+    /// The instant when the sub-state was created or loaded from a snapshot.
+    #[cfg(feature = "std")]
+    #[serde(skip, default = "Instant::now")]
+    hydration_started: Instant,
+}
+
+impl<ID: EventId, S: StateQuery> StatePart<ID, S> {
+    pub fn new(version: ID, payload: S) -> Self {
+        Self {
+            version,
+            applied_events: 0,
+            inner: payload,
+            #[cfg(feature = "std")]
+            hydration_started: Instant::now(),
+        }
+    }
+    pub fn version(&self) -> ID {
+        self.version
+    }
+    pub fn applied_events(&self) -> u64 {
+        self.applied_events
+    }
+    /// Returns the time elapsed since the sub-state was created or loaded from a snapshot.
+    #[cfg(feature = "std")]
+    pub fn hydration_time(&self) -> Duration {
+        self.hydration_started.elapsed()
+    }
+    pub fn query_part(&self) -> StreamQuery<ID, <S as StateQuery>::Event> {
+        self.inner.query().change_origin(self.version)
+    }
+
+    pub fn matches_event<U>(&self, event: &PersistedEvent<ID, U>) -> bool
+    where
+        U: Event + Clone,
+        <S as StateQuery>::Event: Into<U>,
+    {
+        let () = EventsSubset::<S::Event, U>::ASSERT;
+        self.query_part().cast().matches(event)
+    }
+    pub fn mutate_part<E>(&mut self, event: PersistedEvent<ID, E>)
+    where
+        E: Event,
+        S: StateMutate,
+        <S as StateQuery>::Event: TryFrom<E>,
+        <<S as StateQuery>::Event as TryFrom<E>>::Error: core::error::Error + 'static + Send + Sync,
+    {
+        self.version = event.id();
+        self.applied_events += 1;
+        self.inner.mutate(event.into_inner().try_into().unwrap());
+    }
+}
+
+impl<ID: EventId, S: StateQuery> Deref for StatePart<ID, S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.inner
+    }
+}
+
+/// Converts an state into `StatePart`s.
+///
+/// This trait is used to initialize a multi-state object by converting a state into a state part
+/// with version and event information.
+///
+/// # Type Parameters
+///
+/// - `T`: The type of the object that can be converted into a `StatePart`.
+///
+/// # Associated Types
+///
+/// - `Target`: The resulting type after conversion, representing a `StatePart`.
+pub trait IntoStatePart<ID: EventId, T>: Sized {
+    type Target;
+    /// Converts the object into a `StatePart`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the resulting `StatePart` after the conversion.
+    fn into_state_part(self) -> Self::Target;
+}
+
+/// Extracts the state payload from a `StatePart`.
+///
+/// # Type Parameters
+///
+/// - `T`: The type representing the concrete state to be obtained from the `StatePart`.
+pub trait IntoState<T>: Sized {
+    /// Converts the `StatePart` into a concrete state type.
+    ///
+    /// # Returns
+    ///
+    /// Returns the concrete state obtained from the `StatePart`.
+    fn into_state(self) -> T;
+}
+
+macro_rules! impl_from_state {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        #[allow(unused_parens)]
+        impl<ID, $($ty,)* $last> IntoStatePart<ID, ($($ty,)* $last)> for ($($ty,)* $last) where
+            ID: EventId,
+            $($ty: StateQuery,)*
+            $last: StateQuery,
+        {
+            type Target = ($(StatePart<ID, $ty>,)* StatePart<ID, $last>);
+            paste! {
+                fn into_state_part(self) -> ($(StatePart<ID, $ty>,)*StatePart<ID, $last>){
+                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>])= self;
+                    ($(StatePart::new(Default::default(), [<state_ $ty:lower>]),)* StatePart::new(Default::default(), [<state_ $last:lower>]))
+                }
+            }
+        }
+
+        #[allow(unused_parens)]
+        impl<ID, $($ty,)* $last> IntoState<($($ty,)* $last)> for ($(StatePart<ID, $ty>,)* StatePart<ID, $last>) where
+            ID: EventId,
+            $($ty: StateQuery,)*
+            $last: StateQuery,
+        {
+            paste! {
+                fn into_state(self) -> ($($ty,)* $last){
+                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>])= self;
+                    ($( [<state_ $ty:lower>].inner,)* [<state_ $last:lower>].inner)
+                }
+            }
+        }
+    }
+}
+
+all_the_tuples!(impl_from_state);
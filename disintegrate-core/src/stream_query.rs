@@ -0,0 +1,884 @@
+//! A stream query represents a query for filtering event streams based on certain criteria.
+//!
+//! This module provides functionality for querying event streams using `StreamQuery`.
+//! It allows you to define filters and constraints to narrow down the events of interest and specify
+//! the starting point or origin within the event stream.
+//!
+//! The module also provides utility functions and macros for creating and combining stream filters,
+//! such as `eq`, `and`, and `or`. These can be used to construct complex filter expressions.
+//!
+//! The `StreamFilter` enum defines different types of filters that can be applied to event streams,
+//! including equality filters, logical AND filters, and logical OR filters. Filters are evaluated
+//! using the `FilterEvaluator` trait, which provides an `eval` method for evaluating a filter against
+//! an event.
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+
+use crate::{
+    domain_identifiers, event::EventId, DomainIdentifier, DomainIdentifierSet, Event, Identifier,
+    IdentifierValue, IntoIdentifierValue, PersistedEvent,
+};
+
+/// Represents a query for filtering event streams.
+///
+/// A `StreamQuery` is used to define filters and constraints for querying event streams.
+/// It allows you to specify a filter to narrow down the events of interest and an origin
+/// to determine the starting point of the query within the event stream.
+#[derive(Debug, Clone)]
+pub struct StreamQuery<ID: EventId, E: Event + Clone> {
+    /// An optional filter applied to the event stream. It determines which events are included
+    /// in the query results based on certain criteria.
+    filters: Vec<StreamFilter<ID, E>>,
+    /// A marker indicating the event type associated with the stream query.
+    event_type: PhantomData<E>,
+    /// A marker indicating the event id type associated with the stream query.
+    event_id_type: PhantomData<ID>,
+}
+
+impl<ID: EventId, E: Event + Clone> StreamQuery<ID, E> {
+    /// Returns the filter associated with the stream query, if any.
+    pub fn filters(&self) -> &[StreamFilter<ID, E>] {
+        &self.filters
+    }
+
+    /// Casts the stream query to a different event type.
+    pub fn cast<U>(&self) -> StreamQuery<ID, U>
+    where
+        E: Event + Into<U>,
+        U: Event + Clone,
+    {
+        StreamQuery {
+            filters: self.filters.iter().map(|f| f.cast()).collect(),
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Unions two stream queries into a single query.
+    pub fn union<U, O>(&self, other: &StreamQuery<ID, O>) -> StreamQuery<ID, U>
+    where
+        E: Event + Into<U>,
+        U: Event + Clone,
+        O: Event + Into<U> + Clone,
+    {
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| f.cast())
+            .chain(other.filters.iter().map(|f| f.cast()))
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Changes the origin of the stream query.
+    ///
+    /// The origin determines the starting point of the query within the event stream.
+    pub fn change_origin(self, origin: ID) -> Self {
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| StreamFilter {
+                origin,
+                ..f.clone()
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Restricts the stream query to the events up to `position`, included.
+    ///
+    /// Together with `change_origin`, which starts the query after an event, it scopes the query to a
+    /// segment of the stream, for example to replay or audit the events appended in a period. Events
+    /// appended after `position` never match the query, so they are not considered conflicting either.
+    pub fn until(self, position: ID) -> Self {
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| StreamFilter {
+                until: Some(position),
+                ..f.clone()
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Restricts the stream query to the events after `origin` and up to `position`, included.
+    pub fn between(self, origin: ID, position: ID) -> Self {
+        self.change_origin(origin).until(position)
+    }
+
+    /// Restricts the stream query to the events whose payload field `field` is equal to `value`.
+    ///
+    /// The constraint applies only to the events that have the field, as declared by the `#[filter]`
+    /// attribute of the `Event` derive, and is evaluated by the event store when it is supported.
+    pub fn filter_payload(self, field: Identifier, value: impl IntoIdentifierValue) -> Self {
+        let value = value.into_identifier_value();
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| {
+                let mut payload_filters = f.payload_filters.clone();
+                payload_filters.insert(DomainIdentifier {
+                    key: field,
+                    value: value.clone(),
+                });
+                StreamFilter {
+                    payload_filters,
+                    ..f.clone()
+                }
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Restricts the stream query to the events matching at least one of the given domain identifiers.
+    ///
+    /// Each event is checked against the identifiers it has, for example the transfers where either
+    /// `account_id` or `beneficiary_id` is `A`. Events with none of the identifiers are not restricted,
+    /// consistently with the other identifier filters. The disjunction is part of the query, so it is
+    /// also used to detect conflicting events when the decision is persisted.
+    pub fn filter_any(self, identifiers: impl IntoIterator<Item = DomainIdentifier>) -> Self {
+        let disjunction: Vec<DomainIdentifier> = identifiers.into_iter().collect();
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| {
+                let mut disjunctions = f.disjunctions.clone();
+                disjunctions.push(disjunction.clone());
+                StreamFilter {
+                    disjunctions,
+                    ..f.clone()
+                }
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Restricts the stream query to the events whose domain identifier `identifier` is one of `values`.
+    ///
+    /// It is the disjunction of the values, so a single query loads the events of many entities, for
+    /// example the carts of a batch decision. Event stores translate it to an `IN` list when they can.
+    /// Events without the identifier are not restricted, while an empty list matches none of the events
+    /// with the identifier, as an empty `IN` list does in SQL.
+    pub fn filter_in<V>(self, identifier: Identifier, values: impl IntoIterator<Item = V>) -> Self
+    where
+        V: IntoIdentifierValue,
+    {
+        let mut values = values.into_iter().peekable();
+        if values.peek().is_some() {
+            return self.filter_any(values.map(|value| DomainIdentifier {
+                key: identifier,
+                value: value.into_identifier_value(),
+            }));
+        }
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| {
+                let mut empty_lists = f.empty_lists.clone();
+                empty_lists.push(identifier);
+                StreamFilter {
+                    empty_lists,
+                    ..f.clone()
+                }
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Restricts the stream query to the events whose domain identifier `identifier` is within `range`.
+    ///
+    /// Identifier values are compared as their type, so dates are best stored as ISO 8601 strings.
+    /// Events without the identifier are not restricted, consistently with the other identifier
+    /// filters.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use disintegrate_core::{ident, DomainIdentifierSet, Event, EventSchema, StreamQuery};
+    /// # #[derive(Clone)]
+    /// # struct InvoiceEvent;
+    /// # impl Event for InvoiceEvent {
+    /// #     const SCHEMA: EventSchema = EventSchema { events: &[], events_info: &[], domain_identifiers: &[] };
+    /// #     fn name(&self) -> &'static str { "" }
+    /// #     fn domain_identifiers(&self) -> DomainIdentifierSet { DomainIdentifierSet::default() }
+    /// # }
+    /// let march_invoices: StreamQuery<i64, InvoiceEvent> = disintegrate_core::query!(InvoiceEvent)
+    ///     .filter_range(ident!(#issued_on), "2024-03-01".."2024-04-01");
+    /// ```
+    pub fn filter_range<V>(self, identifier: Identifier, range: impl RangeBounds<V>) -> Self
+    where
+        V: IntoIdentifierValue + Clone,
+    {
+        let range = IdentifierRange {
+            key: identifier,
+            start: range.start_bound().cloned().map(V::into_identifier_value),
+            end: range.end_bound().cloned().map(V::into_identifier_value),
+        };
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| {
+                let mut ranges = f.ranges.clone();
+                ranges.push(range.clone());
+                StreamFilter {
+                    ranges,
+                    ..f.clone()
+                }
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Excludes the specified events from the stream query.
+    ///
+    /// The excluded events are not included in the query results.
+    pub fn exclude_events(self, excluded_events: &'static [&'static str]) -> Self {
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| StreamFilter {
+                excluded_events: Some(
+                    excluded_events
+                        .iter()
+                        .filter(|e| f.events.contains(e))
+                        .cloned()
+                        .collect(),
+                ),
+                ..f.clone()
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Excludes from the stream query the events whose domain identifier `identifier` is `value`.
+    ///
+    /// Events without the identifier are not excluded. Combined with `exclude_events`, it expresses
+    /// the negative constraints of a query without loading the events to discard them in `mutate`.
+    pub fn exclude_identifier(
+        self,
+        identifier: Identifier,
+        value: impl IntoIdentifierValue,
+    ) -> Self {
+        let excluded = DomainIdentifier {
+            key: identifier,
+            value: value.into_identifier_value(),
+        };
+        let filters = self
+            .filters
+            .iter()
+            .map(|f| {
+                let mut excluded_identifiers = f.excluded_identifiers.clone();
+                excluded_identifiers.push(excluded.clone());
+                StreamFilter {
+                    excluded_identifiers,
+                    ..f.clone()
+                }
+            })
+            .collect();
+
+        StreamQuery {
+            filters,
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+
+    /// Checks if the stream query matches the given event.
+    pub fn matches(&self, event: &PersistedEvent<ID, E>) -> bool {
+        self.matches_any(event)
+    }
+
+    /// Checks if the stream query matches the given event of any type, such as an event of the store.
+    pub fn matches_any<T: Event>(&self, event: &PersistedEvent<ID, T>) -> bool {
+        self.filters.iter().any(|filter| {
+            if let Some(excluded_events) = &filter.excluded_events {
+                if excluded_events.contains(&event.name()) {
+                    return false;
+                }
+            }
+
+            if !filter.events.contains(&event.name()) {
+                return false;
+            }
+
+            if filter
+                .identifiers
+                .iter()
+                .any(|(ident, value)| event.domain_identifiers().get(ident) != Some(value))
+            {
+                return false;
+            }
+
+            if !filter.disjunctions.is_empty() {
+                let domain_identifiers = event.domain_identifiers();
+                if filter.disjunctions.iter().any(|disjunction| {
+                    let mut applicable = disjunction
+                        .iter()
+                        .filter_map(|i| domain_identifiers.get(&i.key).map(|v| v == &i.value))
+                        .peekable();
+                    applicable.peek().is_some() && !applicable.any(|matches| matches)
+                }) {
+                    return false;
+                }
+            }
+
+            if !filter.empty_lists.is_empty() {
+                let domain_identifiers = event.domain_identifiers();
+                if filter
+                    .empty_lists
+                    .iter()
+                    .any(|ident| domain_identifiers.contains_key(ident))
+                {
+                    return false;
+                }
+            }
+
+            if !filter.excluded_identifiers.is_empty() {
+                let domain_identifiers = event.domain_identifiers();
+                if filter
+                    .excluded_identifiers
+                    .iter()
+                    .any(|excluded| domain_identifiers.get(&excluded.key) == Some(&excluded.value))
+                {
+                    return false;
+                }
+            }
+
+            if !filter.ranges.is_empty() {
+                let domain_identifiers = event.domain_identifiers();
+                if filter.ranges.iter().any(|range| {
+                    domain_identifiers
+                        .get(&range.key)
+                        .is_some_and(|value| !range.contains(value))
+                }) {
+                    return false;
+                }
+            }
+
+            if !filter.payload_filters.is_empty() {
+                let payload_fields = event.payload_fields();
+                if filter.payload_filters.iter().any(|(field, value)| {
+                    payload_fields
+                        .get(field)
+                        .is_some_and(|field_value| field_value != value)
+                }) {
+                    return false;
+                }
+            }
+
+            if event.id() <= filter.origin {
+                return false;
+            }
+
+            if filter.until.is_some_and(|until| event.id() > until) {
+                return false;
+            }
+
+            true
+        })
+    }
+
+    pub fn matches_event(&self, event: &str) -> bool {
+        self.filters.iter().any(|filter| {
+            if let Some(excluded_events) = &filter.excluded_events {
+                if excluded_events.contains(&event) {
+                    return false;
+                }
+            }
+            true
+        })
+    }
+}
+
+impl<ID: EventId, E: Event + Clone + PartialEq> PartialEq for StreamQuery<ID, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.filters == other.filters
+    }
+}
+
+/// Creates a new stream query with the given filter.
+pub fn query<ID, E, O>(filter: Option<StreamFilter<ID, O>>) -> StreamQuery<ID, E>
+where
+    ID: EventId,
+    E: Event + Clone,
+    O: Event + Clone + Into<E>,
+{
+    if let Some(filter) = filter {
+        StreamQuery {
+            filters: vec![filter.cast()],
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    } else {
+        StreamQuery {
+            filters: vec![StreamFilter::new(domain_identifiers!())],
+            event_type: PhantomData,
+            event_id_type: PhantomData,
+        }
+    }
+}
+
+/// Creates a stream query with a given event type and filter.
+#[macro_export]
+macro_rules! query {
+    ($event_ty: ty) => {{
+        $crate::query::<_, $event_ty, $event_ty>(None)
+    }};
+    ($event_ty:ty; $($filter:tt)+ ) => {{
+        $crate::query::<_, $event_ty, _>(Some($crate::filter!($event_ty; $($filter)*)))
+    }};
+    ($origin:expr => $event_ty:ty;  $($filter:tt)+ ) => {{
+        $crate::query!($event_ty; $($filter)*).change_origin($origin)
+    }};
+}
+
+/// Creates a list of domain identifiers of which at least one must match, see `StreamQuery::filter_any`.
+///
+/// Prefixing the list with the event type, as in `any_of!(TransferEvent; account_id == id)`, checks at
+/// compile time that the events declare the identifiers.
+///
+/// # Example
+///
+/// ```rust
+/// # use disintegrate_core::{any_of, DomainIdentifier};
+/// let account_id = "A";
+/// let identifiers: Vec<DomainIdentifier> = any_of!(account_id == account_id, beneficiary_id == account_id);
+/// assert_eq!(identifiers.len(), 2);
+/// ```
+#[macro_export]
+macro_rules! any_of {
+    ($($ident:ident == $value:expr),+ $(,)?) => {
+        $crate::utils::vec![$($crate::DomainIdentifier {
+            key: $crate::ident!(#$ident),
+            value: $crate::IntoIdentifierValue::into_identifier_value($value.clone()),
+        }),+]
+    };
+    ($event_ty:ty; $($ident:ident == $value:expr),+ $(,)?) => {{
+        $crate::assert_domain_identifiers!($event_ty; $($ident),+);
+        $crate::any_of!($($ident == $value),+)
+    }};
+}
+
+/// A convenient macro to get the list of event types as a list of `&'static str`.
+/// It performs compile-time checks to guarantee that the specified variants exist.  
+#[macro_export]
+macro_rules! event_types{
+    ($event_ty:ty, [$($events:ty),+]) =>{
+        {
+            use $crate::Event;
+            const EVENTS: &[&str] = {
+                const FILTER_ARG: &[&str] = &[$(stringify!($events),)+];
+                   if !$crate::utils::include(<$event_ty>::SCHEMA.events, FILTER_ARG) {
+                        panic!("one or more of the specified events do not exist");
+                   }
+                FILTER_ARG
+            };
+            EVENTS
+        }
+    };
+}
+
+/// Creates stream filters for querying event streams.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! filter {
+    ($origin:expr => $event_ty:ty; $($ident:ident == $value:expr),*) =>{
+        $crate::filter!($event_ty; $($ident == $value),*).change_origin($origin)
+    };
+    ($event_ty:ty; $($ident:ident == $value:expr),*) =>{
+        {
+            $crate::assert_domain_identifiers!($event_ty; $($ident),*);
+            $crate::StreamFilter::<_, $event_ty>::new($crate::domain_identifiers!($($ident: $value.clone()),*))
+        }
+    };
+}
+
+/// Checks at compile time that the events of `$event_ty` declare the given domain identifiers.
+///
+/// It is used by the query macros, so that a misspelled identifier fails the build instead of silently
+/// leaving the query unrestricted.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! assert_domain_identifiers {
+    ($event_ty:ty; $($ident:ident),*) => {
+        #[allow(dead_code)]
+        {
+            use $crate::Event;
+            // Check if the domain identifiers exist
+            const DOMAIN_IDENTIFIERS: &[&$crate::DomainIdentifierInfo] = <$event_ty>::SCHEMA.domain_identifiers;
+            const DOMAIN_IDENTIFIERS_INDENTS: &[&str] = &$crate::const_slice_iter!(DOMAIN_IDENTIFIERS, const fn map(item: &$crate::DomainIdentifierInfo) -> &str {
+                item.ident.into_inner()
+            });
+
+            $(
+               const _:&[&str] = {
+                   const FILTER_ARG: &[&str] = &[stringify!($ident)];
+                   if !$crate::utils::include(DOMAIN_IDENTIFIERS_INDENTS, FILTER_ARG) {
+                       panic!(concat!(
+                           "Invalid domain filter: the domain identifier `",
+                           stringify!($ident),
+                           "` is not declared by any event of `",
+                           stringify!($event_ty),
+                           "`. Check its spelling or mark the field with #[id] in the events."
+                       ));
+                   }
+                   FILTER_ARG
+               };
+
+            )*
+        }
+    };
+}
+
+/// unions two or more stream queries into a single query.
+#[macro_export]
+macro_rules! union {
+    ($query:expr) =>{
+        Into::<$crate::StreamQuery<_, _>>::into($query).cast()
+    };
+    ($query1:expr, $query2: expr) =>{
+        $crate::StreamQuery::<_, _>::union(&Into::<$crate::StreamQuery<_, _>>::into($query1),&Into::<$crate::StreamQuery<_, _>>::into($query2))
+    };
+    ($query:expr, $($queries: expr),*) =>{
+        {
+                let mut result = $crate::union!($($queries),*);
+                result = $crate::StreamQuery::<_, _>::union(&Into::<$crate::StreamQuery<_, _>>::into($query), &result);
+                result
+        }
+    };
+}
+
+/// Represents a filter applied to an event stream.
+///
+/// A `StreamFilter` is used to define filters and constraints for querying event streams.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamFilter<ID: EventId, E: Event + Clone> {
+    /// The names of the events to include in the query results.
+    events: &'static [&'static str],
+    /// The domain identifiers and values used to filter the events.
+    identifiers: DomainIdentifierSet,
+    /// The groups of domain identifiers of which at least one must match.
+    disjunctions: Vec<Vec<DomainIdentifier>>,
+    /// The domain identifiers restricted to an empty list of values, so no event with them matches.
+    empty_lists: Vec<Identifier>,
+    /// The ranges of values of the domain identifiers.
+    ranges: Vec<IdentifierRange>,
+    /// The payload fields and values used to filter the events.
+    payload_filters: DomainIdentifierSet,
+    /// The starting point of the query within the event stream.
+    origin: ID,
+    /// The last event of the query within the event stream, if any.
+    until: Option<ID>,
+    /// The names of the events to exclude from the query results.
+    excluded_events: Option<Vec<&'static str>>,
+    /// The domain identifier values to exclude from the query results.
+    excluded_identifiers: Vec<DomainIdentifier>,
+    /// A marker indicating the event type associated with the stream filter.
+    event_type: PhantomData<E>,
+}
+
+impl<ID: EventId, E: Event + Clone> StreamFilter<ID, E> {
+    /// Creates a new stream filter with the specified domain identifiers.
+    pub fn new(identifiers: DomainIdentifierSet) -> Self {
+        Self {
+            events: E::SCHEMA.events,
+            identifiers,
+            disjunctions: vec![],
+            empty_lists: vec![],
+            ranges: vec![],
+            payload_filters: DomainIdentifierSet::default(),
+            origin: Default::default(),
+            until: None,
+            excluded_events: None,
+            excluded_identifiers: vec![],
+            event_type: PhantomData,
+        }
+    }
+
+    /// Changes the origin of the stream filter.
+    pub fn change_origin(self, origin: ID) -> Self {
+        Self { origin, ..self }
+    }
+
+    /// Excludes the specified events from the stream filter.
+    pub fn exclude_events(self, excluded_events: &'static [&'static str]) -> Self {
+        Self {
+            excluded_events: Some(excluded_events.to_vec()),
+            ..self
+        }
+    }
+
+    /// Casts the stream filter to a different event type.
+    pub fn cast<O>(&self) -> StreamFilter<ID, O>
+    where
+        E: Event + Into<O>,
+        O: Event + Clone,
+    {
+        StreamFilter {
+            events: self.events,
+            identifiers: self.identifiers.clone(),
+            disjunctions: self.disjunctions.clone(),
+            empty_lists: self.empty_lists.clone(),
+            ranges: self.ranges.clone(),
+            payload_filters: self.payload_filters.clone(),
+            origin: self.origin,
+            until: self.until,
+            excluded_events: self.excluded_events.clone(),
+            excluded_identifiers: self.excluded_identifiers.clone(),
+            event_type: PhantomData,
+        }
+    }
+
+    /// Returns the names of the events to include in the query results.
+    pub fn events(&self) -> &'static [&'static str] {
+        self.events
+    }
+
+    /// Returns the domain identifiers used to filter the events.
+    pub fn identifiers(&self) -> &DomainIdentifierSet {
+        &self.identifiers
+    }
+
+    /// Returns the groups of domain identifiers of which at least one must match.
+    pub fn disjunctions(&self) -> &[Vec<DomainIdentifier>] {
+        &self.disjunctions
+    }
+
+    /// Returns the domain identifiers restricted to an empty list of values.
+    pub fn empty_lists(&self) -> &[Identifier] {
+        &self.empty_lists
+    }
+
+    /// Returns the ranges of values of the domain identifiers.
+    pub fn ranges(&self) -> &[IdentifierRange] {
+        &self.ranges
+    }
+
+    /// Returns the payload fields used to filter the events.
+    pub fn payload_filters(&self) -> &DomainIdentifierSet {
+        &self.payload_filters
+    }
+
+    /// Returns the starting point of the query within the event stream.
+    pub fn origin(&self) -> ID {
+        self.origin
+    }
+
+    /// Returns the last event of the query within the event stream, if any.
+    pub fn until(&self) -> Option<ID> {
+        self.until
+    }
+
+    /// Returns the names of the events to exclude from the query results.
+    pub fn excluded_events(&self) -> Option<&Vec<&'static str>> {
+        self.excluded_events.as_ref()
+    }
+
+    /// Returns the domain identifier values to exclude from the query results.
+    pub fn excluded_identifiers(&self) -> &[DomainIdentifier] {
+        &self.excluded_identifiers
+    }
+}
+
+/// A range of values of a domain identifier, used to filter the events of a stream query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentifierRange {
+    /// The domain identifier.
+    pub key: Identifier,
+    /// The lower bound of the range.
+    pub start: Bound<IdentifierValue>,
+    /// The upper bound of the range.
+    pub end: Bound<IdentifierValue>,
+}
+
+impl IdentifierRange {
+    /// Returns the comparisons that define the range, as pairs of operator and value.
+    pub fn conditions(&self) -> Vec<(&'static str, &IdentifierValue)> {
+        let start = match &self.start {
+            Bound::Included(value) => Some((">=", value)),
+            Bound::Excluded(value) => Some((">", value)),
+            Bound::Unbounded => None,
+        };
+        let end = match &self.end {
+            Bound::Included(value) => Some(("<=", value)),
+            Bound::Excluded(value) => Some(("<", value)),
+            Bound::Unbounded => None,
+        };
+        start.into_iter().chain(end).collect()
+    }
+}
+
+impl RangeBounds<IdentifierValue> for IdentifierRange {
+    fn start_bound(&self) -> Bound<&IdentifierValue> {
+        self.start.as_ref()
+    }
+
+    fn end_bound(&self) -> Bound<&IdentifierValue> {
+        self.end.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ident;
+    use crate::stream_query::StreamFilter;
+    use crate::utils::tests::*;
+    use crate::IdentifierValue;
+    use crate::{PersistedEvent, StreamQuery};
+
+    #[test]
+    fn test_filter_with_no_origin_and_no_exclude_events() {
+        let filter: StreamFilter<i64, _> = filter! {
+            ShoppingCartEvent;
+            cart_id == 42
+        };
+
+        assert_eq!(filter.identifiers.len(), 1);
+        assert_eq!(
+            filter.identifiers[&ident!(#cart_id)],
+            IdentifierValue::i64(42)
+        );
+    }
+
+    #[test]
+    fn test_filter_with_origin() {
+        let filter = filter! {
+            10 =>
+            ShoppingCartEvent;
+            cart_id == 42
+        };
+
+        assert_eq!(filter.origin, 10);
+    }
+
+    #[test]
+    fn test_filter_with_all_parameters() {
+        let filter = filter! {
+            10 =>
+            ShoppingCartEvent;
+            cart_id == 42
+        };
+
+        assert_eq!(filter.origin, 10);
+        assert_eq!(filter.identifiers.len(), 1);
+        assert_eq!(
+            filter.identifiers[&ident!(#cart_id)],
+            IdentifierValue::i64(42)
+        );
+    }
+
+    #[test]
+    fn it_matches_the_events_between_two_positions() {
+        let query: StreamQuery<i64, ShoppingCartEvent> =
+            query!(ShoppingCartEvent; cart_id == "c1").between(1, 3);
+
+        assert!(!query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(2, item_added_event("p2", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(3, item_added_event("p3", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(4, item_added_event("p4", "c1"))));
+    }
+
+    #[test]
+    fn it_matches_the_events_with_any_of_the_identifiers() {
+        let query: StreamQuery<i64, ShoppingCartEvent> = query!(ShoppingCartEvent)
+            .filter_any(any_of!(ShoppingCartEvent; cart_id == "c1", item_id == "p1"));
+
+        assert!(query.matches(&PersistedEvent::new(1, item_added_event("p2", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(2, item_added_event("p1", "c2"))));
+        assert!(!query.matches(&PersistedEvent::new(3, item_removed_event("p2", "c2"))));
+    }
+
+    #[test]
+    fn it_does_not_match_the_excluded_identifiers() {
+        let query: StreamQuery<i64, ShoppingCartEvent> =
+            query!(ShoppingCartEvent; cart_id == "c1").exclude_identifier(ident!(#item_id), "p1");
+
+        assert!(!query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(2, item_added_event("p2", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(3, item_removed_event("p1", "c1"))));
+    }
+
+    #[test]
+    fn it_matches_the_events_with_an_identifier_in_the_list() {
+        let query: StreamQuery<i64, ShoppingCartEvent> =
+            query!(ShoppingCartEvent).filter_in(ident!(ShoppingCartEvent, #cart_id), ["c1", "c3"]);
+
+        assert!(query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(2, item_added_event("p1", "c2"))));
+        assert!(query.matches(&PersistedEvent::new(3, item_removed_event("p1", "c3"))));
+    }
+
+    #[test]
+    fn it_does_not_match_the_events_with_an_identifier_in_an_empty_list() {
+        let query: StreamQuery<i64, ShoppingCartEvent> = query!(ShoppingCartEvent)
+            .filter_in(ident!(ShoppingCartEvent, #cart_id), Vec::<String>::new());
+
+        assert!(!query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(2, item_removed_event("p1", "c2"))));
+    }
+
+    #[test]
+    fn it_matches_the_events_within_a_range() {
+        let query: StreamQuery<i64, ShoppingCartEvent> =
+            query!(ShoppingCartEvent).filter_range(ident!(#item_id), "p2".."p4");
+
+        assert!(!query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(2, item_added_event("p2", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(3, item_added_event("p3", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(4, item_added_event("p4", "c1"))));
+    }
+
+    #[test]
+    fn it_matches_the_events_with_the_payload_field() {
+        let query: StreamQuery<i64, ShoppingCartEvent> =
+            query!(ShoppingCartEvent; cart_id == "c1").filter_payload(ident!(#item_id), "p1");
+
+        assert!(query.matches(&PersistedEvent::new(1, item_added_event("p1", "c1"))));
+        assert!(!query.matches(&PersistedEvent::new(2, item_added_event("p2", "c1"))));
+        assert!(query.matches(&PersistedEvent::new(3, item_removed_event("p2", "c1"))));
+    }
+}
@@ -0,0 +1,286 @@
+#![doc(hidden)]
+
+pub use alloc::vec;
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! const_slice_unique {
+    ($ty:ty, $a:expr, $compare:stmt) => {
+        &{
+            $compare
+            const A: &[$ty] = $crate::const_slice_sort!($ty, $a, $compare);
+            const DUPLICATES: usize = $crate::const_count_dup!(A, $compare);
+            const LEN: usize = A.len() - DUPLICATES;
+
+            let mut out: [_; LEN] = if LEN == 0 {
+                unsafe { ::core::mem::transmute([0u8; ::core::mem::size_of::<$ty>() * LEN]) }
+            } else {
+                [A[0]; LEN]
+            };
+
+            let mut r: usize = 1;
+            let mut w: usize = 1;
+            while r < A.len() {
+                if compare(A[r], out[w - 1]) != 0 {
+                    out[w] = A[r];
+                    w += 1;
+                }
+                r += 1;
+            }
+            out
+        }
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! const_count_dup {
+    ($a:expr, $compare:stmt) => {{
+        $compare
+        let mut count = 0;
+        let mut i = 0;
+        let mut j = 1;
+        while i < $a.len() {
+            while j < $a.len() {
+                if compare($a[i], $a[j]) == 0 {
+                    count += 1;
+                    break;
+                }
+                j += 1;
+            }
+            i += 1;
+            j = i + 1;
+        }
+        count
+    }};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! const_slices_concat {
+    ($ty:ty, $a:expr, $b:expr) => {
+        &{
+            const A: &[$ty] = $a;
+            const B: &[$ty] = $b;
+            let mut out: [_; { A.len() + B.len() }] = if A.len() == 0 && B.len() == 0 {
+                unsafe {
+                    ::core::mem::transmute(
+                        [0u8; ::core::mem::size_of::<$ty>() * (A.len() + B.len())],
+                    )
+                }
+            } else if A.len() == 0 {
+                [B[0]; { A.len() + B.len() }]
+            } else {
+                [A[0]; { A.len() + B.len() }]
+            };
+            let mut i = 0;
+            while i < A.len() {
+                out[i] = A[i];
+                i += 1;
+            }
+            i = 0;
+            while i < B.len() {
+                out[i + A.len()] = B[i];
+                i += 1;
+            }
+            out
+        }
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! const_slice_sort {
+    ($ty:ty, $a:expr, $compare:stmt) => {
+        &{
+            $compare
+            const A: &[$ty] = $a;
+            let mut out: [_; A.len()] = if A.len() == 0 {
+                unsafe { ::core::mem::transmute([0u8; ::core::mem::size_of::<$ty>() * A.len()]) }
+            } else {
+                [A[0]; A.len()]
+            };
+
+            let mut i = 1;
+            while i < A.len() {
+                out[i] = A[i];
+                let mut j = i;
+                while j > 0 && compare(out[j], out[j - 1]) == -1 {
+                    //swap
+                    let tmp = out[j];
+                    out[j] = out[j - 1];
+                    out[j - 1] = tmp;
+
+                    j -= 1;
+                }
+                i += 1;
+            }
+            out
+        }
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! const_slice_iter {
+    ($slice:ident, $map:stmt) => {{
+        $map
+        let mut out: [_; $slice.len()] = if $slice.len() == 0 {
+            #[allow(clippy::missing_transmute_annotations)]
+            unsafe { ::core::mem::transmute([0u8; ::core::mem::size_of::<&str>() * $slice.len()]) }
+        } else {
+            [""; $slice.len()]
+        };
+        let mut i = 0;
+        while i < $slice.len() {
+            out[i] = map($slice[i]);
+            i += 1;
+        }
+        out
+    }};
+}
+
+pub const fn include(a: &[&str], b: &[&str]) -> bool {
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < a.len() && j < b.len() {
+        if eq(a[i], b[j]) {
+            j += 1;
+            i = 0;
+        } else {
+            i += 1;
+        }
+    }
+
+    j == b.len()
+}
+
+pub const fn compare(lhs: &str, rhs: &str) -> i8 {
+    let lhs = lhs.as_bytes();
+    let rhs = rhs.as_bytes();
+    let lhs_len = lhs.len();
+    let rhs_len = rhs.len();
+    let min_len = if lhs_len < rhs_len { lhs_len } else { rhs_len };
+
+    let mut i = 0;
+    while i < min_len {
+        if lhs[i] < rhs[i] {
+            return -1;
+        }
+        if lhs[i] > rhs[i] {
+            return 1;
+        }
+        i += 1;
+    }
+
+    if lhs_len < rhs_len {
+        -1
+    } else if lhs_len > rhs_len {
+        1
+    } else {
+        0
+    }
+}
+
+pub const fn eq(lhs: &str, rhs: &str) -> bool {
+    let lhs = lhs.as_bytes();
+    let rhs = rhs.as_bytes();
+    let lhs_len = lhs.len();
+    let rhs_len = rhs.len();
+
+    if lhs_len != rhs_len {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < lhs_len {
+        if lhs[i] != rhs[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::string::{String, ToString};
+
+    use crate::{
+        domain_identifiers,
+        event::{DomainIdentifierInfo, EventInfo},
+        ident, DomainIdentifierSet, Event, EventSchema, IdentifierType,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ShoppingCartEvent {
+        ItemAdded { item_id: String, cart_id: String },
+        ItemRemoved { item_id: String, cart_id: String },
+    }
+
+    pub fn item_added_event(item_id: &str, cart_id: &str) -> ShoppingCartEvent {
+        ShoppingCartEvent::ItemAdded {
+            item_id: item_id.to_string(),
+            cart_id: cart_id.to_string(),
+        }
+    }
+
+    pub fn item_removed_event(item_id: &str, cart_id: &str) -> ShoppingCartEvent {
+        ShoppingCartEvent::ItemRemoved {
+            item_id: item_id.to_string(),
+            cart_id: cart_id.to_string(),
+        }
+    }
+
+    impl Event for ShoppingCartEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["ItemAdded", "ItemRemoved"],
+            events_info: &[
+                &EventInfo {
+                    name: "ItemAdded",
+                    domain_identifiers: &[&ident!(#item_id), &ident!(#cart_id)],
+                },
+                &EventInfo {
+                    name: "ItemRemoved",
+                    domain_identifiers: &[&ident!(#item_id), &ident!(#cart_id)],
+                },
+            ],
+            domain_identifiers: &[
+                &DomainIdentifierInfo {
+                    ident: ident!(#cart_id),
+                    type_info: IdentifierType::String,
+                },
+                &DomainIdentifierInfo {
+                    ident: ident!(#item_id),
+                    type_info: IdentifierType::String,
+                },
+            ],
+        };
+        fn name(&self) -> &'static str {
+            match self {
+                ShoppingCartEvent::ItemAdded { .. } => "ItemAdded",
+                ShoppingCartEvent::ItemRemoved { .. } => "ItemRemoved",
+            }
+        }
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            match self {
+                ShoppingCartEvent::ItemAdded {
+                    item_id, cart_id, ..
+                } => domain_identifiers! {item_id: item_id, cart_id: cart_id},
+                ShoppingCartEvent::ItemRemoved {
+                    item_id, cart_id, ..
+                } => domain_identifiers! {item_id: item_id, cart_id: cart_id},
+            }
+        }
+        fn payload_fields(&self) -> DomainIdentifierSet {
+            match self {
+                ShoppingCartEvent::ItemAdded { item_id, .. } => {
+                    domain_identifiers! {item_id: item_id}
+                }
+                ShoppingCartEvent::ItemRemoved { .. } => domain_identifiers! {},
+            }
+        }
+    }
+}
@@ -1,15 +1,25 @@
 mod stream;
 
+use heck::{
+    ToKebabCase, ToLowerCamelCase, ToShoutyKebabCase, ToShoutySnakeCase, ToSnakeCase,
+    ToUpperCamelCase,
+};
 use proc_macro2::TokenStream;
 use quote::quote;
 use stream::{impl_stream, streams};
-use syn::{AngleBracketedGenericArguments, Data, DeriveInput, Error, Result};
-use syn::{DataEnum, DataStruct, Fields};
+use syn::ext::IdentExt;
+use syn::{
+    parse_quote, AngleBracketedGenericArguments, Attribute, Data, DeriveInput, Error, Generics,
+    LitStr, Result,
+};
+use syn::{DataEnum, DataStruct, Field, Fields, Meta};
 
-use crate::reserved_identifier_names;
-use crate::symbol::ID;
+use crate::symbol::{EVENT, FILTER, ID, NESTED, PII, RENAME, RENAME_ALL};
+use crate::{fnv1a, mentions_type_params, reserved_identifier_names};
 
 pub fn event_inner(ast: &DeriveInput) -> Result<TokenStream> {
+    validate_id_attributes(ast)?;
+    validate_generic_fields(ast)?;
     match ast.data {
         Data::Enum(ref data) => {
             let derive_event = impl_enum(ast, data)?;
@@ -40,6 +50,178 @@ pub fn event_inner(ast: &DeriveInput) -> Result<TokenStream> {
     }
 }
 
+/// The kinds of the fields marked with the `id` attribute.
+#[derive(PartialEq, Eq)]
+enum IdKind {
+    /// The field is a domain identifier: `#[id]`.
+    Value,
+    /// The field is a struct deriving `Event`, whose domain identifiers are the ones of the event:
+    /// `#[id(nested)]`.
+    Nested,
+}
+
+fn id_kind(field: &Field) -> Result<Option<IdKind>> {
+    let Some(attr) = field.attrs.iter().find(|attr| attr.path() == ID) else {
+        return Ok(None);
+    };
+    if let Meta::Path(_) = attr.meta {
+        return Ok(Some(IdKind::Value));
+    }
+    let mut kind = IdKind::Value;
+    attr.parse_nested_meta(|meta| {
+        if meta.path == NESTED {
+            kind = IdKind::Nested;
+            Ok(())
+        } else {
+            Err(meta.error("invalid argument, expected `nested`"))
+        }
+    })?;
+    Ok(Some(kind))
+}
+
+fn is_identifier(field: &&Field) -> bool {
+    matches!(id_kind(field), Ok(Some(IdKind::Value)))
+}
+
+fn is_nested_identifier(field: &&Field) -> bool {
+    matches!(id_kind(field), Ok(Some(IdKind::Nested)))
+}
+
+fn is_filter(field: &&Field) -> bool {
+    field.attrs.iter().any(|attr| attr.path() == FILTER)
+}
+
+fn is_pii(field: &&Field) -> bool {
+    field.attrs.iter().any(|attr| attr.path() == PII)
+}
+
+/// Returns the names of the fields marked with the `pii` attribute.
+fn pii_fields<'a>(fields: impl Iterator<Item = &'a Field>) -> Vec<String> {
+    fields
+        .filter(is_pii)
+        .filter_map(|f| f.ident.as_ref())
+        .map(|ident| ident.unraw().to_string())
+        .collect()
+}
+
+/// Returns the fields of the struct or of all the variants of the enum.
+fn fields(ast: &DeriveInput) -> Vec<&Field> {
+    match ast.data {
+        Data::Enum(ref data) => data.variants.iter().flat_map(|v| v.fields.iter()).collect(),
+        Data::Struct(ref data) => data.fields.iter().collect(),
+        _ => vec![],
+    }
+}
+
+fn validate_id_attributes(ast: &DeriveInput) -> Result<()> {
+    for field in fields(ast) {
+        id_kind(field)?;
+    }
+    Ok(())
+}
+
+/// Checks that the domain identifiers and the payloads of the variants do not depend on the type
+/// parameters of the event: the schema of the event is computed at compile time, once for all of them.
+fn validate_generic_fields(ast: &DeriveInput) -> Result<()> {
+    let is_enum = matches!(ast.data, Data::Enum(_));
+    for field in fields(ast) {
+        let is_payload = is_enum && field.ident.is_none();
+        if (is_payload || id_kind(field)?.is_some())
+            && mentions_type_params(&field.ty, &ast.generics)
+        {
+            return Err(Error::new_spanned(
+                &field.ty,
+                "domain identifiers and event payloads cannot depend on the type parameters of the event",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the generics of the `Event` implementation, which bound the generic filter fields.
+fn event_generics(ast: &DeriveInput) -> Generics {
+    let mut generics = ast.generics.clone();
+    for field in fields(ast).into_iter().filter(is_filter) {
+        if mentions_type_params(&field.ty, &ast.generics) {
+            let ty = &field.ty;
+            generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#ty: disintegrate::IntoIdentifierValue + Clone));
+        }
+    }
+    generics
+}
+
+/// The arguments of the `#[event]` attribute.
+#[derive(Default)]
+struct EventArgs {
+    rename: Option<LitStr>,
+    rename_all: Option<LitStr>,
+}
+
+impl EventArgs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut args = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path() == EVENT) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path == RENAME {
+                    args.rename = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path == RENAME_ALL {
+                    args.rename_all = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("invalid argument"))
+                }
+            })?;
+        }
+        Ok(args)
+    }
+}
+
+/// Applies a casing policy, named as in serde, to the name of a variant.
+fn apply_case(policy: &LitStr, name: &str) -> Result<String> {
+    Ok(match policy.value().as_str() {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "PascalCase" => name.to_upper_camel_case(),
+        "camelCase" => name.to_lower_camel_case(),
+        "snake_case" => name.to_snake_case(),
+        "SCREAMING_SNAKE_CASE" => name.to_shouty_snake_case(),
+        "kebab-case" => name.to_kebab_case(),
+        "SCREAMING-KEBAB-CASE" => name.to_shouty_kebab_case(),
+        _ => {
+            return Err(Error::new(
+                policy.span(),
+                "unknown casing policy, expected one of: lowercase, UPPERCASE, PascalCase, camelCase, snake_case, SCREAMING_SNAKE_CASE, kebab-case, SCREAMING-KEBAB-CASE",
+            ))
+        }
+    })
+}
+
+/// Returns the names of the events of the enum variants, as stored and queried in the event store.
+///
+/// A variant is named after its identifier, unless it is renamed with `#[event(rename = "...")]` or the
+/// enum declares a casing policy with `#[event(rename_all = "...")]`.
+fn event_names(ast: &DeriveInput, data: &DataEnum) -> Result<Vec<String>> {
+    let enum_args = EventArgs::parse(&ast.attrs)?;
+    data.variants
+        .iter()
+        .map(|variant| {
+            let variant_args = EventArgs::parse(&variant.attrs)?;
+            if let Some(rename) = variant_args.rename {
+                return Ok(rename.value());
+            }
+            let name = variant.ident.to_string();
+            match enum_args.rename_all {
+                Some(ref policy) => apply_case(policy, &name),
+                None => Ok(name),
+            }
+        })
+        .collect()
+}
+
 fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
     let name = ast.ident.clone();
     let no_variants_deref = if data.variants.is_empty() {
@@ -47,14 +229,18 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
     } else {
         quote!()
     };
-    let impl_name = data.variants.iter().map(|variant| {
-        let variant_ident = &variant.ident;
-        let event_name = variant_ident.to_string();
+    let event_names = event_names(ast, data)?;
+    let impl_name = data
+        .variants
+        .iter()
+        .zip(&event_names)
+        .map(|(variant, event_name)| {
+            let variant_ident = &variant.ident;
 
-        quote! {
-            #name::#variant_ident{ .. } => #event_name,
-        }
-    });
+            quote! {
+                #name::#variant_ident{ .. } => #event_name,
+            }
+        });
 
     let impl_domain_identifiers = data.variants.iter().map(|variant| {
         let event_type = &variant.ident;
@@ -66,15 +252,27 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
             Fields::Named(fields) => {
                 let identifiers_fields : Vec<_> = fields.named
                     .iter()
-                    .filter(|f| f.attrs.iter().any(|attr| attr.path() == ID))
+                    .filter(is_identifier)
+                    .flat_map(|f| f.ident.as_ref())
+                    .collect();
+                let nested_fields: Vec<_> = fields.named
+                    .iter()
+                    .filter(is_nested_identifier)
                     .flat_map(|f| f.ident.as_ref())
                     .collect();
 
                 let reserved_identifiers = reserved_identifier_names(&identifiers_fields);
                 quote! {
-                    #name::#event_type{#(#identifiers_fields,)*..} => {
+                    #name::#event_type{#(#identifiers_fields,)* #(#nested_fields,)* ..} => {
                         #reserved_identifiers
-                        disintegrate::domain_identifiers!{#(#identifiers_fields: #identifiers_fields),*}
+                        #[allow(unused_mut)]
+                        let mut domain_identifiers = disintegrate::domain_identifiers!{#(#identifiers_fields: #identifiers_fields),*};
+                        #(
+                            for (key, value) in disintegrate::Event::domain_identifiers(#nested_fields).iter() {
+                                domain_identifiers.insert(disintegrate::DomainIdentifier { key: *key, value: value.clone() });
+                            }
+                        )*
+                        domain_identifiers
                     },
                 }
             },
@@ -85,6 +283,33 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
 
     });
 
+    let impl_payload_fields = data.variants.iter().map(|variant| {
+        let event_type = &variant.ident;
+
+        match &variant.fields {
+            Fields::Unnamed(_fields) => quote! {
+                  #name::#event_type(payload) => payload.payload_fields(),
+            },
+            Fields::Named(fields) => {
+                let filter_fields: Vec<_> = fields
+                    .named
+                    .iter()
+                    .filter(is_filter)
+                    .flat_map(|f| f.ident.as_ref())
+                    .collect();
+
+                quote! {
+                    #name::#event_type{#(#filter_fields,)*..} => {
+                        disintegrate::domain_identifiers!{#(#filter_fields: #filter_fields),*}
+                    },
+                }
+            }
+            Fields::Unit => quote! {
+                     #name::#event_type => disintegrate::domain_identifiers!{},
+            },
+        }
+    });
+
     let domain_identifiers_slice =
         data.variants
             .iter()
@@ -104,7 +329,7 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
                     let identifiers_fields =  fields
                         .named
                         .iter()
-                        .filter(|f| f.attrs.iter().any(|attr| attr.path() == ID));
+                        .filter(is_identifier);
 
                     let identifiers_idents: Vec<_> = identifiers_fields.clone()
                         .map(|f| f.ident.as_ref())
@@ -114,23 +339,21 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
                         .map(|f| f.ty.clone())
                         .collect();
 
-                    quote! {
+                    let acc = quote! {
                         disintegrate::const_slices_concat!(&disintegrate::DomainIdentifierInfo, #acc, &[#(&disintegrate::DomainIdentifierInfo{ident: disintegrate::ident!(##identifiers_idents), type_info: <#identifiers_types as disintegrate::IntoIdentifierValue>::TYPE},)*])
-                    }
+                    };
+                    nested_domain_identifiers_schema(fields.named.iter(), acc)
                 }
                 Fields::Unit => quote!(disintegrate::const_slices_concat!(&disintegrate::DomainIdentifierInfo, #acc, &[])),
             });
 
-    let events = data
-        .variants
-        .iter()
-        .map(|variant| variant.ident.to_string());
+    let events = event_names.iter();
 
     let events_info= data
         .variants
         .iter()
-        .fold(quote!(&[]), |acc, variant| {
-           let variant_ident = &variant.ident.to_string();
+        .zip(&event_names)
+        .fold(quote!(&[]), |acc, (variant, variant_ident)| {
             match &variant.fields {
             Fields::Unnamed(fields) => {
                 let payload_field = fields.unnamed.first().unwrap();
@@ -155,11 +378,15 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
                 let identifiers_idents: Vec<_> = fields
                     .named
                     .iter()
-                    .filter(|f| f.attrs.iter().any(|attr| attr.path() == ID))
+                    .filter(is_identifier)
                     .map(|f| f.ident.as_ref())
                     .collect();
+                let domain_identifiers = nested_event_info_identifiers(
+                    fields.named.iter(),
+                    quote!(&[#(&disintegrate::ident!(##identifiers_idents),)*]),
+                );
                 quote! {
-                    disintegrate::const_slices_concat!(&disintegrate::EventInfo, #acc, &[&disintegrate::EventInfo{name: #variant_ident, domain_identifiers: &[#(&disintegrate::ident!(##identifiers_idents),)*]}])
+                    disintegrate::const_slices_concat!(&disintegrate::EventInfo, #acc, &[&disintegrate::EventInfo{name: #variant_ident, domain_identifiers: #domain_identifiers}])
                 }
             }
             Fields::Unit => quote!(
@@ -176,14 +403,60 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
            result
         })
     };
+    let fingerprints = data.variants.iter().map(|variant| match &variant.fields {
+        Fields::Unnamed(fields) => {
+            let payload_type = enum_unnamed_field_type(fields.unnamed.first().unwrap());
+            quote! {
+                if <#payload_type as disintegrate::Event>::FINGERPRINTS.is_empty() {
+                    0
+                } else {
+                    <#payload_type as disintegrate::Event>::FINGERPRINTS[0]
+                }
+            }
+        }
+        fields => {
+            let fingerprint = fingerprint(fields);
+            quote!(#fingerprint)
+        }
+    });
+
+    let impl_pii_fields = data
+        .variants
+        .iter()
+        .zip(&event_names)
+        .map(|(variant, event_name)| match &variant.fields {
+            Fields::Unnamed(fields) => {
+                let payload_type = enum_unnamed_field_type(fields.unnamed.first().unwrap());
+                quote! {
+                    #event_name => <#payload_type as disintegrate::Event>::pii_fields(
+                        <#payload_type as disintegrate::Event>::SCHEMA.events[0]
+                    ),
+                }
+            }
+            fields => {
+                let pii_fields = pii_fields(fields.iter());
+                quote!(#event_name => &[#(#pii_fields),*],)
+            }
+        });
+
+    let generics = event_generics(ast);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     Ok(quote! {
         #[automatically_derived]
-        impl disintegrate::Event for #name {
+        impl #impl_generics disintegrate::Event for #name #ty_generics #where_clause {
             const SCHEMA: disintegrate::EventSchema = disintegrate::EventSchema {
                 events: &[#(#events,)*],
                 events_info: #events_info,
                 domain_identifiers: #impl_domain_identifiers_schema,
             };
+            const FINGERPRINTS: &'static [u64] = &[#(#fingerprints),*];
+
+            fn pii_fields(event_type: &str) -> &'static [&'static str] {
+                match event_type {
+                    #(#impl_pii_fields)*
+                    _ => &[],
+                }
+            }
 
             fn name(&self) -> &'static str {
                 match #no_variants_deref self {
@@ -196,10 +469,55 @@ fn impl_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
                     #(#impl_domain_identifiers)*
                  }
             }
+
+            fn payload_fields(&self) -> disintegrate::DomainIdentifierSet {
+                match #no_variants_deref self {
+                    #(#impl_payload_fields)*
+                 }
+            }
         }
     })
 }
 
+/// Appends the domain identifiers of the nested fields to the domain identifiers schema `acc`.
+fn nested_domain_identifiers_schema<'a>(
+    fields: impl Iterator<Item = &'a Field>,
+    acc: TokenStream,
+) -> TokenStream {
+    fields.filter(is_nested_identifier).fold(acc, |acc, field| {
+        let ty = &field.ty;
+        quote! {
+            disintegrate::const_slices_concat!(
+                &disintegrate::DomainIdentifierInfo,
+                #acc,
+                <#ty as disintegrate::Event>::SCHEMA.domain_identifiers
+            )
+        }
+    })
+}
+
+/// Appends the domain identifiers of the nested fields to the identifiers `acc` of an event info.
+fn nested_event_info_identifiers<'a>(
+    fields: impl Iterator<Item = &'a Field>,
+    acc: TokenStream,
+) -> TokenStream {
+    fields.filter(is_nested_identifier).fold(acc, |acc, field| {
+        let ty = &field.ty;
+        quote! {
+            disintegrate::const_slices_concat!(
+                &disintegrate::Identifier,
+                #acc,
+                <#ty as disintegrate::Event>::SCHEMA.events_info[0].domain_identifiers
+            )
+        }
+    })
+}
+
+/// Computes the fingerprint of the shape of the payload of an event.
+fn fingerprint(fields: &Fields) -> u64 {
+    fnv1a(&quote!(#fields).to_string())
+}
+
 fn enum_unnamed_field_type(payload_field: &syn::Field) -> &syn::Type {
     if let syn::Type::Path(ref ty_path) = payload_field.ty {
         let last_segment = ty_path.path.segments.last().expect("one path segment");
@@ -221,12 +539,12 @@ fn enum_unnamed_field_type(payload_field: &syn::Field) -> &syn::Type {
 
 fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> Result<TokenStream> {
     let name = ast.ident.clone();
-    let impl_type = name.to_string();
+    let impl_type = EventArgs::parse(&ast.attrs)?
+        .rename
+        .map(|rename| rename.value())
+        .unwrap_or_else(|| name.to_string());
 
-    let identifiers_fields = data
-        .fields
-        .iter()
-        .filter(|f| f.attrs.iter().any(|attr| attr.path() == ID));
+    let identifiers_fields = data.fields.iter().filter(is_identifier);
 
     let identifiers_idents: Vec<_> = identifiers_fields
         .clone()
@@ -235,16 +553,49 @@ fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> Result<TokenStream> {
 
     let identifiers_types: Vec<_> = identifiers_fields.clone().map(|f| f.ty.clone()).collect();
 
+    let nested_idents: Vec<_> = data
+        .fields
+        .iter()
+        .filter(is_nested_identifier)
+        .filter_map(|f| f.ident.as_ref())
+        .collect();
+
     let reserved_identifiers = reserved_identifier_names(&identifiers_idents);
 
+    let events_info_identifiers = nested_event_info_identifiers(
+        data.fields.iter(),
+        quote!(&[#(&disintegrate::ident!(##identifiers_idents),)*]),
+    );
+    let domain_identifiers_schema = nested_domain_identifiers_schema(
+        data.fields.iter(),
+        quote!(&[#(&disintegrate::DomainIdentifierInfo{ident: disintegrate::ident!(##identifiers_idents), type_info: <#identifiers_types as disintegrate::IntoIdentifierValue>::TYPE},)*]),
+    );
+
+    let filter_idents: Vec<_> = data
+        .fields
+        .iter()
+        .filter(is_filter)
+        .filter_map(|f| f.ident.as_ref())
+        .collect();
+
+    let fingerprint = fingerprint(&data.fields);
+    let pii_fields = pii_fields(data.fields.iter());
+
+    let generics = event_generics(ast);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     Ok(quote! {
         #[automatically_derived]
-        impl disintegrate::Event for #name {
+        impl #impl_generics disintegrate::Event for #name #ty_generics #where_clause {
             const SCHEMA: disintegrate::EventSchema = disintegrate::EventSchema{
                 events: &[#impl_type],
-                events_info: &[&disintegrate::EventInfo{name: #impl_type, domain_identifiers: &[#(&disintegrate::ident!(##identifiers_idents),)*]}],
-                domain_identifiers:&[#(&disintegrate::DomainIdentifierInfo{ident: disintegrate::ident!(##identifiers_idents), type_info: <#identifiers_types as disintegrate::IntoIdentifierValue>::TYPE},)*]
+                events_info: &[&disintegrate::EventInfo{name: #impl_type, domain_identifiers: #events_info_identifiers}],
+                domain_identifiers: #domain_identifiers_schema
             };
+            const FINGERPRINTS: &'static [u64] = &[#fingerprint];
+
+            fn pii_fields(_event_type: &str) -> &'static [&'static str] {
+                &[#(#pii_fields),*]
+            }
 
             fn name(&self) -> &'static str {
                 #impl_type
@@ -252,7 +603,18 @@ fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> Result<TokenStream> {
 
             fn domain_identifiers(&self) -> disintegrate::DomainIdentifierSet {
                 #reserved_identifiers
-                disintegrate::domain_identifiers!{#(#identifiers_idents: self.#identifiers_idents),*}
+                #[allow(unused_mut)]
+                let mut domain_identifiers = disintegrate::domain_identifiers!{#(#identifiers_idents: self.#identifiers_idents),*};
+                #(
+                    for (key, value) in disintegrate::Event::domain_identifiers(&self.#nested_idents).iter() {
+                        domain_identifiers.insert(disintegrate::DomainIdentifier { key: *key, value: value.clone() });
+                    }
+                )*
+                domain_identifiers
+            }
+
+            fn payload_fields(&self) -> disintegrate::DomainIdentifierSet {
+                disintegrate::domain_identifiers!{#(#filter_idents: self.#filter_idents),*}
             }
         }
     })
@@ -6,9 +6,11 @@ use syn::{
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
     token::Comma,
-    Data, DeriveInput, Error, Field, Ident, Result, Token, Type, Variant,
+    Data, DeriveInput, Error, Field, GenericParam, Generics, Ident, Result, Token, Type, Variant,
 };
 
+use crate::mentions_type_params;
+
 #[derive(Debug)]
 pub struct QueryArgs {
     name: Ident,
@@ -54,26 +56,61 @@ pub fn streams(ast: &DeriveInput) -> Result<Vec<DeriveInput>> {
             stream_data.variants = event_data
                 .variants
                 .iter()
-                .filter(|variant| {
-                    selected_variants
-                        .iter()
-                        .any(|selected| variant.ident == *selected)
-                })
+                .filter(|variant| selected_variants.contains(&variant.ident))
                 .cloned()
                 .collect();
 
             let mut stream = ast.clone();
             stream.ident = stream_ident;
+            stream.generics = stream_generics(&ast.generics, &stream_data.variants);
             stream.data = Data::Enum(stream_data);
-            stream.attrs = vec![];
+            // Keep the event names of the parent enum
+            stream.attrs.retain(|attr| attr.path().is_ident("event"));
 
             Ok(stream)
         })
         .collect()
 }
 
+/// Returns the generics of the parent enum without the type parameters unused by the variants of a stream.
+fn stream_generics<'a>(
+    generics: &Generics,
+    variants: impl IntoIterator<Item = &'a Variant>,
+) -> Generics {
+    let fields: Vec<&Type> = variants
+        .into_iter()
+        .flat_map(|variant| variant.fields.iter().map(|field| &field.ty))
+        .collect();
+    let (unused_params, used_params): (Vec<GenericParam>, Vec<GenericParam>) =
+        generics.params.iter().cloned().partition(|param| {
+            let GenericParam::Type(_) = param else {
+                return false;
+            };
+            let mut param_generics = Generics::default();
+            param_generics.params.push(param.clone());
+            !fields
+                .iter()
+                .any(|field| mentions_type_params(field, &param_generics))
+        });
+
+    let mut unused = Generics::default();
+    unused.params.extend(unused_params);
+    let mut stream_generics = generics.clone();
+    stream_generics.params = used_params.into_iter().collect();
+    if let Some(where_clause) = stream_generics.where_clause.as_mut() {
+        where_clause.predicates = where_clause
+            .predicates
+            .iter()
+            .filter(|predicate| !mentions_type_params(predicate, &unused))
+            .cloned()
+            .collect();
+    }
+    stream_generics
+}
+
 pub fn impl_stream(parent: &DeriveInput, stream: &DeriveInput) -> Result<TokenStream> {
     let mut stream = stream.clone();
+    stream.attrs = vec![];
     let stream_ident = &stream.ident;
     let parent_ident = &parent.ident;
 
@@ -87,16 +124,16 @@ pub fn impl_stream(parent: &DeriveInput, stream: &DeriveInput) -> Result<TokenSt
         )),
     }?;
 
-    stream_data
-        .variants
-        .iter_mut()
-        .for_each(|variant| match &mut variant.fields {
+    stream_data.variants.iter_mut().for_each(|variant| {
+        variant.attrs.retain(|attr| !attr.path().is_ident("event"));
+        match &mut variant.fields {
             syn::Fields::Named(fields) => {
                 fields.named.iter_mut().for_each(|f| f.attrs = vec![]);
             }
             syn::Fields::Unnamed(_) => (),
             syn::Fields::Unit => (),
-        });
+        }
+    });
 
     let pats: Vec<TokenStream> = stream_data
         .variants
@@ -110,7 +147,7 @@ pub fn impl_stream(parent: &DeriveInput, stream: &DeriveInput) -> Result<TokenSt
 
     let try_from_event_arms = pats
         .iter()
-        .map(|pat| quote!(#parent_ident::#pat => std::result::Result::Ok(#stream_ident::#pat)));
+        .map(|pat| quote!(#parent_ident::#pat => ::core::result::Result::Ok(#stream_ident::#pat)));
 
     let vis = &stream.vis;
     let (_stream_impl, stream_ty, _stream_where) = stream.generics.split_for_impl();
@@ -125,16 +162,16 @@ pub fn impl_stream(parent: &DeriveInput, stream: &DeriveInput) -> Result<TokenSt
         #[derive(Copy, Clone, Debug)]
         #vis struct #error;
 
-        impl std::fmt::Display for #error {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                std::fmt::Debug::fmt(self, f)
+        impl ::core::fmt::Display for #error {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(self, f)
             }
         }
 
-        impl std::error::Error for #error {}
+        impl ::core::error::Error for #error {}
 
         #[automatically_derived]
-        impl #event_impl std::convert::From<#stream_ident #stream_ty> for #parent_ident #event_ty #event_where {
+        impl #event_impl ::core::convert::From<#stream_ident #stream_ty> for #parent_ident #event_ty #event_where {
             fn from(child: #stream_ident #stream_ty) -> Self {
                 match child {
                     #(#from_stream_arms),*
@@ -143,13 +180,13 @@ pub fn impl_stream(parent: &DeriveInput, stream: &DeriveInput) -> Result<TokenSt
         }
 
         #[automatically_derived]
-        impl #event_impl std::convert::TryFrom<#parent_ident #event_ty> for #stream_ident #stream_ty #event_where {
+        impl #event_impl ::core::convert::TryFrom<#parent_ident #event_ty> for #stream_ident #stream_ty #event_where {
             type Error = #error;
 
-            fn try_from(parent: #parent_ident #event_ty) -> std::result::Result<Self, Self::Error> {
+            fn try_from(parent: #parent_ident #event_ty) -> ::core::result::Result<Self, Self::Error> {
                 match parent {
                     #(#try_from_event_arms),*,
-                    _ => std::result::Result::Err(#error)
+                    _ => ::core::result::Result::Err(#error)
                 }
             }
         }
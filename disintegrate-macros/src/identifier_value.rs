@@ -0,0 +1,61 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Data, DeriveInput, Error, Fields, Index, Member, Result};
+
+pub fn identifier_value_inner(ast: &DeriveInput) -> Result<TokenStream> {
+    let name = &ast.ident;
+    let fields = match ast.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "`IntoIdentifierValue` can only be derived for newtype structs",
+            ))
+        }
+    };
+    let field = match fields {
+        Fields::Named(named) if named.named.len() == 1 => named.named.first().unwrap(),
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => unnamed.unnamed.first().unwrap(),
+        _ => {
+            return Err(Error::new_spanned(
+                fields,
+                "`IntoIdentifierValue` can only be derived for structs with a single field",
+            ))
+        }
+    };
+    let member = field
+        .ident
+        .clone()
+        .map(Member::Named)
+        .unwrap_or_else(|| Member::Unnamed(Index::from(0)));
+    let ty = &field.ty;
+
+    let mut generics = ast.generics.clone();
+    if !generics.params.is_empty() {
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#ty: disintegrate::IntoIdentifierValue + Clone));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics disintegrate::IntoIdentifierValue for #name #ty_generics #where_clause {
+            const TYPE: disintegrate::IdentifierType = <#ty as disintegrate::IntoIdentifierValue>::TYPE;
+
+            fn into_identifier_value(self) -> disintegrate::IdentifierValue {
+                disintegrate::IntoIdentifierValue::into_identifier_value(self.#member)
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics disintegrate::IntoIdentifierValue for &#name #ty_generics #where_clause {
+            const TYPE: disintegrate::IdentifierType = <#ty as disintegrate::IntoIdentifierValue>::TYPE;
+
+            fn into_identifier_value(self) -> disintegrate::IdentifierValue {
+                disintegrate::IntoIdentifierValue::into_identifier_value(self.#member.clone())
+            }
+        }
+    })
+}
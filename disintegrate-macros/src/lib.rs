@@ -1,13 +1,15 @@
 mod event;
+mod identifier_value;
 mod state_query;
 mod symbol;
 
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::{Ident, TokenStream as TokenStream2};
+use proc_macro2::{Ident, TokenStream as TokenStream2, TokenTree};
 
-use syn::{parse_macro_input, DeriveInput};
+use quote::ToTokens;
+use syn::{parse_macro_input, DeriveInput, Generics};
 
 /// Derives the `Event` trait for an enum, allowing it to be used as an event in Disintegrate.
 ///
@@ -16,7 +18,9 @@ use syn::{parse_macro_input, DeriveInput};
 ///
 /// The `Event` trait can be customized using attributes. The `id` attribute can be used to specify
 /// the domain identifier of an event, while the `stream` attribute can be used to stream related
-/// events together.
+/// events together. The `filter` attribute marks the payload fields that stream queries can filter on
+/// with `StreamQuery::filter_payload`, while the `pii` attribute marks the fields holding personal data,
+/// which a `Redactor` masks wherever the payloads leave the event store.
 ///
 /// # Example
 ///
@@ -53,7 +57,26 @@ use syn::{parse_macro_input, DeriveInput};
 /// In this example, the `OrderEvent` enum is marked as an event by deriving the `Event` trait. The
 /// `#[stream]` attribute specifies the event stream name and the list of variants to include in the stream, while the `#[id]` attribute is used
 /// to specify the domain identifiers of each variant.
-#[proc_macro_derive(Event, attributes(stream, id))]
+///
+/// Variants whose payload is a struct deriving `Event` take the domain identifiers of the struct. A field
+/// of a variant can be such a struct too: marking it with `#[id(nested)]` adds the domain identifiers of
+/// the struct to the ones of the variant, so rich payload types keep their identifiers where they belong.
+///
+/// The name of an event, as stored and queried in the event store, is the name of its variant. The `event`
+/// attribute decouples it from the Rust code: `#[event(rename = "OrderPlacedV2")]` renames a variant or
+/// a struct, while `#[event(rename_all = "snake_case")]` on the enum applies a casing policy to all the
+/// variants that are not renamed. The policies are the ones of serde: `lowercase`, `UPPERCASE`,
+/// `PascalCase`, `camelCase`, `snake_case`, `SCREAMING_SNAKE_CASE`, `kebab-case` and
+/// `SCREAMING-KEBAB-CASE`. The event names must be used in `event_types!` and `exclude_events`.
+///
+/// Generic enums and structs, such as `LedgerEvent<Currency>`, are supported. The schema of an event is
+/// computed at compile time, so the domain identifiers and the payloads of the variants cannot depend on
+/// the type parameters, while the other fields can. A generic `#[filter]` field requires its type to
+/// implement `IntoIdentifierValue`, and a stream only keeps the type parameters used by its variants.
+///
+/// The derive also fingerprints the payload of each event, so that `Event::descriptors` lists the name,
+/// the domain identifiers and the fingerprint of every event for tooling and schema validation.
+#[proc_macro_derive(Event, attributes(stream, id, filter, event, pii))]
 pub fn event(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     event::event_inner(&ast)
@@ -71,6 +94,13 @@ pub fn event(input: TokenStream) -> TokenStream {
 /// It is also possible to rename a state using the `rename` argument in the `state_query` attribute. This feature is beneficial
 /// for snapshotting, and the name specified in `rename` is used to identify the snapshot.
 ///
+/// A state query can be limited to a suffix of the event stream with the `last_events` argument, which folds only
+/// the last N matching events, or with the `within_secs` argument, which folds only the events appended in the last
+/// given seconds. Windowed state queries are never snapshotted.
+///
+/// The fields marked with the `filter` attribute restrict the stream query to the events whose payload field with
+/// the same name has the value of the field, see `StreamQuery::filter_payload`.
+///
 /// # Example
 ///
 /// ```rust
@@ -98,7 +128,38 @@ pub fn event(input: TokenStream) -> TokenStream {
 /// indicating its role as a state query. The `#[state_query]` attribute specifies the associated event type,
 /// and the `#[id]` attribute is used to define the domain identifiers. The `#[state_query]` attribute with `rename`
 /// renames the state to 'user-query-v1' for snapshotting purposes.
-#[proc_macro_derive(StateQuery, attributes(state_query, id))]
+///
+/// Fields that only hold bookkeeping data, such as caches or counters, can be marked with `#[state(skip)]`.
+/// They are not part of the fingerprint of the state query, so adding or changing them does not
+/// invalidate the existing snapshots, and they cannot be combined with `#[id]` or `#[filter]`. Pair the
+/// attribute with `#[serde(skip)]` to initialize them with their `Default` value when a snapshot is loaded.
+///
+/// Generic state queries, such as `#[state_query(LedgerEvent<C>)] struct Balance<C>`, are supported too.
+/// Since the event type is generic, the domain identifiers of their query are not checked at compile time.
+///
+/// The `stream_query` attribute refines the derived query when it is almost, but not quite, the right one:
+/// `exclude_events = [CouponRevoked]` excludes event types, while `filter(region == "eu")` and
+/// `exclude(status == "archived")` add constraints on domain identifiers. The values are expressions
+/// evaluated by `query`, so they can refer to the fields of the state with `self`.
+///
+/// ```rust
+/// # use disintegrate::Event;
+/// # #[derive(Event, Clone)]
+/// # enum CartEvent {
+/// #     ItemAdded { #[id] cart_id: String, #[id] region: String },
+/// #     CouponRevoked { #[id] cart_id: String },
+/// # }
+/// use disintegrate::StateQuery;
+///
+/// #[derive(StateQuery, Clone)]
+/// #[state_query(CartEvent)]
+/// #[stream_query(exclude_events = [CouponRevoked], filter(region == "eu"))]
+/// struct EuCart {
+///     #[id]
+///     cart_id: String,
+/// }
+/// ```
+#[proc_macro_derive(StateQuery, attributes(state_query, stream_query, id, filter, state))]
 pub fn state_query(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     state_query::state_query_inner(&ast)
@@ -106,6 +167,36 @@ pub fn state_query(input: TokenStream) -> TokenStream {
         .into()
 }
 
+/// Derives the `IntoIdentifierValue` trait for a newtype, so that it can be used as a domain identifier.
+///
+/// The struct must have a single field, whose type implements `IntoIdentifierValue` and `Clone`: the
+/// identifier value is the one of the field, with the same type.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::{Event, IntoIdentifierValue};
+///
+/// #[derive(Clone, IntoIdentifierValue)]
+/// struct CartId(String);
+///
+/// #[derive(Event, Clone)]
+/// enum CartEvent {
+///     ItemAdded {
+///         #[id]
+///         cart_id: CartId,
+///         item_id: String,
+///     },
+/// }
+/// ```
+#[proc_macro_derive(IntoIdentifierValue)]
+pub fn into_identifier_value(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    identifier_value::identifier_value_inner(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 fn reserved_identifier_names(identifiers_fields: &[&Ident]) -> Option<TokenStream2> {
     const RESERVED_NAMES: &[&str] = &["event_id", "payload", "event_type", "inserted_at"];
 
@@ -120,3 +211,22 @@ fn reserved_identifier_names(identifiers_fields: &[&Ident]) -> Option<TokenStrea
             .to_compile_error()
         })
 }
+
+/// Returns whether the tokens mention one of the type parameters of the generics.
+fn mentions_type_params(tokens: &impl ToTokens, generics: &Generics) -> bool {
+    fn walk(tokens: TokenStream2, generics: &Generics) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => generics.type_params().any(|param| param.ident == ident),
+            TokenTree::Group(group) => walk(group.stream(), generics),
+            _ => false,
+        })
+    }
+    walk(tokens.to_token_stream(), generics)
+}
+
+/// Computes the FNV-1a hash of a string, used for the fingerprints of the derived types.
+fn fnv1a(shape: &str) -> u64 {
+    shape.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,14 +1,21 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
 use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::token::Comma;
-use syn::{Data, DeriveInput, Error};
-use syn::{DataStruct, LitStr};
+use syn::{bracketed, parenthesized, parse_quote, Attribute, Data, DeriveInput, Error, Expr, Type};
+use syn::{DataStruct, Field, Fields, LitInt, LitStr, Token};
 
-use crate::symbol::{ID, RENAME, STATE_QUERY};
+use crate::symbol::{
+    EXCLUDE, EXCLUDE_EVENTS, FILTER, ID, LAST_EVENTS, RENAME, SKIP, STATE, STATE_QUERY,
+    STREAM_QUERY, WITHIN_SECS,
+};
+use crate::{fnv1a, mentions_type_params};
 
 enum StateQueryOptionalArgs {
     Rename(LitStr),
+    LastEvents(LitInt),
+    WithinSecs(LitInt),
 }
 
 impl Parse for StateQueryOptionalArgs {
@@ -21,18 +28,28 @@ impl Parse for StateQueryOptionalArgs {
             return Ok(Self::Rename(value));
         }
 
+        if name == LAST_EVENTS {
+            let value = input.parse::<LitInt>()?;
+            return Ok(Self::LastEvents(value));
+        }
+
+        if name == WITHIN_SECS {
+            let value = input.parse::<LitInt>()?;
+            return Ok(Self::WithinSecs(value));
+        }
+
         Err(Error::new(name.span(), "invalid argument"))
     }
 }
 
 struct StateQueryArgs {
-    event: Ident,
+    event: Type,
     optional_args: Vec<StateQueryOptionalArgs>,
 }
 
 impl Parse for StateQueryArgs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let event = input.parse::<Ident>()?;
+        let event = input.parse::<Type>()?;
 
         let comma = input.parse::<Comma>().ok();
 
@@ -51,6 +68,113 @@ impl Parse for StateQueryArgs {
     }
 }
 
+/// An event of the `exclude_events` argument of the `stream_query` attribute.
+enum EventName {
+    Ident(Ident),
+    Str(LitStr),
+}
+
+impl Parse for EventName {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            Ok(Self::Str(input.parse()?))
+        } else {
+            Ok(Self::Ident(input.parse()?))
+        }
+    }
+}
+
+/// An argument of the `stream_query` attribute, which customizes the stream query of the state query.
+enum StreamQueryArgs {
+    /// `exclude_events = [ItemRemoved, "item-archived"]`
+    ExcludeEvents(Vec<EventName>),
+    /// `filter(region == "eu")`
+    Filter(Ident, Expr),
+    /// `exclude(status == "archived")`
+    Exclude(Ident, Expr),
+}
+
+impl Parse for StreamQueryArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse::<Ident>()?;
+
+        if name == EXCLUDE_EVENTS {
+            input.parse::<syn::token::Eq>()?;
+            let content;
+            bracketed!(content in input);
+            let events = content.parse_terminated(EventName::parse, Comma)?;
+            return Ok(Self::ExcludeEvents(events.into_iter().collect()));
+        }
+
+        if name == FILTER || name == EXCLUDE {
+            let content;
+            parenthesized!(content in input);
+            let ident = content.parse::<Ident>()?;
+            content.parse::<Token![==]>()?;
+            let value = content.parse::<Expr>()?;
+            return Ok(if name == FILTER {
+                Self::Filter(ident, value)
+            } else {
+                Self::Exclude(ident, value)
+            });
+        }
+
+        Err(Error::new(name.span(), "invalid argument"))
+    }
+}
+
+/// Returns the calls that apply the `stream_query` attributes to the stream query of the state query.
+///
+/// The identifiers and the events are checked at compile time, unless the event type is generic.
+fn impl_stream_query_args(
+    attrs: &[&Attribute],
+    event_type: &Type,
+    checked: bool,
+) -> syn::Result<Vec<TokenStream>> {
+    let ident = |ident: &Ident| {
+        if checked {
+            quote!(disintegrate::ident!(#event_type, ##ident))
+        } else {
+            quote!(disintegrate::ident!(##ident))
+        }
+    };
+    let mut calls = vec![];
+    for attr in attrs {
+        let args = attr.parse_args_with(Punctuated::<StreamQueryArgs, Comma>::parse_terminated)?;
+        for arg in args {
+            calls.push(match arg {
+                StreamQueryArgs::ExcludeEvents(events) => {
+                    let idents: Vec<_> = events
+                        .iter()
+                        .filter_map(|event| match event {
+                            EventName::Ident(ident) => Some(ident),
+                            EventName::Str(_) => None,
+                        })
+                        .collect();
+                    if checked && idents.len() == events.len() {
+                        quote!(.exclude_events(disintegrate::event_types!(#event_type, [#(#idents),*])))
+                    } else {
+                        let names = events.iter().map(|event| match event {
+                            EventName::Ident(ident) => ident.to_string(),
+                            EventName::Str(name) => name.value(),
+                        });
+                        quote!(.exclude_events(&[#(#names),*]))
+                    }
+                }
+                StreamQueryArgs::Filter(key, value) => {
+                    let key = ident(&key);
+                    quote!(.filter_in(#key, [(#value).clone()]))
+                }
+                StreamQueryArgs::Exclude(key, value) => {
+                    let key = ident(&key);
+                    quote!(.exclude_identifier(#key, (#value).clone()))
+                }
+            });
+        }
+    }
+    Ok(calls)
+}
+
 pub fn state_query_inner(ast: &DeriveInput) -> Result<TokenStream, Error> {
     match ast.data {
         Data::Struct(ref data) => impl_struct(ast, data),
@@ -82,12 +206,41 @@ fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> syn::Result<TokenStream>
     let state_query_name = state_query_attrs
         .optional_args
         .iter()
-        .map(|attrs| {
-            let StateQueryOptionalArgs::Rename(rename) = attrs;
-            rename.value()
+        .filter_map(|attrs| match attrs {
+            StateQueryOptionalArgs::Rename(rename) => Some(rename.value()),
+            _ => None,
         })
         .next_back()
         .unwrap_or_else(|| state_query_ident.to_string());
+    let window = state_query_attrs
+        .optional_args
+        .iter()
+        .filter_map(|attrs| match attrs {
+            StateQueryOptionalArgs::LastEvents(events) => Some(quote! {
+                const WINDOW: Option<disintegrate::HydrationWindow> =
+                    Some(disintegrate::HydrationWindow::LastEvents(#events));
+            }),
+            StateQueryOptionalArgs::WithinSecs(secs) => Some(quote! {
+                const WINDOW: Option<disintegrate::HydrationWindow> =
+                    Some(disintegrate::HydrationWindow::Within(::core::time::Duration::from_secs(#secs)));
+            }),
+            _ => None,
+        })
+        .next_back();
+
+    for field in data.fields.iter() {
+        if is_skipped(field)?
+            && field
+                .attrs
+                .iter()
+                .any(|attr| attr.path() == ID || attr.path() == FILTER)
+        {
+            return Err(Error::new_spanned(
+                field,
+                format!("a `{STATE}({SKIP})` field cannot be an `{ID}` or a `{FILTER}`"),
+            ));
+        }
+    }
 
     let identifiers_fields: Vec<_> = data
         .fields
@@ -96,31 +249,87 @@ fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> syn::Result<TokenStream>
         .flat_map(|f| f.ident.as_ref())
         .collect();
 
-    let state_query = impl_state_query(event_type.clone(), &identifiers_fields);
+    let filter_fields: Vec<_> = data
+        .fields
+        .iter()
+        .filter(|f| f.attrs.iter().any(|attr| attr.path() == FILTER))
+        .flat_map(|f| f.ident.as_ref())
+        .collect();
+
+    // The identifiers of a generic event type cannot be checked at compile time.
+    let checked = !mentions_type_params(&event_type, &ast.generics);
+    let state_query = impl_state_query(&event_type, &identifiers_fields, checked);
+    let stream_query_attrs: Vec<_> = ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path() == STREAM_QUERY)
+        .collect();
+    let stream_query_args = impl_stream_query_args(&stream_query_attrs, &event_type, checked)?;
+    let fingerprint = fingerprint(&event_type, data, &stream_query_attrs);
+
+    let mut generics = ast.generics.clone();
+    if !generics.params.is_empty() {
+        let (_, ty_generics, _) = ast.generics.split_for_impl();
+        let where_clause = generics.make_where_clause();
+        where_clause
+            .predicates
+            .push(parse_quote!(#state_query_ident #ty_generics: Clone + Send + Sync));
+        where_clause
+            .predicates
+            .push(parse_quote!(#event_type: disintegrate::Event + Clone + Send + Sync));
+        for field in data.fields.iter().filter(|f| {
+            f.attrs
+                .iter()
+                .any(|attr| attr.path() == ID || attr.path() == FILTER)
+        }) {
+            if mentions_type_params(&field.ty, &ast.generics) {
+                let ty = &field.ty;
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#ty: disintegrate::IntoIdentifierValue + Clone));
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut from_generics = generics.clone();
+    from_generics
+        .params
+        .push(parse_quote!(__ID: disintegrate::EventId));
+    from_generics
+        .params
+        .push(parse_quote!(__E: disintegrate::Event + Clone));
+    from_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(
+            <#state_query_ident #ty_generics as disintegrate::StateQuery>::Event: Into<__E>
+        ));
+    let (from_impl_generics, _, from_where_clause) = from_generics.split_for_impl();
 
     Ok(quote! {
         #[automatically_derived]
-        impl disintegrate::StateQuery for #state_query_ident {
+        impl #impl_generics disintegrate::StateQuery for #state_query_ident #ty_generics #where_clause {
             const NAME: &'static str = #state_query_name;
+            const FINGERPRINT: u64 = #fingerprint;
+            #window
 
             type Event = #event_type;
 
             fn query<ID: disintegrate::EventId>(&self) -> disintegrate::StreamQuery<ID, Self::Event> {
                 #state_query
+                    #(.filter_payload(disintegrate::ident!(##filter_fields), self.#filter_fields.clone()))*
+                    #(#stream_query_args)*
             }
         }
 
-        impl<ID, E> From<#state_query_ident> for disintegrate::StreamQuery<ID, E>
-        where
-            ID: disintegrate::EventId,
-            E: disintegrate::Event + Clone, <#state_query_ident as disintegrate::StateQuery>::Event: Into<E>
-         {
-            fn from(state: #state_query_ident) -> Self {
+        impl #from_impl_generics From<#state_query_ident #ty_generics> for disintegrate::StreamQuery<__ID, __E> #from_where_clause {
+            fn from(state: #state_query_ident #ty_generics) -> Self {
                 state.query().cast()
             }
         }
 
-        impl #state_query_ident {
+        impl #impl_generics #state_query_ident #ty_generics #where_clause {
             pub fn exclude_events<ID: disintegrate::EventId>(&self, events: &'static [&'static str]) -> disintegrate::StreamQuery<ID, <Self as disintegrate::StateQuery>::Event> {
                 self.query().exclude_events(events)
             }
@@ -129,11 +338,56 @@ fn impl_struct(ast: &DeriveInput, data: &DataStruct) -> syn::Result<TokenStream>
     })
 }
 
-fn impl_state_query(event_type: Ident, identifiers_fields: &[&Ident]) -> TokenStream {
+/// Returns whether the field is marked with `#[state(skip)]`.
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skipped = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path() == STATE) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path == SKIP {
+                skipped = true;
+                Ok(())
+            } else {
+                Err(meta.error("invalid argument, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skipped)
+}
+
+/// Computes the FNV-1a hash of the fields definition, the event type and the `stream_query` attributes of
+/// the state query.
+///
+/// The fields marked with `#[state(skip)]` are not part of the fingerprint.
+fn fingerprint(event_type: &Type, data: &DataStruct, stream_query_attrs: &[&Attribute]) -> u64 {
+    let mut fields = data.fields.clone();
+    if let Fields::Named(named) = &mut fields {
+        named.named = std::mem::take(&mut named.named)
+            .into_pairs()
+            .filter(|pair| !matches!(is_skipped(pair.value()), Ok(true)))
+            .collect();
+    }
+    let mut shape = format!("{}:{}", quote!(#event_type), quote!(#fields));
+    if !stream_query_attrs.is_empty() {
+        shape.push_str(&format!(":{}", quote!(#(#stream_query_attrs)*)));
+    }
+    fnv1a(&shape)
+}
+
+fn impl_state_query(
+    event_type: &Type,
+    identifiers_fields: &[&Ident],
+    checked: bool,
+) -> TokenStream {
     if identifiers_fields.is_empty() {
         quote! {
             disintegrate::query!(#event_type)
         }
+    } else if !checked {
+        quote! {
+            disintegrate::query::<_, #event_type, #event_type>(Some(disintegrate::StreamFilter::new(
+                disintegrate::domain_identifiers!{#(#identifiers_fields: self.#identifiers_fields),*}
+            )))
+        }
     } else {
         let filters = impl_state_filters(identifiers_fields);
         quote! {
@@ -5,8 +5,20 @@ use syn::{Ident, Path};
 pub struct Symbol(&'static str);
 
 pub const RENAME: Symbol = Symbol("rename");
+pub const RENAME_ALL: Symbol = Symbol("rename_all");
+pub const EVENT: Symbol = Symbol("event");
+pub const LAST_EVENTS: Symbol = Symbol("last_events");
+pub const WITHIN_SECS: Symbol = Symbol("within_secs");
 pub const STATE_QUERY: Symbol = Symbol("state_query");
 pub const ID: Symbol = Symbol("id");
+pub const FILTER: Symbol = Symbol("filter");
+pub const PII: Symbol = Symbol("pii");
+pub const NESTED: Symbol = Symbol("nested");
+pub const STATE: Symbol = Symbol("state");
+pub const SKIP: Symbol = Symbol("skip");
+pub const STREAM_QUERY: Symbol = Symbol("stream_query");
+pub const EXCLUDE_EVENTS: Symbol = Symbol("exclude_events");
+pub const EXCLUDE: Symbol = Symbol("exclude");
 
 impl PartialEq<Symbol> for Ident {
     fn eq(&self, word: &Symbol) -> bool {
@@ -4,6 +4,8 @@ use disintegrate::{ident, DomainIdentifierInfo, Event, IdentifierType, IntoIdent
 struct UserUpdatedData {
     #[id]
     user_id: String,
+    #[filter]
+    #[pii]
     email: String,
 }
 
@@ -21,7 +23,10 @@ enum DomainEvent {
     UserCreated {
         #[id]
         user_id: String,
+        #[pii]
         name: String,
+        #[filter]
+        #[pii]
         email: String,
     },
     UserUpdated(UserUpdatedData),
@@ -53,6 +58,245 @@ fn it_correctly_sets_event_names() {
     );
 }
 
+#[test]
+fn it_describes_the_events() {
+    let descriptors = DomainEvent::descriptors();
+
+    assert_eq!(
+        descriptors
+            .iter()
+            .map(|descriptor| descriptor.name)
+            .collect::<Vec<_>>(),
+        DomainEvent::SCHEMA.events
+    );
+    let user_created = DomainEvent::descriptor("UserCreated").unwrap();
+    assert_eq!(
+        user_created.domain_identifiers,
+        vec![DomainIdentifierInfo {
+            ident: ident!(#user_id),
+            type_info: IdentifierType::String
+        }]
+    );
+    assert_ne!(user_created.fingerprint, 0);
+    assert_ne!(
+        user_created.fingerprint,
+        DomainEvent::descriptor("OrderCreated").unwrap().fingerprint
+    );
+    assert_eq!(
+        DomainEvent::descriptor("UserUpdated").unwrap().fingerprint,
+        UserUpdatedData::FINGERPRINTS[0]
+    );
+    assert_eq!(UserEvent::descriptor("UserCreated").unwrap(), user_created);
+    assert_eq!(DomainEvent::descriptor("UserRenamed"), None);
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[event(rename_all = "snake_case")]
+#[stream(PaymentStream, [PaymentReceived, PaymentRefunded])]
+enum PaymentEvent {
+    PaymentReceived {
+        #[id]
+        payment_id: String,
+    },
+    #[event(rename = "payment_refunded_v2")]
+    PaymentRefunded {
+        #[id]
+        payment_id: String,
+    },
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[event(rename = "InvoiceIssuedV2")]
+struct InvoiceIssued {
+    #[id]
+    invoice_id: String,
+}
+
+#[test]
+fn it_renames_the_events() {
+    let refunded = PaymentEvent::PaymentRefunded {
+        payment_id: "p1".to_string(),
+    };
+
+    assert_eq!(
+        PaymentEvent::SCHEMA.events,
+        &["payment_received", "payment_refunded_v2"]
+    );
+    assert_eq!(refunded.name(), "payment_refunded_v2");
+    assert_eq!(
+        PaymentStream::SCHEMA.events,
+        &["payment_received", "payment_refunded_v2"]
+    );
+    assert_eq!(InvoiceIssued::SCHEMA.events, &["InvoiceIssuedV2"]);
+    assert_eq!(
+        InvoiceIssued {
+            invoice_id: "i1".to_string()
+        }
+        .name(),
+        "InvoiceIssuedV2"
+    );
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+struct Customer {
+    #[id]
+    customer_id: String,
+    email: String,
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+enum ShipmentEvent {
+    ShipmentCreated {
+        #[id]
+        shipment_id: String,
+        #[id(nested)]
+        customer: Customer,
+    },
+}
+
+#[test]
+fn it_extracts_the_domain_identifiers_of_nested_payloads() {
+    let event = ShipmentEvent::ShipmentCreated {
+        shipment_id: "s1".to_string(),
+        customer: Customer {
+            customer_id: "c1".to_string(),
+            email: "jane@example.com".to_string(),
+        },
+    };
+
+    let domain_identifiers = event.domain_identifiers();
+    assert_eq!(
+        domain_identifiers.get(&ident!(#shipment_id)),
+        Some(&"s1".into_identifier_value())
+    );
+    assert_eq!(
+        domain_identifiers.get(&ident!(#customer_id)),
+        Some(&"c1".into_identifier_value())
+    );
+    assert_eq!(
+        ShipmentEvent::SCHEMA.events_info[0].domain_identifiers,
+        &[&ident!(#shipment_id), &ident!(#customer_id)]
+    );
+    assert_eq!(
+        ShipmentEvent::SCHEMA
+            .domain_identifiers
+            .iter()
+            .map(|info| info.ident)
+            .collect::<Vec<_>>(),
+        vec![ident!(#customer_id), ident!(#shipment_id)]
+    );
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+#[stream(RefundEvent, [PaymentRefunded])]
+enum LedgerEvent<C> {
+    PaymentReceived {
+        #[id]
+        account_id: String,
+        amount: u64,
+        #[filter]
+        currency: C,
+    },
+    PaymentRefunded {
+        #[id]
+        account_id: String,
+    },
+}
+
+#[test]
+fn it_derives_generic_events() {
+    let event = LedgerEvent::PaymentReceived {
+        account_id: "a1".to_string(),
+        amount: 10,
+        currency: "EUR".to_string(),
+    };
+
+    assert_eq!(event.name(), "PaymentReceived");
+    assert_eq!(
+        event.domain_identifiers().get(&ident!(#account_id)),
+        Some(&"a1".into_identifier_value())
+    );
+    assert_eq!(
+        event.payload_fields().get(&ident!(#currency)),
+        Some(&"EUR".into_identifier_value())
+    );
+    assert_eq!(
+        LedgerEvent::<String>::SCHEMA.events,
+        &["PaymentReceived", "PaymentRefunded"]
+    );
+
+    let refund = RefundEvent::PaymentRefunded {
+        account_id: "a1".to_string(),
+    };
+    let event: LedgerEvent<String> = refund.clone().into();
+    assert_eq!(RefundEvent::try_from(event).unwrap(), refund);
+    assert_eq!(RefundEvent::SCHEMA.events, &["PaymentRefunded"]);
+}
+
+#[allow(dead_code)]
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+enum CouponEvent {
+    CouponIssued {
+        #[id]
+        coupon_id: String,
+    },
+    CouponApplied {
+        #[id]
+        coupon_id: String,
+        #[id]
+        order_id: String,
+    },
+}
+
+disintegrate::event_union! {
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    enum ShopEvent {
+        Order(OrderEvent),
+        Coupon(CouponEvent),
+    }
+}
+
+#[test]
+fn it_composes_event_types() {
+    assert_eq!(
+        ShopEvent::SCHEMA.events,
+        &[
+            "OrderCreated",
+            "OrderCancelled",
+            "CouponIssued",
+            "CouponApplied"
+        ]
+    );
+    assert_eq!(
+        ShopEvent::SCHEMA
+            .domain_identifiers
+            .iter()
+            .map(|info| info.ident)
+            .collect::<Vec<_>>(),
+        vec![ident!(#coupon_id), ident!(#order_id)]
+    );
+    assert_eq!(
+        ShopEvent::descriptor("CouponIssued"),
+        CouponEvent::descriptor("CouponIssued")
+    );
+
+    let coupon_applied = CouponEvent::CouponApplied {
+        coupon_id: "c1".to_string(),
+        order_id: "o1".to_string(),
+    };
+    let event: ShopEvent = coupon_applied.clone().into();
+    assert_eq!(event.name(), "CouponApplied");
+    assert_eq!(
+        event.domain_identifiers(),
+        coupon_applied.domain_identifiers()
+    );
+    assert_eq!(CouponEvent::try_from(event.clone()), Ok(coupon_applied));
+    assert_eq!(
+        OrderEvent::try_from(event),
+        Err(disintegrate::ProjectionError("CouponApplied"))
+    );
+}
+
 #[test]
 fn it_returns_correct_domain_identifiers() {
     let user_id = "user123".to_string();
@@ -95,6 +339,44 @@ fn it_returns_correct_domain_identifiers() {
     assert!(domain_identifiers.is_empty());
 }
 
+#[test]
+fn it_returns_the_payload_fields() {
+    let enum_struct_variant_event = DomainEvent::UserCreated {
+        user_id: "user123".to_string(),
+        name: "John Doe".to_string(),
+        email: "john@example.com".to_string(),
+    };
+    let payload_fields = enum_struct_variant_event.payload_fields();
+    assert_eq!(payload_fields.len(), 1);
+    assert_eq!(
+        payload_fields.get(&ident!(#email)),
+        Some(&"john@example.com".into_identifier_value())
+    );
+
+    let enum_unnamed_variant_event = DomainEvent::UserUpdated(UserUpdatedData {
+        user_id: "user123".to_string(),
+        email: "john@example.com".to_string(),
+    });
+    assert_eq!(
+        enum_unnamed_variant_event
+            .payload_fields()
+            .get(&ident!(#email)),
+        Some(&"john@example.com".into_identifier_value())
+    );
+
+    assert!(DomainEvent::UserChanged.payload_fields().is_empty());
+}
+
+#[test]
+fn it_returns_the_pii_fields() {
+    assert_eq!(DomainEvent::pii_fields("UserCreated"), &["name", "email"]);
+    assert_eq!(DomainEvent::pii_fields("UserUpdated"), &["email"]);
+    assert!(DomainEvent::pii_fields("OrderCreated").is_empty());
+    assert!(DomainEvent::pii_fields("Unknown").is_empty());
+    assert_eq!(UserEvent::pii_fields("UserCreated"), &["name", "email"]);
+    assert!(OrderEvent::pii_fields("UserCreated").is_empty());
+}
+
 #[test]
 fn it_generates_event_streams() {
     let user_event = UserEvent::UserCreated {
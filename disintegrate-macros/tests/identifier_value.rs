@@ -0,0 +1,75 @@
+use disintegrate::{
+    ident, DomainIdentifierInfo, Event, IdentifierType, IdentifierValue, IntoIdentifierValue,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, IntoIdentifierValue)]
+struct CartId(String);
+
+#[derive(Clone, Debug, PartialEq, Eq, IntoIdentifierValue)]
+struct OrderId {
+    value: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, IntoIdentifierValue)]
+struct Sequence(u32);
+
+#[allow(dead_code)]
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+enum CartEvent {
+    ItemAdded {
+        #[id]
+        cart_id: CartId,
+        #[id]
+        order_id: OrderId,
+        #[id]
+        sequence: Sequence,
+    },
+}
+
+#[test]
+fn it_converts_newtypes_into_identifier_values() {
+    let event = CartEvent::ItemAdded {
+        cart_id: CartId("c1".to_string()),
+        order_id: OrderId { value: 42 },
+        sequence: Sequence(3),
+    };
+
+    let domain_identifiers = event.domain_identifiers();
+    assert_eq!(
+        domain_identifiers.get(&ident!(#cart_id)),
+        Some(&IdentifierValue::String("c1".to_string()))
+    );
+    assert_eq!(
+        domain_identifiers.get(&ident!(#order_id)),
+        Some(&IdentifierValue::i64(42))
+    );
+    assert_eq!(
+        domain_identifiers.get(&ident!(#sequence)),
+        Some(&IdentifierValue::i64(3))
+    );
+    assert_eq!(
+        (&CartId("c2".to_string())).into_identifier_value(),
+        IdentifierValue::String("c2".to_string())
+    );
+}
+
+#[test]
+fn it_takes_the_identifier_type_of_the_wrapped_value() {
+    assert_eq!(
+        CartEvent::SCHEMA.domain_identifiers,
+        &[
+            &DomainIdentifierInfo {
+                ident: ident!(#cart_id),
+                type_info: IdentifierType::String
+            },
+            &DomainIdentifierInfo {
+                ident: ident!(#order_id),
+                type_info: IdentifierType::i64
+            },
+            &DomainIdentifierInfo {
+                ident: ident!(#sequence),
+                type_info: IdentifierType::i64
+            },
+        ]
+    );
+}
@@ -1,4 +1,4 @@
-use disintegrate::{query, Event, StateQuery};
+use disintegrate::{ident, query, Event, HydrationWindow, StateQuery};
 
 #[allow(dead_code)]
 #[derive(Event, Debug, PartialEq, Eq, Clone)]
@@ -14,6 +14,8 @@ enum DomainEvent {
         user_id: i64,
         #[id]
         order_id: String,
+        #[filter]
+        warehouse: String,
         amount: u32,
     },
 }
@@ -34,6 +36,31 @@ struct UserOrder {
     order_id: String,
 }
 
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent)]
+struct WarehouseOrders {
+    #[id]
+    user_id: i64,
+    #[filter]
+    warehouse: String,
+}
+
+#[allow(dead_code)]
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent, last_events = 100)]
+struct RecentUserOrders {
+    #[id]
+    user_id: i64,
+}
+
+#[allow(dead_code)]
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent, within_secs = 2592000)]
+struct MonthlyUserOrders {
+    #[id]
+    user_id: i64,
+}
+
 #[test]
 fn it_sets_the_name_of_a_state_query() {
     assert_eq!(UserOrders::NAME, "UserOrders");
@@ -44,6 +71,108 @@ fn it_renames_a_state_query() {
     assert_eq!(UserOrder::NAME, "UserOrderData");
 }
 
+#[allow(dead_code)]
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent, rename = "UserOrderData")]
+struct UserOrderV2 {
+    #[id]
+    user_id: i64,
+    #[id]
+    order_id: String,
+    amount: u32,
+}
+
+#[test]
+fn it_fingerprints_the_shape_of_a_state_query() {
+    assert_ne!(UserOrders::FINGERPRINT, 0);
+    assert_ne!(UserOrder::FINGERPRINT, UserOrderV2::FINGERPRINT);
+}
+
+#[allow(dead_code)]
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent, rename = "UserOrderData")]
+struct UserOrderWithCache {
+    #[id]
+    user_id: i64,
+    #[id]
+    order_id: String,
+    #[state(skip)]
+    cached_total: u64,
+}
+
+#[test]
+fn it_skips_the_bookkeeping_fields() {
+    assert_eq!(UserOrderWithCache::FINGERPRINT, UserOrder::FINGERPRINT);
+
+    let user_order = UserOrderWithCache {
+        user_id: 2,
+        order_id: "order1".to_string(),
+        cached_total: 42,
+    };
+    assert_eq!(
+        user_order.query::<i64>(),
+        query!(DomainEvent; user_id == 2, order_id == "order1")
+    );
+}
+
+#[allow(dead_code)]
+#[derive(Event, Debug, PartialEq, Eq, Clone)]
+enum LedgerEvent<C> {
+    PaymentReceived {
+        #[id]
+        account_id: String,
+        #[filter]
+        currency: C,
+    },
+}
+
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(LedgerEvent<C>)]
+struct AccountBalance<C> {
+    #[id]
+    account_id: String,
+    #[filter]
+    currency: C,
+}
+
+#[test]
+fn it_builds_the_stream_query_of_a_generic_state_query() {
+    let balance = AccountBalance {
+        account_id: "a1".to_string(),
+        currency: "EUR".to_string(),
+    };
+    assert_eq!(
+        balance.query::<i64>(),
+        query!(LedgerEvent<String>; account_id == "a1").filter_payload(ident!(#currency), "EUR")
+    );
+}
+
+#[derive(StateQuery, Debug, PartialEq, Eq, Clone)]
+#[state_query(DomainEvent)]
+#[stream_query(exclude_events = [UserCreated], exclude(order_id == "cancelled"))]
+#[stream_query(filter(order_id == self.current_order))]
+struct OpenUserOrders {
+    #[id]
+    user_id: i64,
+    current_order: String,
+}
+
+#[test]
+fn it_applies_the_stream_query_attributes() {
+    let open_orders = OpenUserOrders {
+        user_id: 1,
+        current_order: "order1".to_string(),
+    };
+    assert_eq!(
+        open_orders.query::<i64>(),
+        query!(DomainEvent; user_id == 1)
+            .exclude_events(&["UserCreated"])
+            .exclude_identifier(ident!(#order_id), "cancelled")
+            .filter_in(ident!(#order_id), ["order1"])
+    );
+    assert_ne!(OpenUserOrders::FINGERPRINT, UserOrders::FINGERPRINT);
+}
+
 #[test]
 fn it_builds_the_stream_query() {
     let user_orders = UserOrders { user_id: 1 };
@@ -60,4 +189,28 @@ fn it_builds_the_stream_query() {
         user_order.query::<i64>(),
         query!(DomainEvent; user_id == 2, order_id == "order1")
     );
+
+    let warehouse_orders = WarehouseOrders {
+        user_id: 3,
+        warehouse: "EU-1".to_string(),
+    };
+    assert_eq!(
+        warehouse_orders.query::<i64>(),
+        query!(DomainEvent; user_id == 3).filter_payload(ident!(#warehouse), "EU-1")
+    );
+}
+
+#[test]
+fn it_sets_the_window_of_a_state_query() {
+    assert_eq!(UserOrders::WINDOW, None);
+    assert_eq!(
+        RecentUserOrders::WINDOW,
+        Some(HydrationWindow::LastEvents(100))
+    );
+    assert_eq!(
+        MonthlyUserOrders::WINDOW,
+        Some(HydrationWindow::Within(std::time::Duration::from_secs(
+            2592000
+        )))
+    );
 }
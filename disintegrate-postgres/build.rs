@@ -0,0 +1,8 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(true)
+        .compile_protos(&["proto/subscription.proto"], &["proto"])
+        .unwrap();
+}
@@ -0,0 +1,373 @@
+//! # PostgreSQL Administration
+//!
+//! This module provides the typed operations of the back-office tools, so the admin UIs browse the events, follow
+//! the event listeners and maintain the snapshots without querying the internal tables of the store.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use disintegrate::{
+    error_kind, Actor, BoxDynError, Classify, DomainIdentifierSet, ErrorKind, Event, Identifier,
+    IdentifierValue, IntoIdentifierValue, PersistedEvent, SnapshotInfo, SnapshotRetention,
+    SnapshotStore,
+};
+use disintegrate_serde::Serde;
+use sqlx::Row;
+
+use crate::error::sqlx_error_kind;
+use crate::{PgEventId, PgEventStore, PgSnapshotStore};
+
+/// A page of events, in the order they were appended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventPage<E: Event> {
+    /// The events of the page.
+    pub events: Vec<PersistedEvent<PgEventId, E>>,
+    /// The ID after which the next page starts, or `None` if this is the last page.
+    pub next: Option<PgEventId>,
+}
+
+/// An event along with the metadata stored with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventDetails<E: Event> {
+    /// The event.
+    pub event: PersistedEvent<PgEventId, E>,
+    /// The time when the event was appended.
+    pub inserted_at: SystemTime,
+    /// The actor that appended the event, if it was appended within an actor context.
+    pub actor: Option<Actor>,
+    /// The domain identifiers of the event.
+    pub domain_identifiers: DomainIdentifierSet,
+}
+
+/// The checkpoint of an event listener.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerCheckpoint {
+    /// The ID of the listener.
+    pub id: String,
+    /// The ID of the last event processed by the listener.
+    pub last_processed_event_id: PgEventId,
+    /// The number of event IDs between the last processed event and the last appended event.
+    ///
+    /// Events that do not match the query of the listener are counted as well.
+    pub lag: i64,
+    /// The time when the listener last ran.
+    pub updated_at: Option<SystemTime>,
+}
+
+/// The administration operations of a PostgreSQL event store.
+///
+/// # Overview
+///
+/// `PgAdmin` reads the event store with the serde of the application, so the events are returned decoded, along
+/// with the metadata stored with them. The events are read within the tenant of the event store, if any, while
+/// the listeners and the snapshots are shared across the tenants.
+///
+/// The maintenance operations act on the running listeners and states:
+///
+/// * The checkpoint of a listener can be moved back to handle the events again, or forward to skip them, and the
+///   listener picks it up at its next run.
+/// * The snapshots of a state can be deleted, e.g. after a bug fix in the state, or pruned. The deleted snapshots
+///   are rebuilt from the events the next time the state is loaded.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let admin = PgAdmin::new(event_store);
+///
+/// let page = admin.events_by_identifier(&ident!(#cart_id), "c1", 0, 50).await?;
+/// let details = admin.event(page.events[0].id()).await?;
+/// let checkpoints = admin.listener_checkpoints().await?;
+/// ```
+pub struct PgAdmin<E, S>
+where
+    S: Serde<E> + Send + Sync,
+    E: Event,
+{
+    event_store: PgEventStore<E, S>,
+    snapshot_store: PgSnapshotStore,
+}
+
+impl<E, S> PgAdmin<E, S>
+where
+    E: Event + Clone + Send + Sync + 'static,
+    S: Serde<E> + Send + Sync,
+{
+    /// Creates the administration of the event store and of the snapshots stored in the same database.
+    pub fn new(event_store: PgEventStore<E, S>) -> Self {
+        let snapshot_store = PgSnapshotStore::new_uninitialized(event_store.pool.clone());
+        Self {
+            event_store,
+            snapshot_store,
+        }
+    }
+
+    /// Returns a page of the events having the domain identifier `identifier` equal to `value`.
+    ///
+    /// # Parameters
+    ///
+    /// * `identifier`: The domain identifier of the events.
+    /// * `value`: The value of the domain identifier.
+    /// * `after`: The ID after which the page starts, `0` for the first page.
+    /// * `limit`: The maximum number of events of the page.
+    ///
+    /// # Returns
+    ///
+    /// The page of events, which is empty if no event has the domain identifier.
+    pub async fn events_by_identifier(
+        &self,
+        identifier: &Identifier,
+        value: impl IntoIdentifierValue,
+        after: PgEventId,
+        limit: usize,
+    ) -> Result<EventPage<E>, Error> {
+        if !E::SCHEMA
+            .domain_identifiers
+            .iter()
+            .any(|info| info.ident == *identifier)
+        {
+            return Ok(EventPage {
+                events: vec![],
+                next: None,
+            });
+        }
+        let sql = format!(
+            "SELECT event_id, payload FROM event WHERE event_id <= event_store_current_epoch() AND event_id > $1 AND {identifier} = $2 ORDER BY event_id ASC LIMIT $3"
+        );
+        let query = sqlx::query(&sql).bind(after);
+        let query = match value.into_identifier_value() {
+            IdentifierValue::String(value) => query.bind(value),
+            IdentifierValue::i64(value) => query.bind(value),
+            IdentifierValue::Uuid(value) => query.bind(value),
+        };
+        let rows = self
+            .event_store
+            .fetch_all(query.bind(limit as i64 + 1))
+            .await?;
+        let mut events = self.event_store.decode_rows::<E>(rows).await?;
+        let next = if events.len() > limit {
+            events.truncate(limit);
+            events.last().map(PersistedEvent::id)
+        } else {
+            None
+        };
+        Ok(EventPage { events, next })
+    }
+
+    /// Returns the event with the given ID along with its metadata, if it exists.
+    pub async fn event(&self, event_id: PgEventId) -> Result<Option<EventDetails<E>>, Error> {
+        let rows = self
+            .event_store
+            .fetch_all(
+                sqlx::query(
+                    "SELECT event_id, payload, EXTRACT(EPOCH FROM inserted_at)::float8, actor FROM event WHERE event_id = $1",
+                )
+                .bind(event_id),
+            )
+            .await?;
+        let Some(row) = rows.first() else {
+            return Ok(None);
+        };
+        let inserted_at = timestamp(row.try_get(2)?).unwrap_or(UNIX_EPOCH);
+        let actor = row
+            .try_get::<Option<String>, _>(3)?
+            .and_then(|actor| actor.parse().ok());
+        let Some(event) = self.event_store.decode_rows::<E>(rows).await?.pop() else {
+            return Ok(None);
+        };
+        let domain_identifiers = event.domain_identifiers();
+        Ok(Some(EventDetails {
+            event,
+            inserted_at,
+            actor,
+            domain_identifiers,
+        }))
+    }
+
+    /// Returns the checkpoints of the event listeners, ordered by ID.
+    ///
+    /// The checkpoints are empty if the event listeners were never set up.
+    pub async fn listener_checkpoints(&self) -> Result<Vec<ListenerCheckpoint>, Error> {
+        if !self.listeners_exist().await? {
+            return Ok(vec![]);
+        }
+        let rows = sqlx::query(
+            "SELECT id, COALESCE(last_processed_event_id, 0), EXTRACT(EPOCH FROM updated_at)::float8, (SELECT COALESCE(MAX(event_id), 0) FROM event) FROM event_listener ORDER BY id",
+        )
+        .fetch_all(&self.event_store.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                let last_processed_event_id: PgEventId = row.try_get(1)?;
+                let head_event_id: PgEventId = row.try_get(3)?;
+                Ok(ListenerCheckpoint {
+                    id: row.try_get(0)?,
+                    last_processed_event_id,
+                    lag: (head_event_id - last_processed_event_id).max(0),
+                    updated_at: timestamp(row.try_get(2)?),
+                })
+            })
+            .collect()
+    }
+
+    /// Moves the checkpoint of the event listener, so it handles the events following `last_processed_event_id`
+    /// at its next run.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the listener exists.
+    pub async fn move_listener_checkpoint(
+        &self,
+        listener_id: &str,
+        last_processed_event_id: PgEventId,
+    ) -> Result<bool, Error> {
+        if !self.listeners_exist().await? {
+            return Ok(false);
+        }
+        let result = sqlx::query(
+            "UPDATE event_listener SET last_processed_event_id = $2, updated_at = now() WHERE id = $1",
+        )
+        .bind(listener_id)
+        .bind(last_processed_event_id)
+        .execute(&self.event_store.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lists the snapshots, optionally restricted to the state query `name`.
+    pub async fn snapshots(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Vec<SnapshotInfo<PgEventId>>, Error> {
+        self.snapshot_store
+            .list(name)
+            .await
+            .map_err(Error::SnapshotStore)
+    }
+
+    /// Deletes all the snapshots of the state query `name`. Returns the number of deleted snapshots.
+    pub async fn delete_snapshots(&self, name: &str) -> Result<u64, Error> {
+        Ok(sqlx::query("DELETE FROM snapshot WHERE name = $1")
+            .bind(name)
+            .execute(&self.event_store.pool)
+            .await?
+            .rows_affected())
+    }
+
+    /// Deletes the snapshots of the state query `name` that are not retained by `retention`. Returns the number
+    /// of deleted snapshots.
+    pub async fn prune_snapshots(
+        &self,
+        name: &str,
+        retention: &SnapshotRetention,
+    ) -> Result<u64, Error> {
+        self.snapshot_store
+            .prune(name, retention)
+            .await
+            .map_err(Error::SnapshotStore)
+    }
+
+    async fn listeners_exist(&self) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT to_regclass('event_listener') IS NOT NULL")
+            .fetch_one(&self.event_store.pool)
+            .await
+    }
+}
+
+/// Converts the seconds since the epoch read from the database into a time.
+fn timestamp(seconds: Option<f64>) -> Option<SystemTime> {
+    seconds.map(|seconds| UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+/// PostgreSQL administration error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Error returned from the database.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// The events could not be read from the event store.
+    #[error(transparent)]
+    EventStore(#[from] crate::Error),
+    /// The snapshots could not be read or deleted.
+    #[error("snapshot store error: {0}")]
+    SnapshotStore(#[source] BoxDynError),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::EventStore(err) => err.kind(),
+            Error::SnapshotStore(err) => error_kind(err.as_ref()).unwrap_or(ErrorKind::Io),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{ident, EventStore};
+    use disintegrate_macros::Event;
+    use disintegrate_serde::serde::json::Json;
+    use serde::{Deserialize, Serialize};
+    use sqlx::PgPool;
+
+    #[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum CartEvent {
+        ItemAdded {
+            #[id]
+            cart_id: String,
+            item_id: String,
+        },
+    }
+
+    fn item_added(cart_id: &str, item_id: &str) -> CartEvent {
+        CartEvent::ItemAdded {
+            cart_id: cart_id.to_string(),
+            item_id: item_id.to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn it_browses_the_events_by_identifier(pool: PgPool) {
+        let event_store = PgEventStore::new(pool, Json::<CartEvent>::default())
+            .await
+            .unwrap();
+        event_store
+            .append_without_validation(vec![
+                item_added("c1", "i1"),
+                item_added("c2", "i2"),
+                item_added("c1", "i3"),
+                item_added("c1", "i4"),
+            ])
+            .await
+            .unwrap();
+        let admin = PgAdmin::new(event_store);
+
+        let first = admin
+            .events_by_identifier(&ident!(#cart_id), "c1", 0, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            first.events.iter().map(|e| e.id()).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(first.next, Some(3));
+        let second = admin
+            .events_by_identifier(&ident!(#cart_id), "c1", 3, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            second.events,
+            vec![PersistedEvent::new(4, item_added("c1", "i4"))]
+        );
+        assert_eq!(second.next, None);
+
+        let details = admin.event(2).await.unwrap().unwrap();
+        assert_eq!(*details.event, item_added("c2", "i2"));
+        assert_eq!(details.actor, None);
+        assert_eq!(
+            details.domain_identifiers.get(&ident!(#cart_id)),
+            Some(&"c2".into_identifier_value())
+        );
+        assert!(admin.event(5).await.unwrap().is_none());
+        assert!(admin.listener_checkpoints().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,456 @@
+//! CloudEvents Format
+//!
+//! This module converts the persisted events to and from CloudEvents 1.0, using either the JSON event format
+//! (structured content mode) or the HTTP binary content mode.
+use std::collections::BTreeMap;
+
+use disintegrate::{Classify, ErrorKind, Event, PersistedEvent, Redactor};
+use disintegrate_serde::Serde;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::PgEventId;
+
+/// The CloudEvents specification version.
+pub const SPEC_VERSION: &str = "1.0";
+/// The prefix of the HTTP headers carrying the attributes in binary content mode.
+pub const HEADER_PREFIX: &str = "ce-";
+/// The media type of a CloudEvent in structured content mode.
+pub const STRUCTURED_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+/// A CloudEvent in the JSON event format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudEvent {
+    /// The version of the specification.
+    pub specversion: String,
+    /// The ID of the event.
+    pub id: String,
+    /// The context in which the event happened.
+    pub source: String,
+    /// The type of the event.
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The time when the CloudEvent was produced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// The media type of `data`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datacontenttype: Option<String>,
+    /// The payload of the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    /// The extension attributes.
+    #[serde(flatten)]
+    pub extensions: BTreeMap<String, Value>,
+}
+
+/// Converts persisted events to and from CloudEvents.
+///
+/// # Overview
+///
+/// The attributes of the CloudEvent are mapped from the persisted event:
+///
+/// * `id` is the event ID, and `type` is the event name.
+/// * `source` is the value given when creating the converter.
+/// * `time` is the time of the conversion, in RFC 3339 format.
+/// * Each domain identifier becomes an extension attribute. CloudEvents only allow lowercase letters and digits
+///   in attribute names, so `cart_id` becomes `cartid`.
+/// * The actor who appended the event, if known, becomes the `actor` extension attribute.
+/// * Additional extension attributes can be set with `extension`.
+///
+/// In structured mode, the event is serialized with the provided serde, which must produce JSON, and embedded in
+/// `data`. In binary mode, the serialized event is the HTTP body and the attributes are sent as `ce-` headers, so
+/// any serialization format can be used: set its media type with `content_type`.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::PersistedEvent;
+/// use disintegrate_macros::Event;
+/// use disintegrate_postgres::{CloudEvents, PgEventId};
+/// use disintegrate_serde::serde::json::Json;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// let cloudevents = CloudEvents::new("/shop/orders", Json::<OrderPlaced>::default());
+/// let event = PersistedEvent::new(1, OrderPlaced { order_id: "o-1".to_string() });
+///
+/// let json = cloudevents.to_json(&event).unwrap();
+/// let event: PersistedEvent<PgEventId, OrderPlaced> = cloudevents.from_json(&json).unwrap();
+/// assert_eq!(event.id(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CloudEvents<S> {
+    source: String,
+    content_type: String,
+    extensions: BTreeMap<String, String>,
+    serde: S,
+    redactor: Option<Redactor>,
+}
+
+impl<S> CloudEvents<S> {
+    /// Creates a new `CloudEvents` converter.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The value of the `source` attribute, e.g. the URI of the service.
+    /// * `serde` - The serde of the event payloads.
+    pub fn new(source: impl Into<String>, serde: S) -> Self {
+        Self {
+            source: source.into(),
+            content_type: "application/json".to_string(),
+            extensions: BTreeMap::new(),
+            serde,
+            redactor: None,
+        }
+    }
+
+    /// Sets the `datacontenttype` attribute. The default is `application/json`.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    /// Adds an extension attribute to all the events.
+    pub fn extension(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.extensions.insert(attribute_name(name), value.into());
+        self
+    }
+
+    /// Masks the personal data of the payloads of the converted events with the redactor.
+    ///
+    /// The events read back with `from_cloud_event`, `from_json` or `from_http` keep the masked values.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    fn attributes<E: Event>(
+        &self,
+        event: &PersistedEvent<PgEventId, E>,
+    ) -> BTreeMap<String, String> {
+        let mut extensions = self.extensions.clone();
+        for (identifier, value) in event.domain_identifiers().iter() {
+            extensions.insert(attribute_name(identifier), value.to_string());
+        }
+        if let Some(actor) = event.actor() {
+            extensions.insert("actor".to_string(), actor.to_string());
+        }
+        extensions
+    }
+
+    /// Converts the event into a CloudEvent in the JSON event format.
+    pub fn to_cloud_event<E>(
+        &self,
+        event: &PersistedEvent<PgEventId, E>,
+    ) -> Result<CloudEvent, Error>
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        let mut data =
+            serde_json::from_slice(&self.serde.serialize((**event).clone())).map_err(|source| {
+                Error::InvalidPayload {
+                    event_id: event.id(),
+                    source,
+                }
+            })?;
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_value(event.name(), &mut data);
+        }
+        Ok(CloudEvent {
+            specversion: SPEC_VERSION.to_string(),
+            id: event.id().to_string(),
+            source: self.source.clone(),
+            ty: event.name().to_string(),
+            time: Some(now()),
+            datacontenttype: Some(self.content_type.clone()),
+            data: Some(data),
+            extensions: self
+                .attributes(event)
+                .into_iter()
+                .map(|(name, value)| (name, Value::String(value)))
+                .collect(),
+        })
+    }
+
+    /// Converts a CloudEvent in the JSON event format into a persisted event.
+    pub fn from_cloud_event<E>(
+        &self,
+        cloud_event: CloudEvent,
+    ) -> Result<PersistedEvent<PgEventId, E>, Error>
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        check_spec_version(&cloud_event.specversion)?;
+        let id = parse_id(&cloud_event.id)?;
+        let data = cloud_event
+            .data
+            .ok_or(Error::MissingAttribute("data".to_string()))?;
+        let event = self.serde.deserialize(&serde_json::to_vec(&data)?)?;
+        Ok(PersistedEvent::new(id, event))
+    }
+
+    /// Serializes the event as a CloudEvent in structured content mode, with the JSON event format.
+    ///
+    /// The payload is sent with the `STRUCTURED_CONTENT_TYPE` media type.
+    pub fn to_json<E>(&self, event: &PersistedEvent<PgEventId, E>) -> Result<Vec<u8>, Error>
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        Ok(serde_json::to_vec(&self.to_cloud_event(event)?)?)
+    }
+
+    /// Deserializes a CloudEvent in structured content mode, with the JSON event format.
+    pub fn from_json<E>(&self, json: &[u8]) -> Result<PersistedEvent<PgEventId, E>, Error>
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        self.from_cloud_event(serde_json::from_slice(json)?)
+    }
+
+    /// Serializes the event as a CloudEvent in HTTP binary content mode.
+    ///
+    /// # Returns
+    ///
+    /// The HTTP headers and the HTTP body.
+    pub fn to_http<E>(
+        &self,
+        event: &PersistedEvent<PgEventId, E>,
+    ) -> (Vec<(String, String)>, Vec<u8>)
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        let mut headers = vec![
+            ("content-type".to_string(), self.content_type.clone()),
+            (header("specversion"), SPEC_VERSION.to_string()),
+            (header("id"), event.id().to_string()),
+            (header("source"), self.source.clone()),
+            (header("type"), event.name().to_string()),
+            (header("time"), now()),
+        ];
+        headers.extend(
+            self.attributes(event)
+                .into_iter()
+                .map(|(name, value)| (header(&name), value)),
+        );
+        let body = self.serde.serialize((**event).clone());
+        let body = match &self.redactor {
+            Some(redactor) => redactor.redact(event.name(), &body),
+            None => body,
+        };
+        (headers, body)
+    }
+
+    /// Deserializes a CloudEvent in HTTP binary content mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The HTTP headers of the request. Header names are case-insensitive.
+    /// * `body` - The HTTP body of the request.
+    pub fn from_http<'a, E>(
+        &self,
+        headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+        body: &[u8],
+    ) -> Result<PersistedEvent<PgEventId, E>, Error>
+    where
+        E: Event + Clone,
+        S: Serde<E>,
+    {
+        let attributes: BTreeMap<String, &str> = headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let name = name.to_ascii_lowercase();
+                name.strip_prefix(HEADER_PREFIX)
+                    .map(|attribute| (attribute.to_string(), value))
+            })
+            .collect();
+        let attribute = |name: &str| {
+            attributes
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::MissingAttribute(name.to_string()))
+        };
+        check_spec_version(attribute("specversion")?)?;
+        let id = parse_id(attribute("id")?)?;
+        let event = self.serde.deserialize(body)?;
+        Ok(PersistedEvent::new(id, event))
+    }
+}
+
+fn header(attribute: &str) -> String {
+    format!("{HEADER_PREFIX}{attribute}")
+}
+
+fn attribute_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn check_spec_version(specversion: &str) -> Result<(), Error> {
+    if specversion != SPEC_VERSION {
+        return Err(Error::UnsupportedSpecVersion(specversion.to_string()));
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Result<PgEventId, Error> {
+    id.parse().map_err(|_| Error::InvalidId(id.to_string()))
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// CloudEvents conversion error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The serialized event is not valid JSON.
+    #[error("payload of event {event_id} is not valid JSON: {source}")]
+    InvalidPayload {
+        event_id: PgEventId,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A required attribute is missing.
+    #[error("missing attribute {0}")]
+    MissingAttribute(String),
+    /// The ID is not a valid event ID.
+    #[error("invalid event id {0}")]
+    InvalidId(String),
+    /// The CloudEvent uses a different version of the specification.
+    #[error("unsupported spec version {0}")]
+    UnsupportedSpecVersion(String),
+    /// The CloudEvent is not valid JSON.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// The payload could not be deserialized.
+    #[error(transparent)]
+    Deserialization(#[from] disintegrate_serde::Error),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidPayload { .. } => ErrorKind::Corruption,
+            Error::MissingAttribute(_)
+            | Error::InvalidId(_)
+            | Error::UnsupportedSpecVersion(_)
+            | Error::Json(_)
+            | Error::Deserialization(_) => ErrorKind::Validation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{domain_identifiers, DomainIdentifierSet, EventSchema};
+    use disintegrate_serde::{Deserializer, Serializer};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct OrderPlaced {
+        order_id: String,
+    }
+
+    impl Event for OrderPlaced {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["OrderPlaced"],
+            events_info: &[],
+            domain_identifiers: &[],
+        };
+
+        fn name(&self) -> &'static str {
+            "OrderPlaced"
+        }
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {order_id: self.order_id}
+        }
+    }
+
+    struct JsonSerde;
+
+    impl Serializer<OrderPlaced> for JsonSerde {
+        fn serialize(&self, event: OrderPlaced) -> Vec<u8> {
+            serde_json::to_vec(&event).unwrap()
+        }
+    }
+
+    impl Deserializer<OrderPlaced> for JsonSerde {
+        fn deserialize(&self, data: &[u8]) -> Result<OrderPlaced, disintegrate_serde::Error> {
+            Ok(serde_json::from_slice(data).unwrap())
+        }
+    }
+
+    fn order_placed() -> PersistedEvent<PgEventId, OrderPlaced> {
+        PersistedEvent::new(
+            42,
+            OrderPlaced {
+                order_id: "o-1".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn it_converts_events_in_structured_mode() {
+        let cloudevents = CloudEvents::new("/shop", JsonSerde).extension("tenant_id", "acme");
+
+        let cloud_event = cloudevents.to_cloud_event(&order_placed()).unwrap();
+        assert_eq!(cloud_event.specversion, "1.0");
+        assert_eq!(cloud_event.id, "42");
+        assert_eq!(cloud_event.ty, "OrderPlaced");
+        assert_eq!(cloud_event.source, "/shop");
+        assert!(cloud_event.time.is_some());
+        assert_eq!(cloud_event.extensions["orderid"], "o-1");
+        assert_eq!(cloud_event.extensions["tenantid"], "acme");
+
+        let json = cloudevents.to_json(&order_placed()).unwrap();
+        let event: PersistedEvent<PgEventId, OrderPlaced> = cloudevents.from_json(&json).unwrap();
+        assert_eq!(event.id(), 42);
+        assert_eq!(*event, *order_placed());
+    }
+
+    #[test]
+    fn it_converts_events_in_binary_mode() {
+        let cloudevents = CloudEvents::new("/shop", JsonSerde);
+
+        let (headers, body) = cloudevents.to_http(&order_placed());
+        assert!(headers.contains(&("ce-type".to_string(), "OrderPlaced".to_string())));
+        assert!(headers.contains(&("ce-orderid".to_string(), "o-1".to_string())));
+
+        let event: PersistedEvent<PgEventId, OrderPlaced> = cloudevents
+            .from_http(
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+                &body,
+            )
+            .unwrap();
+        assert_eq!(event.id(), 42);
+        assert_eq!(*event, *order_placed());
+    }
+
+    #[test]
+    fn it_rejects_unsupported_spec_versions() {
+        let cloudevents = CloudEvents::new("/shop", JsonSerde);
+
+        let result: Result<PersistedEvent<PgEventId, OrderPlaced>, _> = cloudevents.from_http(
+            [("Ce-Specversion", "0.3"), ("Ce-Id", "1")],
+            b"{\"order_id\":\"o-1\"}",
+        );
+
+        assert!(matches!(result, Err(Error::UnsupportedSpecVersion(version)) if version == "0.3"));
+    }
+}
@@ -0,0 +1,253 @@
+//! # PostgreSQL Credentials Rotation
+//!
+//! This module lets the connection pools of the stores obtain their credentials from a `CredentialsProvider`,
+//! such as Vault, the IAM authentication of AWS or a rotating secret, instead of a static connection string.
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use disintegrate::{BoxDynError, Runtime, TokioRuntime};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+
+use crate::Error;
+
+/// The default interval between two refreshes of credentials without an expiry.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The default time before their expiry at which the credentials are refreshed.
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The delay before retrying a failed refresh, and the minimum delay between two refreshes.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The username and the password of a database connection.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    username: String,
+    password: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl Credentials {
+    /// Creates credentials that do not expire.
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+            expires_at: None,
+        }
+    }
+
+    /// Sets the time after which the credentials can no longer open connections.
+    pub fn with_expiry(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Returns the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Returns the time after which the credentials can no longer open connections, if any.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Provides the credentials of the database connections.
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    /// Returns the current credentials, fetching new ones if the previous ones are about to expire.
+    async fn credentials(&self) -> Result<Credentials, BoxDynError>;
+}
+
+/// Opens a connection pool with the credentials of a `CredentialsProvider` and keeps them fresh.
+///
+/// The credentials are refreshed before they expire, or periodically if they do not expire. The pool opens
+/// its new connections with the refreshed credentials, while the open connections stay authenticated until
+/// they are closed, so the rotation does not interrupt the running queries. Setting the `max_lifetime` of the
+/// pool bounds the time the connections opened with the previous credentials are kept.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let rotation = PgCredentialsRotation::new(PgConnectOptions::from_str(&database_url)?, vault);
+/// let (pool, refresh) = rotation
+///     .connect(PgPoolOptions::new().max_lifetime(Duration::from_secs(600)))
+///     .await?;
+/// tokio::spawn(refresh);
+///
+/// let event_store = PgEventStore::new(pool, serde).await?;
+/// ```
+#[derive(Clone)]
+pub struct PgCredentialsRotation {
+    connect_options: PgConnectOptions,
+    provider: Arc<dyn CredentialsProvider>,
+    refresh_interval: Duration,
+    refresh_margin: Duration,
+    runtime: Arc<dyn Runtime>,
+}
+
+impl PgCredentialsRotation {
+    /// Creates a rotation opening the connections with the given options and the credentials of the provider.
+    pub fn new(
+        connect_options: PgConnectOptions,
+        provider: impl CredentialsProvider + 'static,
+    ) -> Self {
+        Self {
+            connect_options,
+            provider: Arc::new(provider),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+            runtime: Arc::new(TokioRuntime),
+        }
+    }
+
+    /// Sets the interval between two refreshes of the credentials without an expiry, 5 minutes by default.
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// Sets how long before their expiry the credentials are refreshed, 1 minute by default.
+    pub fn with_refresh_margin(mut self, refresh_margin: Duration) -> Self {
+        self.refresh_margin = refresh_margin;
+        self
+    }
+
+    /// Sets the async runtime running the timer of the refresh worker, Tokio by default.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Opens the pool with the current credentials. Returns the pool and the worker that refreshes the
+    /// credentials of the pool, which must be spawned on the async runtime.
+    pub async fn connect(
+        self,
+        pool_options: PgPoolOptions,
+    ) -> Result<(PgPool, impl Future<Output = ()> + Send + 'static), Error> {
+        let credentials = self.credentials().await?;
+        let pool = pool_options
+            .connect_with(self.connect_options(&credentials))
+            .await?;
+        let refresh = self.clone().run(pool.clone(), credentials);
+        Ok((pool, refresh))
+    }
+
+    /// Refreshes the credentials of the pool, returning them.
+    ///
+    /// The new connections of the pool are opened with the refreshed credentials.
+    pub async fn refresh(&self, pool: &PgPool) -> Result<Credentials, Error> {
+        let credentials = self.credentials().await?;
+        pool.set_connect_options(self.connect_options(&credentials));
+        Ok(credentials)
+    }
+
+    async fn run(self, pool: PgPool, credentials: Credentials) {
+        let mut delay = self.refresh_delay(&credentials, SystemTime::now());
+        while !pool.is_closed() {
+            self.runtime.sleep(delay).await;
+            delay = match self.refresh(&pool).await {
+                Ok(credentials) => self.refresh_delay(&credentials, SystemTime::now()),
+                Err(_err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %_err, "unable to refresh the database credentials");
+                    RETRY_DELAY
+                }
+            };
+        }
+    }
+
+    async fn credentials(&self) -> Result<Credentials, Error> {
+        self.provider
+            .credentials()
+            .await
+            .map_err(Error::Credentials)
+    }
+
+    fn connect_options(&self, credentials: &Credentials) -> PgConnectOptions {
+        self.connect_options
+            .clone()
+            .username(&credentials.username)
+            .password(&credentials.password)
+    }
+
+    /// Returns the time to wait before refreshing the credentials, so a provider returning expired
+    /// credentials is not called in a loop.
+    fn refresh_delay(&self, credentials: &Credentials, now: SystemTime) -> Duration {
+        match credentials.expires_at {
+            Some(expires_at) => expires_at
+                .duration_since(now)
+                .unwrap_or_default()
+                .saturating_sub(self.refresh_margin)
+                .min(self.refresh_interval),
+            None => self.refresh_interval,
+        }
+        .max(RETRY_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StaticProvider;
+
+    #[async_trait]
+    impl CredentialsProvider for StaticProvider {
+        async fn credentials(&self) -> Result<Credentials, BoxDynError> {
+            Ok(Credentials::new("disintegrate", "secret"))
+        }
+    }
+
+    #[test]
+    fn it_refreshes_the_credentials_before_they_expire() {
+        let rotation = PgCredentialsRotation::new(PgConnectOptions::new(), StaticProvider)
+            .with_refresh_interval(Duration::from_secs(600))
+            .with_refresh_margin(Duration::from_secs(60));
+        let now = SystemTime::now();
+        let credentials = Credentials::new("disintegrate", "secret");
+
+        assert_eq!(
+            rotation.refresh_delay(&credentials, now),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            rotation.refresh_delay(
+                &credentials
+                    .clone()
+                    .with_expiry(now + Duration::from_secs(900)),
+                now
+            ),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            rotation.refresh_delay(
+                &credentials
+                    .clone()
+                    .with_expiry(now + Duration::from_secs(300)),
+                now
+            ),
+            Duration::from_secs(240)
+        );
+        assert_eq!(
+            rotation.refresh_delay(&credentials.with_expiry(now), now),
+            RETRY_DELAY
+        );
+        assert!(!format!("{:?}", Credentials::new("disintegrate", "secret")).contains("secret"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Debezium Output Format
+//!
+//! This module renders the persisted events in the envelope produced by the Debezium PostgreSQL connector,
+//! so pipelines built to consume change data capture streams can ingest the events without translation.
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use disintegrate::{Classify, ErrorKind, Event, PersistedEvent, Redactor};
+use disintegrate_serde::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::PgEventId;
+
+/// The operation of the rendered events: events are only ever created.
+pub const CREATE_OPERATION: &str = "c";
+
+/// The envelope of a change event, as produced by Debezium with schemas disabled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebeziumEnvelope {
+    /// The state of the row before the change, always `null` for events.
+    pub before: Option<Value>,
+    /// The payload of the event.
+    pub after: Option<Value>,
+    /// The metadata of the event source.
+    pub source: DebeziumSource,
+    /// The operation, always `c`.
+    pub op: String,
+    /// The time when the envelope was rendered, in milliseconds since the Unix epoch.
+    pub ts_ms: i64,
+}
+
+/// The `source` block of a `DebeziumEnvelope`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebeziumSource {
+    /// The version of the library that rendered the envelope.
+    pub version: String,
+    /// The connector name, always `disintegrate`.
+    pub connector: String,
+    /// The logical name of the event store, used by Debezium as the topic prefix.
+    pub name: String,
+    /// The time when the envelope was rendered, in milliseconds since the Unix epoch.
+    pub ts_ms: i64,
+    /// The name of the database.
+    pub db: String,
+    /// The name of the schema.
+    pub schema: String,
+    /// The name of the table, always `event`.
+    pub table: String,
+    /// The ID of the event, which plays the role of the log sequence number.
+    pub lsn: PgEventId,
+    /// The type of the event.
+    pub event_type: String,
+    /// The domain identifiers of the event.
+    pub identifiers: BTreeMap<String, String>,
+    /// The actor who appended the event, as `kind:id`, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+/// Renders persisted events as Debezium change events.
+///
+/// # Overview
+///
+/// The event is serialized with the provided serializer, which must produce JSON, and placed in the `after`
+/// field of the envelope. The `source` block carries the name of the event store, the event ID as `lsn`, the
+/// event type, the domain identifiers and the actor who appended the event. Since events are immutable, `op` is always `c` and `before` is always
+/// `null`.
+///
+/// The adapter can be used by any publisher, such as an outbox `Publisher` or an `EventListener`, to produce
+/// messages that existing Debezium consumers can ingest.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::PersistedEvent;
+/// use disintegrate_macros::Event;
+/// use disintegrate_postgres::Debezium;
+/// use disintegrate_serde::serde::json::Json;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// let debezium = Debezium::new("orders", Json::<OrderPlaced>::default());
+/// let message = debezium
+///     .render(&PersistedEvent::new(1, OrderPlaced { order_id: "o-1".to_string() }))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debezium<S> {
+    name: String,
+    database: String,
+    schema: String,
+    serde: S,
+    redactor: Option<Redactor>,
+}
+
+impl<S> Debezium<S> {
+    /// Creates a new `Debezium` adapter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The logical name of the event store, reported in `source.name`.
+    /// * `serde` - The JSON serializer of the events.
+    pub fn new(name: impl Into<String>, serde: S) -> Self {
+        Self {
+            name: name.into(),
+            database: "postgres".to_string(),
+            schema: "public".to_string(),
+            serde,
+            redactor: None,
+        }
+    }
+
+    /// Sets the database name reported in `source.db`. The default is `postgres`.
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = database.into();
+        self
+    }
+
+    /// Sets the schema name reported in `source.schema`. The default is `public`.
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = schema.into();
+        self
+    }
+
+    /// Masks the personal data of the payloads with the redactor.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Builds the envelope of the event.
+    pub fn envelope<E>(
+        &self,
+        event: &PersistedEvent<PgEventId, E>,
+    ) -> Result<DebeziumEnvelope, Error>
+    where
+        E: Event + Clone,
+        S: Serializer<E>,
+    {
+        let event_id = event.id();
+        let mut payload = serde_json::from_slice(&self.serde.serialize((**event).clone()))
+            .map_err(|source| Error::InvalidPayload { event_id, source })?;
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_value(event.name(), &mut payload);
+        }
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as i64);
+        Ok(DebeziumEnvelope {
+            before: None,
+            after: Some(payload),
+            source: DebeziumSource {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                connector: "disintegrate".to_string(),
+                name: self.name.clone(),
+                ts_ms,
+                db: self.database.clone(),
+                schema: self.schema.clone(),
+                table: "event".to_string(),
+                lsn: event_id,
+                event_type: event.name().to_string(),
+                identifiers: event
+                    .domain_identifiers()
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                actor: event.actor().map(ToString::to_string),
+            },
+            op: CREATE_OPERATION.to_string(),
+            ts_ms,
+        })
+    }
+
+    /// Renders the event as a JSON Debezium change event.
+    pub fn render<E>(&self, event: &PersistedEvent<PgEventId, E>) -> Result<Vec<u8>, Error>
+    where
+        E: Event + Clone,
+        S: Serializer<E>,
+    {
+        Ok(serde_json::to_vec(&self.envelope(event)?)?)
+    }
+}
+
+/// Debezium adapter error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The serialized event is not valid JSON.
+    #[error("payload of event {event_id} is not valid JSON: {source}")]
+    InvalidPayload {
+        event_id: PgEventId,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The envelope could not be serialized.
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidPayload { .. } => ErrorKind::Corruption,
+            Error::Serialization(_) => ErrorKind::Validation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{domain_identifiers, ident, DomainIdentifierSet, EventSchema};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct OrderPlaced {
+        order_id: String,
+    }
+
+    impl Event for OrderPlaced {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["OrderPlaced"],
+            events_info: &[],
+            domain_identifiers: &[],
+        };
+
+        fn name(&self) -> &'static str {
+            "OrderPlaced"
+        }
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {order_id: self.order_id}
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CustomerRegistered {
+        customer_id: String,
+        email: String,
+    }
+
+    impl Event for CustomerRegistered {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["CustomerRegistered"],
+            events_info: &[],
+            domain_identifiers: &[],
+        };
+
+        fn pii_fields(_event_type: &str) -> &'static [&'static str] {
+            &["email"]
+        }
+
+        fn name(&self) -> &'static str {
+            "CustomerRegistered"
+        }
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {customer_id: self.customer_id}
+        }
+    }
+
+    struct JsonSerializer;
+
+    impl Serializer<OrderPlaced> for JsonSerializer {
+        fn serialize(&self, event: OrderPlaced) -> Vec<u8> {
+            serde_json::to_vec(&event).unwrap()
+        }
+    }
+
+    impl Serializer<CustomerRegistered> for JsonSerializer {
+        fn serialize(&self, event: CustomerRegistered) -> Vec<u8> {
+            serde_json::to_vec(&event).unwrap()
+        }
+    }
+
+    #[test]
+    fn it_renders_the_debezium_envelope() {
+        let debezium = Debezium::new("orders", JsonSerializer).database("shop");
+        let event = PersistedEvent::new(
+            42,
+            OrderPlaced {
+                order_id: "o-1".to_string(),
+            },
+        );
+
+        let envelope = debezium.envelope(&event).unwrap();
+
+        assert_eq!(envelope.op, "c");
+        assert_eq!(envelope.before, None);
+        assert_eq!(envelope.after, Some(serde_json::json!({"order_id": "o-1"})));
+        assert_eq!(envelope.source.name, "orders");
+        assert_eq!(envelope.source.db, "shop");
+        assert_eq!(envelope.source.table, "event");
+        assert_eq!(envelope.source.lsn, 42);
+        assert_eq!(envelope.source.event_type, "OrderPlaced");
+        assert_eq!(
+            envelope.source.identifiers,
+            BTreeMap::from([(ident!(#order_id).to_string(), "o-1".to_string())])
+        );
+        let rendered: Value = serde_json::from_slice(&debezium.render(&event).unwrap()).unwrap();
+        assert_eq!(rendered["after"]["order_id"], "o-1");
+    }
+
+    #[test]
+    fn it_redacts_the_pii_fields_of_the_payload() {
+        let debezium = Debezium::new("customers", JsonSerializer)
+            .with_redactor(Redactor::of::<CustomerRegistered>());
+        let event = PersistedEvent::new(
+            7,
+            CustomerRegistered {
+                customer_id: "c-1".to_string(),
+                email: "jane@example.com".to_string(),
+            },
+        );
+
+        let envelope = debezium.envelope(&event).unwrap();
+
+        assert_eq!(
+            envelope.after,
+            Some(serde_json::json!({"customer_id": "c-1", "email": "***"}))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use disintegrate::{error_kind, Classify, ErrorKind};
 use std::error::Error as StdError;
 use thiserror::Error;
 
@@ -22,4 +23,89 @@ pub enum Error {
     /// used to make the current business decision. The event store's state has changed, potentially affecting the decision-making process.
     #[error("concurrent modification error")]
     Concurrency,
+    /// The transaction of the group the append was committed with failed, see `PgEventStore::with_group_commit`.
+    #[error("group commit failed: {0}")]
+    GroupCommit(#[source] std::sync::Arc<Error>),
+    /// The append committing the group was dropped, so the outcome of the group is unknown.
+    #[error("group commit aborted")]
+    GroupCommitAborted,
+    /// The anonymization function changed the type of an event, see `PgEventStore::anonymize`.
+    #[error("the anonymization of the event {event_id} changed its type from {from} to {to}")]
+    AnonymizedEventType {
+        event_id: crate::PgEventId,
+        from: &'static str,
+        to: &'static str,
+    },
+    /// The credentials of the database connections could not be obtained, see `PgCredentialsRotation`.
+    #[error("unable to obtain the database credentials: {0}")]
+    Credentials(#[source] disintegrate::BoxDynError),
+    /// An oversized payload could not be stored or fetched, see `PgEventStore::with_claim_check`.
+    #[cfg(feature = "claim-check")]
+    #[error(transparent)]
+    ClaimCheck(#[from] object_store::Error),
+    /// The payload fetched for a pointer does not match its hash.
+    #[cfg(feature = "claim-check")]
+    #[error("the claim-checked payload {0} does not match its hash")]
+    ClaimCheckMismatch(String),
+    /// A payload could not be encrypted or decrypted, see `PgEventStore::with_envelope_encryption`.
+    #[cfg(feature = "encryption")]
+    #[error(transparent)]
+    Encryption(#[from] disintegrate::encryption::Error),
+    /// The signature of an event could not be made or verified.
+    #[cfg(feature = "signing")]
+    #[error("invalid signature of the event {event_id}: {source}")]
+    Signature {
+        event_id: crate::PgEventId,
+        #[source]
+        source: disintegrate::signing::Error,
+    },
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::Deserialization(_) => ErrorKind::Corruption,
+            Error::AppendPermit(_) => ErrorKind::Io,
+            Error::QueryEventMapping(_) => ErrorKind::Validation,
+            Error::Concurrency => ErrorKind::Conflict,
+            Error::GroupCommit(err) => err.kind(),
+            Error::GroupCommitAborted => ErrorKind::Io,
+            Error::AnonymizedEventType { .. } => ErrorKind::Validation,
+            Error::Credentials(err) => error_kind(err.as_ref()).unwrap_or(ErrorKind::Io),
+            #[cfg(feature = "claim-check")]
+            Error::ClaimCheck(object_store::Error::NotFound { .. }) => ErrorKind::Corruption,
+            #[cfg(feature = "claim-check")]
+            Error::ClaimCheck(_) => ErrorKind::Io,
+            #[cfg(feature = "claim-check")]
+            Error::ClaimCheckMismatch(_) => ErrorKind::Corruption,
+            #[cfg(feature = "encryption")]
+            Error::Encryption(err) => err.kind(),
+            #[cfg(feature = "signing")]
+            Error::Signature { source, .. } => source.kind(),
+        }
+    }
+}
+
+/// Classifies a database error.
+///
+/// The connection failures, the timeouts, the serialization failures and the deadlocks are transient, the
+/// unique violations are conflicts with a concurrent write.
+pub(crate) fn sqlx_error_kind(err: &sqlx::Error) -> ErrorKind {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => ErrorKind::Transient,
+        sqlx::Error::Database(err) => match err.code().as_deref() {
+            Some("23505") => ErrorKind::Conflict,
+            Some("40001" | "40P01" | "55P03" | "57014") => ErrorKind::Transient,
+            Some(code) if code.starts_with("08") || code.starts_with("53") => ErrorKind::Transient,
+            _ => ErrorKind::Validation,
+        },
+        sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => ErrorKind::Corruption,
+        sqlx::Error::RowNotFound
+        | sqlx::Error::TypeNotFound { .. }
+        | sqlx::Error::ColumnIndexOutOfBounds { .. }
+        | sqlx::Error::ColumnNotFound(_)
+        | sqlx::Error::Configuration(_) => ErrorKind::Validation,
+        _ => ErrorKind::Io,
+    }
 }
@@ -2,17 +2,60 @@
 //!
 //! This module provides an implementation of the `EventStore` trait using PostgreSQL as the underlying storage.
 //! It allows storing and retrieving events from a PostgreSQL database.
+mod anonymization;
 mod append;
+#[cfg(feature = "claim-check")]
+mod claim_check;
+#[cfg(feature = "encryption")]
+mod envelope;
+mod explain;
+mod group_commit;
+#[cfg(feature = "hash-chain")]
+mod hash_chain;
+mod hypertable;
+mod index_advisor;
+mod integrity;
+#[cfg(feature = "metrics")]
+pub(crate) mod metrics;
+#[cfg(feature = "otel")]
+mod otel;
 mod query;
+mod query_cache;
+mod retention;
+mod row_level_security;
+#[cfg(feature = "signing")]
+mod signing;
+mod storage;
 #[cfg(test)]
 mod tests;
 
+pub use anonymization::AnonymizationReport;
 use append::{InsertEventSequenceBuilder, InsertEventsBuilder};
+#[cfg(feature = "claim-check")]
+pub use claim_check::ClaimCheck;
+#[cfg(feature = "encryption")]
+pub use envelope::EnvelopeEncryption;
+pub use explain::QueryPlan;
+use explain::{lint, EventIndexes};
 use futures::stream::BoxStream;
-use query::CriteriaBuilder;
-use sqlx::{PgPool, Row};
+use group_commit::GroupCommit;
+#[cfg(feature = "hash-chain")]
+pub use hash_chain::{ChainHead, ChainVerification};
+pub use hypertable::Hypertable;
+pub use index_advisor::{IndexAdvice, IndexAdvisor, IndexRecommendation};
+pub use integrity::{IntegrityReport, RepairReport};
+use query::{stream_sql, CriteriaBuilder};
+use query_cache::QueryCache;
+pub use retention::{EventRetention, RetentionPolicy, RetentionReport};
+pub use row_level_security::RowLevelSecurity;
+use row_level_security::Tenant;
+use sqlx::postgres::PgRow;
+use sqlx::{Execute, Executor, PgPool, Postgres, Row, Transaction};
+use std::collections::BTreeSet;
 use std::error::Error as StdError;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+pub use storage::StorageOptions;
 use tokio::sync::Semaphore;
 
 use std::marker::PhantomData;
@@ -21,21 +64,45 @@ use crate::{Error, PgEventId};
 use async_stream::stream;
 use async_trait::async_trait;
 use disintegrate::StreamQuery;
-use disintegrate::{DomainIdentifierInfo, EventStore};
-use disintegrate::{Event, PersistedEvent};
+use disintegrate::{Actor, Event, PersistedEvent};
+use disintegrate::{ComponentHealth, HealthStatus};
+use disintegrate::{DomainIdentifierInfo, EventStore, HydrationWindow, Identifier, ReadOptions};
 use disintegrate_serde::Serde;
 
 use futures::StreamExt;
 
+/// The default number of events fetched in a single round trip while streaming.
+const DEFAULT_STREAM_PAGE_SIZE: i64 = 1000;
+
+/// The range of event IDs whose search vector is recomputed by a single statement of the reindex.
+const SEARCH_REINDEX_BATCH: i64 = 10_000;
+
 /// PostgreSQL event store implementation.
 #[derive(Clone)]
 pub struct PgEventStore<E, S>
 where
     S: Serde<E> + Send + Sync,
+    E: Event,
 {
     pub(crate) pool: PgPool,
     concurrent_appends: Arc<tokio::sync::Semaphore>,
-    serde: S,
+    pub(crate) serde: S,
+    stream_page_size: i64,
+    #[cfg(feature = "metrics")]
+    metrics_labels: metrics::MetricsLabels,
+    #[cfg(feature = "hash-chain")]
+    hash_chain: bool,
+    #[cfg(feature = "signing")]
+    signer: Option<Arc<dyn disintegrate::Signer>>,
+    #[cfg(feature = "signing")]
+    signature_verifier: Option<Arc<dyn disintegrate::SignatureVerifier>>,
+    #[cfg(feature = "claim-check")]
+    claim_check: Option<Arc<ClaimCheck>>,
+    #[cfg(feature = "encryption")]
+    envelope_encryption: Option<EnvelopeEncryption>,
+    group_commit: Option<Arc<GroupCommit<E>>>,
+    query_cache: Option<Arc<QueryCache>>,
+    tenant: Option<Arc<Tenant>>,
     event_type: PhantomData<E>,
 }
 
@@ -79,6 +146,22 @@ where
             pool,
             concurrent_appends,
             serde,
+            stream_page_size: DEFAULT_STREAM_PAGE_SIZE,
+            #[cfg(feature = "metrics")]
+            metrics_labels: metrics::MetricsLabels::default(),
+            #[cfg(feature = "hash-chain")]
+            hash_chain: false,
+            #[cfg(feature = "signing")]
+            signer: None,
+            #[cfg(feature = "signing")]
+            signature_verifier: None,
+            #[cfg(feature = "claim-check")]
+            claim_check: None,
+            #[cfg(feature = "encryption")]
+            envelope_encryption: None,
+            group_commit: None,
+            query_cache: None,
+            tenant: None,
             event_type: PhantomData,
         }
     }
@@ -110,6 +193,1174 @@ where
         ));
         self
     }
+
+    /// Commits the concurrent appends together, in groups of at most `max_group_size` appends.
+    ///
+    /// The appends arriving while a group is being committed wait for the next group, which is committed in a
+    /// single transaction, saving a commit for each append under a high write concurrency. Each append is
+    /// still validated on its own: a conflicting append fails with `Error::Concurrency` without affecting the
+    /// rest of its group. If the transaction of a group fails, its appends fail with `Error::GroupCommit`.
+    pub fn with_group_commit(mut self, max_group_size: usize) -> Self {
+        assert!(max_group_size > 0, "group size must be greater than 0");
+        self.group_commit = Some(Arc::new(GroupCommit::new(max_group_size)));
+        self
+    }
+
+    /// Sets the number of events fetched from the database in a single round trip while streaming.
+    ///
+    /// Events are fetched in pages and consumed incrementally, so hydrating a state from a huge stream
+    /// keeps at most one page in memory and does not hold a database connection while the state is
+    /// mutated. The default is 1000 events.
+    pub fn with_stream_page_size(mut self, page_size: usize) -> Self {
+        assert!(page_size > 0, "page size must be greater than 0");
+        self.stream_page_size = page_size as i64;
+        self
+    }
+
+    /// Returns the number of events fetched in a single round trip while streaming.
+    #[cfg(feature = "listener")]
+    pub(crate) fn stream_page_size(&self) -> usize {
+        self.stream_page_size as usize
+    }
+
+    /// Caches the events of up to `capacity` recent stream queries for at most `ttl`.
+    ///
+    /// The repeated hydrations of hot entities are served from memory, without querying the database. The
+    /// entries are invalidated by the appends of this event store touching the event types and identifiers of
+    /// their queries, while the appends of other processes are only seen once the entries expire: a decision
+    /// made on a stale state still fails with `Error::Concurrency`, which invalidates the stale entries. The
+    /// queries of the event listeners are never cached.
+    pub fn with_query_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        assert!(capacity > 0, "capacity must be greater than 0");
+        self.query_cache = Some(Arc::new(QueryCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Sets the labels attached to the metrics of the event store.
+    ///
+    /// By default the metrics have no labels, to keep the number of time series bounded.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics_labels(mut self, labels: metrics::MetricsLabels) -> Self {
+        self.metrics_labels = labels;
+        self
+    }
+
+    /// Chains the appended events with their hashes, making the event log tamper-evident.
+    ///
+    /// Each event stores the SHA-256 hash of its ID, type and payload, chained to the hash of the
+    /// previous event. The appends are chained one at a time, so they no longer run concurrently.
+    #[cfg(feature = "hash-chain")]
+    pub fn with_hash_chain(mut self) -> Self {
+        self.hash_chain = true;
+        self
+    }
+
+    /// Signs the appended events with the signer.
+    ///
+    /// The signature covers the ID, the type and the payload of the event and is stored in the `signature`
+    /// column, along with the ID of the signing key.
+    #[cfg(feature = "signing")]
+    pub fn with_signer(mut self, signer: impl disintegrate::Signer + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Verifies the signatures of the streamed events with the verifier.
+    ///
+    /// Streaming fails with `Error::Signature` on the first event that is unsigned or whose signature is
+    /// not valid. The event listeners running on this event store verify the events they handle as well.
+    #[cfg(feature = "signing")]
+    pub fn with_signature_verifier(
+        mut self,
+        verifier: impl disintegrate::SignatureVerifier + 'static,
+    ) -> Self {
+        self.signature_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Stores the payloads exceeding the threshold of the claim check in its object store.
+    ///
+    /// The `event` table keeps a pointer to the stored payload, which is resolved transparently when the
+    /// events are read, so the readers must be configured with the same claim check.
+    #[cfg(feature = "claim-check")]
+    pub fn with_claim_check(mut self, claim_check: ClaimCheck) -> Self {
+        self.claim_check = Some(Arc::new(claim_check));
+        self
+    }
+
+    /// Encrypts the payloads of the appended events with the data keys of the envelope encryption.
+    ///
+    /// The payloads are encrypted before being claim-checked, and decrypted transparently when the events
+    /// are read, so the readers must be configured with the same envelope encryption. The SQL functions over
+    /// the JSON payloads, such as `sum` and the full-text search, do not see the encrypted payloads.
+    #[cfg(feature = "encryption")]
+    pub fn with_envelope_encryption(mut self, envelope_encryption: EnvelopeEncryption) -> Self {
+        self.envelope_encryption = Some(envelope_encryption);
+        self
+    }
+
+    /// Scopes the event store to a tenant isolated by the row-level security policies.
+    ///
+    /// The tenant is set in every transaction of the returned store, so the policies restrict its reads
+    /// and appends to the events of the tenant. The store is cheap to clone, so a scoped store can be
+    /// created for each request. The appends of a scoped store are not grouped, as a group would mix the
+    /// tenants in a single transaction.
+    pub fn with_tenant(
+        mut self,
+        row_level_security: &RowLevelSecurity,
+        tenant: impl ToString,
+    ) -> Self {
+        self.tenant = Some(Arc::new(Tenant {
+            setting: row_level_security.setting().to_string(),
+            value: tenant.to_string(),
+        }));
+        self.group_commit = None;
+        self
+    }
+
+    /// Begins a transaction, scoped to the tenant of the store if any.
+    pub(crate) async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(tenant) = &self.tenant {
+            sqlx::query("SELECT set_config($1, $2, true)")
+                .bind(&tenant.setting)
+                .bind(&tenant.value)
+                .execute(&mut *tx)
+                .await?;
+        }
+        Ok(tx)
+    }
+
+    /// Fetches the rows of the query, within a transaction scoped to the tenant of the store if any.
+    pub(crate) async fn fetch_all<'q, Q>(&self, query: Q) -> Result<Vec<PgRow>, sqlx::Error>
+    where
+        Q: Execute<'q, Postgres> + 'q,
+    {
+        if self.tenant.is_none() {
+            return self.pool.fetch_all(query).await;
+        }
+        let mut tx = self.begin().await?;
+        let rows = Executor::fetch_all(&mut *tx, query).await?;
+        tx.commit().await?;
+        Ok(rows)
+    }
+
+    /// Fetches the single row of the query, within a transaction scoped to the tenant of the store if any.
+    pub(crate) async fn fetch_one<'q, Q>(&self, query: Q) -> Result<PgRow, sqlx::Error>
+    where
+        Q: Execute<'q, Postgres> + 'q,
+    {
+        if self.tenant.is_none() {
+            return self.pool.fetch_one(query).await;
+        }
+        let mut tx = self.begin().await?;
+        let row = Executor::fetch_one(&mut *tx, query).await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    /// Returns the ID of the last event appended at or before `timestamp`, or `0` if there is none.
+    ///
+    /// The ID can be used to load a state as it was at `timestamp`, see `StateQuerier::query_at`.
+    pub async fn event_id_at(&self, timestamp: SystemTime) -> Result<PgEventId, Error> {
+        let seconds = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Ok(self
+            .fetch_one(
+                sqlx::query(
+                    "SELECT COALESCE(MAX(event_id), 0) FROM event WHERE inserted_at <= to_timestamp($1) AT TIME ZONE 'UTC'",
+                )
+                .bind(seconds),
+            )
+            .await?
+            .try_get(0)?)
+    }
+
+    /// Restricts the query to the events appended after `from` and up to `to`, included.
+    ///
+    /// The timestamps are translated into event IDs with `event_id_at`, so the returned query can be
+    /// streamed or used to hydrate a state like any other query.
+    pub async fn between<QE>(
+        &self,
+        query: StreamQuery<PgEventId, QE>,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<StreamQuery<PgEventId, QE>, Error>
+    where
+        QE: Event + Clone,
+    {
+        let origin = self.event_id_at(from).await?;
+        let position = self.event_id_at(to).await?;
+        Ok(query.between(origin, position))
+    }
+}
+
+impl<E, S> PgEventStore<E, S>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    /// Streams the events matching the query and the additional SQL criteria.
+    ///
+    /// The criteria are trusted SQL fragments built by the library, such as the filters of the event listeners.
+    #[cfg(feature = "listener")]
+    pub(crate) fn stream_with_criteria<'a, QE>(
+        &'a self,
+        query: &'a StreamQuery<PgEventId, QE>,
+        criteria: Option<String>,
+    ) -> BoxStream<'a, Result<PersistedEvent<PgEventId, QE>, Error>>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.stream_events(query, criteria, None)
+    }
+
+    /// Streams the events matching the query and the additional SQL criteria, serving them from the cache
+    /// if given.
+    fn stream_events<'a, QE>(
+        &'a self,
+        query: &'a StreamQuery<PgEventId, QE>,
+        criteria: Option<String>,
+        cache: Option<&'a QueryCache>,
+    ) -> BoxStream<'a, Result<PersistedEvent<PgEventId, QE>, Error>>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        stream! {
+            let cache_key = cache.map(|_| self.cache_key(query));
+            if let (Some(cache), Some(cache_key)) = (cache, &cache_key) {
+                if let Some(rows) = cache.get(cache_key) {
+                    for (id, payload) in rows.iter() {
+                        let payload = self.serde.deserialize(payload)?;
+                        yield Ok(PersistedEvent::new(*id, payload.try_into().map_err(|e| Error::QueryEventMapping(Box::new(e)))?));
+                    }
+                    return;
+                }
+            }
+            let generation = cache.map(QueryCache::generation);
+            let mut cached_rows = vec![];
+            let epoch: i64 = self.fetch_one(sqlx::query("SELECT event_store_current_epoch()")).await?.try_get(0)?;
+            let criteria = criteria.map(|criteria| format!(" AND ({criteria})")).unwrap_or_default();
+            let sql = stream_sql(query, epoch, &criteria);
+            #[cfg(feature = "tracing")]
+            let span = tracing::info_span!(
+                "disintegrate.stream",
+                epoch,
+                pages = tracing::field::Empty,
+                events = tracing::field::Empty,
+                last_event_id = tracing::field::Empty
+            );
+            #[cfg(feature = "tracing")]
+            let (mut pages, mut events) = (0, 0);
+            #[cfg(feature = "metrics")]
+            let (started_at, mut streamed_events) = (std::time::Instant::now(), 0);
+
+            let mut last_event_id: PgEventId = 0;
+            loop {
+                let page = self.fetch_all(
+                    sqlx::query(&sql)
+                        .bind(last_event_id)
+                        .bind(self.stream_page_size),
+                );
+                #[cfg(feature = "tracing")]
+                let page = tracing::Instrument::instrument(page, span.clone());
+                let rows = self.payload_rows(page.await?).await?;
+                let page_len = rows.len() as i64;
+                #[cfg(feature = "tracing")]
+                {
+                    pages += 1;
+                    events += page_len;
+                }
+                #[cfg(feature = "metrics")]
+                {
+                    streamed_events += page_len as u64;
+                }
+                #[cfg(feature = "signing")]
+                if let Some(verifier) = &self.signature_verifier {
+                    let events: Vec<(PgEventId, &[u8])> = rows.iter().map(|(id, payload)| (*id, payload.as_slice())).collect();
+                    signing::verify(self, verifier.as_ref(), &events).await?;
+                }
+                for (id, payload) in rows {
+                    last_event_id = id;
+                    let event = self.serde.deserialize(&payload)?;
+                    if cache.is_some() {
+                        cached_rows.push((id, payload));
+                    }
+                    yield Ok(PersistedEvent::new(id, event.try_into().map_err(|e| Error::QueryEventMapping(Box::new(e)))?));
+                }
+                if page_len < self.stream_page_size {
+                    break;
+                }
+            }
+            #[cfg(feature = "tracing")]
+            span.record("pages", pages)
+                .record("events", events)
+                .record("last_event_id", last_event_id);
+            #[cfg(feature = "metrics")]
+            metrics::record_query(query, streamed_events, started_at.elapsed(), self.metrics_labels);
+            if let (Some(cache), Some(cache_key), Some(generation)) = (cache, cache_key, generation) {
+                cache.insert(cache_key, query, cached_rows, generation);
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<E, S> PgEventStore<E, S>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    /// Counts the events matching the query, without fetching them.
+    ///
+    /// The returned version is the ID of the last matching event, so the count can be used to make
+    /// a decision and append its events with the query and the version for conflict detection.
+    pub async fn count<QE>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+    ) -> Result<ScalarState<i64>, Error>
+    where
+        QE: Event + Clone,
+    {
+        let sql = format!(
+            "SELECT COUNT(*), COALESCE(MAX(event_id), 0) FROM event WHERE event_id <= event_store_current_epoch() AND ({})",
+            CriteriaBuilder::new(query).build()
+        );
+        let row = self.fetch_one(sqlx::query(&sql)).await?;
+        Ok(ScalarState {
+            value: row.try_get(0)?,
+            version: row.try_get(1)?,
+        })
+    }
+
+    /// Sums a numeric field of the payloads of the events matching the query, without fetching them.
+    ///
+    /// The payloads must be serialized as JSON. The field is a path of keys separated by dots, such as
+    /// `amount` or `order.total`, and events without the field are ignored. The returned version is the
+    /// ID of the last matching event.
+    pub async fn sum<QE>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+        field: &str,
+    ) -> Result<ScalarState<f64>, Error>
+    where
+        QE: Event + Clone,
+    {
+        let sql = format!(
+            "SELECT COALESCE(SUM((convert_from(payload, 'UTF8')::jsonb #>> $1)::numeric), 0)::float8, COALESCE(MAX(event_id), 0) FROM event WHERE event_id <= event_store_current_epoch() AND ({})",
+            CriteriaBuilder::new(query).build()
+        );
+        let path: Vec<&str> = field.split('.').collect();
+        let row = self.fetch_one(sqlx::query(&sql).bind(path)).await?;
+        Ok(ScalarState {
+            value: row.try_get(0)?,
+            version: row.try_get(1)?,
+        })
+    }
+}
+
+impl<E, S> PgEventStore<E, S>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    /// Enables the full-text search over the payload `fields` of the events.
+    ///
+    /// The store maintains a `search_vector` column, indexed with GIN and computed by a trigger from the
+    /// text of the fields at every insert. The fields are looked up in the object of the event variant and
+    /// then at the top level of the payload, so the payloads must be serialized as JSON. The stored events
+    /// are reindexed with `reindex_search` only when the fields differ from the enabled ones, so it can be
+    /// called at startup.
+    pub async fn enable_search(&self, fields: &[Identifier]) -> Result<(), Error> {
+        let fields = fields
+            .iter()
+            .map(|field| {
+                format!("COALESCE(payload_json -> NEW.event_type ->> '{field}', payload_json ->> '{field}')")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let fields = if fields.is_empty() {
+            "NULL".to_string()
+        } else {
+            fields
+        };
+        let search_vector = format!(
+            r#"
+DECLARE
+    payload_json jsonb;
+BEGIN
+    BEGIN
+        payload_json := convert_from(NEW.payload, 'UTF8')::jsonb;
+    EXCEPTION WHEN others THEN
+        payload_json := NULL;
+    END;
+    NEW.search_vector := to_tsvector('simple', concat_ws(' ', {fields}));
+    RETURN NEW;
+END;
+"#
+        );
+        let enabled: Option<String> = sqlx::query_scalar(
+            "SELECT prosrc FROM pg_proc WHERE oid = to_regproc('event_store_search_vector')",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if enabled.as_deref() == Some(search_vector.as_str()) {
+            return Ok(());
+        }
+
+        sqlx::query("ALTER TABLE event ADD COLUMN IF NOT EXISTS search_vector tsvector")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_event_search_vector ON event USING GIN (search_vector)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE OR REPLACE FUNCTION event_store_search_vector() RETURNS trigger AS $${search_vector}$$ LANGUAGE plpgsql"
+        ))
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE OR REPLACE TRIGGER event_search_vector BEFORE INSERT OR UPDATE OF payload ON event FOR EACH ROW EXECUTE FUNCTION event_store_search_vector()",
+        )
+        .execute(&self.pool)
+        .await?;
+        self.reindex_search().await
+    }
+
+    /// Recomputes the search vector of the stored events with the fields enabled by `enable_search`.
+    ///
+    /// The events are updated in batches of 10,000 IDs, each committed on its own, so the
+    /// reindex does not lock the whole `event` table. An interrupted reindex is resumed by calling it again.
+    pub async fn reindex_search(&self) -> Result<(), Error> {
+        let (first, last): (Option<PgEventId>, Option<PgEventId>) =
+            sqlx::query_as("SELECT MIN(event_id), MAX(event_id) FROM event")
+                .fetch_one(&self.pool)
+                .await?;
+        let (Some(mut start), Some(last)) = (first, last) else {
+            return Ok(());
+        };
+        while start <= last {
+            sqlx::query(
+                "UPDATE event SET payload = payload WHERE event_id >= $1 AND event_id < $2",
+            )
+            .bind(start)
+            .bind(start + SEARCH_REINDEX_BATCH)
+            .execute(&self.pool)
+            .await?;
+            start += SEARCH_REINDEX_BATCH;
+        }
+        Ok(())
+    }
+
+    /// Converts the `event` table into a TimescaleDB hypertable, compressing its old chunks if enabled.
+    ///
+    /// The `timescaledb` extension must be installed in the database. The existing events are moved into
+    /// the chunks, which locks the table until the conversion completes. Once the table is a hypertable,
+    /// only the compression is enabled, so it can be called at startup.
+    pub async fn create_hypertable(&self, hypertable: &Hypertable) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let compression_enabled: Option<bool> = sqlx::query_scalar(
+            "SELECT compression_enabled FROM timescaledb_information.hypertables WHERE hypertable_name = 'event'",
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if compression_enabled.is_none() {
+            for statement in hypertable.create_statements() {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+        }
+        for statement in hypertable.compression_statements(compression_enabled.unwrap_or_default())
+        {
+            sqlx::query(&statement).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Applies the storage options to the `event` table.
+    ///
+    /// The BRIN indexes are built concurrently and the table parameters only affect the future inserts
+    /// and vacuums, so the options can be applied to a live event store, e.g. at startup. The insert
+    /// threshold of the autovacuum requires PostgreSQL 13 or later.
+    pub async fn tune_storage(&self, options: &StorageOptions) -> Result<(), Error> {
+        for statement in options.statements() {
+            sqlx::query(&statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the statements that enable the row-level security policies, to be run by a migration tool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tenant identifier is not a domain identifier of the events.
+    pub fn row_level_security_statements(
+        &self,
+        row_level_security: &RowLevelSecurity,
+    ) -> Vec<String> {
+        let tenant_identifier = E::SCHEMA
+            .domain_identifiers
+            .iter()
+            .find(|info| info.ident == row_level_security.tenant_identifier())
+            .unwrap_or_else(|| {
+                panic!(
+                    "tenant identifier {} is not a domain identifier of the events",
+                    row_level_security.tenant_identifier()
+                )
+            });
+        row_level_security.statements(sql_type(tenant_identifier))
+    }
+
+    /// Enables the row-level security policies isolating the events of the tenants.
+    ///
+    /// The policies are replaced in a single transaction, so it can be called at startup, after `new`, which
+    /// recreates the epoch functions. They apply to the roles that do not own the tables, so the application
+    /// must connect with a role other than the one that runs the migrations. The events without a tenant are
+    /// only visible to the roles that bypass the policies.
+    pub async fn enable_row_level_security(
+        &self,
+        row_level_security: &RowLevelSecurity,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for statement in self.row_level_security_statements(row_level_security) {
+            sqlx::query(&statement).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the events matching the query whose searchable fields contain `text`.
+    ///
+    /// The text uses the web search syntax of PostgreSQL: words are all required, quoted phrases must
+    /// appear in order and `-` excludes a word. The search is not stemmed, so it is suited to emails,
+    /// names and references. The search must be enabled with `enable_search`.
+    pub async fn search<QE>(
+        &self,
+        text: &str,
+        query: &StreamQuery<PgEventId, QE>,
+    ) -> Result<Vec<PersistedEvent<PgEventId, QE>>, Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let sql = format!(
+            "SELECT event_id, payload FROM event WHERE event_id <= event_store_current_epoch() AND search_vector @@ websearch_to_tsquery('simple', $1) AND ({}) ORDER BY event_id ASC",
+            CriteriaBuilder::new(query).build()
+        );
+        let rows = self.fetch_all(sqlx::query(&sql).bind(text)).await?;
+        self.decode_rows(rows).await
+    }
+
+    /// Verifies the hash chain of the events matching the query.
+    ///
+    /// Each event is verified against its content and the hash of the previous event of the chain, so an
+    /// altered event breaks its own link and the link of the next event. The events appended before the
+    /// hash chain was enabled are counted as unchained.
+    #[cfg(feature = "hash-chain")]
+    pub async fn verify_chain<QE>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+    ) -> Result<ChainVerification, Error>
+    where
+        QE: Event + Clone,
+    {
+        hash_chain::verify(self, &CriteriaBuilder::new(query).build()).await
+    }
+
+    /// Returns the last event of the hash chain.
+    ///
+    /// Recording the head outside of the database, e.g. in an audit report, makes the removal of the last
+    /// events of the chain evident as well.
+    #[cfg(feature = "hash-chain")]
+    pub async fn chain_head(&self) -> Result<Option<ChainHead>, Error> {
+        hash_chain::head(&self.pool).await
+    }
+
+    /// Checks the global event ordering for the inconsistencies left by the appends that crashed.
+    ///
+    /// The appends started within the `grace` period are ignored, as they may still be in progress.
+    /// The check only reads the tables, so it can run against a live event store.
+    pub async fn check_integrity(&self, grace: Duration) -> Result<IntegrityReport, Error> {
+        let uncommitted_events = sqlx::query_scalar(integrity::UNCOMMITTED_EVENTS_SQL)
+            .bind(grace.as_secs_f64())
+            .fetch_all(&self.pool)
+            .await?;
+        let missing_events = sqlx::query_scalar(integrity::MISSING_EVENTS_SQL)
+            .fetch_all(&self.pool)
+            .await?;
+        let aborted_appends: i64 = sqlx::query_scalar(&format!(
+            "SELECT count(*) FROM event_sequence WHERE {}",
+            integrity::ABORTED_APPENDS_FILTER
+        ))
+        .bind(grace.as_secs_f64())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(IntegrityReport {
+            uncommitted_events,
+            missing_events,
+            aborted_appends: aborted_appends as u64,
+        })
+    }
+
+    /// Repairs the global event ordering, to be run as a maintenance job.
+    ///
+    /// The events of the appends that were not committed within the `grace` period are removed, and
+    /// the sequence entries of the failed appends are compacted. The committed sequence entries without
+    /// an event cannot be repaired and are left untouched: use `check_integrity` to find them.
+    ///
+    /// The repair runs in a single transaction. The listeners that already handled the removed events
+    /// are not notified, so the events must be reverted by hand in their read models.
+    pub async fn repair(&self, grace: Duration) -> Result<RepairReport, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut removed_events: Vec<PgEventId> = sqlx::query_scalar(&format!(
+            "DELETE FROM event WHERE event_id IN ({}) RETURNING event_id",
+            integrity::UNCOMMITTED_EVENTS_SQL
+        ))
+        .bind(grace.as_secs_f64())
+        .fetch_all(&mut *tx)
+        .await?;
+        let compacted = sqlx::query(&format!(
+            "DELETE FROM event_sequence WHERE {}",
+            integrity::ABORTED_APPENDS_FILTER
+        ))
+        .bind(grace.as_secs_f64())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        removed_events.sort();
+        Ok(RepairReport {
+            removed_events,
+            compacted_sequence_entries: compacted.rows_affected(),
+        })
+    }
+
+    /// Applies the retention policy of the event types, to be run as a maintenance job.
+    ///
+    /// The expired events are archived or deleted, along with their entries in the `event_sequence` table, so
+    /// the integrity check does not report them as missing and the appends are validated against the retained
+    /// events only. The snapshots built from an expired event are deleted, as hydrating their state again would
+    /// not yield the same result, and the cached queries are dropped. The snapshots cached in memory by the
+    /// running applications are not invalidated.
+    ///
+    /// The policy is applied in a single transaction. The removal of the events breaks the hash chain, if it is
+    /// enabled, and the listeners that already handled the expired events are not notified.
+    pub async fn apply_retention(
+        &self,
+        policy: &RetentionPolicy,
+    ) -> Result<RetentionReport, Error> {
+        let mut tx = self.begin().await?;
+        let has_snapshots: bool = sqlx::query_scalar("SELECT to_regclass('snapshot') IS NOT NULL")
+            .fetch_one(&mut *tx)
+            .await?;
+        let mut report = RetentionReport::default();
+        for (event_type, sql) in policy.statements() {
+            let expired_events: Vec<PgEventId> =
+                sqlx::query_scalar(&sql).fetch_all(&mut *tx).await?;
+            let Some(first_expired_event) = expired_events.iter().min().copied() else {
+                continue;
+            };
+            sqlx::query("DELETE FROM event_sequence WHERE event_id = ANY($1)")
+                .bind(&expired_events)
+                .execute(&mut *tx)
+                .await?;
+            if has_snapshots {
+                report.invalidated_snapshots +=
+                    invalidate_snapshots(&mut tx, first_expired_event, &[event_type]).await?;
+            }
+            report.expired_events.extend(expired_events);
+        }
+        tx.commit().await?;
+        if let Some(query_cache) = &self.query_cache {
+            query_cache.clear();
+        }
+        report.expired_events.sort();
+        Ok(report)
+    }
+
+    /// Rewrites the events matching the query through the `anonymize` function, to be run as a maintenance
+    /// job where the crypto-shredding of personal data is not accepted.
+    ///
+    /// The query selects the events of the data subject, usually by its domain identifiers. Each event is
+    /// replaced by the result of `anonymize` in place, so the replacement stream keeps the IDs and the order of
+    /// the events, and its domain identifiers are updated in the `event_sequence` table as well. The function
+    /// must not change the type of the events, or the rewrite fails with `Error::AnonymizedEventType`.
+    ///
+    /// The rewritten events are signed again if a signer is set, while the hash chain, if enabled, no longer
+    /// matches them. The snapshots built from a rewritten event are deleted and the cached queries are dropped.
+    /// The listeners that already handled a rewritten event are returned in the report, to be rewound.
+    ///
+    /// The rewrite runs in a single transaction. The payloads offloaded to the object store by the claim check
+    /// are not deleted.
+    pub async fn anonymize<QE, F>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+        mut anonymize: F,
+    ) -> Result<AnonymizationReport, Error>
+    where
+        QE: Event + Clone,
+        E: Clone,
+        F: FnMut(E) -> E,
+    {
+        let mut tx = self.begin().await?;
+        let rows = sqlx::query(&format!(
+            "SELECT event_id, payload FROM event WHERE event_id <= event_store_current_epoch() AND ({}) ORDER BY event_id FOR UPDATE",
+            CriteriaBuilder::new(query).build()
+        ))
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut report = AnonymizationReport::default();
+        let mut event_types = BTreeSet::new();
+        for (event_id, payload) in self.payload_rows(rows).await? {
+            let event = self.serde.deserialize(&payload)?;
+            let event_type = event.name();
+            let rewritten = PersistedEvent::new(event_id, anonymize(event));
+            if rewritten.name() != event_type {
+                return Err(Error::AnonymizedEventType {
+                    event_id,
+                    from: event_type,
+                    to: rewritten.name(),
+                });
+            }
+            #[cfg(feature = "signing")]
+            let signature = self
+                .signer
+                .as_ref()
+                .map(|signer| {
+                    signing::sign(
+                        signer.as_ref(),
+                        std::slice::from_ref(&rewritten),
+                        &self.serde,
+                    )
+                })
+                .transpose()?
+                .and_then(|signatures| signatures.into_iter().next());
+            #[cfg(not(feature = "signing"))]
+            let signature = None;
+            let domain_identifiers = rewritten.domain_identifiers();
+            let payload = self.serde.serialize(rewritten.into_inner());
+            #[cfg(any(feature = "claim-check", feature = "encryption"))]
+            let payload = if self.encodes_payloads() {
+                self.encode_payload(payload).await?
+            } else {
+                payload
+            };
+            for mut rewrite in [
+                anonymization::rewrite_query(
+                    "event",
+                    event_id,
+                    &domain_identifiers,
+                    Some(payload),
+                    signature,
+                ),
+                anonymization::rewrite_query(
+                    "event_sequence",
+                    event_id,
+                    &domain_identifiers,
+                    None,
+                    None,
+                ),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                rewrite.build().execute(&mut *tx).await?;
+            }
+            event_types.insert(event_type);
+            report.rewritten_events.push(event_id);
+        }
+        let Some(first_rewritten_event) = report.rewritten_events.first().copied() else {
+            return Ok(report);
+        };
+        let (has_snapshots, has_listeners): (bool, bool) = sqlx::query_as(
+            "SELECT to_regclass('snapshot') IS NOT NULL, to_regclass('event_listener') IS NOT NULL",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let event_types: Vec<&str> = event_types.into_iter().collect();
+        if has_snapshots {
+            report.invalidated_snapshots =
+                invalidate_snapshots(&mut tx, first_rewritten_event, &event_types).await?;
+        }
+        if has_listeners {
+            report.stale_listeners = sqlx::query_scalar(
+                "SELECT id FROM event_listener WHERE last_processed_event_id >= $1 ORDER BY id",
+            )
+            .bind(first_rewritten_event)
+            .fetch_all(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        if let Some(query_cache) = &self.query_cache {
+            query_cache.clear();
+        }
+        Ok(report)
+    }
+
+    /// Returns the actors stored with the events, by event ID.
+    ///
+    /// The events appended outside of an actor context are omitted, so the audit of the event log can tell
+    /// who appended each event without decoding the payloads.
+    pub async fn actors(&self, event_ids: &[PgEventId]) -> Result<Vec<(PgEventId, Actor)>, Error> {
+        let rows = self
+            .fetch_all(
+                sqlx::query(
+                    "SELECT event_id, actor FROM event WHERE event_id = ANY($1) AND actor IS NOT NULL ORDER BY event_id",
+                )
+                .bind(event_ids),
+            )
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get::<String, _>(1)?)))
+            .collect::<Result<Vec<(PgEventId, String)>, sqlx::Error>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(event_id, actor)| actor.parse().ok().map(|actor| (event_id, actor)))
+            .collect())
+    }
+
+    /// Returns the trace contexts stored with the events, by event ID.
+    ///
+    /// The events appended outside of a trace are omitted. The returned contexts can be used as the
+    /// parents of the spans that handle the events, to continue the traces that appended them.
+    #[cfg(feature = "otel")]
+    pub async fn trace_contexts(
+        &self,
+        event_ids: &[PgEventId],
+    ) -> Result<Vec<(PgEventId, opentelemetry::Context)>, Error> {
+        let rows = self
+            .fetch_all(
+                sqlx::query(
+                    "SELECT event_id, trace_context FROM event WHERE event_id = ANY($1) AND trace_context IS NOT NULL ORDER BY event_id",
+                )
+                .bind(event_ids),
+            )
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get::<String, _>(1)?)))
+            .collect::<Result<Vec<(PgEventId, String)>, sqlx::Error>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(event_id, trace_context)| {
+                otel::extract_trace_context(&trace_context).map(|context| (event_id, context))
+            })
+            .collect())
+    }
+
+    /// Links the appended events to the hash chain, if it is enabled.
+    #[cfg(feature = "hash-chain")]
+    async fn link_events(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        events: &[PersistedEvent<PgEventId, E>],
+    ) -> Result<Option<Vec<hash_chain::ChainLink>>, Error>
+    where
+        E: Clone,
+    {
+        if !self.hash_chain {
+            return Ok(None);
+        }
+        Ok(Some(hash_chain::extend(tx, events, &self.serde).await?))
+    }
+
+    /// Reserves the IDs of the events in the `event_sequence` table with a single statement.
+    ///
+    /// The reservation is committed on its own, outside of the transaction of the append, so the concurrent
+    /// appends see the staged events while validating. The events not validated by the append are staged as
+    /// `consumed`.
+    async fn stage_events(
+        &self,
+        events: Vec<E>,
+        consumed: bool,
+        actor: Option<Actor>,
+    ) -> Result<Vec<PersistedEvent<PgEventId, E>>, Error>
+    where
+        E: Clone,
+    {
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut event_ids: Vec<PgEventId> = {
+            let mut sequence_insert = InsertEventSequenceBuilder::batch(&events);
+            if consumed {
+                sequence_insert = sequence_insert.with_consumed(true);
+            }
+            self.fetch_all(sequence_insert.build())
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect()
+        };
+        // The IDs increase with the ordinal of the events, whatever the order of the returned rows.
+        event_ids.sort_unstable();
+        Ok(event_ids
+            .into_iter()
+            .zip(events)
+            .map(|(event_id, event)| PersistedEvent::new(event_id, event).with_actor(actor.clone()))
+            .collect())
+    }
+
+    /// Inserts the events in the transaction, chaining and signing them if enabled.
+    async fn insert_events(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        events: &[PersistedEvent<PgEventId, E>],
+    ) -> Result<(), Error>
+    where
+        E: Clone,
+    {
+        #[cfg(feature = "hash-chain")]
+        let chain = self.link_events(tx, events).await?;
+        #[cfg(feature = "signing")]
+        let signatures = self
+            .signer
+            .as_ref()
+            .map(|signer| signing::sign(signer.as_ref(), events, &self.serde))
+            .transpose()?;
+        #[cfg(any(feature = "claim-check", feature = "encryption"))]
+        let payloads = if self.encodes_payloads() {
+            Some(
+                futures::future::try_join_all(events.iter().map(|event| {
+                    self.encode_payload(self.serde.serialize(event.clone().into_inner()))
+                }))
+                .await?,
+            )
+        } else {
+            None
+        };
+        let mut insert =
+            InsertEventsBuilder::new(events, &self.serde).with_trace_context(trace_context());
+        #[cfg(any(feature = "claim-check", feature = "encryption"))]
+        if let Some(payloads) = &payloads {
+            insert = insert.with_payloads(payloads);
+        }
+        #[cfg(feature = "hash-chain")]
+        if let Some(chain) = &chain {
+            insert = insert.with_hash_chain(chain);
+        }
+        #[cfg(feature = "signing")]
+        if let Some(signatures) = &signatures {
+            insert = insert.with_signatures(signatures);
+        }
+        insert.build().execute(&mut **tx).await?;
+        Ok(())
+    }
+
+    /// Drops the cached query results that may be invalidated by the events.
+    fn invalidate_cached_queries(&self, events: &[PersistedEvent<PgEventId, E>]) {
+        if let Some(query_cache) = &self.query_cache {
+            query_cache.invalidate(events);
+        }
+    }
+
+    /// Returns the key of the cached events of the query, which is distinct for each tenant.
+    fn cache_key<QE>(&self, query: &StreamQuery<PgEventId, QE>) -> String
+    where
+        QE: Event + Clone,
+    {
+        let criteria = CriteriaBuilder::new(query).build();
+        match &self.tenant {
+            Some(tenant) => format!("{:?}:{criteria}", tenant.value),
+            None => criteria,
+        }
+    }
+
+    /// Returns `true` if the stored payloads differ from the serialized events, as they are encrypted or
+    /// claim-checked.
+    #[cfg(any(feature = "claim-check", feature = "encryption"))]
+    fn encodes_payloads(&self) -> bool {
+        #[cfg(feature = "claim-check")]
+        if self.claim_check.is_some() {
+            return true;
+        }
+        #[cfg(feature = "encryption")]
+        if self.envelope_encryption.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Encrypts a serialized payload and checks it in, returning the payload to store.
+    #[cfg(any(feature = "claim-check", feature = "encryption"))]
+    async fn encode_payload(&self, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "encryption")]
+        let payload = match &self.envelope_encryption {
+            Some(envelope_encryption) => envelope_encryption.encrypt(&self.pool, payload).await?,
+            None => payload,
+        };
+        #[cfg(feature = "claim-check")]
+        let payload = match &self.claim_check {
+            Some(claim_check) => claim_check.check_in(payload).await?,
+            None => payload,
+        };
+        Ok(payload)
+    }
+
+    /// Checks out and decrypts a stored payload, returning the serialized event.
+    #[cfg(any(feature = "claim-check", feature = "encryption"))]
+    pub(crate) async fn decode_payload(&self, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "claim-check")]
+        let payload = match &self.claim_check {
+            Some(claim_check) => claim_check.check_out(payload).await?,
+            None => payload,
+        };
+        #[cfg(feature = "encryption")]
+        let payload = match &self.envelope_encryption {
+            Some(envelope_encryption) => envelope_encryption.decrypt(&self.pool, payload).await?,
+            None => payload,
+        };
+        Ok(payload)
+    }
+
+    /// Returns the event IDs and the payloads of rows made of the event ID and the payload, fetching the
+    /// claim-checked payloads and decrypting the encrypted ones.
+    pub(crate) async fn payload_rows(
+        &self,
+        rows: Vec<PgRow>,
+    ) -> Result<Vec<(PgEventId, Vec<u8>)>, Error> {
+        let rows = rows.into_iter().map(|row| (row.get(0), row.get(1)));
+        #[cfg(any(feature = "claim-check", feature = "encryption"))]
+        if self.encodes_payloads() {
+            return futures::future::try_join_all(rows.map(|(id, payload)| async move {
+                Ok::<_, Error>((id, self.decode_payload(payload).await?))
+            }))
+            .await;
+        }
+        Ok(rows.collect())
+    }
+
+    /// Decodes rows made of the event ID and the payload into persisted events.
+    pub(crate) async fn decode_rows<QE>(
+        &self,
+        rows: Vec<PgRow>,
+    ) -> Result<Vec<PersistedEvent<PgEventId, QE>>, Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.payload_rows(rows)
+            .await?
+            .into_iter()
+            .map(|(id, payload)| {
+                let payload = self.serde.deserialize(&payload)?;
+                Ok(PersistedEvent::new(
+                    id,
+                    payload
+                        .try_into()
+                        .map_err(|e| Error::QueryEventMapping(Box::new(e)))?,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl<E, S> PgEventStore<E, S>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    /// Explains how the database executes the stream of the query.
+    ///
+    /// The returned plan contains the SQL statement that fetches the first page of the events, the plan
+    /// printed by `EXPLAIN` and the warnings about the filters that cannot use an index of the `event`
+    /// table, such as identifiers without an index or range filters on hash indexes. The statement is
+    /// planned, not executed.
+    pub async fn explain<QE>(&self, query: &StreamQuery<PgEventId, QE>) -> Result<QueryPlan, Error>
+    where
+        QE: Event + Clone,
+    {
+        let sql = stream_sql(query, PgEventId::MAX, "");
+        let plan: Vec<String> = sqlx::query_scalar(&format!("EXPLAIN {sql}"))
+            .bind(0 as PgEventId)
+            .bind(self.stream_page_size)
+            .fetch_all(&self.pool)
+            .await?;
+        let indexes = self.event_indexes().await?;
+        Ok(QueryPlan::new(sql, plan.join("\n"), lint(query, &indexes)))
+    }
+
+    /// Recommends the indexes of the `event` table missing for the queries registered in the advisor.
+    ///
+    /// The advice also reports the filters that no index can serve, such as the payload filters.
+    pub async fn advise_indexes(&self, advisor: &IndexAdvisor) -> Result<IndexAdvice, Error> {
+        Ok(advisor.advise(&self.event_indexes().await?))
+    }
+
+    /// Creates the indexes missing for the queries registered in the advisor, returning the created ones.
+    ///
+    /// The indexes are built concurrently, so the appends are not blocked while they are built. Running it
+    /// again is a no-op once the indexes exist, so it can be called at startup with the queries of the
+    /// application.
+    pub async fn create_recommended_indexes(
+        &self,
+        advisor: &IndexAdvisor,
+    ) -> Result<Vec<IndexRecommendation>, Error> {
+        let advice = self.advise_indexes(advisor).await?;
+        for recommendation in advice.recommendations() {
+            sqlx::query(recommendation.definition())
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(advice.recommendations().to_vec())
+    }
+
+    /// Returns the indexes of the `event` table.
+    async fn event_indexes(&self) -> Result<EventIndexes, Error> {
+        let definitions: Vec<String> =
+            sqlx::query_scalar("SELECT indexdef FROM pg_indexes WHERE tablename = 'event'")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(EventIndexes::from_definitions(
+            definitions.iter().map(String::as_str),
+        ))
+    }
+}
+
+/// A scalar computed by the database from the events matching a query, along with the ID of the last
+/// matching event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalarState<T> {
+    value: T,
+    version: PgEventId,
+}
+
+impl<T: Copy> ScalarState<T> {
+    /// Returns the computed value.
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Returns the ID of the last event matching the query, or `0` if there is none.
+    pub fn version(&self) -> PgEventId {
+        self.version
+    }
+}
+
+/// Reports the event store as unhealthy when the `event` table cannot be read.
+#[async_trait]
+impl<E, S> ComponentHealth for PgEventStore<E, S>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        "event_store"
+    }
+
+    async fn health(&self) -> HealthStatus {
+        match sqlx::query("SELECT 1 FROM event LIMIT 1")
+            .execute(&self.pool)
+            .await
+        {
+            Ok(_) => HealthStatus::Healthy,
+            Err(err) => HealthStatus::Unhealthy(err.to_string()),
+        }
+    }
 }
 
 /// Implementation of the event store using PostgreSQL.
@@ -148,29 +1399,112 @@ where
         QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
         <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
     {
-        stream! {
-            let epoch: i64 = sqlx::query_scalar("SELECT event_store_current_epoch()").fetch_one(&self.pool).await?;
-            let sql = format!("SELECT event_id, payload FROM event WHERE event_id <= {epoch} AND ({}) ORDER BY event_id ASC", CriteriaBuilder::new(query).build());
+        self.stream_events(query, None, self.query_cache.as_deref())
+    }
 
-            for await row in sqlx::query(&sql)
-            .fetch(&self.pool) {
-                let row = row?;
-                let id = row.get(0);
+    /// Checks whether any event matches the provided query.
+    ///
+    /// The check is a single `EXISTS` lookup, which stops at the first matching row without fetching the
+    /// payloads.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The stream query specifying the criteria for filtering events.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if at least one event matches the query, or an error of type `Self::Error`.
+    async fn exists<QE>(&self, query: &StreamQuery<PgEventId, QE>) -> Result<bool, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM event WHERE event_id <= event_store_current_epoch() AND ({}) LIMIT 1)",
+            CriteriaBuilder::new(query).build()
+        );
+        Ok(self.fetch_one(sqlx::query(&sql)).await?.try_get(0)?)
+    }
 
-                let payload = self.serde.deserialize(row.get(1))?;
-                yield Ok(PersistedEvent::new(id, payload.try_into().map_err(|e| Error::QueryEventMapping(Box::new(e)))?));
+    /// Returns the ID of the event preceding the window of the provided query.
+    ///
+    /// A `LastEvents` window is found by skipping the last events matching the query, and a `Within`
+    /// window by the last matching event appended before the beginning of the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The stream query specifying the criteria for filtering events.
+    /// * `window` - The window of the stream.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the ID after which the events of the window start, or an error of type `Self::Error`.
+    async fn window_origin<QE>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+        window: HydrationWindow,
+    ) -> Result<PgEventId, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let criteria = CriteriaBuilder::new(query).build();
+        let origin = match window {
+            HydrationWindow::LastEvents(events) => {
+                let sql = format!("SELECT event_id FROM event WHERE event_id <= event_store_current_epoch() AND ({criteria}) ORDER BY event_id DESC OFFSET $1 LIMIT 1");
+                self.fetch_all(sqlx::query(&sql).bind(events as i64))
+                    .await?
+                    .first()
+                    .map(|row| row.try_get(0))
+                    .transpose()?
             }
-        }
-        .boxed()
+            HydrationWindow::Within(duration) => {
+                let start = SystemTime::now()
+                    .checked_sub(duration)
+                    .unwrap_or(UNIX_EPOCH)
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let sql = format!("SELECT MAX(event_id) FROM event WHERE inserted_at < to_timestamp($1) AT TIME ZONE 'UTC' AND ({criteria})");
+                self.fetch_one(sqlx::query(&sql).bind(start))
+                    .await?
+                    .try_get(0)?
+            }
+        };
+        Ok(origin.unwrap_or_default())
+    }
+
+    /// Reads the events matching the provided query, bounded and ordered by the provided options.
+    ///
+    /// The events are fetched with a single query ordered by ID, which is served by the primary key
+    /// index in both directions.
+    async fn read<QE>(
+        &self,
+        query: &StreamQuery<PgEventId, QE>,
+        options: ReadOptions,
+    ) -> Result<Vec<PersistedEvent<PgEventId, QE>>, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let sql = format!(
+            "SELECT event_id, payload FROM event WHERE event_id <= event_store_current_epoch() AND ({}) ORDER BY event_id {} LIMIT $1",
+            CriteriaBuilder::new(query).build(),
+            if options.is_descending() { "DESC" } else { "ASC" }
+        );
+        let limit = options.max_events().map(|limit| limit as i64);
+        let rows = self.fetch_all(sqlx::query(&sql).bind(limit)).await?;
+        self.decode_rows(rows).await
     }
 
     /// Appends new events to the event store.
     ///
     /// This function inserts the provided `events` into the PostgreSQL event store by performing
     /// two separate inserts. First, it inserts the events into the `event_sequence` table to reclaim
-    /// a set of IDs for the events. Then, it inserts the events into the `event` table along with
-    /// their IDs, event types, domain identifiers, and payloads. Finally, it marks the event IDs as `consumed`
-    /// in the event sequence table. If marking the event IDs as consumed fails (e.g., another process has already consumed the IDs),
+    /// a set of IDs for the events. Then, it marks the event IDs as `consumed` in the event sequence table
+    /// and inserts the events into the `event` table along with their IDs, event types, domain identifiers,
+    /// and payloads, in the same transaction, so the events are not visible until the append commits.
+    /// If marking the event IDs as consumed fails (e.g., another process has already consumed the IDs),
     /// a conflict error is raised. This conflict indicates that the data retrieved by the query is stale,
     /// meaning that the events generated are no longer valid due to being generated from an old version
     /// of the event store.
@@ -185,6 +1519,14 @@ where
     ///
     /// A `Result` containing a vector of `PersistedEvent` representing the appended events,
     /// or an error of type `Self::Error`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.append",
+            skip_all,
+            fields(events = events.len(), version, last_event_id = tracing::field::Empty)
+        )
+    )]
     async fn append<QE>(
         &self,
         events: Vec<E>,
@@ -195,40 +1537,48 @@ where
         E: Clone + 'async_trait,
         QE: Event + Clone + Send + Sync,
     {
-        let mut persisted_events = Vec::with_capacity(events.len());
-        let mut persisted_events_ids: Vec<PgEventId> = Vec::with_capacity(events.len());
+        if let Some(group_commit) = &self.group_commit {
+            let criteria = CriteriaBuilder::new(&query.change_origin(version))
+                .without_payload_filters()
+                .build();
+            return self
+                .append_grouped(group_commit, events, Some(criteria))
+                .await;
+        }
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
         let _permit = self.concurrent_appends.acquire().await?;
-        let mut tx = self.pool.begin().await?;
+        let mut tx = self.begin().await?;
         sqlx::query("SELECT event_store_begin_epoch()")
             .execute(&mut *tx)
             .await?;
-        for event in events {
-            let mut staged_event_insert = InsertEventSequenceBuilder::new(&event);
-            let row = staged_event_insert.build().fetch_one(&self.pool).await?;
-            persisted_events_ids.push(row.get(0));
-            persisted_events.push(PersistedEvent::new(row.get(0), event));
-        }
+        let persisted_events = self.stage_events(events, false, Actor::current()).await?;
+        let persisted_events_ids: Vec<PgEventId> =
+            persisted_events.iter().map(|event| event.id()).collect();
 
         let Some(last_event_id) = persisted_events_ids.last().copied() else {
             return Ok(vec![]);
         };
-        sqlx::query(&format!(r#"UPDATE event_sequence es SET consumed = consumed + 1, committed = (es.event_id = ANY($1))
-                       FROM (SELECT event_id FROM event_sequence WHERE event_id = ANY($1) 
-                       OR ((consumed = 0 OR committed = true) 
-                       AND (event_id <= $2 AND ({}))) ORDER BY event_id FOR UPDATE) upd WHERE es.event_id = upd.event_id"#,
-                    CriteriaBuilder::new(&query.change_origin(version)).build()))
-            .bind(persisted_events_ids)
-            .bind(last_event_id)
-            .execute(&mut *tx)
-            .await
-            .map_err(map_concurrency_err)?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("last_event_id", last_event_id);
+        sqlx::query(&validation_sql(
+            &CriteriaBuilder::new(&query.change_origin(version))
+                .without_payload_filters()
+                .build(),
+        ))
+        .bind(persisted_events_ids)
+        .bind(last_event_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_concurrency_err)
+        .inspect_err(|_| self.invalidate_cached_queries(&persisted_events))?;
 
-        InsertEventsBuilder::new(persisted_events.as_slice(), &self.serde)
-            .build()
-            .execute(&self.pool)
-            .await?;
+        self.insert_events(&mut tx, &persisted_events).await?;
 
         tx.commit().await?;
+        self.invalidate_cached_queries(&persisted_events);
+        #[cfg(feature = "metrics")]
+        metrics::record_append(&persisted_events, started_at.elapsed(), self.metrics_labels);
 
         Ok(persisted_events)
     }
@@ -244,6 +1594,14 @@ where
     ///
     /// A `Result` containing a vector of `PersistedEvent` representing the appended events,
     /// or an error of type `Self::Error`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.append_without_validation",
+            skip_all,
+            fields(events = events.len())
+        )
+    )]
     async fn append_without_validation(
         &self,
         events: Vec<E>,
@@ -251,19 +1609,22 @@ where
     where
         E: Clone + 'async_trait,
     {
-        let mut persisted_events = Vec::with_capacity(events.len());
-        let mut persisted_events_ids: Vec<PgEventId> = Vec::with_capacity(events.len());
+        if let Some(group_commit) = &self.group_commit {
+            return self.append_grouped(group_commit, events, None).await;
+        }
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
         let _permit = self.concurrent_appends.acquire().await?;
-        let mut tx = self.pool.begin().await?;
+        let mut tx = self.begin().await?;
         sqlx::query("SELECT event_store_begin_epoch()")
             .execute(&mut *tx)
             .await?;
-        for event in events {
-            let mut sequence_insert = InsertEventSequenceBuilder::new(&event).with_consumed(true);
-            let row = sequence_insert.build().fetch_one(&self.pool).await?;
-            persisted_events_ids.push(row.get(0));
-            persisted_events.push(PersistedEvent::new(row.get(0), event));
+        let persisted_events = self.stage_events(events, true, Actor::current()).await?;
+        if persisted_events.is_empty() {
+            return Ok(vec![]);
         }
+        let persisted_events_ids: Vec<PgEventId> =
+            persisted_events.iter().map(|event| event.id()).collect();
 
         sqlx::query("UPDATE event_sequence es SET committed = true WHERE event_id = ANY($1)")
             .bind(persisted_events_ids)
@@ -271,19 +1632,30 @@ where
             .await
             .map_err(map_concurrency_err)?;
 
-        InsertEventsBuilder::new(persisted_events.as_slice(), &self.serde)
-            .build()
-            .execute(&mut *tx)
-            .await?;
+        self.insert_events(&mut tx, &persisted_events).await?;
 
         tx.commit().await?;
+        self.invalidate_cached_queries(&persisted_events);
+        #[cfg(feature = "metrics")]
+        metrics::record_append(&persisted_events, started_at.elapsed(), self.metrics_labels);
 
         Ok(persisted_events)
     }
 }
 
 pub async fn setup<E: Event>(pool: &PgPool) -> Result<(), Error> {
-    const RESERVED_NAMES: &[&str] = &["event_id", "payload", "event_type", "inserted_at"];
+    const RESERVED_NAMES: &[&str] = &[
+        "event_id",
+        "payload",
+        "event_type",
+        "inserted_at",
+        "search_vector",
+        "trace_context",
+        "hash",
+        "previous_event_id",
+        "signature",
+        "actor",
+    ];
 
     sqlx::query(include_str!("event_store/sql/table_event.sql"))
         .execute(pool)
@@ -294,6 +1666,9 @@ pub async fn setup<E: Event>(pool: &PgPool) -> Result<(), Error> {
     sqlx::query(include_str!("event_store/sql/table_event_sequence.sql"))
         .execute(pool)
         .await?;
+    sqlx::query(include_str!("event_store/sql/table_event_archive.sql"))
+        .execute(pool)
+        .await?;
     sqlx::query(include_str!("event_store/sql/idx_event_sequence_type.sql"))
         .execute(pool)
         .await?;
@@ -313,6 +1688,33 @@ pub async fn setup<E: Event>(pool: &PgPool) -> Result<(), Error> {
     .execute(pool)
     .await?;
 
+    #[cfg(feature = "hash-chain")]
+    {
+        sqlx::query(
+            "ALTER TABLE event ADD COLUMN IF NOT EXISTS hash BYTEA, ADD COLUMN IF NOT EXISTS previous_event_id BIGINT",
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query(include_str!("event_store/sql/table_event_chain_head.sql"))
+            .execute(pool)
+            .await?;
+    }
+    #[cfg(feature = "signing")]
+    sqlx::query("ALTER TABLE event ADD COLUMN IF NOT EXISTS signature BYTEA")
+        .execute(pool)
+        .await?;
+    sqlx::query("ALTER TABLE event ADD COLUMN IF NOT EXISTS actor TEXT")
+        .execute(pool)
+        .await?;
+    #[cfg(feature = "encryption")]
+    sqlx::query(include_str!("event_store/sql/table_event_data_key.sql"))
+        .execute(pool)
+        .await?;
+    #[cfg(feature = "otel")]
+    sqlx::query("ALTER TABLE event ADD COLUMN IF NOT EXISTS trace_context TEXT")
+        .execute(pool)
+        .await?;
+
     for domain_identifier in E::SCHEMA.domain_identifiers {
         if RESERVED_NAMES.contains(&domain_identifier.ident) {
             panic!("Domain identifier name {domain_identifier} is reserved. Please use a different name.", domain_identifier = domain_identifier.ident);
@@ -323,26 +1725,101 @@ pub async fn setup<E: Event>(pool: &PgPool) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the trace context of the current span, stored with the appended events.
+#[cfg(feature = "otel")]
+fn trace_context() -> Option<String> {
+    otel::current_trace_context()
+}
+
+/// Returns the trace context stored with the appended events, which is empty without the `otel` feature.
+#[cfg(not(feature = "otel"))]
+fn trace_context() -> Option<String> {
+    None
+}
+
+/// Returns the statement that commits the staged events, consuming the events matching the validation
+/// criteria. It fails with a check violation if one of them was consumed by a concurrent append.
+fn validation_sql(criteria: &str) -> String {
+    format!(
+        r#"UPDATE event_sequence es SET consumed = consumed + 1, committed = (es.event_id = ANY($1))
+                       FROM (SELECT event_id FROM event_sequence WHERE event_id = ANY($1) 
+                       OR ((consumed = 0 OR committed = true) 
+                       AND (event_id <= $2 AND ({criteria}))) ORDER BY event_id FOR UPDATE) upd WHERE es.event_id = upd.event_id"#
+    )
+}
+
+/// Deletes the snapshots built from an event of one of the `event_types` with an ID of at least `version`,
+/// returning the number of deleted snapshots.
+async fn invalidate_snapshots(
+    tx: &mut Transaction<'_, Postgres>,
+    version: PgEventId,
+    event_types: &[&str],
+) -> Result<u64, sqlx::Error> {
+    let snapshots: Vec<(uuid::Uuid, String)> =
+        sqlx::query_as("SELECT id, query FROM snapshot WHERE version >= $1")
+            .bind(version)
+            .fetch_all(&mut **tx)
+            .await?;
+    let invalidated: Vec<uuid::Uuid> = snapshots
+        .into_iter()
+        .filter(|(_, query)| {
+            event_types
+                .iter()
+                .any(|event_type| has_event_type(query, event_type))
+        })
+        .map(|(id, _)| id)
+        .collect();
+    if invalidated.is_empty() {
+        return Ok(0);
+    }
+    Ok(sqlx::query("DELETE FROM snapshot WHERE id = ANY($1)")
+        .bind(invalidated)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected())
+}
+
+/// Returns `true` if the snapshot key selects the `event_type` in one of its filters.
+///
+/// The event types are matched as whole names, ignoring the excluded ones, so a type does not match the
+/// types containing its name.
+fn has_event_type(query: &str, event_type: &str) -> bool {
+    query.split(')').any(|filter| {
+        filter
+            .split('|')
+            .nth(1)
+            .and_then(|events| events.split(['-', '!', '[', '{', '?']).next())
+            .is_some_and(|events| events.split(',').any(|name| name == event_type))
+    })
+}
+
 fn map_concurrency_err(err: sqlx::Error) -> Error {
     if let sqlx::Error::Database(ref description) = err {
         if description.code().as_deref() == Some("23514") {
+            #[cfg(feature = "metrics")]
+            metrics::record_conflict();
             return Error::Concurrency;
         }
     }
     Error::Database(err)
 }
 
+/// Returns the SQL type of the column of a domain identifier.
+fn sql_type(domain_identifier: &DomainIdentifierInfo) -> &'static str {
+    match domain_identifier.type_info {
+        disintegrate::IdentifierType::String => "TEXT",
+        disintegrate::IdentifierType::i64 => "BIGINT",
+        disintegrate::IdentifierType::Uuid => "UUID",
+    }
+}
+
 async fn add_domain_identifier_column(
     pool: &PgPool,
     table: &str,
     domain_identifier: &DomainIdentifierInfo,
 ) -> Result<(), Error> {
     let column_name = domain_identifier.ident;
-    let sql_type = match domain_identifier.type_info {
-        disintegrate::IdentifierType::String => "TEXT",
-        disintegrate::IdentifierType::i64 => "BIGINT",
-        disintegrate::IdentifierType::Uuid => "UUID",
-    };
+    let sql_type = sql_type(domain_identifier);
     sqlx::query(&format!(
         "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS {column_name} {sql_type}"
     ))
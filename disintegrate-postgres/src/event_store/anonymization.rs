@@ -0,0 +1,79 @@
+use disintegrate::{DomainIdentifierSet, IdentifierValue};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::PgEventId;
+
+/// The result of `PgEventStore::anonymize`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnonymizationReport {
+    /// The rewritten events, which keep their IDs and their order.
+    pub rewritten_events: Vec<PgEventId>,
+    /// The number of snapshots deleted because they were built from a rewritten event.
+    pub invalidated_snapshots: u64,
+    /// The event listeners that already handled a rewritten event, whose read models may still hold the
+    /// original data. They can be rewound with `PgEventListener::rewind_listeners`.
+    pub stale_listeners: Vec<String>,
+}
+
+/// Builds the statement that replaces the payload, the domain identifiers and the signature of a rewritten
+/// event in the `table`.
+///
+/// The columns set to `None` are left untouched, so only the domain identifiers are replaced in the
+/// `event_sequence` table.
+pub(crate) fn rewrite_query<'a>(
+    table: &str,
+    event_id: PgEventId,
+    domain_identifiers: &DomainIdentifierSet,
+    payload: Option<Vec<u8>>,
+    signature: Option<Vec<u8>>,
+) -> Option<QueryBuilder<'a, Postgres>> {
+    if payload.is_none() && signature.is_none() && domain_identifiers.is_empty() {
+        return None;
+    }
+    let mut builder = QueryBuilder::new(format!("UPDATE {table} SET "));
+    let mut columns = builder.separated(", ");
+    if let Some(payload) = payload {
+        columns.push("payload = ").push_bind_unseparated(payload);
+    }
+    for (ident, value) in domain_identifiers.iter() {
+        columns.push(format!("{ident} = "));
+        match value {
+            IdentifierValue::String(value) => columns.push_bind_unseparated(value.clone()),
+            IdentifierValue::i64(value) => columns.push_bind_unseparated(*value),
+            IdentifierValue::Uuid(value) => columns.push_bind_unseparated(*value),
+        };
+    }
+    if let Some(signature) = signature {
+        columns
+            .push("signature = ")
+            .push_bind_unseparated(signature);
+    }
+    builder.push(" WHERE event_id = ").push_bind(event_id);
+    Some(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::domain_identifiers;
+
+    #[test]
+    fn it_builds_the_rewrite_of_an_event() {
+        let cart_id = "cart-1";
+        let identifiers = domain_identifiers! {cart_id: cart_id};
+
+        assert_eq!(
+            rewrite_query("event", 1, &identifiers, Some(b"{}".to_vec()), None)
+                .unwrap()
+                .sql(),
+            "UPDATE event SET payload = $1, cart_id = $2 WHERE event_id = $3"
+        );
+        assert_eq!(
+            rewrite_query("event_sequence", 1, &identifiers, None, None)
+                .unwrap()
+                .sql(),
+            "UPDATE event_sequence SET cart_id = $1 WHERE event_id = $2"
+        );
+        assert!(rewrite_query("event_sequence", 1, &domain_identifiers! {}, None, None).is_none());
+    }
+}
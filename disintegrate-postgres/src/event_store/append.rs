@@ -10,13 +10,15 @@ use crate::PgEventId;
 
 /// SQL Insert Event Sequence Builder
 ///
-/// A builder for constructing insert SQL queries for the `event_sequence` table.
+/// A builder for constructing insert SQL queries for the `event_sequence` table. The IDs of several events
+/// are reserved with a single statement, so an append takes one round trip and one commit to stage its
+/// events, whatever their number.
 pub struct InsertEventSequenceBuilder<'a, E>
 where
     E: Event + Clone,
 {
     builder: sqlx::QueryBuilder<'a, Postgres>,
-    event: &'a E,
+    events: &'a [E],
     consumed: Option<bool>,
     committed: Option<bool>,
 }
@@ -30,10 +32,20 @@ where
     /// # Arguments
     ///
     /// * `event` - The event to be inserted.
+    #[cfg(test)]
     pub fn new(event: &'a E) -> Self {
+        Self::batch(std::slice::from_ref(event))
+    }
+
+    /// Creates a new instance of `InsertEventSequenceBuilder` for batch inserts.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events to be inserted.
+    pub fn batch(events: &'a [E]) -> Self {
         Self {
-            builder: sqlx::QueryBuilder::new("INSERT INTO event_sequence ("),
-            event,
+            builder: sqlx::QueryBuilder::new(""),
+            events,
             consumed: None,
             committed: None,
         }
@@ -60,48 +72,60 @@ where
         self
     }
 
-    /// Builds the SQL insert query.
+    /// Builds the SQL insert query, returning the reserved IDs.
+    ///
+    /// The rows are inserted sorted by the ordinal of their events, so the IDs drawn from the identity sequence
+    /// increase with the position of the events in the batch. The order of the returned rows is not defined:
+    /// the IDs must be sorted to be paired with the events.
     pub fn build(&'a mut self) -> Query<'a, Postgres, PgArguments> {
-        let domain_identifiers = self.event.domain_identifiers();
-        let mut separated_builder = self.builder.separated(",");
-
-        separated_builder.push("event_type");
+        if self.events.is_empty() {
+            panic!("Cannot build an insert query with no events");
+        }
 
-        for ident in domain_identifiers.keys() {
-            separated_builder.push(ident);
+        let mut all_identifiers: BTreeSet<Identifier> = BTreeSet::new();
+        for event in self.events.iter() {
+            all_identifiers.extend(event.domain_identifiers().keys());
         }
 
+        let mut columns = vec!["event_type".to_string()];
+        columns.extend(all_identifiers.iter().map(ToString::to_string));
         if self.consumed.is_some() {
-            separated_builder.push("consumed");
+            columns.push("consumed".to_string());
         }
-
         if self.committed.is_some() {
-            separated_builder.push("committed");
+            columns.push("committed".to_string());
         }
+        let columns = columns.join(",");
 
-        separated_builder.push_unseparated(") VALUES (");
-
-        separated_builder.push_bind_unseparated(self.event.name());
-
-        for value in domain_identifiers.values() {
-            match value {
-                disintegrate::IdentifierValue::String(value) => {
-                    separated_builder.push_bind(value.clone())
+        self.builder.push(format!(
+            "INSERT INTO event_sequence ({columns}) SELECT {columns} FROM ("
+        ));
+        let (consumed, committed) = (self.consumed, self.committed);
+        self.builder
+            .push_values(self.events.iter().enumerate(), |mut b, (ordinal, event)| {
+                b.push_bind(event.name());
+                let event_identifiers = event.domain_identifiers();
+                for ident in &all_identifiers {
+                    match event_identifiers.get(ident) {
+                        Some(disintegrate::IdentifierValue::String(value)) => {
+                            b.push_bind(value.clone())
+                        }
+                        Some(disintegrate::IdentifierValue::i64(value)) => b.push_bind(*value),
+                        Some(disintegrate::IdentifierValue::Uuid(value)) => b.push_bind(*value),
+                        None => b.push("NULL"),
+                    };
                 }
-                disintegrate::IdentifierValue::i64(value) => separated_builder.push_bind(*value),
-                disintegrate::IdentifierValue::Uuid(value) => separated_builder.push_bind(*value),
-            };
-        }
-
-        if let Some(consumed) = self.consumed {
-            separated_builder.push(if consumed { 1 } else { 0 });
-        }
-
-        if let Some(committed) = self.committed {
-            separated_builder.push(committed);
-        }
-
-        separated_builder.push_unseparated(") RETURNING (event_id)");
+                if let Some(consumed) = consumed {
+                    b.push(if consumed { 1 } else { 0 });
+                }
+                if let Some(committed) = committed {
+                    b.push(committed);
+                }
+                b.push(ordinal);
+            });
+        self.builder.push(format!(
+            ") AS batch ({columns},ordinal) ORDER BY ordinal RETURNING (event_id)"
+        ));
 
         self.builder.build()
     }
@@ -118,6 +142,13 @@ where
     builder: sqlx::QueryBuilder<'a, Postgres>,
     events: &'a [PersistedEvent<PgEventId, E>],
     serde: &'a S,
+    trace_context: Option<String>,
+    #[cfg(any(feature = "claim-check", feature = "encryption"))]
+    payloads: Option<&'a [Vec<u8>]>,
+    #[cfg(feature = "hash-chain")]
+    chain: Option<&'a [super::hash_chain::ChainLink]>,
+    #[cfg(feature = "signing")]
+    signatures: Option<&'a [Vec<u8>]>,
 }
 
 impl<'a, E, S> InsertEventsBuilder<'a, E, S>
@@ -136,9 +167,64 @@ where
             builder: sqlx::QueryBuilder::new("INSERT INTO event ("),
             events,
             serde,
+            trace_context: None,
+            #[cfg(any(feature = "claim-check", feature = "encryption"))]
+            payloads: None,
+            #[cfg(feature = "hash-chain")]
+            chain: None,
+            #[cfg(feature = "signing")]
+            signatures: None,
         }
     }
 
+    /// Sets the links of the events to the hash chain, one for each event.
+    ///
+    /// The payloads of the links are stored in place of the serialized events.
+    #[cfg(feature = "hash-chain")]
+    pub fn with_hash_chain(mut self, chain: &'a [super::hash_chain::ChainLink]) -> Self {
+        assert_eq!(
+            chain.len(),
+            self.events.len(),
+            "every event must be linked to the hash chain"
+        );
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Sets the payloads stored in place of the serialized events, one for each event.
+    #[cfg(any(feature = "claim-check", feature = "encryption"))]
+    pub fn with_payloads(mut self, payloads: &'a [Vec<u8>]) -> Self {
+        assert_eq!(
+            payloads.len(),
+            self.events.len(),
+            "every event must have a payload"
+        );
+        self.payloads = Some(payloads);
+        self
+    }
+
+    /// Sets the signatures of the events, one for each event.
+    #[cfg(feature = "signing")]
+    pub fn with_signatures(mut self, signatures: &'a [Vec<u8>]) -> Self {
+        assert_eq!(
+            signatures.len(),
+            self.events.len(),
+            "every event must be signed"
+        );
+        self.signatures = Some(signatures);
+        self
+    }
+
+    /// Sets the serialized trace context stored with the events.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace_context` - The trace context, or `None` to leave the column empty.
+    pub fn with_trace_context(mut self, trace_context: Option<String>) -> Self {
+        self.trace_context = trace_context;
+        self
+    }
+
     /// Builds the SQL batch insert query.
     pub fn build(&'a mut self) -> Query<'a, Postgres, PgArguments> {
         if self.events.is_empty() {
@@ -149,6 +235,7 @@ where
         for event in self.events.iter() {
             all_identifiers.extend(event.domain_identifiers().keys());
         }
+        let with_actor = self.events.iter().any(|event| event.actor().is_some());
 
         let mut separated_builder = self.builder.separated(",");
 
@@ -158,26 +245,76 @@ where
         for ident in &all_identifiers {
             separated_builder.push(ident);
         }
+        if self.trace_context.is_some() {
+            separated_builder.push("trace_context");
+        }
+        if with_actor {
+            separated_builder.push("actor");
+        }
+        #[cfg(feature = "hash-chain")]
+        if self.chain.is_some() {
+            separated_builder.push("hash");
+            separated_builder.push("previous_event_id");
+        }
+        #[cfg(feature = "signing")]
+        if self.signatures.is_some() {
+            separated_builder.push("signature");
+        }
 
         separated_builder.push_unseparated(")");
 
-        self.builder.push_values(self.events, |mut b, event| {
-            b.push_bind(event.id());
-            b.push_bind(event.name());
-            b.push_bind(self.serde.serialize(event.clone().into_inner()));
-            let event_identifiers = event.domain_identifiers();
-            for ident in &all_identifiers {
-                if let Some(value) = event_identifiers.get(ident) {
-                    match value {
-                        disintegrate::IdentifierValue::String(value) => b.push_bind(value.clone()),
-                        disintegrate::IdentifierValue::i64(value) => b.push_bind(*value),
-                        disintegrate::IdentifierValue::Uuid(value) => b.push_bind(*value),
-                    };
-                } else {
-                    b.push("NULL");
+        self.builder
+            .push_values(self.events.iter().enumerate(), |mut b, (index, event)| {
+                b.push_bind(event.id());
+                b.push_bind(event.name());
+                #[cfg(any(feature = "claim-check", feature = "encryption"))]
+                let payload = self.payloads.map(|payloads| payloads[index].as_slice());
+                #[cfg(not(any(feature = "claim-check", feature = "encryption")))]
+                let payload: Option<&[u8]> = None;
+                #[cfg(feature = "hash-chain")]
+                let payload =
+                    payload.or_else(|| self.chain.map(|chain| chain[index].payload.as_slice()));
+                #[cfg(not(any(
+                    feature = "claim-check",
+                    feature = "encryption",
+                    feature = "hash-chain",
+                    feature = "signing"
+                )))]
+                let _ = index;
+                match payload {
+                    Some(payload) => b.push_bind(payload),
+                    None => b.push_bind(self.serde.serialize(event.clone().into_inner())),
+                };
+                let event_identifiers = event.domain_identifiers();
+                for ident in &all_identifiers {
+                    if let Some(value) = event_identifiers.get(ident) {
+                        match value {
+                            disintegrate::IdentifierValue::String(value) => {
+                                b.push_bind(value.clone())
+                            }
+                            disintegrate::IdentifierValue::i64(value) => b.push_bind(*value),
+                            disintegrate::IdentifierValue::Uuid(value) => b.push_bind(*value),
+                        };
+                    } else {
+                        b.push("NULL");
+                    }
                 }
-            }
-        });
+                if let Some(trace_context) = &self.trace_context {
+                    b.push_bind(trace_context.clone());
+                }
+                if with_actor {
+                    b.push_bind(event.actor().map(ToString::to_string));
+                }
+                #[cfg(feature = "hash-chain")]
+                if let Some(link) = self.chain.map(|chain| &chain[index]) {
+                    b.push_bind(link.hash.as_slice());
+                    b.push_bind(link.previous_event_id);
+                }
+                #[cfg(feature = "signing")]
+                if let Some(signature) = self.signatures.map(|signatures| &signatures[index]) {
+                    b.push_bind(signature.as_slice());
+                }
+            });
         self.builder.build()
     }
 }
@@ -188,6 +325,7 @@ mod tests {
         domain_identifiers, ident, DomainIdentifierInfo, DomainIdentifierSet, EventInfo,
         EventSchema, IdentifierType,
     };
+    use disintegrate_serde::serde::json::Json;
     use serde::{Deserialize, Serialize};
     use sqlx::Execute;
 
@@ -264,7 +402,77 @@ mod tests {
         let mut insert_query = InsertEventSequenceBuilder::new(&event);
         assert_eq!(
             insert_query.build().sql(),
-            "INSERT INTO event_sequence (event_type,cart_id,product_id) VALUES ($1,$2,$3) RETURNING (event_id)"
+            "INSERT INTO event_sequence (event_type,cart_id,product_id) SELECT event_type,cart_id,product_id FROM (VALUES ($1, $2, $3, 0)) AS batch (event_type,cart_id,product_id,ordinal) ORDER BY ordinal RETURNING (event_id)"
+        );
+    }
+
+    #[test]
+    fn it_builds_batch_insert() {
+        let events = [
+            ShoppingCartEvent::Added {
+                product_id: "product_1".into(),
+                cart_id: "cart_1".into(),
+                quantity: 10,
+            },
+            ShoppingCartEvent::Removed {
+                product_id: "product_1".into(),
+                cart_id: "cart_1".into(),
+                quantity: 5,
+            },
+        ];
+        let mut insert_query = InsertEventSequenceBuilder::batch(&events).with_consumed(true);
+        assert_eq!(
+            insert_query.build().sql(),
+            "INSERT INTO event_sequence (event_type,cart_id,product_id,consumed) SELECT event_type,cart_id,product_id,consumed FROM (VALUES ($1, $2, $3, 1, 0), ($4, $5, $6, 1, 1)) AS batch (event_type,cart_id,product_id,consumed,ordinal) ORDER BY ordinal RETURNING (event_id)"
+        );
+    }
+
+    #[test]
+    fn it_builds_insert_with_trace_context() {
+        let events = [PersistedEvent::new(
+            1,
+            ShoppingCartEvent::Added {
+                product_id: "product_1".into(),
+                cart_id: "cart_1".into(),
+                quantity: 10,
+            },
+        )];
+        let serde = Json::<ShoppingCartEvent>::default();
+        let mut insert_query = InsertEventsBuilder::new(&events, &serde).with_trace_context(Some(
+            r#"{"traceparent":"00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"}"#.into(),
+        ));
+        assert_eq!(
+            insert_query.build().sql(),
+            "INSERT INTO event (event_id,event_type,payload,cart_id,product_id,trace_context)VALUES ($1, $2, $3, $4, $5, $6)"
+        );
+    }
+
+    #[test]
+    fn it_builds_insert_with_the_actor() {
+        let events = [
+            PersistedEvent::new(
+                1,
+                ShoppingCartEvent::Added {
+                    product_id: "product_1".into(),
+                    cart_id: "cart_1".into(),
+                    quantity: 10,
+                },
+            )
+            .with_actor(Some(disintegrate::Actor::user("alice"))),
+            PersistedEvent::new(
+                2,
+                ShoppingCartEvent::Added {
+                    product_id: "product_2".into(),
+                    cart_id: "cart_1".into(),
+                    quantity: 1,
+                },
+            ),
+        ];
+        let serde = Json::<ShoppingCartEvent>::default();
+        let mut insert_query = InsertEventsBuilder::new(&events, &serde);
+        assert_eq!(
+            insert_query.build().sql(),
+            "INSERT INTO event (event_id,event_type,payload,cart_id,product_id,actor)VALUES ($1, $2, $3, $4, $5, $6), ($7, $8, $9, $10, $11, $12)"
         );
     }
 }
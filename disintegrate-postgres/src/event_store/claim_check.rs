@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+/// The prefix of the payloads that point to an object.
+const POINTER_MARKER: &[u8] = b"{\"$claim_check\":";
+
+/// The payload stored in place of a claim-checked payload.
+///
+/// It is a JSON document, so the SQL filters on the JSON payloads skip it instead of failing.
+#[derive(Serialize, Deserialize)]
+struct Pointer {
+    #[serde(rename = "$claim_check")]
+    claim_check: PointerTarget,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PointerTarget {
+    hash: String,
+    location: String,
+}
+
+/// Stores the oversized payloads in an object store, keeping only a pointer to them in the `event` table.
+///
+/// The objects are named after the SHA-256 hash of the payload, which is checked when the payload is
+/// read back, so identical payloads are stored once and a replaced object is detected.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let bucket = AmazonS3Builder::from_env().with_bucket_name("events").build()?;
+/// let event_store = PgEventStore::new(pool, serde)
+///     .await?
+///     .with_claim_check(ClaimCheck::new(Arc::new(bucket), 256 * 1024).with_prefix("payloads"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClaimCheck {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    threshold: usize,
+}
+
+impl ClaimCheck {
+    /// Creates a claim check that moves the payloads larger than `threshold` bytes to the `store`.
+    pub fn new(store: Arc<dyn ObjectStore>, threshold: usize) -> Self {
+        Self {
+            store,
+            prefix: Path::default(),
+            threshold,
+        }
+    }
+
+    /// Sets the path prefix of the stored payloads.
+    pub fn with_prefix(mut self, prefix: impl Into<Path>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Stores the payload if it exceeds the threshold, returning the pointer to store in its place.
+    pub(crate) async fn check_in(&self, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if payload.len() <= self.threshold {
+            return Ok(payload);
+        }
+        let hash = hex::encode(Sha256::digest(&payload));
+        let location = self.prefix.child(hash.as_str());
+        self.store.put(&location, PutPayload::from(payload)).await?;
+        Ok(serde_json::to_vec(&Pointer {
+            claim_check: PointerTarget {
+                hash,
+                location: location.to_string(),
+            },
+        })
+        .expect("the pointer is serializable"))
+    }
+
+    /// Fetches the payload a pointer refers to, returning the other payloads as they are.
+    pub(crate) async fn check_out(&self, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if !payload.starts_with(POINTER_MARKER) {
+            return Ok(payload);
+        }
+        let Ok(Pointer {
+            claim_check: PointerTarget { hash, location },
+        }) = serde_json::from_slice(&payload)
+        else {
+            return Ok(payload);
+        };
+        let location = Path::from(location);
+        let object = self.store.get(&location).await?.bytes().await?;
+        if hex::encode(Sha256::digest(&object)) != hash {
+            return Err(Error::ClaimCheckMismatch(location.to_string()));
+        }
+        Ok(object.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[test]
+    fn it_stores_the_oversized_payloads_in_the_object_store() {
+        let store = Arc::new(InMemory::new());
+        let claim_check = ClaimCheck::new(store.clone(), 8).with_prefix("payloads");
+
+        let small = block_on(claim_check.check_in(b"{}".to_vec())).unwrap();
+        let large = block_on(claim_check.check_in(b"{\"document\":\"...\"}".to_vec())).unwrap();
+
+        assert_eq!(small, b"{}");
+        assert!(large.starts_with(POINTER_MARKER));
+        assert_eq!(block_on(claim_check.check_out(small)).unwrap(), b"{}");
+        assert_eq!(
+            block_on(claim_check.check_out(large.clone())).unwrap(),
+            b"{\"document\":\"...\"}"
+        );
+
+        let pointer: Pointer = serde_json::from_slice(&large).unwrap();
+        let location = Path::from(pointer.claim_check.location);
+        block_on(store.put(&location, PutPayload::from_static(b"{}"))).unwrap();
+        assert!(matches!(
+            block_on(claim_check.check_out(large)),
+            Err(Error::ClaimCheckMismatch(_))
+        ));
+    }
+}
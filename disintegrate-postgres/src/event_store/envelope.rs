@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use disintegrate::encryption::{
+    self, generate_key, key_id_of, EncryptionKey, MasterKeyProvider, WrappedKey,
+};
+use disintegrate::KeyProvider;
+use sqlx::PgPool;
+
+use crate::Error;
+
+/// The prefix of the encrypted payloads, which no serialized payload starts with.
+const ENVELOPE_MARKER: &[u8] = b"\0enc\0";
+
+/// Encrypts the event payloads with data keys wrapped by a master key.
+///
+/// The data keys are stored wrapped in the `event_data_key` table and cached once unwrapped, so the
+/// master key provider, e.g. a key management service, is only called once per data key. The ID of the
+/// data key is stored with each payload, so the data key can be rotated with `rotate_data_key`, and the
+/// master key with `rewrap_data_keys`, without rewriting the events. The payloads appended before the
+/// encryption was enabled are read as they are.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let envelope_encryption = EnvelopeEncryption::new(kms_master_key);
+/// let event_store = PgEventStore::new(pool.clone(), serde)
+///     .await?
+///     .with_envelope_encryption(envelope_encryption.clone());
+///
+/// // Once the master key has been rotated in the key management service:
+/// envelope_encryption.rewrap_data_keys(&pool).await?;
+/// ```
+#[derive(Clone)]
+pub struct EnvelopeEncryption {
+    master_key_provider: Arc<dyn MasterKeyProvider>,
+    data_keys: Arc<RwLock<DataKeys>>,
+}
+
+/// The unwrapped data keys.
+#[derive(Default)]
+struct DataKeys {
+    current_key_id: Option<String>,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl KeyProvider for DataKeys {
+    fn current_key_id(&self) -> String {
+        self.current_key_id.clone().unwrap_or_default()
+    }
+
+    fn key(&self, key_id: &str) -> Option<EncryptionKey> {
+        self.keys.get(key_id).copied()
+    }
+}
+
+impl EnvelopeEncryption {
+    /// Creates an envelope encryption wrapping the data keys with the master keys of the provider.
+    pub fn new(master_key_provider: impl MasterKeyProvider + 'static) -> Self {
+        Self {
+            master_key_provider: Arc::new(master_key_provider),
+            data_keys: Arc::default(),
+        }
+    }
+
+    /// Creates a new data key, used to encrypt the payloads appended from now on.
+    ///
+    /// The payloads encrypted with the previous data keys can still be decrypted. The other processes
+    /// keep the data key they loaded until they restart. Returns the ID of the new data key.
+    pub async fn rotate_data_key(&self, pool: &PgPool) -> Result<String, Error> {
+        let key_id = uuid::Uuid::new_v4().to_string();
+        let data_key = generate_key();
+        let wrapped_key = self
+            .master_key_provider
+            .wrap_key(&data_key)
+            .await
+            .map_err(encryption::Error::MasterKey)?;
+        sqlx::query(
+            "INSERT INTO event_data_key (key_id, master_key_id, wrapped_key) VALUES ($1, $2, $3)",
+        )
+        .bind(&key_id)
+        .bind(&wrapped_key.master_key_id)
+        .bind(&wrapped_key.ciphertext)
+        .execute(pool)
+        .await?;
+        let mut data_keys = self.data_keys.write().expect("data keys lock poisoned");
+        data_keys.keys.insert(key_id.clone(), data_key);
+        data_keys.current_key_id = Some(key_id.clone());
+        Ok(key_id)
+    }
+
+    /// Re-wraps the data keys with the current master key, returning the number of re-wrapped keys.
+    ///
+    /// It is meant to be called once the master key has been rotated, before its previous version is
+    /// disabled. The events are not rewritten, and the event store can be used in the meantime.
+    pub async fn rewrap_data_keys(&self, pool: &PgPool) -> Result<usize, Error> {
+        let master_key_id = self.master_key_provider.current_master_key_id();
+        let wrapped_keys: Vec<(String, String, Vec<u8>)> = sqlx::query_as(
+            "SELECT key_id, master_key_id, wrapped_key FROM event_data_key WHERE master_key_id <> $1",
+        )
+        .bind(&master_key_id)
+        .fetch_all(pool)
+        .await?;
+        let mut rewrapped = 0;
+        for (key_id, previous_master_key_id, ciphertext) in wrapped_keys {
+            let data_key = self
+                .master_key_provider
+                .unwrap_key(&WrappedKey {
+                    master_key_id: previous_master_key_id.clone(),
+                    ciphertext,
+                })
+                .await
+                .map_err(encryption::Error::MasterKey)?;
+            let wrapped_key = self
+                .master_key_provider
+                .wrap_key(&data_key)
+                .await
+                .map_err(encryption::Error::MasterKey)?;
+            rewrapped += sqlx::query(
+                "UPDATE event_data_key SET master_key_id = $2, wrapped_key = $3, rewrapped_at = now() WHERE key_id = $1 AND master_key_id = $4",
+            )
+            .bind(&key_id)
+            .bind(&wrapped_key.master_key_id)
+            .bind(&wrapped_key.ciphertext)
+            .bind(&previous_master_key_id)
+            .execute(pool)
+            .await?
+            .rows_affected() as usize;
+        }
+        Ok(rewrapped)
+    }
+
+    /// Encrypts the payload with the current data key, loading it or creating the first one if needed.
+    pub(crate) async fn encrypt(&self, pool: &PgPool, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if self.data_keys().current_key_id.is_none() {
+            self.load_current_data_key(pool).await?;
+        }
+        let encrypted = encryption::encrypt(&*self.data_keys(), &payload)?;
+        Ok([ENVELOPE_MARKER, &encrypted].concat())
+    }
+
+    /// Decrypts the payload with its data key, returning the payloads that are not encrypted as they are.
+    pub(crate) async fn decrypt(&self, pool: &PgPool, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let Some(encrypted) = payload.strip_prefix(ENVELOPE_MARKER) else {
+            return Ok(payload);
+        };
+        let key_id = key_id_of(encrypted)?;
+        if self.data_keys().key(&key_id).is_none() {
+            self.load_data_key(pool, &key_id).await?;
+        }
+        Ok(encryption::decrypt(&*self.data_keys(), encrypted)?)
+    }
+
+    async fn load_current_data_key(&self, pool: &PgPool) -> Result<(), Error> {
+        let key_id: Option<String> = sqlx::query_scalar(
+            "SELECT key_id FROM event_data_key ORDER BY inserted_at DESC, key_id DESC LIMIT 1",
+        )
+        .fetch_optional(pool)
+        .await?;
+        match key_id {
+            Some(key_id) => {
+                self.load_data_key(pool, &key_id).await?;
+                let mut data_keys = self.data_keys.write().expect("data keys lock poisoned");
+                data_keys.current_key_id.get_or_insert(key_id);
+            }
+            None => {
+                self.rotate_data_key(pool).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unwraps the stored data key and caches it. An unknown data key is left out of the cache, so the
+    /// decryption fails with `UnknownKey`.
+    async fn load_data_key(&self, pool: &PgPool, key_id: &str) -> Result<(), Error> {
+        let wrapped_key: Option<(String, Vec<u8>)> = sqlx::query_as(
+            "SELECT master_key_id, wrapped_key FROM event_data_key WHERE key_id = $1",
+        )
+        .bind(key_id)
+        .fetch_optional(pool)
+        .await?;
+        let Some((master_key_id, ciphertext)) = wrapped_key else {
+            return Ok(());
+        };
+        let data_key = self
+            .master_key_provider
+            .unwrap_key(&WrappedKey {
+                master_key_id,
+                ciphertext,
+            })
+            .await
+            .map_err(encryption::Error::MasterKey)?;
+        self.data_keys
+            .write()
+            .expect("data keys lock poisoned")
+            .keys
+            .insert(key_id.to_string(), data_key);
+        Ok(())
+    }
+
+    fn data_keys(&self) -> std::sync::RwLockReadGuard<'_, DataKeys> {
+        self.data_keys.read().expect("data keys lock poisoned")
+    }
+}
+
+impl std::fmt::Debug for EnvelopeEncryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvelopeEncryption")
+            .field(
+                "current_master_key_id",
+                &self.master_key_provider.current_master_key_id(),
+            )
+            .finish_non_exhaustive()
+    }
+}
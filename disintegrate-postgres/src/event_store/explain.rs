@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use crate::PgEventId;
+use disintegrate::{Event, StreamQuery};
+
+/// The execution plan of a stream query.
+///
+/// It is returned by `PgEventStore::explain` to diagnose slow hydrations without reverse-engineering the
+/// SQL generated by the library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryPlan {
+    sql: String,
+    plan: String,
+    warnings: Vec<String>,
+}
+
+impl QueryPlan {
+    pub(crate) fn new(sql: String, plan: String, warnings: Vec<String>) -> Self {
+        Self {
+            sql,
+            plan,
+            warnings,
+        }
+    }
+
+    /// Returns the SQL statement that streams the events of the query.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Returns the plan chosen by the database, as printed by `EXPLAIN`.
+    pub fn plan(&self) -> &str {
+        &self.plan
+    }
+
+    /// Returns the filters of the query that cannot use an index.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+/// The access methods of the indexes of the `event` table, by indexed column.
+#[derive(Debug, Default)]
+pub(crate) struct EventIndexes(BTreeMap<String, Vec<String>>);
+
+impl EventIndexes {
+    /// Collects the indexes from their definitions, as listed by `pg_indexes`.
+    pub(crate) fn from_definitions<'a>(definitions: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut indexes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for definition in definitions {
+            let Some((_, using)) = definition.split_once(" USING ") else {
+                continue;
+            };
+            let Some((method, columns)) = using.split_once(" (") else {
+                continue;
+            };
+            let columns = columns.split(')').next().unwrap_or_default();
+            for column in columns.split(',') {
+                indexes
+                    .entry(column.trim().trim_matches('"').to_string())
+                    .or_default()
+                    .push(method.trim().to_lowercase());
+            }
+        }
+        Self(indexes)
+    }
+
+    pub(crate) fn methods(&self, column: &str) -> &[String] {
+        self.0.get(column).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Returns the warnings about the filters of the query that cannot use the indexes of the `event` table.
+pub(crate) fn lint<QE>(query: &StreamQuery<PgEventId, QE>, indexes: &EventIndexes) -> Vec<String>
+where
+    QE: Event + Clone,
+{
+    let mut warnings = vec![];
+    let mut warn = |warning: String| {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    };
+    for filter in query.filters() {
+        let equalities = filter
+            .identifiers()
+            .keys()
+            .copied()
+            .chain(filter.disjunctions().iter().flatten().map(|i| i.key))
+            .chain(filter.excluded_identifiers().iter().map(|i| i.key));
+        for ident in equalities {
+            if indexes.methods(ident.into_inner()).is_empty() {
+                warn(format!(
+                    "the domain identifier `{ident}` has no index, its filter scans the event table"
+                ));
+            }
+        }
+        for range in filter.ranges() {
+            let methods = indexes.methods(range.key.into_inner());
+            if !methods.iter().any(|method| method == "btree") {
+                warn(format!(
+                    "the range filter on `{}` cannot use an index, create a btree index on the column",
+                    range.key
+                ));
+            }
+        }
+        for (field, _) in filter.payload_filters().iter() {
+            warn(format!(
+                "the payload filter on `{field}` is evaluated on every event of the query, consider declaring it as a domain identifier"
+            ));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{
+        domain_identifiers, ident, query, DomainIdentifierInfo, DomainIdentifierSet, EventInfo,
+        EventSchema, IdentifierType,
+    };
+
+    #[allow(dead_code)]
+    #[derive(Clone)]
+    enum TestEvent {
+        Foo { foo_id: String, bar_id: String },
+    }
+
+    impl Event for TestEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["Foo"],
+            events_info: &[&EventInfo {
+                name: "Foo",
+                domain_identifiers: &[&ident!(#foo_id), &ident!(#bar_id)],
+            }],
+            domain_identifiers: &[
+                &DomainIdentifierInfo {
+                    ident: ident!(#bar_id),
+                    type_info: IdentifierType::String,
+                },
+                &DomainIdentifierInfo {
+                    ident: ident!(#foo_id),
+                    type_info: IdentifierType::String,
+                },
+            ],
+        };
+
+        fn name(&self) -> &'static str {
+            "Foo"
+        }
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {}
+        }
+    }
+
+    fn indexes() -> EventIndexes {
+        EventIndexes::from_definitions([
+            "CREATE UNIQUE INDEX event_pkey ON public.event USING btree (event_id)",
+            "CREATE INDEX idx_event_foo_id ON public.event USING hash (foo_id) WHERE (foo_id IS NOT NULL)",
+        ])
+    }
+
+    #[test]
+    fn it_does_not_warn_about_indexed_identifiers() {
+        let query = query!(TestEvent; foo_id == "value");
+
+        assert!(lint(&query, &indexes()).is_empty());
+    }
+
+    #[test]
+    fn it_warns_about_the_filters_that_cannot_use_an_index() {
+        let query = query!(TestEvent; bar_id == "value")
+            .filter_range(ident!(#foo_id), "a".."m")
+            .filter_payload(ident!(#zone), "eu");
+
+        assert_eq!(
+            lint(&query, &indexes()),
+            vec![
+                "the domain identifier `bar_id` has no index, its filter scans the event table",
+                "the range filter on `foo_id` cannot use an index, create a btree index on the column",
+                "the payload filter on `zone` is evaluated on every event of the query, consider declaring it as a domain identifier",
+            ]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::{Arc, Mutex};
+
+use disintegrate::{Actor, Event, PersistedEvent};
+use disintegrate_serde::Serde;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+use super::{map_concurrency_err, validation_sql, PgEventStore};
+use crate::{Error, PgEventId};
+
+type AppendResult<E> = Result<Vec<PersistedEvent<PgEventId, E>>, Error>;
+
+/// An append waiting for its group to be committed.
+struct PendingAppend<E: Event> {
+    events: Vec<E>,
+    /// The criteria of the validation query, or `None` if the append is not validated.
+    validation: Option<String>,
+    /// The actor of the execution context of the append, as the group is committed by another append.
+    actor: Option<Actor>,
+    result: oneshot::Sender<AppendResult<E>>,
+}
+
+/// The queue of the appends committed together.
+///
+/// The first append that finds its group uncommitted commits the appends waiting in the queue, the others
+/// receive their results once the group is committed.
+pub(crate) struct GroupCommit<E: Event> {
+    max_group_size: usize,
+    pending: Mutex<Vec<PendingAppend<E>>>,
+    committing: tokio::sync::Mutex<()>,
+}
+
+impl<E: Event> GroupCommit<E> {
+    pub(crate) fn new(max_group_size: usize) -> Self {
+        Self {
+            max_group_size,
+            pending: Mutex::new(vec![]),
+            committing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn push(&self, append: PendingAppend<E>) {
+        self.pending.lock().unwrap().push(append);
+    }
+
+    /// Takes the next group of appends from the queue, in arrival order.
+    fn take(&self) -> Vec<PendingAppend<E>> {
+        let mut pending = self.pending.lock().unwrap();
+        let size = pending.len().min(self.max_group_size);
+        pending.drain(..size).collect()
+    }
+}
+
+/// An append of the group being committed.
+struct GroupedAppend<E: Event> {
+    events: Vec<E>,
+    persisted_events: Vec<PersistedEvent<PgEventId, E>>,
+    validation: Option<String>,
+    actor: Option<Actor>,
+    result: oneshot::Sender<AppendResult<E>>,
+    conflict: bool,
+}
+
+impl<E, S> PgEventStore<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    /// Appends the events as part of the next group of appends.
+    pub(super) async fn append_grouped(
+        &self,
+        group_commit: &GroupCommit<E>,
+        events: Vec<E>,
+        validation: Option<String>,
+    ) -> AppendResult<E> {
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+        let (sender, mut receiver) = oneshot::channel();
+        group_commit.push(PendingAppend {
+            events,
+            validation,
+            actor: Actor::current(),
+            result: sender,
+        });
+        loop {
+            let _committing = group_commit.committing.lock().await;
+            match receiver.try_recv() {
+                Ok(result) => return result,
+                Err(TryRecvError::Closed) => return Err(Error::GroupCommitAborted),
+                Err(TryRecvError::Empty) => self.commit_group(group_commit.take()).await,
+            }
+        }
+    }
+
+    /// Commits the group and sends each append its result.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.group_commit",
+            skip_all,
+            fields(appends = group.len())
+        )
+    )]
+    async fn commit_group(&self, group: Vec<PendingAppend<E>>) {
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+        let mut appends: Vec<GroupedAppend<E>> = group
+            .into_iter()
+            .map(|append| GroupedAppend {
+                events: append.events,
+                persisted_events: vec![],
+                validation: append.validation,
+                actor: append.actor,
+                result: append.result,
+                conflict: false,
+            })
+            .collect();
+        match self.try_commit_group(&mut appends).await {
+            Ok(()) => {
+                for append in &appends {
+                    self.invalidate_cached_queries(&append.persisted_events);
+                }
+                for append in appends {
+                    let result = if append.conflict {
+                        Err(Error::Concurrency)
+                    } else {
+                        #[cfg(feature = "metrics")]
+                        super::metrics::record_append(
+                            &append.persisted_events,
+                            started_at.elapsed(),
+                            self.metrics_labels,
+                        );
+                        Ok(append.persisted_events)
+                    };
+                    let _ = append.result.send(result);
+                }
+            }
+            Err(err) if appends.len() == 1 => {
+                let _ = appends.remove(0).result.send(Err(err));
+            }
+            Err(err) => {
+                let err = Arc::new(err);
+                for append in appends {
+                    let _ = append.result.send(Err(Error::GroupCommit(err.clone())));
+                }
+            }
+        }
+    }
+
+    /// Commits the appends in a single transaction.
+    ///
+    /// Each append is validated within a savepoint, so a conflicting append is rolled back and flagged
+    /// without failing the others.
+    async fn try_commit_group(&self, appends: &mut [GroupedAppend<E>]) -> Result<(), Error> {
+        let _permit = self.concurrent_appends.acquire().await?;
+        let mut tx = self.begin().await?;
+        sqlx::query("SELECT event_store_begin_epoch()")
+            .execute(&mut *tx)
+            .await?;
+        for append in appends.iter_mut() {
+            append.persisted_events = self
+                .stage_events(
+                    std::mem::take(&mut append.events),
+                    append.validation.is_none(),
+                    append.actor.clone(),
+                )
+                .await?;
+        }
+
+        for append in appends.iter_mut() {
+            let event_ids: Vec<PgEventId> =
+                append.persisted_events.iter().map(|e| e.id()).collect();
+            let last_event_id = event_ids.last().copied().unwrap_or_default();
+            sqlx::query("SAVEPOINT grouped_append")
+                .execute(&mut *tx)
+                .await?;
+            let result =
+                match &append.validation {
+                    Some(criteria) => {
+                        sqlx::query(&validation_sql(criteria))
+                            .bind(&event_ids)
+                            .bind(last_event_id)
+                            .execute(&mut *tx)
+                            .await
+                    }
+                    None => sqlx::query(
+                        "UPDATE event_sequence es SET committed = true WHERE event_id = ANY($1)",
+                    )
+                    .bind(&event_ids)
+                    .execute(&mut *tx)
+                    .await,
+                };
+            match result.map_err(map_concurrency_err) {
+                Ok(_) => {
+                    sqlx::query("RELEASE SAVEPOINT grouped_append")
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                Err(Error::Concurrency) => {
+                    sqlx::query("ROLLBACK TO SAVEPOINT grouped_append")
+                        .execute(&mut *tx)
+                        .await?;
+                    append.conflict = true;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut persisted_events = vec![];
+        let mut sizes = vec![];
+        for append in appends.iter_mut().filter(|append| !append.conflict) {
+            sizes.push(append.persisted_events.len());
+            persisted_events.append(&mut append.persisted_events);
+        }
+        if !persisted_events.is_empty() {
+            self.insert_events(&mut tx, &persisted_events).await?;
+        }
+        tx.commit().await?;
+
+        let mut persisted_events = persisted_events.into_iter();
+        for (append, size) in appends
+            .iter_mut()
+            .filter(|append| !append.conflict)
+            .zip(sizes)
+        {
+            append.persisted_events = persisted_events.by_ref().take(size).collect();
+        }
+        Ok(())
+    }
+}
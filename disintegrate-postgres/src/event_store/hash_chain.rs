@@ -0,0 +1,235 @@
+use disintegrate::{Event, PersistedEvent};
+use disintegrate_serde::Serde;
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::PgEventStore;
+use crate::{Error, PgEventId};
+
+/// The link of an appended event to the hash chain.
+pub(crate) struct ChainLink {
+    /// The serialized payload of the event, hashed and stored as is.
+    pub payload: Vec<u8>,
+    /// The ID of the previous event of the chain, if any.
+    pub previous_event_id: Option<PgEventId>,
+    /// The hash of the event, chained to the hash of the previous event.
+    pub hash: Vec<u8>,
+}
+
+/// The last event of the hash chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHead {
+    /// The ID of the last chained event.
+    pub event_id: PgEventId,
+    /// The hash of the last chained event.
+    pub hash: Vec<u8>,
+}
+
+/// The result of the verification of the hash chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainVerification {
+    /// The number of events whose hash matches their content and the previous event of the chain.
+    pub verified_events: u64,
+    /// The number of events appended before the hash chain was enabled.
+    pub unchained_events: u64,
+    /// The events that were altered, or whose previous event was altered or removed.
+    pub broken_links: Vec<PgEventId>,
+}
+
+impl ChainVerification {
+    /// Returns `true` if none of the verified events was altered.
+    pub fn is_intact(&self) -> bool {
+        self.broken_links.is_empty()
+    }
+}
+
+/// Computes the hash of an event, chained to the hash of the previous event.
+///
+/// The hash covers the ID, the type and the serialized payload of the event.
+pub(crate) fn event_hash(
+    previous_hash: &[u8],
+    event_id: PgEventId,
+    event_type: &str,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(previous_hash);
+    hasher.update(event_id.to_be_bytes());
+    hasher.update((event_type.len() as u64).to_be_bytes());
+    hasher.update(event_type.as_bytes());
+    hasher.update(payload);
+    hasher.finalize().to_vec()
+}
+
+/// Links the events to the chain, starting from its head.
+pub(crate) fn link<E, S>(
+    events: &[PersistedEvent<PgEventId, E>],
+    serde: &S,
+    head: Option<ChainHead>,
+) -> Vec<ChainLink>
+where
+    E: Event + Clone,
+    S: Serde<E>,
+{
+    let mut previous = head;
+    events
+        .iter()
+        .map(|event| {
+            let payload = serde.serialize(event.clone().into_inner());
+            let previous_hash = previous.as_ref().map(|head| head.hash.as_slice());
+            let hash = event_hash(
+                previous_hash.unwrap_or_default(),
+                event.id(),
+                event.name(),
+                &payload,
+            );
+            let link = ChainLink {
+                payload,
+                previous_event_id: previous.as_ref().map(|head| head.event_id),
+                hash: hash.clone(),
+            };
+            previous = Some(ChainHead {
+                event_id: event.id(),
+                hash,
+            });
+            link
+        })
+        .collect()
+}
+
+/// Returns the head of the chain.
+pub(crate) async fn head(pool: &PgPool) -> Result<Option<ChainHead>, Error> {
+    Ok(
+        sqlx::query_as::<_, (PgEventId, Vec<u8>)>("SELECT event_id, hash FROM event_chain_head")
+            .fetch_optional(pool)
+            .await?
+            .map(|(event_id, hash)| ChainHead { event_id, hash }),
+    )
+}
+
+/// Links the events to the chain and moves its head to the last event.
+///
+/// The appends are chained one at a time: the lock is held until the transaction commits.
+pub(crate) async fn extend<E, S>(
+    tx: &mut Transaction<'_, Postgres>,
+    events: &[PersistedEvent<PgEventId, E>],
+    serde: &S,
+) -> Result<Vec<ChainLink>, Error>
+where
+    E: Event + Clone,
+    S: Serde<E>,
+{
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('disintegrate_hash_chain'))")
+        .execute(&mut **tx)
+        .await?;
+    let head =
+        sqlx::query_as::<_, (PgEventId, Vec<u8>)>("SELECT event_id, hash FROM event_chain_head")
+            .fetch_optional(&mut **tx)
+            .await?
+            .map(|(event_id, hash)| ChainHead { event_id, hash });
+    let chain = link(events, serde, head);
+    if let (Some(event), Some(link)) = (events.last(), chain.last()) {
+        sqlx::query(
+            "INSERT INTO event_chain_head (id, event_id, hash) VALUES (true, $1, $2) ON CONFLICT (id) DO UPDATE SET event_id = $1, hash = $2",
+        )
+        .bind(event.id())
+        .bind(&link.hash)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(chain)
+}
+
+/// Verifies the hashes of the events matching the criteria against their content and the previous
+/// event of the chain.
+///
+/// The claim-checked payloads are fetched from the object store of the claim check, and the encrypted
+/// payloads are decrypted.
+pub(crate) async fn verify<E, S>(
+    event_store: &PgEventStore<E, S>,
+    criteria: &str,
+) -> Result<ChainVerification, Error>
+where
+    E: Event + Send + Sync,
+    S: Serde<E> + Send + Sync,
+{
+    let pool = &event_store.pool;
+    let genesis: Option<PgEventId> = sqlx::query_scalar(
+        "SELECT min(event_id) FROM event WHERE hash IS NOT NULL AND previous_event_id IS NULL",
+    )
+    .fetch_one(pool)
+    .await?;
+    let sql = format!(
+        "SELECT e.event_id, e.event_type, e.payload, e.hash, e.previous_event_id, p.hash \
+         FROM (SELECT event_id, event_type, payload, hash, previous_event_id FROM event WHERE {criteria}) e \
+         LEFT JOIN event p ON p.event_id = e.previous_event_id ORDER BY e.event_id"
+    );
+    let mut rows = sqlx::query_as::<
+        _,
+        (
+            PgEventId,
+            String,
+            Vec<u8>,
+            Option<Vec<u8>>,
+            Option<PgEventId>,
+            Option<Vec<u8>>,
+        ),
+    >(&sql)
+    .fetch(pool);
+    let mut verification = ChainVerification::default();
+    while let Some((event_id, event_type, payload, hash, previous_event_id, previous_hash)) =
+        rows.try_next().await?
+    {
+        let Some(hash) = hash else {
+            verification.unchained_events += 1;
+            continue;
+        };
+        #[cfg(any(feature = "claim-check", feature = "encryption"))]
+        let payload = event_store.decode_payload(payload).await?;
+        let previous_hash = match previous_event_id {
+            None if genesis == Some(event_id) => Some(vec![]),
+            None => None,
+            Some(_) => previous_hash,
+        };
+        match previous_hash {
+            Some(previous_hash)
+                if event_hash(&previous_hash, event_id, &event_type, &payload) == hash =>
+            {
+                verification.verified_events += 1
+            }
+            _ => verification.broken_links.push(event_id),
+        }
+    }
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_chains_the_hash_of_the_previous_event() {
+        let first = event_hash(&[], 1, "Deposited", b"{\"amount\":10}");
+        let second = event_hash(&first, 2, "Withdrawn", b"{\"amount\":5}");
+
+        assert_eq!(first.len(), 32);
+        assert_eq!(
+            second,
+            event_hash(&first, 2, "Withdrawn", b"{\"amount\":5}")
+        );
+        assert_ne!(
+            second,
+            event_hash(&first, 2, "Withdrawn", b"{\"amount\":50}")
+        );
+        assert_ne!(
+            second,
+            event_hash(
+                &event_hash(&[], 1, "Deposited", b"{\"amount\":1}"),
+                2,
+                "Withdrawn",
+                b"{\"amount\":5}"
+            )
+        );
+    }
+}
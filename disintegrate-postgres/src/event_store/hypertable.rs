@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+/// How the events are partitioned into the chunks of the hypertable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Partitioning {
+    /// Chunks of consecutive event IDs.
+    EventId { chunk_events: i64 },
+    /// Chunks of consecutive insertion times.
+    InsertedAt { chunk_interval: Duration },
+}
+
+/// The TimescaleDB hypertable of the `event` table, created by `PgEventStore::create_hypertable`.
+///
+/// Partitioning by event ID keeps the streams efficient, since their pages are bounded by event ID and
+/// only read the chunks covering them. Partitioning by insertion time suits the lookups by time, such as
+/// `event_id_at`, but every stream checks the indexes of all the chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hypertable {
+    partitioning: Partitioning,
+    compress_after_chunks: Option<u32>,
+}
+
+impl Hypertable {
+    /// Partitions the events into chunks of `chunk_events` consecutive event IDs.
+    pub fn by_event_id(chunk_events: i64) -> Self {
+        assert!(chunk_events > 0, "chunk size must be greater than 0");
+        Self {
+            partitioning: Partitioning::EventId { chunk_events },
+            compress_after_chunks: None,
+        }
+    }
+
+    /// Partitions the events into chunks of `chunk_interval` of insertion time.
+    ///
+    /// The partitioning column must be part of the primary key, so the primary key of the `event` table
+    /// becomes `(event_id, inserted_at)`.
+    pub fn by_inserted_at(chunk_interval: Duration) -> Self {
+        assert!(
+            chunk_interval.as_secs() > 0,
+            "chunk interval must be at least one second"
+        );
+        Self {
+            partitioning: Partitioning::InsertedAt { chunk_interval },
+            compress_after_chunks: None,
+        }
+    }
+
+    /// Compresses the chunks once `chunks` newer chunks have been filled.
+    ///
+    /// The compressed chunks are read as usual, but updating or deleting their events, e.g. to index an
+    /// identifier with `PgIdIndexer` or to `repair` the event store, is slower.
+    pub fn with_compression_after_chunks(mut self, chunks: u32) -> Self {
+        assert!(chunks > 0, "chunks must be greater than 0");
+        self.compress_after_chunks = Some(chunks);
+        self
+    }
+
+    /// Returns the statements that convert the `event` table into the hypertable.
+    pub(crate) fn create_statements(&self) -> Vec<String> {
+        match self.partitioning {
+            Partitioning::EventId { chunk_events } => vec![
+                format!("SELECT create_hypertable('event', 'event_id', chunk_time_interval => {chunk_events}::BIGINT, migrate_data => TRUE)"),
+                "CREATE OR REPLACE FUNCTION event_integer_now() RETURNS BIGINT LANGUAGE SQL STABLE AS $$ SELECT COALESCE(MAX(event_id), 0) FROM event $$".to_string(),
+                "SELECT set_integer_now_func('event', 'event_integer_now', replace_if_exists => TRUE)".to_string(),
+            ],
+            Partitioning::InsertedAt { chunk_interval } => vec![
+                "ALTER TABLE event DROP CONSTRAINT IF EXISTS event_pkey".to_string(),
+                "ALTER TABLE event ADD PRIMARY KEY (event_id, inserted_at)".to_string(),
+                format!(
+                    "SELECT create_hypertable('event', 'inserted_at', chunk_time_interval => INTERVAL '{} seconds', migrate_data => TRUE)",
+                    chunk_interval.as_secs()
+                ),
+            ],
+        }
+    }
+
+    /// Returns the statements that set the compression of the hypertable, if enabled.
+    ///
+    /// The compression settings are omitted if the compression is already enabled, since they cannot
+    /// change once chunks are compressed.
+    pub(crate) fn compression_statements(&self, compression_enabled: bool) -> Vec<String> {
+        let Some(chunks) = self.compress_after_chunks else {
+            return vec![];
+        };
+        let compress_after = match self.partitioning {
+            Partitioning::EventId { chunk_events } => {
+                format!("{}::BIGINT", chunk_events * i64::from(chunks))
+            }
+            Partitioning::InsertedAt { chunk_interval } => format!(
+                "INTERVAL '{} seconds'",
+                chunk_interval.as_secs() * u64::from(chunks)
+            ),
+        };
+        let mut statements = vec![];
+        if !compression_enabled {
+            statements.push("ALTER TABLE event SET (timescaledb.compress, timescaledb.compress_orderby = 'event_id')".to_string());
+        }
+        statements.push(format!("SELECT add_compression_policy('event', compress_after => {compress_after}, if_not_exists => TRUE)"));
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_partitions_the_events_by_event_id() {
+        let hypertable = Hypertable::by_event_id(1_000_000).with_compression_after_chunks(2);
+
+        assert_eq!(
+            hypertable.create_statements()[0],
+            "SELECT create_hypertable('event', 'event_id', chunk_time_interval => 1000000::BIGINT, migrate_data => TRUE)"
+        );
+        assert_eq!(
+            hypertable.compression_statements(false)[1],
+            "SELECT add_compression_policy('event', compress_after => 2000000::BIGINT, if_not_exists => TRUE)"
+        );
+    }
+
+    #[test]
+    fn it_partitions_the_events_by_insertion_time() {
+        let hypertable = Hypertable::by_inserted_at(Duration::from_secs(86400));
+
+        assert_eq!(
+            hypertable.create_statements(),
+            vec![
+                "ALTER TABLE event DROP CONSTRAINT IF EXISTS event_pkey",
+                "ALTER TABLE event ADD PRIMARY KEY (event_id, inserted_at)",
+                "SELECT create_hypertable('event', 'inserted_at', chunk_time_interval => INTERVAL '86400 seconds', migrate_data => TRUE)",
+            ]
+        );
+        assert!(hypertable.compression_statements(false).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+
+use disintegrate::{Event, Identifier, StreamQuery};
+
+use super::explain::EventIndexes;
+use crate::PgEventId;
+
+/// Collects the domain identifiers used by the stream queries of an application, to recommend the
+/// indexes of the `event` table they need.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let advisor = IndexAdvisor::default()
+///     .with_query(&query!(DomainEvent; cart_id == "c1"))
+///     .with_query(&query!(DomainEvent).filter_range(ident!(#placed_at), from..to));
+/// for recommendation in event_store.advise_indexes(&advisor).await?.recommendations() {
+///     println!("{}: {}", recommendation.reason(), recommendation.definition());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IndexAdvisor {
+    equalities: BTreeSet<Identifier>,
+    ranges: BTreeSet<Identifier>,
+    payload_fields: BTreeSet<Identifier>,
+}
+
+impl IndexAdvisor {
+    /// Registers the identifiers filtered by the query.
+    pub fn with_query<QE>(mut self, query: &StreamQuery<PgEventId, QE>) -> Self
+    where
+        QE: Event + Clone,
+    {
+        for filter in query.filters() {
+            let equalities = filter
+                .identifiers()
+                .keys()
+                .copied()
+                .chain(filter.disjunctions().iter().flatten().map(|i| i.key))
+                .chain(filter.excluded_identifiers().iter().map(|i| i.key));
+            self.equalities.extend(equalities);
+            self.ranges
+                .extend(filter.ranges().iter().map(|range| range.key));
+            self.payload_fields
+                .extend(filter.payload_filters().keys().copied());
+        }
+        self
+    }
+
+    /// Returns the advice for the registered queries, given the existing indexes.
+    pub(crate) fn advise(&self, indexes: &EventIndexes) -> IndexAdvice {
+        let mut recommendations = vec![];
+        for column in &self.equalities {
+            if indexes.methods(column.into_inner()).is_empty() {
+                recommendations.push(IndexRecommendation {
+                    name: format!("idx_event_{column}"),
+                    definition: format!(
+                        "CREATE INDEX CONCURRENTLY IF NOT EXISTS idx_event_{column} ON event USING HASH ({column}) WHERE {column} IS NOT NULL"
+                    ),
+                    reason: format!("the domain identifier `{column}` has no index"),
+                });
+            }
+        }
+        for column in &self.ranges {
+            if !indexes
+                .methods(column.into_inner())
+                .iter()
+                .any(|method| method == "btree")
+            {
+                recommendations.push(IndexRecommendation {
+                    name: format!("idx_event_{column}_range"),
+                    definition: format!(
+                        "CREATE INDEX CONCURRENTLY IF NOT EXISTS idx_event_{column}_range ON event USING BTREE ({column}) WHERE {column} IS NOT NULL"
+                    ),
+                    reason: format!("the range filters on `{column}` need a btree index"),
+                });
+            }
+        }
+        let unindexed = self
+            .payload_fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "the payload filter on `{field}` cannot use an index, consider declaring it as a domain identifier"
+                )
+            })
+            .collect();
+        IndexAdvice {
+            recommendations,
+            unindexed,
+        }
+    }
+}
+
+/// An index of the `event` table missing for the registered queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexRecommendation {
+    name: String,
+    definition: String,
+    reason: String,
+}
+
+impl IndexRecommendation {
+    /// Returns the name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the statement that creates the index without locking the writes.
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    /// Returns why the index is recommended.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// The indexes recommended for the registered queries, returned by `PgEventStore::advise_indexes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexAdvice {
+    recommendations: Vec<IndexRecommendation>,
+    unindexed: Vec<String>,
+}
+
+impl IndexAdvice {
+    /// Returns the missing indexes.
+    pub fn recommendations(&self) -> &[IndexRecommendation] {
+        &self.recommendations
+    }
+
+    /// Returns the query patterns that no index of the `event` table can serve.
+    pub fn unindexed(&self) -> &[String] {
+        &self.unindexed
+    }
+
+    /// Returns `true` if every registered query can use the indexes.
+    pub fn is_complete(&self) -> bool {
+        self.recommendations.is_empty() && self.unindexed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{
+        domain_identifiers, ident, query, DomainIdentifierInfo, DomainIdentifierSet, EventInfo,
+        EventSchema, IdentifierType,
+    };
+
+    #[allow(dead_code)]
+    #[derive(Clone)]
+    enum TestEvent {
+        Foo { foo_id: String, bar_id: String },
+    }
+
+    impl Event for TestEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["Foo"],
+            events_info: &[&EventInfo {
+                name: "Foo",
+                domain_identifiers: &[&ident!(#foo_id), &ident!(#bar_id)],
+            }],
+            domain_identifiers: &[
+                &DomainIdentifierInfo {
+                    ident: ident!(#bar_id),
+                    type_info: IdentifierType::String,
+                },
+                &DomainIdentifierInfo {
+                    ident: ident!(#foo_id),
+                    type_info: IdentifierType::String,
+                },
+            ],
+        };
+
+        fn name(&self) -> &'static str {
+            "Foo"
+        }
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {}
+        }
+    }
+
+    #[test]
+    fn it_recommends_the_indexes_missing_for_the_registered_queries() {
+        let indexes = EventIndexes::from_definitions([
+            "CREATE INDEX idx_event_foo_id ON public.event USING hash (foo_id) WHERE (foo_id IS NOT NULL)",
+        ]);
+        let advisor = IndexAdvisor::default()
+            .with_query(&query!(TestEvent; foo_id == "value", bar_id == "value"))
+            .with_query(&query!(TestEvent).filter_range(ident!(#foo_id), "a".."m"))
+            .with_query(&query!(TestEvent).filter_payload(ident!(#zone), "eu"));
+
+        let advice = advisor.advise(&indexes);
+
+        let names: Vec<&str> = advice
+            .recommendations()
+            .iter()
+            .map(IndexRecommendation::name)
+            .collect();
+        assert_eq!(names, vec!["idx_event_bar_id", "idx_event_foo_id_range"]);
+        assert_eq!(
+            advice.unindexed(),
+            ["the payload filter on `zone` cannot use an index, consider declaring it as a domain identifier"]
+        );
+        assert!(!advice.is_complete());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::PgEventId;
+
+/// The result of the integrity check of the global event ordering.
+///
+/// Each event gets its ID from the `event_sequence` table before it is written to the `event` table,
+/// and the sequence entry is marked as committed in the same transaction that validates the append.
+/// An append that fails or crashes leaves a gap in the ordering. The gaps are harmless, as the
+/// readers skip them, but the event IDs are unique by construction, so duplicates cannot occur.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The events whose append was not committed. They are visible to the readers even though
+    /// their decision was rejected, so they must be removed.
+    pub uncommitted_events: Vec<PgEventId>,
+    /// The committed sequence entries without an event. They cannot be repaired, as the payload of
+    /// the events is lost, and should be investigated.
+    pub missing_events: Vec<PgEventId>,
+    /// The number of sequence entries left by the appends that failed, each one a gap in the
+    /// ordering. They are removed by the repair.
+    pub aborted_appends: u64,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if the event table reflects the committed appends.
+    pub fn is_consistent(&self) -> bool {
+        self.uncommitted_events.is_empty() && self.missing_events.is_empty()
+    }
+}
+
+/// The result of the repair of the global event ordering.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// The uncommitted events removed from the event table.
+    pub removed_events: Vec<PgEventId>,
+    /// The number of sequence entries of the failed appends that were removed.
+    pub compacted_sequence_entries: u64,
+}
+
+/// Selects the events of the appends that were not committed within the grace period.
+pub(crate) const UNCOMMITTED_EVENTS_SQL: &str =
+    "SELECT e.event_id FROM event e JOIN event_sequence s ON s.event_id = e.event_id \
+     WHERE NOT s.committed AND e.inserted_at < now() - make_interval(secs => $1) \
+     ORDER BY e.event_id";
+
+/// Selects the committed sequence entries without an event.
+pub(crate) const MISSING_EVENTS_SQL: &str = "SELECT s.event_id FROM event_sequence s \
+     WHERE s.committed AND NOT EXISTS (SELECT 1 FROM event e WHERE e.event_id = s.event_id) \
+     ORDER BY s.event_id";
+
+/// Selects the sequence entries of the appends that failed within the grace period.
+pub(crate) const ABORTED_APPENDS_FILTER: &str =
+    "NOT committed AND inserted_at < now() - make_interval(secs => $1) \
+     AND NOT EXISTS (SELECT 1 FROM event e WHERE e.event_id = event_sequence.event_id)";
@@ -0,0 +1,149 @@
+//! Metrics of the event store.
+//!
+//! The metrics are reported to the `metrics` crate facade, so they can be exported to any backend.
+//! The `prometheus` feature provides a ready to use Prometheus recorder.
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use disintegrate::{Event, PersistedEvent, StreamQuery};
+
+use crate::PgEventId;
+
+/// The labels attached to the metrics of the event store.
+///
+/// Every distinct label value creates a time series, so the labels are opt-in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetricsLabels {
+    /// The metrics have no labels.
+    #[default]
+    None,
+    /// The append metrics are labeled with the event type, and the query metrics with the
+    /// event types of the query.
+    EventType,
+}
+
+pub(crate) fn record_append<E: Event + Clone>(
+    events: &[PersistedEvent<PgEventId, E>],
+    elapsed: Duration,
+    labels: MetricsLabels,
+) {
+    ::metrics::histogram!("disintegrate_append_seconds").record(elapsed.as_secs_f64());
+    match labels {
+        MetricsLabels::None => {
+            ::metrics::counter!("disintegrate_append_events_total").increment(events.len() as u64)
+        }
+        MetricsLabels::EventType => {
+            for event in events {
+                ::metrics::counter!("disintegrate_append_events_total", "event_type" => event.name())
+                    .increment(1);
+            }
+        }
+    }
+}
+
+pub(crate) fn record_conflict() {
+    ::metrics::counter!("disintegrate_append_conflicts_total").increment(1);
+}
+
+pub(crate) fn record_query<QE: Event + Clone>(
+    query: &StreamQuery<PgEventId, QE>,
+    events: u64,
+    elapsed: Duration,
+    labels: MetricsLabels,
+) {
+    match labels {
+        MetricsLabels::None => {
+            ::metrics::histogram!("disintegrate_query_seconds").record(elapsed.as_secs_f64());
+            ::metrics::counter!("disintegrate_query_events_total").increment(events);
+        }
+        MetricsLabels::EventType => {
+            let event_types = query_event_types(query);
+            ::metrics::histogram!("disintegrate_query_seconds", "event_types" => event_types.clone())
+                .record(elapsed.as_secs_f64());
+            ::metrics::counter!("disintegrate_query_events_total", "event_types" => event_types)
+                .increment(events);
+        }
+    }
+}
+
+/// Returns the sorted event types streamed by the query, separated by commas.
+fn query_event_types<QE: Event + Clone>(query: &StreamQuery<PgEventId, QE>) -> String {
+    query
+        .filters()
+        .iter()
+        .flat_map(|filter| filter.events().iter().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The buckets of the latency histograms, in seconds.
+#[cfg(feature = "prometheus")]
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs a Prometheus recorder as the global recorder of the `metrics` crate.
+///
+/// The returned handle renders the metrics of the event store, the snapshots and the event
+/// listeners in the Prometheus text format, to be served by the metrics endpoint of the application.
+/// The latencies are recorded as histograms with buckets from 1ms to 10s.
+#[cfg(feature = "prometheus")]
+pub fn install_prometheus_recorder(
+) -> Result<metrics_exporter_prometheus::PrometheusHandle, metrics_exporter_prometheus::BuildError>
+{
+    metrics_exporter_prometheus::PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            metrics_exporter_prometheus::Matcher::Suffix("_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )?
+        .install_recorder()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{domain_identifiers, query, DomainIdentifierSet, EventInfo, EventSchema};
+
+    #[allow(dead_code)]
+    #[derive(Clone)]
+    enum TestEvent {
+        Opened,
+        Closed,
+    }
+
+    impl Event for TestEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["Opened", "Closed"],
+            events_info: &[
+                &EventInfo {
+                    name: "Opened",
+                    domain_identifiers: &[],
+                },
+                &EventInfo {
+                    name: "Closed",
+                    domain_identifiers: &[],
+                },
+            ],
+            domain_identifiers: &[],
+        };
+
+        fn name(&self) -> &'static str {
+            match self {
+                TestEvent::Opened => "Opened",
+                TestEvent::Closed => "Closed",
+            }
+        }
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {}
+        }
+    }
+
+    #[test]
+    fn it_labels_the_queries_with_their_event_types() {
+        let query = query::<PgEventId, TestEvent, TestEvent>(None);
+
+        assert_eq!(query_event_types(&query), "Closed,Opened");
+    }
+}
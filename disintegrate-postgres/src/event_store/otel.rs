@@ -0,0 +1,26 @@
+//! Propagation of the OpenTelemetry trace context through the events.
+use std::collections::HashMap;
+
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{global, Context};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Returns the trace context of the current span, serialized with the global text map propagator.
+///
+/// Returns `None` if the current span does not belong to a trace.
+pub(crate) fn current_trace_context() -> Option<String> {
+    let context = tracing::Span::current().context();
+    if !context.span().span_context().is_valid() {
+        return None;
+    }
+    let mut carrier: HashMap<String, String> = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    serde_json::to_string(&carrier).ok()
+}
+
+/// Restores the trace context stored with an event.
+pub(crate) fn extract_trace_context(trace_context: &str) -> Option<Context> {
+    let carrier: HashMap<String, String> = serde_json::from_str(trace_context).ok()?;
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    context.span().span_context().is_valid().then_some(context)
+}
@@ -12,6 +12,7 @@ where
 {
     query: &'a StreamQuery<PgEventId, QE>,
     builder: String,
+    payload_filters: bool,
 }
 
 impl<'a, QE> CriteriaBuilder<'a, QE>
@@ -28,9 +29,20 @@ where
         Self {
             query,
             builder: String::with_capacity(512),
+            payload_filters: true,
         }
     }
 
+    /// Leaves the payload filters out of the criteria.
+    ///
+    /// The criteria can then be run against the `event_sequence` table, which has no payload, such as to
+    /// validate an append. Without the payload filters the criteria match more events, which is safe for
+    /// the optimistic validation: it may only report a conflict that the payload would have excluded.
+    pub fn without_payload_filters(mut self) -> Self {
+        self.payload_filters = false;
+        self
+    }
+
     /// Builds the SQL criteria string.
     pub fn build(mut self) -> String {
         let mut filters = self.query.filters().iter().peekable();
@@ -49,10 +61,23 @@ where
 
             // Start filter group
             self.builder.push('(');
+            let has_payload_filters = self.payload_filters && !filter.payload_filters().is_empty();
+            if has_payload_filters {
+                self.builder.push('(');
+            }
 
-            // Add event_id condition if needed
-            if filter.origin() > 0 {
-                write!(self.builder, "event_id > {}", filter.origin()).unwrap();
+            // Add event_id conditions if needed
+            let has_bounds = filter.origin() > 0 || filter.until().is_some();
+            if has_bounds {
+                if filter.origin() > 0 {
+                    write!(self.builder, "event_id > {}", filter.origin()).unwrap();
+                }
+                if let Some(until) = filter.until() {
+                    if filter.origin() > 0 {
+                        write!(self.builder, " AND ").unwrap();
+                    }
+                    write!(self.builder, "event_id <= {}", until).unwrap();
+                }
 
                 if has_events {
                     write!(self.builder, " AND (").unwrap();
@@ -78,22 +103,75 @@ where
 
                 while let Some((ident, value)) = event_identifiers.next() {
                     write!(self.builder, "{} = ", ident).unwrap();
-                    match value {
-                        disintegrate::IdentifierValue::String(value) => {
-                            write!(self.builder, "'{}'", value).unwrap();
-                        }
-                        disintegrate::IdentifierValue::i64(value) => {
-                            write!(self.builder, "{}", value).unwrap();
-                        }
-                        disintegrate::IdentifierValue::Uuid(value) => {
-                            write!(self.builder, "'{}'", value).unwrap();
-                        }
-                    };
+                    write_value(&mut self.builder, value);
                     if event_identifiers.peek().is_some() {
                         write!(self.builder, " AND ").unwrap();
                     }
                 }
 
+                // Process excluded identifiers
+                for excluded in filter
+                    .excluded_identifiers()
+                    .iter()
+                    .filter(|i| event_info.has_domain_identifier(&i.key))
+                {
+                    write!(self.builder, " AND {} <> ", excluded.key).unwrap();
+                    write_value(&mut self.builder, &excluded.value);
+                }
+
+                // Process ranges
+                for range in filter
+                    .ranges()
+                    .iter()
+                    .filter(|range| event_info.has_domain_identifier(&range.key))
+                {
+                    for (op, value) in range.conditions() {
+                        write!(self.builder, " AND {} {op} ", range.key).unwrap();
+                        write_value(&mut self.builder, value);
+                    }
+                }
+
+                // Process empty IN lists
+                if filter
+                    .empty_lists()
+                    .iter()
+                    .any(|ident| event_info.has_domain_identifier(ident))
+                {
+                    write!(self.builder, " AND FALSE").unwrap();
+                }
+
+                // Process disjunctions
+                for disjunction in filter.disjunctions() {
+                    let mut event_identifiers = disjunction
+                        .iter()
+                        .filter(|i| event_info.has_domain_identifier(&i.key))
+                        .peekable();
+                    let Some(first) = event_identifiers.peek() else {
+                        continue;
+                    };
+                    let key = first.key;
+                    if disjunction.len() > 1 && disjunction.iter().all(|i| i.key == key) {
+                        write!(self.builder, " AND {key} IN (").unwrap();
+                        while let Some(identifier) = event_identifiers.next() {
+                            write_value(&mut self.builder, &identifier.value);
+                            if event_identifiers.peek().is_some() {
+                                write!(self.builder, ", ").unwrap();
+                            }
+                        }
+                        self.builder.push(')');
+                        continue;
+                    }
+                    write!(self.builder, " AND (").unwrap();
+                    while let Some(identifier) = event_identifiers.next() {
+                        write!(self.builder, "{} = ", identifier.key).unwrap();
+                        write_value(&mut self.builder, &identifier.value);
+                        if event_identifiers.peek().is_some() {
+                            write!(self.builder, " OR ").unwrap();
+                        }
+                    }
+                    self.builder.push(')');
+                }
+
                 self.builder.push(')');
                 if events.peek().is_some() {
                     write!(self.builder, " OR ").unwrap();
@@ -101,10 +179,28 @@ where
             }
 
             // Close events group if needed
-            if filter.origin() > 0 && has_events {
+            if has_bounds && has_events {
                 self.builder.push(')');
             }
 
+            // Process payload filters
+            if has_payload_filters {
+                self.builder.push(')');
+                for (field, value) in filter.payload_filters().iter() {
+                    let field = payload_field(field);
+                    let value = match value {
+                        disintegrate::IdentifierValue::String(value) => value.replace('\'', "''"),
+                        disintegrate::IdentifierValue::i64(value) => value.to_string(),
+                        disintegrate::IdentifierValue::Uuid(value) => value.to_string(),
+                    };
+                    write!(
+                        self.builder,
+                        " AND ({field} IS NULL OR {field} = '{value}')"
+                    )
+                    .unwrap();
+                }
+            }
+
             // Close filter group
             self.builder.push(')');
             if filters.peek().is_some() {
@@ -116,12 +212,76 @@ where
     }
 }
 
+/// Writes the SQL literal of an identifier value, escaping the quotes of the strings.
+fn write_value(builder: &mut String, value: &disintegrate::IdentifierValue) {
+    match value {
+        disintegrate::IdentifierValue::String(value) => {
+            write!(builder, "'{}'", value.replace('\'', "''")).unwrap();
+        }
+        disintegrate::IdentifierValue::i64(value) => {
+            write!(builder, "{}", value).unwrap();
+        }
+        disintegrate::IdentifierValue::Uuid(value) => {
+            write!(builder, "'{}'", value).unwrap();
+        }
+    };
+}
+
+/// Builds the SQL statement that fetches a page of the events of a stream query.
+///
+/// The statement binds the ID after which the page starts as `$1` and the page size as `$2`. A query made
+/// of several filters, such as the query of a multi-state, is executed as a `UNION ALL` of one branch per
+/// filter, so that each branch can use the indexes of its own identifiers instead of scanning the table
+/// for a disjunction of all of them. The branches select only the IDs, and the payloads of the page are
+/// fetched once. Each branch is limited to the page size, which bounds the union while still including
+/// the first page of its events.
+pub fn stream_sql<QE>(
+    query: &StreamQuery<PgEventId, QE>,
+    epoch: PgEventId,
+    criteria: &str,
+) -> String
+where
+    QE: Event + Clone,
+{
+    if query.filters().len() < 2 {
+        return format!(
+            "SELECT event_id, payload FROM event WHERE event_id <= {epoch} AND event_id > $1 AND ({}){criteria} ORDER BY event_id ASC LIMIT $2",
+            CriteriaBuilder::new(query).build()
+        );
+    }
+    let branches = query
+        .filters()
+        .iter()
+        .map(|filter| {
+            let filter_query = disintegrate::query::<PgEventId, QE, QE>(Some(filter.clone()));
+            format!(
+                "(SELECT event_id FROM event WHERE event_id <= {epoch} AND event_id > $1 AND ({}){criteria} ORDER BY event_id ASC LIMIT $2)",
+                CriteriaBuilder::new(&filter_query).build()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    format!(
+        "SELECT event_id, payload FROM event WHERE event_id IN ({branches}) ORDER BY event_id ASC LIMIT $2"
+    )
+}
+
+/// Returns the SQL expression of a field of the JSON payload.
+///
+/// The field is looked up in the object of the event variant, as serialized for externally tagged enums,
+/// and then at the top level of the payload.
+fn payload_field(field: &str) -> String {
+    format!(
+        "COALESCE(convert_from(payload, 'UTF8')::jsonb -> event_type ->> '{field}', convert_from(payload, 'UTF8')::jsonb ->> '{field}')"
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use disintegrate::{
-        domain_identifiers, event_types, ident, query, DomainIdentifierInfo, DomainIdentifierSet,
-        Event, EventInfo, EventSchema, IdentifierType,
+        any_of, domain_identifiers, event_types, ident, query, DomainIdentifierInfo,
+        DomainIdentifierSet, Event, EventInfo, EventSchema, IdentifierType,
     };
 
     #[allow(dead_code)]
@@ -186,6 +346,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_escapes_the_quotes_of_the_identifier_values() {
+        let query = query!(TestEvent; foo_id == "it's")
+            .filter_in(ident!(#bar_id), ["o'hare", "x' OR '1'='1"]);
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar' AND bar_id IN ('o''hare', 'x'' OR ''1''=''1')) OR (event_type = 'Foo' AND foo_id = 'it''s'))"
+        );
+    }
+
     #[test]
     fn it_builds_criteria_with_two_ids() {
         let query = query!(TestEvent; foo_id == "value", bar_id == "value2");
@@ -208,6 +380,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_builds_criteria_between_two_positions() {
+        let query = query!(TestEvent; foo_id == "value").between(10, 20);
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "(event_id > 10 AND event_id <= 20 AND ((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id = 'value')))"
+        );
+    }
+
     #[test]
     fn it_builds_criteria_with_union() {
         let query: StreamQuery<PgEventId, TestEvent> =
@@ -220,6 +403,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_builds_a_union_of_the_filters_of_a_stream() {
+        let query: StreamQuery<PgEventId, TestEvent> =
+            query!(TestEvent; bar_id == "value1").union(&query!(TestEvent; foo_id == "value2"));
+
+        assert_eq!(
+            stream_sql(&query, 42, ""),
+            "SELECT event_id, payload FROM event WHERE event_id IN (\
+            (SELECT event_id FROM event WHERE event_id <= 42 AND event_id > $1 AND (((event_type = 'Bar' AND bar_id = 'value1') OR (event_type = 'Foo'))) ORDER BY event_id ASC LIMIT $2) \
+            UNION ALL \
+            (SELECT event_id FROM event WHERE event_id <= 42 AND event_id > $1 AND (((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id = 'value2'))) ORDER BY event_id ASC LIMIT $2)\
+            ) ORDER BY event_id ASC LIMIT $2"
+        );
+    }
+
     #[test]
     fn it_builds_criteria_with_excluded_events() {
         let query =
@@ -228,4 +426,83 @@ mod tests {
 
         assert_eq!(criteria_builder.build(), r#"((event_type = 'Foo'))"#);
     }
+
+    #[test]
+    fn it_builds_criteria_with_excluded_identifiers() {
+        let query =
+            query!(TestEvent; bar_id == "value1").exclude_identifier(ident!(#foo_id), "value2");
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar' AND bar_id = 'value1') OR (event_type = 'Foo' AND foo_id <> 'value2'))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_with_a_disjunction() {
+        let query = query!(TestEvent).filter_any(any_of!(foo_id == "value1", bar_id == "value2"));
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar' AND (bar_id = 'value2')) OR (event_type = 'Foo' AND (foo_id = 'value1')))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_with_an_in_list() {
+        let query = query!(TestEvent).filter_in(ident!(#foo_id), ["value1", "value2"]);
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id IN ('value1', 'value2')))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_with_an_empty_in_list() {
+        let query = query!(TestEvent).filter_in(ident!(#foo_id), Vec::<String>::new());
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar') OR (event_type = 'Foo' AND FALSE))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_with_a_range() {
+        let query = query!(TestEvent).filter_range(ident!(#foo_id), "a"..="m");
+        let criteria_builder = CriteriaBuilder::new(&query);
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id >= 'a' AND foo_id <= 'm'))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_without_the_payload_filters() {
+        let query = query!(TestEvent; foo_id == "value").filter_payload(ident!(#zone), "it's");
+        let criteria_builder = CriteriaBuilder::new(&query).without_payload_filters();
+
+        assert_eq!(
+            criteria_builder.build(),
+            "((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id = 'value'))"
+        );
+    }
+
+    #[test]
+    fn it_builds_criteria_with_a_payload_filter() {
+        let query = query!(TestEvent; foo_id == "value").filter_payload(ident!(#zone), "it's");
+        let criteria_builder = CriteriaBuilder::new(&query);
+        let zone = payload_field("zone");
+
+        assert_eq!(
+            criteria_builder.build(),
+            format!("(((event_type = 'Bar') OR (event_type = 'Foo' AND foo_id = 'value')) AND ({zone} IS NULL OR {zone} = 'it''s'))")
+        );
+    }
 }
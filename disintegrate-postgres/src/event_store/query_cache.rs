@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use disintegrate::{DomainIdentifierSet, Event, PersistedEvent, StreamQuery};
+
+use crate::PgEventId;
+
+/// A cached row of a stream, made of the event ID and the serialized payload.
+pub(crate) type CachedRow = (PgEventId, Vec<u8>);
+
+/// The filter of a cached query, used to find the entries invalidated by the appended events.
+///
+/// Only the event types and the identifiers of the filter are checked, so an entry may be invalidated by
+/// an event that does not match its query, but never kept if the event matches it.
+struct CachedFilter {
+    events: &'static [&'static str],
+    excluded_events: Vec<&'static str>,
+    identifiers: DomainIdentifierSet,
+    until: Option<PgEventId>,
+}
+
+impl CachedFilter {
+    fn matches<E: Event>(&self, event: &PersistedEvent<PgEventId, E>) -> bool {
+        let domain_identifiers = event.domain_identifiers();
+        self.events.contains(&event.name())
+            && !self.excluded_events.contains(&event.name())
+            && self
+                .identifiers
+                .iter()
+                .all(|(ident, value)| domain_identifiers.get(ident) == Some(value))
+            && self.until.is_none_or(|until| event.id() <= until)
+    }
+}
+
+struct CachedQuery {
+    filters: Vec<CachedFilter>,
+    rows: Arc<[CachedRow]>,
+    cached_at: Instant,
+    used_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CachedQuery>,
+    /// Incremented at every invalidation, so a stream that raced with an append does not cache its result.
+    generation: u64,
+}
+
+/// The results of the recent stream queries, keyed by the SQL criteria of the query.
+pub(crate) struct QueryCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns the cached rows of the query, unless they expired.
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<[CachedRow]>> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.get_mut(key)?;
+        if entry.cached_at.elapsed() > self.ttl {
+            state.entries.remove(key);
+            return None;
+        }
+        entry.used_at = Instant::now();
+        Some(entry.rows.clone())
+    }
+
+    /// Returns the current generation, to be passed to `insert` once the query is streamed.
+    pub(crate) fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    /// Caches the rows of the query, unless an append invalidated the cache since `generation`.
+    ///
+    /// The least recently used entry is evicted when the cache is full.
+    pub(crate) fn insert<QE>(
+        &self,
+        key: String,
+        query: &StreamQuery<PgEventId, QE>,
+        rows: Vec<CachedRow>,
+        generation: u64,
+    ) where
+        QE: Event + Clone,
+    {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            let least_recently_used = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used_at)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recently_used) = least_recently_used {
+                state.entries.remove(&least_recently_used);
+            }
+        }
+        let filters = query
+            .filters()
+            .iter()
+            .map(|filter| CachedFilter {
+                events: filter.events(),
+                excluded_events: filter.excluded_events().cloned().unwrap_or_default(),
+                identifiers: filter.identifiers().clone(),
+                until: filter.until(),
+            })
+            .collect();
+        let now = Instant::now();
+        state.entries.insert(
+            key,
+            CachedQuery {
+                filters,
+                rows: rows.into(),
+                cached_at: now,
+                used_at: now,
+            },
+        );
+    }
+
+    /// Drops all the entries, e.g. when events are removed from the event log.
+    pub(crate) fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.clear();
+    }
+
+    /// Drops the entries whose query may match one of the events.
+    pub(crate) fn invalidate<E: Event>(&self, events: &[PersistedEvent<PgEventId, E>]) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.retain(|_, entry| {
+            !entry
+                .filters
+                .iter()
+                .any(|filter| events.iter().any(|event| filter.matches(event)))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{
+        domain_identifiers, ident, query, DomainIdentifierInfo, EventInfo, EventSchema,
+        IdentifierType,
+    };
+
+    #[derive(Clone)]
+    struct CartEvent {
+        cart_id: String,
+    }
+
+    impl Event for CartEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["ItemAdded"],
+            events_info: &[&EventInfo {
+                name: "ItemAdded",
+                domain_identifiers: &[&ident!(#cart_id)],
+            }],
+            domain_identifiers: &[&DomainIdentifierInfo {
+                ident: ident!(#cart_id),
+                type_info: IdentifierType::String,
+            }],
+        };
+
+        fn name(&self) -> &'static str {
+            "ItemAdded"
+        }
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {cart_id: self.cart_id}
+        }
+    }
+
+    fn item_added(id: PgEventId, cart_id: &str) -> PersistedEvent<PgEventId, CartEvent> {
+        PersistedEvent::new(
+            id,
+            CartEvent {
+                cart_id: cart_id.to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn it_invalidates_the_queries_matching_the_appended_events() {
+        let cache = QueryCache::new(10, Duration::from_secs(60));
+        let generation = cache.generation();
+        cache.insert(
+            "c1".to_string(),
+            &query!(CartEvent; cart_id == "c1"),
+            vec![(1, b"{}".to_vec())],
+            generation,
+        );
+        cache.insert(
+            "c2".to_string(),
+            &query!(CartEvent; cart_id == "c2"),
+            vec![(2, b"{}".to_vec())],
+            generation,
+        );
+
+        cache.invalidate(&[item_added(3, "c1")]);
+
+        assert!(cache.get("c1").is_none());
+        assert_eq!(cache.get("c2").unwrap().len(), 1);
+        cache.insert(
+            "c1".to_string(),
+            &query!(CartEvent; cart_id == "c1"),
+            vec![],
+            generation,
+        );
+        assert!(cache.get("c1").is_none());
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use disintegrate::{Event, Identifier};
+
+use crate::PgEventId;
+
+/// How long the events of a type are kept, see `RetentionPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventRetention {
+    /// Keeps the events forever, which is the default of the event types without a rule.
+    KeepForever,
+    /// Keeps the events appended within the given duration.
+    MaxAge(Duration),
+    /// Keeps the given number of most recent events of each value of the domain identifier.
+    KeepLatest {
+        identifier: Identifier,
+        count: usize,
+    },
+}
+
+impl EventRetention {
+    /// Keeps the events appended within the given number of years, of 365 days each.
+    pub fn years(years: u64) -> Self {
+        Self::MaxAge(Duration::from_secs(years * 365 * 24 * 60 * 60))
+    }
+
+    /// Keeps the given number of most recent events of each value of the domain identifier.
+    pub fn keep_latest(identifier: Identifier, count: usize) -> Self {
+        Self::KeepLatest { identifier, count }
+    }
+}
+
+/// The retention rules of the event types, applied by `PgEventStore::apply_retention`.
+///
+/// The expired events are moved to the `event_archive` table by default, or deleted with `with_deletion`.
+/// The event types without a rule are kept forever.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let policy = RetentionPolicy::default()
+///     .retain::<DomainEvent>("LoginAttempted", EventRetention::MaxAge(Duration::from_secs(90 * 86400)))
+///     .retain::<DomainEvent>("AddressChanged", EventRetention::keep_latest(ident!(#customer_id), 1))
+///     .retain::<DomainEvent>("InvoiceIssued", EventRetention::years(10));
+/// let report = event_store.apply_retention(&policy).await?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    rules: BTreeMap<&'static str, EventRetention>,
+    delete: bool,
+}
+
+impl RetentionPolicy {
+    /// Sets the retention of the event type of `E` named `event_type`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `E` has no event type named `event_type`, or if the event type does not have the domain
+    /// identifier of a `KeepLatest` retention.
+    pub fn retain<E: Event>(mut self, event_type: &str, retention: EventRetention) -> Self {
+        let info = E::SCHEMA
+            .events_info
+            .iter()
+            .find(|info| info.name == event_type)
+            .unwrap_or_else(|| panic!("unknown event {event_type}"));
+        if let EventRetention::KeepLatest { identifier, .. } = retention {
+            assert!(
+                info.domain_identifiers.contains(&&identifier),
+                "event {event_type} has no domain identifier {identifier}"
+            );
+        }
+        self.rules.insert(info.name, retention);
+        self
+    }
+
+    /// Deletes the expired events instead of archiving them.
+    pub fn with_deletion(mut self) -> Self {
+        self.delete = true;
+        self
+    }
+
+    /// Returns, for each event type with an expiring retention, the statement that removes its expired
+    /// events and returns their IDs.
+    pub(crate) fn statements(&self) -> Vec<(&'static str, String)> {
+        self.rules
+            .iter()
+            .filter_map(|(event_type, retention)| {
+                let expired = expired_events_sql(event_type, retention)?;
+                let sql = if self.delete {
+                    format!("DELETE FROM event WHERE event_id IN ({expired}) RETURNING event_id")
+                } else {
+                    format!(
+                        "WITH expired AS (DELETE FROM event WHERE event_id IN ({expired}) RETURNING event_id, event_type, payload, inserted_at), \
+                         archived AS (INSERT INTO event_archive (event_id, event_type, payload, inserted_at) \
+                         SELECT event_id, event_type, payload, inserted_at FROM expired) \
+                         SELECT event_id FROM expired"
+                    )
+                };
+                Some((*event_type, sql))
+            })
+            .collect()
+    }
+}
+
+/// Selects the committed events of the type that are not retained.
+fn expired_events_sql(event_type: &str, retention: &EventRetention) -> Option<String> {
+    match retention {
+        EventRetention::KeepForever => None,
+        EventRetention::MaxAge(age) => Some(format!(
+            "SELECT event_id FROM event WHERE event_type = '{event_type}' \
+             AND inserted_at < now() - make_interval(secs => {}) AND event_id <= event_store_current_epoch()",
+            age.as_secs_f64()
+        )),
+        EventRetention::KeepLatest { identifier, count } => Some(format!(
+            "SELECT event_id FROM (SELECT event_id, row_number() OVER (PARTITION BY {identifier} ORDER BY event_id DESC) AS position \
+             FROM event WHERE event_type = '{event_type}' AND event_id <= event_store_current_epoch()) ranked WHERE position > {count}"
+        )),
+    }
+}
+
+/// The result of `PgEventStore::apply_retention`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    /// The events removed from the event log, archived or deleted according to the policy.
+    pub expired_events: Vec<PgEventId>,
+    /// The number of snapshots deleted because they were built from an expired event.
+    pub invalidated_snapshots: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{domain_identifiers, ident, DomainIdentifierSet, EventInfo, EventSchema};
+
+    #[derive(Clone)]
+    struct CustomerEvent;
+
+    impl Event for CustomerEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["AddressChanged", "CustomerRegistered"],
+            events_info: &[
+                &EventInfo {
+                    name: "AddressChanged",
+                    domain_identifiers: &[&ident!(#customer_id)],
+                },
+                &EventInfo {
+                    name: "CustomerRegistered",
+                    domain_identifiers: &[&ident!(#customer_id)],
+                },
+            ],
+            domain_identifiers: &[],
+        };
+
+        fn name(&self) -> &'static str {
+            "AddressChanged"
+        }
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {}
+        }
+    }
+
+    #[test]
+    fn it_builds_the_statements_of_the_expiring_rules() {
+        let policy = RetentionPolicy::default()
+            .retain::<CustomerEvent>(
+                "AddressChanged",
+                EventRetention::keep_latest(ident!(#customer_id), 2),
+            )
+            .retain::<CustomerEvent>("CustomerRegistered", EventRetention::KeepForever)
+            .with_deletion();
+
+        assert_eq!(
+            policy.statements(),
+            vec![(
+                "AddressChanged",
+                "DELETE FROM event WHERE event_id IN (SELECT event_id FROM (SELECT event_id, row_number() OVER (PARTITION BY customer_id ORDER BY event_id DESC) AS position \
+                 FROM event WHERE event_type = 'AddressChanged' AND event_id <= event_store_current_epoch()) ranked WHERE position > 2) RETURNING event_id"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "event AddressChanged has no domain identifier order_id")]
+    fn it_rejects_the_identifiers_missing_from_the_event() {
+        RetentionPolicy::default().retain::<CustomerEvent>(
+            "AddressChanged",
+            EventRetention::keep_latest(ident!(#order_id), 1),
+        );
+    }
+}
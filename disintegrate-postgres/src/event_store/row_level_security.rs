@@ -0,0 +1,118 @@
+use disintegrate::Identifier;
+
+/// The tables of the event store isolated by the row-level security policies.
+const TABLES: &[&str] = &["event", "event_sequence"];
+
+/// The functions of the event store that must see the events of every tenant.
+const EPOCH_FUNCTIONS: &[&str] = &["event_store_current_epoch()", "event_store_begin_epoch()"];
+
+/// The row-level security policies isolating the events of the tenants, applied by
+/// `PgEventStore::enable_row_level_security`.
+///
+/// The events are isolated by a domain identifier, compared with a setting of the transaction that the event
+/// store sets to its tenant, see `PgEventStore::with_tenant`. The epoch functions run with the rights of their
+/// owner, so the global ordering of the events is preserved across the tenants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowLevelSecurity {
+    tenant_identifier: Identifier,
+    setting: String,
+}
+
+impl RowLevelSecurity {
+    /// Isolates the events by the value of the `tenant_identifier` domain identifier.
+    pub fn new(tenant_identifier: Identifier) -> Self {
+        Self {
+            tenant_identifier,
+            setting: "disintegrate.tenant".to_string(),
+        }
+    }
+
+    /// Sets the name of the setting that holds the tenant of a transaction, `disintegrate.tenant` by default.
+    ///
+    /// The name must be qualified by a prefix, like any custom setting of PostgreSQL.
+    pub fn with_setting(mut self, setting: &str) -> Self {
+        assert!(
+            setting.contains('.')
+                && setting
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+            "setting must be a qualified name, such as `app.tenant`"
+        );
+        self.setting = setting.to_string();
+        self
+    }
+
+    /// Returns the domain identifier isolating the events.
+    pub fn tenant_identifier(&self) -> Identifier {
+        self.tenant_identifier
+    }
+
+    /// Returns the name of the setting that holds the tenant of a transaction.
+    pub fn setting(&self) -> &str {
+        &self.setting
+    }
+
+    /// Returns the statements that create the policies, given the SQL type of the tenant identifier.
+    pub(crate) fn statements(&self, sql_type: &str) -> Vec<String> {
+        let column = self.tenant_identifier;
+        let tenant = format!(
+            "{column} = NULLIF(current_setting('{}', true), '')::{sql_type}",
+            self.setting
+        );
+        let mut statements = vec![];
+        for table in TABLES {
+            statements.push(format!("ALTER TABLE {table} ENABLE ROW LEVEL SECURITY"));
+            statements.push(format!(
+                "DROP POLICY IF EXISTS {table}_tenant_isolation ON {table}"
+            ));
+            statements.push(format!(
+                "CREATE POLICY {table}_tenant_isolation ON {table} USING ({tenant}) WITH CHECK ({tenant})"
+            ));
+        }
+        for function in EPOCH_FUNCTIONS {
+            statements.push(format!(
+                "ALTER FUNCTION {function} SECURITY DEFINER SET search_path FROM CURRENT"
+            ));
+        }
+        statements
+    }
+}
+
+/// The tenant of the transactions of a scoped event store.
+#[derive(Debug)]
+pub(crate) struct Tenant {
+    pub(crate) setting: String,
+    pub(crate) value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use disintegrate::ident;
+
+    use super::*;
+
+    #[test]
+    fn it_builds_the_statements_of_the_policies() {
+        assert_eq!(
+            RowLevelSecurity::new(ident!(#tenant_id))
+                .with_setting("app.tenant")
+                .statements("UUID"),
+            vec![
+                "ALTER TABLE event ENABLE ROW LEVEL SECURITY",
+                "DROP POLICY IF EXISTS event_tenant_isolation ON event",
+                "CREATE POLICY event_tenant_isolation ON event USING (tenant_id = NULLIF(current_setting('app.tenant', true), '')::UUID) WITH CHECK (tenant_id = NULLIF(current_setting('app.tenant', true), '')::UUID)",
+                "ALTER TABLE event_sequence ENABLE ROW LEVEL SECURITY",
+                "DROP POLICY IF EXISTS event_sequence_tenant_isolation ON event_sequence",
+                "CREATE POLICY event_sequence_tenant_isolation ON event_sequence USING (tenant_id = NULLIF(current_setting('app.tenant', true), '')::UUID) WITH CHECK (tenant_id = NULLIF(current_setting('app.tenant', true), '')::UUID)",
+                "ALTER FUNCTION event_store_current_epoch() SECURITY DEFINER SET search_path FROM CURRENT",
+                "ALTER FUNCTION event_store_begin_epoch() SECURITY DEFINER SET search_path FROM CURRENT",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_an_unqualified_setting() {
+        RowLevelSecurity::new(ident!(#tenant_id)).with_setting("tenant");
+    }
+}
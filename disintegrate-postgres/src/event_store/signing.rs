@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use disintegrate::signing::{self, SignatureVerifier, Signer};
+use disintegrate::{Event, PersistedEvent};
+use disintegrate_serde::Serde;
+use sqlx::Row;
+
+use crate::{Error, PgEventId, PgEventStore};
+
+/// Returns the message signed for an event.
+///
+/// The message covers the ID, the type and the serialized payload of the event, so a signature cannot be
+/// moved to another event.
+pub(crate) fn signed_message(event_id: PgEventId, event_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(16 + event_type.len() + payload.len());
+    message.extend(event_id.to_be_bytes());
+    message.extend((event_type.len() as u64).to_be_bytes());
+    message.extend(event_type.as_bytes());
+    message.extend(payload);
+    message
+}
+
+/// Signs the events, returning one signature for each event.
+pub(crate) fn sign<E, S>(
+    signer: &dyn Signer,
+    events: &[PersistedEvent<PgEventId, E>],
+    serde: &S,
+) -> Result<Vec<Vec<u8>>, Error>
+where
+    E: Event + Clone,
+    S: Serde<E>,
+{
+    events
+        .iter()
+        .map(|event| {
+            let payload = serde.serialize(event.clone().into_inner());
+            signing::sign(signer, &signed_message(event.id(), event.name(), &payload)).map_err(
+                |source| Error::Signature {
+                    event_id: event.id(),
+                    source,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Verifies the signatures of the events, given their IDs and serialized payloads.
+pub(crate) async fn verify<E, S>(
+    event_store: &PgEventStore<E, S>,
+    verifier: &dyn SignatureVerifier,
+    events: &[(PgEventId, &[u8])],
+) -> Result<(), Error>
+where
+    E: Event,
+    S: Serde<E> + Send + Sync,
+{
+    let event_ids: Vec<PgEventId> = events.iter().map(|(event_id, _)| *event_id).collect();
+    let signatures: HashMap<PgEventId, (String, Option<Vec<u8>>)> = event_store
+        .fetch_all(
+            sqlx::query(
+                "SELECT event_id, event_type, signature FROM event WHERE event_id = ANY($1)",
+            )
+            .bind(&event_ids),
+        )
+        .await?
+        .iter()
+        .map(|row| Ok((row.try_get(0)?, (row.try_get(1)?, row.try_get(2)?))))
+        .collect::<Result<_, sqlx::Error>>()?;
+    for (event_id, payload) in events {
+        match signatures.get(event_id) {
+            Some((event_type, Some(signature))) => signing::verify(
+                verifier,
+                &signed_message(*event_id, event_type, payload),
+                signature,
+            ),
+            _ => Err(signing::Error::MissingSignature),
+        }
+        .map_err(|source| Error::Signature {
+            event_id: *event_id,
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disintegrate::{Ed25519Signer, Ed25519Verifier};
+
+    #[test]
+    fn it_binds_the_signature_to_the_event() {
+        let signer = Ed25519Signer::new("k1", [1; 32]);
+        let verifier = Ed25519Verifier::default().with_key("k1", signer.verifying_key());
+        let signature = signing::sign(&signer, &signed_message(1, "Added", b"{}")).unwrap();
+
+        assert!(signing::verify(&verifier, &signed_message(1, "Added", b"{}"), &signature).is_ok());
+        assert!(
+            signing::verify(&verifier, &signed_message(2, "Added", b"{}"), &signature).is_err()
+        );
+        assert!(
+            signing::verify(&verifier, &signed_message(1, "Removed", b"{}"), &signature).is_err()
+        );
+    }
+}
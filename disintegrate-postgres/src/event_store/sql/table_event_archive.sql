@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS event_archive (
+    event_id bigint PRIMARY KEY,
+    event_type varchar(255),
+    payload bytea,
+    inserted_at TIMESTAMP,
+    archived_at TIMESTAMP DEFAULT now()
+);
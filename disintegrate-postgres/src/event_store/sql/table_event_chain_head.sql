@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS event_chain_head (
+    id boolean PRIMARY KEY DEFAULT true CHECK (id),
+    event_id bigint NOT NULL,
+    hash bytea NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS event_data_key (
+    key_id text PRIMARY KEY,
+    master_key_id text NOT NULL,
+    wrapped_key bytea NOT NULL,
+    inserted_at TIMESTAMP DEFAULT now(),
+    rewrapped_at TIMESTAMP
+);
@@ -0,0 +1,145 @@
+/// The storage options of the `event` table, applied by `PgEventStore::tune_storage`.
+///
+/// The defaults leave the table as created by the event store. `append_only` returns the options tuned for
+/// a large, append-only event log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageOptions {
+    brin_event_id: bool,
+    brin_inserted_at: bool,
+    brin_pages_per_range: Option<u32>,
+    fillfactor: Option<u8>,
+    autovacuum_vacuum_scale_factor: Option<f64>,
+    autovacuum_vacuum_insert_scale_factor: Option<f64>,
+    autovacuum_analyze_scale_factor: Option<f64>,
+}
+
+impl StorageOptions {
+    /// Returns the options tuned for an append-only event log.
+    ///
+    /// The events are never updated, so the pages are filled completely, and the insertion time is indexed
+    /// with BRIN, which stays tiny on huge tables since the events are stored in insertion order. The
+    /// autovacuum runs after a fixed share of new rows instead of the default 20%, which is too seldom once
+    /// the table holds millions of events.
+    pub fn append_only() -> Self {
+        Self::default()
+            .with_brin_inserted_at()
+            .with_fillfactor(100)
+            .with_autovacuum_vacuum_scale_factor(0.01)
+            .with_autovacuum_vacuum_insert_scale_factor(0.01)
+            .with_autovacuum_analyze_scale_factor(0.005)
+    }
+
+    /// Creates a BRIN index on the `event_id` column, for the range scans of the event IDs.
+    pub fn with_brin_event_id(mut self) -> Self {
+        self.brin_event_id = true;
+        self
+    }
+
+    /// Creates a BRIN index on the `inserted_at` column, for the lookups by time such as `event_id_at`.
+    pub fn with_brin_inserted_at(mut self) -> Self {
+        self.brin_inserted_at = true;
+        self
+    }
+
+    /// Sets the number of table pages summarized by each entry of the BRIN indexes.
+    ///
+    /// It only applies to the BRIN indexes created by `tune_storage`, not to the existing ones.
+    pub fn with_brin_pages_per_range(mut self, pages_per_range: u32) -> Self {
+        assert!(
+            pages_per_range > 0,
+            "pages per range must be greater than 0"
+        );
+        self.brin_pages_per_range = Some(pages_per_range);
+        self
+    }
+
+    /// Sets the percentage of each table page filled by the inserts.
+    pub fn with_fillfactor(mut self, fillfactor: u8) -> Self {
+        assert!(
+            (10..=100).contains(&fillfactor),
+            "fillfactor must be between 10 and 100"
+        );
+        self.fillfactor = Some(fillfactor);
+        self
+    }
+
+    /// Sets the share of updated or deleted rows that triggers the autovacuum of the table.
+    pub fn with_autovacuum_vacuum_scale_factor(mut self, scale_factor: f64) -> Self {
+        self.autovacuum_vacuum_scale_factor = Some(scale_factor);
+        self
+    }
+
+    /// Sets the share of inserted rows that triggers the autovacuum of the table, which keeps the
+    /// visibility map of an append-only table current.
+    pub fn with_autovacuum_vacuum_insert_scale_factor(mut self, scale_factor: f64) -> Self {
+        self.autovacuum_vacuum_insert_scale_factor = Some(scale_factor);
+        self
+    }
+
+    /// Sets the share of changed rows that triggers the analysis of the table.
+    pub fn with_autovacuum_analyze_scale_factor(mut self, scale_factor: f64) -> Self {
+        self.autovacuum_analyze_scale_factor = Some(scale_factor);
+        self
+    }
+
+    /// Returns the statements that apply the options.
+    pub(crate) fn statements(&self) -> Vec<String> {
+        let mut statements = vec![];
+        let with = self
+            .brin_pages_per_range
+            .map(|pages_per_range| format!(" WITH (pages_per_range = {pages_per_range})"))
+            .unwrap_or_default();
+        for (enabled, column) in [
+            (self.brin_event_id, "event_id"),
+            (self.brin_inserted_at, "inserted_at"),
+        ] {
+            if enabled {
+                statements.push(format!(
+                    "CREATE INDEX CONCURRENTLY IF NOT EXISTS idx_event_{column}_brin ON event USING BRIN ({column}){with}"
+                ));
+            }
+        }
+        let parameters: Vec<String> = [
+            ("fillfactor", self.fillfactor.map(|f| f.to_string())),
+            (
+                "autovacuum_vacuum_scale_factor",
+                self.autovacuum_vacuum_scale_factor.map(|f| f.to_string()),
+            ),
+            (
+                "autovacuum_vacuum_insert_scale_factor",
+                self.autovacuum_vacuum_insert_scale_factor
+                    .map(|f| f.to_string()),
+            ),
+            (
+                "autovacuum_analyze_scale_factor",
+                self.autovacuum_analyze_scale_factor.map(|f| f.to_string()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{name} = {value}")))
+        .collect();
+        if !parameters.is_empty() {
+            statements.push(format!("ALTER TABLE event SET ({})", parameters.join(", ")));
+        }
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_statements_of_the_storage_options() {
+        assert!(StorageOptions::default().statements().is_empty());
+        assert_eq!(
+            StorageOptions::append_only()
+                .with_brin_pages_per_range(32)
+                .statements(),
+            vec![
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS idx_event_inserted_at_brin ON event USING BRIN (inserted_at) WITH (pages_per_range = 32)",
+                "ALTER TABLE event SET (fillfactor = 100, autovacuum_vacuum_scale_factor = 0.01, autovacuum_vacuum_insert_scale_factor = 0.01, autovacuum_analyze_scale_factor = 0.005)",
+            ]
+        );
+    }
+}
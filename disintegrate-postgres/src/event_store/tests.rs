@@ -1,8 +1,12 @@
 use super::append::{InsertEventSequenceBuilder, InsertEventsBuilder};
-use crate::{Error, PgEventId, PgEventStore};
+use crate::{
+    AnonymizationReport, Error, EventRetention, IndexAdvisor, IntegrityReport, PgEventId,
+    PgEventStore, RepairReport, RetentionPolicy, RetentionReport, RowLevelSecurity, StorageOptions,
+};
 use disintegrate::{
-    domain_identifiers, ident, query, DomainIdentifierInfo, DomainIdentifierSet, Event, EventInfo,
-    EventSchema, EventStore, IdentifierType, PersistedEvent,
+    any_of, domain_identifiers, ident, query, snapshot_key, Actor, DomainIdentifierInfo,
+    DomainIdentifierSet, Event, EventInfo, EventSchema, EventStore, HydrationWindow,
+    IdentifierType, PersistedEvent, ReadOptions, WithActor,
 };
 use disintegrate_serde::serde::json::Json;
 use disintegrate_serde::Deserializer;
@@ -11,6 +15,7 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
+use std::time::{Duration, UNIX_EPOCH};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "event_type", rename_all = "snake_case")]
@@ -98,7 +103,543 @@ async fn it_queries_events(pool: PgPool) {
     let query = query!(ShoppingCartEvent; product_id == "product_1");
     let result = event_store.stream(&query).collect::<Vec<_>>().await;
 
-    assert_eq!(result.len(), 2);
+    assert_eq!(result.len(), 2);
+}
+
+#[sqlx::test]
+async fn it_streams_events_in_pages(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_stream_page_size(2);
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        removed_event("product_1", "cart_1"),
+        added_event("product_2", "cart_1"),
+        added_event("product_2", "cart_2"),
+        added_event("product_3", "cart_1"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1");
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![1, 2, 3, 5]);
+}
+
+#[sqlx::test]
+async fn it_queries_events_matching_any_of_the_identifiers(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        removed_event("product_1", "cart_1"),
+        added_event("product_2", "cart_1"),
+        added_event("product_2", "cart_2"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent)
+        .filter_any(any_of!(product_id == "product_1", cart_id == "cart_2"));
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![1, 2, 4]);
+}
+
+#[sqlx::test]
+async fn it_queries_events_with_an_identifier_in_a_list(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        added_event("product_1", "cart_2"),
+        added_event("product_1", "cart_3"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent).filter_in(ident!(#cart_id), ["cart_1", "cart_3"]);
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![1, 3]);
+}
+
+#[sqlx::test]
+async fn it_filters_events_on_payload_fields(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        removed_event("product_1", "cart_1"),
+        added_event("product_2", "cart_1"),
+        added_event("product_2", "cart_2"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1")
+        .filter_payload(ident!(#product_id), "product_2");
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![3]);
+}
+
+#[sqlx::test]
+async fn it_appends_events_validated_by_a_query_with_payload_filters(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    insert_events(&pool, &[added_event("product_1", "cart_1")]).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1")
+        .filter_payload(ident!(#product_id), "product_1");
+    let appended = event_store
+        .append(vec![removed_event("product_1", "cart_1")], query.clone(), 1)
+        .await
+        .unwrap();
+
+    assert_eq!(appended.len(), 1);
+    let result = event_store
+        .append(vec![removed_event("product_1", "cart_1")], query, 1)
+        .await;
+    assert!(matches!(result, Err(Error::Concurrency)));
+}
+
+#[sqlx::test]
+async fn it_excludes_events_and_identifier_values(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        removed_event("product_1", "cart_1"),
+        added_event("product_2", "cart_1"),
+        added_event("product_3", "cart_1"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1")
+        .exclude_events(&["ShoppingCartRemoved"])
+        .exclude_identifier(ident!(#product_id), "product_2");
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![1, 4]);
+}
+
+#[sqlx::test]
+async fn it_searches_the_text_of_the_payload_fields(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    insert_events(&pool, &[added_event("red-shoes", "cart_1")]).await;
+    event_store
+        .enable_search(&[ident!(#product_id)])
+        .await
+        .unwrap();
+    insert_events(
+        &pool,
+        &[
+            added_event("blue-shoes", "cart_1"),
+            added_event("red-hat", "cart_2"),
+        ],
+    )
+    .await;
+
+    let query = query!(ShoppingCartEvent);
+    let found = |events: Vec<PersistedEvent<PgEventId, ShoppingCartEvent>>| {
+        events.iter().map(|event| event.id()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        found(event_store.search("red", &query).await.unwrap()),
+        vec![1, 3]
+    );
+    assert_eq!(
+        found(event_store.search("shoes -blue", &query).await.unwrap()),
+        vec![1]
+    );
+    assert_eq!(
+        found(
+            event_store
+                .search("red", &query!(ShoppingCartEvent; cart_id == "cart_2"))
+                .await
+                .unwrap()
+        ),
+        vec![3]
+    );
+
+    event_store
+        .enable_search(&[ident!(#product_id)])
+        .await
+        .unwrap();
+    assert_eq!(
+        found(event_store.search("red", &query).await.unwrap()),
+        vec![1, 3]
+    );
+
+    event_store
+        .enable_search(&[ident!(#cart_id)])
+        .await
+        .unwrap();
+    assert_eq!(
+        found(event_store.search("cart_1", &query).await.unwrap()),
+        vec![1, 2]
+    );
+    assert!(event_store.search("red", &query).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn it_explains_the_stream_of_a_query(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let plan = event_store
+        .explain(
+            &query!(ShoppingCartEvent; cart_id == "cart_1")
+                .filter_range(ident!(#product_id), "a".."m"),
+        )
+        .await
+        .unwrap();
+
+    assert!(plan.sql().contains("cart_id = 'cart_1'"));
+    assert!(plan.plan().contains("event"));
+    assert_eq!(
+        plan.warnings(),
+        ["the range filter on `product_id` cannot use an index, create a btree index on the column"]
+    );
+}
+
+#[sqlx::test]
+async fn it_creates_the_indexes_recommended_for_the_queries(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let query =
+        query!(ShoppingCartEvent; cart_id == "cart_1").filter_range(ident!(#product_id), "a".."m");
+    let advisor = IndexAdvisor::default().with_query(&query);
+
+    let created = event_store
+        .create_recommended_indexes(&advisor)
+        .await
+        .unwrap();
+
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].name(), "idx_event_product_id_range");
+    assert!(event_store
+        .advise_indexes(&advisor)
+        .await
+        .unwrap()
+        .is_complete());
+    assert!(event_store
+        .explain(&query)
+        .await
+        .unwrap()
+        .warnings()
+        .is_empty());
+}
+
+#[sqlx::test]
+async fn it_tunes_the_storage_of_the_event_table(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    event_store
+        .tune_storage(&StorageOptions::append_only())
+        .await
+        .unwrap();
+
+    let options: Vec<String> =
+        sqlx::query_scalar("SELECT unnest(reloptions) FROM pg_class WHERE relname = 'event'")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert!(options.contains(&"fillfactor=100".to_string()));
+    let brin_indexes: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pg_indexes WHERE tablename = 'event' AND indexdef LIKE '%USING brin (inserted_at)%'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(brin_indexes, 1);
+}
+
+#[sqlx::test]
+async fn it_computes_scalars_in_the_database(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("10", "cart_1"),
+        added_event("20", "cart_1"),
+        removed_event("10", "cart_1"),
+        added_event("5", "cart_2"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1");
+    let count = event_store.count(&query).await.unwrap();
+    let sum = event_store
+        .sum(
+            &query.exclude_events(&["ShoppingCartRemoved"]),
+            "product_id",
+        )
+        .await
+        .unwrap();
+    let empty = event_store
+        .count(&query!(ShoppingCartEvent; cart_id == "cart_3"))
+        .await
+        .unwrap();
+
+    assert_eq!((count.value(), count.version()), (3, 3));
+    assert_eq!((sum.value(), sum.version()), (30.0, 2));
+    assert_eq!((empty.value(), empty.version()), (0, 0));
+}
+
+#[sqlx::test]
+async fn it_checks_whether_events_exist(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    insert_events(&pool, &[added_event("product_1", "cart_1")]).await;
+
+    assert!(event_store
+        .exists(&query!(ShoppingCartEvent; cart_id == "cart_1"))
+        .await
+        .unwrap());
+    assert!(!event_store
+        .exists(&query!(ShoppingCartEvent; cart_id == "cart_2"))
+        .await
+        .unwrap());
+}
+
+#[sqlx::test]
+async fn it_returns_the_latest_events(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        added_event("product_2", "cart_1"),
+        added_event("product_3", "cart_2"),
+        added_event("product_4", "cart_1"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let latest = event_store
+        .latest(&query!(ShoppingCartEvent; cart_id == "cart_1"), 2)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        latest.iter().map(|event| event.id()).collect::<Vec<_>>(),
+        vec![4, 2]
+    );
+
+    let first = event_store
+        .read(
+            &query!(ShoppingCartEvent; cart_id == "cart_1"),
+            ReadOptions::new().limit(2),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        first.iter().map(|event| event.id()).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+}
+
+#[sqlx::test]
+async fn it_finds_the_origin_of_a_window(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+
+    let events = vec![
+        added_event("product_1", "cart_1"),
+        added_event("product_2", "cart_2"),
+        added_event("product_3", "cart_1"),
+        added_event("product_4", "cart_1"),
+    ];
+    insert_events(&pool, &events).await;
+
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1");
+    let last_two = event_store
+        .window_origin(&query, HydrationWindow::LastEvents(2))
+        .await
+        .unwrap();
+    let last_ten = event_store
+        .window_origin(&query, HydrationWindow::LastEvents(10))
+        .await
+        .unwrap();
+    let last_hour = event_store
+        .window_origin(&query, HydrationWindow::Within(Duration::from_secs(3600)))
+        .await
+        .unwrap();
+
+    assert_eq!(last_two, 1);
+    assert_eq!(last_ten, 0);
+    assert_eq!(last_hour, 0);
+
+    sqlx::query("UPDATE event SET inserted_at = '2024-01-01 00:00:00' WHERE event_id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let last_hour = event_store
+        .window_origin(&query, HydrationWindow::Within(Duration::from_secs(3600)))
+        .await
+        .unwrap();
+
+    assert_eq!(last_hour, 1);
+}
+
+#[sqlx::test]
+async fn it_returns_the_last_event_id_at_a_timestamp(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    insert_events(
+        &pool,
+        &[
+            added_event("product_1", "cart_1"),
+            added_event("product_2", "cart_1"),
+        ],
+    )
+    .await;
+    sqlx::query("UPDATE event SET inserted_at = '2024-01-01 00:00:00' WHERE event_id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE event SET inserted_at = '2024-01-03 00:00:00' WHERE event_id = 2")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let january_second = UNIX_EPOCH + Duration::from_secs(1_704_153_600);
+    assert_eq!(event_store.event_id_at(january_second).await.unwrap(), 1);
+    assert_eq!(event_store.event_id_at(UNIX_EPOCH).await.unwrap(), 0);
+}
+
+#[sqlx::test]
+async fn it_streams_the_events_between_two_timestamps(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    insert_events(
+        &pool,
+        &[
+            added_event("product_1", "cart_1"),
+            added_event("product_2", "cart_1"),
+            added_event("product_3", "cart_1"),
+        ],
+    )
+    .await;
+    for (event_id, inserted_at) in [(1, "2024-01-01"), (2, "2024-01-03"), (3, "2024-01-05")] {
+        sqlx::query("UPDATE event SET inserted_at = $1::timestamp WHERE event_id = $2")
+            .bind(inserted_at)
+            .bind(event_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let january_second = UNIX_EPOCH + Duration::from_secs(1_704_153_600);
+    let january_fourth = UNIX_EPOCH + Duration::from_secs(1_704_326_400);
+    let query = event_store
+        .between(
+            query!(ShoppingCartEvent; cart_id == "cart_1"),
+            january_second,
+            january_fourth,
+        )
+        .await
+        .unwrap();
+    let event_ids: Vec<PgEventId> = event_store
+        .stream(&query)
+        .map(|event| event.unwrap().id())
+        .collect()
+        .await;
+
+    assert_eq!(event_ids, vec![2]);
 }
 
 #[sqlx::test]
@@ -171,6 +712,66 @@ async fn it_appends_unchecked(pool: PgPool) {
     );
 }
 
+#[sqlx::test]
+async fn it_pairs_the_reserved_ids_with_the_events_of_a_batch(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let events: Vec<ShoppingCartEvent> = (0..20)
+        .map(|index| added_event(&format!("product_{index}"), &format!("cart_{}", index % 3)))
+        .collect();
+
+    let persisted_events = event_store
+        .append_without_validation(events.clone())
+        .await
+        .unwrap();
+
+    let event_ids: Vec<PgEventId> = persisted_events.iter().map(|event| event.id()).collect();
+    assert!(event_ids.windows(2).all(|ids| ids[0] < ids[1]));
+    for (persisted_event, event) in persisted_events.iter().zip(&events) {
+        assert_eq!(persisted_event.clone().into_inner(), *event);
+        let row =
+            sqlx::query("SELECT event_id, event_type, payload FROM event WHERE event_id = $1")
+                .bind(persisted_event.id())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_event_row(
+            &row,
+            persisted_event.id(),
+            "ShoppingCartAdded",
+            event.clone(),
+        );
+    }
+}
+
+#[sqlx::test]
+async fn it_stamps_the_events_with_the_actor_of_the_execution_context(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let alice = Actor::user("alice");
+
+    let persisted_events = event_store
+        .append_without_validation(vec![added_event("product_1", "cart_1")])
+        .with_actor(alice.clone())
+        .await
+        .unwrap();
+    event_store
+        .append_without_validation(vec![removed_event("product_1", "cart_1")])
+        .await
+        .unwrap();
+
+    assert_eq!(persisted_events[0].actor(), Some(&alice));
+    assert_eq!(event_store.actors(&[1, 2]).await.unwrap(), vec![(1, alice)]);
+}
+
 #[track_caller]
 fn assert_event_row(
     row: &PgRow,
@@ -182,7 +783,7 @@ fn assert_event_row(
     assert_eq!(stored_event_id, event_id);
     let stored_event_type: String = row.get(1);
     assert_eq!(stored_event_type, event_type);
-    let stored_payload: Vec<u8> = row.get(2);
+    let stored_payload: &[u8] = row.get(2);
     assert_eq!(
         Json::<ShoppingCartEvent>::default()
             .deserialize(stored_payload)
@@ -266,6 +867,519 @@ async fn it_returns_a_concurrency_error_when_it_appends_events_of_a_query_which_
     assert!(matches!(result, Err(Error::Concurrency)));
 }
 
+#[sqlx::test]
+async fn it_commits_the_concurrent_appends_in_groups(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_group_commit(10);
+
+    let (first, conflicting, other) = futures::join!(
+        event_store.append(
+            vec![added_event("product_1", "cart_1")],
+            query!(ShoppingCartEvent; cart_id == "cart_1"),
+            0,
+        ),
+        event_store.append(
+            vec![added_event("product_2", "cart_1")],
+            query!(ShoppingCartEvent; cart_id == "cart_1"),
+            0,
+        ),
+        event_store.append(
+            vec![added_event("product_3", "cart_2")],
+            query!(ShoppingCartEvent; cart_id == "cart_2"),
+            0,
+        ),
+    );
+
+    assert_eq!(first.unwrap().len(), 1);
+    assert!(matches!(conflicting, Err(Error::Concurrency)));
+    assert_eq!(
+        other.unwrap()[0].clone().into_inner(),
+        added_event("product_3", "cart_2")
+    );
+    let stored_events: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM event")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored_events, 2);
+}
+
+#[sqlx::test]
+async fn it_serves_the_cached_queries_until_an_append_invalidates_them(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_query_cache(10, Duration::from_secs(60));
+    let query = query!(ShoppingCartEvent; cart_id == "cart_1");
+    let stream_len = || async {
+        event_store
+            .stream(&query)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+            .len()
+    };
+    event_store
+        .append_without_validation(vec![added_event("product_1", "cart_1")])
+        .await
+        .unwrap();
+    assert_eq!(stream_len().await, 1);
+
+    insert_events(&pool, &[added_event("product_2", "cart_1")]).await;
+    assert_eq!(stream_len().await, 1);
+
+    event_store
+        .append_without_validation(vec![added_event("product_3", "cart_2")])
+        .await
+        .unwrap();
+    assert_eq!(stream_len().await, 1);
+
+    event_store
+        .append_without_validation(vec![removed_event("product_1", "cart_1")])
+        .await
+        .unwrap();
+    assert_eq!(stream_len().await, 3);
+}
+
+#[sqlx::test]
+async fn it_repairs_the_appends_that_crashed(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    insert_events(&pool, &[added_event("product_1", "cart_1")]).await;
+    let crashed_event = added_event("product_2", "cart_1");
+    let crashed_event_id: PgEventId = InsertEventSequenceBuilder::new(&crashed_event)
+        .build()
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+        .get(0);
+    InsertEventsBuilder::new(
+        &[PersistedEvent::new(crashed_event_id, crashed_event)],
+        &Json::<ShoppingCartEvent>::default(),
+    )
+    .build()
+    .execute(&pool)
+    .await
+    .unwrap();
+    InsertEventSequenceBuilder::new(&added_event("product_3", "cart_1"))
+        .build()
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let report = event_store.check_integrity(Duration::ZERO).await.unwrap();
+    assert_eq!(
+        report,
+        IntegrityReport {
+            uncommitted_events: vec![crashed_event_id],
+            missing_events: vec![],
+            aborted_appends: 1,
+        }
+    );
+    assert!(!report.is_consistent());
+
+    let repair = event_store.repair(Duration::ZERO).await.unwrap();
+    assert_eq!(
+        repair,
+        RepairReport {
+            removed_events: vec![crashed_event_id],
+            compacted_sequence_entries: 2,
+        }
+    );
+    let report = event_store.check_integrity(Duration::ZERO).await.unwrap();
+    assert_eq!(report, IntegrityReport::default());
+    assert!(report.is_consistent());
+}
+
+#[sqlx::test]
+async fn it_rewrites_the_events_of_the_data_subject(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    event_store
+        .append_without_validation(vec![
+            added_event("product_1", "cart_1"),
+            added_event("product_2", "cart_2"),
+            removed_event("product_1", "cart_1"),
+        ])
+        .await
+        .unwrap();
+
+    let report = event_store
+        .anonymize(
+            &query!(ShoppingCartEvent; cart_id == "cart_1"),
+            |event| match event {
+                ShoppingCartEvent::Added { product_id, .. } => {
+                    added_event(&product_id, "anonymous")
+                }
+                ShoppingCartEvent::Removed { product_id, .. } => {
+                    removed_event(&product_id, "anonymous")
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        AnonymizationReport {
+            rewritten_events: vec![1, 3],
+            invalidated_snapshots: 0,
+            stale_listeners: vec![],
+        }
+    );
+    let events: Vec<ShoppingCartEvent> = event_store
+        .stream(&query!(ShoppingCartEvent))
+        .map(|event| event.unwrap().into_inner())
+        .collect()
+        .await;
+    assert_eq!(
+        events,
+        vec![
+            added_event("product_1", "anonymous"),
+            added_event("product_2", "cart_2"),
+            removed_event("product_1", "anonymous"),
+        ]
+    );
+    let sequenced_carts: Vec<String> =
+        sqlx::query_scalar("SELECT cart_id FROM event_sequence ORDER BY event_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(sequenced_carts, vec!["anonymous", "cart_2", "anonymous"]);
+}
+
+#[test]
+fn it_matches_the_whole_event_types_of_the_snapshot_keys() {
+    let key = snapshot_key::<PgEventId, _>(&query!(ShoppingCartEvent; cart_id == "cart_1"));
+
+    assert!(super::has_event_type(&key, "ShoppingCartAdded"));
+    assert!(super::has_event_type(&key, "ShoppingCartRemoved"));
+    assert!(!super::has_event_type(&key, "CartAdded"));
+    assert!(!super::has_event_type(&key, "cart_1"));
+    assert!(!super::has_event_type(
+        "(0|ShoppingCartAddedV2|cart_id=cart_1)",
+        "ShoppingCartAdded"
+    ));
+    assert!(!super::has_event_type(
+        "(0|ShoppingCartRemoved-ShoppingCartAdded|)",
+        "ShoppingCartAdded"
+    ));
+}
+
+#[sqlx::test]
+async fn it_archives_the_events_expired_by_the_retention_policy(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    crate::snapshotter::setup(&pool).await.unwrap();
+    event_store
+        .append_without_validation(vec![
+            added_event("product_1", "cart_1"),
+            added_event("product_2", "cart_1"),
+            removed_event("product_1", "cart_1"),
+            added_event("product_3", "cart_1"),
+            added_event("product_1", "cart_2"),
+        ])
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO snapshot (id, name, query, payload, version) VALUES (gen_random_uuid(), 'cart', '(0|ShoppingCartAdded|cart_id=cart_1)', '{}', 4), (gen_random_uuid(), 'removed', '(0|ShoppingCartRemoved|cart_id=cart_1)', '{}', 4)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let policy = RetentionPolicy::default()
+        .retain::<ShoppingCartEvent>(
+            "ShoppingCartAdded",
+            EventRetention::keep_latest(ident!(#cart_id), 1),
+        )
+        .retain::<ShoppingCartEvent>("ShoppingCartRemoved", EventRetention::years(1));
+
+    let report = event_store.apply_retention(&policy).await.unwrap();
+
+    assert_eq!(
+        report,
+        RetentionReport {
+            expired_events: vec![1, 2],
+            invalidated_snapshots: 1,
+        }
+    );
+    let archived_events: Vec<PgEventId> =
+        sqlx::query_scalar("SELECT event_id FROM event_archive ORDER BY event_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(archived_events, vec![1, 2]);
+    let snapshots: Vec<String> = sqlx::query_scalar("SELECT name FROM snapshot")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(snapshots, vec!["removed"]);
+    assert_eq!(
+        event_store.check_integrity(Duration::ZERO).await.unwrap(),
+        IntegrityReport::default()
+    );
+}
+
+#[cfg(feature = "hash-chain")]
+#[sqlx::test]
+async fn it_detects_the_altered_events_of_the_hash_chain(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_hash_chain();
+    let query = query!(ShoppingCartEvent);
+    let events = event_store
+        .append(
+            vec![
+                added_event("product_1", "cart_1"),
+                added_event("product_2", "cart_1"),
+            ],
+            query.clone(),
+            0,
+        )
+        .await
+        .unwrap();
+    event_store
+        .append_without_validation(vec![removed_event("product_1", "cart_1")])
+        .await
+        .unwrap();
+
+    let verification = event_store.verify_chain(&query).await.unwrap();
+    assert_eq!(verification.verified_events, 3);
+    assert!(verification.is_intact());
+
+    sqlx::query("UPDATE event SET payload = $1 WHERE event_id = $2")
+        .bind(serde_json::to_vec(&added_event("product_3", "cart_1")).unwrap())
+        .bind(events[0].id())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let verification = event_store.verify_chain(&query).await.unwrap();
+    assert_eq!(verification.broken_links, vec![events[0].id()]);
+    assert!(!verification.is_intact());
+}
+
+#[cfg(feature = "signing")]
+#[sqlx::test]
+async fn it_verifies_the_signatures_of_the_streamed_events(pool: PgPool) {
+    use disintegrate::{Ed25519Signer, Ed25519Verifier};
+
+    let signer = Ed25519Signer::new("k1", [1; 32]);
+    let verifier = Ed25519Verifier::default().with_key("k1", signer.verifying_key());
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_signer(signer)
+    .with_signature_verifier(verifier);
+    let events = event_store
+        .append_without_validation(vec![
+            added_event("product_1", "cart_1"),
+            added_event("product_2", "cart_1"),
+        ])
+        .await
+        .unwrap();
+    let query = query!(ShoppingCartEvent);
+
+    let streamed: Vec<_> = event_store.stream(&query).collect().await;
+    assert!(streamed.iter().all(Result::is_ok));
+
+    sqlx::query("UPDATE event SET payload = $1 WHERE event_id = $2")
+        .bind(serde_json::to_vec(&added_event("product_3", "cart_1")).unwrap())
+        .bind(events[1].id())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let streamed: Vec<_> = event_store.stream(&query).collect().await;
+    assert!(matches!(
+        streamed.last(),
+        Some(Err(Error::Signature { event_id, .. })) if *event_id == events[1].id()
+    ));
+}
+
+#[cfg(feature = "claim-check")]
+#[sqlx::test]
+async fn it_stores_the_oversized_payloads_in_the_object_store(pool: PgPool) {
+    use crate::ClaimCheck;
+    use object_store::memory::InMemory;
+    use std::sync::Arc;
+
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_claim_check(ClaimCheck::new(Arc::new(InMemory::new()), 128));
+    let small = added_event("product_1", "cart_1");
+    let large = added_event(&"product_2".repeat(20), "cart_1");
+    event_store
+        .append_without_validation(vec![small.clone(), large.clone()])
+        .await
+        .unwrap();
+
+    let payloads: Vec<Vec<u8>> = sqlx::query_scalar("SELECT payload FROM event ORDER BY event_id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(payloads[0], serde_json::to_vec(&small).unwrap());
+    assert_ne!(payloads[1], serde_json::to_vec(&large).unwrap());
+
+    let streamed: Vec<ShoppingCartEvent> = event_store
+        .stream(&query!(ShoppingCartEvent; cart_id == "cart_1"))
+        .map(|event| event.unwrap().into_inner())
+        .collect()
+        .await;
+    assert_eq!(streamed, vec![small, large]);
+}
+
+#[cfg(feature = "encryption")]
+#[sqlx::test]
+async fn it_encrypts_the_payloads_and_rewraps_the_data_keys(pool: PgPool) {
+    use crate::EnvelopeEncryption;
+    use disintegrate::StaticKeyProvider;
+
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap()
+    .with_envelope_encryption(EnvelopeEncryption::new(StaticKeyProvider::new(
+        "m1", [1; 32],
+    )));
+    let event = added_event("product_1", "cart_1");
+    event_store
+        .append_without_validation(vec![event.clone()])
+        .await
+        .unwrap();
+
+    let payload: Vec<u8> = sqlx::query_scalar("SELECT payload FROM event")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!payload
+        .windows(b"product_1".len())
+        .any(|window| window == b"product_1"));
+
+    let rotated =
+        EnvelopeEncryption::new(StaticKeyProvider::new("m2", [2; 32]).with_key("m1", [1; 32]));
+    assert_eq!(rotated.rewrap_data_keys(&pool).await.unwrap(), 1);
+    assert_eq!(rotated.rewrap_data_keys(&pool).await.unwrap(), 0);
+
+    let event_store = event_store.with_envelope_encryption(EnvelopeEncryption::new(
+        StaticKeyProvider::new("m2", [2; 32]),
+    ));
+    let streamed: Vec<ShoppingCartEvent> = event_store
+        .stream(&query!(ShoppingCartEvent; cart_id == "cart_1"))
+        .map(|event| event.unwrap().into_inner())
+        .collect()
+        .await;
+    assert_eq!(streamed, vec![event]);
+}
+
+#[sqlx::test]
+async fn it_isolates_the_events_of_the_tenants(pool: PgPool) {
+    let event_store = PgEventStore::new(pool.clone(), Json::<ShoppingCartEvent>::default())
+        .await
+        .unwrap();
+    let row_level_security = RowLevelSecurity::new(ident!(#cart_id));
+    event_store
+        .enable_row_level_security(&row_level_security)
+        .await
+        .unwrap();
+    sqlx::query(
+        "DO $$ BEGIN CREATE ROLE disintegrate_tenant; EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL; END $$",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("GRANT SELECT, INSERT, UPDATE ON event, event_sequence TO disintegrate_tenant")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let tenant_pool = sqlx::postgres::PgPoolOptions::new()
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                sqlx::Executor::execute(conn, "SET ROLE disintegrate_tenant").await?;
+                Ok(())
+            })
+        })
+        .connect_with((*pool.connect_options()).clone())
+        .await
+        .unwrap();
+    let tenant_store =
+        PgEventStore::new_uninitialized(tenant_pool, Json::<ShoppingCartEvent>::default());
+    let cart_1 = tenant_store
+        .clone()
+        .with_tenant(&row_level_security, "cart_1");
+    let cart_2 = tenant_store.with_tenant(&row_level_security, "cart_2");
+
+    cart_1
+        .append_without_validation(vec![added_event("product_1", "cart_1")])
+        .await
+        .unwrap();
+    cart_2
+        .append_without_validation(vec![added_event("product_2", "cart_2")])
+        .await
+        .unwrap();
+    assert!(matches!(
+        cart_1
+            .append_without_validation(vec![added_event("product_3", "cart_2")])
+            .await,
+        Err(Error::Database(_))
+    ));
+
+    let streamed: Vec<ShoppingCartEvent> = cart_1
+        .stream(&query!(ShoppingCartEvent))
+        .map(|event| event.unwrap().into_inner())
+        .collect()
+        .await;
+    assert_eq!(streamed, vec![added_event("product_1", "cart_1")]);
+    assert_eq!(
+        cart_2
+            .count(&query!(ShoppingCartEvent))
+            .await
+            .unwrap()
+            .value,
+        1
+    );
+    assert_eq!(
+        event_store
+            .count(&query!(ShoppingCartEvent))
+            .await
+            .unwrap()
+            .value,
+        2
+    );
+}
+
 pub async fn insert_events<E: Event + Clone + Serialize + DeserializeOwned>(
     pool: &PgPool,
     events: &[E],
@@ -1,18 +1,88 @@
 //! # PostgreSQL Disintegrate Backend Library
+mod admin;
+#[cfg(feature = "cloudevents")]
+mod cloudevents;
+mod credentials;
+mod debezium;
 mod error;
 mod event_store;
 #[cfg(feature = "listener")]
 mod listener;
+#[cfg(feature = "outbox")]
+mod outbox;
+#[cfg(feature = "scheduler")]
+mod scheduler;
+mod serverless;
 mod snapshotter;
+#[cfg(feature = "encryption")]
+mod subject_keys;
+#[cfg(feature = "grpc")]
+mod subscription;
 
-pub use crate::event_store::PgEventStore;
+pub use crate::admin::{Error as AdminError, EventDetails, EventPage, ListenerCheckpoint, PgAdmin};
+#[cfg(feature = "cloudevents")]
+pub use crate::cloudevents::{
+    CloudEvent, CloudEvents, Error as CloudEventsError, STRUCTURED_CONTENT_TYPE,
+};
+pub use crate::credentials::{Credentials, CredentialsProvider, PgCredentialsRotation};
+pub use crate::debezium::{Debezium, DebeziumEnvelope, DebeziumSource, Error as DebeziumError};
+#[cfg(feature = "prometheus")]
+pub use crate::event_store::metrics::install_prometheus_recorder;
+#[cfg(feature = "metrics")]
+pub use crate::event_store::metrics::MetricsLabels;
+#[cfg(feature = "claim-check")]
+pub use crate::event_store::ClaimCheck;
+#[cfg(feature = "encryption")]
+pub use crate::event_store::EnvelopeEncryption;
+pub use crate::event_store::{
+    AnonymizationReport, EventRetention, Hypertable, IndexAdvice, IndexAdvisor,
+    IndexRecommendation, IntegrityReport, PgEventStore, QueryPlan, RepairReport, RetentionPolicy,
+    RetentionReport, RowLevelSecurity, ScalarState, StorageOptions,
+};
+#[cfg(feature = "hash-chain")]
+pub use crate::event_store::{ChainHead, ChainVerification};
+#[cfg(feature = "amqp")]
+pub use crate::listener::amqp::{AmqpPublisher, Error as AmqpPublisherError};
+#[cfg(feature = "aws")]
+pub use crate::listener::aws::{AwsDestination, AwsPublisher, Error as AwsPublisherError};
+#[cfg(feature = "kafka")]
+pub use crate::listener::kafka::{Error as KafkaPublisherError, KafkaPublisher};
+#[cfg(feature = "nats")]
+pub use crate::listener::nats::{Error as NatsPublisherError, NatsPublisher};
+#[cfg(feature = "parquet")]
+pub use crate::listener::parquet::{
+    Error as ParquetExporterError, ParquetExporter, ParquetManifest,
+};
+#[cfg(feature = "webhook")]
+pub use crate::listener::webhook::{
+    Error as WebhookListenerError, WebhookEndpoint, WebhookListener,
+};
 #[cfg(feature = "listener")]
 pub use crate::listener::{
+    filter::PgEventListenerFilter,
     id_indexer::{Error as PgIdIndexerError, PgIdIndexer},
-    PgEventListener, PgEventListenerConfig,
+    metrics::{ListenerFailure, ListenerMetrics, PgEventListenerMetrics},
+    projection::{
+        Error as SqlProjectionError, ProjectionChange, ProjectionRow, SqlProjection,
+        SqlProjectionBuilder, SqlValue,
+    },
+    DeliveryMode, PgEventListener, PgEventListenerConfig, StartPosition,
+};
+#[cfg(feature = "outbox")]
+pub use crate::outbox::{Error as OutboxError, PgOutboxHealth, PgOutboxRelay, Publisher};
+#[cfg(feature = "scheduler")]
+pub use crate::scheduler::{Error as SchedulerError, PgScheduler};
+pub use crate::serverless::PgServerlessPool;
+pub use crate::snapshotter::{PgSnapshotStore, PgSnapshotter};
+#[cfg(feature = "encryption")]
+pub use crate::subject_keys::PgSubjectKeyStore;
+#[cfg(feature = "grpc")]
+pub use crate::subscription::{
+    proto as subscription_proto, PgSubscriptionServer, SubscriptionAuthorizer,
+};
+use disintegrate::{
+    DecisionMaker, Event, EventSourcedStateStore, SnapshotConfig, StateQuerier, WithSnapshot,
 };
-pub use crate::snapshotter::PgSnapshotter;
-use disintegrate::{DecisionMaker, Event, EventSourcedStateStore, SnapshotConfig, WithSnapshot};
 use disintegrate_serde::Serde;
 pub use error::Error;
 
@@ -22,6 +92,10 @@ pub type PgEventId = i64;
 pub type PgDecisionMaker<E, S, SN> =
     DecisionMaker<EventSourcedStateStore<PgEventId, E, PgEventStore<E, S>, SN>>;
 
+/// An alias for [`StateQuerier`], specialized for Postgres.
+pub type PgStateQuerier<E, S, SN> =
+    StateQuerier<EventSourcedStateStore<PgEventId, E, PgEventStore<E, S>, SN>>;
+
 /// An alias for [`WithSnapshot`], specialized for Postgres.
 pub type WithPgSnapshot = WithSnapshot<PgEventId, PgSnapshotter>;
 
@@ -45,3 +119,24 @@ pub fn decision_maker<
 ) -> PgDecisionMaker<E, S, SN> {
     DecisionMaker::new(EventSourcedStateStore::new(event_store, snapshot_config))
 }
+
+/// Creates a state querier specialized for PostgreSQL.
+///
+/// # Arguments
+///
+/// - `event_store`: An instance of `PgEventStore`.
+/// - `snapshot_config`: The `SnapshotConfig` to be used for the snapshotting.
+///
+/// # Returns
+///
+/// A `PgStateQuerier` with snapshotting configured according to the provided `snapshot_config`.
+pub fn state_querier<
+    E: Event + Send + Sync + Clone,
+    S: Serde<E> + Clone + Sync + Send,
+    SN: SnapshotConfig + Clone,
+>(
+    event_store: PgEventStore<E, S>,
+    snapshot_config: SN,
+) -> PgStateQuerier<E, S, SN> {
+    StateQuerier::new(EventSourcedStateStore::new(event_store, snapshot_config))
+}
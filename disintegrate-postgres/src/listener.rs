@@ -7,23 +7,46 @@
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "amqp")]
+pub(crate) mod amqp;
+#[cfg(feature = "aws")]
+pub(crate) mod aws;
+pub(crate) mod filter;
 pub(crate) mod id_indexer;
+#[cfg(feature = "kafka")]
+pub(crate) mod kafka;
+pub(crate) mod metrics;
+#[cfg(feature = "nats")]
+pub(crate) mod nats;
+#[cfg(feature = "parquet")]
+pub(crate) mod parquet;
+pub(crate) mod projection;
+#[cfg(feature = "webhook")]
+pub(crate) mod webhook;
 
+use self::filter::PgEventListenerFilter;
 use crate::{Error, PgEventId};
 use async_trait::async_trait;
-use disintegrate::{Event, EventListener, EventStore, StreamQuery};
+use disintegrate::{
+    BatchError, Classify, Event, EventListener, Identifier, PersistedEvent, Runtime, StreamQuery,
+    TokioRuntime,
+};
 use disintegrate_serde::Serde;
-use futures::future::join_all;
-use futures::{try_join, Future, StreamExt};
+use futures::future::{join, join_all, select, Either, RemoteHandle};
+use futures::{stream, try_join, Future, FutureExt, StreamExt};
 use sqlx::{PgPool, Postgres, Row, Transaction};
+use std::collections::BTreeMap;
 use std::error::Error as StdError;
+use std::fmt::Display;
 use std::marker::PhantomData;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::watch;
-use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
+use self::metrics::{ListenerRecorder, PgEventListenerMetrics};
 use crate::event_store::PgEventStore;
 
 /// PostgreSQL event listener implementation.
@@ -36,6 +59,8 @@ where
     event_store: PgEventStore<E, S>,
     intialize: bool,
     shutdown_token: CancellationToken,
+    metrics: PgEventListenerMetrics,
+    runtime: Arc<dyn Runtime>,
 }
 
 impl<E, S> PgEventListener<E, S>
@@ -58,6 +83,8 @@ where
             executors: vec![],
             shutdown_token: CancellationToken::new(),
             intialize: true,
+            metrics: PgEventListenerMetrics::default(),
+            runtime: Arc::new(TokioRuntime),
         }
     }
 
@@ -77,6 +104,14 @@ where
         self
     }
 
+    /// Sets the async runtime running the event listeners and their timers, Tokio by default.
+    ///
+    /// The runtime of the database driver is chosen with the `runtime-*` features of the crate.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
     /// Registers an event listener to the `PgEventListener`.
     ///
     /// # Parameters
@@ -87,24 +122,113 @@ where
     /// # Returns
     ///
     /// The updated `PgEventListener` instance with the registered event handler.
-    pub fn register_listener<QE>(
+    pub fn register_listener<QE, L>(
         mut self,
-        event_listener: impl EventListener<PgEventId, QE> + 'static,
+        event_listener: L,
         config: PgEventListenerConfig,
     ) -> Self
     where
         QE: TryFrom<E> + Into<E> + Event + Send + Sync + Clone + 'static,
         <QE as TryFrom<E>>::Error: StdError + Send + Sync,
+        L: EventListener<PgEventId, QE> + 'static,
+        L::Error: Display + Send + Sync + 'static,
     {
-        self.executors.push(Box::new(PgEventListerExecutor::new(
-            self.event_store.clone(),
-            event_listener,
-            self.shutdown_token.clone(),
-            config,
-        )));
+        self.executors.push(Box::new(
+            PgEventListerExecutor::new(
+                self.event_store.clone(),
+                event_listener,
+                self.shutdown_token.clone(),
+                config,
+            )
+            .with_metrics(&self.metrics),
+        ));
         self
     }
 
+    /// Returns a handle to the metrics of the registered event listeners.
+    ///
+    /// The handle can be retained before starting the listeners and read while they are running.
+    pub fn metrics(&self) -> PgEventListenerMetrics {
+        self.metrics.clone()
+    }
+
+    /// Returns the IDs of the event listeners that have not run for the given duration.
+    ///
+    /// Every run of a listener refreshes its checkpoint, even when there are no new events, so the listeners
+    /// returned are likely decommissioned or renamed and can be removed with `purge_listeners`.
+    ///
+    /// # Parameters
+    ///
+    /// * `inactive_for`: The minimum time elapsed since the last run of the listener.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the inactive listeners.
+    pub async fn stale_listeners(&self, inactive_for: Duration) -> Result<Vec<String>, Error> {
+        Ok(sqlx::query_scalar(
+            "SELECT id FROM event_listener WHERE updated_at < now() - make_interval(secs => $1) ORDER BY id",
+        )
+        .bind(inactive_for.as_secs_f64())
+        .fetch_all(&self.event_store.pool)
+        .await?)
+    }
+
+    /// Removes the state stored for the given event listeners: their checkpoint and, with the `webhook` feature,
+    /// their delivery records.
+    ///
+    /// The listeners must be decommissioned: a running listener whose state is removed stops processing events
+    /// and, once restarted, handles the events again from its start position.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener_ids`: The IDs of the listeners to purge.
+    ///
+    /// # Returns
+    ///
+    /// The number of purged listeners.
+    pub async fn purge_listeners(&self, listener_ids: &[&str]) -> Result<u64, Error> {
+        let mut tx = self.event_store.pool.begin().await?;
+        let purged = sqlx::query("DELETE FROM event_listener WHERE id = ANY($1)")
+            .bind(listener_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        #[cfg(feature = "webhook")]
+        webhook::purge(&mut tx, listener_ids).await?;
+        tx.commit().await?;
+        Ok(purged)
+    }
+
+    /// Moves the checkpoint of the given event listeners back, so they handle again the events following
+    /// `last_processed_event_id`, e.g. to rebuild their read models from the events rewritten by
+    /// `PgEventStore::anonymize`.
+    ///
+    /// The checkpoints already before `last_processed_event_id` are left untouched. The listeners handle the
+    /// events again at their next run, so they must be idempotent.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener_ids`: The IDs of the listeners to rewind.
+    /// * `last_processed_event_id`: The ID of the last event considered processed by the listeners.
+    ///
+    /// # Returns
+    ///
+    /// The number of rewound listeners.
+    pub async fn rewind_listeners(
+        &self,
+        listener_ids: &[&str],
+        last_processed_event_id: PgEventId,
+    ) -> Result<u64, Error> {
+        Ok(sqlx::query(
+            "UPDATE event_listener SET last_processed_event_id = $2, updated_at = now() WHERE id = ANY($1) AND last_processed_event_id > $2",
+        )
+        .bind(listener_ids)
+        .bind(last_processed_event_id)
+        .execute(&self.event_store.pool)
+        .await?
+        .rows_affected())
+    }
+
     /// Starts the listener process for all registered event listeners.
     ///
     /// # Returns
@@ -118,7 +242,7 @@ where
         let mut wakers = vec![];
         for executor in self.executors {
             executor.init().await?;
-            let (waker, task) = executor.run();
+            let (waker, task) = executor.run(&self.runtime);
             if let Some(waker) = waker {
                 wakers.push(waker);
             }
@@ -127,7 +251,7 @@ where
         if !wakers.is_empty() {
             let pool = self.event_store.pool.clone();
             let shutdown = self.shutdown_token.clone();
-            let watch_new_events = tokio::spawn(async move {
+            let watch_new_events = self.runtime.spawn_with_handle(async move {
                 loop {
                     let mut listener = sqlx::postgres::PgListener::connect_with(&pool).await?;
                     listener.listen("new_events").await?;
@@ -191,11 +315,30 @@ pub struct PgEventListenerError {
 /// * `poll`: The `poll` property represents the interval at which the
 ///   listener should poll for new events from the event store. This determines how frequently the
 ///   event handler will handles new events.
+/// * `fetch_size`: The maximum number of events fetched in a single poll.
+/// * `prefetch_pages`: The number of pages of events fetched ahead of the batches.
+/// * `min_poll_interval`: The minimum time between the start of two polls.
+/// * `max_idle_poll`: The longest interval the poll backs off to while no events are found, if enabled.
 /// * `notifier_enabled`: The `notifier_enabled` indicates if the listener is configured to handle events in "real time".
+/// * `batch_size`: The maximum number of events passed to the listener at once.
+/// * `start_position`: The position of the event stream from which a new listener starts.
+/// * `max_in_flight_batches`: The maximum number of batches fetched ahead of the listener.
+/// * `target_latency`: The handling time per batch that the adaptive batching aims for, if enabled.
+/// * `delivery_mode`: How the events of a batch are passed to the listener.
+/// * `filter`: The criteria evaluated by the database on the events fetched for the listener.
 #[derive(Clone)]
 pub struct PgEventListenerConfig {
     poll: Duration,
     fetch_size: usize,
+    prefetch_pages: usize,
+    min_poll_interval: Duration,
+    max_idle_poll: Option<Duration>,
+    batch_size: usize,
+    max_in_flight_batches: usize,
+    target_latency: Option<Duration>,
+    delivery_mode: DeliveryMode,
+    filter: PgEventListenerFilter,
+    start_position: StartPosition,
     notifier_enabled: bool,
 }
 
@@ -213,12 +356,23 @@ impl PgEventListenerConfig {
         Self {
             poll,
             fetch_size: usize::MAX,
+            prefetch_pages: 0,
+            min_poll_interval: Duration::ZERO,
+            max_idle_poll: None,
+            batch_size: 1,
+            max_in_flight_batches: 1,
+            target_latency: None,
+            delivery_mode: DeliveryMode::Sequential,
+            filter: PgEventListenerFilter::default(),
+            start_position: StartPosition::Beginning,
             notifier_enabled: false,
         }
     }
 
     /// Sets the fetch size for the event listener.
     /// The fetch size determines the number of events to fetch from the event store at a time.
+    /// The pages fetched by the listener are no larger than the fetch size. When a poll reaches the fetch size,
+    /// the next poll starts right away, so a listener catching up is not slowed down by the poll interval.
     ///
     /// # Parameters
     ///
@@ -232,6 +386,160 @@ impl PgEventListenerConfig {
         self
     }
 
+    /// Sets the number of pages of events fetched from the event store ahead of the event listener.
+    /// While the listener handles a page, the following ones are fetched in the background, so the listener
+    /// does not wait for the database while catching up. The default is 0, which fetches a page once the
+    /// previous one is consumed.
+    ///
+    /// # Parameters
+    ///
+    /// * `prefetch_pages`: The number of pages fetched ahead.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn prefetch(mut self, prefetch_pages: usize) -> Self {
+        self.prefetch_pages = prefetch_pages;
+        self
+    }
+
+    /// Sets the minimum time between the start of two polls, limiting the poll frequency of the listener.
+    /// The notifications of the db notifier and the polls of a listener catching up are delayed until the
+    /// interval has elapsed since the previous poll, so a burst of appends results in a single poll.
+    /// The default is no limit.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_poll_interval`: The minimum time between two polls.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn min_poll_interval(mut self, min_poll_interval: Duration) -> Self {
+        self.min_poll_interval = min_poll_interval;
+        self
+    }
+
+    /// Enables the backoff of the poll interval while the listener is idle.
+    /// The poll interval doubles after each poll that finds no events, up to `max_idle_poll`, and it is
+    /// reset to the one set with `poller` once events are found. The db notifier still wakes the listener
+    /// as soon as a matching event is appended.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_idle_poll`: The longest interval between two polls of an idle listener.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn idle_backoff(mut self, max_idle_poll: Duration) -> Self {
+        self.max_idle_poll = Some(max_idle_poll);
+        self
+    }
+
+    /// Sets the batch size for the event listener.
+    /// The batch size determines the maximum number of events passed to `EventListener::handle_batch` at a time.
+    /// Events already fetched from the event store are grouped up to this size. The default is 1.
+    ///
+    /// # Parameters
+    ///
+    /// * `batch_size`: The maximum number of events in a batch.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch_size must be greater than 0");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the maximum number of batches fetched from the event store ahead of the event listener.
+    /// While the listener handles a batch, the following ones are fetched in the background, up to this limit.
+    /// The default is 1.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_in_flight_batches`: The maximum number of batches waiting to be handled.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn max_in_flight_batches(mut self, max_in_flight_batches: usize) -> Self {
+        assert!(
+            max_in_flight_batches > 0,
+            "max_in_flight_batches must be greater than 0"
+        );
+        self.max_in_flight_batches = max_in_flight_batches;
+        self
+    }
+
+    /// Enables the adaptive batching.
+    /// The batch size starts at 1 and it is adjusted after each batch: it doubles when the batch is handled in less
+    /// than half of the target latency, and it halves when the batch takes longer than the target latency.
+    /// The batch size never exceeds the one set with `batch_size`, so the adaptive batching has no effect
+    /// unless `batch_size` is raised above its default of 1.
+    ///
+    /// # Parameters
+    ///
+    /// * `target_latency`: The handling time per batch to aim for.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn adaptive_batching(mut self, target_latency: Duration) -> Self {
+        self.target_latency = Some(target_latency);
+        self
+    }
+
+    /// Sets the delivery mode of the event listener. The default is `DeliveryMode::Sequential`.
+    ///
+    /// # Parameters
+    ///
+    /// * `delivery_mode`: The delivery mode of the event listener.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
+        if let DeliveryMode::PerIdentifier { concurrency, .. } = delivery_mode {
+            assert!(concurrency > 0, "concurrency must be greater than 0");
+        }
+        self.delivery_mode = delivery_mode;
+        self
+    }
+
+    /// Sets the filter of the event listener. The filter is evaluated by the database, on top of the
+    /// listener query, so the events it rejects are not fetched.
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter of the event listener.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn filter(mut self, filter: PgEventListenerFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the position from which the event listener starts when it has no checkpoint yet.
+    /// The start position is ignored once the listener has stored its last processed event. The default is
+    /// `StartPosition::Beginning`.
+    ///
+    /// # Parameters
+    ///
+    /// * `start_position`: The start position of the event listener.
+    ///
+    /// # Returns
+    ///
+    /// A new `PgEventListenerConfig` instance.
+    pub fn start_from(mut self, start_position: StartPosition) -> Self {
+        self.start_position = start_position;
+        self
+    }
+
     /// Sets the db notifier.
     ///
     /// # Returns
@@ -244,10 +552,45 @@ impl PgEventListenerConfig {
     }
 }
 
+/// Defines how the events are passed to an event listener.
+///
+/// Whatever the mode, a listener is run by a single instance at a time: the instances competing for the same
+/// listener are serialized by a lock on its checkpoint, so the guarantees hold across processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Events are handled one batch at a time, in the order they were appended.
+    Sequential,
+    /// Events of a batch are grouped by the value of a domain identifier, and up to `concurrency` groups are
+    /// handled concurrently. The events sharing the same identifier value are handled in order and never
+    /// concurrently. Events without the identifier are grouped together.
+    PerIdentifier {
+        /// The domain identifier used to group the events.
+        identifier: Identifier,
+        /// The maximum number of groups handled at the same time.
+        concurrency: usize,
+    },
+}
+
+/// The position of the event stream from which a new event listener starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartPosition {
+    /// Handles all the events of the event stream.
+    Beginning,
+    /// Handles only the events appended after the listener is registered.
+    Now,
+    /// Handles the events inserted at or after the given time.
+    Timestamp(SystemTime),
+    /// Handles the events following the given event ID.
+    EventId(PgEventId),
+}
+
 #[async_trait]
 trait EventListenerExecutor<E: Event + Clone> {
     async fn init(&self) -> Result<(), Error>;
-    fn run(&self) -> (Option<ExecutorWaker<E>>, JoinHandle<Result<(), Error>>);
+    fn run(
+        &self,
+        runtime: &Arc<dyn Runtime>,
+    ) -> (Option<ExecutorWaker<E>>, RemoteHandle<Result<(), Error>>);
 }
 
 struct PgEventListerExecutor<L, QE, E, S>
@@ -261,6 +604,10 @@ where
     event_store: PgEventStore<E, S>,
     event_handler: Arc<L>,
     config: PgEventListenerConfig,
+    batch_size: Arc<AtomicUsize>,
+    /// Set when the last poll stopped at the fetch size, so more events may be waiting.
+    backlog: Arc<AtomicBool>,
+    recorder: ListenerRecorder,
     wake_channel: (watch::Sender<bool>, watch::Receiver<bool>),
     shutdown_token: CancellationToken,
     _event_store_events: PhantomData<E>,
@@ -274,6 +621,7 @@ where
     QE: TryFrom<E> + Event + 'static + Send + Sync + Clone,
     <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
     L: EventListener<PgEventId, QE> + 'static,
+    L::Error: Display + Send + Sync + 'static,
 {
     pub fn new(
         event_store: PgEventStore<E, S>,
@@ -281,10 +629,20 @@ where
         shutdown_token: CancellationToken,
         config: PgEventListenerConfig,
     ) -> Self {
+        let batch_size = if config.target_latency.is_some() {
+            1
+        } else {
+            config.batch_size
+        };
+        let recorder = PgEventListenerMetrics::default().register(event_handler.id());
+        let page_size = event_store.stream_page_size().min(config.fetch_size.max(1));
         Self {
-            event_store,
+            event_store: event_store.with_stream_page_size(page_size),
             event_handler: Arc::new(event_handler),
             config,
+            batch_size: Arc::new(AtomicUsize::new(batch_size)),
+            backlog: Arc::new(AtomicBool::new(false)),
+            recorder,
             wake_channel: watch::channel(true),
             shutdown_token,
             _event_store_events: PhantomData,
@@ -292,13 +650,21 @@ where
         }
     }
 
+    pub fn with_metrics(mut self, metrics: &PgEventListenerMetrics) -> Self {
+        self.recorder = metrics.register(self.event_handler.id());
+        self
+    }
+
+    /// Locks the listener, returning its checkpoint and the ID of the last event of the store.
+    ///
+    /// The last event is read with the lock, so recording the lag of the listener costs no round-trip.
     async fn lock_event_listener(
         &self,
         tx: &mut Transaction<'_, Postgres>,
-    ) -> Result<Option<PgEventId>, sqlx::Error> {
+    ) -> Result<Option<(PgEventId, PgEventId)>, sqlx::Error> {
         Ok(sqlx::query(
             r#"
-                SELECT last_processed_event_id 
+                SELECT last_processed_event_id, (SELECT COALESCE(MAX(event_id), 0) FROM event)
                 FROM event_listener
                 WHERE id = $1  
                 FOR UPDATE SKIP LOCKED 
@@ -307,7 +673,7 @@ where
         .bind(self.event_handler.id())
         .fetch_optional(&mut **tx)
         .await?
-        .map(|r| r.get(0)))
+        .map(|r| (r.get(0), r.get(1))))
     }
 
     async fn release_event_listener(
@@ -331,6 +697,33 @@ where
         tx.commit().await
     }
 
+    async fn start_event_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<PgEventId, sqlx::Error> {
+        match self.config.start_position {
+            StartPosition::Beginning => Ok(0),
+            StartPosition::EventId(event_id) => Ok(event_id),
+            StartPosition::Now => {
+                sqlx::query_scalar("SELECT COALESCE(MAX(event_id), 0) FROM event")
+                    .fetch_one(&mut **tx)
+                    .await
+            }
+            StartPosition::Timestamp(timestamp) => {
+                let seconds = timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                sqlx::query_scalar(
+                    "SELECT COALESCE(MAX(event_id), 0) FROM event WHERE inserted_at < to_timestamp($1) AT TIME ZONE 'UTC'",
+                )
+                .bind(seconds)
+                .fetch_one(&mut **tx)
+                .await
+            }
+        }
+    }
+
     pub async fn handle_events_from(
         &self,
         mut last_processed_event_id: PgEventId,
@@ -340,57 +733,268 @@ where
             .query()
             .clone()
             .change_origin(last_processed_event_id);
-        let mut events_stream = self.event_store.stream(&query).take(self.config.fetch_size);
+        let (batches_tx, mut batches_rx) =
+            tokio::sync::mpsc::channel(self.config.max_in_flight_batches);
+        let (events_tx, events_rx) = tokio::sync::mpsc::channel(
+            self.config
+                .prefetch_pages
+                .saturating_mul(self.event_store.stream_page_size())
+                .max(1),
+        );
+        self.backlog.store(false, Ordering::Relaxed);
 
-        while let Some(event) = events_stream.next().await {
-            let event = event.map_err(|_err| PgEventListenerError {
-                last_processed_event_id,
-            })?;
-            let event_id = event.id();
-            match self.event_handler.handle(event).await {
-                Ok(_) => last_processed_event_id = event_id,
-                Err(_) => {
-                    return Err(PgEventListenerError {
-                        last_processed_event_id,
-                    })
+        let fetcher = async move {
+            let mut events = self
+                .event_store
+                .stream_with_criteria(&query, self.config.filter.criteria())
+                .take(self.config.fetch_size);
+            let mut fetched = 0;
+            while let Some(event) = events.next().await {
+                fetched += 1;
+                if events_tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+            self.backlog
+                .store(fetched == self.config.fetch_size, Ordering::Relaxed);
+        };
+
+        let producer = async move {
+            let mut events = pin!(stream::unfold(events_rx, |mut events_rx| async move {
+                events_rx.recv().await.map(|event| (event, events_rx))
+            }));
+            while let Some(event) = events.next().await {
+                let batch_size = self.batch_size.load(Ordering::Relaxed);
+                let mut batch = vec![event];
+                while batch.len() < batch_size {
+                    match events.next().now_or_never() {
+                        Some(Some(event)) => batch.push(event),
+                        _ => break,
+                    }
+                }
+                if batches_tx.send(batch).await.is_err() {
+                    break;
                 }
             }
-            if self.shutdown_token.is_cancelled() {
-                break;
+        };
+
+        let consumer = async move {
+            while let Some(batch) = batches_rx.recv().await {
+                let events = batch
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        self.recorder.record_error(err.to_string());
+                        PgEventListenerError {
+                            last_processed_event_id,
+                        }
+                    })?;
+                let events_count = events.len();
+                let Some(last_event_id) = events.last().map(|event| event.id()) else {
+                    continue;
+                };
+                let started_at = Instant::now();
+                match self.dispatch(events).await {
+                    Ok(_) => {
+                        last_processed_event_id = last_event_id;
+                        self.recorder
+                            .record_batch(events_count, started_at.elapsed());
+                    }
+                    Err(BatchError {
+                        last_handled_event_id,
+                        error,
+                    }) => {
+                        self.recorder.record_error(error.to_string());
+                        return Err(PgEventListenerError {
+                            last_processed_event_id: last_handled_event_id
+                                .unwrap_or(last_processed_event_id),
+                        });
+                    }
+                }
+                if let Some(target_latency) = self.config.target_latency {
+                    let batch_size = adapt_batch_size(
+                        self.batch_size.load(Ordering::Relaxed),
+                        self.config.batch_size,
+                        started_at.elapsed(),
+                        target_latency,
+                    );
+                    self.batch_size.store(batch_size, Ordering::Relaxed);
+                }
+                if self.shutdown_token.is_cancelled() {
+                    break;
+                }
             }
+            Ok::<PgEventId, PgEventListenerError>(last_processed_event_id)
+        };
+
+        match select(pin!(consumer), pin!(join(fetcher, producer))).await {
+            Either::Left((result, _)) => result,
+            Either::Right((_, consumer)) => consumer.await,
         }
+    }
 
-        Ok(last_processed_event_id)
+    /// Connects the span of the batch to the traces that appended its events.
+    ///
+    /// The span continues the trace if all the events belong to it, otherwise it links to each trace.
+    #[cfg(feature = "otel")]
+    async fn continue_traces(&self, events: &[PersistedEvent<PgEventId, QE>]) {
+        use opentelemetry::trace::TraceContextExt;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let event_ids: Vec<PgEventId> = events.iter().map(|event| event.id()).collect();
+        let Ok(contexts) = self.event_store.trace_contexts(&event_ids).await else {
+            return;
+        };
+        let mut traces: Vec<opentelemetry::Context> = vec![];
+        for (_, context) in contexts {
+            let trace_id = context.span().span_context().trace_id();
+            if !traces
+                .iter()
+                .any(|trace| trace.span().span_context().trace_id() == trace_id)
+            {
+                traces.push(context);
+            }
+        }
+        let span = tracing::Span::current();
+        match traces.as_slice() {
+            [] => {}
+            [context] => span.set_parent(context.clone()),
+            _ => {
+                for context in traces {
+                    span.add_link(context.span().span_context().clone());
+                }
+            }
+        }
     }
 
-    pub async fn try_execute(&self) -> Result<(), sqlx::Error> {
+    /// Passes the events to the listener according to the delivery mode.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.listener",
+            skip_all,
+            fields(
+                listener = self.event_handler.id(),
+                events = events.len(),
+                first_event_id = events.first().map(|event| event.id()),
+                last_event_id = events.last().map(|event| event.id())
+            )
+        )
+    )]
+    async fn dispatch(
+        &self,
+        events: Vec<PersistedEvent<PgEventId, QE>>,
+    ) -> Result<(), BatchError<PgEventId, L::Error>> {
+        #[cfg(feature = "otel")]
+        self.continue_traces(&events).await;
+        let DeliveryMode::PerIdentifier {
+            identifier,
+            concurrency,
+        } = self.config.delivery_mode
+        else {
+            return self.event_handler.handle_batch(events).await;
+        };
+
+        let event_ids: Vec<PgEventId> = events.iter().map(|event| event.id()).collect();
+        let mut groups: BTreeMap<Option<String>, Vec<_>> = BTreeMap::new();
+        for event in events {
+            let key = event
+                .domain_identifiers()
+                .get(&identifier)
+                .map(|value| value.to_string());
+            groups.entry(key).or_default().push(event);
+        }
+
+        let results: Vec<_> = stream::iter(groups.into_values().map(|group| async move {
+            let group_ids: Vec<PgEventId> = group.iter().map(|event| event.id()).collect();
+            self.event_handler.handle_batch(group).await.map_err(|err| {
+                let first_unhandled_event_id = match err.last_handled_event_id {
+                    Some(last_handled) => group_ids.into_iter().find(|id| *id > last_handled),
+                    None => group_ids.first().copied(),
+                };
+                (first_unhandled_event_id, err.error)
+            })
+        }))
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+        let mut failure: Option<(PgEventId, L::Error)> = None;
+        for result in results {
+            if let Err((Some(first_unhandled_event_id), error)) = result {
+                if failure
+                    .as_ref()
+                    .is_none_or(|(event_id, _)| first_unhandled_event_id < *event_id)
+                {
+                    failure = Some((first_unhandled_event_id, error));
+                }
+            }
+        }
+        match failure {
+            None => Ok(()),
+            Some((first_unhandled_event_id, error)) => Err(BatchError {
+                last_handled_event_id: event_ids
+                    .into_iter()
+                    .take_while(|event_id| *event_id < first_unhandled_event_id)
+                    .last(),
+                error,
+            }),
+        }
+    }
+
+    pub async fn try_execute(&self) -> Result<PollOutcome, sqlx::Error> {
         let mut tx = self.event_store.pool.begin().await?;
-        let Some(last_processed_id) = self.lock_event_listener(&mut tx).await? else {
-            return Ok(());
+        let Some((last_processed_id, head_event_id)) = self.lock_event_listener(&mut tx).await?
+        else {
+            return Ok(PollOutcome::Idle);
         };
         let result = self.handle_events_from(last_processed_id).await;
-        self.release_event_listener(result, tx).await
+        let last_processed_event_id = match &result {
+            Ok(last_processed_event_id) => *last_processed_event_id,
+            Err(err) => err.last_processed_event_id,
+        };
+        let outcome = if last_processed_event_id == last_processed_id {
+            PollOutcome::Idle
+        } else if result.is_ok() && self.backlog.load(Ordering::Relaxed) {
+            PollOutcome::Backlog
+        } else {
+            PollOutcome::Handled
+        };
+        self.release_event_listener(result, tx).await?;
+        self.recorder.record_position(
+            last_processed_event_id,
+            head_event_id.max(last_processed_event_id),
+        );
+        Ok(outcome)
     }
 
-    async fn execute(&self) -> Result<(), Error> {
-        let result = self.try_execute().await;
-        match result {
-            Err(sqlx::Error::Io(_)) | Err(sqlx::Error::PoolTimedOut) => Ok(()),
-            Err(err) => Err(Error::Database(err)),
-            _ => Ok(()),
+    async fn execute(&self) -> Result<PollOutcome, Error> {
+        match self.try_execute().await.map_err(Error::Database) {
+            Err(err) if err.is_retryable() => Ok(PollOutcome::Idle),
+            result => result,
         }
     }
 
-    pub fn spawn_task(self) -> JoinHandle<Result<(), Error>> {
+    pub fn spawn_task(self, runtime: &Arc<dyn Runtime>) -> RemoteHandle<Result<(), Error>> {
         let shutdown = self.shutdown_token.clone();
-        let mut poll = tokio::time::interval(self.config.poll);
-        poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
         let mut wake_tx = self.wake_channel.1.clone();
-        tokio::spawn(async move {
+        let timer = Arc::clone(runtime);
+        runtime.spawn_with_handle(async move {
+            let mut poll = self.config.poll;
             loop {
+                let started_at = Instant::now();
+                let outcome = self.execute().await?;
+                poll =
+                    next_poll_interval(poll, outcome, self.config.poll, self.config.max_idle_poll);
+                if outcome != PollOutcome::Backlog {
+                    tokio::select! {
+                        Ok(()) = wake_tx.changed() => {}
+                        _ = timer.sleep(poll.saturating_sub(started_at.elapsed())) => {}
+                        _ = shutdown.cancelled() => return Ok::<(), Error>(()),
+                    };
+                }
                 tokio::select! {
-                    Ok(()) =  wake_tx.changed() => self.execute().await?,
-                    _ = poll.tick() => self.execute().await?,
+                    _ = timer.sleep(self.config.min_poll_interval.saturating_sub(started_at.elapsed())) => {}
                     _ = shutdown.cancelled() => return Ok::<(), Error>(()),
                 };
             }
@@ -406,18 +1010,31 @@ where
     QE: TryFrom<E> + Into<E> + Event + 'static + Send + Sync + Clone,
     <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
     L: EventListener<PgEventId, QE> + 'static,
+    L::Error: Display + Send + Sync + 'static,
 {
     async fn init(&self) -> Result<(), Error> {
         let mut tx = self.event_store.pool.begin().await?;
-        sqlx::query("INSERT INTO event_listener (id, last_processed_event_id) VALUES ($1, 0) ON CONFLICT (id) DO NOTHING")
+        let exists = sqlx::query("SELECT 1 FROM event_listener WHERE id = $1")
+            .bind(self.event_handler.id())
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if !exists {
+            let start_event_id = self.start_event_id(&mut tx).await?;
+            sqlx::query("INSERT INTO event_listener (id, last_processed_event_id) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING")
                 .bind(self.event_handler.id())
+                .bind(start_event_id)
                 .execute(&mut *tx)
                 .await?;
+        }
         tx.commit().await?;
         Ok(())
     }
 
-    fn run(&self) -> (Option<ExecutorWaker<E>>, JoinHandle<Result<(), Error>>) {
+    fn run(
+        &self,
+        runtime: &Arc<dyn Runtime>,
+    ) -> (Option<ExecutorWaker<E>>, RemoteHandle<Result<(), Error>>) {
         let waker = if self.config.notifier_enabled {
             Some(ExecutorWaker {
                 wake_tx: self.wake_channel.0.clone(),
@@ -426,7 +1043,7 @@ where
         } else {
             None
         };
-        (waker, self.clone().spawn_task())
+        (waker, self.clone().spawn_task(runtime))
     }
 }
 
@@ -443,6 +1060,9 @@ where
             event_store: self.event_store.clone(),
             event_handler: Arc::clone(&self.event_handler),
             config: self.config.clone(),
+            batch_size: Arc::clone(&self.batch_size),
+            backlog: Arc::clone(&self.backlog),
+            recorder: self.recorder.clone(),
             wake_channel: self.wake_channel.clone(),
             shutdown_token: self.shutdown_token.clone(),
             _event_store_events: PhantomData,
@@ -464,6 +1084,48 @@ impl<E: Event + Clone> ExecutorWaker<E> {
     }
 }
 
+/// The outcome of a poll of the event store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PollOutcome {
+    /// No events were handled, either because none were found or because another instance runs the listener.
+    Idle,
+    /// New events were handled.
+    Handled,
+    /// The poll stopped at the fetch size, so more events may be waiting.
+    Backlog,
+}
+
+/// Computes the interval until the next poll from the outcome of the last poll.
+fn next_poll_interval(
+    interval: Duration,
+    outcome: PollOutcome,
+    poll: Duration,
+    max_idle_poll: Option<Duration>,
+) -> Duration {
+    match (outcome, max_idle_poll) {
+        (PollOutcome::Idle, Some(max_idle_poll)) => {
+            interval.saturating_mul(2).min(max_idle_poll).max(poll)
+        }
+        _ => poll,
+    }
+}
+
+/// Computes the next batch size from the time spent to handle the last batch.
+fn adapt_batch_size(
+    batch_size: usize,
+    max_batch_size: usize,
+    elapsed: Duration,
+    target_latency: Duration,
+) -> usize {
+    if elapsed > target_latency {
+        (batch_size / 2).max(1)
+    } else if elapsed < target_latency / 2 {
+        batch_size.saturating_mul(2).min(max_batch_size)
+    } else {
+        batch_size
+    }
+}
+
 async fn setup(pool: &PgPool) -> Result<(), Error> {
     sqlx::query(include_str!("listener/sql/table_event_listener.sql"))
         .execute(pool)
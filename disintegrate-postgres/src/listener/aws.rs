@@ -0,0 +1,367 @@
+//! An `EventListener` implementation that publishes events to AWS SNS topics or SQS queues.
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use disintegrate::{
+    BatchError, BoxDynError, Classify, ErrorKind, Event, EventListener, PersistedEvent, StreamQuery,
+};
+use disintegrate_serde::Serializer;
+
+use crate::PgEventId;
+
+/// The message attribute containing the ID of the published event.
+pub const EVENT_ID_ATTRIBUTE: &str = "event_id";
+/// The message attribute containing the name of the published event.
+pub const EVENT_TYPE_ATTRIBUTE: &str = "event_type";
+
+/// The maximum number of entries accepted by the SNS and SQS batch APIs.
+const MAX_BATCH_ENTRIES: usize = 10;
+/// The maximum number of message attributes accepted by SNS and SQS.
+const MAX_MESSAGE_ATTRIBUTES: usize = 10;
+
+/// The destination of an `AwsPublisher`.
+#[derive(Debug, Clone)]
+pub enum AwsDestination {
+    /// An SNS topic.
+    Sns {
+        client: aws_sdk_sns::Client,
+        topic_arn: String,
+    },
+    /// An SQS queue.
+    Sqs {
+        client: aws_sdk_sqs::Client,
+        queue_url: String,
+    },
+}
+
+impl AwsDestination {
+    /// Creates an SNS topic destination.
+    pub fn sns(client: aws_sdk_sns::Client, topic_arn: impl Into<String>) -> Self {
+        Self::Sns {
+            client,
+            topic_arn: topic_arn.into(),
+        }
+    }
+
+    /// Creates an SQS queue destination.
+    pub fn sqs(client: aws_sdk_sqs::Client, queue_url: impl Into<String>) -> Self {
+        Self::Sqs {
+            client,
+            queue_url: queue_url.into(),
+        }
+    }
+}
+
+struct Message {
+    event_id: PgEventId,
+    body: String,
+    attributes: Vec<(String, String)>,
+    group_id: String,
+}
+
+/// The `AwsPublisher` publishes the events matching its query to an SNS topic or an SQS queue.
+///
+/// # Overview
+///
+/// Each event is serialized with the provided serializer and published as the body of a message, so the
+/// serializer must produce UTF-8 payloads, e.g. JSON. The `event_id` and `event_type` message attributes carry
+/// the ID and the name of the event, and each domain identifier of the event is added as a message attribute
+/// named after the identifier, so subscriptions can filter the events on the server side. SNS and SQS accept
+/// up to 10 message attributes: the identifiers beyond the first 8 are not added as attributes and are only
+/// available in the body of the message.
+///
+/// The publisher overrides `EventListener::handle_batch` and sends the events with the `PublishBatch` and
+/// `SendMessageBatch` APIs, up to 10 events per request. Configure the batch size of the listener with
+/// `PgEventListenerConfig::batch_size` to take advantage of it. The checkpoint of the listener moves only past
+/// the events accepted by AWS: when an entry of a batch fails, the events after the last accepted one are
+/// published again at the next run.
+///
+/// For FIFO topics and queues, enable `fifo`: the deduplication ID is set to the event ID and the message
+/// group ID is built from the domain identifiers of the event.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_serde::serde::json::Json;
+/// use disintegrate_postgres::{AwsDestination, AwsPublisher};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// fn publisher(client: aws_sdk_sns::Client) -> AwsPublisher<OrderPlaced, Json<OrderPlaced>> {
+///     AwsPublisher::new(
+///         "order_publisher",
+///         AwsDestination::sns(client, "arn:aws:sns:eu-west-1:123456789012:orders"),
+///         Json::default(),
+///     )
+/// }
+/// ```
+pub struct AwsPublisher<E: Event + Clone, S: Serializer<E>> {
+    id: &'static str,
+    destination: AwsDestination,
+    query: StreamQuery<PgEventId, E>,
+    serde: S,
+    fifo: bool,
+    _event: PhantomData<E>,
+}
+
+impl<E: Event + Clone, S: Serializer<E>> AwsPublisher<E, S> {
+    /// Creates a new `AwsPublisher`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `destination` - The SNS topic or SQS queue where the events are published.
+    /// * `serde` - The serializer used to build the message body.
+    pub fn new(id: &'static str, destination: AwsDestination, serde: S) -> Self {
+        Self {
+            id,
+            destination,
+            query: disintegrate::query!(E),
+            serde,
+            fifo: false,
+            _event: PhantomData,
+        }
+    }
+
+    /// Sets the deduplication ID and the message group ID of the messages, as required by FIFO topics and queues.
+    pub fn fifo(mut self) -> Self {
+        self.fifo = true;
+        self
+    }
+
+    /// Overrides the stream query of the listener. By default, the listener publishes all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+
+    fn message(&self, event: PersistedEvent<PgEventId, E>) -> Result<Message, Error> {
+        let event_id = event.id();
+        let mut attributes = vec![
+            (EVENT_ID_ATTRIBUTE.to_string(), event_id.to_string()),
+            (EVENT_TYPE_ATTRIBUTE.to_string(), event.name().to_string()),
+        ];
+        let mut group_id = event.name().to_string();
+        for (key, value) in event.domain_identifiers().iter() {
+            if attributes.len() < MAX_MESSAGE_ATTRIBUTES {
+                attributes.push((key.to_string(), value.to_string()));
+            }
+            group_id = format!("{group_id}:{value}");
+        }
+        let body = String::from_utf8(self.serde.serialize(event.into_inner()))
+            .map_err(|_| Error::InvalidPayload { event_id })?;
+        Ok(Message {
+            event_id,
+            body,
+            attributes,
+            group_id,
+        })
+    }
+
+    /// Publishes the messages and returns the index of the first rejected message, if any.
+    async fn publish(&self, messages: &[Message]) -> Result<Option<usize>, BoxDynError> {
+        let failed: Vec<String> = match &self.destination {
+            AwsDestination::Sns { client, topic_arn } => {
+                let mut request = client.publish_batch().topic_arn(topic_arn);
+                for (index, message) in messages.iter().enumerate() {
+                    request =
+                        request.publish_batch_request_entries(self.sns_entry(index, message)?);
+                }
+                let output = request.send().await?;
+                output.failed().iter().map(|f| f.id().to_string()).collect()
+            }
+            AwsDestination::Sqs { client, queue_url } => {
+                let mut request = client.send_message_batch().queue_url(queue_url);
+                for (index, message) in messages.iter().enumerate() {
+                    request = request.entries(self.sqs_entry(index, message)?);
+                }
+                let output = request.send().await?;
+                output.failed().iter().map(|f| f.id().to_string()).collect()
+            }
+        };
+        first_rejected(&failed)
+    }
+
+    fn sns_entry(
+        &self,
+        index: usize,
+        message: &Message,
+    ) -> Result<aws_sdk_sns::types::PublishBatchRequestEntry, BoxDynError> {
+        use aws_sdk_sns::types::{MessageAttributeValue, PublishBatchRequestEntry};
+
+        let mut entry = PublishBatchRequestEntry::builder()
+            .id(index.to_string())
+            .message(&message.body);
+        for (name, value) in &message.attributes {
+            entry = entry.message_attributes(
+                name,
+                MessageAttributeValue::builder()
+                    .data_type("String")
+                    .string_value(value)
+                    .build()?,
+            );
+        }
+        if self.fifo {
+            entry = entry
+                .message_deduplication_id(message.event_id.to_string())
+                .message_group_id(&message.group_id);
+        }
+        Ok(entry.build()?)
+    }
+
+    fn sqs_entry(
+        &self,
+        index: usize,
+        message: &Message,
+    ) -> Result<aws_sdk_sqs::types::SendMessageBatchRequestEntry, BoxDynError> {
+        use aws_sdk_sqs::types::{MessageAttributeValue, SendMessageBatchRequestEntry};
+
+        let mut entry = SendMessageBatchRequestEntry::builder()
+            .id(index.to_string())
+            .message_body(&message.body);
+        for (name, value) in &message.attributes {
+            entry = entry.message_attributes(
+                name,
+                MessageAttributeValue::builder()
+                    .data_type("String")
+                    .string_value(value)
+                    .build()?,
+            );
+        }
+        if self.fifo {
+            entry = entry
+                .message_deduplication_id(message.event_id.to_string())
+                .message_group_id(&message.group_id);
+        }
+        Ok(entry.build()?)
+    }
+}
+
+/// Returns the index of the first rejected message from the IDs of the failed entries.
+///
+/// An ID that is not the index of a message fails the whole batch, so the checkpoint never moves past a
+/// rejected event.
+fn first_rejected(failed: &[String]) -> Result<Option<usize>, BoxDynError> {
+    let mut first = None;
+    for id in failed {
+        let index: usize = id
+            .parse()
+            .map_err(|_| format!("unknown batch entry ID {id}"))?;
+        first = Some(first.map_or(index, |first: usize| first.min(index)));
+    }
+    Ok(first)
+}
+
+/// AWS publisher error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The serialized event is not valid UTF-8.
+    #[error("payload of event {event_id} is not valid UTF-8")]
+    InvalidPayload { event_id: PgEventId },
+    /// The request to AWS failed.
+    #[error("unable to publish events: {0}")]
+    Aws(#[source] BoxDynError),
+    /// AWS rejected the event.
+    #[error("event {event_id} rejected")]
+    Rejected { event_id: PgEventId },
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidPayload { .. } => ErrorKind::Corruption,
+            Error::Aws(_) | Error::Rejected { .. } => ErrorKind::Transient,
+        }
+    }
+}
+
+#[async_trait]
+impl<E, S> EventListener<PgEventId, E> for AwsPublisher<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serializer<E> + Send + Sync,
+{
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        self.handle_batch(vec![event])
+            .await
+            .map_err(|BatchError { error, .. }| error)
+    }
+
+    async fn handle_batch(
+        &self,
+        events: Vec<PersistedEvent<PgEventId, E>>,
+    ) -> Result<(), BatchError<PgEventId, Self::Error>>
+    where
+        E: Send + 'async_trait,
+    {
+        let mut last_handled_event_id = None;
+        let mut messages = Vec::with_capacity(events.len());
+        for event in events {
+            messages.push(self.message(event).map_err(|error| BatchError {
+                last_handled_event_id,
+                error,
+            })?);
+        }
+        for chunk in messages.chunks(MAX_BATCH_ENTRIES) {
+            match self.publish(chunk).await {
+                Ok(None) => {
+                    last_handled_event_id = chunk.last().map(|m| m.event_id);
+                }
+                Ok(Some(failed)) => {
+                    if failed > 0 {
+                        last_handled_event_id = Some(chunk[failed - 1].event_id);
+                    }
+                    return Err(BatchError {
+                        last_handled_event_id,
+                        error: Error::Rejected {
+                            event_id: chunk[failed].event_id,
+                        },
+                    });
+                }
+                Err(err) => {
+                    return Err(BatchError {
+                        last_handled_event_id,
+                        error: Error::Aws(err),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_returns_the_first_rejected_message() {
+        let failed = vec!["3".to_string(), "1".to_string()];
+
+        assert_eq!(first_rejected(&failed).unwrap(), Some(1));
+        assert_eq!(first_rejected(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn it_fails_on_unknown_batch_entry_ids() {
+        let failed = vec!["1".to_string(), "unknown".to_string()];
+
+        assert!(first_rejected(&failed).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Criteria evaluated by the database on the events fetched for an event listener.
+use std::collections::BTreeMap;
+
+use disintegrate::{Identifier, IdentifierValue, IntoIdentifierValue};
+
+/// Restricts the events fetched for an event listener.
+///
+/// # Overview
+///
+/// The filter is added to the SQL query run by the listener poller, on top of the listener query, so the
+/// events that the listener would ignore are never read from the database. It can restrict the event types
+/// and the values of the domain identifiers: an event is fetched only if its type is one of the given types
+/// and, for each filtered identifier, its value is one of the given values. Events without a filtered
+/// identifier are not fetched.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::ident;
+/// use disintegrate_postgres::{PgEventListenerConfig, PgEventListenerFilter};
+/// use std::time::Duration;
+///
+/// let config = PgEventListenerConfig::poller(Duration::from_secs(5)).filter(
+///     PgEventListenerFilter::new()
+///         .events(&["OrderPlaced", "OrderShipped"])
+///         .identifier_in(ident!(#region), ["eu-west", "eu-south"]),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgEventListenerFilter {
+    events: Option<&'static [&'static str]>,
+    identifiers: BTreeMap<Identifier, Vec<IdentifierValue>>,
+}
+
+impl PgEventListenerFilter {
+    /// Creates a filter that does not restrict the events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the fetched events to the given event types.
+    pub fn events(mut self, events: &'static [&'static str]) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Restricts the fetched events to the ones having one of the given values for the domain identifier.
+    pub fn identifier_in<V: IntoIdentifierValue>(
+        mut self,
+        identifier: Identifier,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.identifiers.entry(identifier).or_default().extend(
+            values
+                .into_iter()
+                .map(IntoIdentifierValue::into_identifier_value),
+        );
+        self
+    }
+
+    /// Builds the SQL criteria of the filter, if it restricts the events.
+    pub(crate) fn criteria(&self) -> Option<String> {
+        let mut conditions = vec![];
+        if let Some(events) = self.events {
+            conditions.push(in_criteria(
+                "event_type",
+                events.iter().map(|event| quote(event)),
+            ));
+        }
+        for (identifier, values) in &self.identifiers {
+            conditions.push(in_criteria(
+                identifier.into_inner(),
+                values.iter().map(|value| match value {
+                    IdentifierValue::String(value) => quote(value),
+                    IdentifierValue::i64(value) => value.to_string(),
+                    IdentifierValue::Uuid(value) => quote(&value.to_string()),
+                }),
+            ));
+        }
+        (!conditions.is_empty()).then(|| conditions.join(" AND "))
+    }
+}
+
+fn in_criteria(column: &str, values: impl Iterator<Item = String>) -> String {
+    let values: Vec<String> = values.collect();
+    if values.is_empty() {
+        return "FALSE".to_string();
+    }
+    format!("{column} IN ({})", values.join(", "))
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::ident;
+
+    #[test]
+    fn it_builds_the_filter_criteria() {
+        assert_eq!(PgEventListenerFilter::new().criteria(), None);
+
+        let filter = PgEventListenerFilter::new()
+            .events(&["OrderPlaced", "OrderShipped"])
+            .identifier_in(ident!(#order_id), [1_i64, 2])
+            .identifier_in(ident!(#region), ["eu-west", "o'hare"]);
+
+        assert_eq!(
+            filter.criteria().unwrap(),
+            "event_type IN ('OrderPlaced', 'OrderShipped') AND order_id IN (1, 2) AND region IN ('eu-west', 'o''hare')"
+        );
+    }
+
+    #[test]
+    fn it_matches_nothing_without_values() {
+        let filter =
+            PgEventListenerFilter::new().identifier_in(ident!(#region), Vec::<&str>::new());
+
+        assert_eq!(filter.criteria().unwrap(), "FALSE");
+    }
+}
@@ -2,9 +2,12 @@
 use std::{collections::BTreeMap, marker::PhantomData};
 
 use async_trait::async_trait;
-use disintegrate::{DomainIdentifierSet, Event, EventListener, PersistedEvent, StreamQuery};
+use disintegrate::{
+    Classify, DomainIdentifierSet, ErrorKind, Event, EventListener, PersistedEvent, StreamQuery,
+};
 use sqlx::{PgPool, Postgres};
 
+use crate::error::sqlx_error_kind;
 use crate::PgEventId;
 
 /// The `PgIdIndexer` is a helper to index existing fields that have been newly tagged with the `#[id]` attribute in events.
@@ -107,6 +110,12 @@ impl<E: Event + Clone> PgIdIndexer<E> {
 #[error(transparent)]
 pub struct Error(#[from] sqlx::Error);
 
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        sqlx_error_kind(&self.0)
+    }
+}
+
 #[async_trait]
 impl<E: Event + Clone + Send + Sync> EventListener<PgEventId, E> for PgIdIndexer<E> {
     type Error = Error;
@@ -0,0 +1,273 @@
+//! An `EventListener` implementation that publishes events to Kafka topics.
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use disintegrate::{
+    Classify, DomainIdentifierSet, ErrorKind, Event, EventListener, Identifier, PersistedEvent,
+    StreamQuery,
+};
+use disintegrate_serde::Serializer;
+use md5::{Digest, Md5};
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::Client;
+use rskafka::record::Record;
+
+use crate::PgEventId;
+
+/// The header containing the ID of the published event.
+pub const EVENT_ID_HEADER: &str = "disintegrate-event-id";
+/// The header containing the name of the published event.
+pub const EVENT_TYPE_HEADER: &str = "disintegrate-event-type";
+
+type TopicRouter<E> = Box<dyn Fn(&E) -> String + Send + Sync>;
+
+/// The `KafkaPublisher` publishes the events matching its query to Kafka.
+///
+/// # Overview
+///
+/// Each event is serialized with the provided serializer and published as the value of a Kafka record.
+/// The record key is the event ID, so consumers can use it to discard duplicated records, and the
+/// `disintegrate-event-id` and `disintegrate-event-type` headers carry the ID and the name of the event.
+///
+/// By default, each event is published to a topic named after the event type. A single topic or a custom
+/// routing function can be configured instead. When a topic has more partitions, the partition is chosen by
+/// hashing the value of the partition key, a domain identifier of the entity, so the events of the same entity
+/// keep their order whatever other identifiers they carry. The events without the partition key, or all the
+/// events when no partition key is set, are published to partition 0.
+///
+/// The `handle` method returns only after the broker has acknowledged the record, so the listener moves its
+/// checkpoint only for events that have been stored by Kafka.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_serde::serde::json::Json;
+/// use disintegrate_postgres::KafkaPublisher;
+/// use rskafka::client::ClientBuilder;
+/// use serde::{Serialize, Deserialize};
+/// use std::sync::Arc;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// async fn publisher() -> Result<KafkaPublisher<OrderPlaced, Json<OrderPlaced>>, rskafka::client::error::Error> {
+///     let client = ClientBuilder::new(vec!["localhost:9092".to_owned()]).build().await?;
+///     Ok(KafkaPublisher::new("order_publisher", Arc::new(client), Json::default())
+///         .topic("orders")
+///         .partitions(3)
+///         .partition_by(disintegrate::ident!(#order_id)))
+/// }
+/// ```
+pub struct KafkaPublisher<E: Event + Clone, S: Serializer<E>> {
+    id: &'static str,
+    client: Arc<Client>,
+    query: StreamQuery<PgEventId, E>,
+    serde: S,
+    router: TopicRouter<E>,
+    partitions: i32,
+    partition_key: Option<Identifier>,
+    partition_clients: Mutex<HashMap<(String, i32), Arc<PartitionClient>>>,
+    _event: PhantomData<E>,
+}
+
+impl<E: Event + Clone, S: Serializer<E>> KafkaPublisher<E, S> {
+    /// Creates a new `KafkaPublisher`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `client` - The Kafka client used to publish the events.
+    /// * `serde` - The serializer used to build the record value.
+    pub fn new(id: &'static str, client: Arc<Client>, serde: S) -> Self {
+        Self {
+            id,
+            client,
+            query: disintegrate::query!(E),
+            serde,
+            router: Box::new(|event: &E| event.name().to_string()),
+            partitions: 1,
+            partition_key: None,
+            partition_clients: Mutex::new(HashMap::new()),
+            _event: PhantomData,
+        }
+    }
+
+    /// Publishes all the events to the given topic.
+    pub fn topic(self, topic: impl Into<String>) -> Self {
+        let topic = topic.into();
+        self.route(move |_| topic.clone())
+    }
+
+    /// Sets the function returning the topic of an event. By default, the topic is the event name.
+    pub fn route(mut self, router: impl Fn(&E) -> String + Send + Sync + 'static) -> Self {
+        self.router = Box::new(router);
+        self
+    }
+
+    /// Sets the number of partitions of the topics. By default, the events are published to partition 0.
+    pub fn partitions(mut self, partitions: i32) -> Self {
+        assert!(partitions > 0, "partitions must be greater than 0");
+        self.partitions = partitions;
+        self
+    }
+
+    /// Sets the domain identifier whose value chooses the partition of an event.
+    ///
+    /// The events carrying the same value are published to the same partition, in order.
+    pub fn partition_by(mut self, identifier: Identifier) -> Self {
+        self.partition_key = Some(identifier);
+        self
+    }
+
+    /// Overrides the stream query of the listener. By default, the listener publishes all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+
+    async fn partition_client(
+        &self,
+        topic: &str,
+        partition: i32,
+    ) -> Result<Arc<PartitionClient>, rskafka::client::error::Error> {
+        let key = (topic.to_string(), partition);
+        if let Some(client) = self.partition_clients.lock().unwrap().get(&key) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(
+            self.client
+                .partition_client(topic, partition, UnknownTopicHandling::Retry)
+                .await?,
+        );
+        self.partition_clients
+            .lock()
+            .unwrap()
+            .insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+/// Kafka publisher error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The broker did not acknowledge the event.
+    #[error("unable to publish event {event_id} to {topic}: {source}")]
+    Publish {
+        topic: String,
+        event_id: PgEventId,
+        #[source]
+        source: rskafka::client::error::Error,
+    },
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Transient
+    }
+}
+
+#[async_trait]
+impl<E, S> EventListener<PgEventId, E> for KafkaPublisher<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serializer<E> + Send + Sync,
+{
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        let event_id = event.id();
+        let topic = (self.router)(&event);
+        let partition = partition(
+            &event.domain_identifiers(),
+            self.partition_key.as_ref(),
+            self.partitions,
+        );
+        let headers = BTreeMap::from([
+            (
+                EVENT_ID_HEADER.to_string(),
+                event_id.to_string().into_bytes(),
+            ),
+            (
+                EVENT_TYPE_HEADER.to_string(),
+                event.name().as_bytes().to_vec(),
+            ),
+        ]);
+        let record = Record {
+            key: Some(event_id.to_string().into_bytes()),
+            value: Some(self.serde.serialize(event.into_inner())),
+            headers,
+            timestamp: chrono::Utc::now(),
+        };
+        let publish_error = |source| Error::Publish {
+            topic: topic.clone(),
+            event_id,
+            source,
+        };
+        self.partition_client(&topic, partition)
+            .await
+            .map_err(publish_error)?
+            .produce(vec![record], Compression::NoCompression)
+            .await
+            .map_err(publish_error)?;
+        Ok(())
+    }
+}
+
+fn partition(
+    domain_identifiers: &DomainIdentifierSet,
+    partition_key: Option<&Identifier>,
+    partitions: i32,
+) -> i32 {
+    if partitions == 1 {
+        return 0;
+    }
+    let Some(value) = partition_key.and_then(|key| domain_identifiers.get(key)) else {
+        return 0;
+    };
+    let digest = Md5::digest(value.to_string());
+    let hash = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    (hash % partitions as u32) as i32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{domain_identifiers, ident};
+
+    #[test]
+    fn it_assigns_the_same_partition_to_the_same_partition_key() {
+        let cart_id = ident!(#cart_id);
+        let cart_1 = domain_identifiers! {cart_id: "cart_1"};
+        let cart_1_item = domain_identifiers! {cart_id: "cart_1", item_id: "item_1"};
+        let cart_2 = domain_identifiers! {cart_id: "cart_2"};
+
+        assert_eq!(partition(&cart_1, Some(&cart_id), 1), 0);
+        assert_eq!(
+            partition(&cart_1, Some(&cart_id), 8),
+            partition(&cart_1_item, Some(&cart_id), 8)
+        );
+        assert!((0..8).contains(&partition(&cart_2, Some(&cart_id), 8)));
+    }
+
+    #[test]
+    fn it_assigns_the_first_partition_without_the_partition_key() {
+        let item_1 = domain_identifiers! {item_id: "item_1"};
+
+        assert_eq!(partition(&item_1, Some(&ident!(#cart_id)), 8), 0);
+        assert_eq!(partition(&item_1, None, 8), 0);
+    }
+}
@@ -0,0 +1,308 @@
+//! Metrics of the event listeners.
+//!
+//! The metrics are collected by the `PgEventListener` for each registered listener and can be read
+//! through `PgEventListenerMetrics`. With the `metrics` feature, they are also reported to the `metrics` crate facade.
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use disintegrate::{ComponentHealth, HealthStatus};
+
+use crate::PgEventId;
+
+/// The number of batch latencies used to compute the percentiles.
+const LATENCY_SAMPLES: usize = 1024;
+/// The time window used to compute the throughput.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A snapshot of the metrics of an event listener.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListenerMetrics {
+    /// The ID of the last event processed by the listener.
+    pub last_processed_event_id: PgEventId,
+    /// The ID of the last event in the event store when the listener last ran.
+    pub head_event_id: PgEventId,
+    /// The number of event IDs between the last processed event and the head of the event store.
+    ///
+    /// Events that do not match the query of the listener are counted as well.
+    pub lag: i64,
+    /// The number of events processed since the listener started.
+    pub events_processed: u64,
+    /// The events processed per second over the last minute.
+    pub throughput: f64,
+    /// The median time spent handling a batch of events.
+    pub latency_p50: Duration,
+    /// The 90th percentile of the time spent handling a batch of events.
+    pub latency_p90: Duration,
+    /// The 99th percentile of the time spent handling a batch of events.
+    pub latency_p99: Duration,
+    /// The time when the listener last handled a batch of events successfully, if any.
+    pub last_handled_at: Option<SystemTime>,
+    /// The last error returned by the listener, if any.
+    pub last_error: Option<ListenerFailure>,
+}
+
+impl ListenerMetrics {
+    /// Returns the last error of the listener, if it did not handle a batch successfully afterwards.
+    pub fn failure(&self) -> Option<&ListenerFailure> {
+        self.last_error.as_ref().filter(|error| {
+            self.last_handled_at
+                .is_none_or(|handled_at| handled_at < error.occurred_at)
+        })
+    }
+}
+
+/// An error returned by an event listener.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerFailure {
+    /// The message of the error.
+    pub message: String,
+    /// The time when the error occurred.
+    pub occurred_at: SystemTime,
+}
+
+type ListenersStats = HashMap<&'static str, Arc<Mutex<ListenerStats>>>;
+
+/// A handle to the metrics of the event listeners registered in a `PgEventListener`.
+///
+/// The handle can be cloned and read while the listeners are running.
+///
+/// The handle reports the health of the listeners: a listener is unhealthy when its last batch failed,
+/// and degraded when its lag exceeds the threshold set with `max_lag`.
+#[derive(Debug, Clone, Default)]
+pub struct PgEventListenerMetrics {
+    listeners: Arc<RwLock<ListenersStats>>,
+    max_lag: Option<i64>,
+}
+
+impl PgEventListenerMetrics {
+    /// Returns the metrics of the listener with the given ID.
+    pub fn get(&self, listener_id: &str) -> Option<ListenerMetrics> {
+        self.listeners
+            .read()
+            .unwrap()
+            .get(listener_id)
+            .map(|stats| stats.lock().unwrap().snapshot())
+    }
+
+    /// Returns the metrics of all the listeners.
+    pub fn all(&self) -> HashMap<&'static str, ListenerMetrics> {
+        self.listeners
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, stats)| (*id, stats.lock().unwrap().snapshot()))
+            .collect()
+    }
+
+    /// Reports the listeners whose lag exceeds `lag` as degraded.
+    pub fn max_lag(mut self, lag: i64) -> Self {
+        self.max_lag = Some(lag);
+        self
+    }
+
+    pub(crate) fn register(&self, listener_id: &'static str) -> ListenerRecorder {
+        let stats = Arc::clone(
+            self.listeners
+                .write()
+                .unwrap()
+                .entry(listener_id)
+                .or_default(),
+        );
+        ListenerRecorder { listener_id, stats }
+    }
+}
+
+#[async_trait]
+impl ComponentHealth for PgEventListenerMetrics {
+    fn name(&self) -> &str {
+        "event_listeners"
+    }
+
+    async fn health(&self) -> HealthStatus {
+        let mut failing = String::new();
+        let mut lagging = String::new();
+        let mut listeners: Vec<_> = self.all().into_iter().collect();
+        listeners.sort_by_key(|(id, _)| *id);
+        for (id, metrics) in listeners {
+            if let Some(error) = metrics.failure() {
+                let _ = write!(failing, "{}{id}: {}", separator(&failing), error.message);
+            } else if self.max_lag.is_some_and(|max_lag| metrics.lag > max_lag) {
+                let _ = write!(
+                    lagging,
+                    "{}{id} is {} events behind",
+                    separator(&lagging),
+                    metrics.lag
+                );
+            }
+        }
+        if !failing.is_empty() {
+            HealthStatus::Unhealthy(failing)
+        } else if !lagging.is_empty() {
+            HealthStatus::Degraded(lagging)
+        } else {
+            HealthStatus::Healthy
+        }
+    }
+}
+
+fn separator(message: &str) -> &'static str {
+    if message.is_empty() {
+        ""
+    } else {
+        ", "
+    }
+}
+
+#[derive(Debug, Default)]
+struct ListenerStats {
+    last_processed_event_id: PgEventId,
+    head_event_id: PgEventId,
+    events_processed: u64,
+    processed_window: VecDeque<(Instant, usize)>,
+    latencies: VecDeque<Duration>,
+    last_handled_at: Option<SystemTime>,
+    last_error: Option<ListenerFailure>,
+}
+
+impl ListenerStats {
+    fn snapshot(&self) -> ListenerMetrics {
+        let mut latencies: Vec<Duration> = self.latencies.iter().copied().collect();
+        latencies.sort();
+        let processed_in_window: usize = self
+            .processed_window
+            .iter()
+            .filter(|(at, _)| at.elapsed() <= THROUGHPUT_WINDOW)
+            .map(|(_, events)| events)
+            .sum();
+        ListenerMetrics {
+            last_processed_event_id: self.last_processed_event_id,
+            head_event_id: self.head_event_id,
+            lag: (self.head_event_id - self.last_processed_event_id).max(0),
+            events_processed: self.events_processed,
+            throughput: processed_in_window as f64 / THROUGHPUT_WINDOW.as_secs_f64(),
+            latency_p50: percentile(&latencies, 50),
+            latency_p90: percentile(&latencies, 90),
+            latency_p99: percentile(&latencies, 99),
+            last_handled_at: self.last_handled_at,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+/// Records the metrics of a single listener.
+#[derive(Debug, Clone)]
+pub(crate) struct ListenerRecorder {
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    listener_id: &'static str,
+    stats: Arc<Mutex<ListenerStats>>,
+}
+
+impl ListenerRecorder {
+    pub fn record_batch(&self, events: usize, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let now = Instant::now();
+        stats.events_processed += events as u64;
+        stats.processed_window.push_back((now, events));
+        while stats
+            .processed_window
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > THROUGHPUT_WINDOW)
+        {
+            stats.processed_window.pop_front();
+        }
+        if stats.latencies.len() == LATENCY_SAMPLES {
+            stats.latencies.pop_front();
+        }
+        stats.latencies.push_back(latency);
+        stats.last_handled_at = Some(SystemTime::now());
+        #[cfg(feature = "metrics")]
+        {
+            let listener = self.listener_id;
+            ::metrics::counter!("disintegrate_listener_events_total", "listener" => listener)
+                .increment(events as u64);
+            ::metrics::histogram!("disintegrate_listener_handle_seconds", "listener" => listener)
+                .record(latency.as_secs_f64());
+        }
+    }
+
+    pub fn record_error(&self, message: String) {
+        self.stats.lock().unwrap().last_error = Some(ListenerFailure {
+            message,
+            occurred_at: SystemTime::now(),
+        });
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("disintegrate_listener_errors_total", "listener" => self.listener_id)
+            .increment(1);
+    }
+
+    pub fn record_position(&self, last_processed_event_id: PgEventId, head_event_id: PgEventId) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.last_processed_event_id = last_processed_event_id;
+        stats.head_event_id = head_event_id;
+        #[cfg(feature = "metrics")]
+        ::metrics::gauge!("disintegrate_listener_lag", "listener" => self.listener_id)
+            .set((head_event_id - last_processed_event_id).max(0) as f64);
+    }
+}
+
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = (sorted.len() * percentile).div_ceil(100).saturating_sub(1);
+    sorted[index.min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn it_computes_the_listener_metrics() {
+        let metrics = PgEventListenerMetrics::default();
+        let recorder = metrics.register("carts");
+
+        for millis in 1..=100 {
+            recorder.record_batch(2, Duration::from_millis(millis));
+        }
+        recorder.record_position(200, 250);
+        recorder.record_error("connection lost".to_string());
+
+        let snapshot = metrics.get("carts").unwrap();
+        assert_eq!(snapshot.events_processed, 200);
+        assert_eq!(snapshot.lag, 50);
+        assert_eq!(snapshot.latency_p50, Duration::from_millis(50));
+        assert_eq!(snapshot.latency_p90, Duration::from_millis(90));
+        assert_eq!(snapshot.latency_p99, Duration::from_millis(99));
+        assert!((snapshot.throughput - 200.0 / 60.0).abs() < f64::EPSILON);
+        assert_eq!(snapshot.last_error.unwrap().message, "connection lost");
+        assert!(metrics.get("orders").is_none());
+    }
+
+    #[test]
+    fn it_reports_the_health_of_the_listeners() {
+        let metrics = PgEventListenerMetrics::default().max_lag(100);
+        let carts = metrics.register("carts");
+        let orders = metrics.register("orders");
+
+        carts.record_position(10, 500);
+        assert_eq!(
+            block_on(metrics.health()),
+            HealthStatus::Degraded("carts is 490 events behind".to_string())
+        );
+
+        orders.record_error("connection lost".to_string());
+        assert_eq!(
+            block_on(metrics.health()),
+            HealthStatus::Unhealthy("orders: connection lost".to_string())
+        );
+
+        orders.record_batch(1, Duration::from_millis(1));
+        carts.record_position(500, 500);
+        assert_eq!(block_on(metrics.health()), HealthStatus::Healthy);
+    }
+}
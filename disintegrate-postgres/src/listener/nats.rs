@@ -0,0 +1,205 @@
+//! An `EventListener` implementation that publishes events to NATS JetStream.
+use std::marker::PhantomData;
+
+use async_nats::jetstream::context::{Context, PublishError};
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use disintegrate::{Classify, ErrorKind, Event, EventListener, PersistedEvent, StreamQuery};
+use disintegrate_serde::Serializer;
+
+use crate::PgEventId;
+
+/// The header containing the name of the published event.
+pub const EVENT_TYPE_HEADER: &str = "Disintegrate-Event-Type";
+
+type SubjectRouter<E> = Box<dyn Fn(&E) -> String + Send + Sync>;
+
+/// The `NatsPublisher` publishes the events matching its query to NATS JetStream.
+///
+/// # Overview
+///
+/// Each event is serialized with the provided serializer and published as the payload of a JetStream message.
+/// The `Nats-Msg-Id` header is set to the event ID, so the stream discards the messages published twice
+/// within its duplicate window, and the `Disintegrate-Event-Type` header carries the name of the event.
+///
+/// By default, the subject is built from a prefix, the event name and the values of the domain identifiers
+/// of the event, e.g. `events.OrderPlaced.order-1`. A custom routing function can be configured instead.
+///
+/// The `handle` method returns only after the stream has acknowledged the message, so the listener moves its
+/// checkpoint only for events that have been stored by JetStream.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_serde::serde::json::Json;
+/// use disintegrate_postgres::NatsPublisher;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// async fn publisher() -> Result<NatsPublisher<OrderPlaced, Json<OrderPlaced>>, async_nats::ConnectError> {
+///     let client = async_nats::connect("localhost:4222").await?;
+///     Ok(NatsPublisher::new("order_publisher", async_nats::jetstream::new(client), Json::default())
+///         .subject_prefix("orders"))
+/// }
+/// ```
+pub struct NatsPublisher<E: Event + Clone, S: Serializer<E>> {
+    id: &'static str,
+    jetstream: Context,
+    query: StreamQuery<PgEventId, E>,
+    serde: S,
+    router: SubjectRouter<E>,
+    _event: PhantomData<E>,
+}
+
+impl<E: Event + Clone, S: Serializer<E>> NatsPublisher<E, S> {
+    /// Creates a new `NatsPublisher`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `jetstream` - The JetStream context used to publish the events.
+    /// * `serde` - The serializer used to build the message payload.
+    pub fn new(id: &'static str, jetstream: Context, serde: S) -> Self {
+        Self {
+            id,
+            jetstream,
+            query: disintegrate::query!(E),
+            serde,
+            router: Box::new(|event: &E| subject("events", event)),
+            _event: PhantomData,
+        }
+    }
+
+    /// Sets the prefix of the subjects. By default, the prefix is `events`.
+    pub fn subject_prefix(self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.route(move |event| subject(&prefix, event))
+    }
+
+    /// Sets the function returning the subject of an event.
+    pub fn route(mut self, router: impl Fn(&E) -> String + Send + Sync + 'static) -> Self {
+        self.router = Box::new(router);
+        self
+    }
+
+    /// Overrides the stream query of the listener. By default, the listener publishes all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+}
+
+/// NATS publisher error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The stream did not acknowledge the event.
+    #[error("unable to publish event {event_id} to {subject}: {source}")]
+    Publish {
+        subject: String,
+        event_id: PgEventId,
+        #[source]
+        source: PublishError,
+    },
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Transient
+    }
+}
+
+#[async_trait]
+impl<E, S> EventListener<PgEventId, E> for NatsPublisher<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serializer<E> + Send + Sync,
+{
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        let event_id = event.id();
+        let subject = (self.router)(&event);
+        let mut headers = HeaderMap::new();
+        headers.insert(async_nats::header::NATS_MESSAGE_ID, event_id.to_string());
+        headers.insert(EVENT_TYPE_HEADER, event.name());
+        let payload = self.serde.serialize(event.into_inner());
+        let publish_error = |source| Error::Publish {
+            subject: subject.clone(),
+            event_id,
+            source,
+        };
+        self.jetstream
+            .publish_with_headers(subject.clone(), headers, payload.into())
+            .await
+            .map_err(publish_error)?
+            .await
+            .map_err(publish_error)?;
+        Ok(())
+    }
+}
+
+fn subject<E: Event>(prefix: &str, event: &E) -> String {
+    let mut subject = format!("{prefix}.{}", event.name());
+    for value in event.domain_identifiers().values() {
+        subject.push('.');
+        subject.extend(value.to_string().chars().map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        }));
+    }
+    subject
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{domain_identifiers, DomainIdentifierSet, EventInfo, EventSchema};
+
+    #[derive(Clone)]
+    struct OrderPlaced {
+        order_id: String,
+    }
+
+    impl Event for OrderPlaced {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["OrderPlaced"],
+            events_info: &[&EventInfo {
+                name: "OrderPlaced",
+                domain_identifiers: &[],
+            }],
+            domain_identifiers: &[],
+        };
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {order_id: self.order_id}
+        }
+
+        fn name(&self) -> &'static str {
+            "OrderPlaced"
+        }
+    }
+
+    #[test]
+    fn it_builds_the_subject_from_the_event_type_and_identifiers() {
+        let event = OrderPlaced {
+            order_id: "order.1 *".to_string(),
+        };
+
+        assert_eq!(subject("orders", &event), "orders.OrderPlaced.order_1__");
+    }
+}
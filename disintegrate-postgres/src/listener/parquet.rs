@@ -0,0 +1,403 @@
+//! An `EventListener` implementation that exports events to Parquet files in an object store.
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BinaryArray, Int64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use disintegrate::{
+    BatchError, Classify, ErrorKind, Event, EventListener, PersistedEvent, Redactor, StreamQuery,
+};
+use disintegrate_serde::Serializer;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tokio::sync::Mutex;
+
+use crate::error::sqlx_error_kind;
+use crate::PgEventId;
+
+/// The directory, relative to the prefix, containing the manifests of the exported files.
+pub const MANIFESTS_DIR: &str = "_manifests";
+
+/// A manifest listing the files written for a batch of events.
+///
+/// Only the files listed in a manifest are committed: the files left by a failed export are not referenced and
+/// can be ignored, or deleted, by the readers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParquetManifest {
+    /// The ID of the first event of the batch.
+    pub first_event_id: PgEventId,
+    /// The ID of the last event of the batch.
+    pub last_event_id: PgEventId,
+    /// The paths of the Parquet files containing the events of the batch.
+    pub files: Vec<String>,
+}
+
+struct ExportedRow {
+    event_id: PgEventId,
+    event_type: &'static str,
+    inserted_at: i64,
+    identifiers: String,
+    payload: Vec<u8>,
+}
+
+/// The `ParquetExporter` exports the events matching its query to Parquet files in an object store,
+/// such as S3, GCS or Azure Blob Storage.
+///
+/// # Overview
+///
+/// Each batch of events is written to one Parquet file per event type and day, under
+/// `<prefix>/event_type=<type>/date=<YYYY-MM-DD>/<first event id>-<last event id>.parquet`, so query engines can
+/// prune the partitions. The day is the date, in UTC, when the event was appended. Each file has the columns
+/// `event_id`, `event_type`, `inserted_at`, `identifiers`, holding the domain identifiers as a JSON object, and
+/// `payload`, holding the event serialized with the provided serializer.
+///
+/// Once the files of a batch are written, a manifest listing them is written under `<prefix>/_manifests`, named
+/// after the last event ID of the batch. The manifest commits the files: if the listener fails before moving its
+/// checkpoint, the events already covered by a manifest are skipped when they are delivered again, so each event
+/// is committed exactly once. Readers should only consider the files listed in the manifests.
+///
+/// Configure the batch size of the listener with `PgEventListenerConfig::batch_size` to produce files of a
+/// reasonable size.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_serde::serde::json::Json;
+/// use disintegrate_postgres::ParquetExporter;
+/// use object_store::memory::InMemory;
+/// use serde::{Serialize, Deserialize};
+/// use sqlx::PgPool;
+/// use std::sync::Arc;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// fn exporter(pool: PgPool) -> ParquetExporter<OrderPlaced, Json<OrderPlaced>> {
+///     ParquetExporter::new("orders_export", pool, Arc::new(InMemory::new()), Json::default())
+///         .prefix("lake/orders")
+/// }
+/// ```
+pub struct ParquetExporter<E: Event + Clone, S: Serializer<E>> {
+    id: &'static str,
+    pool: PgPool,
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    query: StreamQuery<PgEventId, E>,
+    serde: S,
+    redactor: Option<Redactor>,
+    committed_event_id: Mutex<Option<PgEventId>>,
+    _event: PhantomData<E>,
+}
+
+impl<E: Event + Clone, S: Serializer<E>> ParquetExporter<E, S> {
+    /// Creates a new `ParquetExporter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `pool` - A `PgPool` instance for Postgres, used to read the append time of the events.
+    /// * `store` - The object store where the files are written.
+    /// * `serde` - The serializer used to build the `payload` column.
+    pub fn new(id: &'static str, pool: PgPool, store: Arc<dyn ObjectStore>, serde: S) -> Self {
+        Self {
+            id,
+            pool,
+            store,
+            prefix: Path::from(id),
+            query: disintegrate::query!(E),
+            serde,
+            redactor: None,
+            committed_event_id: Mutex::new(None),
+            _event: PhantomData,
+        }
+    }
+
+    /// Sets the path prefix of the exported files. By default, the files are written under the listener ID.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Path::from(prefix.into());
+        self
+    }
+
+    /// Masks the personal data of the exported payloads with the redactor.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Overrides the stream query of the listener. By default, the listener exports all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Returns the last event ID covered by a manifest, reading the manifests the first time.
+    async fn load_committed_event_id(&self) -> Result<PgEventId, Error> {
+        let mut committed_event_id = self.committed_event_id.lock().await;
+        if let Some(event_id) = *committed_event_id {
+            return Ok(event_id);
+        }
+        let manifests: Vec<_> = self
+            .store
+            .list(Some(&self.prefix.child(MANIFESTS_DIR)))
+            .try_collect()
+            .await?;
+        let event_id = manifests
+            .iter()
+            .filter_map(|meta| {
+                meta.location
+                    .filename()?
+                    .strip_suffix(".json")?
+                    .parse()
+                    .ok()
+            })
+            .max()
+            .unwrap_or_default();
+        *committed_event_id = Some(event_id);
+        Ok(event_id)
+    }
+
+    async fn export(&self, events: Vec<PersistedEvent<PgEventId, E>>) -> Result<(), Error> {
+        let committed_event_id = self.load_committed_event_id().await?;
+        let events: Vec<_> = events
+            .into_iter()
+            .filter(|event| event.id() > committed_event_id)
+            .collect();
+        let (Some(first_event_id), Some(last_event_id)) = (
+            events.first().map(|event| event.id()),
+            events.last().map(|event| event.id()),
+        ) else {
+            return Ok(());
+        };
+
+        let event_ids: Vec<PgEventId> = events.iter().map(|event| event.id()).collect();
+        let inserted_at: HashMap<PgEventId, i64> = sqlx::query(
+            "SELECT event_id, (EXTRACT(EPOCH FROM inserted_at) * 1000)::BIGINT FROM event WHERE event_id = ANY($1)",
+        )
+        .bind(&event_ids)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+        let mut partitions: BTreeMap<(&'static str, String), Vec<ExportedRow>> = BTreeMap::new();
+        for event in events {
+            let event_id = event.id();
+            let inserted_at = inserted_at.get(&event_id).copied().unwrap_or_default();
+            let date = chrono::DateTime::from_timestamp_millis(inserted_at)
+                .unwrap_or_default()
+                .format("%Y-%m-%d")
+                .to_string();
+            let identifiers: BTreeMap<String, String> = event
+                .domain_identifiers()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let event_type = event.name();
+            let payload = self.serde.serialize(event.into_inner());
+            let payload = match &self.redactor {
+                Some(redactor) => redactor.redact(event_type, &payload),
+                None => payload,
+            };
+            partitions
+                .entry((event_type, date))
+                .or_default()
+                .push(ExportedRow {
+                    event_id,
+                    event_type,
+                    inserted_at,
+                    identifiers: serde_json::to_string(&identifiers)?,
+                    payload,
+                });
+        }
+
+        let mut files = vec![];
+        for ((event_type, date), rows) in partitions {
+            let path = self
+                .prefix
+                .child(format!("event_type={event_type}"))
+                .child(format!("date={date}"))
+                .child(format!(
+                    "{}-{}.parquet",
+                    rows[0].event_id,
+                    rows[rows.len() - 1].event_id
+                ));
+            self.store
+                .put(&path, PutPayload::from(write_parquet(rows)?))
+                .await?;
+            files.push(path.to_string());
+        }
+
+        let manifest = ParquetManifest {
+            first_event_id,
+            last_event_id,
+            files,
+        };
+        self.store
+            .put(
+                &self
+                    .prefix
+                    .child(MANIFESTS_DIR)
+                    .child(format!("{last_event_id:020}.json")),
+                PutPayload::from(serde_json::to_vec(&manifest)?),
+            )
+            .await?;
+        *self.committed_event_id.lock().await = Some(last_event_id);
+        Ok(())
+    }
+}
+
+fn write_parquet(rows: Vec<ExportedRow>) -> Result<Vec<u8>, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("event_id", DataType::Int64, false),
+        Field::new("event_type", DataType::Utf8, false),
+        Field::new(
+            "inserted_at",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("identifiers", DataType::Utf8, false),
+        Field::new("payload", DataType::Binary, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|row| row.event_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.event_type),
+        )),
+        Arc::new(
+            TimestampMillisecondArray::from_iter_values(rows.iter().map(|row| row.inserted_at))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.identifiers.as_str()),
+        )),
+        Arc::new(BinaryArray::from_iter_values(
+            rows.iter().map(|row| row.payload.as_slice()),
+        )),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let mut buffer = vec![];
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+/// Parquet exporter error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Error returned from the database.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// Error returned from the object store.
+    #[error(transparent)]
+    ObjectStore(#[from] object_store::Error),
+    /// The record batch could not be built.
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+    /// The Parquet file could not be written.
+    #[error(transparent)]
+    Parquet(#[from] ParquetError),
+    /// The manifest or the identifiers could not be serialized.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::ObjectStore(_) | Error::Parquet(_) => ErrorKind::Io,
+            Error::Arrow(_) | Error::Json(_) => ErrorKind::Validation,
+        }
+    }
+}
+
+#[async_trait]
+impl<E, S> EventListener<PgEventId, E> for ParquetExporter<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serializer<E> + Send + Sync,
+{
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        self.export(vec![event]).await
+    }
+
+    async fn handle_batch(
+        &self,
+        events: Vec<PersistedEvent<PgEventId, E>>,
+    ) -> Result<(), BatchError<PgEventId, Self::Error>>
+    where
+        E: Send + 'async_trait,
+    {
+        self.export(events).await.map_err(|error| BatchError {
+            last_handled_event_id: None,
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn it_writes_the_rows_in_a_parquet_file() {
+        let rows = vec![
+            ExportedRow {
+                event_id: 1,
+                event_type: "OrderPlaced",
+                inserted_at: 1_700_000_000_000,
+                identifiers: r#"{"order_id":"o-1"}"#.to_string(),
+                payload: b"{}".to_vec(),
+            },
+            ExportedRow {
+                event_id: 2,
+                event_type: "OrderPlaced",
+                inserted_at: 1_700_000_000_001,
+                identifiers: r#"{"order_id":"o-2"}"#.to_string(),
+                payload: b"{}".to_vec(),
+            },
+        ];
+
+        let file = write_parquet(rows).unwrap();
+
+        let reader = SerializedFileReader::new(Bytes::from(file)).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .num_columns(),
+            5
+        );
+    }
+}
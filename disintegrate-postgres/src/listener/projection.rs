@@ -0,0 +1,629 @@
+//! An `EventListener` implementation that maintains a SQL read model from declarative mappings.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use disintegrate::{Classify, ErrorKind, Event, EventListener, PersistedEvent, StreamQuery};
+use sqlx::{PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::error::sqlx_error_kind;
+use crate::PgEventId;
+
+/// The name of the column used by `SqlProjection` to track the last event applied to each row.
+const EVENT_ID_COLUMN: &str = "event_id";
+
+/// A value that can be written into a projection column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Int(i32),
+    BigInt(i64),
+    Double(f64),
+    Text(String),
+    Uuid(Uuid),
+}
+
+macro_rules! impl_from_sql_value {
+    ($($ty:ty => $variant:ident),+) => {
+        $(impl From<$ty> for SqlValue {
+            fn from(value: $ty) -> Self {
+                Self::$variant(value.into())
+            }
+        })+
+    };
+}
+
+impl_from_sql_value! {
+    bool => Bool,
+    i32 => Int,
+    i64 => BigInt,
+    f64 => Double,
+    String => Text,
+    &str => Text,
+    &String => Text,
+    Uuid => Uuid
+}
+
+impl<T: Into<SqlValue>> From<Option<T>> for SqlValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(SqlValue::Null)
+    }
+}
+
+/// A set of column values produced by a projection mapping.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectionRow(Vec<(&'static str, SqlValue)>);
+
+impl ProjectionRow {
+    /// Creates an empty row.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of a column.
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - The name of the column.
+    /// * `value` - The value to write into the column.
+    pub fn set(mut self, column: &'static str, value: impl Into<SqlValue>) -> Self {
+        self.0.retain(|(c, _)| *c != column);
+        self.0.push((column, value.into()));
+        self
+    }
+
+    fn get(&self, column: &str) -> Option<&SqlValue> {
+        self.0.iter().find(|(c, _)| *c == column).map(|(_, v)| v)
+    }
+}
+
+/// A change to be applied to the projection table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectionChange {
+    /// Inserts the row, or updates the provided columns if a row with the same key already exists.
+    Upsert(ProjectionRow),
+    /// Deletes the row identified by the key columns.
+    Delete(ProjectionRow),
+}
+
+type Mapping<E> = Arc<dyn Fn(&E) -> Option<ProjectionChange> + Send + Sync>;
+
+/// The `SqlProjection` maintains a table from mechanical event to row mappings.
+///
+/// # Overview
+///
+/// Most read models are a direct translation of events into rows: an event inserts or updates a row,
+/// another event deletes it. `SqlProjection` lets you declare the target table and the mappings from
+/// events to rows, and it takes care of the SQL.
+///
+/// Every row of the projection table stores the `event_id` of the last event applied to it.
+/// Changes coming from an event older than (or equal to) the stored one are skipped, so the projection
+/// is idempotent and can safely handle the duplicated deliveries of the at-least-once listener.
+/// A deleted row leaves a tombstone with the `event_id` of the delete in the `<table>_tombstone` table,
+/// so an older upsert delivered again after the delete does not bring the row back.
+/// All the changes produced by a single event are applied in the same transaction.
+///
+/// # Delivery
+///
+/// The projection is at-least-once, not transactionally checkpointed: the changes are committed in their
+/// own transaction, not in the one of the listener checkpoint. The checkpoint transaction of a
+/// `PgEventListener` spans a whole poll and the batches of a `PerIdentifier` delivery are handled
+/// concurrently, so the writes of the projection cannot share it. If the listener stops after the changes
+/// are committed and before the checkpoint is, the events are delivered again and skipped thanks to the
+/// `event_id` of the rows and of the tombstones, so the table ends up as if every event were applied once.
+/// Until the checkpoint commits, the table may be ahead of it. Tombstones are never removed, so the
+/// tombstone table grows with the deleted keys.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_postgres::{ProjectionRow, SqlProjection};
+/// use serde::{Serialize, Deserialize};
+/// use sqlx::PgPool;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// enum CartEvent {
+///     ItemAdded {
+///         #[id]
+///         cart_id: String,
+///         #[id]
+///         item_id: String,
+///         quantity: i32,
+///     },
+///     ItemRemoved {
+///         #[id]
+///         cart_id: String,
+///         #[id]
+///         item_id: String,
+///     },
+/// }
+///
+/// async fn cart_items(pool: PgPool) -> Result<SqlProjection<CartEvent>, sqlx::Error> {
+///     SqlProjection::builder("cart_items", pool, "cart_item")
+///         .column("cart_id", "TEXT")
+///         .column("item_id", "TEXT")
+///         .column("quantity", "INT")
+///         .key(&["cart_id", "item_id"])
+///         .upsert(|event| match event {
+///             CartEvent::ItemAdded { cart_id, item_id, quantity } => Some(
+///                 ProjectionRow::new()
+///                     .set("cart_id", cart_id)
+///                     .set("item_id", item_id)
+///                     .set("quantity", *quantity),
+///             ),
+///             _ => None,
+///         })
+///         .delete(|event| match event {
+///             CartEvent::ItemRemoved { cart_id, item_id } => Some(
+///                 ProjectionRow::new()
+///                     .set("cart_id", cart_id)
+///                     .set("item_id", item_id),
+///             ),
+///             _ => None,
+///         })
+///         .build()
+///         .await
+/// }
+/// ```
+///
+/// The returned `SqlProjection` is an `EventListener` and can be registered in a `PgEventListener`.
+pub struct SqlProjection<E: Event + Clone> {
+    id: &'static str,
+    pool: PgPool,
+    table: &'static str,
+    key: Vec<&'static str>,
+    query: StreamQuery<PgEventId, E>,
+    mappings: Vec<Mapping<E>>,
+}
+
+impl<E: Event + Clone> SqlProjection<E> {
+    /// Creates a new `SqlProjectionBuilder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `pool` - A `PgPool` instance for Postgres.
+    /// * `table` - The name of the table maintained by the projection.
+    pub fn builder(id: &'static str, pool: PgPool, table: &'static str) -> SqlProjectionBuilder<E> {
+        SqlProjectionBuilder {
+            id,
+            pool,
+            table,
+            columns: vec![],
+            key: vec![],
+            query: disintegrate::query!(E),
+            mappings: vec![],
+        }
+    }
+}
+
+/// Builder of a `SqlProjection`.
+pub struct SqlProjectionBuilder<E: Event + Clone> {
+    id: &'static str,
+    pool: PgPool,
+    table: &'static str,
+    columns: Vec<(&'static str, &'static str)>,
+    key: Vec<&'static str>,
+    query: StreamQuery<PgEventId, E>,
+    mappings: Vec<Mapping<E>>,
+}
+
+impl<E: Event + Clone> SqlProjectionBuilder<E> {
+    /// Declares a column of the projection table.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the column.
+    /// * `sql_type` - The SQL type of the column (e.g. `TEXT`, `BIGINT`).
+    pub fn column(mut self, name: &'static str, sql_type: &'static str) -> Self {
+        self.columns.push((name, sql_type));
+        self
+    }
+
+    /// Declares the columns that identify a row of the projection table.
+    pub fn key(mut self, columns: &[&'static str]) -> Self {
+        self.key = columns.to_vec();
+        self
+    }
+
+    /// Overrides the stream query of the projection. By default, the projection handles all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Registers an upsert mapping.
+    ///
+    /// When the mapping returns a row, the row is inserted in the table, or the provided columns are updated if
+    /// a row with the same key already exists. The row must contain all the key columns.
+    pub fn upsert(
+        mut self,
+        mapping: impl Fn(&E) -> Option<ProjectionRow> + Send + Sync + 'static,
+    ) -> Self {
+        self.mappings
+            .push(Arc::new(move |e| mapping(e).map(ProjectionChange::Upsert)));
+        self
+    }
+
+    /// Registers a delete mapping.
+    ///
+    /// When the mapping returns a row, the row with the same key is deleted from the table.
+    /// The row must contain all the key columns.
+    pub fn delete(
+        mut self,
+        mapping: impl Fn(&E) -> Option<ProjectionRow> + Send + Sync + 'static,
+    ) -> Self {
+        self.mappings
+            .push(Arc::new(move |e| mapping(e).map(ProjectionChange::Delete)));
+        self
+    }
+
+    /// Creates the projection and tombstone tables, if they do not exist, and returns the `SqlProjection`.
+    pub async fn build(self) -> Result<SqlProjection<E>, sqlx::Error> {
+        sqlx::query(&create_table_sql(self.table, &self.columns, &self.key))
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(&create_tombstone_table_sql(
+            self.table,
+            &self.columns,
+            &self.key,
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(self.build_uninitialized())
+    }
+
+    /// Returns the `SqlProjection` without creating the projection and tombstone tables.
+    ///
+    /// The table must contain the declared columns, the key columns as primary key
+    /// and a `BIGINT` `event_id` column. The `<table>_tombstone` table must contain the key columns
+    /// as primary key and a `BIGINT` `event_id` column.
+    pub fn build_uninitialized(self) -> SqlProjection<E> {
+        assert!(!self.key.is_empty(), "a projection requires a key");
+        SqlProjection {
+            id: self.id,
+            pool: self.pool,
+            table: self.table,
+            key: self.key,
+            query: self.query,
+            mappings: self.mappings,
+        }
+    }
+}
+
+/// PostgreSQL SQL Projection error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// An error occurred while applying the changes to the projection table.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// A row produced by a mapping does not contain a key column of the projection.
+    #[error("the row must contain the key column {0}")]
+    MissingKeyColumn(&'static str),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::MissingKeyColumn(_) => ErrorKind::Validation,
+        }
+    }
+}
+
+#[async_trait]
+impl<E: Event + Clone + Send + Sync> EventListener<PgEventId, E> for SqlProjection<E> {
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        let changes: Vec<_> = self
+            .mappings
+            .iter()
+            .filter_map(|mapping| mapping(&event))
+            .collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for change in changes {
+            let query_builders = match change {
+                ProjectionChange::Upsert(row) => vec![
+                    upsert_sql_builder(self.table, &self.key, event.id(), row.clone())?,
+                    remove_stale_sql_builder(self.table, &self.key, &row)?,
+                ],
+                ProjectionChange::Delete(row) => {
+                    vec![delete_sql_builder(self.table, &self.key, event.id(), row)?]
+                }
+            };
+            for mut query_builder in query_builders {
+                query_builder.build().execute(&mut *tx).await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+fn create_table_sql(
+    table: &str,
+    columns: &[(&'static str, &'static str)],
+    key: &[&'static str],
+) -> String {
+    let columns = columns
+        .iter()
+        .map(|(name, sql_type)| format!("{name} {sql_type}"))
+        .chain(std::iter::once(format!(
+            "{EVENT_ID_COLUMN} BIGINT NOT NULL"
+        )))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "CREATE TABLE IF NOT EXISTS {table} ({columns}, PRIMARY KEY ({}))",
+        key.join(", ")
+    )
+}
+
+fn create_tombstone_table_sql(
+    table: &str,
+    columns: &[(&'static str, &'static str)],
+    key: &[&'static str],
+) -> String {
+    let key_columns: Vec<_> = columns
+        .iter()
+        .filter(|(name, _)| key.contains(name))
+        .copied()
+        .collect();
+    create_table_sql(&tombstone_table(table), &key_columns, key)
+}
+
+fn tombstone_table(table: &str) -> String {
+    format!("{table}_tombstone")
+}
+
+fn push_value(
+    separated: &mut sqlx::query_builder::Separated<'_, 'static, Postgres, &str>,
+    value: SqlValue,
+    unseparated: bool,
+) {
+    macro_rules! bind {
+        ($value:expr) => {
+            if unseparated {
+                separated.push_bind_unseparated($value)
+            } else {
+                separated.push_bind($value)
+            }
+        };
+    }
+    // An untyped `NULL` literal, so Postgres infers the type of the column instead of a bound `TEXT`.
+    match value {
+        SqlValue::Null if unseparated => separated.push_unseparated("NULL"),
+        SqlValue::Null => separated.push("NULL"),
+        SqlValue::Bool(value) => bind!(value),
+        SqlValue::Int(value) => bind!(value),
+        SqlValue::BigInt(value) => bind!(value),
+        SqlValue::Double(value) => bind!(value),
+        SqlValue::Text(value) => bind!(value),
+        SqlValue::Uuid(value) => bind!(value),
+    };
+}
+
+fn upsert_sql_builder(
+    table: &str,
+    key: &[&'static str],
+    event_id: PgEventId,
+    row: ProjectionRow,
+) -> Result<sqlx::QueryBuilder<'static, Postgres>, Error> {
+    if let Some(&column) = key.iter().find(|column| row.get(column).is_none()) {
+        return Err(Error::MissingKeyColumn(column));
+    }
+    let columns: Vec<_> = row.0.iter().map(|(c, _)| *c).collect();
+    let mut sql_builder = sqlx::QueryBuilder::new(format!(
+        "INSERT INTO {table} ({}, {EVENT_ID_COLUMN}) VALUES (",
+        columns.join(", ")
+    ));
+    let mut separated = sql_builder.separated(", ");
+    for (_, value) in row.0 {
+        push_value(&mut separated, value, false);
+    }
+    separated.push_bind(event_id);
+    let updates = columns
+        .iter()
+        .filter(|c| !key.contains(c))
+        .chain(std::iter::once(&EVENT_ID_COLUMN))
+        .map(|c| format!("{c} = EXCLUDED.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    sql_builder.push(format!(
+        ") ON CONFLICT ({}) DO UPDATE SET {updates} WHERE {table}.{EVENT_ID_COLUMN} < EXCLUDED.{EVENT_ID_COLUMN}",
+        key.join(", ")
+    ));
+    Ok(sql_builder)
+}
+
+/// Builds the statement that deletes the row and records its tombstone.
+fn delete_sql_builder(
+    table: &str,
+    key: &[&'static str],
+    event_id: PgEventId,
+    row: ProjectionRow,
+) -> Result<sqlx::QueryBuilder<'static, Postgres>, Error> {
+    let key_values = key
+        .iter()
+        .map(|column| {
+            row.get(column)
+                .cloned()
+                .ok_or(Error::MissingKeyColumn(column))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let tombstone_table = tombstone_table(table);
+    let mut sql_builder =
+        sqlx::QueryBuilder::new(format!("WITH deleted AS (DELETE FROM {table} WHERE "));
+    let mut separated = sql_builder.separated(" AND ");
+    for (column, value) in key.iter().zip(key_values.iter().cloned()) {
+        separated.push(format!("{column} = "));
+        push_value(&mut separated, value, true);
+    }
+    separated.push(format!("{EVENT_ID_COLUMN} < "));
+    separated.push_bind_unseparated(event_id);
+    sql_builder.push(format!(
+        ") INSERT INTO {tombstone_table} ({}, {EVENT_ID_COLUMN}) VALUES (",
+        key.join(", ")
+    ));
+    let mut separated = sql_builder.separated(", ");
+    for value in key_values {
+        push_value(&mut separated, value, false);
+    }
+    separated.push_bind(event_id);
+    sql_builder.push(format!(
+        ") ON CONFLICT ({}) DO UPDATE SET {EVENT_ID_COLUMN} = EXCLUDED.{EVENT_ID_COLUMN} WHERE {tombstone_table}.{EVENT_ID_COLUMN} < EXCLUDED.{EVENT_ID_COLUMN}",
+        key.join(", ")
+    ));
+    Ok(sql_builder)
+}
+
+/// Builds the statement that deletes the row if it is older than its tombstone.
+///
+/// It runs after the upsert, in the same transaction, so an upsert older than the delete of the row,
+/// delivered again after the delete, is discarded.
+fn remove_stale_sql_builder(
+    table: &str,
+    key: &[&'static str],
+    row: &ProjectionRow,
+) -> Result<sqlx::QueryBuilder<'static, Postgres>, Error> {
+    let tombstone_table = tombstone_table(table);
+    let mut sql_builder = sqlx::QueryBuilder::new(format!(
+        "DELETE FROM {table} USING {tombstone_table} WHERE "
+    ));
+    let mut separated = sql_builder.separated(" AND ");
+    for column in key {
+        let value = row
+            .get(column)
+            .cloned()
+            .ok_or(Error::MissingKeyColumn(column))?;
+        separated.push(format!("{table}.{column} = "));
+        push_value(&mut separated, value, true);
+        separated.push(format!("{tombstone_table}.{column} = {table}.{column}"));
+    }
+    separated.push(format!(
+        "{table}.{EVENT_ID_COLUMN} < {tombstone_table}.{EVENT_ID_COLUMN}"
+    ));
+    Ok(sql_builder)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_create_table_statement() {
+        assert_eq!(
+            create_table_sql(
+                "cart_item",
+                &[("cart_id", "TEXT"), ("item_id", "TEXT"), ("quantity", "INT")],
+                &["cart_id", "item_id"]
+            ),
+            "CREATE TABLE IF NOT EXISTS cart_item (cart_id TEXT, item_id TEXT, quantity INT, event_id BIGINT NOT NULL, PRIMARY KEY (cart_id, item_id))"
+        );
+    }
+
+    #[test]
+    fn it_builds_upsert() {
+        let row = ProjectionRow::new()
+            .set("cart_id", "c1")
+            .set("item_id", "i1")
+            .set("quantity", 2);
+
+        let builder = upsert_sql_builder("cart_item", &["cart_id", "item_id"], 10, row).unwrap();
+
+        assert_eq!(
+            builder.sql(),
+            "INSERT INTO cart_item (cart_id, item_id, quantity, event_id) VALUES ($1, $2, $3, $4) ON CONFLICT (cart_id, item_id) DO UPDATE SET quantity = EXCLUDED.quantity, event_id = EXCLUDED.event_id WHERE cart_item.event_id < EXCLUDED.event_id"
+        );
+    }
+
+    #[test]
+    fn it_builds_delete() {
+        let row = ProjectionRow::new()
+            .set("cart_id", "c1")
+            .set("item_id", "i1");
+
+        let builder = delete_sql_builder("cart_item", &["cart_id", "item_id"], 10, row).unwrap();
+
+        assert_eq!(
+            builder.sql(),
+            "WITH deleted AS (DELETE FROM cart_item WHERE cart_id = $1 AND item_id = $2 AND event_id < $3) \
+            INSERT INTO cart_item_tombstone (cart_id, item_id, event_id) VALUES ($4, $5, $6) \
+            ON CONFLICT (cart_id, item_id) DO UPDATE SET event_id = EXCLUDED.event_id WHERE cart_item_tombstone.event_id < EXCLUDED.event_id"
+        );
+    }
+
+    #[test]
+    fn it_builds_the_create_tombstone_table_statement() {
+        assert_eq!(
+            create_tombstone_table_sql(
+                "cart_item",
+                &[("cart_id", "TEXT"), ("item_id", "TEXT"), ("quantity", "INT")],
+                &["cart_id", "item_id"]
+            ),
+            "CREATE TABLE IF NOT EXISTS cart_item_tombstone (cart_id TEXT, item_id TEXT, event_id BIGINT NOT NULL, PRIMARY KEY (cart_id, item_id))"
+        );
+    }
+
+    #[test]
+    fn it_builds_the_removal_of_the_rows_older_than_their_tombstone() {
+        let row = ProjectionRow::new()
+            .set("cart_id", "c1")
+            .set("item_id", "i1")
+            .set("quantity", 2);
+
+        let builder = remove_stale_sql_builder("cart_item", &["cart_id", "item_id"], &row).unwrap();
+
+        assert_eq!(
+            builder.sql(),
+            "DELETE FROM cart_item USING cart_item_tombstone WHERE cart_item.cart_id = $1 AND cart_item_tombstone.cart_id = cart_item.cart_id \
+            AND cart_item.item_id = $2 AND cart_item_tombstone.item_id = cart_item.item_id \
+            AND cart_item.event_id < cart_item_tombstone.event_id"
+        );
+    }
+
+    #[test]
+    fn it_binds_null_as_an_untyped_literal() {
+        let row = ProjectionRow::new()
+            .set("cart_id", "c1")
+            .set("item_id", "i1")
+            .set("quantity", Option::<i32>::None);
+
+        let builder = upsert_sql_builder("cart_item", &["cart_id", "item_id"], 10, row).unwrap();
+
+        assert!(builder
+            .sql()
+            .starts_with("INSERT INTO cart_item (cart_id, item_id, quantity, event_id) VALUES ($1, $2, NULL, $3)"));
+    }
+
+    #[test]
+    fn it_rejects_the_rows_missing_a_key_column() {
+        let row = ProjectionRow::new().set("cart_id", "c1");
+
+        let Err(err) = upsert_sql_builder("cart_item", &["cart_id", "item_id"], 10, row.clone())
+        else {
+            panic!("the upsert of a row missing a key column must fail");
+        };
+        assert!(matches!(err, Error::MissingKeyColumn("item_id")));
+        assert_eq!(err.kind(), ErrorKind::Validation);
+
+        let Err(err) = delete_sql_builder("cart_item", &["cart_id", "item_id"], 10, row) else {
+            panic!("the delete of a row missing a key column must fail");
+        };
+        assert!(matches!(err, Error::MissingKeyColumn("item_id")));
+    }
+}
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS webhook_delivery (
+    listener_id TEXT,
+    endpoint TEXT,
+    event_id BIGINT,
+    status TEXT,
+    attempts INT DEFAULT 0,
+    last_error TEXT,
+    updated_at TIMESTAMP DEFAULT now(),
+    PRIMARY KEY (listener_id, endpoint, event_id)
+);
@@ -262,3 +262,525 @@ async fn it_runs_event_listener_with_db_listener(pool: PgPool) {
     assert_eq!("product_1", &first_row.product_id);
     assert_eq!(1, first_row.quantity);
 }
+
+#[sqlx::test]
+async fn it_starts_new_event_listeners_from_the_configured_position(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    setup(&pool).await.unwrap();
+    let persisted = event_store
+        .append_without_validation(vec![
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: "cart_1".to_string(),
+                product_id: "product_1".to_string(),
+                quantity: 1,
+            }),
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: "cart_1".to_string(),
+                product_id: "product_2".to_string(),
+                quantity: 1,
+            }),
+        ])
+        .await
+        .unwrap();
+    let last_event_id = persisted.last().unwrap().id();
+    let checkpoint = || async {
+        sqlx::query_scalar::<_, PgEventId>(
+            "SELECT last_processed_event_id FROM event_listener WHERE id = 'carts'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+    };
+
+    PgEventListerExecutor::new(
+        event_store.clone(),
+        CartEventHandler::new(pool.clone()).await.unwrap(),
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1)).start_from(StartPosition::Now),
+    )
+    .init()
+    .await
+    .unwrap();
+    assert_eq!(checkpoint().await, last_event_id);
+
+    PgEventListerExecutor::new(
+        event_store.clone(),
+        CartEventHandler::new(pool.clone()).await.unwrap(),
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1)).start_from(StartPosition::Beginning),
+    )
+    .init()
+    .await
+    .unwrap();
+    assert_eq!(checkpoint().await, last_event_id);
+}
+
+struct BatchRecorder {
+    query: StreamQuery<PgEventId, ShoppingCartEvent>,
+    batches: std::sync::Mutex<Vec<Vec<PgEventId>>>,
+    failing_event_id: PgEventId,
+}
+
+#[async_trait]
+impl EventListener<PgEventId, ShoppingCartEvent> for BatchRecorder {
+    type Error = String;
+    fn id(&self) -> &'static str {
+        "batch_recorder"
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, ShoppingCartEvent> {
+        &self.query
+    }
+
+    async fn handle(
+        &self,
+        persisted_event: PersistedEvent<PgEventId, ShoppingCartEvent>,
+    ) -> Result<(), Self::Error> {
+        if persisted_event.id() == self.failing_event_id {
+            return Err("failure".to_string());
+        }
+        Ok(())
+    }
+
+    async fn handle_batch(
+        &self,
+        events: Vec<PersistedEvent<PgEventId, ShoppingCartEvent>>,
+    ) -> Result<(), BatchError<PgEventId, Self::Error>> {
+        self.batches
+            .lock()
+            .unwrap()
+            .push(events.iter().map(|event| event.id()).collect());
+        let mut last_handled_event_id = None;
+        for event in events {
+            let event_id = event.id();
+            self.handle(event).await.map_err(|error| BatchError {
+                last_handled_event_id,
+                error,
+            })?;
+            last_handled_event_id = Some(event_id);
+        }
+        Ok(())
+    }
+}
+
+#[sqlx::test]
+async fn it_purges_stale_event_listeners(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    setup(&pool).await.unwrap();
+    sqlx::query(
+        "INSERT INTO event_listener (id, last_processed_event_id, updated_at) VALUES ('carts', 0, now()), ('old_carts', 0, now() - interval '2 days')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let event_listener = PgEventListener::builder(event_store);
+
+    let stale = event_listener
+        .stale_listeners(Duration::from_secs(24 * 60 * 60))
+        .await
+        .unwrap();
+    assert_eq!(stale, vec!["old_carts".to_string()]);
+
+    let purged = event_listener
+        .purge_listeners(&["old_carts", "unknown"])
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    let listeners: Vec<String> = sqlx::query_scalar("SELECT id FROM event_listener")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(listeners, vec!["carts".to_string()]);
+}
+
+#[sqlx::test]
+async fn it_handles_events_in_batches(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let events: Vec<_> = (1..=5)
+        .map(|quantity| {
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: "cart_1".to_string(),
+                product_id: "product_1".to_string(),
+                quantity,
+            })
+        })
+        .collect();
+    let persisted = event_store.append_without_validation(events).await.unwrap();
+    let ids: Vec<PgEventId> = persisted.iter().map(|event| event.id()).collect();
+
+    let executor = PgEventListerExecutor::new(
+        event_store.clone(),
+        BatchRecorder {
+            query: query!(ShoppingCartEvent),
+            batches: std::sync::Mutex::new(vec![]),
+            failing_event_id: ids[3],
+        },
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1)).batch_size(2),
+    );
+
+    let result = executor.handle_events_from(0).await;
+
+    assert_eq!(result.unwrap_err().last_processed_event_id, ids[2]);
+    assert_eq!(
+        *executor.event_handler.batches.lock().unwrap(),
+        vec![vec![ids[0], ids[1]], vec![ids[2], ids[3]]]
+    );
+}
+
+#[sqlx::test]
+async fn it_handles_events_grouped_by_identifier(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let events: Vec<_> = (1..=4)
+        .map(|quantity| {
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: format!("cart_{}", 2 - quantity % 2),
+                product_id: "product_1".to_string(),
+                quantity,
+            })
+        })
+        .collect();
+    let persisted = event_store.append_without_validation(events).await.unwrap();
+    let ids: Vec<PgEventId> = persisted.iter().map(|event| event.id()).collect();
+
+    let executor = PgEventListerExecutor::new(
+        event_store.clone(),
+        BatchRecorder {
+            query: query!(ShoppingCartEvent),
+            batches: std::sync::Mutex::new(vec![]),
+            failing_event_id: ids[3],
+        },
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1))
+            .batch_size(4)
+            .delivery_mode(DeliveryMode::PerIdentifier {
+                identifier: ident!(#cart_id),
+                concurrency: 2,
+            }),
+    );
+
+    let result = executor.handle_events_from(0).await;
+
+    assert_eq!(result.unwrap_err().last_processed_event_id, ids[2]);
+    let mut batches = executor.event_handler.batches.lock().unwrap().clone();
+    batches.sort();
+    assert_eq!(batches, vec![vec![ids[0], ids[2]], vec![ids[1], ids[3]]]);
+}
+
+#[sqlx::test]
+async fn it_fetches_only_the_events_matching_the_filter(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let events: Vec<_> = (1..=3)
+        .map(|quantity| {
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: format!("cart_{quantity}"),
+                product_id: "product_1".to_string(),
+                quantity,
+            })
+        })
+        .collect();
+    let persisted = event_store.append_without_validation(events).await.unwrap();
+    let ids: Vec<PgEventId> = persisted.iter().map(|event| event.id()).collect();
+
+    let executor = PgEventListerExecutor::new(
+        event_store.clone(),
+        BatchRecorder {
+            query: query!(ShoppingCartEvent),
+            batches: std::sync::Mutex::new(vec![]),
+            failing_event_id: 0,
+        },
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1)).filter(
+            PgEventListenerFilter::new()
+                .events(&["ShoppingCartAdded"])
+                .identifier_in(ident!(#cart_id), ["cart_2"]),
+        ),
+    );
+
+    let last_processed_event_id = executor.handle_events_from(0).await.unwrap();
+
+    assert_eq!(last_processed_event_id, ids[1]);
+    assert_eq!(
+        *executor.event_handler.batches.lock().unwrap(),
+        vec![vec![ids[1]]]
+    );
+}
+
+#[sqlx::test]
+async fn it_prefetches_the_events_up_to_the_fetch_size(pool: PgPool) {
+    let event_store = PgEventStore::<ShoppingCartEvent, Json<ShoppingCartEvent>>::new(
+        pool.clone(),
+        Json::default(),
+    )
+    .await
+    .unwrap();
+    let events: Vec<_> = (1..=5)
+        .map(|quantity| {
+            ShoppingCartEvent::Added(CartEventPayload {
+                cart_id: "cart_1".to_string(),
+                product_id: "product_1".to_string(),
+                quantity,
+            })
+        })
+        .collect();
+    let persisted = event_store.append_without_validation(events).await.unwrap();
+    let ids: Vec<PgEventId> = persisted.iter().map(|event| event.id()).collect();
+
+    let executor = PgEventListerExecutor::new(
+        event_store.clone(),
+        BatchRecorder {
+            query: query!(ShoppingCartEvent),
+            batches: std::sync::Mutex::new(vec![]),
+            failing_event_id: 0,
+        },
+        CancellationToken::new(),
+        PgEventListenerConfig::poller(Duration::from_secs(1))
+            .fetch_size(3)
+            .prefetch(2)
+            .batch_size(2),
+    );
+
+    assert_eq!(executor.event_store.stream_page_size(), 3);
+    let last_processed_event_id = executor.handle_events_from(0).await.unwrap();
+    assert_eq!(last_processed_event_id, ids[2]);
+    assert!(executor.backlog.load(Ordering::Relaxed));
+
+    let last_processed_event_id = executor
+        .handle_events_from(last_processed_event_id)
+        .await
+        .unwrap();
+    assert_eq!(last_processed_event_id, ids[4]);
+    assert!(!executor.backlog.load(Ordering::Relaxed));
+}
+
+#[test]
+fn it_backs_off_the_poll_interval_while_idle() {
+    let poll = Duration::from_secs(1);
+    let max_idle_poll = Some(Duration::from_secs(5));
+
+    assert_eq!(
+        next_poll_interval(poll, PollOutcome::Idle, poll, max_idle_poll),
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        next_poll_interval(
+            Duration::from_secs(4),
+            PollOutcome::Idle,
+            poll,
+            max_idle_poll
+        ),
+        Duration::from_secs(5)
+    );
+    assert_eq!(
+        next_poll_interval(
+            Duration::from_secs(4),
+            PollOutcome::Handled,
+            poll,
+            max_idle_poll
+        ),
+        poll
+    );
+    assert_eq!(
+        next_poll_interval(poll, PollOutcome::Idle, poll, None),
+        poll
+    );
+}
+
+#[test]
+fn it_adapts_the_batch_size_to_the_handler_latency() {
+    let target = Duration::from_millis(100);
+
+    assert_eq!(
+        adapt_batch_size(8, 64, Duration::from_millis(10), target),
+        16
+    );
+    assert_eq!(
+        adapt_batch_size(64, 64, Duration::from_millis(10), target),
+        64
+    );
+    assert_eq!(
+        adapt_batch_size(8, 64, Duration::from_millis(70), target),
+        8
+    );
+    assert_eq!(
+        adapt_batch_size(8, 64, Duration::from_millis(150), target),
+        4
+    );
+    assert_eq!(
+        adapt_batch_size(1, 64, Duration::from_millis(150), target),
+        1
+    );
+}
+
+#[sqlx::test]
+async fn it_maintains_a_sql_projection(pool: PgPool) {
+    let projection = crate::SqlProjection::<ShoppingCartEvent>::builder(
+        "cart_projection",
+        pool.clone(),
+        "carts",
+    )
+    .column("cart_id", "TEXT")
+    .column("product_id", "TEXT")
+    .column("quantity", "INT")
+    .key(&["cart_id", "product_id"])
+    .upsert(|event| match event {
+        ShoppingCartEvent::Added(payload) => Some(
+            crate::ProjectionRow::new()
+                .set("cart_id", &payload.cart_id)
+                .set("product_id", &payload.product_id)
+                .set("quantity", payload.quantity as i32),
+        ),
+        _ => None,
+    })
+    .delete(|event| match event {
+        ShoppingCartEvent::Removed(payload) => Some(
+            crate::ProjectionRow::new()
+                .set("cart_id", &payload.cart_id)
+                .set("product_id", &payload.product_id),
+        ),
+        _ => None,
+    })
+    .build()
+    .await
+    .unwrap();
+
+    let added = |quantity| {
+        ShoppingCartEvent::Added(CartEventPayload {
+            cart_id: "cart_1".to_string(),
+            product_id: "product_1".to_string(),
+            quantity,
+        })
+    };
+    projection
+        .handle(PersistedEvent::new(1, added(1)))
+        .await
+        .unwrap();
+    projection
+        .handle(PersistedEvent::new(3, added(3)))
+        .await
+        .unwrap();
+    // duplicated delivery of an older event is skipped
+    projection
+        .handle(PersistedEvent::new(1, added(1)))
+        .await
+        .unwrap();
+
+    let carts = Cart::carts(&pool).await.unwrap();
+    assert_eq!(carts.len(), 1);
+    assert_eq!(3, carts.first().unwrap().quantity);
+
+    projection
+        .handle(PersistedEvent::new(
+            4,
+            ShoppingCartEvent::Removed(CartEventPayload {
+                cart_id: "cart_1".to_string(),
+                product_id: "product_1".to_string(),
+                quantity: 3,
+            }),
+        ))
+        .await
+        .unwrap();
+
+    assert!(Cart::carts(&pool).await.unwrap().is_empty());
+
+    // duplicated delivery of an event older than the delete does not restore the row
+    projection
+        .handle(PersistedEvent::new(3, added(3)))
+        .await
+        .unwrap();
+
+    assert!(Cart::carts(&pool).await.unwrap().is_empty());
+
+    projection
+        .handle(PersistedEvent::new(5, added(5)))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        5,
+        Cart::carts(&pool).await.unwrap().first().unwrap().quantity
+    );
+}
+
+#[cfg(feature = "webhook")]
+#[sqlx::test]
+async fn it_records_webhook_deliveries(pool: PgPool) {
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let server = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hooks", server.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = server.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await;
+        }
+    });
+    let unreachable = "http://127.0.0.1:1/hooks";
+
+    let webhook = crate::WebhookListener::<ShoppingCartEvent, _>::new(
+        "cart_webhook",
+        pool.clone(),
+        Json::<ShoppingCartEvent>::default(),
+    )
+    .await
+    .unwrap()
+    .endpoint(crate::WebhookEndpoint::new(&url).with_secret("secret"))
+    .endpoint(crate::WebhookEndpoint::new(unreachable).with_retry(2, Duration::from_millis(1)));
+
+    let event = ShoppingCartEvent::Added(CartEventPayload {
+        cart_id: "cart_1".to_string(),
+        product_id: "product_1".to_string(),
+        quantity: 1,
+    });
+    let result = webhook.handle(PersistedEvent::new(1, event.clone())).await;
+    assert!(matches!(
+        result,
+        Err(crate::WebhookListenerError::Delivery { event_id: 1, .. })
+    ));
+    let result = webhook.handle(PersistedEvent::new(1, event)).await;
+    assert!(result.is_err());
+
+    let deliveries: Vec<(String, String, i32)> = sqlx::query_as(
+        "SELECT endpoint, status, attempts FROM webhook_delivery WHERE event_id = 1 ORDER BY endpoint",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        deliveries,
+        vec![
+            (unreachable.to_string(), "failed".to_string(), 4),
+            (url, "delivered".to_string(), 1),
+        ]
+    );
+}
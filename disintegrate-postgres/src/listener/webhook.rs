@@ -0,0 +1,413 @@
+//! An `EventListener` implementation that delivers events to HTTP endpoints.
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use disintegrate::{
+    Classify, ErrorKind, Event, EventListener, PersistedEvent, Runtime, StreamQuery, TokioRuntime,
+};
+use disintegrate_serde::Serializer;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::error::sqlx_error_kind;
+use crate::PgEventId;
+
+/// The number of handled events between two compactions of the delivery records.
+const COMPACTION_INTERVAL: u64 = 1000;
+
+/// The header containing the ID of the delivered event.
+pub const EVENT_ID_HEADER: &str = "X-Disintegrate-Event-Id";
+/// The header containing the name of the delivered event.
+pub const EVENT_TYPE_HEADER: &str = "X-Disintegrate-Event-Type";
+/// The header containing the HMAC-SHA256 signature of the request body.
+pub const SIGNATURE_HEADER: &str = "X-Disintegrate-Signature";
+
+/// An HTTP endpoint receiving the events of a `WebhookListener`.
+#[derive(Debug, Clone)]
+pub struct WebhookEndpoint {
+    url: String,
+    secret: Option<String>,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl WebhookEndpoint {
+    /// Creates a new `WebhookEndpoint`.
+    ///
+    /// By default, a delivery is attempted up to 5 times, starting with a backoff of 500 milliseconds
+    /// that doubles at each failed attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL where the events are posted.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            max_attempts: 5,
+            backoff: Duration::from_millis(500),
+        }
+    }
+
+    /// Sets the secret used to sign the requests.
+    ///
+    /// When the secret is set, each request carries the `X-Disintegrate-Signature` header
+    /// with the HMAC-SHA256 of the body, in the form `sha256=<hex digest>`.
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Sets the retry policy of the endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - The maximum number of delivery attempts.
+    /// * `backoff` - The delay before the first retry. The delay doubles at each failed attempt.
+    pub fn with_retry(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        assert!(max_attempts > 0, "max_attempts must be greater than 0");
+        self.max_attempts = max_attempts;
+        self.backoff = backoff;
+        self
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+    }
+}
+
+/// The `WebhookListener` posts the events matching its query to the configured endpoints.
+///
+/// # Overview
+///
+/// Each event is serialized with the provided serializer and sent in the body of a `POST` request,
+/// along with the `X-Disintegrate-Event-Id` and `X-Disintegrate-Event-Type` headers.
+/// A delivery succeeds when the endpoint replies with a success status code. Failed deliveries are retried
+/// according to the retry policy of the endpoint.
+///
+/// The outcome of each delivery is stored in the `webhook_delivery` table. An event is never delivered again
+/// to an endpoint that has already acknowledged it, so when an endpoint exhausts its attempts the listener stops
+/// and, at the next run, retries only the endpoints that did not receive the event yet. The records of the
+/// events preceding the listener checkpoint are no longer needed and are periodically deleted.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_serde::serde::json::Json;
+/// use disintegrate_postgres::{WebhookEndpoint, WebhookListener};
+/// use serde::{Serialize, Deserialize};
+/// use sqlx::PgPool;
+/// use std::time::Duration;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// async fn webhook(pool: PgPool) -> Result<WebhookListener<OrderPlaced, Json<OrderPlaced>>, sqlx::Error> {
+///     Ok(WebhookListener::new("order_webhook", pool, Json::default())
+///         .await?
+///         .endpoint(
+///             WebhookEndpoint::new("https://example.com/hooks/orders")
+///                 .with_secret("s3cr3t")
+///                 .with_retry(3, Duration::from_secs(1)),
+///         ))
+/// }
+/// ```
+pub struct WebhookListener<E: Event + Clone, S: Serializer<E>> {
+    id: &'static str,
+    pool: PgPool,
+    query: StreamQuery<PgEventId, E>,
+    serde: S,
+    endpoints: Vec<WebhookEndpoint>,
+    client: reqwest::Client,
+    handled_events: AtomicU64,
+    runtime: Arc<dyn Runtime>,
+    _event: PhantomData<E>,
+}
+
+impl<E: Event + Clone, S: Serializer<E>> WebhookListener<E, S> {
+    /// Creates and initializes a new `WebhookListener`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - A unique identifier for the listener, used to store the last processed `event_id` in the database.
+    /// * `pool` - A `PgPool` instance for Postgres.
+    /// * `serde` - The serializer used to build the request body.
+    pub async fn new(id: &'static str, pool: PgPool, serde: S) -> Result<Self, sqlx::Error> {
+        setup(&pool).await?;
+        Ok(Self::new_uninitialized(id, pool, serde))
+    }
+
+    /// Creates a new `WebhookListener` without initializing the database.
+    ///
+    /// If you use this constructor, ensure that the `webhook_delivery` table exists.
+    /// Refer to the SQL files in the `listener/sql` folder for the necessary schema.
+    pub fn new_uninitialized(id: &'static str, pool: PgPool, serde: S) -> Self {
+        Self {
+            id,
+            pool,
+            query: disintegrate::query!(E),
+            serde,
+            endpoints: vec![],
+            client: reqwest::Client::new(),
+            handled_events: AtomicU64::new(0),
+            runtime: Arc::new(TokioRuntime),
+            _event: PhantomData,
+        }
+    }
+
+    /// Registers an endpoint.
+    pub fn endpoint(mut self, endpoint: WebhookEndpoint) -> Self {
+        self.endpoints.push(endpoint);
+        self
+    }
+
+    /// Overrides the stream query of the listener. By default, the listener delivers all the events of `E`.
+    pub fn query(mut self, query: StreamQuery<PgEventId, E>) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Overrides the HTTP client used to deliver the events.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets the async runtime running the timer of the retries, Tokio by default.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Deletes the delivery records of the events already covered by the listener checkpoint.
+    async fn compact(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"DELETE FROM webhook_delivery d USING event_listener l
+               WHERE d.listener_id = $1 AND l.id = d.listener_id AND d.event_id <= l.last_processed_event_id"#,
+        )
+        .bind(self.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn is_delivered(
+        &self,
+        endpoint: &WebhookEndpoint,
+        event_id: PgEventId,
+    ) -> Result<bool, sqlx::Error> {
+        Ok(sqlx::query_scalar::<_, i64>(
+            "SELECT count(*) FROM webhook_delivery WHERE listener_id = $1 AND endpoint = $2 AND event_id = $3 AND status = 'delivered'",
+        )
+        .bind(self.id)
+        .bind(&endpoint.url)
+        .bind(event_id)
+        .fetch_one(&self.pool)
+        .await?
+            > 0)
+    }
+
+    async fn record_delivery(
+        &self,
+        endpoint: &WebhookEndpoint,
+        event_id: PgEventId,
+        attempts: u32,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let status = if error.is_none() {
+            "delivered"
+        } else {
+            "failed"
+        };
+        sqlx::query(
+            r#"INSERT INTO webhook_delivery (listener_id, endpoint, event_id, status, attempts, last_error) VALUES ($1, $2, $3, $4, $5, $6)
+               ON CONFLICT (listener_id, endpoint, event_id) DO UPDATE SET status = $4, attempts = webhook_delivery.attempts + $5, last_error = $6, updated_at = now()"#,
+        )
+        .bind(self.id)
+        .bind(&endpoint.url)
+        .bind(event_id)
+        .bind(status)
+        .bind(attempts as i32)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn post(
+        &self,
+        endpoint: &WebhookEndpoint,
+        event_id: PgEventId,
+        event_type: &str,
+        body: &[u8],
+    ) -> Result<(), String> {
+        let mut request = self
+            .client
+            .post(&endpoint.url)
+            .header(EVENT_ID_HEADER, event_id.to_string())
+            .header(EVENT_TYPE_HEADER, event_type)
+            .body(body.to_vec());
+        if let Some(secret) = &endpoint.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body));
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("unexpected status code {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn deliver(
+        &self,
+        endpoint: &WebhookEndpoint,
+        event_id: PgEventId,
+        event_type: &str,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        let mut attempt = 1;
+        loop {
+            match self.post(endpoint, event_id, event_type, body).await {
+                Ok(()) => {
+                    self.record_delivery(endpoint, event_id, attempt, None)
+                        .await?;
+                    return Ok(());
+                }
+                Err(err) if attempt >= endpoint.max_attempts => {
+                    self.record_delivery(endpoint, event_id, attempt, Some(&err))
+                        .await?;
+                    return Err(Error::Delivery {
+                        endpoint: endpoint.url.clone(),
+                        event_id,
+                        reason: err,
+                    });
+                }
+                Err(_) => {
+                    self.runtime.sleep(endpoint.backoff(attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// PostgreSQL Webhook listener error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Error returned from the database.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// The endpoint did not acknowledge the event within the configured attempts.
+    #[error("unable to deliver event {event_id} to {endpoint}: {reason}")]
+    Delivery {
+        endpoint: String,
+        event_id: PgEventId,
+        reason: String,
+    },
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::Delivery { .. } => ErrorKind::Transient,
+        }
+    }
+}
+
+#[async_trait]
+impl<E, S> EventListener<PgEventId, E> for WebhookListener<E, S>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serializer<E> + Send + Sync,
+{
+    type Error = Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<PgEventId, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<PgEventId, E>) -> Result<(), Self::Error> {
+        let event_id = event.id();
+        let event_type = event.name();
+        let body = self.serde.serialize(event.into_inner());
+        for endpoint in &self.endpoints {
+            if self.is_delivered(endpoint, event_id).await? {
+                continue;
+            }
+            self.deliver(endpoint, event_id, event_type, &body).await?;
+        }
+        if (self.handled_events.fetch_add(1, Ordering::Relaxed) + 1)
+            .is_multiple_of(COMPACTION_INTERVAL)
+        {
+            self.compact().await?;
+        }
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Removes the delivery records of the given listeners, if the webhook listener has ever been set up.
+pub(crate) async fn purge(
+    tx: &mut Transaction<'_, Postgres>,
+    listener_ids: &[&str],
+) -> Result<(), sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('webhook_delivery') IS NOT NULL")
+        .fetch_one(&mut **tx)
+        .await?;
+    if exists {
+        sqlx::query("DELETE FROM webhook_delivery WHERE listener_id = ANY($1)")
+            .bind(listener_ids)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn setup(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(include_str!("sql/table_webhook_delivery.sql"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_signs_the_body() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn it_doubles_the_backoff_at_each_attempt() {
+        let endpoint =
+            WebhookEndpoint::new("http://localhost").with_retry(4, Duration::from_millis(100));
+
+        assert_eq!(endpoint.backoff(1), Duration::from_millis(100));
+        assert_eq!(endpoint.backoff(2), Duration::from_millis(200));
+        assert_eq!(endpoint.backoff(3), Duration::from_millis(400));
+    }
+}
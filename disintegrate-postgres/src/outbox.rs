@@ -0,0 +1,502 @@
+//! PostgreSQL Transactional Outbox
+//!
+//! This module provides a transactional outbox for the PostgreSQL event store.
+//! Each appended event gets an outbox record in the same transaction, and a relay publishes the
+//! recorded events to a `Publisher`, removing the records once the events are published.
+//! The events are published at least once, so the consumers should handle duplicated deliveries.
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use disintegrate::{
+    BoxDynError, Classify, ComponentHealth, ErrorKind, Event, HealthStatus, Identifier,
+    PersistedEvent, Runtime, TokioRuntime,
+};
+use disintegrate_serde::Serde;
+use futures::{stream, Future, StreamExt};
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::sqlx_error_kind;
+use crate::{PgEventId, PgEventStore};
+
+/// Publishes the events relayed from the outbox.
+#[async_trait]
+pub trait Publisher<E: Event>: Send + Sync {
+    type Error: StdError + Send + Sync + 'static;
+
+    /// Publishes the event.
+    ///
+    /// The relay awaits the returned future before removing the outbox record, so it must complete only when
+    /// the destination has accepted the event. If the publisher fails, the event is published again later.
+    async fn publish(&self, event: &PersistedEvent<PgEventId, E>) -> Result<(), Self::Error>;
+}
+
+/// Relays the events recorded in the outbox to a `Publisher`.
+///
+/// # Overview
+///
+/// When the relay starts, it sets up the outbox: from then on, every event appended to the event store gets a
+/// record in the `outbox` table, written by a trigger in the same transaction as the event. The relay polls the
+/// table and publishes the recorded events in the order they were appended, deleting each record after its event
+/// is published.
+///
+/// Only one relay publishes at a time: the relays running in other processes skip the poll while another one is
+/// publishing. When `partition_by` is set, the events of each batch are grouped by the value of the domain
+/// identifier, and up to `concurrency` groups are published concurrently. The events sharing the same identifier
+/// value are still published in order: when an event of a group fails, the following events of the group are
+/// kept in the outbox and retried at the next poll.
+///
+/// # Example
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use disintegrate::PersistedEvent;
+/// use disintegrate_macros::Event;
+/// use disintegrate_postgres::{PgEventId, PgEventStore, PgOutboxRelay, Publisher};
+/// use disintegrate_serde::serde::json::Json;
+/// use serde::{Deserialize, Serialize};
+/// use std::time::Duration;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// enum DomainEvent {
+///     OrderPlaced {
+///         #[id]
+///         order_id: String,
+///     },
+/// }
+///
+/// struct StdoutPublisher;
+///
+/// #[async_trait]
+/// impl Publisher<DomainEvent> for StdoutPublisher {
+///     type Error = std::io::Error;
+///
+///     async fn publish(&self, event: &PersistedEvent<PgEventId, DomainEvent>) -> Result<(), Self::Error> {
+///         println!("published event {}", event.id());
+///         Ok(())
+///     }
+/// }
+///
+/// async fn relay(
+///     event_store: PgEventStore<DomainEvent, Json<DomainEvent>>,
+///     shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+/// ) -> Result<(), disintegrate_postgres::OutboxError> {
+///     PgOutboxRelay::new(event_store, StdoutPublisher)
+///         .poll(Duration::from_millis(500))
+///         .partition_by(disintegrate::ident!(#order_id), 8)
+///         .start_with_shutdown(shutdown)
+///         .await
+/// }
+/// ```
+pub struct PgOutboxRelay<E, S, P>
+where
+    S: Serde<E> + Send + Sync,
+    E: Event,
+{
+    event_store: PgEventStore<E, S>,
+    publisher: P,
+    poll: Duration,
+    batch_size: i64,
+    partition_by: Option<Identifier>,
+    concurrency: usize,
+    initialize: bool,
+    shutdown_token: CancellationToken,
+    runtime: Arc<dyn Runtime>,
+}
+
+impl<E, S, P> PgOutboxRelay<E, S, P>
+where
+    E: Event + Clone + Send + Sync,
+    S: Serde<E> + Clone + Send + Sync,
+    P: Publisher<E>,
+{
+    /// Creates a new `PgOutboxRelay` publishing the events of the event store.
+    ///
+    /// By default, the relay polls the outbox every second and publishes up to 100 events per poll, one at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_store` - The event store whose events are relayed.
+    /// * `publisher` - The publisher of the events.
+    pub fn new(event_store: PgEventStore<E, S>, publisher: P) -> Self {
+        Self {
+            event_store,
+            publisher,
+            poll: Duration::from_secs(1),
+            batch_size: 100,
+            partition_by: None,
+            concurrency: 1,
+            initialize: true,
+            shutdown_token: CancellationToken::new(),
+            runtime: Arc::new(TokioRuntime),
+        }
+    }
+
+    /// Marks the relay as uninitialized, indicating that the outbox table and trigger already exist.
+    ///
+    /// Check the SQL files in the `outbox/sql` folder to initialize the database.
+    pub fn uninitialized(mut self) -> Self {
+        self.initialize = false;
+        self
+    }
+
+    /// Sets the interval between two polls of the outbox.
+    pub fn poll(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// Sets the async runtime running the timer of the relay, Tokio by default.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Sets the maximum number of events published in a single poll.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be greater than 0");
+        self.batch_size = batch_size as i64;
+        self
+    }
+
+    /// Publishes the events grouped by the value of the domain identifier, up to `concurrency` groups at a time.
+    ///
+    /// The order is guaranteed only among the events sharing the same identifier value.
+    pub fn partition_by(mut self, identifier: Identifier, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "concurrency must be greater than 0");
+        self.partition_by = Some(identifier);
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Returns the health check of the relay, which can be kept after the relay is started.
+    ///
+    /// The relay is reported as degraded when more than 10 batches of events are waiting in the outbox.
+    pub fn health_check(&self) -> PgOutboxHealth {
+        PgOutboxHealth {
+            pool: self.event_store.pool.clone(),
+            max_pending: self.batch_size * 10,
+        }
+    }
+
+    /// Publishes the events currently in the outbox.
+    ///
+    /// # Returns
+    ///
+    /// The number of published events, or the error of the first event that failed to be published.
+    pub async fn relay(&self) -> Result<usize, Error> {
+        let mut tx = self.event_store.pool.begin().await?;
+        let locked: bool =
+            sqlx::query_scalar("SELECT pg_try_advisory_xact_lock(hashtext('disintegrate_outbox'))")
+                .fetch_one(&mut *tx)
+                .await?;
+        if !locked {
+            return Ok(0);
+        }
+        let rows = sqlx::query(
+            "SELECT o.event_id, e.payload FROM outbox o JOIN event e ON e.event_id = o.event_id ORDER BY o.event_id LIMIT $1",
+        )
+        .bind(self.batch_size)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut groups: BTreeMap<Option<String>, Vec<PersistedEvent<PgEventId, E>>> =
+            BTreeMap::new();
+        for (event_id, payload) in self.event_store.payload_rows(rows).await? {
+            let event =
+                PersistedEvent::new(event_id, self.event_store.serde.deserialize(&payload)?);
+            groups
+                .entry(self.partition(&event))
+                .or_default()
+                .push(event);
+        }
+
+        let results: Vec<(Vec<PgEventId>, Option<Error>)> =
+            stream::iter(groups.into_values().map(|group| self.publish_group(group)))
+                .buffer_unordered(self.concurrency)
+                .collect()
+                .await;
+        let mut published = vec![];
+        let mut failure: Option<Error> = None;
+        for (event_ids, error) in results {
+            published.extend(event_ids);
+            if let Some(error) = error {
+                if failure
+                    .as_ref()
+                    .is_none_or(|failure| error.event_id() < failure.event_id())
+                {
+                    failure = Some(error);
+                }
+            }
+        }
+
+        sqlx::query("DELETE FROM outbox WHERE event_id = ANY($1)")
+            .bind(&published)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        match failure {
+            Some(error) => Err(error),
+            None => Ok(published.len()),
+        }
+    }
+
+    /// Starts relaying the events until the relay fails to access the database.
+    ///
+    /// The errors returned by the publisher are retried at the next poll.
+    pub async fn start(self) -> Result<(), Error> {
+        if self.initialize {
+            setup(&self.event_store.pool).await?;
+        }
+        let mut delay = Duration::ZERO;
+        loop {
+            tokio::select! {
+                _ = self.runtime.sleep(delay) => {}
+                _ = self.shutdown_token.cancelled() => return Ok(()),
+            }
+            delay = self.poll;
+            loop {
+                match self.relay().await {
+                    Ok(published) if published as i64 == self.batch_size => continue,
+                    Ok(_) => break,
+                    Err(err) if err.is_retryable() => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+
+    /// Starts relaying the events with a shutdown signal.
+    ///
+    /// # Arguments
+    ///
+    /// * `shutdown` - A future that represents the shutdown signal.
+    pub async fn start_with_shutdown<F: Future<Output = ()> + Send + 'static>(
+        self,
+        shutdown: F,
+    ) -> Result<(), Error> {
+        let shutdown_token = self.shutdown_token.clone();
+        let shutdown_handle = async move {
+            shutdown.await;
+            shutdown_token.cancel();
+            Ok::<(), Error>(())
+        };
+        tokio::try_join!(self.start(), shutdown_handle).map(|_| ())
+    }
+
+    fn partition(&self, event: &PersistedEvent<PgEventId, E>) -> Option<String> {
+        self.partition_by.and_then(|identifier| {
+            event
+                .domain_identifiers()
+                .get(&identifier)
+                .map(|value| value.to_string())
+        })
+    }
+
+    /// Publishes the events of a group in order, stopping at the first failure.
+    async fn publish_group(
+        &self,
+        group: Vec<PersistedEvent<PgEventId, E>>,
+    ) -> (Vec<PgEventId>, Option<Error>) {
+        let mut published = vec![];
+        for event in group {
+            if let Err(err) = self.publisher.publish(&event).await {
+                let error = Error::Publish {
+                    event_id: event.id(),
+                    source: Box::new(err),
+                };
+                return (published, Some(error));
+            }
+            published.push(event.id());
+        }
+        (published, None)
+    }
+}
+
+/// Reports the health of a `PgOutboxRelay`.
+///
+/// The relay is unhealthy when the outbox cannot be read, and degraded when the events waiting in the
+/// outbox exceed the threshold, as it happens when the publisher keeps failing.
+#[derive(Debug, Clone)]
+pub struct PgOutboxHealth {
+    pool: PgPool,
+    max_pending: i64,
+}
+
+impl PgOutboxHealth {
+    /// Reports the relay as degraded when more than `events` are waiting in the outbox.
+    pub fn max_pending(mut self, events: usize) -> Self {
+        self.max_pending = events as i64;
+        self
+    }
+}
+
+#[async_trait]
+impl ComponentHealth for PgOutboxHealth {
+    fn name(&self) -> &str {
+        "outbox_relay"
+    }
+
+    async fn health(&self) -> HealthStatus {
+        match sqlx::query_scalar::<_, i64>("SELECT count(*) FROM outbox")
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok(pending) if pending > self.max_pending => {
+                HealthStatus::Degraded(format!("{pending} events are waiting in the outbox"))
+            }
+            Ok(_) => HealthStatus::Healthy,
+            Err(err) => HealthStatus::Unhealthy(err.to_string()),
+        }
+    }
+}
+
+/// PostgreSQL outbox relay error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Error returned from the database.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// An error occurred while deserializing an event payload.
+    #[error(transparent)]
+    Deserialization(#[from] disintegrate_serde::Error),
+    /// The events could not be read from the event store, e.g. a claim-checked payload could not be fetched.
+    #[error(transparent)]
+    EventStore(#[from] crate::Error),
+    /// The publisher failed to publish the event.
+    #[error("unable to publish event {event_id}: {source}")]
+    Publish {
+        event_id: PgEventId,
+        #[source]
+        source: BoxDynError,
+    },
+}
+
+impl Classify for Error {
+    /// The publishing failures are transient, as they are retried at the next poll.
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::Deserialization(_) => ErrorKind::Corruption,
+            Error::EventStore(err) => err.kind(),
+            Error::Publish { .. } => ErrorKind::Transient,
+        }
+    }
+}
+
+impl Error {
+    fn event_id(&self) -> Option<PgEventId> {
+        match self {
+            Error::Publish { event_id, .. } => Some(*event_id),
+            _ => None,
+        }
+    }
+}
+
+/// Creates the outbox table and the trigger recording the appended events.
+async fn setup(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(include_str!("outbox/sql/table_outbox.sql"))
+        .execute(pool)
+        .await?;
+    sqlx::query(include_str!("outbox/sql/fn_outbox_enqueue.sql"))
+        .execute(pool)
+        .await?;
+    sqlx::query(include_str!("outbox/sql/trigger_outbox_enqueue.sql"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::EventStore;
+    use disintegrate_macros::Event;
+    use disintegrate_serde::serde::json::Json;
+    use serde::{Deserialize, Serialize};
+    use std::sync::Mutex;
+
+    #[derive(Event, Debug, Clone, Serialize, Deserialize)]
+    enum CartEvent {
+        ItemAdded {
+            #[id]
+            cart_id: String,
+            item_id: String,
+        },
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("broker unavailable")]
+    struct BrokerUnavailable;
+
+    struct RecordingPublisher {
+        published: Mutex<Vec<PgEventId>>,
+        failing_event_id: PgEventId,
+    }
+
+    #[async_trait]
+    impl Publisher<CartEvent> for RecordingPublisher {
+        type Error = BrokerUnavailable;
+
+        async fn publish(
+            &self,
+            event: &PersistedEvent<PgEventId, CartEvent>,
+        ) -> Result<(), Self::Error> {
+            if event.id() == self.failing_event_id {
+                return Err(BrokerUnavailable);
+            }
+            self.published.lock().unwrap().push(event.id());
+            Ok(())
+        }
+    }
+
+    fn item_added(cart_id: &str, item_id: &str) -> CartEvent {
+        CartEvent::ItemAdded {
+            cart_id: cart_id.to_string(),
+            item_id: item_id.to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn it_relays_the_events_in_order_per_partition(pool: PgPool) {
+        let event_store = PgEventStore::new(pool.clone(), Json::<CartEvent>::default())
+            .await
+            .unwrap();
+        setup(&pool).await.unwrap();
+        let ids: Vec<PgEventId> = event_store
+            .append_without_validation(vec![
+                item_added("cart_1", "item_1"),
+                item_added("cart_2", "item_1"),
+                item_added("cart_1", "item_2"),
+                item_added("cart_2", "item_2"),
+            ])
+            .await
+            .unwrap()
+            .iter()
+            .map(|event| event.id())
+            .collect();
+        let relay = PgOutboxRelay::new(
+            event_store,
+            RecordingPublisher {
+                published: Mutex::new(vec![]),
+                failing_event_id: ids[1],
+            },
+        )
+        .partition_by(disintegrate::ident!(#cart_id), 2);
+
+        let result = relay.relay().await;
+
+        assert!(matches!(result, Err(Error::Publish { event_id, .. }) if event_id == ids[1]));
+        let mut published = relay.publisher.published.lock().unwrap().clone();
+        published.sort();
+        assert_eq!(published, vec![ids[0], ids[2]]);
+        let pending: Vec<PgEventId> =
+            sqlx::query_scalar("SELECT event_id FROM outbox ORDER BY event_id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(pending, vec![ids[1], ids[3]]);
+    }
+}
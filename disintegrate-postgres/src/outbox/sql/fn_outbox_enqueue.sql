@@ -0,0 +1,7 @@
+CREATE OR REPLACE FUNCTION outbox_enqueue()
+      RETURNS TRIGGER AS $$
+ BEGIN
+    INSERT INTO outbox (event_id) VALUES (NEW.event_id);
+    RETURN new;
+ END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS outbox (
+    event_id BIGINT PRIMARY KEY,
+    inserted_at TIMESTAMP DEFAULT now()
+);
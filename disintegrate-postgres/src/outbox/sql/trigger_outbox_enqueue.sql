@@ -0,0 +1,4 @@
+CREATE OR REPLACE TRIGGER outbox_enqueue_trigger
+  AFTER INSERT ON event
+  FOR EACH ROW
+  EXECUTE function outbox_enqueue();
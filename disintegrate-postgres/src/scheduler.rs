@@ -0,0 +1,323 @@
+//! PostgreSQL Scheduler
+//!
+//! This module appends the "tick" events of time-driven domain processes, such as `DayClosed { date }`, on cron
+//! schedules, so the processes do not depend on an external cron calling the application.
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use disintegrate::{
+    query, Classify, ErrorKind, Event, EventStore, Runtime, StreamFilter, TokioRuntime,
+};
+use disintegrate_serde::Serde;
+use futures::Future;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::sqlx_error_kind;
+use crate::{PgEventId, PgEventStore};
+
+type Tick<T> = Arc<dyn Fn(DateTime<Utc>) -> T + Send + Sync>;
+
+struct CronSchedule<T> {
+    name: &'static str,
+    schedule: Schedule,
+    tick: Tick<T>,
+}
+
+/// Appends the tick events of cron schedules.
+///
+/// # Overview
+///
+/// Each schedule builds its tick event of type `T` from the time it fires. The ticks are appended exactly once,
+/// even when several schedulers run in different processes:
+///
+/// * Only one scheduler fires at a time: the schedulers running in other processes skip the poll while another
+///   one is firing.
+/// * A tick is appended only if no event of type `T` with the same domain identifiers exists, so the tick event
+///   must have a domain identifier derived from the firing time, such as the `date` of `DayClosed`. A tick
+///   appended by a scheduler that crashed before recording it is not appended again.
+///
+/// The last firing time of each schedule is kept in the `scheduler` table. A new schedule starts firing from the
+/// time it is first polled, while the ticks missed while no scheduler was running are appended at the next poll.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// PgScheduler::<DomainEvent, _, CalendarEvent>::new(event_store)
+///     .schedule("day_closed", "0 0 0 * * *", |fired_at| CalendarEvent::DayClosed {
+///         date: fired_at.date_naive().to_string(),
+///     })
+///     .start_with_shutdown(shutdown)
+///     .await?;
+/// ```
+pub struct PgScheduler<E, S, T>
+where
+    S: Serde<E> + Send + Sync,
+    E: Event,
+{
+    event_store: PgEventStore<E, S>,
+    schedules: Vec<CronSchedule<T>>,
+    poll: Duration,
+    initialize: bool,
+    shutdown_token: CancellationToken,
+    runtime: Arc<dyn Runtime>,
+}
+
+impl<E, S, T> PgScheduler<E, S, T>
+where
+    E: Event + Clone + Send + Sync + From<T>,
+    S: Serde<E> + Clone + Send + Sync,
+    T: Event + Clone + Send + Sync + 'static,
+{
+    /// Creates a new `PgScheduler` appending the ticks to the event store.
+    ///
+    /// By default, the scheduler polls the schedules every second.
+    pub fn new(event_store: PgEventStore<E, S>) -> Self {
+        Self {
+            event_store,
+            schedules: vec![],
+            poll: Duration::from_secs(1),
+            initialize: true,
+            shutdown_token: CancellationToken::new(),
+            runtime: Arc::new(TokioRuntime),
+        }
+    }
+
+    /// Marks the scheduler as uninitialized, indicating that the `scheduler` table already exists.
+    ///
+    /// Check the SQL files in the `scheduler/sql` folder to initialize the database.
+    pub fn uninitialized(mut self) -> Self {
+        self.initialize = false;
+        self
+    }
+
+    /// Sets the interval between two polls of the schedules.
+    pub fn poll(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// Sets the async runtime running the timer of the scheduler, Tokio by default.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Adds the schedule `name`, appending the tick built by `tick` at the times of the cron `expression`.
+    ///
+    /// The expression has a seconds field, as in `0 0 0 * * *` for every midnight UTC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the expression is invalid, if the schedule is already added, or if the tick has no domain
+    /// identifier, since it could not be told apart from the ticks fired before.
+    pub fn schedule(
+        mut self,
+        name: &'static str,
+        expression: &str,
+        tick: impl Fn(DateTime<Utc>) -> T + Send + Sync + 'static,
+    ) -> Self {
+        let schedule = Schedule::from_str(expression)
+            .unwrap_or_else(|err| panic!("invalid cron expression {expression}: {err}"));
+        assert!(
+            self.schedules.iter().all(|schedule| schedule.name != name),
+            "the schedule {name} is already added"
+        );
+        assert!(
+            !tick(Utc::now()).domain_identifiers().is_empty(),
+            "the ticks of the schedule {name} have no domain identifier"
+        );
+        self.schedules.push(CronSchedule {
+            name,
+            schedule,
+            tick: Arc::new(tick),
+        });
+        self
+    }
+
+    /// Appends the ticks due since the last poll.
+    ///
+    /// # Returns
+    ///
+    /// The number of appended ticks, which is zero when another scheduler is firing.
+    pub async fn fire(&self) -> Result<usize, Error> {
+        let mut tx = self.event_store.pool.begin().await?;
+        let locked: bool = sqlx::query_scalar(
+            "SELECT pg_try_advisory_xact_lock(hashtext('disintegrate_scheduler'))",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if !locked {
+            return Ok(0);
+        }
+        let now = Utc::now();
+        let mut fired = 0;
+        for schedule in &self.schedules {
+            let last_fired_at: Option<DateTime<Utc>> =
+                sqlx::query_scalar("SELECT last_fired_at FROM scheduler WHERE name = $1")
+                    .bind(schedule.name)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let Some(last_fired_at) = last_fired_at else {
+                sqlx::query("INSERT INTO scheduler (name, last_fired_at) VALUES ($1, $2)")
+                    .bind(schedule.name)
+                    .bind(now)
+                    .execute(&mut *tx)
+                    .await?;
+                continue;
+            };
+            for fired_at in schedule
+                .schedule
+                .after(&last_fired_at)
+                .take_while(|fired_at| *fired_at <= now)
+            {
+                let tick = (schedule.tick)(fired_at);
+                let filter = StreamFilter::<PgEventId, T>::new(tick.domain_identifiers());
+                match self
+                    .event_store
+                    .append(vec![E::from(tick)], query::<_, T, T>(Some(filter)), 0)
+                    .await
+                {
+                    Ok(_) => fired += 1,
+                    Err(crate::Error::Concurrency) => {}
+                    Err(err) => return Err(err.into()),
+                }
+                sqlx::query("UPDATE scheduler SET last_fired_at = $2 WHERE name = $1")
+                    .bind(schedule.name)
+                    .bind(fired_at)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(fired)
+    }
+
+    /// Starts firing the schedules until the scheduler fails to access the database.
+    ///
+    /// The transient failures are retried at the next poll.
+    pub async fn start(self) -> Result<(), Error> {
+        if self.initialize {
+            setup(&self.event_store.pool).await?;
+        }
+        let mut delay = Duration::ZERO;
+        loop {
+            tokio::select! {
+                _ = self.runtime.sleep(delay) => {}
+                _ = self.shutdown_token.cancelled() => return Ok(()),
+            }
+            delay = self.poll;
+            if let Err(err) = self.fire().await {
+                if !err.is_retryable() {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Starts firing the schedules with a shutdown signal.
+    ///
+    /// # Arguments
+    ///
+    /// * `shutdown` - A future that represents the shutdown signal.
+    pub async fn start_with_shutdown<F: Future<Output = ()> + Send + 'static>(
+        self,
+        shutdown: F,
+    ) -> Result<(), Error> {
+        let shutdown_token = self.shutdown_token.clone();
+        let shutdown_handle = async move {
+            shutdown.await;
+            shutdown_token.cancel();
+            Ok::<(), Error>(())
+        };
+        tokio::try_join!(self.start(), shutdown_handle).map(|_| ())
+    }
+}
+
+/// PostgreSQL scheduler error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Error returned from the database.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// The tick could not be appended to the event store.
+    #[error(transparent)]
+    EventStore(#[from] crate::Error),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Database(err) => sqlx_error_kind(err),
+            Error::EventStore(err) => err.kind(),
+        }
+    }
+}
+
+/// Creates the table of the last firing times of the schedules.
+async fn setup(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(include_str!("scheduler/sql/table_scheduler.sql"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate_macros::Event;
+    use disintegrate_serde::serde::json::Json;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum CalendarEvent {
+        SecondElapsed {
+            #[id]
+            second: String,
+        },
+    }
+
+    #[sqlx::test]
+    async fn it_appends_the_ticks_exactly_once(pool: PgPool) {
+        let event_store = PgEventStore::new(pool.clone(), Json::<CalendarEvent>::default())
+            .await
+            .unwrap();
+        setup(&pool).await.unwrap();
+        let scheduler = || {
+            PgScheduler::<CalendarEvent, _, CalendarEvent>::new(event_store.clone()).schedule(
+                "second_elapsed",
+                "* * * * * *",
+                |fired_at| CalendarEvent::SecondElapsed {
+                    second: fired_at.timestamp().to_string(),
+                },
+            )
+        };
+
+        assert_eq!(scheduler().fire().await.unwrap(), 0);
+        let last_fired_at = Utc::now() - chrono::Duration::seconds(3);
+        sqlx::query("UPDATE scheduler SET last_fired_at = $1")
+            .bind(last_fired_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let fired = scheduler().fire().await.unwrap();
+        assert!(fired >= 3);
+
+        sqlx::query("UPDATE scheduler SET last_fired_at = $1")
+            .bind(last_fired_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let refired = scheduler().fire().await.unwrap();
+        let (ticks, distinct_ticks): (i64, i64) =
+            sqlx::query_as("SELECT count(*), count(DISTINCT second) FROM event")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(ticks, (fired + refired) as i64);
+        assert_eq!(ticks, distinct_ticks);
+    }
+}
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS scheduler (
+    name TEXT PRIMARY KEY,
+    last_fired_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,125 @@
+//! # PostgreSQL Serverless Pools
+//!
+//! This module provides the connection pool of the stores running on serverless platforms, such as AWS Lambda or
+//! Cloud Run, where the instances start often, serve few concurrent requests and may be frozen between them.
+use std::time::Duration;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+
+/// The default maximum number of connections of an instance.
+const DEFAULT_MAX_CONNECTIONS: u32 = 2;
+
+/// The default time after which an idle connection is closed.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default time after which a connection is closed, even if it is in use.
+const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(300);
+
+/// The default time to wait for a connection before failing.
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Builds a connection pool suited to serverless deployments.
+///
+/// The pool is created without connecting: the first connection is opened by the first query, so the cold start
+/// does not wait for the database. The pool keeps at most 2 connections by default, does not keep idle
+/// connections around and closes the idle ones after 10 seconds, so the frozen and the scaled-out instances do
+/// not exhaust the connection quota of the database. The connections are not pinged before being used, saving a
+/// round trip per query.
+///
+/// The stores are created with their `new_uninitialized` constructors, as the schema is expected to be set up by
+/// the deployment, for example by a migration job calling `PgEventStore::new`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let pool = PgServerlessPool::new(PgConnectOptions::from_str(&database_url)?).connect_lazy();
+///
+/// let event_store = PgEventStore::new_uninitialized(pool.clone(), serde);
+/// let snapshotter = PgSnapshotter::new_uninitialized(pool, 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgServerlessPool {
+    connect_options: PgConnectOptions,
+    max_connections: u32,
+    idle_timeout: Duration,
+    max_lifetime: Duration,
+    acquire_timeout: Duration,
+}
+
+impl PgServerlessPool {
+    /// Creates a pool builder opening the connections with the given options.
+    pub fn new(connect_options: PgConnectOptions) -> Self {
+        Self {
+            connect_options,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_lifetime: DEFAULT_MAX_LIFETIME,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+        }
+    }
+
+    /// Sets the maximum number of connections of the pool, 2 by default.
+    ///
+    /// Half of them can be used by the concurrent appends of a `PgEventStore`.
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Sets the time after which an idle connection is closed, 10 seconds by default.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets the time after which a connection is closed, 5 minutes by default.
+    pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Sets the time to wait for a connection before failing, 5 seconds by default.
+    pub fn with_acquire_timeout(mut self, acquire_timeout: Duration) -> Self {
+        self.acquire_timeout = acquire_timeout;
+        self
+    }
+
+    /// Returns the options of the pool, to open it with `PgCredentialsRotation` for example.
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(0)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .acquire_timeout(self.acquire_timeout)
+            .test_before_acquire(false)
+    }
+
+    /// Creates the pool without opening any connection.
+    ///
+    /// Must be called within the async runtime, which runs the reaper of the idle connections.
+    pub fn connect_lazy(self) -> PgPool {
+        self.pool_options().connect_lazy_with(self.connect_options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_options_of_a_serverless_pool() {
+        let options = PgServerlessPool::new(PgConnectOptions::new())
+            .with_max_connections(4)
+            .with_idle_timeout(Duration::from_secs(5))
+            .pool_options();
+
+        assert_eq!(options.get_max_connections(), 4);
+        assert_eq!(options.get_min_connections(), 0);
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(options.get_max_lifetime(), Some(DEFAULT_MAX_LIFETIME));
+        assert_eq!(options.get_acquire_timeout(), DEFAULT_ACQUIRE_TIMEOUT);
+        assert!(!options.get_test_before_acquire());
+    }
+}
@@ -1,15 +1,20 @@
 //! # PostgreSQL Snapshotter
 //!
-//! This module provides an implementation of the `Snapshotter` trait using PostgreSQL as the underlying storage.
+//! This module provides an implementation of the `SnapshotStore` trait using PostgreSQL as the underlying storage.
 //! It allows storing and retrieving snapshots from a PostgreSQL database.
 use async_trait::async_trait;
-use disintegrate::{BoxDynError, Event, IntoState, StateSnapshotter, StreamQuery};
+use disintegrate::{
+    BoxDynError, ComponentHealth, HealthStatus, SnapshotInfo, SnapshotPolicy, SnapshotRetention,
+    SnapshotStore, Snapshotter, StateSnapshotter, StoredSnapshot,
+};
 use disintegrate::{StatePart, StateQuery};
 use md5::{Digest, Md5};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use sqlx::PgPool;
 use sqlx::Row;
+use std::ops::Deref;
+use std::time::{Duration, UNIX_EPOCH};
 use uuid::Uuid;
 
 use crate::{Error, PgEventId};
@@ -17,14 +22,171 @@ use crate::{Error, PgEventId};
 #[cfg(test)]
 mod tests;
 
-/// PostgreSQL implementation for the `Snapshotter` trait.
+/// PostgreSQL implementation for the `SnapshotStore` trait.
 ///
-/// The `PgSnapshotter` struct implements the `Snapshotter` trait for PostgreSQL databases.
+/// The `PgSnapshotStore` struct stores the snapshots in the `snapshot` table. JSON payloads are stored
+/// in the `payload` column, while compressed payloads are stored in the `payload_blob` column. The
+/// `inserted_at` column records when the snapshot was last written, and is used to prune the snapshots.
+#[derive(Clone)]
+pub struct PgSnapshotStore {
+    pool: PgPool,
+    tenant: Option<String>,
+}
+
+impl PgSnapshotStore {
+    /// Creates and initializes a new instance of `PgSnapshotStore` with the specified PostgreSQL connection pool.
+    pub async fn new(pool: PgPool) -> Result<Self, Error> {
+        setup(&pool).await?;
+        Ok(Self::new_uninitialized(pool))
+    }
+
+    /// Creates a new instance of `PgSnapshotStore` with the specified PostgreSQL connection pool.
+    ///
+    /// This constructor does not initialize the database. If you need to initialize the database,
+    /// use `PgSnapshotStore::new` instead.
+    pub fn new_uninitialized(pool: PgPool) -> Self {
+        Self { pool, tenant: None }
+    }
+
+    /// Scopes the snapshots to a tenant, for the states hydrated by an event store scoped to it.
+    ///
+    /// The tenant is part of the ID of the snapshots, so the tenants never load the snapshots of each other,
+    /// even for the state queries that do not filter by tenant. The snapshots are still listed and pruned
+    /// across the tenants.
+    pub fn with_tenant(mut self, tenant: impl ToString) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Returns the ID of the snapshot of a state, scoped to the tenant if any.
+    fn snapshot_id(&self, state_name: &str, query: &str) -> Uuid {
+        match &self.tenant {
+            Some(tenant) => snapshot_id(&format!("{tenant:?}{state_name}"), query),
+            None => snapshot_id(state_name, query),
+        }
+    }
+}
+
+/// Reports the snapshot store as unhealthy when the `snapshot` table cannot be read.
+#[async_trait]
+impl ComponentHealth for PgSnapshotStore {
+    fn name(&self) -> &str {
+        "snapshot_store"
+    }
+
+    async fn health(&self) -> HealthStatus {
+        match sqlx::query("SELECT 1 FROM snapshot LIMIT 1")
+            .execute(&self.pool)
+            .await
+        {
+            Ok(_) => HealthStatus::Healthy,
+            Err(err) => HealthStatus::Unhealthy(err.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl SnapshotStore<PgEventId> for PgSnapshotStore {
+    async fn load(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<PgEventId>>, BoxDynError> {
+        let row = sqlx::query(
+            "SELECT name, query, payload, payload_blob, version, fingerprint FROM snapshot where id = $1",
+        )
+        .bind(self.snapshot_id(name, query))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| StoredSnapshot {
+            name: row.get(0),
+            query: row.get(1),
+            payload: row
+                .get::<Option<Vec<u8>>, _>(3)
+                .or_else(|| row.get::<Option<String>, _>(2).map(String::into_bytes))
+                .unwrap_or_default(),
+            version: row.get(4),
+            fingerprint: row.get::<Option<i64>, _>(5).unwrap_or_default() as u64,
+        }))
+    }
+
+    async fn store(&self, snapshot: StoredSnapshot<PgEventId>) -> Result<(), BoxDynError> {
+        let (payload, payload_blob) = match String::from_utf8(snapshot.payload) {
+            Ok(payload) => (Some(payload), None),
+            Err(err) => (None, Some(err.into_bytes())),
+        };
+        sqlx::query("INSERT INTO snapshot (id, name, query, payload, payload_blob, version, fingerprint) VALUES ($1,$2,$3,$4,$5,$6,$7) ON CONFLICT(id) DO UPDATE SET name = $2, query = $3, payload = $4, payload_blob = $5, version = $6, fingerprint = $7, inserted_at = now() WHERE snapshot.version < $6 OR snapshot.fingerprint IS DISTINCT FROM $7")
+        .bind(self.snapshot_id(&snapshot.name, &snapshot.query))
+        .bind(snapshot.name)
+        .bind(snapshot.query)
+        .bind(payload)
+        .bind(payload_blob)
+        .bind(snapshot.version)
+        .bind(snapshot.fingerprint as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, name: Option<&str>) -> Result<Vec<SnapshotInfo<PgEventId>>, BoxDynError> {
+        let rows = sqlx::query("SELECT name, query, version, fingerprint, COALESCE(octet_length(payload_blob), octet_length(payload), 0), EXTRACT(EPOCH FROM inserted_at)::float8 FROM snapshot WHERE $1::text IS NULL OR name = $1 ORDER BY name, query")
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| SnapshotInfo {
+                name: row.get(0),
+                query: row.get(1),
+                version: row.get(2),
+                fingerprint: row.get::<Option<i64>, _>(3).unwrap_or_default() as u64,
+                size: row.get::<i32, _>(4) as u64,
+                stored_at: row
+                    .get::<Option<f64>, _>(5)
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs_f64(secs)),
+            })
+            .collect())
+    }
+
+    async fn delete(&self, name: &str, query: &str) -> Result<bool, BoxDynError> {
+        let result = sqlx::query("DELETE FROM snapshot WHERE id = $1")
+            .bind(self.snapshot_id(name, query))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn prune(&self, name: &str, retention: &SnapshotRetention) -> Result<u64, BoxDynError> {
+        let result = match retention {
+            SnapshotRetention::KeepLatest(keep) => {
+                sqlx::query("DELETE FROM snapshot WHERE name = $1 AND id NOT IN (SELECT id FROM snapshot WHERE name = $1 ORDER BY inserted_at DESC NULLS LAST, version DESC LIMIT $2)")
+                    .bind(name)
+                    .bind(*keep as i64)
+                    .execute(&self.pool)
+                    .await?
+            }
+            SnapshotRetention::MaxAge(age) => {
+                sqlx::query("DELETE FROM snapshot WHERE name = $1 AND inserted_at < now() - make_interval(secs => $2)")
+                    .bind(name)
+                    .bind(age.as_secs_f64())
+                    .execute(&self.pool)
+                    .await?
+            }
+        };
+        Ok(result.rows_affected())
+    }
+}
+
+/// PostgreSQL implementation for the `StateSnapshotter` trait.
+///
+/// The `PgSnapshotter` struct is a `Snapshotter` backed by a `PgSnapshotStore`. It dereferences to the
+/// `Snapshotter`, which provides the snapshot administration operations.
 /// It allows for stroring and retrieving snapshots of `StateQuery` from PostgreSQL database.
 #[derive(Clone)]
 pub struct PgSnapshotter {
-    pool: PgPool,
-    every: u64,
+    snapshotter: Snapshotter<PgSnapshotStore>,
+    store: PgSnapshotStore,
 }
 
 impl PgSnapshotter {
@@ -60,7 +222,88 @@ impl PgSnapshotter {
     ///
     /// A new `PgSnapshotter` instance.
     pub fn new_uninitialized(pool: PgPool, every: u64) -> Self {
-        Self { pool, every }
+        Self::with_store(PgSnapshotStore::new_uninitialized(pool), every)
+    }
+
+    /// Creates a new instance of `PgSnapshotter` backed by the given snapshot store, such as a store scoped
+    /// to a tenant.
+    ///
+    /// This constructor does not initialize the database.
+    pub fn with_store(store: PgSnapshotStore, every: u64) -> Self {
+        Self {
+            snapshotter: Snapshotter::new(store.clone(), every),
+            store,
+        }
+    }
+
+    /// Sets the policy of the state queries without a specific policy.
+    pub fn policy(mut self, policy: SnapshotPolicy) -> Self {
+        self.snapshotter = self.snapshotter.policy(policy);
+        self
+    }
+
+    /// Sets the policy of the state query `S`.
+    pub fn policy_for<S: StateQuery>(mut self, policy: SnapshotPolicy) -> Self {
+        self.snapshotter = self.snapshotter.policy_for::<S>(policy);
+        self
+    }
+
+    /// Keeps up to `capacity` hydrated state parts in an in-process cache for `ttl`.
+    pub fn cache(mut self, capacity: usize, ttl: std::time::Duration) -> Self {
+        self.snapshotter = self.snapshotter.cache(capacity, ttl);
+        self
+    }
+
+    /// Compresses the snapshot payloads with zstd at the given level.
+    #[cfg(feature = "snapshot-compression")]
+    pub fn compression(mut self, level: i32) -> Self {
+        self.snapshotter = self.snapshotter.compression(level);
+        self
+    }
+
+    /// Encrypts the snapshot payloads with the keys of the given provider.
+    #[cfg(feature = "encryption")]
+    pub fn encryption(mut self, key_provider: impl disintegrate::KeyProvider + 'static) -> Self {
+        self.snapshotter = self.snapshotter.encryption(key_provider);
+        self
+    }
+
+    /// Prunes the snapshots automatically according to the given retention.
+    pub fn retention(mut self, retention: SnapshotRetention) -> Self {
+        self.snapshotter = self.snapshotter.retention(retention);
+        self
+    }
+
+    /// Writes the snapshots in the background. Returns the snapshotter and the worker that writes the
+    /// snapshots, which must be spawned on the async runtime.
+    pub fn background(self) -> (Self, impl std::future::Future<Output = ()> + Send + 'static) {
+        let (snapshotter, worker) = self.snapshotter.background();
+        (
+            Self {
+                snapshotter,
+                store: self.store,
+            },
+            worker,
+        )
+    }
+}
+
+impl Deref for PgSnapshotter {
+    type Target = Snapshotter<PgSnapshotStore>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.snapshotter
+    }
+}
+
+#[async_trait]
+impl ComponentHealth for PgSnapshotter {
+    fn name(&self) -> &str {
+        "snapshotter"
+    }
+
+    async fn health(&self) -> HealthStatus {
+        self.store.health().await
     }
 }
 
@@ -70,45 +313,14 @@ impl StateSnapshotter<PgEventId> for PgSnapshotter {
     where
         S: Send + Sync + DeserializeOwned + StateQuery + 'static,
     {
-        let query = query_key(&default.query());
-        let stored_snapshot =
-            sqlx::query("SELECT name, query, payload, version FROM snapshot where id = $1")
-                .bind(snapshot_id(S::NAME, &query))
-                .fetch_one(&self.pool)
-                .await;
-        if let Ok(row) = stored_snapshot {
-            let snapshot_name: String = row.get(0);
-            let snapshot_query: String = row.get(1);
-            if S::NAME == snapshot_name && query == snapshot_query {
-                let payload = serde_json::from_str(row.get(2)).unwrap_or(default.into_state());
-                return StatePart::new(row.get(3), payload);
-            }
-        }
-
-        default
+        self.snapshotter.load_snapshot(default).await
     }
 
     async fn store_snapshot<S>(&self, state: &StatePart<PgEventId, S>) -> Result<(), BoxDynError>
     where
         S: Send + Sync + Serialize + StateQuery + 'static,
     {
-        if state.applied_events() <= self.every {
-            return Ok(());
-        }
-        let query = query_key(&state.query());
-        let id = snapshot_id(S::NAME, &query);
-        let version = state.version();
-        let payload = serde_json::to_string(&state.clone().into_state())?;
-        sqlx::query("INSERT INTO snapshot (id, name, query, payload, version) VALUES ($1,$2,$3,$4,$5) ON CONFLICT(id) DO UPDATE SET name = $2, query = $3, payload = $4, version = $5 WHERE snapshot.version < $5")
-        .bind(id)
-        .bind(S::NAME)
-        .bind(query)
-        .bind(payload)
-        .bind(version)
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+        self.snapshotter.store_snapshot(state).await
     }
 }
 
@@ -122,32 +334,24 @@ fn snapshot_id(state_name: &str, query: &str) -> Uuid {
     )
 }
 
-fn query_key<E: Event + Clone>(query: &StreamQuery<PgEventId, E>) -> String {
-    let mut result = String::new();
-    for f in query.filters() {
-        let excluded_events = if let Some(exclued_events) = f.excluded_events() {
-            format!("-{}", exclued_events.join(","))
-        } else {
-            "".to_string()
-        };
-        result += &format!(
-            "({}|{}{}|{})",
-            f.origin(),
-            f.events().join(","),
-            excluded_events,
-            f.identifiers()
-                .iter()
-                .map(|(k, v)| format!("{k}={v}"))
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-    }
-    result
-}
-
 pub async fn setup(pool: &PgPool) -> Result<(), Error> {
     sqlx::query(include_str!("snapshotter/sql/table_snapshot.sql"))
         .execute(pool)
         .await?;
+    sqlx::query(include_str!(
+        "snapshotter/sql/column_snapshot_payload_blob.sql"
+    ))
+    .execute(pool)
+    .await?;
+    sqlx::query(include_str!(
+        "snapshotter/sql/column_snapshot_fingerprint.sql"
+    ))
+    .execute(pool)
+    .await?;
+    sqlx::query(include_str!(
+        "snapshotter/sql/idx_snapshot_name_inserted_at.sql"
+    ))
+    .execute(pool)
+    .await?;
     Ok(())
 }
@@ -0,0 +1 @@
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS fingerprint bigint
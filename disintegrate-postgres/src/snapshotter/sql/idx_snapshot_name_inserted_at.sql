@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS idx_snapshot_name_inserted_at ON snapshot (name, inserted_at)
\ No newline at end of file
@@ -4,5 +4,7 @@ CREATE TABLE IF NOT EXISTS snapshot (
     query text,
     version bigint,
     payload text,
+    payload_blob bytea,
+    fingerprint bigint,
     inserted_at TIMESTAMP DEFAULT now()
 );
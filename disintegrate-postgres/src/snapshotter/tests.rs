@@ -1,6 +1,7 @@
 use disintegrate::{
-    domain_identifiers, ident, query, DomainIdentifierInfo, DomainIdentifierSet, Event, EventId,
-    EventInfo, EventSchema, IdentifierType, IntoState, IntoStatePart, PersistedEvent, StateMutate,
+    domain_identifiers, ident, query, snapshot_key, DomainIdentifierInfo, DomainIdentifierSet,
+    Event, EventId, EventInfo, EventSchema, IdentifierType, IntoState, IntoStatePart,
+    PersistedEvent, StateMutate,
 };
 use disintegrate_serde::{serde::json::Json, Deserializer};
 use serde::Deserialize;
@@ -107,14 +108,14 @@ async fn it_stores_snapshots(pool: PgPool) {
         .await
         .unwrap();
 
-    let query_key = query_key(&state.query());
+    let query_key = snapshot_key(&state.query::<PgEventId>());
     let snapshot_id = snapshot_id(CartState::NAME, &query_key);
     assert_eq!(stored_snapshot.id, snapshot_id);
     assert_eq!(stored_snapshot.name, CartState::NAME);
     assert_eq!(stored_snapshot.query, query_key);
     assert_eq!(
         Json::<CartState>::default()
-            .deserialize(stored_snapshot.payload.into_bytes())
+            .deserialize(stored_snapshot.payload.as_bytes())
             .unwrap(),
         state.into_state()
     );
@@ -126,7 +127,7 @@ async fn it_loads_snapshots(pool: PgPool) {
     let snapshotter = PgSnapshotter::new(pool.clone(), 2).await.unwrap();
     let default_state = CartState::new("c1", []);
     let expected_state = CartState::new("c1", ["p1", "p2"]);
-    let query_key = query_key(&default_state.query());
+    let query_key = snapshot_key(&default_state.query::<PgEventId>());
     let snapshot_id = snapshot_id(CartState::NAME, &query_key);
     sqlx::query("INSERT INTO snapshot (id, name, query, payload, version) VALUES ($1,$2,$3,$4,$5) ON CONFLICT(id) DO UPDATE SET name = $2, query = $3, payload = $4, version = $5 WHERE snapshot.version < $5")
         .bind(snapshot_id)
@@ -144,3 +145,130 @@ async fn it_loads_snapshots(pool: PgPool) {
     assert_eq!(loaded_state.version(), 3);
     assert_eq!(loaded_state.into_state(), expected_state);
 }
+
+#[sqlx::test]
+async fn it_ignores_snapshots_with_a_different_fingerprint(pool: PgPool) {
+    let snapshotter = PgSnapshotter::new(pool.clone(), 2).await.unwrap();
+    let default_state = CartState::new("c1", []);
+    let query_key = snapshot_key(&default_state.query::<PgEventId>());
+    sqlx::query("INSERT INTO snapshot (id, name, query, payload, version, fingerprint) VALUES ($1,$2,$3,$4,$5,$6)")
+        .bind(snapshot_id(CartState::NAME, &query_key))
+        .bind(CartState::NAME)
+        .bind(query_key)
+        .bind(serde_json::to_string(&CartState::new("c1", ["p1"])).unwrap())
+        .bind(3)
+        .bind(42)
+        .execute(&pool)
+        .await.unwrap();
+
+    let loaded_state = snapshotter
+        .load_snapshot(default_state.clone().into_state_part())
+        .await;
+
+    assert_eq!(loaded_state.version(), 0);
+    assert_eq!(loaded_state.into_state(), default_state);
+}
+
+#[sqlx::test]
+async fn it_administers_snapshots(pool: PgPool) {
+    let snapshotter = PgSnapshotter::new(pool.clone(), 0).await.unwrap();
+    for cart_id in ["c1", "c2"] {
+        let mut state = CartState::new(cart_id, []).into_state_part();
+        state.mutate_part(PersistedEvent::new(
+            1,
+            CartEvent::ItemAdded {
+                cart_id: cart_id.to_string(),
+                item_id: "p1".to_string(),
+            },
+        ));
+        snapshotter.store_snapshot(&state).await.unwrap();
+    }
+
+    let snapshots = snapshotter.snapshots(Some(CartState::NAME)).await.unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert!(snapshots
+        .iter()
+        .all(|snapshot| snapshot.version == 1 && snapshot.size > 0));
+    assert_eq!(
+        snapshotter
+            .delete_snapshots_by_identifier(CartState::NAME, ident!(#cart_id), "c1")
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(snapshotter
+        .delete_snapshot(&CartState::new("c2", []))
+        .await
+        .unwrap());
+    assert!(snapshotter.snapshots(None).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn it_prunes_snapshots(pool: PgPool) {
+    let snapshotter = PgSnapshotter::new(pool.clone(), 0).await.unwrap();
+    for (id, cart_id) in ["c1", "c2", "c3"].into_iter().enumerate() {
+        let mut state = CartState::new(cart_id, []).into_state_part();
+        state.mutate_part(PersistedEvent::new(
+            id as i64 + 1,
+            CartEvent::ItemAdded {
+                cart_id: cart_id.to_string(),
+                item_id: "p1".to_string(),
+            },
+        ));
+        snapshotter.store_snapshot(&state).await.unwrap();
+    }
+
+    assert_eq!(
+        snapshotter
+            .prune(CartState::NAME, &SnapshotRetention::KeepLatest(1))
+            .await
+            .unwrap(),
+        2
+    );
+    let snapshots = snapshotter.snapshots(Some(CartState::NAME)).await.unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].version, 3);
+    assert!(snapshots[0].stored_at.is_some());
+    assert_eq!(
+        snapshotter
+            .prune(
+                CartState::NAME,
+                &SnapshotRetention::MaxAge(Duration::from_secs(3600))
+            )
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[cfg(feature = "snapshot-compression")]
+#[sqlx::test]
+async fn it_stores_compressed_snapshots(pool: PgPool) {
+    let snapshotter = PgSnapshotter::new(pool.clone(), 0)
+        .await
+        .unwrap()
+        .compression(3);
+    let mut state = CartState::new("c1", []).into_state_part();
+    state.mutate_part(PersistedEvent::new(
+        1,
+        CartEvent::ItemAdded {
+            cart_id: "c1".to_string(),
+            item_id: "p1".to_string(),
+        },
+    ));
+
+    snapshotter.store_snapshot(&state).await.unwrap();
+
+    let (payload, payload_blob): (Option<String>, Option<Vec<u8>>) =
+        sqlx::query_as("SELECT payload, payload_blob FROM snapshot")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(payload, None);
+    assert!(payload_blob.unwrap().starts_with(b"zstd:"));
+    let loaded_state = snapshotter
+        .load_snapshot(CartState::new("c1", []).into_state_part())
+        .await;
+    assert_eq!(loaded_state.version(), 1);
+    assert_eq!(loaded_state.into_state(), CartState::new("c1", ["p1"]));
+}
@@ -0,0 +1,125 @@
+//! # PostgreSQL Subject Key Store
+//!
+//! This module provides an implementation of the `SubjectKeyStore` trait using PostgreSQL as the underlying storage.
+//! It keeps the encryption keys of the data subjects in the `subject_key` table, out of the event log.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use disintegrate::encryption::{self, generate_subject_key, EncryptionKey};
+use disintegrate::{BoxDynError, KeyProvider, SubjectKeyStore};
+use sqlx::PgPool;
+
+use crate::Error;
+
+#[cfg(test)]
+mod tests;
+
+/// PostgreSQL implementation for the `SubjectKeyStore` trait.
+///
+/// The keys are stored in the `subject_key` table. Erasing a subject clears its key and records the
+/// erasure, so the subject never gets a new key. The cleared keys remain in the backups and in the dead
+/// rows of the table until they are vacuumed, so the retention of the backups bounds the time needed to
+/// shred the data of a subject.
+#[derive(Clone)]
+pub struct PgSubjectKeyStore {
+    pool: PgPool,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl PgSubjectKeyStore {
+    /// Creates and initializes a new instance of `PgSubjectKeyStore` with the specified PostgreSQL connection pool.
+    pub async fn new(pool: PgPool) -> Result<Self, Error> {
+        setup(&pool).await?;
+        Ok(Self::new_uninitialized(pool))
+    }
+
+    /// Creates a new instance of `PgSubjectKeyStore` with the specified PostgreSQL connection pool.
+    ///
+    /// This constructor does not initialize the database. If you need to initialize the database,
+    /// use `PgSubjectKeyStore::new` instead.
+    pub fn new_uninitialized(pool: PgPool) -> Self {
+        Self {
+            pool,
+            key_provider: None,
+        }
+    }
+
+    /// Encrypts the stored subject keys with the keys of the given provider, so a copy of the
+    /// `subject_key` table does not reveal them.
+    pub fn with_key_provider(mut self, key_provider: impl KeyProvider + 'static) -> Self {
+        self.key_provider = Some(Arc::new(key_provider));
+        self
+    }
+
+    /// Returns the stored key of the subject: `None` if the subject is unknown, `Some(None)` if it was erased.
+    async fn load(&self, subject_id: &str) -> Result<Option<Option<EncryptionKey>>, BoxDynError> {
+        let key: Option<Option<Vec<u8>>> =
+            sqlx::query_scalar("SELECT key FROM subject_key WHERE subject_id = $1")
+                .bind(subject_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(match key {
+            Some(Some(key)) => Some(Some(self.unwrap_key(&key)?)),
+            Some(None) => Some(None),
+            None => None,
+        })
+    }
+
+    fn wrap_key(&self, key: &EncryptionKey) -> Result<Vec<u8>, encryption::Error> {
+        match &self.key_provider {
+            Some(key_provider) => encryption::encrypt(key_provider.as_ref(), key),
+            None => Ok(key.to_vec()),
+        }
+    }
+
+    fn unwrap_key(&self, key: &[u8]) -> Result<EncryptionKey, encryption::Error> {
+        let key = match &self.key_provider {
+            Some(key_provider) => encryption::decrypt(key_provider.as_ref(), key)?,
+            None => key.to_vec(),
+        };
+        key.try_into().map_err(|_| encryption::Error::Decryption)
+    }
+}
+
+#[async_trait]
+impl SubjectKeyStore for PgSubjectKeyStore {
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<EncryptionKey>, BoxDynError> {
+        if let Some(key) = self.load(subject_id).await? {
+            return Ok(key);
+        }
+        sqlx::query(
+            "INSERT INTO subject_key (subject_id, key) VALUES ($1, $2) ON CONFLICT (subject_id) DO NOTHING",
+        )
+        .bind(subject_id)
+        .bind(self.wrap_key(&generate_subject_key())?)
+        .execute(&self.pool)
+        .await?;
+        Ok(self.load(subject_id).await?.flatten())
+    }
+
+    async fn existing_subject_key(
+        &self,
+        subject_id: &str,
+    ) -> Result<Option<EncryptionKey>, BoxDynError> {
+        Ok(self.load(subject_id).await?.flatten())
+    }
+
+    async fn erase_subject(&self, subject_id: &str) -> Result<bool, BoxDynError> {
+        Ok(sqlx::query_scalar(
+            r#"WITH previous AS (SELECT key FROM subject_key WHERE subject_id = $1 FOR UPDATE)
+               INSERT INTO subject_key (subject_id, key, erased_at) VALUES ($1, NULL, now())
+               ON CONFLICT (subject_id) DO UPDATE SET key = NULL, erased_at = COALESCE(subject_key.erased_at, now())
+               RETURNING COALESCE((SELECT key IS NOT NULL FROM previous), false)"#,
+        )
+        .bind(subject_id)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
+
+pub async fn setup(pool: &PgPool) -> Result<(), Error> {
+    sqlx::query(include_str!("subject_keys/sql/table_subject_key.sql"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
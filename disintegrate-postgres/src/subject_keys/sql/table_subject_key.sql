@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS subject_key (
+    subject_id text PRIMARY KEY,
+    key bytea,
+    inserted_at TIMESTAMP DEFAULT now(),
+    erased_at TIMESTAMP
+);
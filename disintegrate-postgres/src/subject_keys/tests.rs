@@ -0,0 +1,50 @@
+use disintegrate::encryption::{decrypt_for_subject, encrypt_for_subject};
+use disintegrate::StaticKeyProvider;
+use sqlx::PgPool;
+
+use super::*;
+
+#[sqlx::test]
+async fn it_shreds_the_data_of_the_erased_subjects(pool: PgPool) {
+    let key_store = PgSubjectKeyStore::new(pool.clone())
+        .await
+        .unwrap()
+        .with_key_provider(StaticKeyProvider::new("k1", [1; 32]));
+    let alice = encrypt_for_subject(&key_store, "alice", b"alice@example.com")
+        .await
+        .unwrap();
+    let bob = encrypt_for_subject(&key_store, "bob", b"bob@example.com")
+        .await
+        .unwrap();
+
+    let stored_key: Vec<u8> =
+        sqlx::query_scalar("SELECT key FROM subject_key WHERE subject_id = 'alice'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_ne!(
+        stored_key,
+        key_store
+            .existing_subject_key("alice")
+            .await
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(
+        decrypt_for_subject(&key_store, &alice).await.unwrap(),
+        Some(b"alice@example.com".to_vec())
+    );
+
+    assert!(key_store.erase_subject("alice").await.unwrap());
+    assert!(!key_store.erase_subject("alice").await.unwrap());
+
+    assert_eq!(decrypt_for_subject(&key_store, &alice).await.unwrap(), None);
+    assert_eq!(
+        decrypt_for_subject(&key_store, &bob).await.unwrap(),
+        Some(b"bob@example.com".to_vec())
+    );
+    assert!(matches!(
+        encrypt_for_subject(&key_store, "alice", b"alice@example.org").await,
+        Err(encryption::Error::ErasedSubject(_))
+    ));
+}
@@ -0,0 +1,572 @@
+//! PostgreSQL Event Subscription Server
+//!
+//! This module provides a gRPC server that streams the events of the event store to remote subscribers.
+//! It allows services written in any language to consume the events without accessing the database.
+//! Each subscriber acknowledges the events it has processed, so it can resume from its checkpoint
+//! after a reconnection.
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use disintegrate::{Event, Policy, Principal, Redactor, Runtime, TokioRuntime};
+use futures::Stream;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+
+use crate::PgEventId;
+
+/// The types generated from `proto/subscription.proto`.
+pub mod proto {
+    tonic::include_proto!("disintegrate.subscription.v1");
+}
+
+use proto::event_subscription_server::{EventSubscription, EventSubscriptionServer};
+use proto::{
+    AcknowledgeRequest, AcknowledgeResponse, StreamQueryFilter, SubscribeRequest, SubscribedEvent,
+};
+
+/// Authorizes the requests of the subscribers.
+#[async_trait]
+pub trait SubscriptionAuthorizer: Send + Sync {
+    /// Returns an error status if the subscriber is not allowed to consume the events.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - The metadata of the request, e.g. the `authorization` header.
+    /// * `subscriber_id` - The ID of the subscriber making the request.
+    async fn authorize(&self, metadata: &MetadataMap, subscriber_id: &str) -> Result<(), Status>;
+
+    /// Returns the principal making the request, checked against the policy of the server.
+    ///
+    /// By default, the request is authorized with `authorize` and its principal is the subscriber, with no role.
+    async fn principal(
+        &self,
+        metadata: &MetadataMap,
+        subscriber_id: &str,
+    ) -> Result<Principal, Status> {
+        self.authorize(metadata, subscriber_id).await?;
+        Ok(Principal::new(subscriber_id))
+    }
+}
+
+#[async_trait]
+impl<F> SubscriptionAuthorizer for F
+where
+    F: Fn(&MetadataMap, &str) -> Result<(), Status> + Send + Sync,
+{
+    async fn authorize(&self, metadata: &MetadataMap, subscriber_id: &str) -> Result<(), Status> {
+        self(metadata, subscriber_id)
+    }
+}
+
+struct AllowAll;
+
+#[async_trait]
+impl SubscriptionAuthorizer for AllowAll {
+    async fn authorize(&self, _metadata: &MetadataMap, _subscriber_id: &str) -> Result<(), Status> {
+        Ok(())
+    }
+}
+
+/// A gRPC server streaming the events of the event store to remote subscribers.
+///
+/// # Overview
+///
+/// The `Subscribe` RPC streams the events matching the requested filters, starting after `from_event_id`.
+/// When `from_event_id` is not set, the stream starts after the last event acknowledged by the subscriber.
+/// The stream does not end: when the subscriber has caught up, new events are polled at a regular interval.
+///
+/// A filter matches the events whose type is listed in `events`, or any type of `E` when `events` is empty,
+/// and whose domain identifiers equal the given `identifiers`. An identifier is only applied to the event types
+/// that have it. The event matches the request if it matches any of the filters, or all the events of `E`
+/// when no filter is given.
+///
+/// The payload of each event is sent as stored in the event store, so subscribers decode it with the same
+/// format used by the serializer of the event store, e.g. JSON or Protobuf. The personal data of the JSON payloads
+/// can be masked with `with_redactor`.
+///
+/// With a policy, set by `with_policy`, a subscriber only receives the event types its principal may read:
+/// a filter listing another event type is rejected.
+///
+/// The `Acknowledge` RPC stores the checkpoint of a subscriber in the `event_subscription` table.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate_macros::Event;
+/// use disintegrate_postgres::PgSubscriptionServer;
+/// use serde::{Serialize, Deserialize};
+/// use sqlx::PgPool;
+///
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[id]
+///     order_id: String,
+/// }
+///
+/// async fn serve(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+///     let server = PgSubscriptionServer::<OrderPlaced>::new(pool)
+///         .await?
+///         .with_authorizer(|metadata: &tonic::metadata::MetadataMap, _subscriber_id: &str| {
+///             match metadata.get("authorization") {
+///                 Some(token) if token == "Bearer s3cr3t" => Ok(()),
+///                 _ => Err(tonic::Status::unauthenticated("invalid token")),
+///             }
+///         });
+///     tonic::transport::Server::builder()
+///         .add_service(server.into_service())
+///         .serve("0.0.0.0:50051".parse()?)
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+pub struct PgSubscriptionServer<E: Event> {
+    pool: PgPool,
+    authorizer: Arc<dyn SubscriptionAuthorizer>,
+    policy: Option<Arc<dyn Policy>>,
+    poll: Duration,
+    fetch_size: i64,
+    #[cfg(feature = "claim-check")]
+    claim_check: Option<Arc<crate::ClaimCheck>>,
+    #[cfg(feature = "encryption")]
+    envelope_encryption: Option<crate::EnvelopeEncryption>,
+    redactor: Option<Arc<Redactor>>,
+    runtime: Arc<dyn Runtime>,
+    _event: PhantomData<fn() -> E>,
+}
+
+impl<E: Event + Send + Sync + 'static> PgSubscriptionServer<E> {
+    /// Creates and initializes a new `PgSubscriptionServer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - A `PgPool` instance for Postgres.
+    pub async fn new(pool: PgPool) -> Result<Self, sqlx::Error> {
+        setup(&pool).await?;
+        Ok(Self::new_uninitialized(pool))
+    }
+
+    /// Creates a new `PgSubscriptionServer` without initializing the database.
+    ///
+    /// If you use this constructor, ensure that the `event_subscription` table exists.
+    /// Refer to the SQL files in the `subscription/sql` folder for the necessary schema.
+    pub fn new_uninitialized(pool: PgPool) -> Self {
+        Self {
+            pool,
+            authorizer: Arc::new(AllowAll),
+            policy: None,
+            poll: Duration::from_secs(1),
+            fetch_size: 100,
+            #[cfg(feature = "claim-check")]
+            claim_check: None,
+            #[cfg(feature = "encryption")]
+            envelope_encryption: None,
+            redactor: None,
+            runtime: Arc::new(TokioRuntime),
+            _event: PhantomData,
+        }
+    }
+
+    /// Sets the authorizer of the requests. By default, all the requests are allowed.
+    pub fn with_authorizer(mut self, authorizer: impl SubscriptionAuthorizer + 'static) -> Self {
+        self.authorizer = Arc::new(authorizer);
+        self
+    }
+
+    /// Sets the policy telling which event types the principals of the requests may read.
+    ///
+    /// The principals are returned by `SubscriptionAuthorizer::principal`. By default, all the event types are
+    /// readable.
+    pub fn with_policy(mut self, policy: impl Policy + 'static) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Sets the interval at which new events are polled once a subscriber has caught up.
+    pub fn poll(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// Sets the async runtime running the timer of the polls, Tokio by default.
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Sets the maximum number of events fetched from the database at a time.
+    pub fn fetch_size(mut self, fetch_size: usize) -> Self {
+        self.fetch_size = fetch_size as i64;
+        self
+    }
+
+    /// Fetches the claim-checked payloads from the object store of the claim check before sending them.
+    #[cfg(feature = "claim-check")]
+    pub fn with_claim_check(mut self, claim_check: crate::ClaimCheck) -> Self {
+        self.claim_check = Some(Arc::new(claim_check));
+        self
+    }
+
+    /// Decrypts the payloads encrypted by the envelope encryption of the event store before sending them.
+    #[cfg(feature = "encryption")]
+    pub fn with_envelope_encryption(
+        mut self,
+        envelope_encryption: crate::EnvelopeEncryption,
+    ) -> Self {
+        self.envelope_encryption = Some(envelope_encryption);
+        self
+    }
+
+    /// Masks the personal data of the sent payloads with the redactor, e.g. `Redactor::of::<E>()`.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Wraps the server in the tonic service, ready to be added to a `tonic::transport::Server`.
+    pub fn into_service(self) -> EventSubscriptionServer<Self> {
+        EventSubscriptionServer::new(self)
+    }
+
+    async fn checkpoint(&self, subscriber_id: &str) -> Result<PgEventId, sqlx::Error> {
+        Ok(sqlx::query_scalar(
+            "SELECT last_acknowledged_event_id FROM event_subscription WHERE subscriber_id = $1",
+        )
+        .bind(subscriber_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .unwrap_or(0))
+    }
+}
+
+#[tonic::async_trait]
+impl<E: Event + Send + Sync + 'static> EventSubscription for PgSubscriptionServer<E> {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribedEvent, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let subscriber_id = &request.get_ref().subscriber_id;
+        let principal = self
+            .authorizer
+            .principal(request.metadata(), subscriber_id)
+            .await?;
+        let readable_events = readable_events::<E>(self.policy.as_deref(), &principal)?;
+        let request = request.into_inner();
+        let filters = validate_filters::<E>(request.filters, &readable_events)?;
+        let mut last_event_id = match request.from_event_id {
+            Some(from_event_id) => from_event_id,
+            None => self
+                .checkpoint(&request.subscriber_id)
+                .await
+                .map_err(internal)?,
+        };
+
+        let pool = self.pool.clone();
+        let poll = self.poll;
+        let runtime = Arc::clone(&self.runtime);
+        let fetch_size = self.fetch_size;
+        #[cfg(feature = "claim-check")]
+        let claim_check = self.claim_check.clone();
+        #[cfg(feature = "encryption")]
+        let envelope_encryption = self.envelope_encryption.clone();
+        let redactor = self.redactor.clone();
+        let stream = async_stream::try_stream! {
+            loop {
+                let epoch: i64 = sqlx::query_scalar("SELECT event_store_current_epoch()")
+                    .fetch_one(&pool)
+                    .await
+                    .map_err(internal)?;
+                let rows = events_query::<E>(&filters, &readable_events, last_event_id, epoch, fetch_size)
+                    .build()
+                    .fetch_all(&pool)
+                    .await
+                    .map_err(internal)?;
+                if rows.is_empty() {
+                    runtime.sleep(poll).await;
+                    continue;
+                }
+                for row in rows {
+                    last_event_id = row.get(0);
+                    let payload: Vec<u8> = row.get(2);
+                    #[cfg(feature = "claim-check")]
+                    let payload = match &claim_check {
+                        Some(claim_check) => claim_check.check_out(payload).await.map_err(internal)?,
+                        None => payload,
+                    };
+                    #[cfg(feature = "encryption")]
+                    let payload = match &envelope_encryption {
+                        Some(envelope_encryption) => envelope_encryption.decrypt(&pool, payload).await.map_err(internal)?,
+                        None => payload,
+                    };
+                    let event_type: String = row.get(1);
+                    let payload = match &redactor {
+                        Some(redactor) => redactor.redact(&event_type, &payload),
+                        None => payload,
+                    };
+                    yield SubscribedEvent {
+                        event_id: last_event_id,
+                        event_type,
+                        payload,
+                    };
+                }
+            }
+        };
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn acknowledge(
+        &self,
+        request: Request<AcknowledgeRequest>,
+    ) -> Result<Response<AcknowledgeResponse>, Status> {
+        self.authorizer
+            .principal(request.metadata(), &request.get_ref().subscriber_id)
+            .await?;
+        let request = request.into_inner();
+        sqlx::query(
+            r#"INSERT INTO event_subscription (subscriber_id, last_acknowledged_event_id) VALUES ($1, $2)
+               ON CONFLICT (subscriber_id) DO UPDATE SET last_acknowledged_event_id = GREATEST(event_subscription.last_acknowledged_event_id, $2), updated_at = now()"#,
+        )
+        .bind(&request.subscriber_id)
+        .bind(request.event_id)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(Response::new(AcknowledgeResponse {}))
+    }
+}
+
+/// Returns the event types of `E` that the principal may read.
+fn readable_events<E: Event>(
+    policy: Option<&dyn Policy>,
+    principal: &Principal,
+) -> Result<Vec<&'static str>, Status> {
+    let events: Vec<&'static str> = E::SCHEMA
+        .events
+        .iter()
+        .copied()
+        .filter(|event| policy.is_none_or(|policy| policy.can_read(principal, event)))
+        .collect();
+    if events.is_empty() {
+        return Err(Status::permission_denied(format!(
+            "principal {} is not allowed to read any event",
+            principal.id()
+        )));
+    }
+    Ok(events)
+}
+
+/// Checks that the filters only reference event types and domain identifiers of `E`, and that the event types
+/// are readable.
+fn validate_filters<E: Event>(
+    filters: Vec<StreamQueryFilter>,
+    readable_events: &[&str],
+) -> Result<Vec<StreamQueryFilter>, Status> {
+    for filter in &filters {
+        if let Some(event) = filter
+            .events
+            .iter()
+            .find(|event| E::SCHEMA.event_info(event).is_none())
+        {
+            return Err(Status::invalid_argument(format!("unknown event {event}")));
+        }
+        if let Some(event) = filter
+            .events
+            .iter()
+            .find(|event| !readable_events.contains(&event.as_str()))
+        {
+            return Err(Status::permission_denied(format!(
+                "not allowed to read {event}"
+            )));
+        }
+        if let Some(ident) = filter.identifiers.keys().find(|ident| {
+            !E::SCHEMA
+                .domain_identifiers
+                .iter()
+                .any(|info| info.ident.to_string() == **ident)
+        }) {
+            return Err(Status::invalid_argument(format!(
+                "unknown domain identifier {ident}"
+            )));
+        }
+    }
+    Ok(filters)
+}
+
+fn events_query<'a, E: Event>(
+    filters: &'a [StreamQueryFilter],
+    readable_events: &[&'a str],
+    last_event_id: PgEventId,
+    epoch: i64,
+    fetch_size: i64,
+) -> QueryBuilder<'a, Postgres> {
+    let mut builder =
+        QueryBuilder::new("SELECT event_id, event_type, payload FROM event WHERE event_id > ");
+    builder.push_bind(last_event_id);
+    builder.push(" AND event_id <= ");
+    builder.push_bind(epoch);
+    builder.push(" AND (");
+    let all_events = [StreamQueryFilter::default()];
+    let filters = if filters.is_empty() {
+        &all_events[..]
+    } else {
+        filters
+    };
+    let mut first_condition = true;
+    for filter in filters {
+        let events: Vec<&str> = if filter.events.is_empty() {
+            readable_events.to_vec()
+        } else {
+            filter.events.iter().map(String::as_str).collect()
+        };
+        for event in events {
+            let event_info = E::SCHEMA.event_info(event).expect("filters are validated");
+            if !first_condition {
+                builder.push(" OR ");
+            }
+            first_condition = false;
+            builder.push("(event_type = ");
+            builder.push_bind(event);
+            for (ident, value) in &filter.identifiers {
+                if event_info
+                    .domain_identifiers
+                    .iter()
+                    .any(|id| id.to_string() == *ident)
+                {
+                    builder.push(format!(" AND {ident}::text = "));
+                    builder.push_bind(value.as_str());
+                }
+            }
+            builder.push(")");
+        }
+    }
+    if first_condition {
+        builder.push("FALSE");
+    }
+    builder.push(") ORDER BY event_id ASC LIMIT ");
+    builder.push_bind(fetch_size);
+    builder
+}
+
+fn internal(err: impl std::fmt::Display) -> Status {
+    Status::internal(err.to_string())
+}
+
+async fn setup(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(include_str!(
+        "subscription/sql/table_event_subscription.sql"
+    ))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use disintegrate::{
+        ident, DomainIdentifierInfo, DomainIdentifierSet, EventInfo, EventSchema, RolePolicy,
+    };
+    use std::collections::HashMap;
+
+    #[derive(Clone)]
+    enum CartEvent {}
+
+    impl Event for CartEvent {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["ItemAdded", "CartClosed"],
+            events_info: &[
+                &EventInfo {
+                    name: "ItemAdded",
+                    domain_identifiers: &[&ident!(#cart_id), &ident!(#item_id)],
+                },
+                &EventInfo {
+                    name: "CartClosed",
+                    domain_identifiers: &[&ident!(#cart_id)],
+                },
+            ],
+            domain_identifiers: &[
+                &DomainIdentifierInfo {
+                    ident: ident!(#cart_id),
+                    type_info: disintegrate::IdentifierType::String,
+                },
+                &DomainIdentifierInfo {
+                    ident: ident!(#item_id),
+                    type_info: disintegrate::IdentifierType::String,
+                },
+            ],
+        };
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            match *self {}
+        }
+
+        fn name(&self) -> &'static str {
+            match *self {}
+        }
+    }
+
+    #[test]
+    fn it_builds_the_events_query_from_the_filters() {
+        let filters = vec![StreamQueryFilter {
+            events: vec![],
+            identifiers: HashMap::from([("item_id".to_string(), "item_1".to_string())]),
+        }];
+
+        assert_eq!(
+            events_query::<CartEvent>(&filters, CartEvent::SCHEMA.events, 10, 20, 100).sql(),
+            "SELECT event_id, event_type, payload FROM event WHERE event_id > $1 AND event_id <= $2 AND ((event_type = $3 AND item_id::text = $4) OR (event_type = $5)) ORDER BY event_id ASC LIMIT $6"
+        );
+    }
+
+    #[test]
+    fn it_rejects_unknown_events_and_identifiers() {
+        let unknown_event = vec![StreamQueryFilter {
+            events: vec!["OrderPlaced".to_string()],
+            identifiers: HashMap::new(),
+        }];
+        let unknown_identifier = vec![StreamQueryFilter {
+            events: vec![],
+            identifiers: HashMap::from([("order_id".to_string(), "order_1".to_string())]),
+        }];
+
+        assert!(validate_filters::<CartEvent>(unknown_event, CartEvent::SCHEMA.events).is_err());
+        assert!(
+            validate_filters::<CartEvent>(unknown_identifier, CartEvent::SCHEMA.events).is_err()
+        );
+    }
+
+    #[test]
+    fn it_restricts_the_events_to_the_readable_ones() {
+        let policy = RolePolicy::default().allow_event::<CartEvent>("auditor", "CartClosed");
+        let auditor = Principal::new("auditor-1").with_role("auditor");
+        let item_added = vec![StreamQueryFilter {
+            events: vec!["ItemAdded".to_string()],
+            identifiers: HashMap::new(),
+        }];
+
+        let readable_events = readable_events::<CartEvent>(Some(&policy), &auditor).unwrap();
+
+        assert_eq!(readable_events, vec!["CartClosed"]);
+        assert_eq!(
+            validate_filters::<CartEvent>(item_added, &readable_events)
+                .unwrap_err()
+                .code(),
+            tonic::Code::PermissionDenied
+        );
+        assert_eq!(
+            events_query::<CartEvent>(&[], &readable_events, 10, 20, 100).sql(),
+            "SELECT event_id, event_type, payload FROM event WHERE event_id > $1 AND event_id <= $2 AND ((event_type = $3)) ORDER BY event_id ASC LIMIT $4"
+        );
+        assert_eq!(
+            readable_events::<CartEvent>(Some(&policy), &Principal::new("user-1"))
+                .unwrap_err()
+                .code(),
+            tonic::Code::PermissionDenied
+        );
+    }
+}
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS event_subscription (
+    subscriber_id TEXT PRIMARY KEY,
+    last_acknowledged_event_id BIGINT,
+    updated_at TIMESTAMP DEFAULT now()
+);
@@ -38,16 +38,18 @@ pub trait Serializer<T> {
 
 /// Defines the behavior for deserializing values of type `T`.
 pub trait Deserializer<T> {
-    /// Deserializes a byte vector into a value of type `T`.
+    /// Deserializes a byte slice into a value of type `T`.
+    ///
+    /// The data is borrowed, so the payloads can be deserialized in place from the buffers of the storage.
     ///
     /// # Arguments
     ///
-    /// * `data` - The byte vector to be deserialized.
+    /// * `data` - The bytes to be deserialized.
     ///
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<T, Error>;
+    fn deserialize(&self, data: &[u8]) -> Result<T, Error>;
 }
 
 /// Combines the `Serializer` and `Deserializer` traits for convenience.
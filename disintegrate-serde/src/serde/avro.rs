@@ -72,8 +72,8 @@ where
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<I, Error> {
-        let mut reader = Reader::new(&data[..]).map_err(|e| Error::Deserialization(Box::new(e)))?;
+    fn deserialize(&self, data: &[u8]) -> Result<I, Error> {
+        let mut reader = Reader::new(data).map_err(|e| Error::Deserialization(Box::new(e)))?;
         let value = reader
             .next()
             .expect("at least one value should be present")
@@ -144,7 +144,7 @@ mod tests {
         let serialized = avro.serialize(input.clone());
 
         // Deserialize the serialized data
-        let deserialized: InputData = avro.deserialize(serialized).unwrap();
+        let deserialized: InputData = avro.deserialize(&serialized).unwrap();
 
         // Ensure the deserialized data matches the original input
         assert_eq!(deserialized, input);
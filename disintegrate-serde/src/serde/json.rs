@@ -47,8 +47,8 @@ where
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<T, Error> {
-        serde_json::from_slice(&data).map_err(|e| Error::Deserialization(Box::new(e)))
+    fn deserialize(&self, data: &[u8]) -> Result<T, Error> {
+        serde_json::from_slice(data).map_err(|e| Error::Deserialization(Box::new(e)))
     }
 }
 
@@ -71,7 +71,7 @@ mod tests {
         };
 
         let serialized_data = json_serializer.serialize(person.clone());
-        let deserialized_person = json_serializer.deserialize(serialized_data).unwrap();
+        let deserialized_person = json_serializer.deserialize(&serialized_data).unwrap();
 
         assert_eq!(person, deserialized_person);
     }
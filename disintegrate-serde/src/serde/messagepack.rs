@@ -48,8 +48,8 @@ where
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<T, Error> {
-        rmp_serde::from_slice(&data).map_err(|e| Error::Deserialization(Box::new(e)))
+    fn deserialize(&self, data: &[u8]) -> Result<T, Error> {
+        rmp_serde::from_slice(data).map_err(|e| Error::Deserialization(Box::new(e)))
     }
 }
 
@@ -72,7 +72,7 @@ mod tests {
         };
 
         let serialized_data = msgpack_serializer.serialize(person.clone());
-        let deserialized_person = msgpack_serializer.deserialize(serialized_data).unwrap();
+        let deserialized_person = msgpack_serializer.deserialize(&serialized_data).unwrap();
 
         assert_eq!(person, deserialized_person);
     }
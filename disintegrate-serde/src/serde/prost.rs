@@ -3,7 +3,7 @@
 //! This module provides the capability to serialize and deserialize data using the Prost library.
 use std::marker::PhantomData;
 
-use prost::{bytes::Bytes, Message};
+use prost::Message;
 
 use super::Error;
 use crate::serde::{Deserializer, Serializer};
@@ -66,10 +66,8 @@ where
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<I, Error> {
-        let buf = Bytes::from(data);
-
-        let target = O::decode(buf).map_err(|e| Error::Deserialization(Box::new(e)))?;
+    fn deserialize(&self, data: &[u8]) -> Result<I, Error> {
+        let target = O::decode(data).map_err(|e| Error::Deserialization(Box::new(e)))?;
         I::try_from(target).map_err(|_| Error::Conversion)
     }
 }
@@ -100,7 +98,7 @@ mod tests {
         let serialized_data = serde_module.serialize(person.clone());
 
         // Deserialize the bytes back to a person
-        let deserialized_person = serde_module.deserialize(serialized_data).unwrap();
+        let deserialized_person = serde_module.deserialize(&serialized_data).unwrap();
 
         // Verify that the deserialized person matches the original person
         assert_eq!(person, deserialized_person);
@@ -51,17 +51,17 @@ where
     I: TryFrom<O>,
     O: Message,
 {
-    /// Deserializes the given byte vector to a target type.
+    /// Deserializes the given bytes to a target type.
     ///
     /// # Arguments
     ///
-    /// * `data` - The byte vector to be deserialized.
+    /// * `data` - The bytes to be deserialized.
     ///
     /// # Returns
     ///
     /// A `Result` containing the deserialized value on success, or an error on failure.
-    fn deserialize(&self, data: Vec<u8>) -> Result<I, Error> {
-        let target = O::parse_from_bytes(&data).map_err(|e| Error::Deserialization(Box::new(e)))?;
+    fn deserialize(&self, data: &[u8]) -> Result<I, Error> {
+        let target = O::parse_from_bytes(data).map_err(|e| Error::Deserialization(Box::new(e)))?;
         I::try_from(target).map_err(|_| Error::Conversion)
     }
 }
@@ -0,0 +1,268 @@
+//! Integration with the [Actix Web](https://docs.rs/actix-web) framework.
+//!
+//! `app_data` registers the `DecisionMaker` and the event store in the app data, from which the `DecisionMaker` is
+//! extracted. `Decided` and `DecisionRejection` turn the results of the decisions into HTTP responses, and
+//! `EventFeed` streams the events of a query to WebSocket clients.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! #[post("/account/{id}/withdraw")]
+//! async fn withdraw(
+//!     decision_maker: PgDecisionMaker<DomainEvent, Json<DomainEvent>, WithPgSnapshot>,
+//!     id: Path<i64>,
+//!     amount: Json<Amount>,
+//! ) -> Result<Decided<PgEventId, DomainEvent>, DecisionRejection<AccountError>> {
+//!     Ok(Decided(decision_maker.make(WithdrawAmount::new(*id, amount.amount)).await?))
+//! }
+//!
+//! let data = app_data(decision_maker, event_store.clone());
+//! let feed = EventFeed::new(event_store, query!(DomainEvent));
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .configure(&data)
+//!         .service(withdraw)
+//!         .route("/events", feed.clone().websocket_route())
+//! })
+//! ```
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+use std::pin::pin;
+use std::str::FromStr;
+
+use actix_web::body::BoxBody;
+use actix_web::dev::Payload;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Data, ServiceConfig};
+use actix_web::{rt, FromRequest, HttpRequest, HttpResponse, Responder, ResponseError};
+use actix_ws::Message;
+use futures::future::{ready, Ready};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::json;
+
+pub use crate::feed::EventFeed;
+use crate::{
+    Classify, DecisionError, DecisionMaker, ErrorKind, Event, EventId, EventStore, PersistedEvent,
+};
+
+/// Returns the configuration registering the `DecisionMaker` and the event store in the app data.
+///
+/// The registered values are shared by the workers of the server, so the configuration is passed by reference to
+/// `App::configure` in the factory of the `HttpServer`.
+pub fn app_data<SS, ES>(
+    decision_maker: DecisionMaker<SS>,
+    event_store: ES,
+) -> impl Fn(&mut ServiceConfig) + Clone
+where
+    SS: 'static,
+    ES: 'static,
+{
+    let decision_maker = Data::new(decision_maker);
+    let event_store = Data::new(event_store);
+    move |config| {
+        config
+            .app_data(decision_maker.clone())
+            .app_data(event_store.clone());
+    }
+}
+
+impl<SS> FromRequest for DecisionMaker<SS>
+where
+    SS: Clone + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.app_data::<Data<Self>>()
+                .map(|decision_maker| decision_maker.get_ref().clone())
+                .ok_or_else(|| {
+                    ErrorInternalServerError("the DecisionMaker is not registered in the app data")
+                }),
+        )
+    }
+}
+
+/// Maps a domain error to the status code of its HTTP response.
+pub trait IntoStatusCode {
+    /// Returns the status code of the HTTP response, usually a client error.
+    fn status_code(&self) -> StatusCode;
+}
+
+/// The HTTP response of a decision that failed.
+///
+/// The domain errors get the status code of their `IntoStatusCode` implementation, and the unauthorized
+/// decisions get `403 Forbidden`. The errors of the stores are mapped by kind: the conflicts get `409 Conflict`,
+/// the transient failures `503 Service Unavailable` and the others `500 Internal Server Error`. The body is the
+/// error message, except for the server errors, whose details are not disclosed.
+#[derive(Debug)]
+pub struct DecisionRejection<DE>(pub DecisionError<DE>);
+
+impl<DE> From<DecisionError<DE>> for DecisionRejection<DE> {
+    fn from(err: DecisionError<DE>) -> Self {
+        Self(err)
+    }
+}
+
+impl<DE: Display> Display for DecisionRejection<DE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<DE> ResponseError for DecisionRejection<DE>
+where
+    DE: IntoStatusCode + Display + fmt::Debug,
+{
+    fn status_code(&self) -> StatusCode {
+        match &self.0 {
+            DecisionError::Domain(err) => err.status_code(),
+            DecisionError::Unauthorized { .. } => StatusCode::FORBIDDEN,
+            err => match err.kind() {
+                ErrorKind::Conflict => StatusCode::CONFLICT,
+                ErrorKind::Transient => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let status = self.status_code();
+        let body = if status.is_server_error() {
+            status.canonical_reason().unwrap_or_default().to_string()
+        } else {
+            self.to_string()
+        };
+        HttpResponse::build(status).body(body)
+    }
+}
+
+/// The HTTP response of a decision that succeeded.
+///
+/// The body is the JSON array of the appended events, each with its ID, its name and its payload.
+pub struct Decided<ID: EventId, E: Event>(pub Vec<PersistedEvent<ID, E>>);
+
+impl<ID, E> Responder for Decided<ID, E>
+where
+    ID: EventId + Serialize,
+    E: Event + Serialize,
+{
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let events: Vec<_> = self
+            .0
+            .iter()
+            .map(|event| event_json(event.id(), event.name(), json!(&**event)))
+            .collect();
+        HttpResponse::Ok().json(events)
+    }
+}
+
+impl<ES, ID, E, QE> EventFeed<ES, ID, E, QE>
+where
+    ES: EventStore<ID, E> + Clone + Send + Sync + 'static,
+    ID: EventId + Serialize + FromStr,
+    E: Event + Send + Sync + 'static,
+    QE: TryFrom<E> + Event + Serialize + Clone + Send + Sync + 'static,
+    <QE as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    /// Upgrades the request to a WebSocket streaming the feed.
+    ///
+    /// Each event is sent as a text message holding the JSON object of its ID, its name and its payload. The clients
+    /// reconnecting with the `last_event_id` query parameter resume after the last event they received.
+    pub fn websocket(
+        self,
+        req: &HttpRequest,
+        body: web::Payload,
+    ) -> actix_web::Result<HttpResponse> {
+        let last_event_id = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|params| params.get("last_event_id")?.parse().ok())
+            .unwrap_or_default();
+        let (response, mut session, mut messages) = actix_ws::handle(req, body)?;
+        rt::spawn(async move {
+            let mut events = pin!(self.events(last_event_id));
+            loop {
+                tokio::select! {
+                    Some((event, payload)) = events.next() => {
+                        let message = event_json(event.id(), event.name(), payload);
+                        if session.text(message.to_string()).await.is_err() {
+                            return;
+                        }
+                    }
+                    message = messages.next() => match message {
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(Message::Close(reason))) => {
+                            let _ = session.close(reason).await;
+                            return;
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(_)) | None => break,
+                    },
+                    else => break,
+                }
+            }
+            let _ = session.close(None).await;
+        });
+        Ok(response)
+    }
+
+    /// Returns the `GET` route serving the feed over WebSocket.
+    pub fn websocket_route(self) -> actix_web::Route {
+        web::get().to(move |req: HttpRequest, body: web::Payload| {
+            let feed = self.clone();
+            async move { feed.websocket(&req, body) }
+        })
+    }
+}
+
+fn event_json<ID: Serialize>(id: ID, name: &str, payload: serde_json::Value) -> serde_json::Value {
+    json!({
+        "id": id,
+        "event": name,
+        "payload": payload,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("insufficient balance")]
+    struct InsufficientBalance;
+
+    impl IntoStatusCode for InsufficientBalance {
+        fn status_code(&self) -> StatusCode {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+
+    #[test]
+    fn it_maps_the_decision_errors_to_status_codes() {
+        let rejection = |err| DecisionRejection::<InsufficientBalance>(err).error_response();
+
+        let response = rejection(DecisionError::Domain(InsufficientBalance));
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = rejection(DecisionError::Unauthorized {
+            principal: "alice".to_string(),
+            decision: "WithdrawAmount",
+        });
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = rejection(DecisionError::EventStore(Box::new(
+            crate::ClassifiedError::new(ErrorKind::Transient, "connection reset"),
+        )));
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response = rejection(DecisionError::StateStore("connection refused".into()));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
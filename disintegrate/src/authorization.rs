@@ -0,0 +1,172 @@
+//! Authorization of the actors making the decisions and reading the events.
+//!
+//! A `Principal` is the authenticated actor of a request, with its roles. A `Policy` tells which decisions a
+//! principal may make and which event types it may read. The `DecisionMaker` and the subscription servers
+//! check the same policy, so the authorization does not depend on the endpoint serving the request.
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{Actor, Decision, Event};
+
+/// The authenticated actor of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    id: String,
+    roles: BTreeSet<String>,
+}
+
+impl Principal {
+    /// Creates a principal with no role.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            roles: BTreeSet::new(),
+        }
+    }
+
+    /// Grants a role to the principal.
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.roles.insert(role.into());
+        self
+    }
+
+    /// Returns the ID of the principal.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the roles of the principal.
+    pub fn roles(&self) -> impl Iterator<Item = &str> {
+        self.roles.iter().map(String::as_str)
+    }
+
+    /// Returns `true` if the principal has the role.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+impl From<&Principal> for Actor {
+    /// The principals are users.
+    fn from(principal: &Principal) -> Self {
+        Self::user(principal.id())
+    }
+}
+
+/// Decides what a principal is allowed to do.
+pub trait Policy: Send + Sync {
+    /// Returns `true` if the principal may make the decision named `decision`.
+    ///
+    /// The name of a decision is the name of its type, as returned by `decision_name`.
+    fn can_execute(&self, principal: &Principal, decision: &str) -> bool;
+
+    /// Returns `true` if the principal may read the events named `event_type`.
+    fn can_read(&self, principal: &Principal, event_type: &str) -> bool;
+}
+
+/// Returns the name of the decision `D` checked by the policies.
+pub fn decision_name<D: Decision>() -> &'static str {
+    std::any::type_name::<D>()
+}
+
+/// A `Policy` granting the decisions and the event types to roles.
+///
+/// Nothing is allowed unless granted to a role of the principal.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let policy = RolePolicy::default()
+///     .allow_decision::<AddItem>("customer")
+///     .allow_decision::<RemoveItem>("customer")
+///     .allow_events::<CartEvent>("analyst");
+/// let decision_maker = DecisionMaker::new(state_store).with_policy(policy);
+///
+/// let principal = Principal::new("user-1").with_role("customer");
+/// decision_maker.make_as(&principal, AddItem::new(cart_id, item_id)).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RolePolicy {
+    decisions: HashMap<String, HashSet<&'static str>>,
+    events: HashMap<String, HashSet<&'static str>>,
+}
+
+impl RolePolicy {
+    /// Allows the role to make the decision `D`.
+    pub fn allow_decision<D: Decision>(mut self, role: impl Into<String>) -> Self {
+        self.decisions
+            .entry(role.into())
+            .or_default()
+            .insert(decision_name::<D>());
+        self
+    }
+
+    /// Allows the role to read all the event types of `E`.
+    pub fn allow_events<E: Event>(mut self, role: impl Into<String>) -> Self {
+        self.events
+            .entry(role.into())
+            .or_default()
+            .extend(E::SCHEMA.events);
+        self
+    }
+
+    /// Allows the role to read the event type of `E` named `event_type`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `E` has no event type named `event_type`.
+    pub fn allow_event<E: Event>(mut self, role: impl Into<String>, event_type: &str) -> Self {
+        let event_type = E::SCHEMA
+            .events
+            .iter()
+            .find(|name| **name == event_type)
+            .copied()
+            .unwrap_or_else(|| panic!("unknown event {event_type}"));
+        self.events
+            .entry(role.into())
+            .or_default()
+            .insert(event_type);
+        self
+    }
+
+    fn allows(
+        grants: &HashMap<String, HashSet<&'static str>>,
+        principal: &Principal,
+        name: &str,
+    ) -> bool {
+        principal
+            .roles()
+            .filter_map(|role| grants.get(role))
+            .any(|names| names.contains(name))
+    }
+}
+
+impl Policy for RolePolicy {
+    fn can_execute(&self, principal: &Principal, decision: &str) -> bool {
+        Self::allows(&self.decisions, principal, decision)
+    }
+
+    fn can_read(&self, principal: &Principal, event_type: &str) -> bool {
+        Self::allows(&self.events, principal, event_type)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::tests::*;
+
+    #[test]
+    fn it_grants_the_decisions_and_the_events_to_the_roles() {
+        let policy = RolePolicy::default()
+            .allow_decision::<MockDecision>("customer")
+            .allow_event::<ShoppingCartEvent>("analyst", "ItemAdded");
+        let customer = Principal::new("user-1").with_role("customer");
+        let analyst = Principal::new("user-2").with_role("analyst");
+
+        assert!(policy.can_execute(&customer, decision_name::<MockDecision>()));
+        assert!(!policy.can_execute(&analyst, decision_name::<MockDecision>()));
+        assert!(policy.can_read(&analyst, "ItemAdded"));
+        assert!(!policy.can_read(&analyst, "ItemRemoved"));
+        assert!(!policy.can_read(&customer, "ItemAdded"));
+    }
+}
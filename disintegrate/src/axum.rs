@@ -0,0 +1,179 @@
+//! Integration with the [Axum](https://docs.rs/axum) web framework.
+//!
+//! The `DecisionMaker` is extracted from the state of the router, `DecisionRejection` turns the errors of the
+//! decisions into HTTP responses, and `EventFeed` serves the events of a query as Server-Sent Events.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! async fn withdraw(
+//!     decision_maker: PgDecisionMaker<DomainEvent, Json<DomainEvent>, WithPgSnapshot>,
+//!     Path(id): Path<i64>,
+//!     Json(amount): Json<Amount>,
+//! ) -> Result<StatusCode, DecisionRejection<AccountError>> {
+//!     decision_maker.make(WithdrawAmount::new(id, amount.amount)).await?;
+//!     Ok(StatusCode::NO_CONTENT)
+//! }
+//!
+//! let app = Router::new()
+//!     .route("/account/{id}/withdraw", post(withdraw))
+//!     .route("/events", EventFeed::new(event_store, query!(DomainEvent)).route())
+//!     .with_state(decision_maker);
+//! ```
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use ::axum::extract::{FromRef, FromRequestParts};
+use ::axum::http::request::Parts;
+use ::axum::http::{HeaderMap, StatusCode};
+use ::axum::response::sse::{self, KeepAlive, Sse};
+use ::axum::response::{IntoResponse, Response};
+use ::axum::routing::{get, MethodRouter};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+
+pub use crate::feed::EventFeed;
+use crate::{Classify, DecisionError, DecisionMaker, ErrorKind, Event, EventId, EventStore};
+
+impl<S, SS> FromRequestParts<S> for DecisionMaker<SS>
+where
+    DecisionMaker<SS>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_ref(state))
+    }
+}
+
+/// Maps a domain error to the status code of its HTTP response.
+pub trait IntoStatusCode {
+    /// Returns the status code of the HTTP response, usually a client error.
+    fn status_code(&self) -> StatusCode;
+}
+
+/// The HTTP response of a decision that failed.
+///
+/// The domain errors get the status code of their `IntoStatusCode` implementation, and the unauthorized
+/// decisions get `403 Forbidden`. The errors of the stores are mapped by kind: the conflicts get `409 Conflict`,
+/// the transient failures `503 Service Unavailable` and the others `500 Internal Server Error`. The body is the
+/// error message, except for the server errors, whose details are not disclosed.
+#[derive(Debug)]
+pub struct DecisionRejection<DE>(pub DecisionError<DE>);
+
+impl<DE> From<DecisionError<DE>> for DecisionRejection<DE> {
+    fn from(err: DecisionError<DE>) -> Self {
+        Self(err)
+    }
+}
+
+impl<DE> DecisionRejection<DE>
+where
+    DE: IntoStatusCode,
+{
+    /// Returns the status code of the response.
+    pub fn status_code(&self) -> StatusCode {
+        match &self.0 {
+            DecisionError::Domain(err) => err.status_code(),
+            DecisionError::Unauthorized { .. } => StatusCode::FORBIDDEN,
+            err => match err.kind() {
+                ErrorKind::Conflict => StatusCode::CONFLICT,
+                ErrorKind::Transient => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+}
+
+impl<DE> IntoResponse for DecisionRejection<DE>
+where
+    DE: IntoStatusCode + Display,
+{
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let body = if status.is_server_error() {
+            status.canonical_reason().unwrap_or_default().to_string()
+        } else {
+            self.0.to_string()
+        };
+        (status, body).into_response()
+    }
+}
+
+impl<ES, ID, E, QE> EventFeed<ES, ID, E, QE>
+where
+    ES: EventStore<ID, E> + Clone + Send + Sync + 'static,
+    ID: EventId + Display + FromStr,
+    E: Event + Send + Sync + 'static,
+    QE: TryFrom<E> + Event + Serialize + Clone + Send + Sync + 'static,
+    <QE as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    /// Returns the Server-Sent Events following the event `last_event_id`.
+    ///
+    /// Each event is sent with its ID, its name as the SSE event type and its JSON payload.
+    pub fn stream(
+        self,
+        last_event_id: ID,
+    ) -> impl Stream<Item = Result<sse::Event, Infallible>> + Send + 'static {
+        self.events(last_event_id).map(|(event, payload)| {
+            Ok(sse::Event::default()
+                .id(event.id().to_string())
+                .event(event.name())
+                .data(payload.to_string()))
+        })
+    }
+
+    /// Returns the `GET` route serving the feed as Server-Sent Events.
+    ///
+    /// The clients reconnecting with the `Last-Event-ID` header resume after the last event they received.
+    pub fn route<S>(self) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        get(move |headers: HeaderMap| async move {
+            let last_event_id = headers
+                .get("last-event-id")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default();
+            Sse::new(self.stream(last_event_id)).keep_alive(KeepAlive::default())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("insufficient balance")]
+    struct InsufficientBalance;
+
+    impl IntoStatusCode for InsufficientBalance {
+        fn status_code(&self) -> StatusCode {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+
+    #[test]
+    fn it_maps_the_decision_errors_to_status_codes() {
+        let rejection = |err| DecisionRejection::<InsufficientBalance>(err).into_response();
+
+        let response = rejection(DecisionError::Domain(InsufficientBalance));
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = rejection(DecisionError::Unauthorized {
+            principal: "alice".to_string(),
+            decision: "WithdrawAmount",
+        });
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = rejection(DecisionError::EventStore(Box::new(
+            crate::ClassifiedError::new(ErrorKind::Conflict, "concurrent append"),
+        )));
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = rejection(DecisionError::StateStore("connection refused".into()));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -1,58 +1,16 @@
 //! A Decision serves as a building block for developing the business logic of an application.
 
+use std::sync::Arc;
+
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use crate::event::EventId;
+use crate::authorization::{decision_name, Policy, Principal};
 use crate::state_store::LoadedState;
-use crate::stream_query::StreamQuery;
-use crate::{event::Event, PersistedEvent};
-use crate::{BoxDynError, IntoState, IntoStatePart, LoadState, MultiState};
-
-/// Represents a business decision taken from a state built upon the occurred events.
-pub trait Decision: Send + Sync {
-    type Event: Event + Clone + Send + Sync;
-    type StateQuery: Clone + Send + Sync;
-    type Error: Send + Sync;
-
-    /// Returns the state query to compute the decision state from the events in the event store.
-    ///
-    /// If there are no events that match the specified query, the default values of the state query is utilized to make the decision.
-    fn state_query(&self) -> Self::StateQuery;
-
-    /// Returns the stream query used to validate the decision.
-    ///
-    /// If the validation query is `None`, the state query will be used for validation.
-    /// This means that the decision will only be confirmed if new events that would make the state query outdated are not found.
-    /// However, if a `validation_query` is provided, it will be used to confirm the decision.
-    /// This allows narrowing down the set of events that could invalidate the decision.
-    ///
-    /// For example, in a banking system, deposit events should not invalidate withdrawals if the account balance is already sufficient.
-    /// In this case, the state query needs to include both withdraw and deposit events to compute the available balance,
-    /// but only withdraw events should invalidate the decision.
-    /// In other words, once we have confirmed that the account has a sufficient amount,
-    /// only a withdraw event can reduce the balance below the requested amount and invalidate the decision.
-    fn validation_query<ID: EventId>(&self) -> Option<StreamQuery<ID, Self::Event>> {
-        None
-    }
-
-    /// Evaluates the decision based on the mutated state, ensuring that all business rules
-    /// are verified against the current state. This method generates a series of events
-    /// that capture the changes made by the decision, allowing the results to be
-    /// persisted in the event store.
-    ///
-    /// # Parameters
-    ///
-    /// - `state`: A reference to the current state of the system, obtained through
-    ///   the implementation of the `StateQuery` trait.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` indicating the success of the process. If successful, it contains
-    /// a vector of events representing the changes made. In case of an error, it
-    /// contains details about the encountered issue.
-    fn process(&self, state: &Self::StateQuery) -> Result<Vec<Self::Event>, Self::Error>;
-}
+use crate::{
+    error_kind, Actor, BoxDynError, Classify, Decision, ErrorKind, Event, EventId, IntoState,
+    IntoStatePart, LoadState, MultiState, PersistedEvent, StreamQuery, WithActor,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error<DE> {
@@ -62,12 +20,31 @@ pub enum Error<DE> {
     StateStore(#[source] BoxDynError),
     #[error("domain error: {0}")]
     Domain(#[source] DE),
+    #[error("principal {principal} is not allowed to make {decision}")]
+    Unauthorized {
+        principal: String,
+        decision: &'static str,
+    },
+}
+
+impl<DE> Classify for Error<DE> {
+    /// The domain and authorization errors are `Validation` errors, while the errors of the stores keep the kind they were
+    /// boxed with, defaulting to `Io`.
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::EventStore(err) | Error::StateStore(err) => {
+                error_kind(err.as_ref()).unwrap_or(ErrorKind::Io)
+            }
+            Error::Domain(_) | Error::Unauthorized { .. } => ErrorKind::Validation,
+        }
+    }
 }
 
 /// The `DecisionMaker` struct is responsible for executing and persisting business decisions.
 #[derive(Clone)]
 pub struct DecisionMaker<SS> {
     state_store: SS,
+    policy: Option<Arc<dyn Policy>>,
 }
 
 impl<SS> DecisionMaker<SS> {
@@ -78,7 +55,25 @@ impl<SS> DecisionMaker<SS> {
     /// - `state_store`: The state store backend used by the `DecisionMaker` to load the current state
     ///   and persist the decision.
     pub fn new(state_store: SS) -> Self {
-        Self { state_store }
+        Self {
+            state_store,
+            policy: None,
+        }
+    }
+
+    /// Sets the policy checked by `make_as` before making a decision on behalf of a principal.
+    ///
+    /// Without a policy, every principal may make every decision.
+    pub fn with_policy(mut self, policy: impl Policy + 'static) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Returns `true` if the policy allows the principal to make the decision `D`.
+    pub fn can_make<D: Decision>(&self, principal: &Principal) -> bool {
+        self.policy
+            .as_ref()
+            .is_none_or(|policy| policy.can_execute(principal, decision_name::<D>()))
     }
 
     /// Makes the given business decision, persisting the resulting events in the event store.
@@ -92,6 +87,14 @@ impl<SS> DecisionMaker<SS> {
     /// A `Result` indicating the success of the decision-making process. If successful,
     /// it contains a vector of `PersistedEvent` representing the changes made. In case of
     /// an error, it contains details about the encountered issue.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.decision",
+            skip_all,
+            fields(decision = std::any::type_name::<D>(), events = tracing::field::Empty)
+        )
+    )]
     pub async fn make<D, S, ID, E>(
         &self,
         decision: D,
@@ -123,9 +126,220 @@ impl<SS> DecisionMaker<SS> {
             )
             .await
             .map_err(Error::StateStore)?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("events", events.len());
 
         Ok(events)
     }
+
+    /// Makes the given business decision on behalf of the principal, once the policy allows it.
+    ///
+    /// The events are stamped with the actor of the execution context or, if there is none, with the
+    /// principal as a user. `make` does not check the policy: it serves the decisions of the system itself,
+    /// such as the ones of the process managers.
+    pub async fn make_as<D, S, ID, E>(
+        &self,
+        principal: &Principal,
+        decision: D,
+    ) -> Result<Vec<PersistedEvent<ID, E>>, Error<D::Error>>
+    where
+        ID: EventId,
+        E: Event + Clone + Sync + Send + 'static,
+        SS: LoadState<ID, S, E> + PersistDecision<ID, S, E>,
+        D: Decision<StateQuery = S, Event = E>,
+        S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S>,
+        <S as IntoStatePart<ID, S>>::Target:
+            Send + Sync + Serialize + DeserializeOwned + IntoState<S> + MultiState<ID, E>,
+        <D as Decision>::Error: 'static,
+    {
+        if !self.can_make::<D>(principal) {
+            return Err(Error::Unauthorized {
+                principal: principal.id().to_string(),
+                decision: decision_name::<D>(),
+            });
+        }
+        let actor = Actor::current().unwrap_or_else(|| Actor::from(principal));
+        self.make(decision).with_actor(actor).await
+    }
+
+    /// Loads the current state of the given state query without making a decision.
+    ///
+    /// # Returns
+    ///
+    /// The loaded state, along with its version, or an error if the load fails.
+    pub async fn query_state<ID, E, S>(
+        &self,
+        state_query: S,
+    ) -> Result<LoadedState<ID, S>, BoxDynError>
+    where
+        ID: EventId,
+        E: Event + Clone,
+        SS: LoadState<ID, S, E>,
+    {
+        self.state_store.load(state_query).await
+    }
+}
+
+/// Generates a command enum for a set of decisions, along with a dispatcher that makes the decision of a
+/// command with a `DecisionMaker`.
+///
+/// Each variant wraps a decision, and the enum implements `From` for every decision. The attributes are
+/// applied to the enum, so deriving `Serialize` and `Deserialize` makes the commands serializable, for
+/// example to read them from a queue. All the decisions must share the same `Event` type, and their
+/// errors must convert into the error of the first decision.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// disintegrate::commands! {
+///     #[derive(Debug, Serialize, Deserialize)]
+///     pub enum CartCommand {
+///         AddItem(AddItem),
+///         RemoveItem(RemoveItem),
+///     }
+/// }
+///
+/// let command: CartCommand = serde_json::from_slice(&message)?;
+/// let events = command.dispatch(&decision_maker).await?;
+/// ```
+#[macro_export]
+macro_rules! commands {
+    (
+        @impl [$(#[$meta:meta])*] $vis:vis $name:ident, $error_decision:ty,
+        [$($variant:ident($ty:ty)),+]
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant($ty),)+
+        }
+
+        $(
+            impl From<$ty> for $name {
+                fn from(decision: $ty) -> Self {
+                    Self::$variant(decision)
+                }
+            }
+        )+
+
+        $crate::utils::paste! {
+            impl $name {
+                /// Makes the decision of the command, persisting the resulting events.
+                #[allow(clippy::useless_conversion)]
+                pub async fn dispatch<SS, ID, E, $([<$variant State>]),+>(
+                    self,
+                    decision_maker: &$crate::DecisionMaker<SS>,
+                ) -> Result<
+                    Vec<$crate::PersistedEvent<ID, E>>,
+                    $crate::DecisionError<<$error_decision as $crate::Decision>::Error>,
+                >
+                where
+                    ID: $crate::EventId,
+                    E: $crate::Event + Clone + Sync + Send + 'static,
+                    $(
+                        $ty: $crate::Decision<Event = E, StateQuery = [<$variant State>]>,
+                        <$ty as $crate::Decision>::Error:
+                            Into<<$error_decision as $crate::Decision>::Error> + 'static,
+                        SS: $crate::LoadState<ID, [<$variant State>], E>
+                            + $crate::PersistDecision<ID, [<$variant State>], E>,
+                        [<$variant State>]: Send
+                            + Sync
+                            + $crate::utils::Serialize
+                            + $crate::utils::DeserializeOwned
+                            + $crate::IntoStatePart<ID, [<$variant State>]>,
+                        <[<$variant State>] as $crate::IntoStatePart<ID, [<$variant State>]>>::Target:
+                            Send
+                                + Sync
+                                + $crate::utils::Serialize
+                                + $crate::utils::DeserializeOwned
+                                + $crate::IntoState<[<$variant State>]>
+                                + $crate::MultiState<ID, E>,
+                    )+
+                {
+                    match self {
+                        $(Self::$variant(decision) => decision_maker
+                            .make(decision)
+                            .await
+                            .map_err(|err| match err {
+                                $crate::DecisionError::EventStore(err) => {
+                                    $crate::DecisionError::EventStore(err)
+                                }
+                                $crate::DecisionError::StateStore(err) => {
+                                    $crate::DecisionError::StateStore(err)
+                                }
+                                $crate::DecisionError::Domain(err) => {
+                                    $crate::DecisionError::Domain(err.into())
+                                }
+                                $crate::DecisionError::Unauthorized { principal, decision } => {
+                                    $crate::DecisionError::Unauthorized { principal, decision }
+                                }
+                            }),)+
+                    }
+                }
+
+                /// Makes the decision of the command on behalf of the principal, once the policy of the
+                /// `DecisionMaker` allows it.
+                #[allow(clippy::useless_conversion)]
+                pub async fn dispatch_as<SS, ID, E, $([<$variant State>]),+>(
+                    self,
+                    decision_maker: &$crate::DecisionMaker<SS>,
+                    principal: &$crate::Principal,
+                ) -> Result<
+                    Vec<$crate::PersistedEvent<ID, E>>,
+                    $crate::DecisionError<<$error_decision as $crate::Decision>::Error>,
+                >
+                where
+                    ID: $crate::EventId,
+                    E: $crate::Event + Clone + Sync + Send + 'static,
+                    $(
+                        $ty: $crate::Decision<Event = E, StateQuery = [<$variant State>]>,
+                        <$ty as $crate::Decision>::Error:
+                            Into<<$error_decision as $crate::Decision>::Error> + 'static,
+                        SS: $crate::LoadState<ID, [<$variant State>], E>
+                            + $crate::PersistDecision<ID, [<$variant State>], E>,
+                        [<$variant State>]: Send
+                            + Sync
+                            + $crate::utils::Serialize
+                            + $crate::utils::DeserializeOwned
+                            + $crate::IntoStatePart<ID, [<$variant State>]>,
+                        <[<$variant State>] as $crate::IntoStatePart<ID, [<$variant State>]>>::Target:
+                            Send
+                                + Sync
+                                + $crate::utils::Serialize
+                                + $crate::utils::DeserializeOwned
+                                + $crate::IntoState<[<$variant State>]>
+                                + $crate::MultiState<ID, E>,
+                    )+
+                {
+                    let (allowed, decision) = match &self {
+                        $(Self::$variant(_) => (
+                            decision_maker.can_make::<$ty>(principal),
+                            $crate::decision_name::<$ty>(),
+                        ),)+
+                    };
+                    if !allowed {
+                        return Err($crate::DecisionError::Unauthorized {
+                            principal: principal.id().to_string(),
+                            decision,
+                        });
+                    }
+                    let actor = $crate::Actor::current().unwrap_or_else(|| $crate::Actor::from(principal));
+                    $crate::WithActor::with_actor(self.dispatch(decision_maker), actor).await
+                }
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $first_variant:ident($first:ty)
+            $(, $variant:ident($ty:ty))* $(,)?
+        }
+    ) => {
+        $crate::commands!(
+            @impl [$(#[$meta])*] $vis $name, $first,
+            [$first_variant($first) $(, $variant($ty))*]
+        );
+    };
 }
 
 /// Persists decision changes to the event store.
@@ -154,8 +368,8 @@ pub trait PersistDecision<ID: EventId, S, E: Event + Clone> {
 mod test {
     use mockall::predicate::eq;
 
-    use super::*;
-    use crate::{utils::tests::*, EventSourcedStateStore, NoSnapshot, StateQuery};
+    use super::{Error, *};
+    use crate::{utils::tests::*, EventSourcedStateStore, NoSnapshot, RolePolicy, StateQuery};
 
     #[tokio::test]
     async fn it_processes_a_decision() {
@@ -196,4 +410,123 @@ mod test {
 
         decision_maker.make(mock_add_item).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn it_classifies_the_errors() {
+        let mut database = MockDatabase::new();
+        database
+            .expect_stream()
+            .once()
+            .return_once(|_: &StreamQuery<i64, ShoppingCartEvent>| {
+                vec![Err(crate::utils::tests::Error)]
+            });
+        let mut mock_add_item = MockDecision::new();
+        mock_add_item
+            .expect_state_query()
+            .once()
+            .return_once(|| cart("c1", []));
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let decision_maker = DecisionMaker::new(state_store);
+        let err = decision_maker.make(mock_add_item).await.unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert!(!err.is_retryable());
+        assert_eq!(
+            crate::DecisionError::Domain(CartError("rejected".to_string())).kind(),
+            ErrorKind::Validation
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_the_decisions_not_allowed_to_the_principal() {
+        let database = MockDatabase::new();
+        let mock_add_item = MockDecision::new();
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let decision_maker = DecisionMaker::new(state_store)
+            .with_policy(RolePolicy::default().allow_decision::<MockDecision>("customer"));
+        let analyst = Principal::new("user-1").with_role("analyst");
+        let err = decision_maker
+            .make_as(&analyst, mock_add_item)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Unauthorized { ref principal, decision }
+                if principal == "user-1" && decision == decision_name::<MockDecision>()
+        ));
+        assert_eq!(err.kind(), ErrorKind::Validation);
+        assert!(decision_maker
+            .can_make::<MockDecision>(&Principal::new("user-2").with_role("customer")));
+    }
+
+    crate::commands! {
+        enum CartCommand {
+            AddItem(MockDecision),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_dispatches_a_command() {
+        let mut database = MockDatabase::new();
+        database
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+        database.expect_append().once().return_once(
+            |_, _: StreamQuery<i64, ShoppingCartEvent>, _| {
+                vec![PersistedEvent::new(2, item_added_event("p2", "c1"))]
+            },
+        );
+
+        let mut mock_add_item = MockDecision::new();
+        mock_add_item
+            .expect_state_query()
+            .once()
+            .return_once(|| cart("c1", []));
+        mock_add_item
+            .expect_validation_query()
+            .once()
+            .return_once(|| Option::<StreamQuery<i64, ShoppingCartEvent>>::None);
+        mock_add_item
+            .expect_process()
+            .once()
+            .return_once(|_| Ok(vec![item_added_event("p2", "c1")]));
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let decision_maker = DecisionMaker::new(state_store);
+
+        let command = CartCommand::from(mock_add_item);
+        let events = command.dispatch(&decision_maker).await.unwrap();
+
+        assert_eq!(
+            events,
+            vec![PersistedEvent::new(2, item_added_event("p2", "c1"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_the_commands_not_allowed_to_the_principal() {
+        let database = MockDatabase::new();
+        let mock_add_item = MockDecision::new();
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let decision_maker = DecisionMaker::new(state_store)
+            .with_policy(RolePolicy::default().allow_decision::<MockDecision>("customer"));
+        let analyst = Principal::new("user-1").with_role("analyst");
+
+        let command = CartCommand::from(mock_add_item);
+        let err = command
+            .dispatch_as(&decision_maker, &analyst)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::Unauthorized { ref principal, .. } if principal == "user-1"));
+    }
 }
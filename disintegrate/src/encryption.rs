@@ -0,0 +1,224 @@
+//! Encryption at rest of the data stored by the library, such as the snapshot payloads.
+//!
+//! The keys are supplied by a `KeyProvider`, so they can be kept in a key management service and rotated:
+//! the data is encrypted with the current key and the ID of the key is stored along with it, so data
+//! encrypted with previous keys can still be decrypted.
+//!
+//! The personal data of the data subjects can be encrypted with a key of each subject, kept by a
+//! `SubjectKeyStore`, and shredded by erasing the subject.
+//!
+//! Large volumes of data, such as the event payloads, are encrypted with data keys wrapped by the master
+//! key of a `MasterKeyProvider`, so rotating the master key does not rewrite the data.
+mod envelope;
+mod subject_keys;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+pub use envelope::{MasterKeyProvider, WrappedKey};
+pub use subject_keys::{
+    decrypt_for_subject, encrypt_for_subject, generate_subject_key, subject_of,
+    InMemorySubjectKeyStore, SubjectKeyStore,
+};
+
+use crate::{error_kind, BoxDynError, Classify, ErrorKind};
+
+/// A 256-bit encryption key.
+pub type EncryptionKey = [u8; 32];
+
+/// The size of the AES-GCM nonce stored with the encrypted data.
+const NONCE_SIZE: usize = 12;
+
+/// Provides the keys used to encrypt and decrypt data at rest.
+pub trait KeyProvider: Send + Sync {
+    /// Returns the ID of the key used to encrypt new data.
+    fn current_key_id(&self) -> String;
+
+    /// Returns the key with the given ID, or `None` if the key is unknown.
+    fn key(&self, key_id: &str) -> Option<EncryptionKey>;
+}
+
+impl fmt::Debug for dyn KeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyProvider")
+            .field("current_key_id", &self.current_key_id())
+            .finish()
+    }
+}
+
+/// A `KeyProvider` holding a fixed set of keys.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::StaticKeyProvider;
+///
+/// let key_provider = StaticKeyProvider::new("2024-06", [7; 32]).with_key("2023-01", [1; 32]);
+/// ```
+#[derive(Clone)]
+pub struct StaticKeyProvider {
+    current_key_id: String,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl StaticKeyProvider {
+    /// Creates a new `StaticKeyProvider` that encrypts with the given key.
+    pub fn new(key_id: impl Into<String>, key: EncryptionKey) -> Self {
+        let current_key_id = key_id.into();
+        Self {
+            keys: HashMap::from([(current_key_id.clone(), key)]),
+            current_key_id,
+        }
+    }
+
+    /// Adds a key used only to decrypt data, such as a key that has been rotated.
+    pub fn with_key(mut self, key_id: impl Into<String>, key: EncryptionKey) -> Self {
+        self.keys.insert(key_id.into(), key);
+        self
+    }
+}
+
+impl fmt::Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticKeyProvider")
+            .field("current_key_id", &self.current_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key_id(&self) -> String {
+        self.current_key_id.clone()
+    }
+
+    fn key(&self, key_id: &str) -> Option<EncryptionKey> {
+        self.keys.get(key_id).copied()
+    }
+}
+
+/// Generates a new random key.
+pub fn generate_key() -> EncryptionKey {
+    Aes256Gcm::generate_key(&mut OsRng).into()
+}
+
+/// Encrypts `plaintext` with AES-256-GCM using the current key of the provider.
+///
+/// The result contains the length of the key ID, the key ID, the nonce and the ciphertext.
+pub fn encrypt(key_provider: &dyn KeyProvider, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let key_id = key_provider.current_key_id();
+    let key_id_len = u8::try_from(key_id.len()).map_err(|_| Error::InvalidKeyId(key_id.clone()))?;
+    let key = key_provider
+        .key(&key_id)
+        .ok_or_else(|| Error::UnknownKey(key_id.clone()))?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::Encryption)?;
+
+    let mut encrypted = Vec::with_capacity(1 + key_id.len() + NONCE_SIZE + ciphertext.len());
+    encrypted.push(key_id_len);
+    encrypted.extend(key_id.as_bytes());
+    encrypted.extend(nonce.as_slice());
+    encrypted.extend(ciphertext);
+    Ok(encrypted)
+}
+
+/// Returns the ID of the key stored with the data encrypted by `encrypt`.
+pub fn key_id_of(encrypted: &[u8]) -> Result<String, Error> {
+    let (&key_id_len, rest) = encrypted.split_first().ok_or(Error::Decryption)?;
+    let key_id = rest.get(..key_id_len as usize).ok_or(Error::Decryption)?;
+    Ok(String::from_utf8_lossy(key_id).into_owned())
+}
+
+/// Decrypts data encrypted by `encrypt`, using the key of the provider with the stored key ID.
+pub fn decrypt(key_provider: &dyn KeyProvider, encrypted: &[u8]) -> Result<Vec<u8>, Error> {
+    let (&key_id_len, rest) = encrypted.split_first().ok_or(Error::Decryption)?;
+    if rest.len() < key_id_len as usize + NONCE_SIZE {
+        return Err(Error::Decryption);
+    }
+    let (key_id, rest) = rest.split_at(key_id_len as usize);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    let key_id = String::from_utf8_lossy(key_id);
+    let key = key_provider
+        .key(&key_id)
+        .ok_or_else(|| Error::UnknownKey(key_id.to_string()))?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::Decryption)
+}
+
+/// Encryption error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The key provider does not have the key.
+    #[error("unknown encryption key {0}")]
+    UnknownKey(String),
+    /// The key ID is longer than 255 bytes.
+    #[error("invalid encryption key id {0}")]
+    InvalidKeyId(String),
+    /// The data could not be encrypted.
+    #[error("encryption failed")]
+    Encryption,
+    /// The data is malformed or was not encrypted with the key.
+    #[error("decryption failed")]
+    Decryption,
+    /// The data subject was erased, so its data can no longer be encrypted.
+    #[error("data subject {0} was erased")]
+    ErasedSubject(String),
+    /// The subject key store failed.
+    #[error("subject key store error: {0}")]
+    KeyStore(#[source] BoxDynError),
+    /// The master key provider failed to wrap or unwrap a data key.
+    #[error("master key error: {0}")]
+    MasterKey(#[source] BoxDynError),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::UnknownKey(_) | Error::InvalidKeyId(_) | Error::ErasedSubject(_) => {
+                ErrorKind::Validation
+            }
+            Error::Encryption | Error::Decryption => ErrorKind::Corruption,
+            Error::KeyStore(err) | Error::MasterKey(err) => {
+                error_kind(err.as_ref()).unwrap_or(ErrorKind::Io)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_encrypts_and_decrypts_with_rotated_keys() {
+        let old_provider = StaticKeyProvider::new("k1", [1; 32]);
+        let encrypted = encrypt(&old_provider, b"secret").unwrap();
+        let provider = StaticKeyProvider::new("k2", [2; 32]).with_key("k1", [1; 32]);
+
+        assert!(!encrypted.windows(6).any(|window| window == b"secret"));
+        assert_eq!(decrypt(&provider, &encrypted).unwrap(), b"secret");
+        assert!(matches!(
+            decrypt(&StaticKeyProvider::new("k2", [2; 32]), &encrypted),
+            Err(Error::UnknownKey(key_id)) if key_id == "k1"
+        ));
+    }
+
+    #[test]
+    fn it_rejects_tampered_data() {
+        let provider = StaticKeyProvider::new("k1", [1; 32]);
+        let mut encrypted = encrypt(&provider, b"secret").unwrap();
+        *encrypted.last_mut().unwrap() ^= 1;
+
+        assert!(matches!(
+            decrypt(&provider, &encrypted),
+            Err(Error::Decryption)
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+//! Envelope encryption of the data with data keys wrapped by a master key.
+//!
+//! The data is encrypted with a data key, which is stored wrapped by a master key of a key management
+//! service. Rotating the master key only re-wraps the data keys, so the encrypted data is never rewritten.
+use async_trait::async_trait;
+
+use super::{decrypt, encrypt, EncryptionKey, Error, KeyProvider};
+use crate::BoxDynError;
+
+/// A data key encrypted with a master key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// The ID of the master key that wrapped the data key.
+    pub master_key_id: String,
+    /// The encrypted data key.
+    pub ciphertext: Vec<u8>,
+}
+
+/// Wraps and unwraps the data keys with master keys, such as the keys of a key management service.
+///
+/// Every `KeyProvider` is a master key provider holding the master keys locally.
+#[async_trait]
+pub trait MasterKeyProvider: Send + Sync {
+    /// Returns the ID of the master key wrapping the data keys.
+    fn current_master_key_id(&self) -> String;
+
+    /// Wraps the data key with the current master key.
+    async fn wrap_key(&self, data_key: &EncryptionKey) -> Result<WrappedKey, BoxDynError>;
+
+    /// Unwraps a data key wrapped by `wrap_key`, with the master key that wrapped it.
+    async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> Result<EncryptionKey, BoxDynError>;
+}
+
+#[async_trait]
+impl<K: KeyProvider> MasterKeyProvider for K {
+    fn current_master_key_id(&self) -> String {
+        self.current_key_id()
+    }
+
+    async fn wrap_key(&self, data_key: &EncryptionKey) -> Result<WrappedKey, BoxDynError> {
+        Ok(WrappedKey {
+            master_key_id: self.current_key_id(),
+            ciphertext: encrypt(self, data_key)?,
+        })
+    }
+
+    async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> Result<EncryptionKey, BoxDynError> {
+        let data_key = decrypt(self, &wrapped_key.ciphertext)?;
+        Ok(data_key.try_into().map_err(|_| Error::Decryption)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encryption::{generate_key, StaticKeyProvider};
+
+    #[tokio::test]
+    async fn it_rewraps_the_data_keys_with_the_rotated_master_key() {
+        let data_key = generate_key();
+        let old_master = StaticKeyProvider::new("m1", [1; 32]);
+        let wrapped_key = old_master.wrap_key(&data_key).await.unwrap();
+        let master = StaticKeyProvider::new("m2", [2; 32]).with_key("m1", [1; 32]);
+
+        let rewrapped_key = master
+            .wrap_key(&master.unwrap_key(&wrapped_key).await.unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(wrapped_key.master_key_id, "m1");
+        assert_eq!(rewrapped_key.master_key_id, "m2");
+        assert_eq!(
+            StaticKeyProvider::new("m2", [2; 32])
+                .unwrap_key(&rewrapped_key)
+                .await
+                .unwrap(),
+            data_key
+        );
+    }
+}
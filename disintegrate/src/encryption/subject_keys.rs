@@ -0,0 +1,165 @@
+//! Crypto-shredding of the personal data of the data subjects.
+//!
+//! The personal data of a subject is encrypted with a key of its own, kept out of the immutable event log.
+//! Erasing the subject destroys its key, which renders its historical data unreadable without rewriting
+//! the events.
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+
+use super::{decrypt, encrypt, generate_key, key_id_of, EncryptionKey, Error, KeyProvider};
+use crate::BoxDynError;
+
+/// Stores the encryption keys of the data subjects.
+#[async_trait]
+pub trait SubjectKeyStore: Send + Sync {
+    /// Returns the key of the subject, creating it if the subject has none yet.
+    ///
+    /// Returns `None` if the subject was erased: an erased subject never gets a new key.
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<EncryptionKey>, BoxDynError>;
+
+    /// Returns the key of the subject, or `None` if the subject has no key or was erased.
+    async fn existing_subject_key(
+        &self,
+        subject_id: &str,
+    ) -> Result<Option<EncryptionKey>, BoxDynError>;
+
+    /// Destroys the key of the subject, making the data encrypted with it unreadable.
+    ///
+    /// Returns `true` if the subject had a key.
+    async fn erase_subject(&self, subject_id: &str) -> Result<bool, BoxDynError>;
+}
+
+/// Generates a new random subject key.
+pub fn generate_subject_key() -> EncryptionKey {
+    generate_key()
+}
+
+/// Encrypts the personal data of a subject with its key.
+///
+/// The ID of the subject is stored with the encrypted data, so the data can be decrypted without knowing
+/// its subject. Fails with `Error::ErasedSubject` if the subject was erased.
+pub async fn encrypt_for_subject(
+    key_store: &dyn SubjectKeyStore,
+    subject_id: &str,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let key = key_store
+        .subject_key(subject_id)
+        .await
+        .map_err(Error::KeyStore)?
+        .ok_or_else(|| Error::ErasedSubject(subject_id.to_string()))?;
+    encrypt(&SubjectKey { subject_id, key }, plaintext)
+}
+
+/// Decrypts the personal data encrypted by `encrypt_for_subject`.
+///
+/// Returns `None` if the key of the subject was destroyed, so the data was shredded.
+pub async fn decrypt_for_subject(
+    key_store: &dyn SubjectKeyStore,
+    encrypted: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+    let subject_id = subject_of(encrypted)?;
+    let Some(key) = key_store
+        .existing_subject_key(&subject_id)
+        .await
+        .map_err(Error::KeyStore)?
+    else {
+        return Ok(None);
+    };
+    decrypt(
+        &SubjectKey {
+            subject_id: &subject_id,
+            key,
+        },
+        encrypted,
+    )
+    .map(Some)
+}
+
+/// Returns the ID of the subject stored with the data encrypted by `encrypt_for_subject`.
+pub fn subject_of(encrypted: &[u8]) -> Result<String, Error> {
+    key_id_of(encrypted)
+}
+
+/// The key of a subject, used as the only key of a provider.
+struct SubjectKey<'a> {
+    subject_id: &'a str,
+    key: EncryptionKey,
+}
+
+impl KeyProvider for SubjectKey<'_> {
+    fn current_key_id(&self) -> String {
+        self.subject_id.to_string()
+    }
+
+    fn key(&self, key_id: &str) -> Option<EncryptionKey> {
+        (key_id == self.subject_id).then_some(self.key)
+    }
+}
+
+/// An in-memory `SubjectKeyStore`.
+///
+/// The keys are shared between the clones of the store and lost when the process exits, which shreds
+/// the data of every subject. It is suited for tests.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySubjectKeyStore {
+    keys: Arc<RwLock<HashMap<String, Option<EncryptionKey>>>>,
+}
+
+#[async_trait]
+impl SubjectKeyStore for InMemorySubjectKeyStore {
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<EncryptionKey>, BoxDynError> {
+        let mut keys = self.keys.write().map_err(|e| e.to_string())?;
+        Ok(*keys
+            .entry(subject_id.to_string())
+            .or_insert_with(|| Some(generate_subject_key())))
+    }
+
+    async fn existing_subject_key(
+        &self,
+        subject_id: &str,
+    ) -> Result<Option<EncryptionKey>, BoxDynError> {
+        let keys = self.keys.read().map_err(|e| e.to_string())?;
+        Ok(keys.get(subject_id).copied().flatten())
+    }
+
+    async fn erase_subject(&self, subject_id: &str) -> Result<bool, BoxDynError> {
+        let mut keys = self.keys.write().map_err(|e| e.to_string())?;
+        Ok(keys
+            .insert(subject_id.to_string(), None)
+            .flatten()
+            .is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_shreds_the_data_of_the_erased_subjects() {
+        let key_store = InMemorySubjectKeyStore::default();
+        let alice = encrypt_for_subject(&key_store, "alice", b"alice@example.com")
+            .await
+            .unwrap();
+        let bob = encrypt_for_subject(&key_store, "bob", b"bob@example.com")
+            .await
+            .unwrap();
+
+        assert_eq!(subject_of(&alice).unwrap(), "alice");
+        assert!(key_store.erase_subject("alice").await.unwrap());
+
+        assert_eq!(decrypt_for_subject(&key_store, &alice).await.unwrap(), None);
+        assert_eq!(
+            decrypt_for_subject(&key_store, &bob).await.unwrap(),
+            Some(b"bob@example.com".to_vec())
+        );
+        assert!(matches!(
+            encrypt_for_subject(&key_store, "alice", b"alice@example.org").await,
+            Err(Error::ErasedSubject(subject_id)) if subject_id == "alice"
+        ));
+        assert!(!key_store.erase_subject("alice").await.unwrap());
+    }
+}
@@ -7,14 +7,55 @@
 //!
 //! For more details and specific implementations, refer to the trait documentation and individual implementations
 //! of the `EventStore` trait.
-use crate::{
-    event::{Event, EventId, PersistedEvent},
-    stream_query::StreamQuery,
-};
+use crate::{Classify, Event, EventId, HydrationWindow, PersistedEvent, StreamQuery};
 
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
 use std::error::Error as StdError;
+
+mod in_memory;
+
+pub use in_memory::{Error as InMemoryEventStoreError, InMemoryEventStore};
+
+/// The bounds and the order of a read of the event store.
+///
+/// By default, a read returns all the matching events in ascending order of ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    limit: Option<usize>,
+    descending: bool,
+}
+
+impl ReadOptions {
+    /// Creates the options of a read of all the matching events in ascending order.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the read to the first `limit` events in the order of the read.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Reads the events in descending order of ID, the most recent first.
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    /// Returns the maximum number of events to read, if any.
+    pub fn max_events(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Returns whether the events are read in descending order of ID.
+    pub fn is_descending(&self) -> bool {
+        self.descending
+    }
+}
+
 /// An event store.
 ///
 /// This trait provides methods for streaming events and appending events to the event store.
@@ -24,7 +65,8 @@ where
     ID: EventId,
     E: Event + Send + Sync,
 {
-    type Error: Send + Sync;
+    /// The error of the event store, classified so the conflicts and the transient failures can be retried.
+    type Error: Send + Sync + Classify;
 
     // Streams events based on the provided query.
     ///
@@ -43,6 +85,125 @@ where
         QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
         <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync;
 
+    /// Checks whether any event matches the provided query.
+    ///
+    /// The default implementation stops streaming at the first matching event. Storage backends should
+    /// override it with a lookup that does not fetch the events.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The stream query specifying the filtering conditions.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if at least one event matches the query, or an error.
+    async fn exists<QE>(&self, query: &StreamQuery<ID, QE>) -> Result<bool, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        Ok(self.stream(query).try_next().await?.is_some())
+    }
+
+    /// Returns the ID of the event preceding the window of the provided query, or the default ID if the
+    /// window includes the whole stream.
+    ///
+    /// The default implementation streams the query to find the beginning of a `LastEvents` window.
+    /// Since events do not carry the time they were appended, it returns the beginning of the stream for
+    /// a `Within` window. Storage backends should override it with a lookup that does not fetch the events.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The stream query specifying the filtering conditions.
+    /// * `window` - The window of the stream.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the ID after which the events of the window start, or an error.
+    async fn window_origin<QE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+        window: HydrationWindow,
+    ) -> Result<ID, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let HydrationWindow::LastEvents(events) = window else {
+            return Ok(ID::default());
+        };
+        let mut event_ids = std::collections::VecDeque::new();
+        let mut event_stream = self.stream(query);
+        while let Some(event) = event_stream.try_next().await? {
+            event_ids.push_back(event.id());
+            if event_ids.len() as u64 > events + 1 {
+                event_ids.pop_front();
+            }
+        }
+        Ok(if event_ids.len() as u64 > events {
+            event_ids.front().copied().unwrap_or_default()
+        } else {
+            ID::default()
+        })
+    }
+
+    /// Reads the events matching the provided query, bounded and ordered by the provided options.
+    ///
+    /// It is meant for activity feeds and diagnostics, where only a page of a stream is relevant. The
+    /// default implementation streams the query, keeping only its tail when the events are read in
+    /// descending order. Storage backends should override it with a bounded lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The stream query specifying the filtering conditions.
+    /// * `options` - The maximum number of events to return and their order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the matching events in the requested order, or an error.
+    async fn read<QE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+        options: ReadOptions,
+    ) -> Result<Vec<PersistedEvent<ID, QE>>, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let limit = options.max_events().unwrap_or(usize::MAX);
+        if !options.is_descending() {
+            return self.stream(query).take(limit).try_collect().await;
+        }
+        let mut events = std::collections::VecDeque::new();
+        let mut event_stream = self.stream(query);
+        while let Some(event) = event_stream.try_next().await? {
+            if limit == 0 {
+                break;
+            }
+            if events.len() == limit {
+                events.pop_front();
+            }
+            events.push_back(event);
+        }
+        Ok(events.into_iter().rev().collect())
+    }
+
+    /// Returns the last `limit` events matching the provided query, the most recent first.
+    ///
+    /// It is a shorthand for `read` with a limit in descending order.
+    async fn latest<QE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+        limit: usize,
+    ) -> Result<Vec<PersistedEvent<ID, QE>>, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.read(query, ReadOptions::new().limit(limit).descending())
+            .await
+    }
+
     /// Appends a batch of events to the event store.
     ///
     /// # Arguments
@@ -93,3 +254,56 @@ where
     where
         E: Clone + 'async_trait;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::tests::*;
+    use crate::StateQuery;
+
+    #[tokio::test]
+    async fn it_returns_the_latest_events_in_descending_order() {
+        let mut mock_store = MockDatabase::new();
+        mock_store.expect_stream().once().return_once(|_| {
+            event_stream([
+                item_added_event("p1", "c1"),
+                item_added_event("p2", "c1"),
+                item_added_event("p3", "c1"),
+            ])
+        });
+        let event_store = MockEventStore::new(mock_store);
+
+        let events = event_store
+            .latest(&cart("c1", []).query::<i64>(), 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id()).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_reads_the_first_events_in_ascending_order() {
+        let mut mock_store = MockDatabase::new();
+        mock_store.expect_stream().once().return_once(|_| {
+            event_stream([
+                item_added_event("p1", "c1"),
+                item_added_event("p2", "c1"),
+                item_added_event("p3", "c1"),
+            ])
+        });
+        let event_store = MockEventStore::new(mock_store);
+
+        let events = event_store
+            .read(&cart("c1", []).query::<i64>(), ReadOptions::new().limit(2))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id()).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+}
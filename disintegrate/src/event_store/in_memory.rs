@@ -0,0 +1,219 @@
+//! An in-memory event store.
+use std::error::Error as StdError;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+
+use crate::{
+    Actor, BoxDynError, Classify, ErrorKind, Event, EventStore, PersistedEvent, StreamQuery,
+};
+
+/// An in-memory `EventStore`.
+///
+/// Events are shared between the clones of the store and lost when the process exits. The store does not
+/// depend on an async runtime or on the operating system, so decisions can be run and tested anywhere the
+/// core compiles, including WebAssembly in the browser and on edge runtimes. The event IDs start from 1.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let event_store = InMemoryEventStore::<DomainEvent>::new();
+/// let decision_maker = DecisionMaker::new(EventSourcedStateStore::new(event_store, NoSnapshot));
+/// ```
+#[derive(Debug, Clone)]
+pub struct InMemoryEventStore<E: Event> {
+    events: Arc<RwLock<Vec<PersistedEvent<i64, E>>>>,
+}
+
+impl<E: Event> InMemoryEventStore<E> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            events: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Returns the number of events in the store.
+    pub fn len(&self) -> usize {
+        self.events
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns `true` if the store has no events.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<E: Event> Default for InMemoryEventStore<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Event + Clone> InMemoryEventStore<E> {
+    /// Stores the events, assigning them the IDs following the last event and the current actor.
+    fn push(
+        events: &mut Vec<PersistedEvent<i64, E>>,
+        new_events: Vec<E>,
+    ) -> Vec<PersistedEvent<i64, E>> {
+        let mut last_event_id = events.last().map_or(0, |event| event.id());
+        let actor = Actor::current();
+        let persisted: Vec<_> = new_events
+            .into_iter()
+            .map(|event| {
+                last_event_id += 1;
+                PersistedEvent::new(last_event_id, event).with_actor(actor.clone())
+            })
+            .collect();
+        events.extend(persisted.iter().cloned());
+        persisted
+    }
+}
+
+#[async_trait]
+impl<E> EventStore<i64, E> for InMemoryEventStore<E>
+where
+    E: Event + Clone + Send + Sync,
+{
+    type Error = Error;
+
+    fn stream<'a, QE>(
+        &'a self,
+        query: &'a StreamQuery<i64, QE>,
+    ) -> BoxStream<'a, Result<PersistedEvent<i64, QE>, Self::Error>>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        let events: Vec<_> = self
+            .events
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|event| query.matches_any(event))
+            .map(|event| {
+                QE::try_from(E::clone(event))
+                    .map(|query_event| {
+                        PersistedEvent::new(event.id(), query_event)
+                            .with_actor(event.actor().cloned())
+                    })
+                    .map_err(|err| Error::QueryEventMapping(Box::new(err)))
+            })
+            .collect();
+        stream::iter(events).boxed()
+    }
+
+    async fn append<QE>(
+        &self,
+        events: Vec<E>,
+        query: StreamQuery<i64, QE>,
+        last_event_id: i64,
+    ) -> Result<Vec<PersistedEvent<i64, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+        QE: Event + 'static + Clone + Send + Sync,
+    {
+        let mut stored = self.events.write().unwrap_or_else(PoisonError::into_inner);
+        if stored
+            .iter()
+            .any(|event| event.id() > last_event_id && query.matches_any(event))
+        {
+            return Err(Error::Concurrency);
+        }
+        Ok(Self::push(&mut stored, events))
+    }
+
+    async fn append_without_validation(
+        &self,
+        events: Vec<E>,
+    ) -> Result<Vec<PersistedEvent<i64, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+    {
+        let mut stored = self.events.write().unwrap_or_else(PoisonError::into_inner);
+        Ok(Self::push(&mut stored, events))
+    }
+}
+
+/// The error of an `InMemoryEventStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Events matching the query were appended after the state was loaded.
+    #[error("concurrency error")]
+    Concurrency,
+    /// An event of the store cannot be converted to the event type of the query.
+    #[error("query event mapping error: {0}")]
+    QueryEventMapping(#[source] BoxDynError),
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Concurrency => ErrorKind::Conflict,
+            Error::QueryEventMapping(_) => ErrorKind::Validation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::{Error, *};
+    use crate::{query, utils::tests::*};
+
+    #[tokio::test]
+    async fn it_streams_the_events_matching_the_query() {
+        let event_store = InMemoryEventStore::new();
+        event_store
+            .append_without_validation(vec![
+                item_added_event("p1", "c1"),
+                item_added_event("p2", "c2"),
+                item_removed_event("p1", "c1"),
+            ])
+            .await
+            .unwrap();
+
+        let events = event_store
+            .stream(&query!(ShoppingCartEvent; cart_id == "c1"))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                PersistedEvent::new(1, item_added_event("p1", "c1")),
+                PersistedEvent::new(3, item_removed_event("p1", "c1")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_the_appends_after_a_concurrent_change() {
+        let event_store = InMemoryEventStore::new();
+        let query = query!(ShoppingCartEvent; cart_id == "c1");
+        event_store
+            .append(vec![item_added_event("p1", "c1")], query.clone(), 0)
+            .await
+            .unwrap();
+        event_store
+            .append(vec![item_added_event("p2", "c2")], query.clone(), 1)
+            .await
+            .unwrap();
+
+        let err = event_store
+            .append(vec![item_removed_event("p1", "c1")], query, 0)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::Concurrency));
+        assert_eq!(err.kind(), ErrorKind::Conflict);
+        assert_eq!(event_store.len(), 2);
+    }
+}
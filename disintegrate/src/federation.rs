@@ -0,0 +1,333 @@
+//! A federation of event stores, routing the event domains to different backends.
+//!
+//! Large systems split their events across several physical stores, e.g. one database per bounded context, while
+//! the decisions and the listeners keep using a single `EventStore`. `FederatedEventStore` routes each event type to
+//! the store of its domain, chosen by event type or by domain identifier.
+//!
+//! # Ordering
+//!
+//! Each domain keeps the ordering of its own store: the events of a domain are streamed in the order of their IDs,
+//! and the appends to a domain are validated against the events of the domain. The IDs of different domains are
+//! not comparable, so a query and an append are always served by a single domain, and a query spanning several
+//! domains fails with `FederationError::CrossDomain`. The states and the listeners that need the events of
+//! several domains are split into one query per domain.
+use std::collections::HashMap;
+use std::error::Error as StdError;
+
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{
+    Classify, ErrorKind, Event, EventId, EventStore, HydrationWindow, Identifier, PersistedEvent,
+    ReadOptions, StreamQuery,
+};
+
+/// An event store routing the event types to the stores of their domains.
+///
+/// The event types that are not routed belong to the default store.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let event_store = FederatedEventStore::new(orders_store)
+///     .with_domain(payments_store, PaymentEvent::SCHEMA.events)
+///     .with_identifier_domain::<DomainEvent>(inventory_store, &ident!(#sku));
+///
+/// let decision_maker = DecisionMaker::new(EventSourcedStateStore::new(event_store, NoSnapshot));
+/// ```
+pub struct FederatedEventStore<ES> {
+    stores: Vec<ES>,
+    domains: HashMap<&'static str, usize>,
+}
+
+impl<ES> FederatedEventStore<ES> {
+    /// Creates a federation where all the event types belong to the default store.
+    pub fn new(default_store: ES) -> Self {
+        Self {
+            stores: vec![default_store],
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Adds a domain made of the given event types, stored in `store`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the event types already belongs to another domain.
+    pub fn with_domain(mut self, store: ES, event_types: &[&'static str]) -> Self {
+        let domain = self.stores.len();
+        for event_type in event_types {
+            assert!(
+                self.domains.insert(*event_type, domain).is_none(),
+                "the event type {event_type} already belongs to another domain"
+            );
+        }
+        self.stores.push(store);
+        self
+    }
+
+    /// Adds a domain made of the event types of `E` having the domain identifier `identifier`, stored in `store`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the event types already belongs to another domain.
+    pub fn with_identifier_domain<E: Event>(self, store: ES, identifier: &Identifier) -> Self {
+        let event_types: Vec<&'static str> = E::SCHEMA
+            .events_info
+            .iter()
+            .filter(|info| info.has_domain_identifier(identifier))
+            .map(|info| info.name)
+            .collect();
+        self.with_domain(store, &event_types)
+    }
+
+    /// Returns the domain of the event type.
+    fn domain(&self, event_type: &str) -> usize {
+        self.domains.get(event_type).copied().unwrap_or_default()
+    }
+
+    /// Returns the domain of the event types along with one of them, failing if they span several domains.
+    fn domain_of<SE>(
+        &self,
+        event_types: impl IntoIterator<Item = &'static str>,
+    ) -> Result<Option<(usize, &'static str)>, FederationError<SE>> {
+        let mut domain_of = None;
+        for event_type in event_types {
+            let domain = self.domain(event_type);
+            match domain_of {
+                None => domain_of = Some((domain, event_type)),
+                Some((first_domain, first)) if first_domain != domain => {
+                    return Err(FederationError::CrossDomain {
+                        first,
+                        second: event_type,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(domain_of)
+    }
+
+    /// Returns the domain of the event types of the query.
+    fn query_domain<ID, QE, SE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+    ) -> Result<Option<(usize, &'static str)>, FederationError<SE>>
+    where
+        ID: EventId,
+        QE: Event + Clone,
+    {
+        self.domain_of(query.filters().iter().flat_map(|filter| {
+            filter.events().iter().copied().filter(move |event_type| {
+                !filter
+                    .excluded_events()
+                    .is_some_and(|excluded| excluded.contains(event_type))
+            })
+        }))
+    }
+
+    /// Returns the store serving the query, which is the default store for a query without event types.
+    fn query_store<ID, QE, SE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+    ) -> Result<&ES, FederationError<SE>>
+    where
+        ID: EventId,
+        QE: Event + Clone,
+    {
+        Ok(&self.stores[self.query_domain(query)?.map_or(0, |(domain, _)| domain)])
+    }
+}
+
+#[async_trait]
+impl<ID, E, ES> EventStore<ID, E> for FederatedEventStore<ES>
+where
+    ID: EventId,
+    E: Event + Send + Sync,
+    ES: EventStore<ID, E> + Send + Sync,
+    ES::Error: 'static,
+{
+    type Error = FederationError<ES::Error>;
+
+    fn stream<'a, QE>(
+        &'a self,
+        query: &'a StreamQuery<ID, QE>,
+    ) -> BoxStream<'a, Result<PersistedEvent<ID, QE>, Self::Error>>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        match self.query_store(query) {
+            Ok(store) => store.stream(query).map_err(FederationError::Store).boxed(),
+            Err(err) => stream::once(async { Err(err) }).boxed(),
+        }
+    }
+
+    async fn exists<QE>(&self, query: &StreamQuery<ID, QE>) -> Result<bool, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.query_store(query)?
+            .exists(query)
+            .await
+            .map_err(FederationError::Store)
+    }
+
+    async fn window_origin<QE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+        window: HydrationWindow,
+    ) -> Result<ID, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.query_store(query)?
+            .window_origin(query, window)
+            .await
+            .map_err(FederationError::Store)
+    }
+
+    async fn read<QE>(
+        &self,
+        query: &StreamQuery<ID, QE>,
+        options: ReadOptions,
+    ) -> Result<Vec<PersistedEvent<ID, QE>>, Self::Error>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.query_store(query)?
+            .read(query, options)
+            .await
+            .map_err(FederationError::Store)
+    }
+
+    /// Appends the events to the store of their domain, validating them against the query in the same store.
+    ///
+    /// The events and the query must belong to the same domain.
+    async fn append<QE>(
+        &self,
+        events: Vec<E>,
+        query: StreamQuery<ID, QE>,
+        last_event_id: ID,
+    ) -> Result<Vec<PersistedEvent<ID, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+        QE: Event + 'static + Clone + Send + Sync,
+    {
+        let query_domain = self.query_domain(&query)?;
+        let events_domain = self.domain_of(events.iter().map(Event::name))?;
+        let domain = match (events_domain, query_domain) {
+            (Some((events_domain, first)), Some((query_domain, second)))
+                if events_domain != query_domain =>
+            {
+                return Err(FederationError::CrossDomain { first, second });
+            }
+            (Some((domain, _)), _) | (None, Some((domain, _))) => domain,
+            (None, None) => 0,
+        };
+        let store = &self.stores[domain];
+        store
+            .append(events, query, last_event_id)
+            .await
+            .map_err(FederationError::Store)
+    }
+
+    async fn append_without_validation(
+        &self,
+        events: Vec<E>,
+    ) -> Result<Vec<PersistedEvent<ID, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+    {
+        let domain = self
+            .domain_of(events.iter().map(Event::name))?
+            .map_or(0, |(domain, _)| domain);
+        self.stores[domain]
+            .append_without_validation(events)
+            .await
+            .map_err(FederationError::Store)
+    }
+}
+
+/// The error of a `FederatedEventStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum FederationError<SE> {
+    /// Error returned from the store of a domain.
+    #[error("event store error: {0}")]
+    Store(#[source] SE),
+    /// The event types of a query or an append belong to different domains.
+    #[error("the event types {first} and {second} belong to different domains")]
+    CrossDomain {
+        first: &'static str,
+        second: &'static str,
+    },
+}
+
+impl<SE: Classify> Classify for FederationError<SE> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            FederationError::Store(err) => err.kind(),
+            FederationError::CrossDomain { .. } => ErrorKind::Validation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{query, utils::tests::*};
+
+    #[tokio::test]
+    async fn it_routes_the_events_to_the_store_of_their_domain() {
+        let mut carts = MockDatabase::new();
+        carts
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+        let mut removals = MockDatabase::new();
+        removals
+            .expect_append_without_validation()
+            .once()
+            .return_once(|_| vec![PersistedEvent::new(1, item_removed_event("p1", "c1"))]);
+        let event_store = FederatedEventStore::new(MockEventStore::new(carts))
+            .with_domain(MockEventStore::new(removals), &["ItemRemoved"]);
+
+        let added = event_store
+            .stream(&query!(ShoppingCartEvent).exclude_events(&["ItemRemoved"]))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let removed = event_store
+            .append_without_validation(vec![item_removed_event("p1", "c1")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            added,
+            vec![PersistedEvent::new(1, item_added_event("p1", "c1"))]
+        );
+        assert_eq!(
+            removed,
+            vec![PersistedEvent::new(1, item_removed_event("p1", "c1"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_the_queries_spanning_several_domains() {
+        let event_store = FederatedEventStore::new(MockEventStore::new(MockDatabase::new()))
+            .with_domain(MockEventStore::new(MockDatabase::new()), &["ItemRemoved"]);
+
+        let err = event_store
+            .stream(&query!(ShoppingCartEvent))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, FederationError::CrossDomain { .. }));
+        assert_eq!(err.kind(), ErrorKind::Validation);
+    }
+}
@@ -0,0 +1,116 @@
+//! The live feed of events served by the web integrations.
+use std::error::Error as StdError;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Event, EventId, EventStore, PersistedEvent, Redactor, StreamQuery};
+
+/// The default interval between two polls of the event store by an idle `EventFeed`.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A live feed of the events matching a query.
+///
+/// The feed polls the event store for new events while it is idle, and resumes after the last event received by a
+/// client when it reconnects, so no event is missed. The feed ends when the event store fails, letting the client
+/// reconnect.
+pub struct EventFeed<ES, ID, E, QE>
+where
+    ID: EventId,
+    QE: Event + Clone,
+{
+    event_store: ES,
+    query: StreamQuery<ID, QE>,
+    poll_interval: Duration,
+    redactor: Option<Arc<Redactor>>,
+    event_type: PhantomData<fn() -> E>,
+}
+
+impl<ES, ID, E, QE> Clone for EventFeed<ES, ID, E, QE>
+where
+    ES: Clone,
+    ID: EventId,
+    QE: Event + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            event_store: self.event_store.clone(),
+            query: self.query.clone(),
+            poll_interval: self.poll_interval,
+            redactor: self.redactor.clone(),
+            event_type: PhantomData,
+        }
+    }
+}
+
+impl<ES, ID, E, QE> EventFeed<ES, ID, E, QE>
+where
+    ES: EventStore<ID, E> + Clone + Send + Sync + 'static,
+    ID: EventId,
+    E: Event + Send + Sync + 'static,
+    QE: TryFrom<E> + Event + Serialize + Clone + Send + Sync + 'static,
+    <QE as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    /// Creates a feed of the events matching the query.
+    pub fn new(event_store: ES, query: StreamQuery<ID, QE>) -> Self {
+        Self {
+            event_store,
+            query,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            redactor: None,
+            event_type: PhantomData,
+        }
+    }
+
+    /// Sets the interval between two polls of the event store while there are no new events, 1 second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Masks the personal data of the payloads sent to the clients.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Returns the events following the event `last_event_id`, along with their JSON payload.
+    pub fn events(
+        self,
+        last_event_id: ID,
+    ) -> impl Stream<Item = (PersistedEvent<ID, QE>, Value)> + Send + 'static {
+        async_stream::stream! {
+            let mut last_event_id = last_event_id;
+            loop {
+                let query = self.query.clone().change_origin(last_event_id);
+                let mut events = self.event_store.stream(&query);
+                let mut idle = true;
+                while let Some(event) = events.next().await {
+                    let Ok(event) = event else {
+                        return;
+                    };
+                    idle = false;
+                    last_event_id = event.id();
+                    let payload = self.payload(&event);
+                    yield (event, payload);
+                }
+                drop(events);
+                if idle {
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    fn payload(&self, event: &PersistedEvent<ID, QE>) -> Value {
+        let mut payload = serde_json::to_value(&**event).unwrap_or_default();
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_value(event.name(), &mut payload);
+        }
+        payload
+    }
+}
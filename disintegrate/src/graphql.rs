@@ -0,0 +1,203 @@
+//! Integration with [async-graphql](https://docs.rs/async-graphql).
+//!
+//! `EventFeed` exposes the events of a query as a GraphQL subscription, and `DecisionRejection` turns the errors of
+//! the decisions made by the mutations into GraphQL errors carrying a `code` extension.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! #[Object]
+//! impl MutationRoot {
+//!     async fn withdraw(&self, ctx: &Context<'_>, id: i64, amount: i32) -> Result<Vec<GraphQLEvent>> {
+//!         let decision_maker = ctx.data::<DecisionMaker>()?;
+//!         let events = decision_maker
+//!             .make(WithdrawAmount::new(id, amount))
+//!             .await
+//!             .map_err(DecisionRejection::<AccountError>::from)?;
+//!         Ok(events.into_iter().map(GraphQLEvent::from).collect())
+//!     }
+//! }
+//!
+//! #[Subscription]
+//! impl SubscriptionRoot {
+//!     async fn account_events(
+//!         &self,
+//!         ctx: &Context<'_>,
+//!         id: i64,
+//!         last_event_id: Option<ID>,
+//!     ) -> Result<impl Stream<Item = GraphQLEvent>> {
+//!         let event_store = ctx.data::<EventStore>()?.clone();
+//!         Ok(EventFeed::new(event_store, query!(DomainEvent; account_id == id)).subscribe(last_event_id))
+//!     }
+//! }
+//! ```
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use async_graphql::{ErrorExtensions, Json, SimpleObject, ID as GraphQLId};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+
+pub use crate::feed::EventFeed;
+use crate::{Classify, DecisionError, ErrorKind, Event, EventId, EventStore, PersistedEvent};
+
+/// An event returned by the subscriptions and the mutations.
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(name = "DomainEvent")]
+pub struct GraphQLEvent {
+    /// The ID of the event.
+    pub id: GraphQLId,
+    /// The name of the event.
+    pub name: String,
+    /// The JSON payload of the event.
+    pub payload: Json<Value>,
+}
+
+impl<ID, E> From<PersistedEvent<ID, E>> for GraphQLEvent
+where
+    ID: EventId + Display,
+    E: Event + Serialize,
+{
+    fn from(event: PersistedEvent<ID, E>) -> Self {
+        let payload = serde_json::to_value(&*event).unwrap_or_default();
+        Self {
+            id: event.id().to_string().into(),
+            name: event.name().to_string(),
+            payload: Json(payload),
+        }
+    }
+}
+
+/// Maps a domain error to the `code` extension of its GraphQL error.
+pub trait IntoErrorCode {
+    /// Returns the code of the GraphQL error.
+    fn error_code(&self) -> &'static str;
+}
+
+/// The GraphQL error of a decision that failed.
+///
+/// The domain errors get the code of their `IntoErrorCode` implementation, and the unauthorized decisions get
+/// `FORBIDDEN`. The errors of the stores are mapped by kind: the conflicts get `CONFLICT`, the transient failures
+/// `UNAVAILABLE` and the others `INTERNAL_SERVER_ERROR`. The message is the error message, except for the internal
+/// errors, whose details are not disclosed. The `retryable` extension tells whether the mutation can be retried.
+#[derive(Debug)]
+pub struct DecisionRejection<DE>(pub DecisionError<DE>);
+
+impl<DE> From<DecisionError<DE>> for DecisionRejection<DE> {
+    fn from(err: DecisionError<DE>) -> Self {
+        Self(err)
+    }
+}
+
+impl<DE> DecisionRejection<DE>
+where
+    DE: IntoErrorCode,
+{
+    /// Returns the code of the GraphQL error.
+    pub fn error_code(&self) -> &'static str {
+        match &self.0 {
+            DecisionError::Domain(err) => err.error_code(),
+            DecisionError::Unauthorized { .. } => "FORBIDDEN",
+            err => match err.kind() {
+                ErrorKind::Conflict => "CONFLICT",
+                ErrorKind::Transient => "UNAVAILABLE",
+                _ => "INTERNAL_SERVER_ERROR",
+            },
+        }
+    }
+}
+
+impl<DE> From<DecisionRejection<DE>> for async_graphql::Error
+where
+    DE: IntoErrorCode + Display,
+{
+    fn from(rejection: DecisionRejection<DE>) -> Self {
+        let code = rejection.error_code();
+        let retryable = rejection.0.is_retryable();
+        let message = if code == "INTERNAL_SERVER_ERROR" {
+            "internal server error".to_string()
+        } else {
+            rejection.0.to_string()
+        };
+        async_graphql::Error::new(message).extend_with(|_, extensions| {
+            extensions.set("code", code);
+            extensions.set("retryable", retryable);
+        })
+    }
+}
+
+impl<ES, ID, E, QE> EventFeed<ES, ID, E, QE>
+where
+    ES: EventStore<ID, E> + Clone + Send + Sync + 'static,
+    ID: EventId + Display + FromStr,
+    E: Event + Send + Sync + 'static,
+    QE: TryFrom<E> + Event + Serialize + Clone + Send + Sync + 'static,
+    <QE as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    /// Returns the stream of a subscription to the feed.
+    ///
+    /// The clients resubscribing with the ID of the last event they received resume after it.
+    pub fn subscribe(
+        self,
+        last_event_id: Option<GraphQLId>,
+    ) -> impl Stream<Item = GraphQLEvent> + Send + 'static {
+        let last_event_id = last_event_id
+            .and_then(|id| id.parse().ok())
+            .unwrap_or_default();
+        self.events(last_event_id)
+            .map(|(event, payload)| GraphQLEvent {
+                id: event.id().to_string().into(),
+                name: event.name().to_string(),
+                payload: Json(payload),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_graphql::Value as GraphQLValue;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("insufficient balance")]
+    struct InsufficientBalance;
+
+    impl IntoErrorCode for InsufficientBalance {
+        fn error_code(&self) -> &'static str {
+            "INSUFFICIENT_BALANCE"
+        }
+    }
+
+    fn code(err: DecisionError<InsufficientBalance>) -> Option<GraphQLValue> {
+        let err = async_graphql::Error::from(DecisionRejection(err));
+        err.extensions?.get("code").cloned()
+    }
+
+    #[test]
+    fn it_maps_the_decision_errors_to_error_codes() {
+        assert_eq!(
+            code(DecisionError::Domain(InsufficientBalance)),
+            Some(GraphQLValue::from("INSUFFICIENT_BALANCE"))
+        );
+        assert_eq!(
+            code(DecisionError::Unauthorized {
+                principal: "alice".to_string(),
+                decision: "WithdrawAmount",
+            }),
+            Some(GraphQLValue::from("FORBIDDEN"))
+        );
+        assert_eq!(
+            code(DecisionError::EventStore(Box::new(
+                crate::ClassifiedError::new(ErrorKind::Conflict, "concurrent append"),
+            ))),
+            Some(GraphQLValue::from("CONFLICT"))
+        );
+        let err = async_graphql::Error::from(DecisionRejection::<InsufficientBalance>(
+            DecisionError::StateStore("connection refused".into()),
+        ));
+        assert_eq!(err.message, "internal server error");
+    }
+}
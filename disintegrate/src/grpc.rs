@@ -0,0 +1,295 @@
+//! A [Tonic](https://docs.rs/tonic) gRPC service making the decisions of a domain.
+//!
+//! `CommandService` exposes each registered decision as a unary RPC. The request message is decoded with the Prost
+//! serde backend and converted into the decision, and the reply carries the IDs of the appended events.
+//!
+//! The protobuf definition of a service registering `OpenAccount` and `WithdrawAmount` looks like:
+//!
+//! ```proto
+//! package bank;
+//!
+//! service AccountService {
+//!   rpc OpenAccount(OpenAccountRequest) returns (DecisionReply);
+//!   rpc WithdrawAmount(WithdrawAmountRequest) returns (DecisionReply);
+//! }
+//! ```
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! struct AccountService;
+//!
+//! impl NamedService for AccountService {
+//!     const NAME: &'static str = "bank.AccountService";
+//! }
+//!
+//! let service = CommandService::<AccountService>::new()
+//!     .with_decision::<OpenAccount, proto::OpenAccountRequest, _, _, _>("OpenAccount", decision_maker.clone())
+//!     .with_decision::<WithdrawAmount, proto::WithdrawAmountRequest, _, _, _>("WithdrawAmount", decision_maker);
+//!
+//! Server::builder().add_service(service).serve(addr).await?;
+//! ```
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use disintegrate_serde::serde::prost::Prost;
+use disintegrate_serde::Deserializer;
+use futures::future::{self, BoxFuture};
+use prost::bytes::{Buf, BufMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::{http, Body, Service, StdError};
+use tonic::server::{Grpc, NamedService, UnaryService};
+use tonic::{Code, Request, Response, Status};
+
+use crate::{
+    Classify, Decision, DecisionError, DecisionMaker, ErrorKind, Event, EventId, IntoState,
+    IntoStatePart, LoadState, MultiState, PersistDecision,
+};
+
+/// The reply of a decision made by a `CommandService`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DecisionReply {
+    /// The IDs of the events appended by the decision.
+    #[prost(string, repeated, tag = "1")]
+    pub event_ids: Vec<String>,
+}
+
+type Rpc = Arc<dyn Fn(Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, Status>> + Send + Sync>;
+
+/// A gRPC service with an RPC for each registered decision.
+///
+/// The name of the service is the `NAME` of `N`, so the service can be added to a Tonic server. The errors of the
+/// decisions are mapped to gRPC statuses: the domain errors get `FAILED_PRECONDITION`, the unauthorized decisions
+/// `PERMISSION_DENIED`, the conflicts `ABORTED`, the transient failures `UNAVAILABLE` and the others `INTERNAL`,
+/// whose details are not disclosed. The requests that cannot be converted into their decision get
+/// `INVALID_ARGUMENT`.
+pub struct CommandService<N> {
+    rpcs: Arc<HashMap<&'static str, Rpc>>,
+    service: PhantomData<fn() -> N>,
+}
+
+impl<N> Clone for CommandService<N> {
+    fn clone(&self) -> Self {
+        Self {
+            rpcs: self.rpcs.clone(),
+            service: PhantomData,
+        }
+    }
+}
+
+impl<N> Default for CommandService<N> {
+    fn default() -> Self {
+        Self {
+            rpcs: Arc::new(HashMap::new()),
+            service: PhantomData,
+        }
+    }
+}
+
+impl<N: NamedService> NamedService for CommandService<N> {
+    const NAME: &'static str = N::NAME;
+}
+
+impl<N> CommandService<N> {
+    /// Creates a service without RPCs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the RPC `method`, which decodes its request into a `P` message, converts it into the decision `D`
+    /// and makes it with the `DecisionMaker`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `method` is already registered.
+    pub fn with_decision<D, P, SS, ID, E>(
+        mut self,
+        method: &'static str,
+        decision_maker: DecisionMaker<SS>,
+    ) -> Self
+    where
+        D: Decision<Event = E> + TryFrom<P> + Send + 'static,
+        <D as Decision>::Error: Display,
+        P: prost::Message + Default,
+        SS: LoadState<ID, D::StateQuery, E>
+            + PersistDecision<ID, D::StateQuery, E>
+            + Send
+            + Sync
+            + 'static,
+        ID: EventId + Display,
+        E: Event + Clone + Sync + Send + 'static,
+        D::StateQuery:
+            Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, D::StateQuery>,
+        <D::StateQuery as IntoStatePart<ID, D::StateQuery>>::Target: Send
+            + Sync
+            + Serialize
+            + DeserializeOwned
+            + IntoState<D::StateQuery>
+            + MultiState<ID, E>,
+    {
+        let decision_maker = Arc::new(decision_maker);
+        let rpc: Rpc = Arc::new(move |payload| {
+            let decision_maker = decision_maker.clone();
+            let decision = Prost::<D, P>::new().deserialize(&payload);
+            Box::pin(async move {
+                let decision = decision.map_err(|err| Status::invalid_argument(err.to_string()))?;
+                let events = decision_maker
+                    .make(decision)
+                    .await
+                    .map_err(decision_status)?;
+                let reply = DecisionReply {
+                    event_ids: events.iter().map(|event| event.id().to_string()).collect(),
+                };
+                Ok(prost::Message::encode_to_vec(&reply))
+            })
+        });
+        assert!(
+            Arc::make_mut(&mut self.rpcs).insert(method, rpc).is_none(),
+            "the RPC {method} is already registered"
+        );
+        self
+    }
+}
+
+/// Maps the error of a decision to its gRPC status.
+fn decision_status<DE: Display>(err: DecisionError<DE>) -> Status {
+    let code = match &err {
+        DecisionError::Domain(_) => Code::FailedPrecondition,
+        DecisionError::Unauthorized { .. } => Code::PermissionDenied,
+        err => match err.kind() {
+            ErrorKind::Conflict => Code::Aborted,
+            ErrorKind::Transient => Code::Unavailable,
+            _ => Code::Internal,
+        },
+    };
+    match code {
+        Code::Internal => Status::internal("internal error"),
+        code => Status::new(code, err.to_string()),
+    }
+}
+
+impl<N, B> Service<http::Request<B>> for CommandService<N>
+where
+    N: NamedService,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let rpc = req
+            .uri()
+            .path()
+            .strip_prefix('/')
+            .and_then(|path| path.strip_prefix(N::NAME))
+            .and_then(|path| path.strip_prefix('/'))
+            .and_then(|method| self.rpcs.get(method))
+            .cloned();
+        match rpc {
+            Some(rpc) => Box::pin(async move {
+                let mut grpc = Grpc::new(BytesCodec);
+                Ok(grpc.unary(RpcService(rpc), req).await)
+            }),
+            None => Box::pin(future::ready(Ok(Status::unimplemented(format!(
+                "unknown method {}",
+                req.uri().path()
+            ))
+            .into_http()))),
+        }
+    }
+}
+
+struct RpcService(Rpc);
+
+impl UnaryService<Vec<u8>> for RpcService {
+    type Response = Vec<u8>;
+    type Future = BoxFuture<'static, Result<Response<Vec<u8>>, Status>>;
+
+    fn call(&mut self, request: Request<Vec<u8>>) -> Self::Future {
+        let reply = (self.0)(request.into_inner());
+        Box::pin(async move { reply.await.map(Response::new) })
+    }
+}
+
+/// Passes the encoded messages through, so each RPC decodes its own request message.
+#[derive(Debug, Clone, Copy, Default)]
+struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = BytesCodec;
+    type Decoder = BytesCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BytesCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BytesCodec
+    }
+}
+
+impl Encoder for BytesCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Some(src.copy_to_bytes(src.remaining()).to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("insufficient balance")]
+    struct InsufficientBalance;
+
+    #[test]
+    fn it_maps_the_decision_errors_to_grpc_statuses() {
+        let status = decision_status(DecisionError::Domain(InsufficientBalance));
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "domain error: insufficient balance");
+
+        let status = decision_status(DecisionError::<InsufficientBalance>::Unauthorized {
+            principal: "alice".to_string(),
+            decision: "WithdrawAmount",
+        });
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        let status = decision_status(DecisionError::<InsufficientBalance>::EventStore(Box::new(
+            crate::ClassifiedError::new(ErrorKind::Conflict, "concurrent append"),
+        )));
+        assert_eq!(status.code(), Code::Aborted);
+
+        let status = decision_status(DecisionError::<InsufficientBalance>::StateStore(
+            "connection refused".into(),
+        ));
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message(), "internal error");
+    }
+}
@@ -0,0 +1,150 @@
+//! Health checks of the components of an event-sourced application.
+use async_trait::async_trait;
+use futures::future::join_all;
+
+/// The health of a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The component works as expected.
+    Healthy,
+    /// The component works, but it needs attention, e.g. it is falling behind.
+    Degraded(String),
+    /// The component does not work.
+    Unhealthy(String),
+}
+
+impl HealthStatus {
+    fn severity(&self) -> u8 {
+        match self {
+            Self::Healthy => 0,
+            Self::Degraded(_) => 1,
+            Self::Unhealthy(_) => 2,
+        }
+    }
+}
+
+/// A component that reports its health, such as an event store, an event listener or a snapshotter.
+#[async_trait]
+pub trait ComponentHealth: Send + Sync {
+    /// Returns the name of the component in the `HealthReport`.
+    fn name(&self) -> &str;
+
+    /// Checks the health of the component.
+    async fn health(&self) -> HealthStatus;
+}
+
+/// The health of a component in a `HealthReport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentReport {
+    /// The name of the component.
+    pub name: String,
+    /// The health of the component.
+    pub status: HealthStatus,
+}
+
+/// The health of the components of an application.
+///
+/// The report backs the readiness and liveness endpoints of the application: it is ready when none of
+/// its components is unhealthy, while degraded components are reported without taking it out of service.
+///
+/// # Example
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use disintegrate::{ComponentHealth, HealthReport, HealthStatus};
+///
+/// struct Cache;
+///
+/// #[async_trait]
+/// impl ComponentHealth for Cache {
+///     fn name(&self) -> &str {
+///         "cache"
+///     }
+///
+///     async fn health(&self) -> HealthStatus {
+///         HealthStatus::Degraded("cold cache".to_string())
+///     }
+/// }
+///
+/// # futures::executor::block_on(async {
+/// let report = HealthReport::check(&[&Cache]).await;
+/// assert!(report.is_ready());
+/// assert_eq!(report.status(), &HealthStatus::Degraded("cold cache".to_string()));
+/// # });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    components: Vec<ComponentReport>,
+}
+
+impl HealthReport {
+    /// Checks the health of the components concurrently.
+    pub async fn check(components: &[&dyn ComponentHealth]) -> Self {
+        let statuses = join_all(components.iter().map(|component| component.health())).await;
+        Self {
+            components: components
+                .iter()
+                .zip(statuses)
+                .map(|(component, status)| ComponentReport {
+                    name: component.name().to_string(),
+                    status,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the health of each component.
+    pub fn components(&self) -> &[ComponentReport] {
+        &self.components
+    }
+
+    /// Returns the worst health among the components, or `Healthy` if there are none.
+    pub fn status(&self) -> &HealthStatus {
+        self.components
+            .iter()
+            .map(|component| &component.status)
+            .max_by_key(|status| status.severity())
+            .unwrap_or(&HealthStatus::Healthy)
+    }
+
+    /// Returns `true` if none of the components is unhealthy.
+    pub fn is_ready(&self) -> bool {
+        !matches!(self.status(), HealthStatus::Unhealthy(_))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Component(&'static str, HealthStatus);
+
+    #[async_trait]
+    impl ComponentHealth for Component {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn health(&self) -> HealthStatus {
+            self.1.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_worst_health_of_the_components() {
+        let event_store = Component("event_store", HealthStatus::Healthy);
+        let listeners = Component("listeners", HealthStatus::Degraded("lagging".to_string()));
+        let snapshotter = Component("snapshotter", HealthStatus::Unhealthy("down".to_string()));
+
+        let report = HealthReport::check(&[&event_store, &listeners]).await;
+        assert!(report.is_ready());
+        assert_eq!(report.components()[0].name, "event_store");
+
+        let report = HealthReport::check(&[&event_store, &listeners, &snapshotter]).await;
+        assert!(!report.is_ready());
+        assert_eq!(
+            report.status(),
+            &HealthStatus::Unhealthy("down".to_string())
+        );
+    }
+}
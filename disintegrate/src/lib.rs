@@ -1,47 +1,143 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "actix")]
+pub mod actix;
+mod authorization;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "store")]
 mod decision;
-mod domain_identifier;
-mod event;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(feature = "store")]
 mod event_store;
-mod identifier;
+#[cfg(feature = "store")]
+mod federation;
+#[cfg(any(feature = "axum", feature = "actix", feature = "graphql"))]
+mod feed;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "store")]
+mod health;
+#[cfg(feature = "store")]
 mod listener;
+#[cfg(feature = "store")]
+mod redaction;
+#[cfg(feature = "store")]
+mod runtime;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "store")]
+mod simulation;
+#[cfg(feature = "store")]
+mod snapshot_store;
 mod state;
+#[cfg(feature = "store")]
 mod state_store;
-mod stream_query;
 mod testing;
+#[cfg(feature = "store")]
+mod time;
+#[cfg(feature = "tower")]
+pub mod tower;
 pub mod utils;
+#[cfg(feature = "store")]
+mod workflow;
 
 #[doc(inline)]
-pub use crate::decision::{Decision, DecisionMaker, Error as DecisionError, PersistDecision};
+pub use crate::authorization::{decision_name, Policy, Principal, RolePolicy};
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::domain_identifier::{DomainIdentifier, DomainIdentifierSet};
+pub use crate::decision::{DecisionMaker, Error as DecisionError, PersistDecision};
+#[cfg(feature = "encryption")]
 #[doc(inline)]
-pub use crate::event::{
-    DomainIdentifierInfo, Event, EventId, EventInfo, EventSchema, PersistedEvent,
+pub use crate::encryption::{
+    InMemorySubjectKeyStore, KeyProvider, StaticKeyProvider, SubjectKeyStore,
 };
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::event_store::EventStore;
+pub use crate::event_store::{
+    EventStore, InMemoryEventStore, InMemoryEventStoreError, ReadOptions,
+};
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::identifier::{Identifier, IdentifierType, IdentifierValue, IntoIdentifierValue};
+pub use crate::federation::{FederatedEventStore, FederationError};
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::listener::EventListener;
+pub use crate::health::{ComponentHealth, ComponentReport, HealthReport, HealthStatus};
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::state::{IntoState, IntoStatePart, MultiState, StateMutate, StatePart, StateQuery};
+pub use crate::listener::{BatchError, EventListener};
+#[cfg(feature = "store")]
 #[doc(inline)]
-pub use crate::state_store::{
-    EventSourcedStateStore, LoadState, LoadedState, NoSnapshot, SnapshotConfig, StateSnapshotter,
-    WithSnapshot,
+pub use crate::redaction::Redactor;
+#[cfg(feature = "runtime-async-std")]
+#[doc(inline)]
+pub use crate::runtime::AsyncStdRuntime;
+#[cfg(feature = "store")]
+#[doc(inline)]
+pub use crate::runtime::Runtime;
+#[cfg(feature = "runtime-smol")]
+#[doc(inline)]
+pub use crate::runtime::SmolRuntime;
+#[cfg(feature = "runtime-tokio")]
+#[doc(inline)]
+pub use crate::runtime::TokioRuntime;
+#[cfg(feature = "signing")]
+#[doc(inline)]
+pub use crate::signing::{Ed25519Signer, Ed25519Verifier, SignatureVerifier, Signer};
+#[cfg(feature = "store")]
+#[doc(inline)]
+pub use crate::simulation::{
+    Faults, InjectedFault, SimulatedEventStore, SimulatedEventStoreError, SimulatedRuntime,
+    Simulation,
+};
+#[cfg(feature = "snapshot-redis")]
+#[doc(inline)]
+pub use crate::snapshot_store::RedisSnapshotStore;
+#[cfg(feature = "store")]
+#[doc(inline)]
+pub use crate::snapshot_store::{
+    snapshot_key, FileSnapshotStore, InMemorySnapshotStore, SnapshotInfo, SnapshotPolicy,
+    SnapshotRetention, SnapshotStore, Snapshotter, StoredSnapshot,
 };
 #[doc(inline)]
-pub use crate::stream_query::{query, StreamFilter, StreamQuery};
+pub use crate::state::MultiState;
+#[cfg(feature = "store")]
+#[doc(inline)]
+pub use crate::state_store::{
+    exists, query_state, query_state_at, EventSourcedStateStore, ExternalStateStore, GrowthWarning,
+    GrowthWarnings, LoadState, LoadStateAt, LoadedState, NoSnapshot, SnapshotConfig,
+    SnapshotStateStore, StateQuerier, StateRepository, StateSnapshotter, WithSnapshot,
+};
 #[doc(inline)]
 pub use crate::testing::TestHarness;
+#[cfg(feature = "store")]
+#[doc(inline)]
+pub use crate::workflow::{
+    DecisionActivity, IdempotencyStore, IdempotencyToken, InMemoryIdempotencyStore, SignalListener,
+    WorkflowSignal, WorkflowSignaler,
+};
 
-pub type BoxDynError = Box<dyn std::error::Error + 'static + Send + Sync>;
+#[doc(hidden)]
+pub use disintegrate_core::{
+    all_the_tuples, assert_domain_identifiers, const_count_dup, const_slice_iter, const_slice_sort,
+    const_slice_unique, const_slices_concat, filter, EventsSubset,
+};
+pub use disintegrate_core::{any_of, domain_identifiers, event_types, event_union, ident, union};
+#[doc(inline)]
+pub use disintegrate_core::{
+    error_kind, query, Actor, ActorKind, ActorScoped, BoxDynError, ClassifiedError, Classify,
+    Decision, DomainIdentifier, DomainIdentifierInfo, DomainIdentifierSet, ErrorKind, Event,
+    EventDescriptor, EventId, EventInfo, EventSchema, HydrationWindow, Identifier, IdentifierRange,
+    IdentifierType, IdentifierValue, IntoIdentifierValue, IntoState, IntoStatePart,
+    ParseActorError, PersistedEvent, ProjectionError, StateMutate, StatePart, StateQuery,
+    StreamFilter, StreamQuery, WithActor,
+};
 
 #[cfg(feature = "macros")]
-pub use disintegrate_macros::{Event, StateQuery};
+pub use disintegrate_macros::{Event, IntoIdentifierValue, StateQuery};
 
 #[cfg(feature = "serde")]
 pub mod serde {
@@ -64,15 +160,3 @@ pub mod serde {
     #[doc(inline)]
     pub use disintegrate_serde::{Deserializer, Serde, Serializer};
 }
-
-#[doc(hidden)]
-#[macro_export]
-macro_rules! all_the_tuples {
-    ($name:ident) => {
-        $name!([], T1);
-        $name!([T1], T2);
-        $name!([T1, T2], T3);
-        $name!([T1, T2, T3], T4);
-        $name!([T1, T2, T3, T4], T5);
-    };
-}
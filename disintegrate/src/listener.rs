@@ -1,10 +1,7 @@
 //! Event listener handles events that are emitted.
 use async_trait::async_trait;
 
-use crate::{
-    event::{Event, EventId, PersistedEvent},
-    stream_query::StreamQuery,
-};
+use crate::{Event, EventId, PersistedEvent, StreamQuery};
 
 /// Represents an event listener, which handles persisted events.
 #[async_trait]
@@ -27,4 +24,38 @@ pub trait EventListener<ID: EventId, E: Event + Clone>: Send + Sync {
     /// This method handle the event coming from the event stream.
     /// The method returns a result indicating success or an error that may occur during the event handler.
     async fn handle(&self, event: PersistedEvent<ID, E>) -> Result<(), Self::Error>;
+
+    /// Handles a batch of events.
+    ///
+    /// The default implementation handles the events one by one. Listeners that can process several events
+    /// at once, e.g. by sending them to a remote service in a single request, can override this method.
+    /// When an error occurs, the returned `BatchError` holds the ID of the last event handled successfully,
+    /// so the events after it are delivered again.
+    async fn handle_batch(
+        &self,
+        events: Vec<PersistedEvent<ID, E>>,
+    ) -> Result<(), BatchError<ID, Self::Error>>
+    where
+        E: Send + Sync + 'async_trait,
+    {
+        let mut last_handled_event_id = None;
+        for event in events {
+            let event_id = event.id();
+            self.handle(event).await.map_err(|error| BatchError {
+                last_handled_event_id,
+                error,
+            })?;
+            last_handled_event_id = Some(event_id);
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a batch of events is partially handled.
+#[derive(Debug)]
+pub struct BatchError<ID, E> {
+    /// The ID of the last event handled successfully, if any.
+    pub last_handled_event_id: Option<ID>,
+    /// The error that stopped the batch.
+    pub error: E,
 }
@@ -0,0 +1,185 @@
+//! Redaction of the personal data held by the event payloads.
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::Event;
+
+/// The value replacing the redacted fields by default.
+const DEFAULT_MASK: &str = "***";
+
+/// Masks the payload fields holding personal data, marked with `#[pii]` in the events.
+///
+/// The payloads are kept complete in the event store: the redactor is applied where they leave it, such as
+/// the exports, the logs and the subscriptions. The fields are masked at any depth of the JSON payloads, so
+/// the layout chosen by the serializer, e.g. an externally tagged enum, does not matter.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// #[derive(Event, Clone, Serialize, Deserialize)]
+/// enum UserEvent {
+///     UserRegistered {
+///         #[id]
+///         user_id: String,
+///         #[pii]
+///         email: String,
+///     },
+/// }
+///
+/// let redactor = Redactor::of::<UserEvent>();
+/// // {"user_id":"u1","email":"***"}
+/// let redacted = redactor.redact("UserRegistered", br#"{"user_id":"u1","email":"jane@example.com"}"#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    fields: HashMap<&'static str, &'static [&'static str]>,
+    mask: Value,
+}
+
+impl Redactor {
+    /// Creates a redactor masking the personal data of the events of `E`.
+    pub fn of<E: Event>() -> Self {
+        Self {
+            fields: HashMap::new(),
+            mask: Value::from(DEFAULT_MASK),
+        }
+        .with_events::<E>()
+    }
+
+    /// Masks the personal data of the events of `E` as well, for the components serving several event types.
+    pub fn with_events<E: Event>(mut self) -> Self {
+        for event_type in E::SCHEMA.events {
+            let fields = E::pii_fields(event_type);
+            if !fields.is_empty() {
+                self.fields.insert(event_type, fields);
+            }
+        }
+        self
+    }
+
+    /// Sets the value replacing the redacted fields, `"***"` by default.
+    pub fn with_mask(mut self, mask: impl Into<Value>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    /// Returns `true` if the payloads of the event named `event_type` hold personal data.
+    pub fn is_sensitive(&self, event_type: &str) -> bool {
+        self.fields.contains_key(event_type)
+    }
+
+    /// Masks the personal data of a JSON payload of the event named `event_type`.
+    pub fn redact_value(&self, event_type: &str, payload: &mut Value) {
+        if let Some(fields) = self.fields.get(event_type) {
+            mask(payload, fields, &self.mask);
+        }
+    }
+
+    /// Masks the personal data of a serialized payload of the event named `event_type`.
+    ///
+    /// The payloads of the sensitive events must be serialized as JSON: a payload that cannot be parsed is
+    /// replaced by the mask altogether.
+    pub fn redact(&self, event_type: &str, payload: &[u8]) -> Vec<u8> {
+        if !self.is_sensitive(event_type) {
+            return payload.to_vec();
+        }
+        let redacted = match serde_json::from_slice(payload) {
+            Ok(mut payload) => {
+                self.redact_value(event_type, &mut payload);
+                payload
+            }
+            Err(_) => self.mask.clone(),
+        };
+        serde_json::to_vec(&redacted).expect("a JSON value is serializable")
+    }
+}
+
+/// Replaces the values of the `fields` with the `mask`, at any depth of the value.
+fn mask(value: &mut Value, fields: &[&str], mask_value: &Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if fields.contains(&key.as_str()) {
+                    *value = mask_value.clone();
+                } else {
+                    mask(value, fields, mask_value);
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                mask(value, fields, mask_value);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{domain_identifiers, ident, DomainIdentifierSet, EventInfo, EventSchema};
+
+    #[derive(Clone)]
+    struct UserRegistered;
+
+    impl Event for UserRegistered {
+        const SCHEMA: EventSchema = EventSchema {
+            events: &["UserRegistered"],
+            events_info: &[&EventInfo {
+                name: "UserRegistered",
+                domain_identifiers: &[&ident!(#user_id)],
+            }],
+            domain_identifiers: &[],
+        };
+
+        fn pii_fields(_event_type: &str) -> &'static [&'static str] {
+            &["email", "phone"]
+        }
+
+        fn name(&self) -> &'static str {
+            "UserRegistered"
+        }
+
+        fn domain_identifiers(&self) -> DomainIdentifierSet {
+            domain_identifiers! {}
+        }
+    }
+
+    #[test]
+    fn it_masks_the_pii_fields_at_any_depth() {
+        let redactor = Redactor::of::<UserRegistered>().with_mask(json!(null));
+        let mut payload = json!({
+            "UserRegistered": {
+                "user_id": "u1",
+                "email": "jane@example.com",
+                "contacts": [{"phone": "555-0100", "kind": "home"}]
+            }
+        });
+
+        redactor.redact_value("UserRegistered", &mut payload);
+
+        assert_eq!(
+            payload,
+            json!({
+                "UserRegistered": {
+                    "user_id": "u1",
+                    "email": null,
+                    "contacts": [{"phone": null, "kind": "home"}]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn it_masks_the_payloads_that_cannot_be_parsed() {
+        let redactor = Redactor::of::<UserRegistered>();
+
+        assert_eq!(redactor.redact("UserRegistered", b"\x01\x02"), b"\"***\"");
+        assert_eq!(redactor.redact("OrderPlaced", b"\x01\x02"), b"\x01\x02");
+        assert!(!redactor.is_sensitive("OrderPlaced"));
+    }
+}
@@ -0,0 +1,114 @@
+//! Async runtimes running the background tasks and the timers.
+//!
+//! The library does not depend on a specific async runtime, except for the background tasks, such as the event
+//! listeners, and their timers, which are run by a `Runtime`. The runtimes of Tokio, async-std and smol are
+//! provided behind the `runtime-tokio`, `runtime-async-std` and `runtime-smol` features, and other runtimes can
+//! be plugged in by implementing the trait.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let event_listener = PgEventListener::builder(event_store)
+//!     .with_runtime(AsyncStdRuntime)
+//!     .register_listener(projection, PgEventListenerConfig::poller(Duration::from_secs(1)));
+//! ```
+use std::time::Duration;
+
+use futures::future::{BoxFuture, RemoteHandle};
+use futures::{Future, FutureExt};
+
+/// An async runtime, spawning the background tasks and running their timers.
+pub trait Runtime: Send + Sync {
+    /// Spawns a task running in the background until it completes.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+
+    /// Returns a future completing after `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl dyn Runtime {
+    /// Spawns a task, returning a handle that resolves to the output of the task.
+    ///
+    /// The task is cancelled when the handle is dropped.
+    pub fn spawn_with_handle<F>(&self, future: F) -> RemoteHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = future.remote_handle();
+        self.spawn(task.boxed());
+        handle
+    }
+}
+
+/// The [Tokio](https://tokio.rs) runtime.
+///
+/// The tasks are spawned on the runtime of the caller, which must be within a Tokio runtime.
+#[cfg(feature = "runtime-tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "runtime-tokio")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// The [async-std](https://async.rs) runtime.
+#[cfg(feature = "runtime-async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "runtime-async-std")]
+impl Runtime for AsyncStdRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        async_std::task::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        async_std::task::sleep(duration).boxed()
+    }
+}
+
+/// The [smol](https://docs.rs/smol) runtime.
+///
+/// The tasks are spawned on the global executor of smol.
+#[cfg(feature = "runtime-smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolRuntime;
+
+#[cfg(feature = "runtime-smol")]
+impl Runtime for SmolRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        smol::spawn(task).detach();
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        smol::Timer::after(duration).map(|_| ()).boxed()
+    }
+}
+
+#[cfg(all(test, feature = "runtime-tokio"))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_spawns_the_tasks_on_the_runtime() {
+        let runtime: Arc<dyn Runtime> = Arc::new(TokioRuntime);
+        let sleep = runtime.sleep(Duration::from_millis(10));
+
+        let handle = runtime.spawn_with_handle(async move {
+            sleep.await;
+            42
+        });
+
+        assert_eq!(handle.await, 42);
+    }
+}
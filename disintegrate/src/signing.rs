@@ -0,0 +1,199 @@
+//! Signing of the appended events, so their origin can be verified by the readers.
+//!
+//! The events are signed with a `Signer` and verified with a `SignatureVerifier`. The ID of the signing key
+//! is stored along with the signature, so each party can sign with its own keys and the keys can be rotated.
+use std::collections::HashMap;
+use std::fmt;
+
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+
+use crate::{Classify, ErrorKind};
+
+/// Signs the messages with the current key of the signer.
+pub trait Signer: Send + Sync {
+    /// Returns the ID of the key used to sign.
+    fn key_id(&self) -> String;
+
+    /// Returns the signature of `message`.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Verifies the signatures made by the `Signer`s.
+pub trait SignatureVerifier: Send + Sync {
+    /// Verifies the `signature` of `message` made with the key with the given ID.
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> Result<(), Error>;
+}
+
+impl fmt::Debug for dyn Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("key_id", &self.key_id())
+            .finish()
+    }
+}
+
+/// A `Signer` using an Ed25519 key.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::{Ed25519Signer, Ed25519Verifier};
+///
+/// let signer = Ed25519Signer::new("billing-2024", [7; 32]);
+/// let verifier = Ed25519Verifier::default().with_key("billing-2024", signer.verifying_key());
+/// ```
+#[derive(Clone)]
+pub struct Ed25519Signer {
+    key_id: String,
+    key: SigningKey,
+}
+
+impl Ed25519Signer {
+    /// Creates a new `Ed25519Signer` from the 32-byte secret key.
+    pub fn new(key_id: impl Into<String>, secret_key: [u8; 32]) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key: SigningKey::from_bytes(&secret_key),
+        }
+    }
+
+    /// Returns the public key that verifies the signatures of the signer.
+    pub fn verifying_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+}
+
+impl fmt::Debug for Ed25519Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ed25519Signer")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Signer for Ed25519Signer {
+    fn key_id(&self) -> String {
+        self.key_id.clone()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(ed25519_dalek::Signer::sign(&self.key, message)
+            .to_bytes()
+            .to_vec())
+    }
+}
+
+/// A `SignatureVerifier` holding the Ed25519 public keys of the trusted signers.
+#[derive(Debug, Clone, Default)]
+pub struct Ed25519Verifier {
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl Ed25519Verifier {
+    /// Trusts the signatures made with the key with the given ID.
+    pub fn with_key(mut self, key_id: impl Into<String>, public_key: [u8; 32]) -> Self {
+        self.keys.insert(key_id.into(), public_key);
+        self
+    }
+}
+
+impl SignatureVerifier for Ed25519Verifier {
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> Result<(), Error> {
+        let public_key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| Error::UnknownKey(key_id.to_string()))?;
+        let public_key = VerifyingKey::from_bytes(public_key)
+            .map_err(|_| Error::InvalidKey(key_id.to_string()))?;
+        let signature = Signature::from_slice(signature).map_err(|_| Error::InvalidSignature)?;
+        public_key
+            .verify_strict(message, &signature)
+            .map_err(|_| Error::InvalidSignature)
+    }
+}
+
+/// Signs `message` with the signer.
+///
+/// The result contains the length of the key ID, the key ID and the signature.
+pub fn sign(signer: &dyn Signer, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let key_id = signer.key_id();
+    let key_id_len = u8::try_from(key_id.len()).map_err(|_| Error::InvalidKeyId(key_id.clone()))?;
+    let signature = signer.sign(message)?;
+
+    let mut signed = Vec::with_capacity(1 + key_id.len() + signature.len());
+    signed.push(key_id_len);
+    signed.extend(key_id.as_bytes());
+    signed.extend(signature);
+    Ok(signed)
+}
+
+/// Verifies the signature of `message` returned by `sign`.
+pub fn verify(
+    verifier: &dyn SignatureVerifier,
+    message: &[u8],
+    signed: &[u8],
+) -> Result<(), Error> {
+    let (&key_id_len, rest) = signed.split_first().ok_or(Error::InvalidSignature)?;
+    if rest.len() < key_id_len as usize {
+        return Err(Error::InvalidSignature);
+    }
+    let (key_id, signature) = rest.split_at(key_id_len as usize);
+    verifier.verify(&String::from_utf8_lossy(key_id), message, signature)
+}
+
+/// Signing error.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The verifier does not trust the key.
+    #[error("unknown signing key {0}")]
+    UnknownKey(String),
+    /// The key ID is longer than 255 bytes.
+    #[error("invalid signing key id {0}")]
+    InvalidKeyId(String),
+    /// The public key is malformed.
+    #[error("invalid public key {0}")]
+    InvalidKey(String),
+    /// The message could not be signed.
+    #[error("signing failed: {0}")]
+    Signing(String),
+    /// The message is not signed.
+    #[error("missing signature")]
+    MissingSignature,
+    /// The signature is malformed or does not match the message.
+    #[error("invalid signature")]
+    InvalidSignature,
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::UnknownKey(_) | Error::InvalidKeyId(_) | Error::InvalidKey(_) => {
+                ErrorKind::Validation
+            }
+            Error::Signing(_) => ErrorKind::Io,
+            Error::MissingSignature | Error::InvalidSignature => ErrorKind::Corruption,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_verifies_the_signatures_of_the_trusted_keys() {
+        let signer = Ed25519Signer::new("k1", [1; 32]);
+        let signed = sign(&signer, b"message").unwrap();
+        let verifier = Ed25519Verifier::default().with_key("k1", signer.verifying_key());
+
+        assert!(verify(&verifier, b"message", &signed).is_ok());
+        assert!(matches!(
+            verify(&verifier, b"tampered", &signed),
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify(&Ed25519Verifier::default(), b"message", &signed),
+            Err(Error::UnknownKey(key_id)) if key_id == "k1"
+        ));
+    }
+}
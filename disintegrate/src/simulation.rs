@@ -0,0 +1,465 @@
+//! Deterministic simulation of the event pipeline.
+//!
+//! A `Simulation` runs the decisions, the listeners and the sagas against a simulated event store and a simulated
+//! async runtime, injecting the failures of a production deployment: appends failing or crashing after the events
+//! are stored, events delivered twice, and listeners crashing between the handling of a batch and the storage of
+//! their checkpoint. The failures are drawn from a pseudo-random generator, so a schedule that breaks an invariant
+//! is reproduced by running the simulation again with the same seed.
+//!
+//! The simulated runtime has its own clock: the timers complete as soon as all the tasks are waiting, without
+//! waiting for the real time, and the tasks ready to run are polled in an order drawn from the seed.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! for seed in 0..1000 {
+//!     let simulation = Simulation::<DomainEvent>::new(seed).with_faults(
+//!         Faults::default()
+//!             .with_crash_after_append(0.1)
+//!             .with_duplicate_delivery(0.1)
+//!             .with_crash_before_checkpoint(0.1),
+//!     );
+//!     let decision_maker =
+//!         DecisionMaker::new(EventSourcedStateStore::new(simulation.event_store(), NoSnapshot));
+//!     let projection = CartProjection::default();
+//!
+//!     simulation.runtime().block_on(async {
+//!         decision_maker.make(AddItem::new("c1", "p1")).await.ok();
+//!         simulation.deliver(&projection).await.unwrap();
+//!     });
+//!     assert!(projection.is_consistent(), "seed {seed}: {:?}", simulation.injected_faults());
+//! }
+//! ```
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::{StreamExt, TryStreamExt};
+
+mod event_store;
+mod runtime;
+
+pub use event_store::{Error as SimulatedEventStoreError, SimulatedEventStore};
+pub use runtime::SimulatedRuntime;
+
+use crate::{Event, EventListener, EventStore, InMemoryEventStore, PersistedEvent};
+
+/// The probabilities of the failures injected by a `Simulation`.
+///
+/// No failure is injected by default. The probabilities are between 0 and 1, and the ones of the failures
+/// causing a redelivery must be lower than 1, otherwise the listeners never catch up.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Faults {
+    append_failure: f64,
+    crash_after_append: f64,
+    duplicate_delivery: f64,
+    crash_before_checkpoint: f64,
+}
+
+impl Faults {
+    /// Sets the probability of an append failing without storing the events.
+    pub fn with_append_failure(mut self, probability: f64) -> Self {
+        self.append_failure = probability;
+        self
+    }
+
+    /// Sets the probability of an append storing the events and then failing, as a crash of the process or a
+    /// connection lost before the acknowledgement does.
+    pub fn with_crash_after_append(mut self, probability: f64) -> Self {
+        self.crash_after_append = probability;
+        self
+    }
+
+    /// Sets the probability of an event being delivered twice in a row to a listener.
+    pub fn with_duplicate_delivery(mut self, probability: f64) -> Self {
+        self.duplicate_delivery = probability;
+        self
+    }
+
+    /// Sets the probability of a listener crashing after handling a batch and before storing its checkpoint,
+    /// so the batch is delivered again.
+    pub fn with_crash_before_checkpoint(mut self, probability: f64) -> Self {
+        self.crash_before_checkpoint = probability;
+        self
+    }
+}
+
+/// A failure injected by a `Simulation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectedFault {
+    /// An append failed without storing the events.
+    AppendFailure,
+    /// An append stored the events up to `last_event_id` and then failed.
+    CrashAfterAppend { last_event_id: i64 },
+    /// The event `event_id` was delivered twice to the listener `listener_id`.
+    DuplicateDelivery {
+        listener_id: &'static str,
+        event_id: i64,
+    },
+    /// The listener `listener_id` crashed before storing the checkpoint `event_id`.
+    CrashBeforeCheckpoint {
+        listener_id: &'static str,
+        event_id: i64,
+    },
+}
+
+/// A deterministic simulation of the event pipeline.
+///
+/// The event store, the runtime and the checkpoints of the listeners live in memory and are shared by the
+/// clones of the store and of the runtime returned by the simulation.
+pub struct Simulation<E: Event> {
+    seed: u64,
+    store: InMemoryEventStore<E>,
+    chaos: Arc<Mutex<Chaos>>,
+    runtime: SimulatedRuntime,
+    checkpoints: Mutex<HashMap<&'static str, i64>>,
+    batch_size: usize,
+}
+
+impl<E: Event + Clone + Send + Sync> Simulation<E> {
+    /// Creates a simulation without failures, drawing its schedule from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            store: InMemoryEventStore::new(),
+            chaos: Arc::new(Mutex::new(Chaos {
+                rng: Rng::new(seed),
+                faults: Faults::default(),
+                injected: Vec::new(),
+            })),
+            runtime: SimulatedRuntime::new(seed),
+            checkpoints: Mutex::new(HashMap::new()),
+            batch_size: 10,
+        }
+    }
+
+    /// Sets the failures injected by the simulation.
+    pub fn with_faults(self, faults: Faults) -> Self {
+        self.chaos
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .faults = faults;
+        self
+    }
+
+    /// Sets the maximum number of events delivered to a listener in a batch, 10 by default.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the seed of the simulation.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the simulated event store, injecting the append failures.
+    pub fn event_store(&self) -> SimulatedEventStore<E> {
+        SimulatedEventStore::new(self.store.clone(), Arc::clone(&self.chaos))
+    }
+
+    /// Returns the simulated runtime, to run the background tasks and to drive the simulation.
+    pub fn runtime(&self) -> SimulatedRuntime {
+        self.runtime.clone()
+    }
+
+    /// Returns the checkpoint of the listener `listener_id`, the ID of the last event it handled.
+    pub fn checkpoint(&self, listener_id: &str) -> i64 {
+        self.checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(listener_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the failures injected so far, in the order they happened.
+    pub fn injected_faults(&self) -> Vec<InjectedFault> {
+        self.chaos
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .injected
+            .clone()
+    }
+
+    /// Delivers the events following its checkpoint to the listener, until it has handled all of them.
+    ///
+    /// The events are delivered in batches, as the listener executors do, injecting the duplicated deliveries
+    /// and the crashes between the handling of a batch and the storage of the checkpoint.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating the success of the delivery, or the error returned by the listener. The checkpoint
+    /// is moved to the last event handled before the error, so the next delivery resumes from there.
+    pub async fn deliver<L>(&self, listener: &L) -> Result<(), L::Error>
+    where
+        E: 'static,
+        L: EventListener<i64, E>,
+    {
+        let listener_id = listener.id();
+        loop {
+            let checkpoint = self.checkpoint(listener_id);
+            let query = listener.query().clone().change_origin(checkpoint);
+            let mut batch: Vec<PersistedEvent<i64, E>> = self
+                .store
+                .stream(&query)
+                .take(self.batch_size)
+                .try_collect()
+                .await
+                .expect("the in-memory event store never fails to stream");
+            let Some(last_event_id) = batch.last().map(PersistedEvent::id) else {
+                return Ok(());
+            };
+
+            {
+                let mut chaos = self.chaos.lock().unwrap_or_else(PoisonError::into_inner);
+                let duplicate_delivery = chaos.faults.duplicate_delivery;
+                if chaos.rng.chance(duplicate_delivery) {
+                    let index = chaos.rng.below(batch.len());
+                    let event = batch[index].clone();
+                    chaos.injected.push(InjectedFault::DuplicateDelivery {
+                        listener_id,
+                        event_id: event.id(),
+                    });
+                    batch.insert(index, event);
+                }
+            }
+
+            if let Err(err) = listener.handle_batch(batch).await {
+                if let Some(event_id) = err.last_handled_event_id {
+                    self.store_checkpoint(listener_id, event_id);
+                }
+                return Err(err.error);
+            }
+
+            let crashed = {
+                let mut chaos = self.chaos.lock().unwrap_or_else(PoisonError::into_inner);
+                let crash_before_checkpoint = chaos.faults.crash_before_checkpoint;
+                let crashed = chaos.rng.chance(crash_before_checkpoint);
+                if crashed {
+                    chaos.injected.push(InjectedFault::CrashBeforeCheckpoint {
+                        listener_id,
+                        event_id: last_event_id,
+                    });
+                }
+                crashed
+            };
+            if !crashed {
+                self.store_checkpoint(listener_id, last_event_id);
+            }
+        }
+    }
+
+    fn store_checkpoint(&self, listener_id: &'static str, event_id: i64) {
+        self.checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(listener_id, event_id);
+    }
+}
+
+/// The failures of a simulation and the generator drawing them.
+#[derive(Debug)]
+struct Chaos {
+    rng: Rng,
+    faults: Faults,
+    injected: Vec<InjectedFault>,
+}
+
+/// A SplitMix64 pseudo-random generator, reproducing its sequence from the seed on every platform.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns `true` with the given probability.
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// Returns a number lower than `bound`, which must not be zero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{query, utils::tests::*, Runtime, StreamQuery};
+
+    struct CartItems {
+        query: StreamQuery<i64, ShoppingCartEvent>,
+        handled: Mutex<Vec<i64>>,
+    }
+
+    impl CartItems {
+        fn new() -> Self {
+            Self {
+                query: query!(ShoppingCartEvent),
+                handled: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn handled(&self) -> Vec<i64> {
+            self.handled.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl EventListener<i64, ShoppingCartEvent> for CartItems {
+        type Error = Infallible;
+
+        fn id(&self) -> &'static str {
+            "cart_items"
+        }
+
+        fn query(&self) -> &StreamQuery<i64, ShoppingCartEvent> {
+            &self.query
+        }
+
+        async fn handle(
+            &self,
+            event: PersistedEvent<i64, ShoppingCartEvent>,
+        ) -> Result<(), Self::Error> {
+            self.handled.lock().unwrap().push(event.id());
+            Ok(())
+        }
+    }
+
+    fn faults() -> Faults {
+        Faults::default()
+            .with_append_failure(0.2)
+            .with_crash_after_append(0.2)
+            .with_duplicate_delivery(0.3)
+            .with_crash_before_checkpoint(0.3)
+    }
+
+    fn run(seed: u64) -> (Vec<InjectedFault>, Vec<i64>) {
+        let simulation = Simulation::new(seed)
+            .with_faults(faults())
+            .with_batch_size(2);
+        let event_store = simulation.event_store();
+        let listener = CartItems::new();
+        simulation.runtime().block_on(async {
+            for item in 0..10 {
+                let event = item_added_event(&format!("p{item}"), "c1");
+                while event_store
+                    .append_without_validation(vec![event.clone()])
+                    .await
+                    .is_err()
+                {}
+            }
+            simulation.deliver(&listener).await.unwrap();
+        });
+        (simulation.injected_faults(), listener.handled())
+    }
+
+    #[test]
+    fn it_reproduces_the_failures_from_the_seed() {
+        let (faults, handled) = run(42);
+
+        assert!(!faults.is_empty());
+        assert_eq!(run(42), (faults, handled));
+    }
+
+    #[test]
+    fn it_delivers_all_the_events_at_least_once() {
+        for seed in 0..20 {
+            let simulation = Simulation::new(seed)
+                .with_faults(faults())
+                .with_batch_size(3);
+            let event_store = simulation.event_store();
+            let listener = CartItems::new();
+            let stored = simulation.runtime().block_on(async {
+                let mut stored = HashSet::new();
+                for item in 0..10 {
+                    let event = item_added_event(&format!("p{item}"), "c1");
+                    if let Ok(events) = event_store.append_without_validation(vec![event]).await {
+                        stored.extend(events.iter().map(PersistedEvent::id));
+                    }
+                }
+                simulation.deliver(&listener).await.unwrap();
+                stored
+            });
+
+            let handled: HashSet<i64> = listener.handled().into_iter().collect();
+            assert!(stored.is_subset(&handled), "seed {seed}");
+            assert_eq!(
+                simulation.checkpoint("cart_items"),
+                event_store.len() as i64,
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_delivers_the_batch_again_after_a_crash_before_the_checkpoint() {
+        let simulation =
+            Simulation::new(7).with_faults(Faults::default().with_crash_before_checkpoint(0.5));
+        let event_store = simulation.event_store();
+        let listener = CartItems::new();
+        simulation.runtime().block_on(async {
+            event_store
+                .append_without_validation(vec![
+                    item_added_event("p1", "c1"),
+                    item_added_event("p2", "c1"),
+                ])
+                .await
+                .unwrap();
+            simulation.deliver(&listener).await.unwrap();
+        });
+
+        let crashes = simulation.injected_faults().len();
+        assert_eq!(listener.handled().len(), 2 * (crashes + 1));
+        assert_eq!(simulation.checkpoint("cart_items"), 2);
+    }
+
+    #[test]
+    fn it_runs_the_listeners_in_the_background_with_the_simulated_time() {
+        let simulation = Simulation::new(1);
+        let event_store = simulation.event_store();
+        let runtime = simulation.runtime();
+        let simulation = Arc::new(simulation);
+        let listener = Arc::new(CartItems::new());
+
+        let background: Arc<dyn Runtime> = Arc::new(runtime.clone());
+        let poller = {
+            let simulation = Arc::clone(&simulation);
+            let listener = Arc::clone(&listener);
+            let timer = runtime.clone();
+            background.spawn_with_handle(async move {
+                for _ in 0..3 {
+                    timer.sleep(Duration::from_secs(60)).await;
+                    simulation.deliver(listener.as_ref()).await.unwrap();
+                }
+            })
+        };
+        runtime.block_on(async {
+            event_store
+                .append_without_validation(vec![item_added_event("p1", "c1")])
+                .await
+                .unwrap();
+            poller.await;
+        });
+
+        assert_eq!(listener.handled(), vec![1]);
+        assert_eq!(runtime.elapsed(), Duration::from_secs(180));
+    }
+}
@@ -0,0 +1,176 @@
+//! An event store injecting the append failures of a simulation.
+use std::error::Error as StdError;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+
+use super::{Chaos, InjectedFault};
+use crate::{
+    Classify, ErrorKind, Event, EventStore, InMemoryEventStore, InMemoryEventStoreError,
+    PersistedEvent, StreamQuery,
+};
+
+/// The `EventStore` of a `Simulation`.
+///
+/// The events are stored in memory and shared with the simulation, which delivers them to the listeners. The
+/// appends fail or crash after storing the events with the probabilities of the `Faults` of the simulation.
+#[derive(Debug, Clone)]
+pub struct SimulatedEventStore<E: Event> {
+    store: InMemoryEventStore<E>,
+    chaos: Arc<Mutex<Chaos>>,
+}
+
+impl<E: Event> SimulatedEventStore<E> {
+    pub(super) fn new(store: InMemoryEventStore<E>, chaos: Arc<Mutex<Chaos>>) -> Self {
+        Self { store, chaos }
+    }
+
+    /// Returns the number of events in the store.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns `true` if the store has no events.
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Fails the append before the events are stored, if the failure is drawn.
+    fn before_append(&self) -> Result<(), Error> {
+        let mut chaos = self.chaos.lock().unwrap_or_else(PoisonError::into_inner);
+        let append_failure = chaos.faults.append_failure;
+        if chaos.rng.chance(append_failure) {
+            chaos.injected.push(InjectedFault::AppendFailure);
+            return Err(Error::AppendFailure);
+        }
+        Ok(())
+    }
+
+    /// Fails the append after the events are stored, if the crash is drawn.
+    fn after_append(
+        &self,
+        persisted: Vec<PersistedEvent<i64, E>>,
+    ) -> Result<Vec<PersistedEvent<i64, E>>, Error> {
+        let mut chaos = self.chaos.lock().unwrap_or_else(PoisonError::into_inner);
+        let crash_after_append = chaos.faults.crash_after_append;
+        if chaos.rng.chance(crash_after_append) {
+            let last_event_id = persisted.last().map_or(0, PersistedEvent::id);
+            chaos
+                .injected
+                .push(InjectedFault::CrashAfterAppend { last_event_id });
+            return Err(Error::CrashAfterAppend);
+        }
+        Ok(persisted)
+    }
+}
+
+#[async_trait]
+impl<E> EventStore<i64, E> for SimulatedEventStore<E>
+where
+    E: Event + Clone + Send + Sync,
+{
+    type Error = Error;
+
+    fn stream<'a, QE>(
+        &'a self,
+        query: &'a StreamQuery<i64, QE>,
+    ) -> BoxStream<'a, Result<PersistedEvent<i64, QE>, Self::Error>>
+    where
+        QE: TryFrom<E> + Event + 'static + Clone + Send + Sync,
+        <QE as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+    {
+        self.store.stream(query).map_err(Error::Store).boxed()
+    }
+
+    async fn append<QE>(
+        &self,
+        events: Vec<E>,
+        query: StreamQuery<i64, QE>,
+        last_event_id: i64,
+    ) -> Result<Vec<PersistedEvent<i64, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+        QE: Event + 'static + Clone + Send + Sync,
+    {
+        self.before_append()?;
+        let persisted = self.store.append(events, query, last_event_id).await?;
+        self.after_append(persisted)
+    }
+
+    async fn append_without_validation(
+        &self,
+        events: Vec<E>,
+    ) -> Result<Vec<PersistedEvent<i64, E>>, Self::Error>
+    where
+        E: Clone + 'async_trait,
+    {
+        self.before_append()?;
+        let persisted = self.store.append_without_validation(events).await?;
+        self.after_append(persisted)
+    }
+}
+
+/// The error of a `SimulatedEventStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The error of the underlying in-memory store.
+    #[error(transparent)]
+    Store(#[from] InMemoryEventStoreError),
+    /// A simulated failure of the append, the events are not stored.
+    #[error("simulated append failure")]
+    AppendFailure,
+    /// A simulated crash after the append, the events are stored.
+    #[error("simulated crash after the append")]
+    CrashAfterAppend,
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Store(err) => err.kind(),
+            Error::AppendFailure | Error::CrashAfterAppend => ErrorKind::Transient,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, *};
+    use crate::{utils::tests::*, Faults, Simulation};
+
+    #[test]
+    fn it_stores_the_events_of_an_append_crashing_after_the_append() {
+        let simulation =
+            Simulation::new(3).with_faults(Faults::default().with_crash_after_append(1.0));
+        let event_store = simulation.event_store();
+
+        let err = simulation
+            .runtime()
+            .block_on(event_store.append_without_validation(vec![item_added_event("p1", "c1")]))
+            .unwrap_err();
+
+        assert!(matches!(err, Error::CrashAfterAppend));
+        assert_eq!(err.kind(), ErrorKind::Transient);
+        assert_eq!(event_store.len(), 1);
+        assert_eq!(
+            simulation.injected_faults(),
+            vec![InjectedFault::CrashAfterAppend { last_event_id: 1 }]
+        );
+    }
+
+    #[test]
+    fn it_does_not_store_the_events_of_a_failed_append() {
+        let simulation = Simulation::new(3).with_faults(Faults::default().with_append_failure(1.0));
+        let event_store = simulation.event_store();
+
+        let err = simulation
+            .runtime()
+            .block_on(event_store.append_without_validation(vec![item_added_event("p1", "c1")]))
+            .unwrap_err();
+
+        assert!(matches!(err, Error::AppendFailure));
+        assert!(event_store.is_empty());
+    }
+}
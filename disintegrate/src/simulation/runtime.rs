@@ -0,0 +1,324 @@
+//! A deterministic async runtime with a simulated clock.
+use std::collections::BTreeMap;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::task::{waker_ref, ArcWake};
+use futures::{Future, FutureExt};
+
+use super::Rng;
+use crate::Runtime;
+
+/// The `Runtime` of a `Simulation`.
+///
+/// The tasks run on the thread driving the runtime with `block_on`. When all the tasks are waiting, the clock
+/// jumps to the next timer, so hours of simulated time pass in a few milliseconds. The tasks woken at the same
+/// time are polled in an order drawn from the seed of the simulation, which makes their interleaving
+/// reproducible.
+#[derive(Clone)]
+pub struct SimulatedRuntime {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    rng: Mutex<Rng>,
+    elapsed: Mutex<Duration>,
+    tasks: Mutex<Vec<Arc<Task>>>,
+    timers: Mutex<BTreeMap<(Duration, u64), Waker>>,
+    next_timer_id: AtomicU64,
+}
+
+struct Task {
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    woken: AtomicBool,
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Flag(AtomicBool);
+
+impl ArcWake for Flag {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl SimulatedRuntime {
+    pub(super) fn new(seed: u64) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                rng: Mutex::new(Rng::new(seed)),
+                elapsed: Mutex::new(Duration::ZERO),
+                tasks: Mutex::new(Vec::new()),
+                timers: Mutex::new(BTreeMap::new()),
+                next_timer_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Returns the simulated time elapsed since the runtime was created.
+    pub fn elapsed(&self) -> Duration {
+        *self
+            .inner
+            .elapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs the future to completion, together with the tasks spawned on the runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the future and the tasks are all waiting and no timer is pending, since nothing can wake
+    /// them up in the simulation.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let flag = Arc::new(Flag(AtomicBool::new(true)));
+        loop {
+            if flag.0.swap(false, Ordering::SeqCst) {
+                let waker = waker_ref(&flag);
+                if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker))
+                {
+                    return output;
+                }
+            }
+            self.run_until_idle();
+            if !flag.0.load(Ordering::SeqCst) && !self.fire_next_timers() {
+                panic!(
+                    "the simulation is stuck: all the tasks are waiting and no timer is pending"
+                );
+            }
+        }
+    }
+
+    /// Runs the spawned tasks and the timers until the simulated clock has moved forward by `duration`.
+    pub fn run_for(&self, duration: Duration) {
+        let deadline = self.elapsed() + duration;
+        loop {
+            self.run_until_idle();
+            let next_timer = self.next_deadline().filter(|next| *next <= deadline);
+            if next_timer.is_none() || !self.fire_next_timers() {
+                break;
+            }
+        }
+        let mut elapsed = self
+            .inner
+            .elapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *elapsed = (*elapsed).max(deadline);
+    }
+
+    /// Polls the woken tasks until all the tasks are waiting.
+    fn run_until_idle(&self) {
+        loop {
+            let mut woken: Vec<Arc<Task>> = self
+                .inner
+                .tasks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .filter(|task| task.woken.swap(false, Ordering::SeqCst))
+                .cloned()
+                .collect();
+            if woken.is_empty() {
+                return;
+            }
+            self.shuffle(&mut woken);
+            for task in woken {
+                let mut slot = task.future.lock().unwrap_or_else(PoisonError::into_inner);
+                let Some(mut future) = slot.take() else {
+                    continue;
+                };
+                drop(slot);
+                let waker = waker_ref(&task);
+                if future
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                    .is_pending()
+                {
+                    *task.future.lock().unwrap_or_else(PoisonError::into_inner) = Some(future);
+                }
+            }
+            self.inner
+                .tasks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|task| {
+                    task.future
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .is_some()
+                });
+        }
+    }
+
+    /// Moves the clock to the next timer and wakes the tasks waiting for it, returning `false` if no timer is
+    /// pending.
+    fn fire_next_timers(&self) -> bool {
+        let mut timers = self
+            .inner
+            .timers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(&(deadline, _)) = timers.keys().next() else {
+            return false;
+        };
+        let pending = timers.split_off(&(deadline, u64::MAX));
+        let expired = std::mem::replace(&mut *timers, pending);
+        drop(timers);
+        {
+            let mut elapsed = self
+                .inner
+                .elapsed
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            *elapsed = (*elapsed).max(deadline);
+        }
+        for waker in expired.into_values() {
+            waker.wake();
+        }
+        true
+    }
+
+    fn next_deadline(&self) -> Option<Duration> {
+        self.inner
+            .timers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .next()
+            .map(|(deadline, _)| *deadline)
+    }
+
+    fn shuffle(&self, tasks: &mut [Arc<Task>]) {
+        let mut rng = self
+            .inner
+            .rng
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for index in (1..tasks.len()).rev() {
+            tasks.swap(index, rng.below(index + 1));
+        }
+    }
+}
+
+impl Runtime for SimulatedRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        self.inner
+            .tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(Task {
+                future: Mutex::new(Some(task)),
+                woken: AtomicBool::new(true),
+            }));
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Sleep {
+            inner: Arc::clone(&self.inner),
+            deadline: self.elapsed() + duration,
+            timer: None,
+        }
+        .boxed()
+    }
+}
+
+/// A future completing when the simulated clock reaches its deadline.
+struct Sleep {
+    inner: Arc<Inner>,
+    deadline: Duration,
+    timer: Option<(Duration, u64)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let elapsed = *self
+            .inner
+            .elapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if elapsed >= self.deadline {
+            self.timer = None;
+            return Poll::Ready(());
+        }
+        let timer = match self.timer {
+            Some(timer) => timer,
+            None => (
+                self.deadline,
+                self.inner.next_timer_id.fetch_add(1, Ordering::SeqCst),
+            ),
+        };
+        self.timer = Some(timer);
+        self.inner
+            .timers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(timer, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer {
+            self.inner
+                .timers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&timer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_moves_the_clock_to_the_next_timer() {
+        let runtime = SimulatedRuntime::new(0);
+
+        runtime.block_on(runtime.sleep(Duration::from_secs(3600)));
+
+        assert_eq!(runtime.elapsed(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn it_interleaves_the_tasks_in_the_same_order_for_the_same_seed() {
+        fn interleaving(seed: u64) -> Vec<usize> {
+            let runtime = SimulatedRuntime::new(seed);
+            let order = Arc::new(Mutex::new(Vec::new()));
+            for task in 0..8 {
+                let order = Arc::clone(&order);
+                let timer = runtime.clone();
+                runtime.spawn(
+                    async move {
+                        timer.sleep(Duration::from_secs(1)).await;
+                        order.lock().unwrap().push(task);
+                    }
+                    .boxed(),
+                );
+            }
+            runtime.run_for(Duration::from_secs(1));
+            let order = order.lock().unwrap();
+            order.clone()
+        }
+
+        let order = interleaving(5);
+
+        assert_eq!(order.len(), 8);
+        assert_eq!(interleaving(5), order);
+    }
+}
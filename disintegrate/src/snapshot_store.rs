@@ -0,0 +1,1324 @@
+//! Snapshot Store provides the persistence of the snapshots taken by the decision state store.
+//!
+//! The `Snapshotter` decides when a state part must be snapshotted and how it is serialized, while a
+//! `SnapshotStore` only reads and writes the serialized snapshots. This makes snapshotting available
+//! regardless of the event store backend.
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+mod background;
+mod cache;
+#[cfg(feature = "snapshot-redis")]
+mod redis_store;
+
+#[cfg(feature = "snapshot-redis")]
+pub use redis_store::RedisSnapshotStore;
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, KeyProvider};
+use background::BackgroundWrites;
+use cache::SnapshotCache;
+use futures::FutureExt;
+use std::future::Future;
+
+use crate::time::Instant;
+use crate::EventId;
+use crate::StatePart;
+use crate::{
+    BoxDynError, Event, Identifier, IntoIdentifierValue, IntoState, StateQuery, StateSnapshotter,
+    StreamQuery,
+};
+
+/// A serialized snapshot of a state part.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredSnapshot<ID> {
+    /// The name of the state query.
+    pub name: String,
+    /// The key of the stream query used to build the state, see `snapshot_key`.
+    pub query: String,
+    /// The ID of the last event applied to the state.
+    pub version: ID,
+    /// The fingerprint of the state query shape, see `StateQuery::FINGERPRINT`.
+    pub fingerprint: u64,
+    /// The JSON payload of the state, prefixed by `zstd:` when compressed and by `aes256gcm:` when
+    /// encrypted.
+    pub payload: Vec<u8>,
+}
+
+/// The description of a stored snapshot, returned by `SnapshotStore::list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo<ID> {
+    /// The name of the state query.
+    pub name: String,
+    /// The key of the stream query used to build the state.
+    pub query: String,
+    /// The ID of the last event applied to the state.
+    pub version: ID,
+    /// The fingerprint of the state query shape.
+    pub fingerprint: u64,
+    /// The size of the stored payload in bytes.
+    pub size: u64,
+    /// When the snapshot was stored, if the store records it.
+    pub stored_at: Option<SystemTime>,
+}
+
+impl<ID: Copy> From<&StoredSnapshot<ID>> for SnapshotInfo<ID> {
+    fn from(snapshot: &StoredSnapshot<ID>) -> Self {
+        Self {
+            name: snapshot.name.clone(),
+            query: snapshot.query.clone(),
+            version: snapshot.version,
+            fingerprint: snapshot.fingerprint,
+            size: snapshot.payload.len() as u64,
+            stored_at: None,
+        }
+    }
+}
+
+/// A storage backend for snapshots.
+///
+/// Snapshots are identified by the name of the state query and the key of its stream query.
+#[async_trait]
+pub trait SnapshotStore<ID: EventId>: Send + Sync {
+    /// Loads the snapshot identified by `name` and `query`, if any.
+    async fn load(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<ID>>, BoxDynError>;
+
+    /// Stores the snapshot, unless a snapshot with the same fingerprint and a greater or equal version is
+    /// already stored.
+    async fn store(&self, snapshot: StoredSnapshot<ID>) -> Result<(), BoxDynError>;
+
+    /// Lists the stored snapshots, optionally restricted to the state query `name`.
+    async fn list(&self, name: Option<&str>) -> Result<Vec<SnapshotInfo<ID>>, BoxDynError>;
+
+    /// Deletes the snapshot identified by `name` and `query`. Returns `true` if the snapshot existed.
+    async fn delete(&self, name: &str, query: &str) -> Result<bool, BoxDynError>;
+
+    /// Deletes the snapshots of the state query `name` that are not retained by `retention`. Returns
+    /// the number of deleted snapshots.
+    ///
+    /// The default implementation lists the snapshots and deletes them one by one.
+    async fn prune(&self, name: &str, retention: &SnapshotRetention) -> Result<u64, BoxDynError> {
+        let snapshots = self.list(Some(name)).await?;
+        let mut deleted = 0;
+        for snapshot in retention.expired(snapshots) {
+            if self.delete(name, &snapshot.query).await? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+/// Decides which snapshots of a state query are kept when the snapshots are pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotRetention {
+    /// Keeps the given number of most recently stored snapshots of each state query.
+    KeepLatest(usize),
+    /// Keeps the snapshots stored within the given duration.
+    MaxAge(Duration),
+}
+
+impl SnapshotRetention {
+    /// Returns the snapshots that are not retained, assuming they belong to the same state query.
+    ///
+    /// Snapshots without a storage time are ordered by version and never expire by age.
+    fn expired<ID: EventId>(&self, mut snapshots: Vec<SnapshotInfo<ID>>) -> Vec<SnapshotInfo<ID>> {
+        match self {
+            Self::KeepLatest(keep) => {
+                snapshots.sort_by_key(|snapshot| Reverse((snapshot.stored_at, snapshot.version)));
+                snapshots.split_off((*keep).min(snapshots.len()))
+            }
+            Self::MaxAge(age) => snapshots
+                .into_iter()
+                .filter(|snapshot| {
+                    snapshot
+                        .stored_at
+                        .and_then(|stored_at| stored_at.elapsed().ok())
+                        .is_some_and(|elapsed| elapsed > *age)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The minimum interval between two automatic prunes of the snapshots of a state query.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Decides when the `Snapshotter` takes a snapshot of a state part.
+#[derive(Clone)]
+pub enum SnapshotPolicy {
+    /// Takes a snapshot when more than the given number of events were applied since the last snapshot.
+    EveryEvents(u64),
+    /// Takes a snapshot when events were applied since the last snapshot, at most once per interval.
+    AtMostEvery(Duration),
+    /// Takes a snapshot when the predicate returns `true`. The predicate receives the number of events
+    /// applied since the last snapshot and the hydration time of the state part.
+    Custom(Arc<dyn Fn(u64, Duration) -> bool + Send + Sync>),
+}
+
+impl SnapshotPolicy {
+    /// Creates a `SnapshotPolicy::Custom` policy from the given predicate.
+    pub fn custom(predicate: impl Fn(u64, Duration) -> bool + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(predicate))
+    }
+}
+
+impl fmt::Debug for SnapshotPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EveryEvents(events) => f.debug_tuple("EveryEvents").field(events).finish(),
+            Self::AtMostEvery(interval) => f.debug_tuple("AtMostEvery").field(interval).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// A `StateSnapshotter` that persists the snapshots in a `SnapshotStore`.
+///
+/// States are serialized as JSON. A snapshot is discarded when it cannot be deserialized, when it was
+/// built from a different stream query or when its fingerprint does not match the one of the state query.
+/// A discarded snapshot is rebuilt the next time the state is stored, regardless of the policy.
+///
+/// The `SnapshotPolicy` decides when a snapshot is taken. It can be tuned for each state query, so that
+/// entities with very different event rates are snapshotted at a suitable pace.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::{InMemorySnapshotStore, SnapshotPolicy, Snapshotter, WithSnapshot};
+/// use std::time::Duration;
+///
+/// let snapshotter = Snapshotter::new(InMemorySnapshotStore::<i64>::default(), 10)
+///     .policy(SnapshotPolicy::AtMostEvery(Duration::from_secs(30)));
+/// let snapshot = WithSnapshot::<i64, _>::new(snapshotter);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Snapshotter<ST> {
+    store: ST,
+    policy: SnapshotPolicy,
+    policies: HashMap<&'static str, SnapshotPolicy>,
+    last_snapshots: Arc<Mutex<HashMap<(String, String), Instant>>>,
+    stale_snapshots: Arc<Mutex<HashSet<(String, String)>>>,
+    compression_level: Option<i32>,
+    #[cfg(feature = "encryption")]
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    cache: Option<Arc<SnapshotCache>>,
+    background: Option<Arc<BackgroundWrites>>,
+    retention: Option<SnapshotRetention>,
+    last_prunes: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl<ST> Snapshotter<ST> {
+    /// Creates a new `Snapshotter`.
+    ///
+    /// # Arguments
+    ///
+    /// - `store`: The storage backend of the snapshots.
+    /// - `every`: The frequency of snapshot creation, specified as the number of events between consecutive snapshots.
+    pub fn new(store: ST, every: u64) -> Self {
+        Self {
+            store,
+            policy: SnapshotPolicy::EveryEvents(every),
+            policies: HashMap::new(),
+            last_snapshots: Arc::new(Mutex::new(HashMap::new())),
+            stale_snapshots: Arc::new(Mutex::new(HashSet::new())),
+            compression_level: None,
+            #[cfg(feature = "encryption")]
+            key_provider: None,
+            cache: None,
+            background: None,
+            retention: None,
+            last_prunes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sets the policy of the state queries without a specific policy.
+    pub fn policy(mut self, policy: SnapshotPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets the policy of the state query `S`.
+    pub fn policy_for<S: StateQuery>(mut self, policy: SnapshotPolicy) -> Self {
+        self.policies.insert(S::NAME, policy);
+        self
+    }
+
+    /// Compresses the snapshot payloads with zstd at the given level.
+    ///
+    /// Compressed payloads are marked, so snapshots stored before enabling the compression can still be
+    /// loaded.
+    #[cfg(feature = "snapshot-compression")]
+    pub fn compression(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Encrypts the snapshot payloads with AES-256-GCM, using the keys of the given provider.
+    ///
+    /// Payloads are compressed before being encrypted. Encrypted payloads are marked and carry the ID of
+    /// the key, so snapshots encrypted with rotated keys or stored before enabling the encryption can
+    /// still be loaded, as long as the provider knows the key. Snapshots that cannot be decrypted are
+    /// ignored and rebuilt from the events.
+    #[cfg(feature = "encryption")]
+    pub fn encryption(mut self, key_provider: impl KeyProvider + 'static) -> Self {
+        self.key_provider = Some(Arc::new(key_provider));
+        self
+    }
+
+    /// Keeps the hydrated state parts in an in-process cache in front of the snapshot store.
+    ///
+    /// Loading a cached state part skips the snapshot fetch and replays only the events appended after
+    /// its version, so the cached states stay valid when new events are appended. At most `capacity`
+    /// state parts are cached, evicting the least recently used, and each one is dropped after `ttl`.
+    /// The snapshots are still written to the store according to the policy.
+    pub fn cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(Arc::new(SnapshotCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Writes the snapshots in the background, so loading a state does not wait for its snapshot to be
+    /// stored.
+    ///
+    /// Returns the snapshotter and the worker that writes the snapshots, which must be spawned on the
+    /// async runtime. When a state part is snapshotted again before its previous snapshot is written,
+    /// only the latest one is written. Failed writes are dropped, and the snapshot is taken again by a
+    /// following load. The worker completes when all the clones of the snapshotter are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use disintegrate::{InMemorySnapshotStore, Snapshotter};
+    ///
+    /// # async fn example() {
+    /// let (snapshotter, worker) = Snapshotter::new(InMemorySnapshotStore::<i64>::default(), 10).background();
+    /// tokio::spawn(worker);
+    /// # }
+    /// ```
+    pub fn background(mut self) -> (Self, impl Future<Output = ()> + Send + 'static) {
+        let (writes, worker) = BackgroundWrites::new();
+        self.background = Some(Arc::new(writes));
+        (self, worker)
+    }
+
+    /// Prunes the snapshots automatically according to the given retention.
+    ///
+    /// The snapshots of a state query are pruned after one of its snapshots is stored, at most once a
+    /// minute. Pruned snapshots are rebuilt from the events the next time their state is loaded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use disintegrate::{InMemorySnapshotStore, SnapshotRetention, Snapshotter};
+    ///
+    /// let snapshotter = Snapshotter::new(InMemorySnapshotStore::<i64>::default(), 10)
+    ///     .retention(SnapshotRetention::KeepLatest(10_000));
+    /// ```
+    pub fn retention(mut self, retention: SnapshotRetention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Returns the retention of the state query `name` if its snapshots are due to be pruned.
+    fn prune_due(&self, name: &str) -> Option<SnapshotRetention> {
+        let retention = self.retention?;
+        let mut last_prunes = self.last_prunes.lock().ok()?;
+        if last_prunes
+            .get(name)
+            .is_some_and(|at| at.elapsed() < PRUNE_INTERVAL)
+        {
+            return None;
+        }
+        last_prunes.insert(name.to_string(), Instant::now());
+        Some(retention)
+    }
+
+    /// Removes the cached state of the given state query, for example after its events have been
+    /// changed outside of the event store API.
+    pub fn invalidate<ID, S>(&self, state_query: &S)
+    where
+        ID: EventId + Display,
+        S: StateQuery,
+    {
+        if let Some(cache) = &self.cache {
+            cache.remove(S::NAME, Some(&snapshot_key(&state_query.query::<ID>())));
+        }
+    }
+
+    /// Removes all the cached states.
+    pub fn invalidate_all(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Returns the storage backend of the snapshots.
+    pub fn store(&self) -> &ST {
+        &self.store
+    }
+
+    fn should_snapshot(&self, name: &str, query: &str, events: u64, hydration: Duration) -> bool {
+        if self.stale_snapshots.lock().is_ok_and(|stale_snapshots| {
+            stale_snapshots.contains(&(name.to_string(), query.to_string()))
+        }) {
+            return true;
+        }
+        match self.policies.get(name).unwrap_or(&self.policy) {
+            SnapshotPolicy::EveryEvents(every) => events > *every,
+            SnapshotPolicy::AtMostEvery(interval) => {
+                events > 0
+                    && self
+                        .last_snapshots
+                        .lock()
+                        .map(|last_snapshots| {
+                            last_snapshots
+                                .get(&(name.to_string(), query.to_string()))
+                                .is_none_or(|at| at.elapsed() >= *interval)
+                        })
+                        .unwrap_or(true)
+            }
+            SnapshotPolicy::Custom(predicate) => predicate(events, hydration),
+        }
+    }
+
+    /// Lists the stored snapshots, optionally restricted to the state query `name`.
+    pub async fn snapshots<ID>(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Vec<SnapshotInfo<ID>>, BoxDynError>
+    where
+        ID: EventId,
+        ST: SnapshotStore<ID>,
+    {
+        self.store.list(name).await
+    }
+
+    /// Deletes the snapshot of the given state query. Returns `true` if the snapshot existed.
+    pub async fn delete_snapshot<ID, S>(&self, state_query: &S) -> Result<bool, BoxDynError>
+    where
+        ID: EventId + Display,
+        ST: SnapshotStore<ID>,
+        S: StateQuery,
+    {
+        self.invalidate::<ID, S>(state_query);
+        self.store
+            .delete(S::NAME, &snapshot_key(&state_query.query::<ID>()))
+            .await
+    }
+
+    /// Deletes the snapshots of the state query `name` built from a stream query filtering the domain
+    /// identifier `identifier` by `value`. Returns the number of deleted snapshots.
+    pub async fn delete_snapshots_by_identifier<ID>(
+        &self,
+        name: &str,
+        identifier: Identifier,
+        value: impl IntoIdentifierValue,
+    ) -> Result<u64, BoxDynError>
+    where
+        ID: EventId,
+        ST: SnapshotStore<ID>,
+    {
+        let pair = format!("{identifier}={}", value.into_identifier_value());
+        let mut deleted = 0;
+        for snapshot in self.store.list(Some(name)).await? {
+            if !has_identifier(&snapshot.query, &pair) {
+                continue;
+            }
+            if let Some(cache) = &self.cache {
+                cache.remove(name, Some(&snapshot.query));
+            }
+            if self.store.delete(name, &snapshot.query).await? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Deletes the snapshots of the state query `name` that are not retained by `retention`. Returns
+    /// the number of deleted snapshots.
+    pub async fn prune<ID>(
+        &self,
+        name: &str,
+        retention: &SnapshotRetention,
+    ) -> Result<u64, BoxDynError>
+    where
+        ID: EventId,
+        ST: SnapshotStore<ID>,
+    {
+        self.store.prune(name, retention).await
+    }
+
+    /// Forces the rebuild of the snapshot of the given state query.
+    ///
+    /// The snapshot is deleted, so the next load hydrates the state from the event store, and a new
+    /// snapshot is taken right after, regardless of the policy.
+    pub async fn rebuild<ID, S>(&self, state_query: &S) -> Result<(), BoxDynError>
+    where
+        ID: EventId + Display,
+        ST: SnapshotStore<ID>,
+        S: StateQuery,
+    {
+        let query = snapshot_key(&state_query.query::<ID>());
+        if let Some(cache) = &self.cache {
+            cache.remove(S::NAME, Some(&query));
+        }
+        self.store.delete(S::NAME, &query).await?;
+        if let Ok(mut stale_snapshots) = self.stale_snapshots.lock() {
+            stale_snapshots.insert((S::NAME.to_string(), query));
+        }
+        Ok(())
+    }
+
+    fn encode_payload(&self, payload: Vec<u8>) -> Result<Vec<u8>, BoxDynError> {
+        let payload = match self.compression_level {
+            #[cfg(feature = "snapshot-compression")]
+            Some(level) => {
+                let mut encoded = ZSTD_MARKER.to_vec();
+                encoded.extend(zstd::encode_all(payload.as_slice(), level)?);
+                encoded
+            }
+            _ => payload,
+        };
+        #[cfg(feature = "encryption")]
+        if let Some(key_provider) = &self.key_provider {
+            let mut encoded = ENCRYPTION_MARKER.to_vec();
+            encoded.extend(encryption::encrypt(key_provider.as_ref(), &payload)?);
+            return Ok(encoded);
+        }
+        Ok(payload)
+    }
+
+    fn decode_payload(&self, payload: Vec<u8>) -> Option<Vec<u8>> {
+        let payload = match payload.strip_prefix(ENCRYPTION_MARKER) {
+            #[cfg(feature = "encryption")]
+            Some(encrypted) => {
+                encryption::decrypt(self.key_provider.as_deref()?, encrypted).ok()?
+            }
+            #[cfg(not(feature = "encryption"))]
+            Some(_) => return None,
+            None => payload,
+        };
+        match payload.strip_prefix(ZSTD_MARKER) {
+            #[cfg(feature = "snapshot-compression")]
+            Some(compressed) => zstd::decode_all(compressed).ok(),
+            #[cfg(not(feature = "snapshot-compression"))]
+            Some(_) => None,
+            None => Some(payload),
+        }
+    }
+}
+
+/// Computes a stable FNV-1a hash of the snapshot name and query, used to derive storage keys.
+fn snapshot_hash(name: &str, query: &str) -> u64 {
+    name.bytes()
+        .chain([0])
+        .chain(query.bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Returns `true` if the snapshot key contains the `identifier=value` pair in one of its filters.
+fn has_identifier(query: &str, pair: &str) -> bool {
+    query.split(')').any(|filter| {
+        filter
+            .rsplit('|')
+            .next()
+            .is_some_and(|identifiers| identifiers.split(',').any(|p| p == pair))
+    })
+}
+
+/// Counts the snapshot loads of the state query by result: `hit`, `miss` or `stale`.
+#[cfg(feature = "metrics")]
+fn record_snapshot_load(state: &'static str, result: &'static str) {
+    ::metrics::counter!("disintegrate_snapshot_loads_total", "state" => state, "result" => result)
+        .increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+fn record_snapshot_load(_state: &'static str, _result: &'static str) {}
+
+/// The marker of the zstd compressed payloads. It cannot be the beginning of a JSON document.
+const ZSTD_MARKER: &[u8] = b"zstd:";
+
+/// The marker of the encrypted payloads. It cannot be the beginning of a JSON document.
+const ENCRYPTION_MARKER: &[u8] = b"aes256gcm:";
+
+#[async_trait]
+impl<ID, ST> StateSnapshotter<ID> for Snapshotter<ST>
+where
+    ID: EventId + Display,
+    ST: SnapshotStore<ID> + Clone + Send + Sync + 'static,
+{
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "disintegrate.snapshot.load", skip_all, fields(state = S::NAME))
+    )]
+    async fn load_snapshot<S>(&self, default: StatePart<ID, S>) -> StatePart<ID, S>
+    where
+        S: Send + Sync + DeserializeOwned + StateQuery + 'static,
+    {
+        let query = snapshot_key(&default.query::<ID>());
+        if let Some(state) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get::<StatePart<ID, S>>(S::NAME, &query))
+        {
+            record_snapshot_load(S::NAME, "hit");
+            return StatePart::new(state.version(), state.into_state());
+        }
+        if let Ok(Some(snapshot)) = self.store.load(S::NAME, &query).await {
+            if snapshot.fingerprint != S::FINGERPRINT {
+                if let Ok(mut stale_snapshots) = self.stale_snapshots.lock() {
+                    stale_snapshots.insert((S::NAME.to_string(), query));
+                }
+                record_snapshot_load(S::NAME, "stale");
+                return default;
+            }
+            if snapshot.name == S::NAME && snapshot.query == query {
+                let payload = self
+                    .decode_payload(snapshot.payload)
+                    .and_then(|payload| serde_json::from_slice(&payload).ok());
+                if let Some(payload) = payload {
+                    record_snapshot_load(S::NAME, "hit");
+                    return StatePart::new(snapshot.version, payload);
+                }
+            }
+        }
+
+        record_snapshot_load(S::NAME, "miss");
+        default
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "disintegrate.snapshot.store", skip_all, fields(state = S::NAME))
+    )]
+    async fn store_snapshot<S>(&self, state: &StatePart<ID, S>) -> Result<(), BoxDynError>
+    where
+        S: Send + Sync + Serialize + StateQuery + 'static,
+    {
+        let query = snapshot_key(&state.query::<ID>());
+        let events = state.applied_events()
+            + self
+                .cache
+                .as_ref()
+                .map_or(0, |cache| cache.pending_events(S::NAME, &query));
+        if !self.should_snapshot(S::NAME, &query, events, state.hydration_time()) {
+            if let Some(cache) = &self.cache {
+                cache.insert(S::NAME, &query, state.clone(), events);
+            }
+            return Ok(());
+        }
+        let snapshot = StoredSnapshot {
+            name: S::NAME.to_string(),
+            query,
+            version: state.version(),
+            fingerprint: S::FINGERPRINT,
+            payload: self.encode_payload(serde_json::to_vec(&state.clone().into_state())?)?,
+        };
+        let key = (snapshot.name.clone(), snapshot.query.clone());
+        if let Some(background) = &self.background {
+            if let Some(cache) = &self.cache {
+                cache.insert(&key.0, &key.1, state.clone(), 0);
+            }
+            let store = self.store.clone();
+            let stale_snapshots = self.stale_snapshots.clone();
+            let last_snapshots = self.last_snapshots.clone();
+            let write_key = key.clone();
+            let retention = self.prune_due(&key.0);
+            background.submit(
+                &key.0,
+                &key.1,
+                async move {
+                    if store.store(snapshot).await.is_ok() {
+                        if let Some(retention) = retention {
+                            let _ = store.prune(&write_key.0, &retention).await;
+                        }
+                        mark_stored(&stale_snapshots, &last_snapshots, write_key);
+                    }
+                }
+                .boxed(),
+            );
+            return Ok(());
+        }
+        self.store.store(snapshot).await?;
+        if let Some(cache) = &self.cache {
+            cache.insert(&key.0, &key.1, state.clone(), 0);
+        }
+        if let Some(retention) = self.prune_due(&key.0) {
+            self.store.prune(&key.0, &retention).await?;
+        }
+        mark_stored(&self.stale_snapshots, &self.last_snapshots, key);
+        Ok(())
+    }
+}
+
+/// Records that the snapshot of a state part has been stored.
+fn mark_stored(
+    stale_snapshots: &Mutex<HashSet<(String, String)>>,
+    last_snapshots: &Mutex<HashMap<(String, String), Instant>>,
+    key: (String, String),
+) {
+    if let Ok(mut stale_snapshots) = stale_snapshots.lock() {
+        stale_snapshots.remove(&key);
+    }
+    if let Ok(mut last_snapshots) = last_snapshots.lock() {
+        last_snapshots.insert(key, Instant::now());
+    }
+}
+
+/// Returns the key of a stream query, used to identify the snapshots built from it.
+pub fn snapshot_key<ID: EventId + Display, E: Event + Clone>(query: &StreamQuery<ID, E>) -> String {
+    let mut result = String::new();
+    for f in query.filters() {
+        let excluded_events = if let Some(exclued_events) = f.excluded_events() {
+            format!("-{}", exclued_events.join(","))
+        } else {
+            "".to_string()
+        };
+        let excluded_identifiers: String = f
+            .excluded_identifiers()
+            .iter()
+            .map(|i| format!("!{}={}", i.key, i.value))
+            .collect();
+        let payload_filters = if f.payload_filters().is_empty() {
+            "".to_string()
+        } else {
+            format!(
+                "?{}",
+                f.payload_filters()
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+        let disjunctions: String = f
+            .disjunctions()
+            .iter()
+            .map(|disjunction| {
+                format!(
+                    "[{}]",
+                    disjunction
+                        .iter()
+                        .map(|i| format!("{}={}", i.key, i.value))
+                        .collect::<Vec<_>>()
+                        .join("/")
+                )
+            })
+            .collect();
+        let empty_lists: String = f
+            .empty_lists()
+            .iter()
+            .map(|ident| format!("[{ident}=]"))
+            .collect();
+        let ranges: String = f
+            .ranges()
+            .iter()
+            .map(|range| {
+                format!(
+                    "{{{}}}",
+                    range
+                        .conditions()
+                        .iter()
+                        .map(|(op, value)| format!("{}{op}{value}", range.key))
+                        .collect::<Vec<_>>()
+                        .join("&")
+                )
+            })
+            .collect();
+        result += &format!(
+            "({}{}|{}{}{}{}{}{}{}|{})",
+            f.origin(),
+            f.until()
+                .map(|until| format!("..{until}"))
+                .unwrap_or_default(),
+            f.events().join(","),
+            excluded_events,
+            excluded_identifiers,
+            disjunctions,
+            empty_lists,
+            ranges,
+            payload_filters,
+            f.identifiers()
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+    result
+}
+
+type StoredSnapshots<ID> = HashMap<(String, String), (StoredSnapshot<ID>, SystemTime)>;
+
+/// An in-memory `SnapshotStore`.
+///
+/// Snapshots are shared between the clones of the store and lost when the process exits. It is suited
+/// for tests and for event stores that are rebuilt at every start.
+#[derive(Debug, Clone)]
+pub struct InMemorySnapshotStore<ID> {
+    snapshots: Arc<RwLock<StoredSnapshots<ID>>>,
+}
+
+impl<ID> Default for InMemorySnapshotStore<ID> {
+    fn default() -> Self {
+        Self {
+            snapshots: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl<ID: EventId> SnapshotStore<ID> for InMemorySnapshotStore<ID> {
+    async fn load(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<ID>>, BoxDynError> {
+        let snapshots = self.snapshots.read().map_err(|e| e.to_string())?;
+        Ok(snapshots
+            .get(&(name.to_string(), query.to_string()))
+            .map(|(snapshot, _)| snapshot.clone()))
+    }
+
+    async fn store(&self, snapshot: StoredSnapshot<ID>) -> Result<(), BoxDynError> {
+        let mut snapshots = self.snapshots.write().map_err(|e| e.to_string())?;
+        let key = (snapshot.name.clone(), snapshot.query.clone());
+        if snapshots.get(&key).is_none_or(|(stored, _)| {
+            stored.fingerprint != snapshot.fingerprint || stored.version < snapshot.version
+        }) {
+            snapshots.insert(key, (snapshot, crate::time::now()));
+        }
+        Ok(())
+    }
+
+    async fn list(&self, name: Option<&str>) -> Result<Vec<SnapshotInfo<ID>>, BoxDynError> {
+        let snapshots = self.snapshots.read().map_err(|e| e.to_string())?;
+        Ok(snapshots
+            .values()
+            .filter(|(snapshot, _)| name.is_none_or(|name| snapshot.name == name))
+            .map(|(snapshot, stored_at)| SnapshotInfo {
+                stored_at: Some(*stored_at),
+                ..SnapshotInfo::from(snapshot)
+            })
+            .collect())
+    }
+
+    async fn delete(&self, name: &str, query: &str) -> Result<bool, BoxDynError> {
+        let mut snapshots = self.snapshots.write().map_err(|e| e.to_string())?;
+        Ok(snapshots
+            .remove(&(name.to_string(), query.to_string()))
+            .is_some())
+    }
+}
+
+/// A `SnapshotStore` that keeps each snapshot in a file of a directory.
+///
+/// A file holds a JSON header line with the name, query and version of the snapshot, followed by the
+/// payload. The file name is derived from the snapshot name and query. Files are written to a temporary file and
+/// then renamed, so a crash never leaves a partially written snapshot. The store performs blocking I/O
+/// and is meant for local development and single node deployments.
+#[derive(Debug, Clone)]
+pub struct FileSnapshotStore {
+    directory: PathBuf,
+}
+
+impl FileSnapshotStore {
+    /// Creates a new `FileSnapshotStore`, creating the directory if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    fn path(&self, name: &str, query: &str) -> PathBuf {
+        self.directory
+            .join(format!("{:016x}.snapshot", snapshot_hash(name, query)))
+    }
+
+    fn read<ID: DeserializeOwned>(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<ID>>, BoxDynError> {
+        read_file(&self.path(name, query))
+    }
+}
+
+fn read_file<ID: DeserializeOwned>(path: &Path) -> Result<Option<StoredSnapshot<ID>>, BoxDynError> {
+    match std::fs::read(path) {
+        Ok(content) => {
+            let header_end = content
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .ok_or("invalid snapshot file")?;
+            let header: FileSnapshotHeader<ID> = serde_json::from_slice(&content[..header_end])?;
+            Ok(Some(StoredSnapshot {
+                name: header.name,
+                query: header.query,
+                version: header.version,
+                fingerprint: header.fingerprint,
+                payload: content[header_end + 1..].to_vec(),
+            }))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileSnapshotHeader<ID> {
+    name: String,
+    query: String,
+    version: ID,
+    #[serde(default)]
+    fingerprint: u64,
+}
+
+#[async_trait]
+impl<ID> SnapshotStore<ID> for FileSnapshotStore
+where
+    ID: EventId + Serialize + DeserializeOwned,
+{
+    async fn load(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<ID>>, BoxDynError> {
+        self.read(name, query)
+    }
+
+    async fn store(&self, snapshot: StoredSnapshot<ID>) -> Result<(), BoxDynError> {
+        let stored: Option<StoredSnapshot<ID>> = self
+            .read(&snapshot.name, &snapshot.query)
+            .unwrap_or_default();
+        if stored.is_some_and(|stored| {
+            stored.fingerprint == snapshot.fingerprint && stored.version >= snapshot.version
+        }) {
+            return Ok(());
+        }
+        let path = self.path(&snapshot.name, &snapshot.query);
+        let tmp_path = path.with_extension("snapshot.tmp");
+        let mut content = serde_json::to_vec(&FileSnapshotHeader {
+            name: snapshot.name,
+            query: snapshot.query,
+            version: snapshot.version,
+            fingerprint: snapshot.fingerprint,
+        })?;
+        content.push(b'\n');
+        content.extend(snapshot.payload);
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    async fn list(&self, name: Option<&str>) -> Result<Vec<SnapshotInfo<ID>>, BoxDynError> {
+        let mut snapshots = vec![];
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "snapshot")
+            {
+                continue;
+            }
+            if let Some(snapshot) = read_file::<ID>(&path)? {
+                if name.is_none_or(|name| snapshot.name == name) {
+                    snapshots.push(SnapshotInfo {
+                        stored_at: std::fs::metadata(&path)?.modified().ok(),
+                        ..SnapshotInfo::from(&snapshot)
+                    });
+                }
+            }
+        }
+        Ok(snapshots)
+    }
+
+    async fn delete(&self, name: &str, query: &str) -> Result<bool, BoxDynError> {
+        match std::fs::remove_file(self.path(name, query)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::tests::*;
+    use crate::{ident, IntoStatePart, PersistedEvent};
+
+    fn cart_with_items(applied_items: &[&str]) -> StatePart<i64, Cart> {
+        let mut state = Cart::new("c1").into_state_part();
+        for (id, item) in applied_items.iter().enumerate() {
+            state.mutate_part(PersistedEvent::new(
+                id as i64 + 1,
+                item_added_event(item, "c1"),
+            ));
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn it_stores_and_loads_snapshots() {
+        let snapshotter = Snapshotter::new(InMemorySnapshotStore::default(), 1);
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert_eq!(loaded.version(), 2);
+        assert_eq!(
+            loaded.into_state(),
+            cart("c1", ["p1".to_owned(), "p2".to_owned()])
+        );
+    }
+
+    #[tokio::test]
+    async fn it_skips_snapshots_below_the_frequency() {
+        let snapshotter = Snapshotter::new(InMemorySnapshotStore::default(), 2);
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert_eq!(loaded.version(), 0);
+        assert_eq!(loaded.into_state(), Cart::new("c1"));
+    }
+
+    #[tokio::test]
+    async fn it_snapshots_at_most_once_per_interval() {
+        let snapshotter = Snapshotter::new(InMemorySnapshotStore::default(), 0)
+            .policy(SnapshotPolicy::AtMostEvery(Duration::from_secs(3600)));
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1"]))
+            .await
+            .unwrap();
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert_eq!(loaded.version(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_applies_the_policy_of_the_state_query() {
+        let snapshotter = Snapshotter::new(InMemorySnapshotStore::default(), 100)
+            .policy_for::<Cart>(SnapshotPolicy::custom(|events, hydration| {
+                events >= 2 || hydration > Duration::from_secs(1)
+            }));
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            snapshotter
+                .load_snapshot(Cart::new("c1").into_state_part())
+                .await
+                .version(),
+            0
+        );
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            snapshotter
+                .load_snapshot(Cart::new("c1").into_state_part())
+                .await
+                .version(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rebuilds_snapshots_with_a_different_fingerprint() {
+        let store = InMemorySnapshotStore::default();
+        let query = snapshot_key(&Cart::new("c1").query::<i64>());
+        store
+            .store(StoredSnapshot {
+                name: "Cart".to_string(),
+                query: query.clone(),
+                version: 10,
+                fingerprint: 42,
+                payload: serde_json::to_vec(&cart("c1", ["p1".to_owned()])).unwrap(),
+            })
+            .await
+            .unwrap();
+        let snapshotter = Snapshotter::new(store.clone(), 100);
+
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+        assert_eq!(loaded.version(), 0);
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+
+        let stored = store.load("Cart", &query).await.unwrap().unwrap();
+        assert_eq!(stored.version, 2);
+        assert_eq!(stored.fingerprint, Cart::FINGERPRINT);
+    }
+
+    #[tokio::test]
+    async fn it_administers_the_snapshots() {
+        let store = InMemorySnapshotStore::default();
+        let snapshotter = Snapshotter::new(store.clone(), 100);
+        for cart_id in ["c1", "c2"] {
+            store
+                .store(StoredSnapshot {
+                    name: "Cart".to_string(),
+                    query: snapshot_key(&Cart::new(cart_id).query::<i64>()),
+                    version: 1,
+                    fingerprint: Cart::FINGERPRINT,
+                    payload: serde_json::to_vec(&Cart::new(cart_id)).unwrap(),
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(snapshotter.snapshots(Some("Cart")).await.unwrap().len(), 2);
+        assert_eq!(
+            snapshotter
+                .delete_snapshots_by_identifier("Cart", ident!(#cart_id), "c1")
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(snapshotter.delete_snapshot(&Cart::new("c2")).await.unwrap());
+        assert!(snapshotter.snapshots(None).await.unwrap().is_empty());
+
+        snapshotter.rebuild(&Cart::new("c1")).await.unwrap();
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1"]))
+            .await
+            .unwrap();
+        assert_eq!(snapshotter.snapshots(None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_prunes_the_snapshots() {
+        let store = InMemorySnapshotStore::default();
+        let snapshotter =
+            Snapshotter::new(store.clone(), 0).retention(SnapshotRetention::KeepLatest(2));
+        for (id, cart_id) in ["c1", "c2", "c3"].into_iter().enumerate() {
+            let mut state = Cart::new(cart_id).into_state_part();
+            state.mutate_part(PersistedEvent::new(
+                id as i64 + 1,
+                item_added_event("p1", cart_id),
+            ));
+            snapshotter.store_snapshot(&state).await.unwrap();
+        }
+
+        assert_eq!(snapshotter.snapshots(Some("Cart")).await.unwrap().len(), 3);
+        assert_eq!(
+            snapshotter
+                .prune("Cart", &SnapshotRetention::KeepLatest(1))
+                .await
+                .unwrap(),
+            2
+        );
+        let snapshots = snapshotter.snapshots(Some("Cart")).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            snapshots[0].query,
+            snapshot_key(&Cart::new("c3").query::<i64>())
+        );
+        assert_eq!(
+            snapshotter
+                .prune("Cart", &SnapshotRetention::MaxAge(Duration::ZERO))
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_most_recent_snapshot() {
+        let store = InMemorySnapshotStore::default();
+        let snapshot = |version: i64| StoredSnapshot {
+            name: "Cart".to_string(),
+            query: "q".to_string(),
+            version,
+            fingerprint: 0,
+            payload: b"{}".to_vec(),
+        };
+
+        store.store(snapshot(3)).await.unwrap();
+        store.store(snapshot(2)).await.unwrap();
+
+        assert_eq!(store.load("Cart", "q").await.unwrap(), Some(snapshot(3)));
+    }
+
+    #[cfg(feature = "snapshot-compression")]
+    #[tokio::test]
+    async fn it_compresses_snapshots() {
+        let store = InMemorySnapshotStore::default();
+        let snapshotter = Snapshotter::new(store.clone(), 0).compression(3);
+
+        let state = cart_with_items(&["p1"]);
+        snapshotter.store_snapshot(&state).await.unwrap();
+        let stored = store
+            .load("Cart", &snapshot_key(&state.query::<i64>()))
+            .await
+            .unwrap()
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert!(stored.payload.starts_with(ZSTD_MARKER));
+        assert_eq!(loaded.into_state(), cart("c1", ["p1".to_owned()]));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn it_encrypts_snapshots() {
+        let store = InMemorySnapshotStore::default();
+        let snapshotter = Snapshotter::new(store.clone(), 0)
+            .encryption(crate::StaticKeyProvider::new("k1", [1; 32]));
+
+        let state = cart_with_items(&["p1"]);
+        snapshotter.store_snapshot(&state).await.unwrap();
+        let stored = store
+            .load("Cart", &snapshot_key(&state.query::<i64>()))
+            .await
+            .unwrap()
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+        let without_key = Snapshotter::new(store.clone(), 0)
+            .encryption(crate::StaticKeyProvider::new("k2", [2; 32]))
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert!(stored.payload.starts_with(ENCRYPTION_MARKER));
+        assert!(!stored.payload.windows(2).any(|window| window == b"p1"));
+        assert_eq!(loaded.into_state(), cart("c1", ["p1".to_owned()]));
+        assert_eq!(without_key.into_state(), cart("c1", []));
+    }
+
+    #[tokio::test]
+    async fn it_caches_the_hydrated_states() {
+        let store = InMemorySnapshotStore::default();
+        let snapshotter = Snapshotter::new(store.clone(), 2).cache(10, Duration::from_secs(60));
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1", "p2"]))
+            .await
+            .unwrap();
+        let cached = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+        let stored = snapshotter
+            .store()
+            .load("Cart", &snapshot_key(&cached.query::<i64>()))
+            .await
+            .unwrap();
+
+        assert_eq!(cached.version(), 2);
+        assert_eq!(
+            cached.into_state(),
+            cart("c1", ["p1".to_owned(), "p2".to_owned()])
+        );
+        assert_eq!(stored, None);
+
+        let mut state = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+        state.mutate_part(PersistedEvent::new(3, item_added_event("p3", "c1")));
+        snapshotter.store_snapshot(&state).await.unwrap();
+        let stored = store
+            .load("Cart", &snapshot_key(&state.query::<i64>()))
+            .await
+            .unwrap();
+
+        assert_eq!(stored.map(|snapshot| snapshot.version), Some(3));
+
+        snapshotter.invalidate::<i64, _>(&Cart::new("c1"));
+        store
+            .delete("Cart", &snapshot_key(&state.query::<i64>()))
+            .await
+            .unwrap();
+        let loaded = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert_eq!(loaded.version(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_writes_snapshots_in_the_background() {
+        let store = InMemorySnapshotStore::default();
+        let (snapshotter, worker) = Snapshotter::new(store.clone(), 0).background();
+
+        let state = cart_with_items(&["p1"]);
+        snapshotter.store_snapshot(&state).await.unwrap();
+        let key = snapshot_key(&state.query::<i64>());
+
+        assert_eq!(store.load("Cart", &key).await.unwrap(), None);
+
+        drop(snapshotter);
+        worker.await;
+
+        assert_eq!(
+            store
+                .load("Cart", &key)
+                .await
+                .unwrap()
+                .map(|snapshot| snapshot.version),
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_persists_snapshots_in_files() {
+        let directory =
+            std::env::temp_dir().join(format!("disintegrate-snapshots-{}", std::process::id()));
+        let snapshotter = Snapshotter::new(FileSnapshotStore::new(&directory).unwrap(), 0);
+
+        snapshotter
+            .store_snapshot(&cart_with_items(&["p1"]))
+            .await
+            .unwrap();
+        let snapshotter = Snapshotter::new(FileSnapshotStore::new(&directory).unwrap(), 0);
+        let loaded: StatePart<i64, Cart> = snapshotter
+            .load_snapshot(Cart::new("c1").into_state_part())
+            .await;
+
+        assert_eq!(loaded.version(), 1);
+        assert_eq!(loaded.into_state(), cart("c1", ["p1".to_owned()]));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+//! Background writes of the snapshots, out of the decision hot path.
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+
+type WriteKey = (String, String);
+
+/// A queue of snapshot writes executed by a background worker.
+///
+/// The writes are coalesced by state query name and snapshot key: when a snapshot is submitted while a
+/// previous one of the same state part is still waiting, only the latest is written.
+pub(super) struct BackgroundWrites {
+    pending: Arc<Mutex<HashMap<WriteKey, BoxFuture<'static, ()>>>>,
+    sender: UnboundedSender<WriteKey>,
+}
+
+impl BackgroundWrites {
+    /// Creates the queue and the worker that executes its writes.
+    pub(super) fn new() -> (Self, impl Future<Output = ()> + Send + 'static) {
+        let pending: Arc<Mutex<HashMap<WriteKey, BoxFuture<'static, ()>>>> = Arc::default();
+        let (sender, mut receiver) = mpsc::unbounded::<WriteKey>();
+        let worker_pending = pending.clone();
+        let worker = async move {
+            while let Some(key) = receiver.next().await {
+                let write = worker_pending
+                    .lock()
+                    .ok()
+                    .and_then(|mut pending| pending.remove(&key));
+                if let Some(write) = write {
+                    write.await;
+                }
+            }
+        };
+        (Self { pending, sender }, worker)
+    }
+
+    /// Queues the write, replacing the pending write of the same state part.
+    pub(super) fn submit(&self, name: &str, query: &str, write: BoxFuture<'static, ()>) {
+        let key = (name.to_string(), query.to_string());
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        if pending.insert(key.clone(), write).is_none() {
+            let _ = self.sender.unbounded_send(key);
+        }
+    }
+}
+
+impl fmt::Debug for BackgroundWrites {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackgroundWrites").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn it_coalesces_the_pending_writes() {
+        let (writes, worker) = BackgroundWrites::new();
+        let written = Arc::new(Mutex::new(vec![]));
+
+        for version in 1..=3 {
+            let written = written.clone();
+            writes.submit(
+                "Cart",
+                "c1",
+                async move { written.lock().unwrap().push(version) }.boxed(),
+            );
+        }
+        drop(writes);
+        worker.await;
+
+        assert_eq!(*written.lock().unwrap(), vec![3]);
+    }
+}
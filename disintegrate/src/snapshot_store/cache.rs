@@ -0,0 +1,165 @@
+//! An in-process cache of the state parts, in front of the snapshot store.
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::time::Instant;
+
+type CacheKey = (String, String);
+
+/// A least recently used cache of the hydrated state parts, keyed by state query name and snapshot key.
+///
+/// The cache keeps the state parts as they were after the last hydration, so loading a hot state part
+/// skips the snapshot fetch and replays only the events appended after it. Each entry counts the events
+/// applied since the last snapshot written to the store, so the snapshot policy is still honored.
+pub(super) struct SnapshotCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    entries: HashMap<CacheKey, CacheEntry>,
+    clock: u64,
+}
+
+struct CacheEntry {
+    state: Arc<dyn Any + Send + Sync>,
+    pending_events: u64,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+impl SnapshotCache {
+    pub(super) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    /// Returns a copy of the cached state, if it is present, not expired and of type `T`.
+    pub(super) fn get<T: Clone + 'static>(&self, name: &str, query: &str) -> Option<T> {
+        let mut entries = self.entries.lock().ok()?;
+        let key = (name.to_string(), query.to_string());
+        if entries
+            .entries
+            .get(&key)
+            .is_some_and(|entry| entry.cached_at.elapsed() >= self.ttl)
+        {
+            entries.entries.remove(&key);
+            return None;
+        }
+        entries.clock += 1;
+        let clock = entries.clock;
+        let entry = entries.entries.get_mut(&key)?;
+        entry.last_used = clock;
+        entry.state.downcast_ref::<T>().cloned()
+    }
+
+    /// Returns the number of events applied to the cached state since the last stored snapshot.
+    pub(super) fn pending_events(&self, name: &str, query: &str) -> u64 {
+        self.entries
+            .lock()
+            .ok()
+            .and_then(|entries| {
+                entries
+                    .entries
+                    .get(&(name.to_string(), query.to_string()))
+                    .map(|entry| entry.pending_events)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Caches the state, evicting the least recently used entry when the cache is full.
+    pub(super) fn insert<T: Send + Sync + 'static>(
+        &self,
+        name: &str,
+        query: &str,
+        state: T,
+        pending_events: u64,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        let key = (name.to_string(), query.to_string());
+        if !entries.entries.contains_key(&key) && entries.entries.len() >= self.capacity {
+            if let Some(lru) = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                entries.entries.remove(&lru);
+            }
+        }
+        entries.clock += 1;
+        let entry = CacheEntry {
+            state: Arc::new(state),
+            pending_events,
+            cached_at: Instant::now(),
+            last_used: entries.clock,
+        };
+        entries.entries.insert(key, entry);
+    }
+
+    /// Removes the cached state of the state query `name`, or all of them if `query` is `None`.
+    pub(super) fn remove(&self, name: &str, query: Option<&str>) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries
+                .entries
+                .retain(|(n, q), _| n != name || query.is_some_and(|query| query != q));
+        }
+    }
+
+    /// Removes all the cached states.
+    pub(super) fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.entries.clear();
+        }
+    }
+}
+
+impl fmt::Debug for SnapshotCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotCache")
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_evicts_the_least_recently_used_entry() {
+        let cache = SnapshotCache::new(2, Duration::from_secs(60));
+
+        cache.insert("Cart", "c1", 1, 0);
+        cache.insert("Cart", "c2", 2, 0);
+        assert_eq!(cache.get::<i32>("Cart", "c1"), Some(1));
+        cache.insert("Cart", "c3", 3, 0);
+
+        assert_eq!(cache.get::<i32>("Cart", "c1"), Some(1));
+        assert_eq!(cache.get::<i32>("Cart", "c2"), None);
+        assert_eq!(cache.get::<i32>("Cart", "c3"), Some(3));
+    }
+
+    #[test]
+    fn it_expires_the_entries() {
+        let cache = SnapshotCache::new(2, Duration::ZERO);
+
+        cache.insert("Cart", "c1", 1, 0);
+
+        assert_eq!(cache.get::<i32>("Cart", "c1"), None);
+    }
+}
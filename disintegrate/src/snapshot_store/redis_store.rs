@@ -0,0 +1,213 @@
+//! A `SnapshotStore` backed by Redis.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{snapshot_hash, SnapshotInfo, SnapshotStore, StoredSnapshot};
+use crate::{BoxDynError, EventId};
+
+/// A `SnapshotStore` that keeps the snapshots in Redis.
+///
+/// The store treats snapshots as a disposable cache: each snapshot can expire after a time to live, and
+/// payloads larger than the size limit are not stored, so the state is hydrated from the event store
+/// instead. Each snapshot is a Redis hash with the `name`, `query`, `version`, `fingerprint`, `payload`
+/// and `stored_at` fields, under a key made of the configured prefix and a hash of the snapshot name and query.
+///
+/// The check of the stored version is not atomic, so two concurrent writers of the same snapshot may
+/// store an older version, which is then replaced by the next snapshot.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use disintegrate::{RedisSnapshotStore, Snapshotter};
+/// use std::time::Duration;
+///
+/// # async fn example() -> Result<(), redis::RedisError> {
+/// let client = redis::Client::open("redis://127.0.0.1/")?;
+/// let store = RedisSnapshotStore::new(client)
+///     .await?
+///     .prefix("orders:snapshot:")
+///     .ttl(Duration::from_secs(24 * 3600))
+///     .max_size(1024 * 1024);
+/// let snapshotter = Snapshotter::new(store, 10);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisSnapshotStore {
+    connection: ConnectionManager,
+    prefix: String,
+    ttl: Option<Duration>,
+    max_size: Option<usize>,
+}
+
+impl RedisSnapshotStore {
+    /// Creates a new `RedisSnapshotStore` connected with the given client.
+    pub async fn new(client: redis::Client) -> Result<Self, redis::RedisError> {
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            prefix: "disintegrate:snapshot:".to_string(),
+            ttl: None,
+            max_size: None,
+        })
+    }
+
+    /// Sets the prefix of the snapshot keys. The default is `disintegrate:snapshot:`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the time to live of the snapshots. By default snapshots do not expire.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the maximum size in bytes of the stored payloads. Larger snapshots are not stored.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    fn key(&self, name: &str, query: &str) -> String {
+        format!("{}{name}:{:016x}", self.prefix, snapshot_hash(name, query))
+    }
+}
+
+#[async_trait]
+impl<ID> SnapshotStore<ID> for RedisSnapshotStore
+where
+    ID: EventId + Serialize + DeserializeOwned,
+{
+    async fn load(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<Option<StoredSnapshot<ID>>, BoxDynError> {
+        let mut connection = self.connection.clone();
+        let fields: (
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<u64>,
+            Option<Vec<u8>>,
+        ) = redis::cmd("HMGET")
+            .arg(self.key(name, query))
+            .arg(&["name", "query", "version", "fingerprint", "payload"])
+            .query_async(&mut connection)
+            .await?;
+        let (Some(name), Some(query), Some(version), fingerprint, Some(payload)) = fields else {
+            return Ok(None);
+        };
+        Ok(Some(StoredSnapshot {
+            name,
+            query,
+            version: serde_json::from_str(&version)?,
+            fingerprint: fingerprint.unwrap_or_default(),
+            payload,
+        }))
+    }
+
+    async fn store(&self, snapshot: StoredSnapshot<ID>) -> Result<(), BoxDynError> {
+        if self
+            .max_size
+            .is_some_and(|max_size| snapshot.payload.len() > max_size)
+        {
+            return Ok(());
+        }
+        let stored: Option<StoredSnapshot<ID>> = self.load(&snapshot.name, &snapshot.query).await?;
+        if stored.is_some_and(|stored| {
+            stored.fingerprint == snapshot.fingerprint && stored.version >= snapshot.version
+        }) {
+            return Ok(());
+        }
+        let key = self.key(&snapshot.name, &snapshot.query);
+        let mut pipeline = redis::pipe();
+        pipeline
+            .atomic()
+            .hset_multiple(
+                &key,
+                &[
+                    ("name", snapshot.name.into_bytes()),
+                    ("query", snapshot.query.into_bytes()),
+                    ("version", serde_json::to_vec(&snapshot.version)?),
+                    ("fingerprint", snapshot.fingerprint.to_string().into_bytes()),
+                    ("payload", snapshot.payload),
+                    (
+                        "stored_at",
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)?
+                            .as_secs()
+                            .to_string()
+                            .into_bytes(),
+                    ),
+                ],
+            )
+            .ignore();
+        match self.ttl {
+            Some(ttl) => pipeline.pexpire(&key, ttl.as_millis() as i64).ignore(),
+            None => pipeline.persist(&key).ignore(),
+        };
+        let mut connection = self.connection.clone();
+        pipeline.query_async::<()>(&mut connection).await?;
+        Ok(())
+    }
+
+    async fn list(&self, name: Option<&str>) -> Result<Vec<SnapshotInfo<ID>>, BoxDynError> {
+        let mut connection = self.connection.clone();
+        let pattern = format!("{}{}:*", self.prefix, name.unwrap_or("*"));
+        let keys: Vec<String> = {
+            let mut iter = connection.scan_match::<_, String>(pattern).await?;
+            let mut keys = vec![];
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        let mut snapshots = vec![];
+        for key in keys {
+            let (stored_name, query, version, fingerprint, stored_at): (
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                Option<u64>,
+                Option<u64>,
+            ) = redis::cmd("HMGET")
+                .arg(&key)
+                .arg(&["name", "query", "version", "fingerprint", "stored_at"])
+                .query_async(&mut connection)
+                .await?;
+            let size: u64 = redis::cmd("HSTRLEN")
+                .arg(&key)
+                .arg("payload")
+                .query_async(&mut connection)
+                .await?;
+            let (Some(stored_name), Some(query), Some(version)) = (stored_name, query, version)
+            else {
+                continue;
+            };
+            if name.is_some_and(|name| name != stored_name) {
+                continue;
+            }
+            snapshots.push(SnapshotInfo {
+                name: stored_name,
+                query,
+                version: serde_json::from_str(&version)?,
+                fingerprint: fingerprint.unwrap_or_default(),
+                size,
+                stored_at: stored_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            });
+        }
+        Ok(snapshots)
+    }
+
+    async fn delete(&self, name: &str, query: &str) -> Result<bool, BoxDynError> {
+        let mut connection = self.connection.clone();
+        let deleted: u64 = connection.del(self.key(name, query)).await?;
+        Ok(deleted > 0)
+    }
+}
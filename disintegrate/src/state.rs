@@ -1,25 +1,17 @@
 //! A State contains the initial conditions that a `Decision` uses to make the changes.
-use serde::Deserialize;
+#[cfg(feature = "store")]
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::event::EventId;
-use crate::stream_query::StreamQuery;
-use crate::{all_the_tuples, union, BoxDynError, StateSnapshotter};
-use crate::{event::Event, PersistedEvent};
+use crate::{all_the_tuples, union};
+#[cfg(feature = "store")]
+use crate::{BoxDynError, ClassifiedError, EventStore, EventsSubset, StateSnapshotter};
+use crate::{Event, EventId, PersistedEvent, StateMutate, StatePart, StateQuery, StreamQuery};
+#[cfg(feature = "store")]
 use async_trait::async_trait;
+#[cfg(feature = "store")]
+use futures::TryStreamExt;
 use paste::paste;
 use std::error::Error as StdError;
-use std::ops::Deref;
-
-/// A mutable state that can be changed by events from the event store.
-pub trait StateMutate: StateQuery {
-    /// Mutates the state object based on the provided event.
-    ///
-    /// # Arguments
-    ///
-    /// * `event` - The event to be applied to mutate the state.
-    fn mutate(&mut self, event: Self::Event);
-}
 
 /// A group of states that can be queried and modified together.
 ///
@@ -84,7 +76,7 @@ macro_rules! impl_multi_state {
                         }
                     )*
                     if [<state_ $last:lower>].matches_event(&event) {
-                         [<state_ $last:lower>].mutate_part(event.clone());
+                         [<state_ $last:lower>].mutate_part(event);
                     }
                 }
             }
@@ -119,6 +111,7 @@ all_the_tuples!(impl_multi_state);
 ///
 /// - `T`: The type of snapshotter used for loading and storing snapshots.
 /// - `E`: The type of events that the multi-state object handles.
+#[cfg(feature = "store")]
 #[async_trait]
 pub trait MultiStateSnapshot<ID: EventId, T: StateSnapshotter<ID>> {
     // Loads the state of all sub-states using the provided snapshotter
@@ -144,6 +137,7 @@ pub trait MultiStateSnapshot<ID: EventId, T: StateSnapshotter<ID>> {
     async fn store_all(&self, backend: &T) -> Result<(), BoxDynError>;
 }
 
+#[cfg(feature = "store")]
 macro_rules! impl_multi_state_snapshot {
     (
         [$($ty:ident),*], $last:ident
@@ -160,11 +154,15 @@ macro_rules! impl_multi_state_snapshot {
                 paste! {
 
                     let ($([<state_ $ty:lower>],)* [<state_ $last:lower>]) = self;
-                    *[<state_ $last:lower>] = backend.load_snapshot([<state_ $last:lower>].clone()).await;
-                    let last_event_id = [<state_ $last:lower>].version;
+                    if $last::WINDOW.is_none() {
+                        *[<state_ $last:lower>] = backend.load_snapshot([<state_ $last:lower>].clone()).await;
+                    }
+                    let last_event_id = [<state_ $last:lower>].version();
                     $(
-                        *[<state_ $ty:lower>] = backend.load_snapshot([<state_ $ty:lower>].clone()).await;
-                        let last_event_id = last_event_id.max([<state_ $ty:lower>].version);
+                        if $ty::WINDOW.is_none() {
+                            *[<state_ $ty:lower>] = backend.load_snapshot([<state_ $ty:lower>].clone()).await;
+                        }
+                        let last_event_id = last_event_id.max([<state_ $ty:lower>].version());
                     )*
                 }
                 last_event_id
@@ -175,179 +173,181 @@ macro_rules! impl_multi_state_snapshot {
 
                     let ($([<state_ $ty:lower>],)* [<state_ $last:lower>]) = self;
                     $(
-                    backend.store_snapshot(&[<state_ $ty:lower>]).await?;
+                    if $ty::WINDOW.is_none() {
+                        backend.store_snapshot(&[<state_ $ty:lower>]).await?;
+                    }
                     )*
-                    backend.store_snapshot(&[<state_ $last:lower>]).await?;
+                    if $last::WINDOW.is_none() {
+                        backend.store_snapshot(&[<state_ $last:lower>]).await?;
+                    }
                 }
                 Ok(())
             }
         }
     }
 }
+#[cfg(feature = "store")]
 all_the_tuples!(impl_multi_state_snapshot);
 
-/// Represents a state query used to retrieve events from the event store to build a state.
+/// A multi-state hydrated with a separate query for each sub-state.
 ///
-/// The query method returns a `StreamQuery` to be used for querying the event store.
-pub trait StateQuery: Clone + Send + Sync {
-    /// the unique name of the state query.
-    const NAME: &'static str;
-    /// The type of events queried by this state query.
-    type Event: Event + Clone + Send + Sync;
-
-    /// Returns the stream query used to retrieve relevant events for building the state.
-    fn query<ID: EventId>(&self) -> StreamQuery<ID, Self::Event>;
-}
-
-impl<ID, S, E: Event + Clone> From<&S> for StreamQuery<ID, E>
-where
-    S: StateQuery<Event = E>,
-    ID: EventId,
-{
-    fn from(state: &S) -> Self {
-        state.query()
-    }
-}
-
-/// A structure representing a sub-state in a multi-state object. It encapsulates
-/// the version, applied events count, and the payload of a sub-state.
+/// A trait necessary to hydrate the sub-states of a multi-state concurrently, streaming the events of each
+/// sub-state with its own query instead of the unified one.
 ///
 /// # Type Parameters
 ///
-/// - `S`: The type implementing the `StateMutate` trait, representing the sub-state.
-#[derive(Clone, Serialize, Deserialize)]
-pub struct StatePart<ID: EventId, S: StateQuery> {
-    /// The version of the sub-state.
-    version: ID,
-    /// The count of events applied to the sub-state.
-    applied_events: u64,
-    /// The payload of the sub-state.
-    inner: S,
+/// - `E`: The type of events that the multi-state object handles.
+/// - `ES`: The type of event store used to stream the events.
+#[cfg(feature = "store")]
+#[async_trait]
+pub trait MultiStateHydrate<ID: EventId, E: Event + Clone, ES> {
+    /// Hydrates all sub-states concurrently, each one from the events of its own query.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_store` - The event store used to stream the events of the sub-states.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of the hydration.
+    async fn hydrate_all(&mut self, event_store: &ES) -> Result<(), BoxDynError>;
+    /// Moves the origin of the windowed sub-states to the beginning of their window.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_store` - The event store used to find the beginning of the windows.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of the operation.
+    async fn window_all(&mut self, event_store: &ES) -> Result<(), BoxDynError>;
 }
 
-impl<ID: EventId, S: StateQuery> StatePart<ID, S> {
-    pub fn new(version: ID, payload: S) -> Self {
-        Self {
-            version,
-            applied_events: 0,
-            inner: payload,
-        }
-    }
-    pub fn version(&self) -> ID {
-        self.version
-    }
-    pub fn applied_events(&self) -> u64 {
-        self.applied_events
-    }
-    pub fn query_part(&self) -> StreamQuery<ID, <S as StateQuery>::Event> {
-        self.inner.query().change_origin(self.version)
-    }
-
-    pub fn matches_event<U>(&self, event: &PersistedEvent<ID, U>) -> bool
-    where
-        U: Event + Clone,
-        <S as StateQuery>::Event: Into<U>,
-    {
-        self.query_part().cast().matches(event)
-    }
-    pub fn mutate_part<E>(&mut self, event: PersistedEvent<ID, E>)
-    where
-        E: Event,
-        S: StateMutate,
-        <S as StateQuery>::Event: TryFrom<E>,
-        <<S as StateQuery>::Event as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+#[cfg(feature = "store")]
+async fn hydrate_part<ID, E, ES, S>(
+    event_store: &ES,
+    state_part: &mut StatePart<ID, S>,
+) -> Result<(), BoxDynError>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Sync,
+    <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+    S: StateMutate + 'static,
+    <S as StateQuery>::Event: TryFrom<E> + 'static,
+    <<S as StateQuery>::Event as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    let () = EventsSubset::<S::Event, E>::ASSERT;
+    let query = state_part.query_part();
+    let mut event_stream = event_store.stream(&query);
+    while let Some(event) = event_stream
+        .try_next()
+        .await
+        .map_err(ClassifiedError::boxed)?
     {
-        self.version = event.id;
-        self.applied_events += 1;
-        self.inner.mutate(event.event.try_into().unwrap());
+        state_part.mutate_part::<S::Event>(event);
     }
+    Ok(())
 }
 
-impl<ID: EventId, S: StateQuery> Deref for StatePart<ID, S> {
-    type Target = S;
-
-    fn deref(&self) -> &S {
-        &self.inner
+#[cfg(feature = "store")]
+async fn window_part<ID, E, ES, S>(
+    event_store: &ES,
+    state_part: &mut StatePart<ID, S>,
+) -> Result<(), BoxDynError>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Sync,
+    <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+    S: StateMutate + 'static,
+    <S as StateQuery>::Event: TryFrom<E> + 'static,
+    <<S as StateQuery>::Event as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+{
+    if let Some(window) = S::WINDOW {
+        let origin = event_store
+            .window_origin(&state_part.query::<ID>(), window)
+            .await
+            .map_err(ClassifiedError::boxed)?;
+        *state_part = StatePart::new(origin, S::clone(state_part));
     }
+    Ok(())
 }
 
-/// Converts an state into `StatePart`s.
-///
-/// This trait is used to initialize a multi-state object by converting a state into a state part
-/// with version and event information.
-///
-/// # Type Parameters
-///
-/// - `T`: The type of the object that can be converted into a `StatePart`.
-///
-/// # Associated Types
-///
-/// - `Target`: The resulting type after conversion, representing a `StatePart`.
-pub trait IntoStatePart<ID: EventId, T>: Sized {
-    type Target;
-    /// Converts the object into a `StatePart`.
-    ///
-    /// # Returns
-    ///
-    /// Returns the resulting `StatePart` after the conversion.
-    fn into_state_part(self) -> Self::Target;
-}
-
-/// Extracts the state payload from a `StatePart`.
-///
-/// # Type Parameters
-///
-/// - `T`: The type representing the concrete state to be obtained from the `StatePart`.
-pub trait IntoState<T>: Sized {
-    /// Converts the `StatePart` into a concrete state type.
-    ///
-    /// # Returns
-    ///
-    /// Returns the concrete state obtained from the `StatePart`.
-    fn into_state(self) -> T;
-}
-
-macro_rules! impl_from_state {
+#[cfg(feature = "store")]
+macro_rules! impl_multi_state_hydrate {
     (
         [$($ty:ident),*], $last:ident
     ) => {
+        #[async_trait]
         #[allow(unused_parens)]
-        impl<ID, $($ty,)* $last> IntoStatePart<ID, ($($ty,)* $last)> for ($($ty,)* $last) where
+        impl<ID, E, ES, $($ty,)* $last> MultiStateHydrate<ID, E, ES> for ($(StatePart<ID, $ty>,)* StatePart<ID, $last>)
+        where
             ID: EventId,
-            $($ty: StateQuery,)*
-            $last: StateQuery,
+            E: Event + Clone + Send + Sync,
+            ES: EventStore<ID, E> + Sync,
+            <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+            $(
+                $ty: StateMutate + 'static,
+                <$ty as StateQuery>::Event: TryFrom<E> + 'static,
+                <<$ty as StateQuery>::Event as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
+            )*
+            $last: StateMutate + 'static,
+            <$last as StateQuery>::Event: TryFrom<E> + 'static,
+            <<$last as StateQuery>::Event as TryFrom<E>>::Error: StdError + Send + Sync + 'static,
         {
-            type Target = ($(StatePart<ID, $ty>,)* StatePart<ID, $last>);
-            paste::paste! {
-                fn into_state_part(self) -> ($(StatePart<ID, $ty>,)*StatePart<ID, $last>){
-                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>])= self;
-                    ($(StatePart{ inner: [<state_ $ty:lower>], version: Default::default(), applied_events: 0},)* StatePart{inner: [<state_ $last:lower>], version: Default::default(), applied_events: 0})
+            async fn hydrate_all(&mut self, event_store: &ES) -> Result<(), BoxDynError> {
+                paste! {
+                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>]) = self;
+                    futures::try_join!(
+                        $(hydrate_part(event_store, [<state_ $ty:lower>]),)*
+                        hydrate_part(event_store, [<state_ $last:lower>])
+                    )?;
                 }
+                Ok(())
             }
-        }
 
-        #[allow(unused_parens)]
-        impl<ID, $($ty,)* $last> IntoState<($($ty,)* $last)> for ($(StatePart<ID, $ty>,)* StatePart<ID, $last>) where
-            ID: EventId,
-            $($ty: StateQuery,)*
-            $last: StateQuery,
-        {
-            paste::paste! {
-                fn into_state(self) -> ($($ty,)* $last){
-                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>])= self;
-                    ($( [<state_ $ty:lower>].inner,)* [<state_ $last:lower>].inner)
+            async fn window_all(&mut self, event_store: &ES) -> Result<(), BoxDynError> {
+                paste! {
+                    let ($([<state_ $ty:lower>],)* [<state_ $last:lower>]) = self;
+                    $(window_part(event_store, [<state_ $ty:lower>]).await?;)*
+                    window_part(event_store, [<state_ $last:lower>]).await?;
                 }
+                Ok(())
             }
         }
     }
 }
-
-all_the_tuples!(impl_from_state);
+#[cfg(feature = "store")]
+all_the_tuples!(impl_multi_state_hydrate);
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::utils::tests::*;
+    use crate::{IntoState, IntoStatePart};
+
+    #[test]
+    fn it_composes_many_state_queries_in_a_single_query() {
+        let mut state = (
+            Cart::new("c1"),
+            Cart::new("c2"),
+            Cart::new("c3"),
+            Cart::new("c4"),
+            Cart::new("c5"),
+            Cart::new("c6"),
+            Cart::new("c7"),
+        )
+            .into_state_part();
+
+        let query: StreamQuery<i64, ShoppingCartEvent> = state.query_all();
+        state.mutate_all(PersistedEvent::new(1, item_added_event("p1", "c7")));
+
+        assert_eq!(query.filters().len(), 7);
+        assert_eq!(MultiState::<i64, ShoppingCartEvent>::version(&state), 1);
+        assert_eq!(state.6.into_state(), cart("c7", ["p1".to_string()]));
+        assert_eq!(state.0.applied_events(), 0);
+    }
 
     #[test]
     fn it_mutates_all() {
@@ -355,11 +355,11 @@ mod test {
         state.mutate_all(PersistedEvent::new(1, item_added_event("p1", "c1")));
         state.mutate_all(PersistedEvent::new(2, item_added_event("p2", "c2")));
         let (cart1, cart2) = state;
-        assert_eq!(cart1.version, 1);
-        assert_eq!(cart1.applied_events, 1);
+        assert_eq!(cart1.version(), 1);
+        assert_eq!(cart1.applied_events(), 1);
         assert_eq!(cart1.into_state(), cart("c1", ["p1".to_string()]));
-        assert_eq!(cart2.version, 2);
-        assert_eq!(cart2.applied_events, 1);
+        assert_eq!(cart2.version(), 2);
+        assert_eq!(cart2.applied_events(), 1);
         assert_eq!(cart2.into_state(), cart("c2", ["p2".to_string()]));
     }
 
@@ -378,6 +378,7 @@ mod test {
         );
     }
 
+    #[cfg(feature = "store")]
     #[tokio::test]
     async fn it_stores_all() {
         let multi_state = (cart("c1", []), cart("c2", [])).into_state_part();
@@ -385,16 +386,17 @@ mod test {
         snapshotter
             .expect_store_snapshot()
             .once()
-            .withf(|s: &StatePart<i64, Cart>| s.inner == cart("c1", []))
+            .withf(|s: &StatePart<i64, Cart>| **s == cart("c1", []))
             .return_once(|_| Ok(()));
         snapshotter
             .expect_store_snapshot()
             .once()
-            .withf(|s: &StatePart<i64, Cart>| s.inner == cart("c2", []))
+            .withf(|s: &StatePart<i64, Cart>| **s == cart("c2", []))
             .return_once(|_| Ok(()));
         multi_state.store_all(&snapshotter).await.unwrap();
     }
 
+    #[cfg(feature = "store")]
     #[tokio::test]
     async fn it_loads_all() {
         let mut multi_state = (cart("c1", []), cart("c2", [])).into_state_part();
@@ -402,16 +404,16 @@ mod test {
         snapshotter
             .expect_load_snapshot()
             .once()
-            .withf(|q| q.inner == cart("c1", []))
+            .withf(|q| **q == cart("c1", []))
             .returning(|_| cart("c1", ["p1".to_owned()]).into_state_part());
         snapshotter
             .expect_load_snapshot()
             .once()
-            .withf(|q| q.inner == cart("c2", []))
+            .withf(|q| **q == cart("c2", []))
             .returning(|_| cart("c2", ["p2".to_owned()]).into_state_part());
         multi_state.load_all(&snapshotter).await;
         let (cart1, cart2) = multi_state;
-        assert_eq!(cart1.inner, cart("c1", ["p1".to_owned()]));
-        assert_eq!(cart2.inner, cart("c2", ["p2".to_owned()]));
+        assert_eq!(*cart1, cart("c1", ["p1".to_owned()]));
+        assert_eq!(*cart2, cart("c2", ["p2".to_owned()]));
     }
 }
@@ -1,16 +1,39 @@
 //! State Store provides components for retrieving decision states and persisting decision changes.
+//!
+//! A decision state store implements `LoadState` and `PersistDecision`, and decides how the state of a
+//! decision is loaded and how its events are appended. The library ships with these strategies:
+//!
+//! - `EventSourcedStateStore`: hydrates the state from the events, optionally starting from snapshots.
+//! - `SnapshotStateStore`: loads the state only from its snapshots, and updates them with the events of
+//!   the decisions.
+//! - `ExternalStateStore`: loads the state from a user-provided `StateRepository`.
+//!
+//! The strategies can be mixed in one system, with a `DecisionMaker` for each of them, and custom ones
+//! can be provided by implementing the two traits.
+mod external;
+mod snapshot;
+mod warnings;
+
+pub use external::{ExternalStateStore, StateRepository};
+pub use snapshot::SnapshotStateStore;
+pub use warnings::{GrowthWarning, GrowthWarnings};
+
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::state::{MultiState, MultiStateSnapshot, StatePart};
+use super::state::{MultiState, MultiStateHydrate, MultiStateSnapshot};
 use super::{IntoState, IntoStatePart};
 use crate::decision::PersistDecision;
-use crate::event::EventId;
+use crate::time::Instant;
 use crate::BoxDynError;
+use crate::ClassifiedError;
+use crate::EventId;
 use crate::EventStore;
+use crate::StatePart;
 use crate::StateQuery;
 use crate::{Event, PersistedEvent, StreamQuery};
 use async_trait::async_trait;
 use futures::TryStreamExt;
+use std::any::type_name;
 use std::error::Error as StdError;
 use std::ops::Deref;
 
@@ -30,6 +53,11 @@ pub struct LoadedState<ID: EventId, S> {
 }
 
 impl<ID: EventId, S> LoadedState<ID, S> {
+    /// Creates a new `LoadedState` with the given state and version.
+    pub fn new(state: S, version: ID) -> Self {
+        Self { state, version }
+    }
+
     /// Returns a reference to the loaded state.
     pub fn state(&self) -> &S {
         &self.state
@@ -39,6 +67,11 @@ impl<ID: EventId, S> LoadedState<ID, S> {
     pub fn version(&self) -> ID {
         self.version
     }
+
+    /// Consumes the loaded state, returning the state.
+    pub fn into_state(self) -> S {
+        self.state
+    }
 }
 /// Trait to load a state.
 ///
@@ -60,6 +93,119 @@ pub trait LoadState<ID: EventId, S, E: Event + Clone> {
     async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError>;
 }
 
+/// Trait to load a state as it was at a past position of the event store.
+#[async_trait]
+pub trait LoadStateAt<ID: EventId, S, E: Event + Clone> {
+    /// Loads the state based on the provided state query, applying only the events up to `position`,
+    /// included.
+    ///
+    /// Snapshots are neither used nor stored, as they may contain events following `position`.
+    ///
+    /// # Parameters
+    ///
+    /// - `state_query`: The query object representing the desired state to hydrate.
+    /// - `position`: The ID of the last event to apply.
+    ///
+    /// # Returns
+    ///
+    /// the loaded state, or an error if the load fails.
+    async fn load_at(
+        &self,
+        state_query: S,
+        position: ID,
+    ) -> Result<LoadedState<ID, S>, BoxDynError>;
+}
+
+/// Hydrates state queries outside of a decision.
+///
+/// The `StateQuerier` loads the current state of any `StateQuery` from a state store, using the
+/// snapshots if the state store is configured with them. It is meant for read endpoints and diagnostics,
+/// where a decision would only be used to read the state.
+#[derive(Clone)]
+pub struct StateQuerier<SS> {
+    state_store: SS,
+}
+
+impl<SS> StateQuerier<SS> {
+    /// Creates a new instance of `StateQuerier`.
+    ///
+    /// # Parameters
+    ///
+    /// - `state_store`: The state store backend used to load the states.
+    pub fn new(state_store: SS) -> Self {
+        Self { state_store }
+    }
+
+    /// Loads the current state of the given state query, along with its version.
+    pub async fn query<ID, E, S>(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError>
+    where
+        ID: EventId,
+        E: Event + Clone,
+        SS: LoadState<ID, S, E>,
+    {
+        query_state(&self.state_store, state_query).await
+    }
+
+    /// Loads the state of the given state query as it was at `position`, applying only the events up
+    /// to `position`, included.
+    pub async fn query_at<ID, E, S>(
+        &self,
+        state_query: S,
+        position: ID,
+    ) -> Result<LoadedState<ID, S>, BoxDynError>
+    where
+        ID: EventId,
+        E: Event + Clone,
+        SS: LoadStateAt<ID, S, E>,
+    {
+        query_state_at(&self.state_store, state_query, position).await
+    }
+}
+
+/// Loads the current state of the given state query from the state store, along with its version.
+pub async fn query_state<ID, E, S, SS>(
+    state_store: &SS,
+    state_query: S,
+) -> Result<LoadedState<ID, S>, BoxDynError>
+where
+    ID: EventId,
+    E: Event + Clone,
+    SS: LoadState<ID, S, E>,
+{
+    state_store.load(state_query).await
+}
+
+/// Loads the state of the given state query as it was at `position`, applying only the events up to
+/// `position`, included.
+pub async fn query_state_at<ID, E, S, SS>(
+    state_store: &SS,
+    state_query: S,
+    position: ID,
+) -> Result<LoadedState<ID, S>, BoxDynError>
+where
+    ID: EventId,
+    E: Event + Clone,
+    SS: LoadStateAt<ID, S, E>,
+{
+    state_store.load_at(state_query, position).await
+}
+
+/// Checks whether any event of the given state query has ever been appended, without hydrating the state.
+///
+/// It is meant for checks such as "has this email already been registered", where the state would only
+/// record that a matching event occurred. The check is delegated to `EventStore::exists`.
+pub async fn exists<ID, E, ES, S>(event_store: &ES, state_query: &S) -> Result<bool, ES::Error>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Sync,
+    S: StateQuery,
+    <S as StateQuery>::Event: TryFrom<E> + 'static,
+    <<S as StateQuery>::Event as TryFrom<E>>::Error: StdError + 'static + Send + Sync,
+{
+    event_store.exists(&state_query.query()).await
+}
+
 /// A snapshotter.
 ///
 /// Snapshots optimize the retrieval of `StatePart` by storing and loading partial or complete
@@ -122,6 +268,13 @@ impl<ID: EventId, T: StateSnapshotter<ID> + Clone> Deref for WithSnapshot<ID, T>
 }
 
 /// Represents an event sourced decision state store. It loads and stores decision states from events in a event store.
+///
+/// The events are consumed one at a time from the event store stream while the state is mutated, so
+/// the memory used by the hydration does not grow with the number of events.
+///
+/// By default, a state made of multiple state queries is hydrated with a single query, the union of the
+/// queries of its parts. With `with_parallel_hydration`, each part is hydrated concurrently with its own
+/// query, which cuts the latency of decisions that join several entities.
 #[derive(Clone)]
 pub struct EventSourcedStateStore<ID, E, ES, SN>
 where
@@ -132,6 +285,8 @@ where
 {
     event_store: ES,
     snapshot: SN,
+    parallel_hydration: bool,
+    warnings: GrowthWarnings,
     event_id_type: std::marker::PhantomData<ID>,
     event_type: std::marker::PhantomData<E>,
 }
@@ -147,12 +302,68 @@ where
         EventSourcedStateStore {
             event_store,
             snapshot,
+            parallel_hydration: false,
+            warnings: GrowthWarnings::default(),
             event_id_type: std::marker::PhantomData,
             event_type: std::marker::PhantomData,
         }
     }
 
-    async fn mutate_state<S>(&self, mut state_query: S) -> Result<S, BoxDynError>
+    /// Hydrates the parts of a state concurrently, streaming the events of each part with its own query.
+    ///
+    /// Once the parts are hydrated, the union of their queries is streamed from the version of each part,
+    /// to apply the events appended while the parts were hydrated. This keeps the parts consistent
+    /// with the version of the loaded state, which is used to validate the decision.
+    pub fn with_parallel_hydration(mut self) -> Self {
+        self.parallel_hydration = true;
+        self
+    }
+
+    /// Raises a `GrowthWarning` when a hydration or a decision exceeds the thresholds of `warnings`.
+    pub fn with_growth_warnings(mut self, warnings: GrowthWarnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.hydrate",
+            skip_all,
+            fields(state, parallel = self.parallel_hydration)
+        )
+    )]
+    async fn hydrate_state<S>(
+        &self,
+        mut state_query: S,
+        state: &'static str,
+    ) -> Result<S, BoxDynError>
+    where
+        ES: EventStore<ID, E> + Clone + Sync + Send,
+        <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+        S: MultiState<ID, E> + MultiStateHydrate<ID, E, ES> + Send + Sync + 'static,
+        E: 'static,
+    {
+        let started_at = Instant::now();
+        state_query.window_all(&self.event_store).await?;
+        if self.parallel_hydration {
+            state_query.hydrate_all(&self.event_store).await?;
+        }
+        let (state_query, applied_events) = self.mutate_state(state_query).await?;
+        self.warnings
+            .check_hydration(state, applied_events, started_at.elapsed());
+        Ok(state_query)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.mutate",
+            skip_all,
+            fields(events = tracing::field::Empty)
+        )
+    )]
+    async fn mutate_state<S>(&self, mut state_query: S) -> Result<(S, u64), BoxDynError>
     where
         ES: EventStore<ID, E> + Clone + Sync + Send,
         <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
@@ -161,7 +372,41 @@ where
     {
         let query = state_query.query_all();
         let mut event_stream = self.event_store.stream(&query);
-        while let Some(event) = event_stream.try_next().await? {
+        let mut applied_events = 0;
+        while let Some(event) = event_stream
+            .try_next()
+            .await
+            .map_err(ClassifiedError::boxed)?
+        {
+            state_query.mutate_all(event);
+            applied_events += 1;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("events", applied_events);
+        Ok((state_query, applied_events))
+    }
+
+    async fn mutate_state_until<S>(
+        &self,
+        mut state_query: S,
+        position: ID,
+    ) -> Result<S, BoxDynError>
+    where
+        ES: EventStore<ID, E> + Clone + Sync + Send,
+        <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+        S: MultiState<ID, E> + Send + Sync + 'static,
+        E: 'static,
+    {
+        let query = state_query.query_all().until(position);
+        let mut event_stream = self.event_store.stream(&query);
+        while let Some(event) = event_stream
+            .try_next()
+            .await
+            .map_err(ClassifiedError::boxed)?
+        {
+            if event.id() > position {
+                break;
+            }
             state_query.mutate_all(event);
         }
         Ok(state_query)
@@ -169,7 +414,7 @@ where
 }
 
 #[async_trait]
-impl<ID, ES, E, S> LoadState<ID, S, E> for EventSourcedStateStore<ID, E, ES, NoSnapshot>
+impl<ID, ES, E, S, SN> LoadStateAt<ID, S, E> for EventSourcedStateStore<ID, E, ES, SN>
 where
     ES: EventStore<ID, E> + Clone + Sync + Send,
     <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
@@ -178,9 +423,44 @@ where
     S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S> + 'static,
     <S as IntoStatePart<ID, S>>::Target:
         Send + Sync + Serialize + DeserializeOwned + IntoState<S> + MultiState<ID, E>,
+    SN: SnapshotConfig + Clone + Send + Sync,
+{
+    async fn load_at(
+        &self,
+        state_query: S,
+        position: ID,
+    ) -> Result<LoadedState<ID, S>, BoxDynError> {
+        let mutated_state = self
+            .mutate_state_until(state_query.into_state_part(), position)
+            .await?;
+        let version = mutated_state.version();
+        Ok(LoadedState {
+            state: mutated_state.into_state(),
+            version,
+        })
+    }
+}
+
+#[async_trait]
+impl<ID, ES, E, S> LoadState<ID, S, E> for EventSourcedStateStore<ID, E, ES, NoSnapshot>
+where
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+    ID: EventId,
+    E: Event + Clone + Send + Sync + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S> + 'static,
+    <S as IntoStatePart<ID, S>>::Target: Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + IntoState<S>
+        + MultiState<ID, E>
+        + MultiStateHydrate<ID, E, ES>,
 {
     async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError> {
-        let mutated_state = self.mutate_state(state_query.into_state_part()).await?;
+        let mutated_state = self
+            .hydrate_state(state_query.into_state_part(), type_name::<S>())
+            .await?;
         let version = mutated_state.version();
         Ok(LoadedState {
             state: mutated_state.into_state(),
@@ -204,12 +484,13 @@ where
         + DeserializeOwned
         + IntoState<S>
         + MultiState<ID, E>
+        + MultiStateHydrate<ID, E, ES>
         + MultiStateSnapshot<ID, B>,
 {
     async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError> {
         let mut state_query = state_query.into_state_part();
         state_query.load_all(&self.snapshot.backend).await;
-        let state = self.mutate_state(state_query).await?;
+        let state = self.hydrate_state(state_query, type_name::<S>()).await?;
         state.store_all(&self.snapshot.backend).await?;
         let version = state.version();
         Ok(LoadedState {
@@ -231,25 +512,59 @@ where
         Send + Sync + Serialize + DeserializeOwned + IntoState<S> + MultiState<ID, E>,
     SC: SnapshotConfig + Clone + Send + Sync + 'static,
 {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "disintegrate.persist",
+            skip_all,
+            fields(events = events.len())
+        )
+    )]
     async fn persist(
         &self,
         loaded_state: LoadedState<ID, S>,
         events: Vec<E>,
         validation_query: Option<StreamQuery<ID, E>>,
     ) -> Result<Vec<PersistedEvent<ID, E>>, BoxDynError> {
+        self.warnings.check_append(events.len());
         let query =
             validation_query.unwrap_or_else(|| loaded_state.state.into_state_part().query_all());
         Ok(self
             .event_store
             .append(events, query, loaded_state.version)
-            .await?)
+            .await
+            .map_err(ClassifiedError::boxed)?)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{utils::tests::*, IntoStatePart};
+    use crate::{query, utils::tests::*, HydrationWindow, IntoStatePart, StateMutate};
+
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+    struct RecentItems {
+        cart_id: String,
+        items: Vec<String>,
+    }
+
+    impl StateQuery for RecentItems {
+        const NAME: &'static str = "RecentItems";
+        const WINDOW: Option<HydrationWindow> = Some(HydrationWindow::LastEvents(2));
+        type Event = ShoppingCartEvent;
+
+        fn query<ID: EventId>(&self) -> StreamQuery<ID, Self::Event> {
+            query!(ShoppingCartEvent; cart_id == self.cart_id.clone())
+        }
+    }
+
+    impl StateMutate for RecentItems {
+        fn mutate(&mut self, event: Self::Event) {
+            if let ShoppingCartEvent::ItemAdded { item_id, .. } = event {
+                self.items.push(item_id);
+            }
+        }
+    }
 
     #[tokio::test]
     async fn it_loads_query_state() {
@@ -276,6 +591,175 @@ mod test {
         assert_eq!(cart2, cart("c2", ["p3".to_owned()]));
     }
 
+    #[tokio::test]
+    async fn it_warns_about_the_large_hydrations() {
+        let mut mock_store = MockDatabase::new();
+        mock_store.expect_stream().once().return_once(|_| {
+            event_stream([
+                item_added_event("p1", "c1"),
+                item_added_event("p2", "c1"),
+                item_added_event("p3", "c1"),
+            ])
+        });
+        let warnings = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let hook_warnings = warnings.clone();
+
+        let event_store = MockEventStore::new(mock_store);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot)
+            .with_growth_warnings(
+                GrowthWarnings::default()
+                    .hydration_events(2)
+                    .on_warning(move |warning| hook_warnings.lock().unwrap().push(warning.clone())),
+            );
+        state_store.load(cart("c1", [])).await.unwrap();
+
+        let warnings = warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            warnings[0],
+            GrowthWarning::LargeHydration { events: 3, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_hydrates_the_state_parts_in_parallel() {
+        let mut mock_store = MockDatabase::new();
+        let mut sequence = mockall::Sequence::new();
+
+        mock_store
+            .expect_stream()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_once(|_| vec![Ok(PersistedEvent::new(1, item_added_event("p1", "c1")))]);
+        mock_store
+            .expect_stream()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_once(|_| vec![Ok(PersistedEvent::new(2, item_added_event("p2", "c2")))]);
+        mock_store
+            .expect_stream()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_once(|_| vec![Ok(PersistedEvent::new(3, item_added_event("p3", "c1")))]);
+
+        let event_store = MockEventStore::new(mock_store);
+        let state_store =
+            EventSourcedStateStore::new(event_store, NoSnapshot).with_parallel_hydration();
+        let state = (cart("c1", []), cart("c2", []));
+        let LoadedState {
+            state: (cart1, cart2),
+            version,
+        } = state_store.load(state).await.unwrap();
+
+        assert_eq!(version, 3);
+        assert_eq!(cart1, cart("c1", ["p1".to_owned(), "p3".to_owned()]));
+        assert_eq!(cart2, cart("c2", ["p2".to_owned()]));
+    }
+
+    #[tokio::test]
+    async fn it_hydrates_only_the_window_of_a_state_query() {
+        let mut mock_store = MockDatabase::new();
+        let mut sequence = mockall::Sequence::new();
+
+        mock_store
+            .expect_stream()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_once(|_| {
+                event_stream([
+                    item_added_event("p1", "c1"),
+                    item_added_event("p2", "c1"),
+                    item_added_event("p3", "c1"),
+                    item_added_event("p4", "c1"),
+                ])
+            });
+        mock_store
+            .expect_stream()
+            .once()
+            .in_sequence(&mut sequence)
+            .withf(|query: &StreamQuery<i64, ShoppingCartEvent>| {
+                query.filters().iter().all(|filter| filter.origin() == 2)
+            })
+            .return_once(|_| {
+                vec![
+                    Ok(PersistedEvent::new(3, item_added_event("p3", "c1"))),
+                    Ok(PersistedEvent::new(4, item_added_event("p4", "c1"))),
+                ]
+            });
+
+        let event_store = MockEventStore::new(mock_store);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let loaded_state = state_store
+            .load(RecentItems {
+                cart_id: "c1".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(loaded_state.version(), 4);
+        assert_eq!(loaded_state.state().items, vec!["p3", "p4"]);
+    }
+
+    #[tokio::test]
+    async fn it_queries_the_state_outside_of_a_decision() {
+        let mut mock_store = MockDatabase::new();
+
+        mock_store
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+
+        let event_store = MockEventStore::new(mock_store);
+        let querier = StateQuerier::new(EventSourcedStateStore::new(event_store, NoSnapshot));
+        let loaded_state = querier.query(cart("c1", [])).await.unwrap();
+
+        assert_eq!(loaded_state.version(), 1);
+        assert_eq!(loaded_state.into_state(), cart("c1", ["p1".to_owned()]));
+    }
+
+    #[tokio::test]
+    async fn it_queries_the_state_at_a_past_position() {
+        let mut mock_store = MockDatabase::new();
+
+        mock_store.expect_stream().once().return_once(|_| {
+            event_stream([
+                item_added_event("p1", "c1"),
+                item_added_event("p2", "c1"),
+                item_added_event("p3", "c1"),
+            ])
+        });
+
+        let event_store = MockEventStore::new(mock_store);
+        let querier = StateQuerier::new(EventSourcedStateStore::new(event_store, NoSnapshot));
+        let loaded_state = querier.query_at(cart("c1", []), 2).await.unwrap();
+
+        assert_eq!(loaded_state.version(), 2);
+        assert_eq!(
+            loaded_state.into_state(),
+            cart("c1", ["p1".to_owned(), "p2".to_owned()])
+        );
+    }
+
+    #[tokio::test]
+    async fn it_checks_whether_the_events_of_a_state_query_exist() {
+        let mut mock_store = MockDatabase::new();
+
+        mock_store
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+        mock_store
+            .expect_stream()
+            .once()
+            .return_once(|_: &StreamQuery<i64, ShoppingCartEvent>| event_stream([]));
+
+        let event_store = MockEventStore::new(mock_store);
+
+        assert!(exists(&event_store, &Cart::new("c1")).await.unwrap());
+        assert!(!exists(&event_store, &Cart::new("c2")).await.unwrap());
+    }
+
     #[tokio::test]
     async fn it_persists_decision_changes() {
         let mut mock_store = MockDatabase::new();
@@ -0,0 +1,154 @@
+//! A decision state store that loads the states from a repository outside of the event store.
+use std::error::Error as StdError;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{LoadState, LoadedState};
+use crate::decision::PersistDecision;
+use crate::state::MultiState;
+use crate::EventId;
+use crate::{
+    BoxDynError, ClassifiedError, Event, EventStore, IntoState, IntoStatePart, PersistedEvent,
+    StreamQuery,
+};
+
+/// A repository of states kept outside of the event store, such as the tables of a read model.
+#[async_trait]
+pub trait StateRepository<ID: EventId, S> {
+    /// Loads the state of the given state query, along with the ID of the last event it reflects.
+    async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError>;
+}
+
+/// Represents a decision state store that loads the states from a `StateRepository` and appends the
+/// events of the decisions to the event store.
+///
+/// The version returned by the repository is used to detect conflicts, so a decision fails with a
+/// concurrency error if an event matching the query of its state was appended after the last event
+/// reflected by the repository. A repository updated asynchronously, such as a projection, should
+/// therefore be up to date before the decisions that depend on it are retried.
+#[derive(Clone)]
+pub struct ExternalStateStore<ID, E, ES, R>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+{
+    event_store: ES,
+    repository: R,
+    event_id_type: std::marker::PhantomData<ID>,
+    event_type: std::marker::PhantomData<E>,
+}
+
+impl<ID, E, ES, R> ExternalStateStore<ID, E, ES, R>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+{
+    pub fn new(event_store: ES, repository: R) -> Self {
+        ExternalStateStore {
+            event_store,
+            repository,
+            event_id_type: std::marker::PhantomData,
+            event_type: std::marker::PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<ID, ES, E, S, R> LoadState<ID, S, E> for ExternalStateStore<ID, E, ES, R>
+where
+    ID: EventId,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    E: Event + Clone + Send + Sync + 'static,
+    S: Send + 'static,
+    R: StateRepository<ID, S> + Send + Sync,
+{
+    async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError> {
+        self.repository.load(state_query).await
+    }
+}
+
+#[async_trait]
+impl<ID, ES, E, S, R> PersistDecision<ID, S, E> for ExternalStateStore<ID, E, ES, R>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync + 'static,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S> + 'static,
+    <S as IntoStatePart<ID, S>>::Target:
+        Send + Sync + Serialize + DeserializeOwned + IntoState<S> + MultiState<ID, E>,
+    R: Send + Sync,
+{
+    async fn persist(
+        &self,
+        loaded_state: LoadedState<ID, S>,
+        events: Vec<E>,
+        validation_query: Option<StreamQuery<ID, E>>,
+    ) -> Result<Vec<PersistedEvent<ID, E>>, BoxDynError> {
+        let query =
+            validation_query.unwrap_or_else(|| loaded_state.state.into_state_part().query_all());
+        Ok(self
+            .event_store
+            .append(events, query, loaded_state.version)
+            .await
+            .map_err(ClassifiedError::boxed)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::tests::*;
+    use crate::{DecisionMaker, StateQuery};
+    use mockall::predicate::eq;
+
+    struct CartRepository;
+
+    #[async_trait]
+    impl StateRepository<i64, Cart> for CartRepository {
+        async fn load(&self, state_query: Cart) -> Result<LoadedState<i64, Cart>, BoxDynError> {
+            Ok(LoadedState::new(
+                cart(&state_query.cart_id, ["p1".to_owned()]),
+                7,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_makes_decisions_on_the_external_state() {
+        let mut mock_store = MockDatabase::new();
+        mock_store
+            .expect_append()
+            .with(
+                eq(vec![item_added_event("p2", "c1")]),
+                eq(cart("c1", []).query().change_origin(0)),
+                eq(7),
+            )
+            .once()
+            .return_once(|_, _, _| vec![PersistedEvent::new(8, item_added_event("p2", "c1"))]);
+
+        let mut add_item = MockDecision::new();
+        add_item
+            .expect_state_query()
+            .once()
+            .return_once(|| cart("c1", []));
+        add_item
+            .expect_validation_query()
+            .once()
+            .return_once(|| Option::<StreamQuery<i64, ShoppingCartEvent>>::None);
+        add_item
+            .expect_process()
+            .once()
+            .withf(|state: &Cart| state.items == ["p1".to_owned()])
+            .return_once(|_| Ok(vec![item_added_event("p2", "c1")]));
+
+        let event_store = MockEventStore::new(mock_store);
+        let decision_maker =
+            DecisionMaker::new(ExternalStateStore::new(event_store, CartRepository));
+
+        decision_maker.make(add_item).await.unwrap();
+    }
+}
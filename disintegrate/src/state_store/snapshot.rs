@@ -0,0 +1,173 @@
+//! A decision state store that loads the states only from their snapshots.
+use std::error::Error as StdError;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{LoadState, LoadedState, StateSnapshotter};
+use crate::decision::PersistDecision;
+use crate::state::{MultiState, MultiStateSnapshot};
+use crate::EventId;
+use crate::{
+    BoxDynError, ClassifiedError, Event, EventStore, IntoState, IntoStatePart, PersistedEvent,
+    StreamQuery,
+};
+
+/// Represents a decision state store that keeps the states in their snapshots.
+///
+/// The state is loaded from the snapshots, without replaying the events of the event store. The events
+/// of a decision are appended with the usual conflict detection, then they are applied to the loaded
+/// state and the resulting snapshots are stored. States without a snapshot start from their default.
+///
+/// The snapshots are the source of truth of the states, so the events of the state queries must only be
+/// appended by this store and the snapshotter must take a snapshot at every change, for example a
+/// `Snapshotter` created with `every` set to 0. An event appended by other means makes the decisions of
+/// the affected states fail with a concurrency error until their snapshots are rebuilt.
+#[derive(Clone)]
+pub struct SnapshotStateStore<ID, E, ES, B>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    B: StateSnapshotter<ID> + Clone,
+{
+    event_store: ES,
+    snapshotter: B,
+    event_id_type: std::marker::PhantomData<ID>,
+    event_type: std::marker::PhantomData<E>,
+}
+
+impl<ID, E, ES, B> SnapshotStateStore<ID, E, ES, B>
+where
+    ID: EventId,
+    E: Event + Clone + Send + Sync,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    B: StateSnapshotter<ID> + Clone,
+{
+    pub fn new(event_store: ES, snapshotter: B) -> Self {
+        SnapshotStateStore {
+            event_store,
+            snapshotter,
+            event_id_type: std::marker::PhantomData,
+            event_type: std::marker::PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<ID, ES, E, S, B> LoadState<ID, S, E> for SnapshotStateStore<ID, E, ES, B>
+where
+    ID: EventId,
+    B: StateSnapshotter<ID> + Send + Sync + Clone,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    E: Event + Clone + Send + Sync + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S> + 'static,
+    <S as IntoStatePart<ID, S>>::Target: Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + IntoState<S>
+        + MultiState<ID, E>
+        + MultiStateSnapshot<ID, B>,
+{
+    async fn load(&self, state_query: S) -> Result<LoadedState<ID, S>, BoxDynError> {
+        let mut state = state_query.into_state_part();
+        state.load_all(&self.snapshotter).await;
+        let version = state.version();
+        Ok(LoadedState {
+            state: state.into_state(),
+            version,
+        })
+    }
+}
+
+#[async_trait]
+impl<ID, ES, E, S, B> PersistDecision<ID, S, E> for SnapshotStateStore<ID, E, ES, B>
+where
+    ID: EventId,
+    B: StateSnapshotter<ID> + Send + Sync + Clone,
+    E: Event + Clone + Send + Sync + 'static,
+    ES: EventStore<ID, E> + Clone + Sync + Send,
+    <ES as EventStore<ID, E>>::Error: StdError + Send + Sync + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S> + 'static,
+    <S as IntoStatePart<ID, S>>::Target: Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + IntoState<S>
+        + MultiState<ID, E>
+        + MultiStateSnapshot<ID, B>,
+{
+    async fn persist(
+        &self,
+        loaded_state: LoadedState<ID, S>,
+        events: Vec<E>,
+        validation_query: Option<StreamQuery<ID, E>>,
+    ) -> Result<Vec<PersistedEvent<ID, E>>, BoxDynError> {
+        let mut state = loaded_state.state.into_state_part();
+        let query = validation_query.unwrap_or_else(|| state.query_all());
+        let persisted_events = self
+            .event_store
+            .append(events, query, loaded_state.version)
+            .await
+            .map_err(ClassifiedError::boxed)?;
+        for event in persisted_events.iter().cloned() {
+            state.mutate_all(event);
+        }
+        state.store_all(&self.snapshotter).await?;
+        Ok(persisted_events)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{utils::tests::*, StatePart};
+
+    #[tokio::test]
+    async fn it_loads_the_state_from_the_snapshot() {
+        let mut snapshotter = MockStateSnapshotter::new();
+        snapshotter
+            .expect_load_snapshot()
+            .once()
+            .returning(|_: StatePart<i64, Cart>| StatePart::new(5, cart("c1", ["p1".to_owned()])));
+
+        let event_store = MockEventStore::new(MockDatabase::new());
+        let state_store = SnapshotStateStore::new(event_store, snapshotter);
+        let LoadedState { state, version } = state_store.load(cart("c1", [])).await.unwrap();
+
+        assert_eq!(version, 5);
+        assert_eq!(state, cart("c1", ["p1".to_owned()]));
+    }
+
+    #[tokio::test]
+    async fn it_stores_the_snapshot_of_the_decision_changes() {
+        let mut mock_store = MockDatabase::new();
+        mock_store.expect_append().once().return_once(
+            |_, _: StreamQuery<i64, ShoppingCartEvent>, last_event_id| {
+                assert_eq!(last_event_id, 5);
+                vec![PersistedEvent::new(6, item_added_event("p2", "c1"))]
+            },
+        );
+
+        let mut snapshotter = MockStateSnapshotter::new();
+        snapshotter
+            .expect_store_snapshot()
+            .once()
+            .withf(|state: &StatePart<i64, Cart>| {
+                state.version() == 6 && state.items == ["p1".to_owned(), "p2".to_owned()]
+            })
+            .returning(|_| Ok(()));
+
+        let event_store = MockEventStore::new(mock_store);
+        let state_store = SnapshotStateStore::new(event_store, snapshotter);
+        let loaded_state = LoadedState {
+            state: cart("c1", ["p1".to_owned()]),
+            version: 5,
+        };
+        state_store
+            .persist(loaded_state, vec![item_added_event("p2", "c1")], None)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! Warnings raised when the hydrations or the appends of a state store grow beyond a threshold.
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+type WarningHook = Arc<dyn Fn(&GrowthWarning) + Send + Sync>;
+
+/// A warning about a hydration or an append that exceeded a threshold of `GrowthWarnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrowthWarning {
+    /// A hydration applied more events than the threshold.
+    LargeHydration {
+        /// The type of the hydrated state query.
+        state: &'static str,
+        /// The number of events applied by the hydration.
+        events: u64,
+    },
+    /// A hydration took longer than the threshold.
+    SlowHydration {
+        /// The type of the hydrated state query.
+        state: &'static str,
+        /// The time spent hydrating the state.
+        elapsed: Duration,
+    },
+    /// A decision appended more events than the threshold.
+    LargeAppend {
+        /// The number of events appended by the decision.
+        events: usize,
+    },
+}
+
+impl fmt::Display for GrowthWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LargeHydration { state, events } => {
+                write!(f, "the hydration of {state} applied {events} events")
+            }
+            Self::SlowHydration { state, elapsed } => {
+                write!(f, "the hydration of {state} took {elapsed:?}")
+            }
+            Self::LargeAppend { events } => write!(f, "a decision appended {events} events"),
+        }
+    }
+}
+
+/// The thresholds beyond which a state store raises a `GrowthWarning`.
+///
+/// A warning is passed to the hook, if any, and logged as a `tracing` warning with the `tracing`
+/// feature. The thresholds are disabled by default.
+///
+/// # Example
+///
+/// ```rust
+/// use disintegrate::GrowthWarnings;
+/// use std::time::Duration;
+///
+/// let warnings = GrowthWarnings::default()
+///     .hydration_events(10_000)
+///     .hydration_time(Duration::from_millis(500))
+///     .append_events(100)
+///     .on_warning(|warning| eprintln!("{warning}"));
+/// ```
+#[derive(Clone, Default)]
+pub struct GrowthWarnings {
+    hydration_events: Option<u64>,
+    hydration_time: Option<Duration>,
+    append_events: Option<usize>,
+    hook: Option<WarningHook>,
+}
+
+impl GrowthWarnings {
+    /// Warns when a hydration applies more than the given number of events.
+    pub fn hydration_events(mut self, events: u64) -> Self {
+        self.hydration_events = Some(events);
+        self
+    }
+
+    /// Warns when a hydration takes longer than the given duration.
+    pub fn hydration_time(mut self, elapsed: Duration) -> Self {
+        self.hydration_time = Some(elapsed);
+        self
+    }
+
+    /// Warns when a decision appends more than the given number of events.
+    pub fn append_events(mut self, events: usize) -> Self {
+        self.append_events = Some(events);
+        self
+    }
+
+    /// Sets the hook called with each warning.
+    pub fn on_warning(mut self, hook: impl Fn(&GrowthWarning) + Send + Sync + 'static) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn check_hydration(&self, state: &'static str, events: u64, elapsed: Duration) {
+        if self
+            .hydration_events
+            .is_some_and(|threshold| events > threshold)
+        {
+            self.report(GrowthWarning::LargeHydration { state, events });
+        }
+        if self
+            .hydration_time
+            .is_some_and(|threshold| elapsed > threshold)
+        {
+            self.report(GrowthWarning::SlowHydration { state, elapsed });
+        }
+    }
+
+    pub(crate) fn check_append(&self, events: usize) {
+        if self
+            .append_events
+            .is_some_and(|threshold| events > threshold)
+        {
+            self.report(GrowthWarning::LargeAppend { events });
+        }
+    }
+
+    fn report(&self, warning: GrowthWarning) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%warning, "disintegrate growth threshold exceeded");
+        if let Some(hook) = &self.hook {
+            hook(&warning);
+        }
+    }
+}
+
+impl fmt::Debug for GrowthWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrowthWarnings")
+            .field("hydration_events", &self.hydration_events)
+            .field("hydration_time", &self.hydration_time)
+            .field("append_events", &self.append_events)
+            .finish_non_exhaustive()
+    }
+}
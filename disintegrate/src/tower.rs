@@ -0,0 +1,140 @@
+//! A [Tower](https://docs.rs/tower) service making decisions.
+//!
+//! `DecisionService` implements `Service` for every decision the `DecisionMaker` can make, so the middleware of
+//! Tower, such as timeouts, rate limits, concurrency limits and load shedding, wraps the decisions like any other
+//! request.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let service = ServiceBuilder::new()
+//!     .load_shed()
+//!     .concurrency_limit(64)
+//!     .timeout(Duration::from_secs(5))
+//!     .service(decision_maker.into_service::<PgEventId>());
+//!
+//! let events = service.oneshot(WithdrawAmount::new(id, amount)).await?;
+//! ```
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use ::tower::Service;
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{
+    Decision, DecisionError, DecisionMaker, Event, EventId, IntoState, IntoStatePart, LoadState,
+    MultiState, PersistDecision, PersistedEvent,
+};
+
+/// A Tower service making the decisions it receives with a `DecisionMaker`.
+///
+/// The service is always ready and can be cloned cheaply, each call making its decision concurrently. It responds
+/// with the events appended by the decision, and fails with the `DecisionError` of the decision.
+pub struct DecisionService<SS, ID> {
+    decision_maker: Arc<DecisionMaker<SS>>,
+    event_id: PhantomData<fn() -> ID>,
+}
+
+impl<SS, ID> Clone for DecisionService<SS, ID> {
+    fn clone(&self) -> Self {
+        Self {
+            decision_maker: self.decision_maker.clone(),
+            event_id: PhantomData,
+        }
+    }
+}
+
+impl<SS, ID> DecisionService<SS, ID> {
+    /// Creates a service making the decisions with the `DecisionMaker`.
+    pub fn new(decision_maker: DecisionMaker<SS>) -> Self {
+        Self {
+            decision_maker: Arc::new(decision_maker),
+            event_id: PhantomData,
+        }
+    }
+}
+
+impl<SS> DecisionMaker<SS> {
+    /// Turns the `DecisionMaker` into a Tower service appending events identified by `ID`.
+    pub fn into_service<ID>(self) -> DecisionService<SS, ID> {
+        DecisionService::new(self)
+    }
+}
+
+impl<SS, ID, D, E> Service<D> for DecisionService<SS, ID>
+where
+    D: Decision<Event = E> + Send + 'static,
+    D::Error: 'static,
+    SS: LoadState<ID, D::StateQuery, E>
+        + PersistDecision<ID, D::StateQuery, E>
+        + Send
+        + Sync
+        + 'static,
+    ID: EventId,
+    E: Event + Clone + Sync + Send + 'static,
+    D::StateQuery: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, D::StateQuery>,
+    <D::StateQuery as IntoStatePart<ID, D::StateQuery>>::Target:
+        Send + Sync + Serialize + DeserializeOwned + IntoState<D::StateQuery> + MultiState<ID, E>,
+{
+    type Response = Vec<PersistedEvent<ID, E>>;
+    type Error = DecisionError<D::Error>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, decision: D) -> Self::Future {
+        let decision_maker = self.decision_maker.clone();
+        Box::pin(async move { decision_maker.make(decision).await })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::tower::ServiceExt;
+
+    use super::*;
+    use crate::{utils::tests::*, EventSourcedStateStore, NoSnapshot, StreamQuery};
+
+    #[tokio::test]
+    async fn it_makes_the_decisions_it_receives() {
+        let mut database = MockDatabase::new();
+        database
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+        database
+            .expect_append()
+            .once()
+            .return_once(|_, _, _| vec![PersistedEvent::new(2, item_added_event("p2", "c1"))]);
+
+        let mut mock_add_item = MockDecision::new();
+        mock_add_item
+            .expect_state_query()
+            .once()
+            .return_once(|| cart("c1", []));
+        mock_add_item
+            .expect_validation_query()
+            .once()
+            .return_once(|| Option::<StreamQuery<i64, ShoppingCartEvent>>::None);
+        mock_add_item
+            .expect_process()
+            .once()
+            .return_once(|_| Ok(vec![item_added_event("p2", "c1")]));
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let service = DecisionMaker::new(state_store).into_service::<i64>();
+
+        let events = service.oneshot(mock_add_item).await.unwrap();
+
+        assert_eq!(
+            events,
+            vec![PersistedEvent::new(2, item_added_event("p2", "c1"))]
+        );
+    }
+}
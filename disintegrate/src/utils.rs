@@ -1,224 +1,31 @@
 #![doc(hidden)]
 
-#[macro_export]
-#[doc(hidden)]
-macro_rules! const_slice_unique {
-    ($ty:ty, $a:expr, $compare:stmt) => {
-        &{
-            $compare
-            const A: &[$ty] = $crate::const_slice_sort!($ty, $a, $compare);
-            const DUPLICATES: usize = $crate::const_count_dup!(A, $compare);
-            const LEN: usize = A.len() - DUPLICATES;
-
-            let mut out: [_; LEN] = if LEN == 0 {
-                unsafe { std::mem::transmute([0u8; std::mem::size_of::<$ty>() * LEN]) }
-            } else {
-                [A[0]; LEN]
-            };
-
-            let mut r: usize = 1;
-            let mut w: usize = 1;
-            while r < A.len() {
-                if compare(A[r], out[w - 1]) != 0 {
-                    out[w] = A[r];
-                    w += 1;
-                }
-                r += 1;
-            }
-            out
-        }
-    };
-}
-
-#[macro_export]
-#[doc(hidden)]
-macro_rules! const_count_dup {
-    ($a:expr, $compare:stmt) => {{
-        $compare
-        let mut count = 0;
-        let mut i = 0;
-        let mut j = 1;
-        while i < $a.len() {
-            while j < $a.len() {
-                if compare($a[i], $a[j]) == 0 {
-                    count += 1;
-                    break;
-                }
-                j += 1;
-            }
-            i += 1;
-            j = i + 1;
-        }
-        count
-    }};
-}
-
-#[macro_export]
-#[doc(hidden)]
-macro_rules! const_slices_concat {
-    ($ty:ty, $a:expr, $b:expr) => {
-        &{
-            const A: &[$ty] = $a;
-            const B: &[$ty] = $b;
-            let mut out: [_; { A.len() + B.len() }] = if A.len() == 0 && B.len() == 0 {
-                unsafe {
-                    std::mem::transmute([0u8; std::mem::size_of::<$ty>() * (A.len() + B.len())])
-                }
-            } else if A.len() == 0 {
-                [B[0]; { A.len() + B.len() }]
-            } else {
-                [A[0]; { A.len() + B.len() }]
-            };
-            let mut i = 0;
-            while i < A.len() {
-                out[i] = A[i];
-                i += 1;
-            }
-            i = 0;
-            while i < B.len() {
-                out[i + A.len()] = B[i];
-                i += 1;
-            }
-            out
-        }
-    };
-}
-
-#[macro_export]
-#[doc(hidden)]
-macro_rules! const_slice_sort {
-    ($ty:ty, $a:expr, $compare:stmt) => {
-        &{
-            $compare
-            const A: &[$ty] = $a;
-            let mut out: [_; A.len()] = if A.len() == 0 {
-                unsafe { std::mem::transmute([0u8; std::mem::size_of::<$ty>() * A.len()]) }
-            } else {
-                [A[0]; A.len()]
-            };
-
-            let mut i = 1;
-            while i < A.len() {
-                out[i] = A[i];
-                let mut j = i;
-                while j > 0 && compare(out[j], out[j - 1]) == -1 {
-                    //swap
-                    let tmp = out[j];
-                    out[j] = out[j - 1];
-                    out[j - 1] = tmp;
-
-                    j -= 1;
-                }
-                i += 1;
-            }
-            out
-        }
-    };
-}
-
-#[macro_export]
-#[doc(hidden)]
-macro_rules! const_slice_iter {
-    ($slice:ident, $map:stmt) => {{
-        $map
-        let mut out: [_; $slice.len()] = if $slice.len() == 0 {
-            #[allow(clippy::missing_transmute_annotations)]
-            unsafe { std::mem::transmute([0u8; std::mem::size_of::<&str>() * $slice.len()]) }
-        } else {
-            [""; $slice.len()]
-        };
-        let mut i = 0;
-        while i < $slice.len() {
-            out[i] = map($slice[i]);
-            i += 1;
-        }
-        out
-    }};
-}
-
-pub const fn include(a: &[&str], b: &[&str]) -> bool {
-    let mut i = 0;
-    let mut j = 0;
-
-    while i < a.len() && j < b.len() {
-        if eq(a[i], b[j]) {
-            j += 1;
-            i = 0;
-        } else {
-            i += 1;
-        }
-    }
-
-    j == b.len()
-}
-
-pub const fn compare(lhs: &str, rhs: &str) -> i8 {
-    let lhs = lhs.as_bytes();
-    let rhs = rhs.as_bytes();
-    let lhs_len = lhs.len();
-    let rhs_len = rhs.len();
-    let min_len = if lhs_len < rhs_len { lhs_len } else { rhs_len };
-
-    let mut i = 0;
-    while i < min_len {
-        if lhs[i] < rhs[i] {
-            return -1;
-        }
-        if lhs[i] > rhs[i] {
-            return 1;
-        }
-        i += 1;
-    }
-
-    if lhs_len < rhs_len {
-        -1
-    } else if lhs_len > rhs_len {
-        1
-    } else {
-        0
-    }
-}
-
-pub const fn eq(lhs: &str, rhs: &str) -> bool {
-    let lhs = lhs.as_bytes();
-    let rhs = rhs.as_bytes();
-    let lhs_len = lhs.len();
-    let rhs_len = rhs.len();
-
-    if lhs_len != rhs_len {
-        return false;
-    }
-
-    let mut i = 0;
-    while i < lhs_len {
-        if lhs[i] != rhs[i] {
-            return false;
-        }
-        i += 1;
-    }
-
-    true
-}
+pub use disintegrate_core::utils::*;
+pub use paste::paste;
+pub use serde::{de::DeserializeOwned, Serialize};
 
 #[cfg(test)]
 pub mod tests {
-    use crate::event::EventId;
+    #[cfg(feature = "store")]
     use async_trait::async_trait;
+    #[cfg(feature = "store")]
     use futures::{
         stream::{self, BoxStream},
         StreamExt,
     };
     use mockall::mock;
-    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    #[cfg(feature = "store")]
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
     use std::{error::Error as StdError, fmt};
 
     use crate::{
-        domain_identifiers,
-        event::{DomainIdentifierInfo, EventInfo},
-        ident, query, BoxDynError, Decision, DomainIdentifierSet, Event, EventSchema, EventStore,
-        IdentifierType, PersistedEvent, StateMutate, StatePart, StateQuery, StateSnapshotter,
-        StreamQuery,
+        domain_identifiers, ident, query, Classify, Decision, DomainIdentifierInfo,
+        DomainIdentifierSet, ErrorKind, Event, EventId, EventInfo, EventSchema, IdentifierType,
+        PersistedEvent, StateMutate, StateQuery, StreamQuery,
     };
+    #[cfg(feature = "store")]
+    use crate::{BoxDynError, EventStore, StatePart, StateSnapshotter};
 
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(tag = "event_type", rename_all = "snake_case")]
@@ -292,12 +99,22 @@ pub mod tests {
                 } => domain_identifiers! {item_id: item_id, cart_id: cart_id},
             }
         }
+        fn payload_fields(&self) -> DomainIdentifierSet {
+            match self {
+                ShoppingCartEvent::ItemAdded { item_id, .. } => {
+                    domain_identifiers! {item_id: item_id}
+                }
+                ShoppingCartEvent::ItemRemoved { .. } => domain_identifiers! {},
+            }
+        }
     }
 
+    #[cfg(feature = "store")]
     #[derive(Clone)]
     pub struct MockEventStore<D> {
         pub database: D,
     }
+    #[cfg(feature = "store")]
     impl<D> MockEventStore<D> {
         pub fn new(database: D) -> Self {
             Self { database }
@@ -312,6 +129,11 @@ pub mod tests {
             write!(f, "test error")
         }
     }
+    impl Classify for Error {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Io
+        }
+    }
 
     pub trait Database {
         fn stream<QE: Event + Clone + 'static + Send + Sync>(
@@ -353,6 +175,7 @@ pub mod tests {
         }
     }
 
+    #[cfg(feature = "store")]
     #[async_trait]
     impl<D: Database + Sync> EventStore<i64, ShoppingCartEvent> for MockEventStore<D> {
         type Error = Error;
@@ -455,6 +278,7 @@ pub mod tests {
         }
     }
 
+    #[cfg(feature = "store")]
     mock! {
             pub StateSnapshotter{}
             #[async_trait]
@@ -0,0 +1,374 @@
+//! Bridge to the workflow engines, such as Temporal.
+//!
+//! A `DecisionActivity` makes a decision as an activity of a workflow, and a `SignalListener` raises the signals
+//! of the workflows from the appended events. The event store stays the source of truth, while the engine
+//! orchestrates the long-running processes. The engines deliver the activities and the signals at least once, so
+//! both carry an `IdempotencyToken`:
+//!
+//! * The activities are identified by the token of the engine, e.g. the workflow ID and the activity ID, so a
+//!   retried activity returns the events appended by its first completion instead of making the decision again.
+//! * The signals are identified by the listener and the event they come from, so the engine can discard the
+//!   signals delivered again by the listener.
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    BoxDynError, Decision, DecisionError, DecisionMaker, Event, EventId, EventListener, IntoState,
+    IntoStatePart, LoadState, MultiState, PersistDecision, PersistedEvent, StreamQuery,
+};
+
+/// Identifies an operation delivered at least once, so its repetitions can be detected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdempotencyToken(String);
+
+impl IdempotencyToken {
+    /// Creates a token.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// Returns the token as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for IdempotencyToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Records the activities that completed, along with the IDs of the events they appended.
+#[async_trait]
+pub trait IdempotencyStore<ID: EventId>: Send + Sync {
+    /// Returns the IDs of the events appended by the activity, if it already completed.
+    async fn completed(&self, token: &IdempotencyToken) -> Result<Option<Vec<ID>>, BoxDynError>;
+
+    /// Records the completion of the activity.
+    async fn complete(&self, token: &IdempotencyToken, event_ids: &[ID])
+        -> Result<(), BoxDynError>;
+}
+
+/// An `IdempotencyStore` keeping the completed activities in memory.
+///
+/// The completions are lost on restart, so it only suits the tests and the single-instance deployments.
+#[derive(Debug, Default)]
+pub struct InMemoryIdempotencyStore<ID> {
+    completed: Mutex<HashMap<IdempotencyToken, Vec<ID>>>,
+}
+
+impl<ID> InMemoryIdempotencyStore<ID> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            completed: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl<ID: EventId> IdempotencyStore<ID> for InMemoryIdempotencyStore<ID> {
+    async fn completed(&self, token: &IdempotencyToken) -> Result<Option<Vec<ID>>, BoxDynError> {
+        Ok(self.completed.lock().unwrap().get(token).cloned())
+    }
+
+    async fn complete(
+        &self,
+        token: &IdempotencyToken,
+        event_ids: &[ID],
+    ) -> Result<(), BoxDynError> {
+        self.completed
+            .lock()
+            .unwrap()
+            .insert(token.clone(), event_ids.to_vec());
+        Ok(())
+    }
+}
+
+/// Makes decisions as the activities of a workflow.
+///
+/// The activity returns the IDs of the appended events, and its errors tell the engine whether it can be retried
+/// through `Classify::is_retryable`. A completed activity is not made again when the engine retries it, e.g.
+/// because the worker crashed before reporting the completion. The completion is recorded after the events are
+/// appended, so a crash in between makes the decision again on retry, which the validation of the decision is
+/// expected to reject.
+pub struct DecisionActivity<SS, IS> {
+    decision_maker: DecisionMaker<SS>,
+    idempotency_store: IS,
+}
+
+impl<SS, IS> DecisionActivity<SS, IS> {
+    /// Creates an activity making the decisions with the `DecisionMaker`, and recording their completions in the
+    /// `IdempotencyStore`.
+    pub fn new(decision_maker: DecisionMaker<SS>, idempotency_store: IS) -> Self {
+        Self {
+            decision_maker,
+            idempotency_store,
+        }
+    }
+
+    /// Makes the decision of the activity identified by the token, unless it already completed.
+    ///
+    /// The errors of the `IdempotencyStore` are returned as `StateStore` errors.
+    pub async fn run<D, S, ID, E>(
+        &self,
+        token: &IdempotencyToken,
+        decision: D,
+    ) -> Result<Vec<ID>, DecisionError<D::Error>>
+    where
+        IS: IdempotencyStore<ID>,
+        ID: EventId,
+        E: Event + Clone + Sync + Send + 'static,
+        SS: LoadState<ID, S, E> + PersistDecision<ID, S, E>,
+        D: Decision<StateQuery = S, Event = E>,
+        S: Send + Sync + Serialize + DeserializeOwned + IntoStatePart<ID, S>,
+        <S as IntoStatePart<ID, S>>::Target:
+            Send + Sync + Serialize + DeserializeOwned + IntoState<S> + MultiState<ID, E>,
+        <D as Decision>::Error: 'static,
+    {
+        if let Some(event_ids) = self
+            .idempotency_store
+            .completed(token)
+            .await
+            .map_err(DecisionError::StateStore)?
+        {
+            return Ok(event_ids);
+        }
+        let event_ids: Vec<ID> = self
+            .decision_maker
+            .make(decision)
+            .await?
+            .iter()
+            .map(PersistedEvent::id)
+            .collect();
+        self.idempotency_store
+            .complete(token, &event_ids)
+            .await
+            .map_err(DecisionError::StateStore)?;
+        Ok(event_ids)
+    }
+}
+
+/// A signal sent to a workflow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowSignal {
+    workflow_id: String,
+    name: String,
+    payload: Value,
+    idempotency_token: Option<IdempotencyToken>,
+}
+
+impl WorkflowSignal {
+    /// Creates the signal `name` of the workflow `workflow_id`, without payload.
+    pub fn new(workflow_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            workflow_id: workflow_id.into(),
+            name: name.into(),
+            payload: Value::Null,
+            idempotency_token: None,
+        }
+    }
+
+    /// Sets the payload of the signal.
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Returns the ID of the signaled workflow.
+    pub fn workflow_id(&self) -> &str {
+        &self.workflow_id
+    }
+
+    /// Returns the name of the signal.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the payload of the signal.
+    pub fn payload(&self) -> &Value {
+        &self.payload
+    }
+
+    /// Returns the token identifying the signal, set by the `SignalListener` raising it.
+    pub fn idempotency_token(&self) -> Option<&IdempotencyToken> {
+        self.idempotency_token.as_ref()
+    }
+}
+
+/// Sends the signals to the workflow engine.
+#[async_trait]
+pub trait WorkflowSignaler: Send + Sync {
+    /// The error of the engine.
+    type Error: Send + Sync;
+
+    /// Sends the signal, which the engine discards if it already received a signal with the same token.
+    async fn signal(&self, signal: WorkflowSignal) -> Result<(), Self::Error>;
+}
+
+/// An event listener raising the signals of the workflows from the events.
+///
+/// The signal of an event is chosen by a function, which returns `None` for the events that do not signal any
+/// workflow. The token of the signal is made of the ID of the listener and the ID of the event.
+pub struct SignalListener<ID, E, W, F>
+where
+    ID: EventId,
+    E: Event + Clone,
+{
+    id: &'static str,
+    query: StreamQuery<ID, E>,
+    signaler: W,
+    signal: F,
+}
+
+impl<ID, E, W, F> SignalListener<ID, E, W, F>
+where
+    ID: EventId + Display,
+    E: Event + Clone + Send + Sync,
+    W: WorkflowSignaler,
+    F: Fn(&PersistedEvent<ID, E>) -> Option<WorkflowSignal> + Send + Sync,
+{
+    /// Creates the listener `id` raising the signals of the events matching the query.
+    pub fn new(id: &'static str, query: StreamQuery<ID, E>, signaler: W, signal: F) -> Self {
+        Self {
+            id,
+            query,
+            signaler,
+            signal,
+        }
+    }
+}
+
+#[async_trait]
+impl<ID, E, W, F> EventListener<ID, E> for SignalListener<ID, E, W, F>
+where
+    ID: EventId + Display,
+    E: Event + Clone + Send + Sync,
+    W: WorkflowSignaler,
+    F: Fn(&PersistedEvent<ID, E>) -> Option<WorkflowSignal> + Send + Sync,
+{
+    type Error = W::Error;
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn query(&self) -> &StreamQuery<ID, E> {
+        &self.query
+    }
+
+    async fn handle(&self, event: PersistedEvent<ID, E>) -> Result<(), Self::Error> {
+        let Some(mut signal) = (self.signal)(&event) else {
+            return Ok(());
+        };
+        signal.idempotency_token =
+            Some(IdempotencyToken::new(format!("{}-{}", self.id, event.id())));
+        self.signaler.signal(signal).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::{query, utils::tests::*, EventSourcedStateStore, NoSnapshot};
+
+    #[tokio::test]
+    async fn it_does_not_make_a_completed_activity_again() {
+        let mut database = MockDatabase::new();
+        database
+            .expect_stream()
+            .once()
+            .return_once(|_| event_stream([item_added_event("p1", "c1")]));
+        database.expect_append().once().return_once(
+            |_, _: StreamQuery<i64, ShoppingCartEvent>, _| {
+                vec![PersistedEvent::new(2, item_added_event("p2", "c1"))]
+            },
+        );
+
+        let mut mock_add_item = MockDecision::new();
+        mock_add_item
+            .expect_state_query()
+            .once()
+            .return_once(|| cart("c1", []));
+        mock_add_item
+            .expect_validation_query()
+            .once()
+            .return_once(|| Option::<StreamQuery<i64, ShoppingCartEvent>>::None);
+        mock_add_item
+            .expect_process()
+            .once()
+            .return_once(|_| Ok(vec![item_added_event("p2", "c1")]));
+
+        let event_store = MockEventStore::new(database);
+        let state_store = EventSourcedStateStore::new(event_store, NoSnapshot);
+        let activity = DecisionActivity::new(
+            DecisionMaker::new(state_store),
+            InMemoryIdempotencyStore::new(),
+        );
+        let token = IdempotencyToken::new("checkout-c1-add-item");
+
+        assert_eq!(activity.run(&token, mock_add_item).await.unwrap(), vec![2]);
+        assert_eq!(
+            activity.run(&token, MockDecision::new()).await.unwrap(),
+            vec![2]
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingSignaler {
+        signals: Mutex<Vec<WorkflowSignal>>,
+    }
+
+    #[async_trait]
+    impl WorkflowSignaler for RecordingSignaler {
+        type Error = Infallible;
+
+        async fn signal(&self, signal: WorkflowSignal) -> Result<(), Self::Error> {
+            self.signals.lock().unwrap().push(signal);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn it_raises_the_signals_of_the_events() {
+        let listener = SignalListener::new(
+            "checkout",
+            query!(ShoppingCartEvent),
+            RecordingSignaler::default(),
+            |event: &PersistedEvent<i64, ShoppingCartEvent>| match &**event {
+                ShoppingCartEvent::ItemAdded { cart_id, .. } => Some(WorkflowSignal::new(
+                    format!("checkout-{cart_id}"),
+                    "item_added",
+                )),
+                ShoppingCartEvent::ItemRemoved { .. } => None,
+            },
+        );
+
+        listener
+            .handle(PersistedEvent::new(7, item_added_event("p1", "c1")))
+            .await
+            .unwrap();
+        listener
+            .handle(PersistedEvent::new(8, item_removed_event("p1", "c1")))
+            .await
+            .unwrap();
+
+        let signals = listener.signaler.signals.lock().unwrap();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].workflow_id(), "checkout-c1");
+        assert_eq!(signals[0].name(), "item_added");
+        assert_eq!(
+            signals[0].idempotency_token(),
+            Some(&IdempotencyToken::new("checkout-7"))
+        );
+    }
+}
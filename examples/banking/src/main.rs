@@ -121,6 +121,7 @@ impl error::ResponseError for Error {
     fn status_code(&self) -> StatusCode {
         match self.source {
             disintegrate::DecisionError::Domain(_) => StatusCode::BAD_REQUEST,
+            disintegrate::DecisionError::Unauthorized { .. } => StatusCode::FORBIDDEN,
             disintegrate::DecisionError::EventStore(_) => StatusCode::INTERNAL_SERVER_ERROR,
             disintegrate::DecisionError::StateStore(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }